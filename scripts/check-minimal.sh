@@ -0,0 +1,23 @@
+#!/bin/bash
+# Check that the srt crate builds without default features and that the
+# minimal build leaves out bonding and the CLI dependencies
+# Usage: ./scripts/check-minimal.sh [cargo flags, e.g. --offline]
+
+set -e
+
+EXCLUDED=(srt-bonding srt-cli clap anyhow tracing-subscriber crossbeam)
+
+echo "==> build"
+cargo build -p srt --no-default-features "$@"
+cargo clippy -p srt --no-default-features "$@" -- -D warnings
+
+echo "==> dependencies"
+TREE=$(cargo tree -p srt --no-default-features -e normal --prefix none "$@")
+for crate in "${EXCLUDED[@]}"; do
+  if echo "$TREE" | grep -q "^$crate v"; then
+    echo "$crate is in the minimal dependency tree:"
+    cargo tree -p srt --no-default-features -e normal -i "$crate" "$@"
+    exit 1
+  fi
+done
+echo "Minimal build excludes: ${EXCLUDED[*]}"
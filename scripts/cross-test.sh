@@ -0,0 +1,27 @@
+#!/bin/bash
+# Run the serialization tests on 32-bit and big-endian targets under QEMU
+# Requires cross (cargo install cross) and Docker or Podman.
+# Usage: ./scripts/cross-test.sh [target...]
+
+set -e
+
+TARGETS=("$@")
+if [ ${#TARGETS[@]} -eq 0 ]; then
+  TARGETS=(
+    i686-unknown-linux-gnu          # 32-bit little-endian
+    armv7-unknown-linux-gnueabihf   # 32-bit little-endian
+    powerpc-unknown-linux-gnu       # 32-bit big-endian
+    s390x-unknown-linux-gnu         # 64-bit big-endian
+  )
+fi
+
+if ! command -v cross &> /dev/null; then
+  echo "cross not found; install with: cargo install cross"
+  exit 1
+fi
+
+for target in "${TARGETS[@]}"; do
+  echo "==> $target"
+  cross test --target "$target" -p srt-protocol --lib
+  cross test --target "$target" -p srt-tests --test wire_format_test
+done
@@ -0,0 +1,14 @@
+#!/bin/bash
+# Run the unsafe code and the buffer index math under MIRI to catch
+# undefined behaviour early. The subset skips tests that open sockets,
+# which MIRI cannot run.
+# Usage: ./scripts/miri.sh [cargo flags]
+# Needs a nightly toolchain with the miri component.
+
+set -e
+
+echo "==> srt-io sys"
+cargo +nightly miri test -p srt-io --lib "$@" -- sys::
+
+echo "==> srt-protocol buffers"
+cargo +nightly miri test -p srt-protocol --lib "$@" -- buffer:: sequence:: loss::
@@ -0,0 +1,95 @@
+use bytes::{Bytes, BytesMut};
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use srt_bonding::{AlignmentBuffer, AlignmentIngest};
+use srt_protocol::packet::{DataPacket, MsgNumber};
+use srt_protocol::sequence::SeqNumber;
+use std::thread;
+use std::time::Duration;
+
+/// Packets per iteration: about 0.1 s of a 1 Gbps stream
+const PACKETS: u32 = 10_000;
+const PAYLOAD: usize = 1316;
+const PATHS: u32 = 2;
+
+fn packets() -> Vec<DataPacket> {
+    let payload = Bytes::from(vec![0u8; PAYLOAD]);
+    (0..PACKETS)
+        .map(|i| DataPacket::new(SeqNumber::new(i), MsgNumber::new(i), 0, 0, payload.clone()))
+        .collect()
+}
+
+fn datagrams() -> Vec<BytesMut> {
+    packets().iter().map(|p| p.to_bytes()).collect()
+}
+
+/// One thread receiving every packet from each path, as the receivers do today
+fn bench_alignment_single_thread(c: &mut Criterion) {
+    let mut group = c.benchmark_group("alignment");
+    group.throughput(Throughput::Bytes(PACKETS as u64 * PAYLOAD as u64));
+
+    group.bench_function("single_thread_broadcast", |b| {
+        b.iter_batched(
+            packets,
+            |packets| {
+                let mut buffer = AlignmentBuffer::new(PACKETS as usize, Duration::from_secs(10));
+                let mut delivered = 0;
+                for packet in packets {
+                    for path in 0..PATHS {
+                        let _ = buffer.add_packet(packet.clone(), path, 0);
+                    }
+                    delivered += buffer.pop_ready_packets().len();
+                }
+                black_box(delivered)
+            },
+            BatchSize::LargeInput,
+        );
+    });
+
+    group.finish();
+}
+
+/// Per-path threads parsing datagrams and feeding one ordering consumer
+fn bench_alignment_ingest(c: &mut Criterion) {
+    let mut group = c.benchmark_group("alignment");
+    group.throughput(Throughput::Bytes(PACKETS as u64 * PAYLOAD as u64));
+
+    group.bench_function("ingest_broadcast", |b| {
+        b.iter_batched(
+            datagrams,
+            |datagrams| {
+                let buffer = AlignmentBuffer::new(PACKETS as usize, Duration::from_secs(10));
+                let mut ingest = AlignmentIngest::new(buffer, (PACKETS * PATHS) as usize);
+                let mut delivered = 0;
+                thread::scope(|s| {
+                    for path in 0..PATHS {
+                        let handle = ingest.handle();
+                        let datagrams = &datagrams;
+                        s.spawn(move || {
+                            for datagram in datagrams {
+                                let packet = DataPacket::from_bytes(datagram).unwrap();
+                                while !handle.push(packet.clone(), path, 0) {
+                                    thread::yield_now();
+                                }
+                            }
+                        });
+                    }
+                    while delivered < PACKETS as usize {
+                        ingest.process_timeout(Duration::from_millis(1));
+                        delivered += ingest.buffer_mut().pop_ready_packets().len();
+                    }
+                });
+                black_box(delivered)
+            },
+            BatchSize::LargeInput,
+        );
+    });
+
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_alignment_single_thread,
+    bench_alignment_ingest
+);
+criterion_main!(benches);
@@ -0,0 +1,118 @@
+use bytes::Bytes;
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use parking_lot::RwLock;
+use srt_bonding::BroadcastReceiver;
+use srt_protocol::packet::{DataPacket, MsgNumber};
+use srt_protocol::sequence::SeqNumber;
+use std::collections::{HashMap, VecDeque};
+use std::time::Instant;
+
+/// Packets per iteration: one second of a 10k pps stream
+const PACKETS: u32 = 10_000;
+const PAYLOAD: usize = 1316;
+const BUFFER: usize = 8192;
+
+/// Two paths: the second runs three packets behind, and every tenth pair
+/// of packets arrives swapped on the first
+fn arrivals() -> Vec<(DataPacket, u32)> {
+    let payload = Bytes::from(vec![0u8; PAYLOAD]);
+    let packet =
+        |i: u32| DataPacket::new(SeqNumber::new(i), MsgNumber::new(i), 0, 0, payload.clone());
+    let mut order: Vec<u32> = (0..PACKETS).collect();
+    for pair in order.chunks_mut(20) {
+        pair.swap(0, 1);
+    }
+
+    let mut arrivals = Vec::with_capacity(PACKETS as usize * 2);
+    for (i, &seq) in order.iter().enumerate() {
+        arrivals.push((packet(seq), 1));
+        if i >= 3 {
+            arrivals.push((packet(order[i - 3]), 2));
+        }
+    }
+    arrivals
+}
+
+/// The receiver as it was: pending packets in a HashMap keyed by sequence,
+/// with the same locking as `BroadcastReceiver`
+struct HashMapReceiver {
+    received: RwLock<HashMap<SeqNumber, (DataPacket, u32, Instant)>>,
+    next_expected: RwLock<SeqNumber>,
+    ready_queue: RwLock<VecDeque<DataPacket>>,
+    max_buffer_size: usize,
+}
+
+impl HashMapReceiver {
+    fn new(max_buffer_size: usize) -> Self {
+        HashMapReceiver {
+            received: RwLock::new(HashMap::new()),
+            next_expected: RwLock::new(SeqNumber::new(0)),
+            ready_queue: RwLock::new(VecDeque::new()),
+            max_buffer_size,
+        }
+    }
+
+    fn on_packet_received(&self, packet: DataPacket, member_id: u32) -> bool {
+        let seq = packet.seq_number();
+        if seq.distance_to(*self.next_expected.read()) > 0 {
+            return false;
+        }
+        let mut received = self.received.write();
+        if received.contains_key(&seq) || received.len() >= self.max_buffer_size {
+            return false;
+        }
+        received.insert(seq, (packet, member_id, Instant::now()));
+
+        let mut next_expected = self.next_expected.write();
+        let mut ready_queue = self.ready_queue.write();
+        while let Some((packet, _, _)) = received.remove(&*next_expected) {
+            ready_queue.push_back(packet);
+            *next_expected = next_expected.next();
+        }
+        true
+    }
+
+    fn pop_ready_packet(&self) -> Option<DataPacket> {
+        self.ready_queue.write().pop_front()
+    }
+}
+
+fn bench_broadcast_receiver(c: &mut Criterion) {
+    let mut group = c.benchmark_group("broadcast_receiver");
+    group.throughput(Throughput::Bytes(PACKETS as u64 * PAYLOAD as u64));
+
+    group.bench_function("hashmap", |b| {
+        b.iter_batched(
+            || (arrivals(), HashMapReceiver::new(BUFFER)),
+            |(arrivals, receiver)| {
+                let mut delivered = 0;
+                for (packet, member) in arrivals {
+                    receiver.on_packet_received(packet, member);
+                    delivered += std::iter::from_fn(|| receiver.pop_ready_packet()).count();
+                }
+                black_box(delivered)
+            },
+            BatchSize::LargeInput,
+        );
+    });
+
+    group.bench_function("ring", |b| {
+        b.iter_batched(
+            || (arrivals(), BroadcastReceiver::new(BUFFER)),
+            |(arrivals, receiver)| {
+                let mut delivered = 0;
+                for (packet, member) in arrivals {
+                    let _ = receiver.on_packet_received(packet, member);
+                    delivered += std::iter::from_fn(|| receiver.pop_ready_packet()).count();
+                }
+                black_box(delivered)
+            },
+            BatchSize::LargeInput,
+        );
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_broadcast_receiver);
+criterion_main!(benches);
@@ -0,0 +1,265 @@
+//! Adaptive Group Mode Switching
+//!
+//! Switches a balancing group to broadcast when link loss rises, trading
+//! bandwidth for redundancy, and back to balancing once the links have
+//! stayed clean for a while.
+//!
+//! Separate enter/exit thresholds and a cooldown after every switch keep
+//! the group from flapping between modes.
+
+use crate::balancing::LoadBalancer;
+use crate::group::{GroupError, GroupType, SocketGroup};
+use parking_lot::RwLock;
+use srt_protocol::Labels;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Adaptive mode thresholds
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdaptiveConfig {
+    /// Loss rate at or above which the group switches to broadcast (0.0 to 1.0)
+    pub broadcast_loss: f64,
+    /// Loss rate at or below which links count as clean (0.0 to 1.0)
+    pub balancing_loss: f64,
+    /// How long links must stay clean before switching back to balancing
+    pub clean_period: Duration,
+    /// Minimum time between two switches
+    pub cooldown: Duration,
+}
+
+impl Default for AdaptiveConfig {
+    fn default() -> Self {
+        AdaptiveConfig {
+            broadcast_loss: 0.05,
+            balancing_loss: 0.01,
+            clean_period: Duration::from_secs(10),
+            cooldown: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Mode switch event
+#[derive(Debug, Clone)]
+pub struct ModeSwitchEvent {
+    /// Time of the switch
+    pub timestamp: Instant,
+    /// Previous mode
+    pub from: GroupType,
+    /// New mode
+    pub to: GroupType,
+    /// Loss rate that triggered the switch
+    pub loss_rate: f64,
+    /// Reason for the switch
+    pub reason: ModeSwitchReason,
+    /// Labels of the group
+    pub labels: Labels,
+}
+
+/// Reason for a mode switch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModeSwitchReason {
+    /// Loss reached the broadcast threshold
+    LossAboveThreshold,
+    /// Links stayed clean for the clean period
+    LinksClean,
+}
+
+/// Adaptive mode controller
+///
+/// Feed it the current link loss rate periodically (e.g. from the
+/// balancer's path estimates). The loss must be measured per link, not
+/// after bonding, or broadcast would hide it and never switch back.
+pub struct AdaptiveModeController {
+    /// Socket group whose mode is switched
+    group: Arc<SocketGroup>,
+    /// Switching thresholds
+    config: AdaptiveConfig,
+    /// Time of the last switch
+    last_switch: Arc<RwLock<Option<Instant>>>,
+    /// Start of the current run of clean measurements
+    clean_since: Arc<RwLock<Option<Instant>>>,
+    /// Switch history
+    history: Arc<RwLock<Vec<ModeSwitchEvent>>>,
+}
+
+impl AdaptiveModeController {
+    /// Create a controller for a group configured for balancing
+    pub fn new(group: Arc<SocketGroup>, config: AdaptiveConfig) -> Result<Self, GroupError> {
+        if group.group_type() != GroupType::Balancing {
+            return Err(GroupError::InvalidState);
+        }
+
+        Ok(AdaptiveModeController {
+            group,
+            config,
+            last_switch: Arc::new(RwLock::new(None)),
+            clean_since: Arc::new(RwLock::new(None)),
+            history: Arc::new(RwLock::new(Vec::new())),
+        })
+    }
+
+    /// Get the current mode
+    pub fn mode(&self) -> GroupType {
+        self.group.group_type()
+    }
+
+    /// Get the switching thresholds
+    pub fn config(&self) -> &AdaptiveConfig {
+        &self.config
+    }
+
+    /// Record the current loss rate, switching modes if needed
+    pub fn update(&self, loss_rate: f64) -> Option<ModeSwitchEvent> {
+        self.update_at(loss_rate, Instant::now())
+    }
+
+    /// Record the balancer's mean path loss, switching modes if needed
+    pub fn update_from_balancer(&self, balancer: &LoadBalancer) -> Option<ModeSwitchEvent> {
+        self.update(balancer.stats().mean_loss_rate())
+    }
+
+    fn update_at(&self, loss_rate: f64, now: Instant) -> Option<ModeSwitchEvent> {
+        // Track how long the links have been clean, including during cooldown
+        let clean_for = {
+            let mut clean_since = self.clean_since.write();
+            if loss_rate <= self.config.balancing_loss {
+                now.saturating_duration_since(*clean_since.get_or_insert(now))
+            } else {
+                *clean_since = None;
+                Duration::ZERO
+            }
+        };
+
+        let in_cooldown = self
+            .last_switch
+            .read()
+            .is_some_and(|t| now.saturating_duration_since(t) < self.config.cooldown);
+        if in_cooldown {
+            return None;
+        }
+
+        let (to, reason) = match self.mode() {
+            GroupType::Balancing if loss_rate >= self.config.broadcast_loss => {
+                (GroupType::Broadcast, ModeSwitchReason::LossAboveThreshold)
+            }
+            GroupType::Broadcast if clean_for >= self.config.clean_period => {
+                (GroupType::Balancing, ModeSwitchReason::LinksClean)
+            }
+            _ => return None,
+        };
+
+        let event = ModeSwitchEvent {
+            timestamp: now,
+            from: self.mode(),
+            to,
+            loss_rate,
+            reason,
+            labels: self.group.labels(),
+        };
+
+        self.group.set_group_type(to);
+        *self.last_switch.write() = Some(now);
+        self.history.write().push(event.clone());
+
+        let _span = self.group.span().entered();
+        tracing::info!(
+            "Group mode: {:?} -> {:?} (loss {:.2}%, reason: {:?})",
+            event.from,
+            event.to,
+            loss_rate * 100.0,
+            reason
+        );
+
+        Some(event)
+    }
+
+    /// Get the switch history
+    pub fn history(&self) -> Vec<ModeSwitchEvent> {
+        self.history.read().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn controller(clean_period: Duration) -> AdaptiveModeController {
+        let group = Arc::new(SocketGroup::new(1, GroupType::Balancing, 4));
+        let config = AdaptiveConfig {
+            broadcast_loss: 0.05,
+            balancing_loss: 0.01,
+            clean_period,
+            cooldown: Duration::from_secs(5),
+        };
+        AdaptiveModeController::new(group, config).unwrap()
+    }
+
+    #[test]
+    fn test_requires_balancing_group() {
+        let group = Arc::new(SocketGroup::new(1, GroupType::Broadcast, 4));
+        assert!(AdaptiveModeController::new(group, AdaptiveConfig::default()).is_err());
+    }
+
+    #[test]
+    fn test_switches_to_broadcast_on_loss() {
+        let controller = controller(Duration::from_secs(10));
+        let start = Instant::now();
+
+        assert!(controller.update_at(0.02, start).is_none());
+        assert_eq!(controller.mode(), GroupType::Balancing);
+
+        let event = controller.update_at(0.08, start).unwrap();
+        assert_eq!(event.from, GroupType::Balancing);
+        assert_eq!(event.to, GroupType::Broadcast);
+        assert_eq!(event.reason, ModeSwitchReason::LossAboveThreshold);
+        assert_eq!(controller.mode(), GroupType::Broadcast);
+        assert_eq!(
+            controller.group.get_stats().group_type,
+            GroupType::Broadcast
+        );
+    }
+
+    #[test]
+    fn test_returns_to_balancing_when_clean() {
+        let controller = controller(Duration::from_secs(10));
+        let start = Instant::now();
+        controller.update_at(0.10, start).unwrap();
+
+        // Clean, but within the clean period
+        assert!(controller
+            .update_at(0.0, start + Duration::from_secs(6))
+            .is_none());
+        // A loss spike restarts the clean period
+        assert!(controller
+            .update_at(0.03, start + Duration::from_secs(10))
+            .is_none());
+        assert!(controller
+            .update_at(0.0, start + Duration::from_secs(11))
+            .is_none());
+
+        let event = controller
+            .update_at(0.005, start + Duration::from_secs(21))
+            .unwrap();
+        assert_eq!(event.to, GroupType::Balancing);
+        assert_eq!(event.reason, ModeSwitchReason::LinksClean);
+        assert_eq!(controller.history().len(), 2);
+    }
+
+    #[test]
+    fn test_cooldown_prevents_flapping() {
+        let controller = controller(Duration::ZERO);
+        let start = Instant::now();
+
+        controller.update_at(0.10, start).unwrap();
+        assert!(controller
+            .update_at(0.0, start + Duration::from_secs(1))
+            .is_none());
+        assert!(controller
+            .update_at(0.0, start + Duration::from_secs(5))
+            .is_some());
+        assert!(controller
+            .update_at(0.10, start + Duration::from_secs(6))
+            .is_none());
+        assert_eq!(controller.mode(), GroupType::Balancing);
+    }
+}
@@ -0,0 +1,313 @@
+//! Encoder Bitrate Advisory
+//!
+//! Turns the balancer's per-path capacity estimates into a single "safe
+//! sending bitrate" that an application can feed back to its encoder for
+//! ABR decisions.
+//!
+//! The usable aggregate is the sum of the path capacities (or the slowest
+//! path while the group broadcasts), less retransmissions and packet
+//! headers. It is smoothed like an RTT estimate, and its mean deviation
+//! gives a confidence interval. The recommendation sits at the low end of
+//! that interval, less a headroom margin, so it starts cautious and rises
+//! as the estimates settle.
+
+use crate::balancing::{LoadBalancer, PathCapacity};
+use crate::group::{GroupError, GroupType, SocketGroup};
+use parking_lot::RwLock;
+use srt_protocol::packet::{DEFAULT_MSS, MAX_PAYLOAD_SIZE};
+use srt_protocol::Labels;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Advisory tuning
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdvisoryConfig {
+    /// Fraction of the low estimate kept in reserve (0.0 to 1.0)
+    pub headroom: f64,
+    /// Relative change in the recommendation that produces a new event
+    pub min_change: f64,
+}
+
+impl Default for AdvisoryConfig {
+    fn default() -> Self {
+        AdvisoryConfig {
+            headroom: 0.1,
+            min_change: 0.05,
+        }
+    }
+}
+
+/// Recommended encoder bitrate
+///
+/// Rates are in bits per second, as encoders expect.
+#[derive(Debug, Clone)]
+pub struct BitrateAdvisory {
+    /// Time of the estimate
+    pub timestamp: Instant,
+    /// Group mode the estimate was made for
+    pub mode: GroupType,
+    /// Paths contributing capacity
+    pub path_count: usize,
+    /// Sum of the path capacity estimates
+    pub capacity_bps: u64,
+    /// Capacity lost to retransmissions, redundant copies and headers
+    pub overhead_bps: u64,
+    /// Low end of the confidence interval for the usable payload rate
+    pub low_bps: u64,
+    /// High end of the confidence interval for the usable payload rate
+    pub high_bps: u64,
+    /// Recommended encoder bitrate
+    pub bitrate_bps: u64,
+    /// Labels of the group
+    pub labels: Labels,
+}
+
+/// Smoothed usable rate and its mean deviation (bits per second)
+#[derive(Debug, Clone, Copy)]
+struct Estimate {
+    mean: f64,
+    deviation: f64,
+}
+
+/// Encoder bitrate advisor for a balancing group
+///
+/// Call `update_from_balancer` periodically (e.g. once per stats interval).
+/// It returns an advisory whenever the recommendation moves by more than
+/// `min_change`; `latest` always holds the most recent one.
+pub struct BitrateAdvisor {
+    /// Socket group the advice is for
+    group: Arc<SocketGroup>,
+    /// Advisory tuning
+    config: AdvisoryConfig,
+    /// Smoothed usable rate
+    estimate: Arc<RwLock<Option<Estimate>>>,
+    /// Most recent advisory
+    latest: Arc<RwLock<Option<BitrateAdvisory>>>,
+    /// Recommendation in the last advisory returned to the caller
+    last_reported: Arc<RwLock<Option<u64>>>,
+}
+
+/// Fraction of each full-size packet that is payload
+fn payload_fraction() -> f64 {
+    MAX_PAYLOAD_SIZE as f64 / DEFAULT_MSS as f64
+}
+
+impl BitrateAdvisor {
+    /// Create an advisor for a group configured for balancing
+    pub fn new(group: Arc<SocketGroup>, config: AdvisoryConfig) -> Result<Self, GroupError> {
+        if group.group_type() != GroupType::Balancing {
+            return Err(GroupError::InvalidState);
+        }
+
+        Ok(BitrateAdvisor {
+            group,
+            config,
+            estimate: Arc::new(RwLock::new(None)),
+            latest: Arc::new(RwLock::new(None)),
+            last_reported: Arc::new(RwLock::new(None)),
+        })
+    }
+
+    /// Get the advisory tuning
+    pub fn config(&self) -> &AdvisoryConfig {
+        &self.config
+    }
+
+    /// Record the current path estimates, returning an advisory if the
+    /// recommendation changed
+    pub fn update(&self, paths: &[PathCapacity]) -> Option<BitrateAdvisory> {
+        self.update_at(paths, Instant::now())
+    }
+
+    /// Record the balancer's path estimates, returning an advisory if the
+    /// recommendation changed
+    pub fn update_from_balancer(&self, balancer: &LoadBalancer) -> Option<BitrateAdvisory> {
+        self.update(&balancer.stats().path_capacities)
+    }
+
+    /// Get the most recent advisory
+    pub fn latest(&self) -> Option<BitrateAdvisory> {
+        self.latest.read().clone()
+    }
+
+    fn update_at(&self, paths: &[PathCapacity], now: Instant) -> Option<BitrateAdvisory> {
+        let mode = self.group.group_type();
+
+        // Broken paths contribute nothing
+        let paths: Vec<_> = paths.iter().filter(|p| p.loss_rate < 1.0).collect();
+        let capacity: f64 = paths.iter().map(|p| p.bandwidth_bps as f64 * 8.0).sum();
+        let goodput = paths
+            .iter()
+            .map(|p| p.bandwidth_bps as f64 * 8.0 * (1.0 - p.loss_rate));
+
+        // Balancing splits the stream across paths; broadcast sends every
+        // packet on each path, so the slowest one limits the stream
+        let usable = match mode {
+            GroupType::Broadcast => goodput.fold(f64::INFINITY, f64::min),
+            _ => goodput.sum(),
+        };
+        let usable = if paths.is_empty() {
+            0.0
+        } else {
+            usable * payload_fraction()
+        };
+
+        let estimate = {
+            let mut estimate = self.estimate.write();
+            let next = match *estimate {
+                // Start a quarter wide, so the first advice is cautious
+                None => Estimate {
+                    mean: usable,
+                    deviation: usable / 4.0,
+                },
+                Some(prev) => Estimate {
+                    mean: prev.mean * 7.0 / 8.0 + usable / 8.0,
+                    deviation: prev.deviation * 3.0 / 4.0 + (prev.mean - usable).abs() / 4.0,
+                },
+            };
+            *estimate = Some(next);
+            next
+        };
+
+        let low = (estimate.mean - 2.0 * estimate.deviation).max(0.0);
+        let high = estimate.mean + 2.0 * estimate.deviation;
+        let advisory = BitrateAdvisory {
+            timestamp: now,
+            mode,
+            path_count: paths.len(),
+            capacity_bps: capacity as u64,
+            overhead_bps: (capacity - usable).max(0.0) as u64,
+            low_bps: low as u64,
+            high_bps: high as u64,
+            bitrate_bps: (low * (1.0 - self.config.headroom)) as u64,
+            labels: self.group.labels(),
+        };
+        *self.latest.write() = Some(advisory.clone());
+
+        let mut last_reported = self.last_reported.write();
+        let changed = match *last_reported {
+            None => true,
+            Some(last) => {
+                advisory.bitrate_bps.abs_diff(last) as f64 > last as f64 * self.config.min_change
+            }
+        };
+        if !changed {
+            return None;
+        }
+        *last_reported = Some(advisory.bitrate_bps);
+
+        let _span = self.group.span().entered();
+        tracing::debug!(
+            "Bitrate advisory: {} kbps ({}-{} kbps usable over {} paths)",
+            advisory.bitrate_bps / 1000,
+            advisory.low_bps / 1000,
+            advisory.high_bps / 1000,
+            advisory.path_count
+        );
+
+        Some(advisory)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn advisor() -> BitrateAdvisor {
+        let group = Arc::new(SocketGroup::new(1, GroupType::Balancing, 4));
+        BitrateAdvisor::new(group, AdvisoryConfig::default()).unwrap()
+    }
+
+    fn path(path_id: u32, bandwidth_bps: u64, loss_rate: f64) -> PathCapacity {
+        let mut capacity = PathCapacity::new(path_id);
+        capacity.bandwidth_bps = bandwidth_bps;
+        capacity.loss_rate = loss_rate;
+        capacity
+    }
+
+    #[test]
+    fn test_requires_balancing_group() {
+        let group = Arc::new(SocketGroup::new(1, GroupType::Broadcast, 4));
+        assert!(BitrateAdvisor::new(group, AdvisoryConfig::default()).is_err());
+    }
+
+    #[test]
+    fn test_converges_on_usable_capacity() {
+        let advisor = advisor();
+        let now = Instant::now();
+        // 1 + 0.5 MB/s, the second path losing 10%
+        let paths = [path(1, 1_000_000, 0.0), path(2, 500_000, 0.1)];
+        let usable = (8_000_000.0 + 3_600_000.0) * payload_fraction();
+
+        let first = advisor.update_at(&paths, now).unwrap();
+        assert_eq!(first.path_count, 2);
+        assert_eq!(first.capacity_bps, 12_000_000);
+        assert_eq!(first.overhead_bps, (12_000_000.0 - usable) as u64);
+        assert!(first.low_bps < usable as u64 && first.high_bps > usable as u64);
+        assert_eq!(first.bitrate_bps, (usable / 2.0 * 0.9) as u64);
+
+        // Steady estimates narrow the interval and raise the advice
+        let mut events = 1;
+        for _ in 0..60 {
+            if let Some(advisory) = advisor.update_at(&paths, now) {
+                assert!(advisory.bitrate_bps > first.bitrate_bps);
+                events += 1;
+            }
+        }
+        assert!(events > 1 && events < 20, "{} events", events);
+
+        let latest = advisor.latest().unwrap();
+        let target = usable * 0.9;
+        assert!((latest.bitrate_bps as f64 - target).abs() < target * 0.01);
+        assert!(latest.high_bps - latest.low_bps < usable as u64 / 100);
+    }
+
+    #[test]
+    fn test_path_loss_lowers_advice() {
+        let advisor = advisor();
+        let now = Instant::now();
+        let paths = [path(1, 1_000_000, 0.0), path(2, 1_000_000, 0.0)];
+        for _ in 0..60 {
+            advisor.update_at(&paths, now);
+        }
+        let before = advisor.latest().unwrap().bitrate_bps;
+
+        // Second path breaks: the spread widens and the advice drops at once,
+        // ahead of the smoothed mean
+        let paths = [path(1, 1_000_000, 0.0), path(2, 1_000_000, 1.0)];
+        let advisory = advisor.update_at(&paths, now).unwrap();
+        assert_eq!(advisory.path_count, 1);
+        assert!(advisory.bitrate_bps < before * 3 / 4, "{:?}", advisory);
+
+        for _ in 0..60 {
+            advisor.update_at(&paths, now);
+        }
+        let after = advisor.latest().unwrap().bitrate_bps;
+        assert!(after.abs_diff(before / 2) < before / 100);
+    }
+
+    #[test]
+    fn test_broadcast_limited_by_slowest_path() {
+        let advisor = advisor();
+        let now = Instant::now();
+        advisor.group.set_group_type(GroupType::Broadcast);
+
+        let paths = [path(1, 1_000_000, 0.0), path(2, 250_000, 0.0)];
+        let advisory = advisor.update_at(&paths, now).unwrap();
+        let usable = 2_000_000.0 * payload_fraction();
+        assert_eq!(advisory.mode, GroupType::Broadcast);
+        assert_eq!(advisory.capacity_bps, 10_000_000);
+        // The redundant copies count as overhead
+        assert_eq!(advisory.overhead_bps, (10_000_000.0 - usable) as u64);
+        assert_eq!(advisory.low_bps, (usable / 2.0) as u64);
+    }
+
+    #[test]
+    fn test_no_paths() {
+        let advisor = advisor();
+        let advisory = advisor.update_at(&[], Instant::now()).unwrap();
+        assert_eq!(advisory.bitrate_bps, 0);
+        assert_eq!(advisory.capacity_bps, 0);
+        assert!(advisor.update_at(&[], Instant::now()).is_none());
+    }
+}
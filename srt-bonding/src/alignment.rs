@@ -1,10 +1,14 @@
 //! Packet Alignment for Multi-Path Reception
 //!
 //! Aligns sequence numbers across multiple paths, detects and eliminates
-//! duplicates, and reorders packets for in-order delivery.
+//! duplicates, and reorders packets for in-order delivery. When copies of a
+//! sequence carry different sender timestamps (e.g. a retransmission and
+//! the original), the earliest sent is kept by default.
 
-use srt_protocol::{DataPacket, SeqNumber};
+use crate::tuning::{ConfigChange, ConfigChangeHook};
+use srt_protocol::{DataPacket, DroppedRanges, LossRange, SeqNumber};
 use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 use thiserror::Error;
 
@@ -30,6 +34,56 @@ pub struct PacketSource {
     pub received_at: Instant,
     /// RTT estimate for this path (microseconds)
     pub rtt_us: u32,
+    /// Sender timestamp of this copy (microseconds)
+    pub timestamp_us: u32,
+}
+
+/// Which copy to keep when paths deliver the same sequence
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConflictPolicy {
+    /// Keep the copy that arrived first
+    KeepFirst,
+    /// Keep the copy with the earliest sender timestamp, so an original
+    /// wins over a retransmission of it
+    #[default]
+    EarliestTimestamp,
+}
+
+/// Alignment buffer tunables, changeable at runtime
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AlignmentConfig {
+    /// Maximum number of buffered packets
+    pub max_buffer_size: usize,
+    /// How long a packet may wait for the gap before it to fill
+    pub max_packet_age: Duration,
+    /// Which copy of a duplicated sequence to keep
+    pub conflict_policy: ConflictPolicy,
+}
+
+/// Redundant copies delivered by one path
+#[derive(Debug, Clone, Default)]
+pub struct RedundancyStats {
+    /// Path identifier (member ID)
+    pub path_id: u32,
+    /// Copies from this path that lost to another path's copy
+    pub redundant_packets: u64,
+    /// Average of how much later the redundant copies were sent than the
+    /// kept ones (microseconds, 0 for copies sent no later)
+    pub avg_lag_us: u32,
+    /// Largest such lag (microseconds)
+    pub max_lag_us: u32,
+}
+
+impl RedundancyStats {
+    fn record(&mut self, lag_us: u32) {
+        self.redundant_packets += 1;
+        if self.redundant_packets == 1 {
+            self.avg_lag_us = lag_us;
+        } else {
+            self.avg_lag_us = ((self.avg_lag_us as u64 * 7 + lag_us as u64) / 8) as u32;
+        }
+        self.max_lag_us = self.max_lag_us.max(lag_us);
+    }
 }
 
 /// Aligned packet (with source tracking)
@@ -43,6 +97,15 @@ pub struct AlignedPacket {
     pub duplicate_sources: Vec<PacketSource>,
 }
 
+/// Data flushed from an alignment buffer on teardown
+#[derive(Debug, Clone, Default)]
+pub struct AlignmentDrain {
+    /// Remaining packets, in sequence order
+    pub packets: Vec<AlignedPacket>,
+    /// Sequence ranges that never arrived and cannot be recovered
+    pub gaps: Vec<LossRange>,
+}
+
 /// Packet alignment buffer
 ///
 /// Receives packets from multiple paths, detects duplicates,
@@ -52,10 +115,14 @@ pub struct AlignmentBuffer {
     buffer: BTreeMap<SeqNumber, AlignedPacket>,
     /// Next expected sequence number for delivery
     next_expected: SeqNumber,
-    /// Maximum buffer size
-    max_buffer_size: usize,
-    /// Maximum age for buffered packets
-    max_packet_age: Duration,
+    /// Buffer size, packet age and conflict policy
+    config: AlignmentConfig,
+    /// Hook run after the config changes
+    config_hook: Option<ConfigChangeHook<AlignmentConfig>>,
+    /// Ranges a sender dropped, skipped instead of waited for
+    dropped: DroppedRanges,
+    /// Redundant copies per path
+    redundancy: HashMap<u32, RedundancyStats>,
     /// Statistics
     stats: AlignmentStats,
 }
@@ -66,12 +133,78 @@ impl AlignmentBuffer {
         AlignmentBuffer {
             buffer: BTreeMap::new(),
             next_expected: SeqNumber::new(0),
-            max_buffer_size,
-            max_packet_age,
+            config: AlignmentConfig {
+                max_buffer_size,
+                max_packet_age,
+                conflict_policy: ConflictPolicy::default(),
+            },
+            config_hook: None,
+            dropped: DroppedRanges::new(),
+            redundancy: HashMap::new(),
             stats: AlignmentStats::default(),
         }
     }
 
+    /// Set which copy of a duplicated sequence to keep
+    pub fn set_conflict_policy(&mut self, policy: ConflictPolicy) {
+        self.commit_config(AlignmentConfig {
+            conflict_policy: policy,
+            ..self.config
+        });
+    }
+
+    /// Get which copy of a duplicated sequence is kept
+    pub fn conflict_policy(&self) -> ConflictPolicy {
+        self.config.conflict_policy
+    }
+
+    /// Get the current tunables
+    pub fn config(&self) -> AlignmentConfig {
+        self.config
+    }
+
+    /// Replace the tunables, keeping the packets held
+    ///
+    /// Fails with `AlignmentError::BufferFull`, leaving the config
+    /// unchanged, if more packets than the new `max_buffer_size` are
+    /// buffered. Returns the change, or `None` if nothing differed.
+    pub fn reconfigure(
+        &mut self,
+        config: AlignmentConfig,
+    ) -> Result<Option<ConfigChange<AlignmentConfig>>, AlignmentError> {
+        if self.buffer.len() > config.max_buffer_size {
+            return Err(AlignmentError::BufferFull);
+        }
+        Ok(self.commit_config(config))
+    }
+
+    /// Set the hook run after each config change
+    pub fn set_config_change_hook<F>(&mut self, hook: F)
+    where
+        F: Fn(&ConfigChange<AlignmentConfig>) + Send + Sync + 'static,
+    {
+        self.config_hook = Some(Arc::new(hook));
+    }
+
+    /// Remove the config change hook
+    pub fn clear_config_change_hook(&mut self) {
+        self.config_hook = None;
+    }
+
+    fn commit_config(&mut self, config: AlignmentConfig) -> Option<ConfigChange<AlignmentConfig>> {
+        let change = ConfigChange::between(self.config, config)?;
+        self.config = config;
+        tracing::debug!(
+            "Alignment config changed: {:?} -> {:?}",
+            change.old,
+            change.new
+        );
+        if let Some(hook) = &self.config_hook {
+            hook(&change);
+        }
+        Some(change)
+    }
+
     /// Add a packet from a specific path
     ///
     /// Returns true if this is a new packet (not a duplicate).
@@ -82,6 +215,9 @@ impl AlignmentBuffer {
         rtt_us: u32,
     ) -> Result<bool, AlignmentError> {
         let seq = packet.seq_number();
+        if packet.msg_number().retransmitted {
+            self.stats.packets_retransmitted += 1;
+        }
 
         // Check if packet is too old
         if seq.lt(self.next_expected) {
@@ -89,47 +225,68 @@ impl AlignmentBuffer {
             return Err(AlignmentError::TooOld);
         }
 
+        let source = PacketSource {
+            member_id,
+            received_at: Instant::now(),
+            rtt_us,
+            timestamp_us: packet.header.timestamp,
+        };
+
+        // Check if we already have this packet; a duplicate takes no space
+        if let Some(existing) = self.buffer.get_mut(&seq) {
+            self.stats.duplicates_detected += 1;
+            // Positive if the new copy was sent after the kept one
+            let lag = source
+                .timestamp_us
+                .wrapping_sub(existing.source.timestamp_us) as i32;
+            let (redundant, redundant_lag) =
+                if self.config.conflict_policy == ConflictPolicy::EarliestTimestamp && lag < 0 {
+                    // Sent earlier: its payload replaces the kept one
+                    existing.packet = packet;
+                    self.stats.conflicts_resolved += 1;
+                    (std::mem::replace(&mut existing.source, source), -lag)
+                } else {
+                    (source, lag)
+                };
+            self.redundancy
+                .entry(redundant.member_id)
+                .or_insert_with(|| RedundancyStats {
+                    path_id: redundant.member_id,
+                    ..Default::default()
+                })
+                .record(redundant_lag.max(0) as u32);
+            existing.duplicate_sources.push(redundant);
+            return Ok(false);
+        }
+
         // Check buffer size
-        if self.buffer.len() >= self.max_buffer_size {
+        if self.buffer.len() >= self.config.max_buffer_size {
             // Try to clean up old packets first
             self.cleanup_old_packets();
 
-            if self.buffer.len() >= self.max_buffer_size {
+            if self.buffer.len() >= self.config.max_buffer_size {
                 self.stats.buffer_full_events += 1;
                 return Err(AlignmentError::BufferFull);
             }
         }
 
-        let source = PacketSource {
-            member_id,
-            received_at: Instant::now(),
-            rtt_us,
+        // New packet
+        let aligned = AlignedPacket {
+            packet,
+            source,
+            duplicate_sources: Vec::new(),
         };
 
-        // Check if we already have this packet
-        if let Some(existing) = self.buffer.get_mut(&seq) {
-            // Duplicate packet
-            existing.duplicate_sources.push(source);
-            self.stats.duplicates_detected += 1;
-            Ok(false)
-        } else {
-            // New packet
-            let aligned = AlignedPacket {
-                packet,
-                source,
-                duplicate_sources: Vec::new(),
-            };
-
-            self.buffer.insert(seq, aligned);
-            self.stats.packets_received += 1;
-            Ok(true)
-        }
+        self.buffer.insert(seq, aligned);
+        self.stats.packets_received += 1;
+        Ok(true)
     }
 
     /// Get next packet in sequence order
     ///
     /// Returns None if the next packet is not yet available.
     pub fn pop_next(&mut self) -> Option<AlignedPacket> {
+        self.skip_dropped();
         if let Some(aligned) = self.buffer.remove(&self.next_expected) {
             self.next_expected = self.next_expected.next();
             self.stats.packets_delivered += 1;
@@ -143,19 +300,54 @@ impl AlignmentBuffer {
     pub fn pop_ready_packets(&mut self) -> Vec<AlignedPacket> {
         let mut ready = Vec::new();
 
+        self.skip_dropped();
         while let Some(aligned) = self.buffer.remove(&self.next_expected) {
             self.next_expected = self.next_expected.next();
             self.stats.packets_delivered += 1;
             ready.push(aligned);
+            self.skip_dropped();
         }
 
         ready
     }
 
+    /// Stop waiting for a range a member's sender dropped (DropReq)
+    ///
+    /// Missing packets in the range are skipped once delivery reaches
+    /// them; packets from it that another path delivers are kept. Returns
+    /// false if the range was already passed.
+    pub fn on_drop_request(&mut self, range: LossRange) -> bool {
+        if !self.dropped.add(range, self.next_expected) {
+            return false;
+        }
+        self.stats.dropped_ranges += 1;
+        true
+    }
+
+    /// Move `next_expected` past missing packets in dropped ranges
+    fn skip_dropped(&mut self) {
+        let buffer = &self.buffer;
+        let skipped = self
+            .dropped
+            .skip(&mut self.next_expected, |seq| buffer.contains_key(&seq));
+        self.stats.dropped_packets += skipped as u64;
+    }
+
+    /// Flush all buffered packets in sequence order, skipping over gaps
+    ///
+    /// For use on group teardown, when missing packets will no longer
+    /// arrive.
+    pub fn drain(&mut self) -> AlignmentDrain {
+        let buffered = std::mem::take(&mut self.buffer).into_iter().collect();
+        let (packets, gaps) = drain_in_order(&mut self.next_expected, buffered);
+        self.stats.packets_delivered += packets.len() as u64;
+        AlignmentDrain { packets, gaps }
+    }
+
     /// Clean up packets that are too old
     fn cleanup_old_packets(&mut self) {
         let now = Instant::now();
-        let max_age = self.max_packet_age;
+        let max_age = self.config.max_packet_age;
 
         self.buffer.retain(|_, aligned| {
             let age = now.duration_since(aligned.source.received_at);
@@ -180,7 +372,7 @@ impl AlignmentBuffer {
         // Find gaps up to the highest received packet
         if let Some((&highest, _)) = self.buffer.iter().next_back() {
             while current.lt(highest) {
-                if !self.buffer.contains_key(&current) {
+                if !self.buffer.contains_key(&current) && !self.dropped.contains(current) {
                     missing.push(current);
                 }
                 current = current.next();
@@ -195,9 +387,19 @@ impl AlignmentBuffer {
         &self.stats
     }
 
+    /// Get redundant copy statistics for a path
+    pub fn redundancy_stats(&self, path_id: u32) -> Option<&RedundancyStats> {
+        self.redundancy.get(&path_id)
+    }
+
+    /// Get redundant copy statistics for all paths
+    pub fn all_redundancy_stats(&self) -> Vec<&RedundancyStats> {
+        self.redundancy.values().collect()
+    }
+
     /// Get current buffer utilization
     pub fn utilization(&self) -> f32 {
-        self.buffer.len() as f32 / self.max_buffer_size as f32
+        self.buffer.len() as f32 / self.config.max_buffer_size as f32
     }
 
     /// Get buffered packet count
@@ -205,6 +407,31 @@ impl AlignmentBuffer {
         self.buffer.len()
     }
 
+    /// Get the maximum number of buffered packets
+    pub fn capacity(&self) -> usize {
+        self.config.max_buffer_size
+    }
+
+    /// Change the maximum number of buffered packets, keeping those held
+    ///
+    /// Fails with `AlignmentError::BufferFull`, leaving the buffer
+    /// unchanged, if more packets than `max_buffer_size` are buffered.
+    pub fn resize(&mut self, max_buffer_size: usize) -> Result<(), AlignmentError> {
+        self.reconfigure(AlignmentConfig {
+            max_buffer_size,
+            ..self.config
+        })
+        .map(|_| ())
+    }
+
+    /// Change how long a packet may wait for the gap before it to fill
+    pub fn set_max_packet_age(&mut self, max_packet_age: Duration) {
+        self.commit_config(AlignmentConfig {
+            max_packet_age,
+            ..self.config
+        });
+    }
+
     /// Get next expected sequence number
     pub fn next_expected(&self) -> SeqNumber {
         self.next_expected
@@ -214,6 +441,41 @@ impl AlignmentBuffer {
     pub fn set_next_expected(&mut self, seq: SeqNumber) {
         self.next_expected = seq;
     }
+
+    /// Resynchronize to a sequence announced by the sender
+    ///
+    /// Buffered packets before `seq` are discarded.
+    pub fn fast_forward(&mut self, seq: SeqNumber) {
+        let before = self.buffer.len();
+        self.buffer.retain(|s, _| s.distance_to(seq) <= 0);
+        self.stats.packets_expired += (before - self.buffer.len()) as u64;
+        self.stats.resyncs += 1;
+        self.next_expected = seq;
+    }
+}
+
+/// Order buffered packets from `next_expected`, recording the gaps
+///
+/// Advances `next_expected` past the last packet.
+pub(crate) fn drain_in_order<T>(
+    next_expected: &mut SeqNumber,
+    mut buffered: Vec<(SeqNumber, T)>,
+) -> (Vec<T>, Vec<LossRange>) {
+    let start = *next_expected;
+    buffered.retain(|(seq, _)| start.distance_to(*seq) >= 0);
+    buffered.sort_by_key(|(seq, _)| start.distance_to(*seq));
+
+    let mut packets = Vec::with_capacity(buffered.len());
+    let mut gaps = Vec::new();
+    for (seq, packet) in buffered {
+        if seq != *next_expected {
+            gaps.push(LossRange::new(*next_expected, seq - 1));
+        }
+        packets.push(packet);
+        *next_expected = seq.next();
+    }
+
+    (packets, gaps)
 }
 
 /// Alignment statistics
@@ -225,12 +487,22 @@ pub struct AlignmentStats {
     pub packets_delivered: u64,
     /// Duplicate packets detected
     pub duplicates_detected: u64,
+    /// Packets received with the retransmitted flag, duplicates included
+    pub packets_retransmitted: u64,
     /// Packets that were too old
     pub packets_too_old: u64,
     /// Packets that expired before delivery
     pub packets_expired: u64,
     /// Buffer full events
     pub buffer_full_events: u64,
+    /// Resynchronizations to a sender-announced sequence
+    pub resyncs: u64,
+    /// Dropped ranges reported by senders (DropReq)
+    pub dropped_ranges: u64,
+    /// Missing packets skipped because their sender dropped them
+    pub dropped_packets: u64,
+    /// Duplicates whose earlier-sent copy replaced the one held
+    pub conflicts_resolved: u64,
 }
 
 impl AlignmentStats {
@@ -264,14 +536,32 @@ pub struct PathStats {
     pub packets_first: u64,
     /// Average RTT (microseconds)
     pub avg_rtt_us: u32,
+    /// Arrival time minus sender timestamp (microseconds), filtered
+    ///
+    /// Includes the unknown offset between the sender's and our clocks, so
+    /// only differences between paths are meaningful.
+    pub offset_us: Option<i64>,
 }
 
+/// Weight of a sample above the current offset estimate (1/N)
+///
+/// Queueing only ever adds delay, so lower samples are taken as-is and
+/// higher ones are followed slowly, to track route changes.
+const OFFSET_RISE_WEIGHT: i64 = 16;
+
 /// Multi-path alignment tracker
 ///
 /// Tracks which paths are delivering packets and their performance.
+/// One-way delays are compared using the sender timestamps carried by
+/// every path, which share one sender clock, so asymmetric paths are
+/// ranked by the direction that matters rather than by RTT.
 pub struct PathTracker {
     /// Statistics per path
     paths: HashMap<u32, PathStats>,
+    /// Local reference for arrival times
+    origin: Instant,
+    /// Last sender timestamp seen and its unwrapped value
+    last_timestamp: Option<(u32, i64)>,
 }
 
 impl PathTracker {
@@ -279,17 +569,24 @@ impl PathTracker {
     pub fn new() -> Self {
         PathTracker {
             paths: HashMap::new(),
+            origin: Instant::now(),
+            last_timestamp: None,
         }
     }
 
-    /// Record packet reception from a path
-    pub fn record_packet(&mut self, path_id: u32, was_first: bool, rtt_us: u32) {
-        let stats = self.paths.entry(path_id).or_insert_with(|| PathStats {
+    fn path_mut(&mut self, path_id: u32) -> &mut PathStats {
+        self.paths.entry(path_id).or_insert_with(|| PathStats {
             path_id,
             packets_received: 0,
             packets_first: 0,
             avg_rtt_us: 0,
-        });
+            offset_us: None,
+        })
+    }
+
+    /// Record packet reception from a path
+    pub fn record_packet(&mut self, path_id: u32, was_first: bool, rtt_us: u32) {
+        let stats = self.path_mut(path_id);
 
         stats.packets_received += 1;
         if was_first {
@@ -304,6 +601,51 @@ impl PathTracker {
         }
     }
 
+    /// Record a packet's sender timestamp and local arrival time on a path
+    ///
+    /// Feeds the one-way delay estimate for the path. Leave retransmitted
+    /// packets out: they carry their original timestamp and would read as
+    /// a path slowing down.
+    pub fn record_arrival(&mut self, path_id: u32, timestamp_us: u32, arrival: Instant) {
+        let sent_us = self.unwrap_timestamp(timestamp_us);
+        let arrival_us = arrival.saturating_duration_since(self.origin).as_micros() as i64;
+        let sample = arrival_us - sent_us;
+
+        let stats = self.path_mut(path_id);
+        stats.offset_us = Some(match stats.offset_us {
+            Some(offset) if sample > offset => offset + (sample - offset) / OFFSET_RISE_WEIGHT,
+            _ => sample,
+        });
+    }
+
+    /// Extend a 32-bit sender timestamp past its wraparound
+    fn unwrap_timestamp(&mut self, timestamp_us: u32) -> i64 {
+        let unwrapped = match self.last_timestamp {
+            Some((last, base)) => base + timestamp_us.wrapping_sub(last) as i32 as i64,
+            None => timestamp_us as i64,
+        };
+        self.last_timestamp = Some((timestamp_us, unwrapped));
+        unwrapped
+    }
+
+    /// Get a path's one-way delay relative to the fastest path
+    ///
+    /// Returns None until the path has delivered a timestamped packet.
+    pub fn one_way_delay_us(&self, path_id: u32) -> Option<u64> {
+        let offset = self.paths.get(&path_id)?.offset_us?;
+        let fastest = self.paths.values().filter_map(|s| s.offset_us).min()?;
+        Some((offset - fastest) as u64)
+    }
+
+    /// Get how long to hold a path's packets to line up with the slowest path
+    ///
+    /// Returns None until the path has delivered a timestamped packet.
+    pub fn equalization_delay(&self, path_id: u32) -> Option<Duration> {
+        let offset = self.paths.get(&path_id)?.offset_us?;
+        let slowest = self.paths.values().filter_map(|s| s.offset_us).max()?;
+        Some(Duration::from_micros((slowest - offset) as u64))
+    }
+
     /// Get statistics for a path
     pub fn get_stats(&self, path_id: u32) -> Option<&PathStats> {
         self.paths.get(&path_id)
@@ -314,8 +656,18 @@ impl PathTracker {
         self.paths.values().collect()
     }
 
-    /// Get fastest path (by average RTT)
+    /// Get fastest path
+    ///
+    /// Ranked by one-way delay once every path has one, otherwise by
+    /// average RTT.
     pub fn fastest_path(&self) -> Option<u32> {
+        if self.paths.values().all(|s| s.offset_us.is_some()) {
+            return self
+                .paths
+                .values()
+                .min_by_key(|s| s.offset_us)
+                .map(|s| s.path_id);
+        }
         self.paths
             .values()
             .min_by_key(|s| s.avg_rtt_us)
@@ -405,6 +757,83 @@ mod tests {
         assert_eq!(buffer.stats().duplicates_detected, 1);
     }
 
+    fn stamped_packet(seq: u32, timestamp_us: u32, payload: &'static str) -> DataPacket {
+        DataPacket::new(
+            SeqNumber::new(seq),
+            MsgNumber::new(seq),
+            timestamp_us,
+            0,
+            bytes::Bytes::from(payload),
+        )
+    }
+
+    #[test]
+    fn test_conflict_earliest_timestamp() {
+        let mut buffer = AlignmentBuffer::new(1024, Duration::from_secs(10));
+        assert_eq!(buffer.conflict_policy(), ConflictPolicy::EarliestTimestamp);
+
+        // A retransmission on path 1 arrives before the original on path 2
+        buffer
+            .add_packet(stamped_packet(1, 5_000, "rexmit"), 1, 0)
+            .unwrap();
+        assert!(!buffer
+            .add_packet(stamped_packet(1, 2_000, "original"), 2, 0)
+            .unwrap());
+        // A later copy on path 3 is redundant
+        assert!(!buffer
+            .add_packet(stamped_packet(1, 2_500, "late"), 3, 0)
+            .unwrap());
+
+        buffer
+            .add_packet(stamped_packet(0, 1_000, "zero"), 2, 0)
+            .unwrap();
+        let ready = buffer.pop_ready_packets();
+        let kept = &ready[1];
+        assert_eq!(kept.packet.payload, "original");
+        assert_eq!(kept.source.member_id, 2);
+        let others: Vec<u32> = kept.duplicate_sources.iter().map(|s| s.member_id).collect();
+        assert_eq!(others, vec![1, 3]);
+
+        assert_eq!(buffer.stats().conflicts_resolved, 1);
+        assert_eq!(buffer.stats().duplicates_detected, 2);
+        let path1 = buffer.redundancy_stats(1).unwrap();
+        assert_eq!(path1.redundant_packets, 1);
+        assert_eq!(path1.max_lag_us, 3_000);
+        assert_eq!(buffer.redundancy_stats(3).unwrap().avg_lag_us, 500);
+        assert!(buffer.redundancy_stats(2).is_none());
+        assert_eq!(buffer.all_redundancy_stats().len(), 2);
+    }
+
+    #[test]
+    fn test_conflict_keep_first() {
+        let mut buffer = AlignmentBuffer::new(1024, Duration::from_secs(10));
+        buffer.set_conflict_policy(ConflictPolicy::KeepFirst);
+
+        buffer
+            .add_packet(stamped_packet(0, 5_000, "first"), 1, 0)
+            .unwrap();
+        buffer
+            .add_packet(stamped_packet(0, 2_000, "earlier"), 2, 0)
+            .unwrap();
+        // Timestamps wrap: u32::MAX is just before 0
+        buffer
+            .add_packet(stamped_packet(1, 10, "after wrap"), 1, 0)
+            .unwrap();
+
+        let kept = buffer.pop_next().unwrap();
+        assert_eq!(kept.packet.payload, "first");
+        assert_eq!(buffer.stats().conflicts_resolved, 0);
+        // Sent earlier than the kept copy, so no lag
+        assert_eq!(buffer.redundancy_stats(2).unwrap().max_lag_us, 0);
+
+        buffer.set_conflict_policy(ConflictPolicy::EarliestTimestamp);
+        buffer
+            .add_packet(stamped_packet(1, u32::MAX - 9, "before wrap"), 2, 0)
+            .unwrap();
+        assert_eq!(buffer.pop_next().unwrap().packet.payload, "before wrap");
+        assert_eq!(buffer.redundancy_stats(1).unwrap().max_lag_us, 20);
+    }
+
     #[test]
     fn test_missing_sequences() {
         let mut buffer = AlignmentBuffer::new(1024, Duration::from_secs(10));
@@ -440,6 +869,58 @@ mod tests {
         assert_eq!(tracker.fastest_path(), Some(1));
     }
 
+    #[test]
+    fn test_one_way_delay() {
+        let mut tracker = PathTracker::new();
+        let base = tracker.origin + Duration::from_secs(1);
+
+        // Path 1: 10ms forward on a 50ms RTT; path 2: 40ms forward on a
+        // 30ms RTT (fast return route)
+        for i in 0..10u32 {
+            let sent = Duration::from_millis(i as u64 * 20);
+            let ts = sent.as_micros() as u32;
+            tracker.record_packet(1, true, 50_000);
+            tracker.record_arrival(1, ts, base + sent + Duration::from_millis(10));
+            tracker.record_packet(2, false, 30_000);
+            tracker.record_arrival(2, ts, base + sent + Duration::from_millis(40));
+        }
+
+        assert_eq!(tracker.fastest_path(), Some(1));
+        assert_eq!(tracker.one_way_delay_us(1), Some(0));
+        assert_eq!(tracker.one_way_delay_us(2), Some(30_000));
+        assert_eq!(
+            tracker.equalization_delay(1),
+            Some(Duration::from_millis(30))
+        );
+        assert_eq!(tracker.equalization_delay(2), Some(Duration::ZERO));
+
+        // A queueing spike barely moves the estimate; a drop is taken at once
+        let offset = |t: &PathTracker, id| t.get_stats(id).unwrap().offset_us.unwrap();
+        let before = offset(&tracker, 1);
+        let ts = 200_000u32;
+        let sent = Duration::from_micros(ts as u64);
+        tracker.record_arrival(1, ts, base + sent + Duration::from_millis(90));
+        assert_eq!(offset(&tracker, 1), before + 5_000);
+        tracker.record_arrival(2, ts, base + sent + Duration::from_millis(5));
+        assert_eq!(tracker.fastest_path(), Some(2));
+        assert_eq!(tracker.one_way_delay_us(1), Some(10_000));
+    }
+
+    #[test]
+    fn test_one_way_delay_timestamp_wrap() {
+        let mut tracker = PathTracker::new();
+        let base = tracker.origin + Duration::from_secs(1);
+
+        tracker.record_arrival(1, u32::MAX - 999, base + Duration::from_millis(5));
+        tracker.record_arrival(2, u32::MAX - 999, base + Duration::from_millis(8));
+        // 2ms later the sender clock has wrapped
+        tracker.record_arrival(1, 1_000, base + Duration::from_millis(7));
+        tracker.record_arrival(2, 1_000, base + Duration::from_millis(10));
+
+        assert_eq!(tracker.one_way_delay_us(2), Some(3_000));
+        assert_eq!(tracker.fastest_path(), Some(1));
+    }
+
     #[test]
     fn test_buffer_full() {
         let mut buffer = AlignmentBuffer::new(2, Duration::from_secs(10));
@@ -451,6 +932,80 @@ mod tests {
         // Should fail - buffer full
         let result = buffer.add_packet(create_test_packet(2), 1, 50_000);
         assert!(matches!(result, Err(AlignmentError::BufferFull)));
+
+        // Duplicates of buffered packets are still recognized
+        assert!(!buffer.add_packet(create_test_packet(1), 2, 50_000).unwrap());
+    }
+
+    #[test]
+    fn test_resize() {
+        let mut buffer = AlignmentBuffer::new(2, Duration::from_secs(10));
+        buffer.add_packet(create_test_packet(1), 1, 50_000).unwrap();
+        buffer.add_packet(create_test_packet(2), 1, 50_000).unwrap();
+        assert!(buffer.add_packet(create_test_packet(3), 1, 50_000).is_err());
+
+        buffer.resize(8).unwrap();
+        assert_eq!(buffer.capacity(), 8);
+        buffer.add_packet(create_test_packet(3), 1, 50_000).unwrap();
+        assert!(matches!(buffer.resize(2), Err(AlignmentError::BufferFull)));
+        assert_eq!(buffer.capacity(), 8);
+
+        // The gap at 0 is still waited on, then everything comes out in order
+        assert!(buffer.pop_next().is_none());
+        buffer.add_packet(create_test_packet(0), 1, 50_000).unwrap();
+        let delivered: Vec<_> = buffer
+            .pop_ready_packets()
+            .iter()
+            .map(|p| p.packet.seq_number().as_raw())
+            .collect();
+        assert_eq!(delivered, vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_reconfigure() {
+        let mut buffer = AlignmentBuffer::new(4, Duration::from_secs(10));
+        let changes = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let seen = changes.clone();
+        buffer.set_config_change_hook(move |change| seen.lock().push(change.new));
+        for seq in 1..4 {
+            buffer
+                .add_packet(create_test_packet(seq), 1, 50_000)
+                .unwrap();
+        }
+
+        let tighter = AlignmentConfig {
+            max_buffer_size: 2,
+            max_packet_age: Duration::from_millis(500),
+            conflict_policy: ConflictPolicy::KeepFirst,
+        };
+        assert!(matches!(
+            buffer.reconfigure(tighter),
+            Err(AlignmentError::BufferFull)
+        ));
+        assert_eq!(buffer.config().max_packet_age, Duration::from_secs(10));
+
+        let applied = AlignmentConfig {
+            max_buffer_size: 3,
+            ..tighter
+        };
+        let change = buffer.reconfigure(applied).unwrap().unwrap();
+        assert_eq!(change.old.max_buffer_size, 4);
+        assert_eq!(buffer.capacity(), 3);
+        assert_eq!(buffer.conflict_policy(), ConflictPolicy::KeepFirst);
+        assert!(buffer.reconfigure(applied).unwrap().is_none());
+
+        // The single-field setters report through the hook too
+        buffer.set_max_packet_age(Duration::from_secs(1));
+        assert_eq!(
+            *changes.lock(),
+            vec![
+                applied,
+                AlignmentConfig {
+                    max_packet_age: Duration::from_secs(1),
+                    ..applied
+                }
+            ]
+        );
     }
 
     #[test]
@@ -465,4 +1020,89 @@ mod tests {
         assert_eq!(stats.duplicates_detected, 1);
         assert_eq!(stats.duplication_rate(), 1.0);
     }
+
+    #[test]
+    fn test_retransmitted_statistics() {
+        let mut buffer = AlignmentBuffer::new(1024, Duration::from_secs(10));
+
+        buffer.add_packet(create_test_packet(0), 1, 50_000).unwrap();
+        let mut resent = create_test_packet(1);
+        resent.header.msg_or_info = MsgNumber {
+            retransmitted: true,
+            ..MsgNumber::new(1)
+        }
+        .to_raw();
+        buffer.add_packet(resent, 1, 50_000).unwrap();
+
+        let stats = buffer.stats();
+        assert_eq!(stats.packets_received, 2);
+        assert_eq!(stats.packets_retransmitted, 1);
+        assert_eq!(stats.duplicates_detected, 0);
+    }
+
+    #[test]
+    fn test_fast_forward() {
+        let mut buffer = AlignmentBuffer::new(100, Duration::from_secs(1));
+        buffer.add_packet(create_test_packet(5), 1, 0).unwrap();
+        buffer.add_packet(create_test_packet(50), 1, 0).unwrap();
+        assert!(buffer.pop_next().is_none());
+
+        buffer.fast_forward(SeqNumber::new(50));
+        assert_eq!(buffer.buffered_count(), 1);
+        assert_eq!(buffer.stats().resyncs, 1);
+        assert_eq!(buffer.pop_next().unwrap().packet.seq_number().as_raw(), 50);
+        assert_eq!(buffer.next_expected(), SeqNumber::new(51));
+    }
+
+    #[test]
+    fn test_drop_request() {
+        let mut buffer = AlignmentBuffer::new(100, Duration::from_secs(1));
+        for seq in [0, 4, 6] {
+            buffer.add_packet(create_test_packet(seq), 1, 0).unwrap();
+        }
+        assert_eq!(buffer.pop_ready_packets().len(), 1);
+        assert_eq!(buffer.get_missing_sequences().len(), 4);
+
+        // Only 5 is still worth waiting for
+        let dropped = LossRange::new(SeqNumber::new(1), SeqNumber::new(3));
+        assert!(buffer.on_drop_request(dropped));
+        assert_eq!(buffer.get_missing_sequences(), vec![SeqNumber::new(5)]);
+        assert_eq!(buffer.pop_next().unwrap().packet.seq_number().as_raw(), 4);
+        assert!(buffer.pop_next().is_none());
+        assert!(!buffer.on_drop_request(dropped));
+
+        buffer.add_packet(create_test_packet(5), 2, 0).unwrap();
+        assert_eq!(buffer.pop_ready_packets().len(), 2);
+
+        let stats = buffer.stats();
+        assert_eq!(stats.dropped_ranges, 1);
+        assert_eq!(stats.dropped_packets, 3);
+    }
+
+    #[test]
+    fn test_drain() {
+        let mut buffer = AlignmentBuffer::new(100, Duration::from_secs(1));
+        for seq in [0, 1, 3, 6, 4] {
+            buffer.add_packet(create_test_packet(seq), 1, 0).unwrap();
+        }
+        assert_eq!(buffer.pop_ready_packets().len(), 2);
+
+        let drain = buffer.drain();
+        let seqs: Vec<u32> = drain
+            .packets
+            .iter()
+            .map(|p| p.packet.seq_number().as_raw())
+            .collect();
+        assert_eq!(seqs, vec![3, 4, 6]);
+        assert_eq!(
+            drain.gaps,
+            vec![
+                LossRange::single(SeqNumber::new(2)),
+                LossRange::single(SeqNumber::new(5)),
+            ]
+        );
+        assert_eq!(buffer.buffered_count(), 0);
+        assert_eq!(buffer.next_expected(), SeqNumber::new(7));
+        assert_eq!(buffer.stats().packets_delivered, 5);
+    }
 }
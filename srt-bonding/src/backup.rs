@@ -4,8 +4,9 @@
 //! Sends on primary, automatically switches to backup on failure.
 
 use crate::group::{GroupError, MemberStatus, SocketGroup};
+use crate::tuning::{ConfigChange, ConfigChangeHook};
 use parking_lot::RwLock;
-use srt_protocol::SeqNumber;
+use srt_protocol::{Labels, SeqNumber, MAX_MISSED_KEEPALIVES};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use thiserror::Error;
@@ -24,6 +25,29 @@ pub enum BackupError {
 
     #[error("All members failed")]
     AllMembersFailed,
+
+    #[error("Invalid configuration: {0}")]
+    InvalidConfig(String),
+}
+
+/// Backup mode tunables, changeable at runtime
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BackupConfig {
+    /// Least time between health checks of the primary
+    pub health_check_interval: Duration,
+    /// Primary failures that trigger a failover (at least 1)
+    pub failure_threshold: u32,
+}
+
+impl BackupConfig {
+    fn validate(&self) -> Result<(), BackupError> {
+        if self.failure_threshold == 0 {
+            return Err(BackupError::InvalidConfig(
+                "failure threshold must be at least 1".into(),
+            ));
+        }
+        Ok(())
+    }
 }
 
 /// Member role in backup mode
@@ -46,6 +70,8 @@ pub struct FailoverEvent {
     pub new_primary: u32,
     /// Reason for failover
     pub reason: FailoverReason,
+    /// Labels of the group
+    pub labels: Labels,
 }
 
 /// Reason for failover
@@ -69,12 +95,12 @@ pub struct BackupBonding {
     backup_ids: Arc<RwLock<Vec<u32>>>,
     /// Failover history
     failover_history: Arc<RwLock<Vec<FailoverEvent>>>,
-    /// Health check interval
-    health_check_interval: Duration,
+    /// Health check interval and failover threshold
+    config: RwLock<BackupConfig>,
+    /// Hook run after the config changes
+    config_hook: RwLock<Option<ConfigChangeHook<BackupConfig>>>,
     /// Last health check time
     last_health_check: Arc<RwLock<Instant>>,
-    /// Failure threshold for triggering failover
-    failure_threshold: u32,
 }
 
 impl BackupBonding {
@@ -89,12 +115,83 @@ impl BackupBonding {
             primary_id: Arc::new(RwLock::new(None)),
             backup_ids: Arc::new(RwLock::new(Vec::new())),
             failover_history: Arc::new(RwLock::new(Vec::new())),
-            health_check_interval,
+            config: RwLock::new(BackupConfig {
+                health_check_interval,
+                failure_threshold,
+            }),
+            config_hook: RwLock::new(None),
             last_health_check: Arc::new(RwLock::new(Instant::now())),
-            failure_threshold,
         }
     }
 
+    /// Get the current tunables
+    pub fn config(&self) -> BackupConfig {
+        *self.config.read()
+    }
+
+    /// Replace the tunables of a running group
+    ///
+    /// Fails with `InvalidConfig`, keeping the current config, if the new
+    /// one is invalid. Returns the change, or `None` if nothing differed.
+    pub fn reconfigure(
+        &self,
+        config: BackupConfig,
+    ) -> Result<Option<ConfigChange<BackupConfig>>, BackupError> {
+        self.apply_config(|current| *current = config)
+    }
+
+    /// Change the least time between health checks
+    pub fn set_health_check_interval(&self, interval: Duration) -> Result<(), BackupError> {
+        self.apply_config(|config| config.health_check_interval = interval)
+            .map(|_| ())
+    }
+
+    /// Change how many primary failures trigger a failover (at least 1)
+    pub fn set_failure_threshold(&self, threshold: u32) -> Result<(), BackupError> {
+        self.apply_config(|config| config.failure_threshold = threshold)
+            .map(|_| ())
+    }
+
+    /// Set the hook run after each config change
+    pub fn set_config_change_hook<F>(&self, hook: F)
+    where
+        F: Fn(&ConfigChange<BackupConfig>) + Send + Sync + 'static,
+    {
+        *self.config_hook.write() = Some(Arc::new(hook));
+    }
+
+    /// Remove the config change hook
+    pub fn clear_config_change_hook(&self) {
+        *self.config_hook.write() = None;
+    }
+
+    fn apply_config(
+        &self,
+        update: impl FnOnce(&mut BackupConfig),
+    ) -> Result<Option<ConfigChange<BackupConfig>>, BackupError> {
+        let mut config = self.config.write();
+        let mut new = *config;
+        update(&mut new);
+        new.validate()?;
+        let Some(change) = ConfigChange::between(*config, new) else {
+            return Ok(None);
+        };
+        *config = new;
+        drop(config);
+
+        let _span = self.group.span().entered();
+        tracing::info!(
+            "Backup config changed: {:?} -> {:?}",
+            change.old,
+            change.new
+        );
+        let hook = self.config_hook.read().clone();
+        if let Some(hook) = hook {
+            hook(&change);
+        }
+        Ok(Some(change))
+    }
+
     /// Set primary member
     pub fn set_primary(&self, member_id: u32) -> Result<(), BackupError> {
         // Verify member exists
@@ -152,6 +249,8 @@ impl BackupBonding {
             .get_member(primary_id)
             .ok_or(BackupError::NoPrimary)?;
 
+        self.group.check_payload_size(data.len())?;
+
         match member.connection.send(data) {
             Ok(_) => {
                 member.record_sent(data.len());
@@ -190,19 +289,21 @@ impl BackupBonding {
         self.group
             .update_member_status(failed_primary, MemberStatus::Broken)?;
 
-        // Find next available backup
+        // Find next available backup: the one the receiver reports as
+        // fastest, else the first in configured order
         let new_primary = {
             let backups = self.backup_ids.read();
             backups
                 .iter()
-                .find(|&&id| {
-                    if let Some(member) = self.group.get_member(id) {
-                        member.get_stats().status == MemberStatus::Idle
-                    } else {
-                        false
-                    }
+                .enumerate()
+                .filter_map(|(order, &id)| {
+                    let stats = self.group.get_member(id)?.get_stats();
+                    let available = stats.status == MemberStatus::Idle
+                        && stats.missed_keepalives < MAX_MISSED_KEEPALIVES;
+                    available.then_some((stats.peer_delay_us.unwrap_or(u32::MAX), order, id))
                 })
-                .copied()
+                .min()
+                .map(|(_, _, id)| id)
                 .ok_or(BackupError::NoBackup)?
             // Drop read lock here
         };
@@ -216,10 +317,12 @@ impl BackupBonding {
             old_primary: failed_primary,
             new_primary,
             reason,
+            labels: self.group.labels(),
         };
 
         self.failover_history.write().push(event.clone());
 
+        let _span = self.group.span().entered();
         tracing::info!(
             "Failover: {} -> {} (reason: {:?})",
             failed_primary,
@@ -233,9 +336,10 @@ impl BackupBonding {
     /// Perform health check on primary
     pub fn health_check(&self) -> Result<bool, BackupError> {
         let now = Instant::now();
+        let config = self.config();
         let mut last_check = self.last_health_check.write();
 
-        if now.duration_since(*last_check) < self.health_check_interval {
+        if now.duration_since(*last_check) < config.health_check_interval {
             return Ok(true); // Too soon for another check
         }
 
@@ -254,13 +358,14 @@ impl BackupBonding {
         let stats = member.get_stats();
 
         // Check for failures
-        if stats.failure_count >= self.failure_threshold {
+        if stats.failure_count >= config.failure_threshold {
             self.handle_primary_failure(primary_id, FailoverReason::QualityDegraded)?;
             return Ok(false);
         }
 
-        // Check if member is still connected
-        if stats.status != MemberStatus::Active {
+        // Check if member is still connected and answering keep-alives
+        if stats.status != MemberStatus::Active || stats.missed_keepalives >= MAX_MISSED_KEEPALIVES
+        {
             self.handle_primary_failure(primary_id, FailoverReason::PrimaryFailed)?;
             return Ok(false);
         }
@@ -298,6 +403,7 @@ impl BackupBonding {
             old_primary,
             new_primary: new_primary_id,
             reason: FailoverReason::Manual,
+            labels: self.group.labels(),
         };
 
         self.failover_history.write().push(event);
@@ -412,6 +518,7 @@ mod tests {
             .add_member(conn2, "127.0.0.1:9002".parse().unwrap())
             .unwrap();
 
+        group.set_labels("site=nyc".parse().unwrap());
         let backup = BackupBonding::new(group, Duration::from_secs(1), 3);
         backup.set_primary(1).unwrap();
         backup.add_backup(2).unwrap();
@@ -420,7 +527,84 @@ mod tests {
         backup.manual_failover(2).unwrap();
 
         assert_eq!(backup.get_primary_id(), Some(2));
-        assert_eq!(backup.failover_history().len(), 1);
+        let history = backup.failover_history();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].labels.get("site"), Some("nyc"));
+    }
+
+    #[test]
+    fn test_keepalive_failover() {
+        let group = create_test_group();
+        let conn1 = create_test_connection(1);
+        let conn2 = create_test_connection(2);
+        conn1.set_keepalive_interval(Duration::ZERO);
+
+        group
+            .add_member(conn1.clone(), "127.0.0.1:9001".parse().unwrap())
+            .unwrap();
+        group
+            .add_member(conn2, "127.0.0.1:9002".parse().unwrap())
+            .unwrap();
+
+        let backup = BackupBonding::new(group, Duration::ZERO, 3);
+        backup.set_primary(1).unwrap();
+        backup.add_backup(2).unwrap();
+        assert!(backup.health_check().unwrap());
+
+        // The primary stops answering keep-alives
+        for _ in 0..=MAX_MISSED_KEEPALIVES {
+            conn1.create_keepalive().unwrap();
+        }
+        assert!(!backup.health_check().unwrap());
+        assert_eq!(backup.get_primary_id(), Some(2));
+        assert_eq!(
+            backup.failover_history()[0].reason,
+            FailoverReason::PrimaryFailed
+        );
+    }
+
+    #[test]
+    fn test_failover_prefers_reported_fastest_backup() {
+        use crate::status::{PathStatus, StatusReport};
+
+        let group = create_test_group();
+        let conn1 = create_test_connection(1);
+        conn1.set_keepalive_interval(Duration::ZERO);
+        group
+            .add_member(conn1.clone(), "127.0.0.1:9001".parse().unwrap())
+            .unwrap();
+        for id in [2, 3] {
+            group
+                .add_member(
+                    create_test_connection(id),
+                    "127.0.0.1:9002".parse().unwrap(),
+                )
+                .unwrap();
+        }
+
+        let backup = BackupBonding::new(group.clone(), Duration::ZERO, 3);
+        backup.set_primary(1).unwrap();
+        backup.add_backup(2).unwrap();
+        backup.add_backup(3).unwrap();
+
+        // The receiver sees member 3 well ahead of member 2
+        let status = |socket_id, delay| PathStatus {
+            socket_id,
+            packets_received: 100,
+            packets_first: 0,
+            relative_delay_us: Some(delay),
+        };
+        let report = StatusReport {
+            buffered_packets: 0,
+            paths: vec![status(2, 40_000), status(3, 0)],
+        };
+        assert_eq!(group.apply_status_report(&report), 2);
+
+        for _ in 0..=MAX_MISSED_KEEPALIVES {
+            conn1.create_keepalive().unwrap();
+        }
+        assert!(!backup.health_check().unwrap());
+        assert_eq!(backup.get_primary_id(), Some(3));
     }
 
     #[test]
@@ -439,4 +623,39 @@ mod tests {
         assert_eq!(stats.primary_id, Some(1));
         assert_eq!(stats.failover_count, 0);
     }
+
+    #[test]
+    fn test_reconfigure() {
+        let backup = BackupBonding::new(create_test_group(), Duration::from_secs(1), 3);
+        let changes = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let seen = changes.clone();
+        backup.set_config_change_hook(move |change| seen.lock().push(change.clone()));
+
+        let initial = backup.config();
+        assert!(backup.reconfigure(initial).unwrap().is_none());
+        assert!(matches!(
+            backup.set_failure_threshold(0),
+            Err(BackupError::InvalidConfig(_))
+        ));
+        assert_eq!(backup.config(), initial);
+
+        backup.set_health_check_interval(Duration::ZERO).unwrap();
+        let change = backup
+            .reconfigure(BackupConfig {
+                health_check_interval: Duration::from_millis(100),
+                failure_threshold: 5,
+            })
+            .unwrap()
+            .unwrap();
+        assert_eq!(change.old.health_check_interval, Duration::ZERO);
+        assert_eq!(backup.config().failure_threshold, 5);
+
+        assert_eq!(changes.lock().len(), 2);
+        assert_eq!(changes.lock()[0].old, initial);
+        assert_eq!(changes.lock()[1], change);
+
+        backup.clear_config_change_hook();
+        backup.set_failure_threshold(1).unwrap();
+        assert_eq!(changes.lock().len(), 2);
+    }
 }
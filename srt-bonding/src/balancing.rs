@@ -2,11 +2,22 @@
 //!
 //! Distributes packets across multiple paths based on bandwidth,
 //! RTT, and path health to maximize throughput.
-
-use crate::group::{GroupError, MemberStatus, SocketGroup};
-use parking_lot::RwLock;
-use srt_protocol::SeqNumber;
-use std::collections::HashMap;
+//!
+//! With congestion control on, each path gets a `CongestionController` and
+//! a packet only goes to a path whose window has room. Data given to
+//! `enqueue` waits in a group-level queue while every window is closed and
+//! goes out as ACKs open them again.
+//!
+//! A path starts from the capacity and RTT its handshake probe measured,
+//! when the caller asked for one (see `srt_protocol::probe`), rather than
+//! from fixed guesses.
+
+use crate::group::{GroupError, GroupMember, MemberStatus, SocketGroup};
+use crate::partition::SequencePartition;
+use crate::tuning::{ConfigChange, ConfigChangeHook};
+use parking_lot::{Mutex, RwLock};
+use srt_protocol::{CongestionController, CongestionStats, Connection, ProbeResult, SeqNumber};
+use std::collections::{HashMap, VecDeque};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use thiserror::Error;
@@ -22,6 +33,12 @@ pub enum BalancingError {
 
     #[error("All paths failed")]
     AllPathsFailed,
+
+    #[error("No path's congestion window is open")]
+    WindowClosed,
+
+    #[error("Send queue full ({0} packets)")]
+    QueueFull(usize),
 }
 
 /// Path capacity estimate
@@ -33,25 +50,64 @@ pub struct PathCapacity {
     pub bandwidth_bps: u64,
     /// Average RTT (microseconds)
     pub rtt_us: u32,
+    /// One-way delay above the receiver's fastest path (microseconds), from
+    /// its status reports
+    pub peer_delay_us: Option<u32>,
     /// Packet loss rate (0.0 to 1.0)
     pub loss_rate: f64,
     /// Current load (packets in flight)
     pub packets_in_flight: u32,
+    /// Packets queued or sent on the path and not yet acknowledged
+    pub queue_depth: u64,
     /// Last capacity update
     pub last_update: Instant,
+    /// Whether the handshake probe's measurement was applied
+    probed: bool,
 }
 
 impl PathCapacity {
     /// Create new path capacity estimate
-    fn new(path_id: u32) -> Self {
+    pub(crate) fn new(path_id: u32) -> Self {
         PathCapacity {
             path_id,
             bandwidth_bps: 1_000_000, // Initial estimate: 1 MB/s
             rtt_us: 100_000,          // Initial estimate: 100ms
+            peer_delay_us: None,
             loss_rate: 0.0,
             packets_in_flight: 0,
+            queue_depth: 0,
             last_update: Instant::now(),
+            probed: false,
+        }
+    }
+
+    /// Replace the initial guesses with the path's handshake probe
+    ///
+    /// Applied once; values the probe could not measure keep their
+    /// current estimate.
+    pub(crate) fn apply_probe(&mut self, probe: &ProbeResult) {
+        if self.probed {
+            return;
+        }
+        self.probed = true;
+        if let Some(bandwidth_bps) = probe.bandwidth_bps.filter(|bw| *bw > 0) {
+            self.bandwidth_bps = bandwidth_bps;
         }
+        if let Some(rtt_us) = probe.rtt_us.filter(|rtt| *rtt > 0) {
+            self.rtt_us = rtt_us;
+        }
+        self.loss_rate = probe.loss_rate;
+    }
+
+    /// Predict how long a packet of `packet_size` bytes sent now takes to
+    /// reach the receiver: the one-way delay plus the time to drain the
+    /// packets queued ahead of it
+    ///
+    /// `one_way_us` is the path's one-way delay; see `select_path`.
+    fn predicted_arrival(&self, one_way_us: u32, packet_size: usize) -> Duration {
+        let queued_bytes = (self.queue_depth + 1) * packet_size as u64;
+        let drain_us = queued_bytes * 1_000_000 / self.bandwidth_bps.max(1);
+        Duration::from_micros(u64::from(one_way_us) + drain_us)
     }
 
     /// Calculate path weight for load balancing
@@ -76,14 +132,33 @@ impl PathCapacity {
     }
 }
 
+/// Load balancer tunables, changeable at runtime
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BalancerConfig {
+    /// Balancing algorithm
+    pub algorithm: BalancingAlgorithm,
+    /// Whether sends wait for the paths' congestion windows
+    pub congestion_control: bool,
+    /// Most packets `enqueue` holds while windows are closed (0 = unlimited)
+    pub max_pending: usize,
+}
+
 /// Load balancer for multi-path transmission
 pub struct LoadBalancer {
     /// Socket group
     group: Arc<SocketGroup>,
     /// Path capacity estimates
     capacities: Arc<RwLock<HashMap<u32, PathCapacity>>>,
-    /// Balancing algorithm
-    algorithm: BalancingAlgorithm,
+    /// Algorithm, congestion control and queue limit
+    config: RwLock<BalancerConfig>,
+    /// Hook run after the config changes
+    config_hook: RwLock<Option<ConfigChangeHook<BalancerConfig>>>,
+    /// Predicted arrival of the last packet sent (`ArrivalEqualized`)
+    last_arrival: RwLock<Option<Instant>>,
+    /// Per-path congestion controllers, created as paths are first used
+    controllers: RwLock<HashMap<u32, CongestionController>>,
+    /// Data waiting for a congestion window to open, oldest first
+    pending: Mutex<VecDeque<Vec<u8>>>,
     /// Maximum packets in flight per path
     _max_in_flight_per_path: u32,
     /// Capacity update interval
@@ -100,25 +175,200 @@ impl LoadBalancer {
         LoadBalancer {
             group,
             capacities: Arc::new(RwLock::new(HashMap::new())),
-            algorithm,
+            config: RwLock::new(BalancerConfig {
+                algorithm,
+                congestion_control: false,
+                max_pending: 0,
+            }),
+            config_hook: RwLock::new(None),
+            last_arrival: RwLock::new(None),
+            controllers: RwLock::new(HashMap::new()),
+            pending: Mutex::new(VecDeque::new()),
             _max_in_flight_per_path: max_in_flight_per_path,
             _capacity_update_interval: Duration::from_millis(100),
         }
     }
 
+    /// Gate sends on each path's congestion window
+    ///
+    /// Off by default. When on, `send` and `enqueue` only use paths whose
+    /// congestion controller allows another packet; `on_ack` and `on_loss`
+    /// drive the controllers.
+    pub fn set_congestion_control(&self, enabled: bool) {
+        self.apply_config(|config| config.congestion_control = enabled);
+    }
+
+    /// Check if sends wait for the paths' congestion windows
+    pub fn congestion_control(&self) -> bool {
+        self.config.read().congestion_control
+    }
+
+    /// Set the most packets `enqueue` holds while windows are closed
+    /// (0 = unlimited)
+    ///
+    /// Lowering it below the queue's length keeps what is queued.
+    pub fn set_max_pending(&self, packets: usize) {
+        self.apply_config(|config| config.max_pending = packets);
+    }
+
+    /// Get the most packets `enqueue` holds (0 = unlimited)
+    pub fn max_pending(&self) -> usize {
+        self.config.read().max_pending
+    }
+
+    /// Switch the balancing algorithm of a running group
+    pub fn set_algorithm(&self, algorithm: BalancingAlgorithm) {
+        self.apply_config(|config| config.algorithm = algorithm);
+    }
+
+    /// Get the balancing algorithm
+    pub fn algorithm(&self) -> BalancingAlgorithm {
+        self.config.read().algorithm
+    }
+
+    /// Get the current tunables
+    pub fn config(&self) -> BalancerConfig {
+        *self.config.read()
+    }
+
+    /// Replace the tunables of a running group
+    ///
+    /// Returns the change, or `None` if nothing differed. Turning
+    /// congestion control on or off restarts the paths' controllers, and a
+    /// new algorithm starts without the previous one's arrival prediction.
+    pub fn reconfigure(&self, config: BalancerConfig) -> Option<ConfigChange<BalancerConfig>> {
+        self.apply_config(|current| *current = config)
+    }
+
+    /// Set the hook run after each config change
+    pub fn set_config_change_hook<F>(&self, hook: F)
+    where
+        F: Fn(&ConfigChange<BalancerConfig>) + Send + Sync + 'static,
+    {
+        *self.config_hook.write() = Some(Arc::new(hook));
+    }
+
+    /// Remove the config change hook
+    pub fn clear_config_change_hook(&self) {
+        *self.config_hook.write() = None;
+    }
+
+    fn apply_config(
+        &self,
+        update: impl FnOnce(&mut BalancerConfig),
+    ) -> Option<ConfigChange<BalancerConfig>> {
+        let mut config = self.config.write();
+        let mut new = *config;
+        update(&mut new);
+        let change = ConfigChange::between(*config, new)?;
+        *config = new;
+        drop(config);
+
+        if change.old.congestion_control != new.congestion_control {
+            self.controllers.write().clear();
+        }
+        if change.old.algorithm != new.algorithm {
+            *self.last_arrival.write() = None;
+        }
+        let _span = self.group.span().entered();
+        tracing::info!(
+            "Balancer config changed: {:?} -> {:?}",
+            change.old,
+            change.new
+        );
+        let hook = self.config_hook.read().clone();
+        if let Some(hook) = hook {
+            hook(&change);
+        }
+        Some(change)
+    }
+
+    /// Get the number of packets waiting for a congestion window
+    pub fn pending(&self) -> usize {
+        self.pending.lock().len()
+    }
+
+    /// Get a path's congestion controller state, once the path was used
+    /// with congestion control on
+    pub fn congestion_stats(&self, path_id: u32) -> Option<CongestionStats> {
+        self.controllers
+            .read()
+            .get(&path_id)
+            .map(CongestionController::stats)
+    }
+
     /// Send data using load balancing
+    ///
+    /// With congestion control on, fails with `WindowClosed` when no path's
+    /// window has room, or while queued data is still waiting; use
+    /// `enqueue` to queue instead.
     pub fn send(&self, data: &[u8]) -> Result<BalancingSendResult, BalancingError> {
+        let mut pending = self.pending.lock();
+        if !pending.is_empty() {
+            self.send_pending(&mut pending)?;
+            if !pending.is_empty() {
+                return Err(BalancingError::WindowClosed);
+            }
+        }
+        self.send_now(data)?.ok_or(BalancingError::WindowClosed)
+    }
+
+    /// Send data if a window is open, or queue it until one opens
+    ///
+    /// Returns what was sent now, queued data first. Without congestion
+    /// control this sends at once, like `send`.
+    pub fn enqueue(&self, data: &[u8]) -> Result<Vec<BalancingSendResult>, BalancingError> {
+        self.group.check_payload_size(data.len())?;
+        let max_pending = self.max_pending();
+        let mut pending = self.pending.lock();
+        if max_pending > 0 && pending.len() >= max_pending {
+            return Err(BalancingError::QueueFull(max_pending));
+        }
+        pending.push_back(data.to_vec());
+        self.send_pending(&mut pending)
+    }
+
+    /// Send queued data as far as the congestion windows allow
+    ///
+    /// `on_ack` and `on_loss` call this as windows open; call it from a
+    /// timer as well if ACKs may stop arriving.
+    pub fn flush(&self) -> Result<Vec<BalancingSendResult>, BalancingError> {
+        self.send_pending(&mut self.pending.lock())
+    }
+
+    fn send_pending(
+        &self,
+        pending: &mut VecDeque<Vec<u8>>,
+    ) -> Result<Vec<BalancingSendResult>, BalancingError> {
+        let mut sent = Vec::new();
+        while let Some(data) = pending.front() {
+            let Some(result) = self.send_now(data)? else {
+                break;
+            };
+            sent.push(result);
+            pending.pop_front();
+        }
+        Ok(sent)
+    }
+
+    /// Send on a path chosen by the algorithm, or return `None` if no
+    /// path's congestion window is open
+    fn send_now(&self, data: &[u8]) -> Result<Option<BalancingSendResult>, BalancingError> {
         let members = self.group.get_active_members();
 
         if members.is_empty() {
             return Err(BalancingError::NoActiveMembers);
         }
 
+        self.group.check_payload_size(data.len())?;
+
         // Update capacity estimates
         self.update_capacities();
 
         // Select path based on algorithm
-        let selected_path = self.select_path(&members)?;
+        let Some(selected_path) = self.select_open_path(&members, data.len())? else {
+            return Ok(None);
+        };
 
         // Send on selected path
         let member = self
@@ -136,31 +386,105 @@ impl LoadBalancer {
                 if let Some(capacity) = self.capacities.write().get_mut(&selected_path) {
                     capacity.packets_in_flight += 1;
                 }
+                if let Some(controller) = self.controllers.write().get_mut(&selected_path) {
+                    controller.on_packet_sent();
+                }
 
-                Ok(BalancingSendResult {
+                Ok(Some(BalancingSendResult {
                     path_id: selected_path,
                     sequence,
                     bytes_sent: data.len(),
-                })
+                }))
             }
             Err(_) => {
                 // Path failed, try another
                 self.mark_path_failed(selected_path);
 
                 // Recursively try another path
-                self.send(data)
+                self.send_now(data)
             }
         }
     }
 
+    /// Select a path whose congestion window is open
+    ///
+    /// `Partitioned` keeps its strides: if the owner's window is closed,
+    /// nothing is sent.
+    fn select_open_path(
+        &self,
+        members: &[Arc<GroupMember>],
+        packet_size: usize,
+    ) -> Result<Option<u32>, BalancingError> {
+        let config = self.config();
+        if !config.congestion_control {
+            return self.select_path(members, packet_size).map(Some);
+        }
+
+        let mut controllers = self.controllers.write();
+        for member in members {
+            controllers
+                .entry(member.connection.local_socket_id())
+                .or_insert_with(|| self.new_controller(&member.connection));
+        }
+        let is_open = |id: u32| controllers.get(&id).map_or(true, |c| c.can_send());
+
+        if config.algorithm == BalancingAlgorithm::Partitioned {
+            let owner = self.select_path(members, packet_size)?;
+            return Ok(is_open(owner).then_some(owner));
+        }
+        let open: Vec<_> = members
+            .iter()
+            .filter(|m| is_open(m.connection.local_socket_id()))
+            .cloned()
+            .collect();
+        if open.is_empty() {
+            return Ok(None);
+        }
+        self.select_path(&open, packet_size).map(Some)
+    }
+
+    /// Create a congestion controller for a path's connection
+    fn new_controller(&self, connection: &Connection) -> CongestionController {
+        let max_bandwidth = match connection.max_bandwidth() {
+            0 => u64::MAX,
+            bytes_per_sec => bytes_per_sec,
+        };
+        let flow_window = u32::try_from(connection.send_buffer_size()).unwrap_or(u32::MAX);
+        let mut controller =
+            CongestionController::new(max_bandwidth, self.group.max_payload_size(), flow_window);
+        if let Some(rtt_us) = connection.handshake_rtt_us() {
+            controller.set_initial_rtt(rtt_us);
+        }
+        controller
+    }
+
+    /// Get the partition `Partitioned` sends by
+    ///
+    /// Active members in socket ID order. It changes with the active
+    /// members; announce it again on every path when it does.
+    pub fn partition(&self) -> SequencePartition {
+        Self::partition_of(&self.group.get_active_members())
+    }
+
+    fn partition_of(members: &[Arc<GroupMember>]) -> SequencePartition {
+        let mut ids: Vec<_> = members
+            .iter()
+            .map(|m| m.connection.local_socket_id())
+            .collect();
+        ids.sort_unstable();
+        SequencePartition::new(ids)
+    }
+
     /// Select a path based on the balancing algorithm
     fn select_path(
         &self,
-        members: &[Arc<crate::group::GroupMember>],
+        members: &[Arc<GroupMember>],
+        packet_size: usize,
     ) -> Result<u32, BalancingError> {
+        let algorithm = self.algorithm();
         let capacities = self.capacities.read();
 
-        match self.algorithm {
+        match algorithm {
             BalancingAlgorithm::RoundRobin => {
                 // Simple round-robin
                 static COUNTER: std::sync::atomic::AtomicUsize =
@@ -218,18 +542,79 @@ impl LoadBalancer {
             }
 
             BalancingAlgorithm::FastestPath => {
-                // Select path with lowest RTT
-                members
+                // Select path the receiver sees first, once it has reported
+                // on every path; otherwise the path with lowest RTT
+                let paths: Vec<_> = members
                     .iter()
-                    .filter_map(|m| {
-                        let id = m.connection.local_socket_id();
-                        capacities.get(&id).map(|c| (id, c.rtt_us))
+                    .filter_map(|m| capacities.get(&m.connection.local_socket_id()))
+                    .collect();
+                let reported = paths.iter().all(|c| c.peer_delay_us.is_some());
+                paths
+                    .iter()
+                    .min_by_key(|c| {
+                        if reported {
+                            c.peer_delay_us.unwrap_or(u32::MAX)
+                        } else {
+                            c.rtt_us
+                        }
                     })
-                    .min_by_key(|(_, rtt)| *rtt)
-                    .map(|(id, _)| id)
+                    .map(|c| c.path_id)
                     .ok_or(BalancingError::NoActiveMembers)
             }
 
+            BalancingAlgorithm::ArrivalEqualized => {
+                // One-way delays as the receiver sees them once it has
+                // reported on every path; otherwise half the RTT
+                let paths: Vec<_> = members
+                    .iter()
+                    .filter_map(|m| capacities.get(&m.connection.local_socket_id()))
+                    .filter(|c| c.loss_rate < 1.0)
+                    .collect();
+                let reported = paths.iter().all(|c| c.peer_delay_us.is_some());
+                let now = Instant::now();
+                let arrivals: Vec<_> = paths
+                    .iter()
+                    .map(|c| {
+                        let one_way_us = if reported {
+                            c.peer_delay_us.unwrap_or(0)
+                        } else {
+                            c.rtt_us / 2
+                        };
+                        (
+                            c.path_id,
+                            now + c.predicted_arrival(one_way_us, packet_size),
+                        )
+                    })
+                    .collect();
+                let earliest = arrivals
+                    .iter()
+                    .map(|(_, at)| *at)
+                    .min()
+                    .ok_or(BalancingError::NoActiveMembers)?;
+
+                // Ideally a packet lands right after the one before it:
+                // earlier overtakes it, later holds up the receiver
+                let mut last_arrival = self.last_arrival.write();
+                let ideal = last_arrival.map_or(earliest, |last| last.max(earliest));
+                let distance = |at: Instant| {
+                    if at > ideal {
+                        at - ideal
+                    } else {
+                        ideal - at
+                    }
+                };
+                let (path_id, at) = arrivals
+                    .into_iter()
+                    .min_by_key(|(id, at)| (distance(*at), *id))
+                    .ok_or(BalancingError::NoActiveMembers)?;
+                *last_arrival = Some(at);
+                Ok(path_id)
+            }
+
+            BalancingAlgorithm::Partitioned => Self::partition_of(members)
+                .owner(self.group.current_sequence())
+                .ok_or(BalancingError::NoActiveMembers),
+
             BalancingAlgorithm::HighestBandwidth => {
                 // Select path with highest bandwidth
                 members
@@ -257,6 +642,9 @@ impl LoadBalancer {
             let capacity = capacities
                 .entry(id)
                 .or_insert_with(|| PathCapacity::new(id));
+            if let Some(probe) = member.connection.probe_result() {
+                capacity.apply_probe(&probe);
+            }
 
             // Update bandwidth estimate (simplified)
             if stats.bandwidth_bps > 0 {
@@ -267,16 +655,29 @@ impl LoadBalancer {
             if stats.rtt_us > 0 {
                 capacity.rtt_us = stats.rtt_us;
             }
+            capacity.peer_delay_us = stats.peer_delay_us;
+            capacity.queue_depth = member.undelivered_packets();
 
             capacity.last_update = Instant::now();
         }
     }
 
     /// Record packet ACK (reduce in-flight count)
+    ///
+    /// Queued data goes out on the room the ACK opens.
     pub fn on_ack(&self, path_id: u32, packets: u32) {
-        if let Some(capacity) = self.capacities.write().get_mut(&path_id) {
+        let rtt_us = {
+            let mut capacities = self.capacities.write();
+            let Some(capacity) = capacities.get_mut(&path_id) else {
+                return;
+            };
             capacity.packets_in_flight = capacity.packets_in_flight.saturating_sub(packets);
+            capacity.rtt_us
+        };
+        if let Some(controller) = self.controllers.write().get_mut(&path_id) {
+            controller.on_ack(packets, rtt_us);
         }
+        self.window_opened();
     }
 
     /// Record packet loss
@@ -288,6 +689,20 @@ impl LoadBalancer {
 
             capacity.packets_in_flight = capacity.packets_in_flight.saturating_sub(lost_packets);
         }
+        if let Some(controller) = self.controllers.write().get_mut(&path_id) {
+            controller.on_loss(lost_packets);
+        }
+        self.window_opened();
+    }
+
+    /// Send queued data after a window may have opened
+    fn window_opened(&self) {
+        if !self.congestion_control() {
+            return;
+        }
+        if let Err(e) = self.flush() {
+            tracing::debug!("Queued data not sent: {}", e);
+        }
     }
 
     /// Mark path as failed
@@ -295,6 +710,7 @@ impl LoadBalancer {
         if let Some(capacity) = self.capacities.write().get_mut(&path_id) {
             capacity.loss_rate = 1.0; // Mark as completely failed
         }
+        self.controllers.write().remove(&path_id);
 
         // Update member status
         let _ = self
@@ -308,7 +724,7 @@ impl LoadBalancer {
         let path_capacities: Vec<_> = capacities.values().cloned().collect();
 
         BalancingStats {
-            algorithm: self.algorithm,
+            algorithm: self.algorithm(),
             path_count: path_capacities.len(),
             path_capacities,
             total_bandwidth_bps: capacities.values().map(|c| c.bandwidth_bps).sum(),
@@ -329,6 +745,14 @@ pub enum BalancingAlgorithm {
     FastestPath,
     /// Send on highest bandwidth path
     HighestBandwidth,
+    /// Send each sequence on the path owning its stride, so the receiver
+    /// can tell which path lost a gap (see `SequencePartition`)
+    Partitioned,
+    /// Send on the path predicted to deliver each packet closest to its
+    /// ideal arrival, right after the packet before it, from each path's
+    /// one-way delay and queue depth; keeps the receiver's reorder buffer
+    /// shallow on asymmetric paths
+    ArrivalEqualized,
 }
 
 /// Balancing send result
@@ -355,6 +779,17 @@ pub struct BalancingStats {
     pub total_bandwidth_bps: u64,
 }
 
+impl BalancingStats {
+    /// Mean loss rate across paths (0.0 to 1.0)
+    pub fn mean_loss_rate(&self) -> f64 {
+        if self.path_capacities.is_empty() {
+            return 0.0;
+        }
+        let total: f64 = self.path_capacities.iter().map(|c| c.loss_rate).sum();
+        total / self.path_capacities.len() as f64
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -400,6 +835,37 @@ mod tests {
         assert_eq!(capacity.calculate_weight(), 0.0);
     }
 
+    #[test]
+    fn test_path_capacity_from_probe() {
+        let probe = ProbeResult {
+            packets_sent: 16,
+            packets_received: 12,
+            bandwidth_bps: Some(5_000_000),
+            rtt_us: Some(30_000),
+            loss_rate: 0.25,
+        };
+        let mut capacity = PathCapacity::new(1);
+        capacity.apply_probe(&probe);
+        assert_eq!(capacity.bandwidth_bps, 5_000_000);
+        assert_eq!(capacity.rtt_us, 30_000);
+        assert_eq!(capacity.loss_rate, 0.25);
+
+        // Applied once, so live measurements are not overwritten
+        capacity.rtt_us = 20_000;
+        capacity.apply_probe(&probe);
+        assert_eq!(capacity.rtt_us, 20_000);
+
+        // Unmeasured values keep the defaults
+        let mut capacity = PathCapacity::new(2);
+        capacity.apply_probe(&ProbeResult {
+            bandwidth_bps: None,
+            rtt_us: None,
+            ..probe
+        });
+        assert_eq!(capacity.bandwidth_bps, PathCapacity::new(2).bandwidth_bps);
+        assert_eq!(capacity.rtt_us, PathCapacity::new(2).rtt_us);
+    }
+
     #[test]
     fn test_on_ack() {
         let group = create_test_group();
@@ -441,4 +907,217 @@ mod tests {
         assert!(cap.loss_rate > 0.0);
         assert_eq!(cap.packets_in_flight, 90);
     }
+
+    #[test]
+    fn test_fastest_path_prefers_receiver_view() {
+        let group = create_test_group();
+        for id in [1, 2] {
+            group
+                .add_member(
+                    create_test_connection(id),
+                    "127.0.0.1:9001".parse().unwrap(),
+                )
+                .unwrap();
+        }
+        let members = group.get_all_members();
+        let balancer = LoadBalancer::new(group, BalancingAlgorithm::FastestPath, 100);
+        let set_path = |id, rtt_us, peer_delay_us| {
+            let mut cap = PathCapacity::new(id);
+            cap.rtt_us = rtt_us;
+            cap.peer_delay_us = peer_delay_us;
+            balancer.capacities.write().insert(id, cap);
+        };
+
+        // Path 1 has the lower RTT, but the receiver sees path 2 first
+        set_path(1, 10_000, None);
+        set_path(2, 50_000, Some(0));
+        assert_eq!(balancer.select_path(&members, 1316).unwrap(), 1);
+
+        set_path(1, 10_000, Some(30_000));
+        assert_eq!(balancer.select_path(&members, 1316).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_arrival_equalized_targets_ideal_arrival() {
+        let group = create_test_group();
+        for id in [1, 2] {
+            group
+                .add_member(
+                    create_test_connection(id),
+                    "127.0.0.1:9001".parse().unwrap(),
+                )
+                .unwrap();
+        }
+        let members = group.get_all_members();
+        let balancer = LoadBalancer::new(group, BalancingAlgorithm::ArrivalEqualized, 100);
+        // 1000-byte packets drain in 1ms on either path
+        let set_path = |id, rtt_us, queue_depth| {
+            let mut cap = PathCapacity::new(id);
+            cap.rtt_us = rtt_us;
+            cap.queue_depth = queue_depth;
+            balancer.capacities.write().insert(id, cap);
+        };
+
+        // Idle: the 5ms path beats the 50ms one
+        set_path(1, 10_000, 0);
+        set_path(2, 100_000, 0);
+        assert_eq!(balancer.select_path(&members, 1000).unwrap(), 1);
+
+        // 60 packets queued on the fast path: the slow path lands first
+        set_path(1, 10_000, 60);
+        assert_eq!(balancer.select_path(&members, 1000).unwrap(), 2);
+
+        // The slow path would land after the last packet (~51ms) by 5ms,
+        // the fast path before it by 2ms
+        set_path(1, 10_000, 43);
+        set_path(2, 100_000, 5);
+        assert_eq!(balancer.select_path(&members, 1000).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_partitioned_follows_sequence_strides() {
+        let group = create_test_group();
+        for id in [3, 1, 2] {
+            group
+                .add_member(
+                    create_test_connection(id),
+                    "127.0.0.1:9001".parse().unwrap(),
+                )
+                .unwrap();
+        }
+        let members = group.get_all_members();
+        let balancer = LoadBalancer::new(group.clone(), BalancingAlgorithm::Partitioned, 100);
+
+        let mut paths = Vec::new();
+        for _ in 0..6 {
+            paths.push(balancer.select_path(&members, 1316).unwrap());
+            group.next_sequence();
+        }
+        assert_eq!(paths, vec![1, 2, 3, 1, 2, 3]);
+        let partition = LoadBalancer::partition_of(&members);
+        assert_eq!(partition.announcement(3).unwrap().slot, 2);
+    }
+
+    /// A group of active members whose connections completed a handshake
+    fn connected_group(ids: &[u32]) -> Arc<SocketGroup> {
+        let group = create_test_group();
+        for &id in ids {
+            let mut conn = Connection::new(
+                id,
+                "127.0.0.1:9000".parse().unwrap(),
+                "127.0.0.1:9001".parse().unwrap(),
+                SeqNumber::new(0),
+                120,
+            );
+            let peer = srt_protocol::SrtHandshake::new_request(
+                0,
+                100 + id,
+                "127.0.0.1:9000".parse().unwrap(),
+                srt_protocol::SrtOptions::default_capabilities(),
+                120,
+                120,
+            );
+            conn.process_handshake(peer).unwrap();
+            group
+                .add_member(Arc::new(conn), "127.0.0.1:9001".parse().unwrap())
+                .unwrap();
+            group
+                .update_member_status(id, MemberStatus::Active)
+                .unwrap();
+        }
+        group
+    }
+
+    #[test]
+    fn test_congestion_window_gates_paths() {
+        let group = connected_group(&[1, 2]);
+        let balancer = LoadBalancer::new(group, BalancingAlgorithm::RoundRobin, 100);
+        balancer.set_congestion_control(true);
+
+        let mut sent = Vec::new();
+        for _ in 0..40 {
+            sent.extend(balancer.enqueue(b"data").unwrap());
+        }
+        // Each path starts with a 16-packet window
+        assert_eq!(sent.len(), 32);
+        assert_eq!(sent.iter().filter(|r| r.path_id == 1).count(), 16);
+        assert_eq!(balancer.pending(), 8);
+        assert!(matches!(
+            balancer.send(b"data"),
+            Err(BalancingError::WindowClosed)
+        ));
+
+        // An ACK opens path 1; the queue drains onto it alone
+        balancer.on_ack(1, 10);
+        assert_eq!(balancer.pending(), 0);
+        let stats = balancer.congestion_stats(1).unwrap();
+        assert_eq!(stats.packets_in_flight, 14);
+        assert_eq!(balancer.congestion_stats(2).unwrap().packets_in_flight, 16);
+        assert!(balancer.send(b"data").is_ok());
+    }
+
+    #[test]
+    fn test_pending_queue_limit() {
+        let group = connected_group(&[1]);
+        let balancer = LoadBalancer::new(group, BalancingAlgorithm::Partitioned, 100);
+        balancer.set_congestion_control(true);
+        balancer.set_max_pending(2);
+
+        for _ in 0..18 {
+            balancer.enqueue(b"data").unwrap();
+        }
+        assert_eq!(balancer.pending(), 2);
+        assert!(matches!(
+            balancer.enqueue(b"data"),
+            Err(BalancingError::QueueFull(2))
+        ));
+
+        // Off: windows are not consulted
+        balancer.set_congestion_control(false);
+        assert_eq!(balancer.flush().unwrap().len(), 2);
+        assert!(balancer.congestion_stats(1).is_none());
+    }
+
+    #[test]
+    fn test_reconfigure_live_balancer() {
+        let group = connected_group(&[1, 2]);
+        let balancer = Arc::new(LoadBalancer::new(
+            group,
+            BalancingAlgorithm::RoundRobin,
+            100,
+        ));
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        let seen = changes.clone();
+        balancer.set_config_change_hook(move |change| seen.lock().push(change.clone()));
+
+        balancer.set_congestion_control(true);
+        for _ in 0..4 {
+            balancer.send(b"data").unwrap();
+        }
+        assert!(balancer.congestion_stats(1).is_some());
+
+        // Algorithm and limits switch together; the windows restart
+        let change = balancer
+            .reconfigure(BalancerConfig {
+                algorithm: BalancingAlgorithm::Partitioned,
+                congestion_control: false,
+                max_pending: 8,
+            })
+            .unwrap();
+        assert_eq!(change.old.algorithm, BalancingAlgorithm::RoundRobin);
+        assert!(change.old.congestion_control);
+        assert_eq!(balancer.algorithm(), BalancingAlgorithm::Partitioned);
+        assert_eq!(balancer.max_pending(), 8);
+        assert!(balancer.congestion_stats(1).is_none());
+        assert_eq!(balancer.stats().algorithm, BalancingAlgorithm::Partitioned);
+
+        // Packets go to each sequence's owner from now on
+        let partition = balancer.partition();
+        let result = balancer.send(b"data").unwrap();
+        assert_eq!(Some(result.path_id), partition.owner(result.sequence));
+
+        assert!(balancer.reconfigure(balancer.config()).is_none());
+        balancer.set_algorithm(BalancingAlgorithm::Partitioned);
+        assert_eq!(changes.lock().len(), 2);
+    }
 }
@@ -2,12 +2,30 @@
 //!
 //! Send the same packet to all group members simultaneously.
 //! Receive from the first member that delivers (fastest path wins).
-
-use crate::group::{GroupError, MemberStatus, SocketGroup};
+//!
+//! With single-path bypass enabled, a group with one active member skips
+//! reordering entirely and delivers each packet as it arrives; reordering
+//! re-engages as soon as a second member becomes active.
+//!
+//! With adaptive redundancy enabled, the sender duplicates only what the
+//! paths need: while one path is clean and delivers first, the others carry
+//! every Nth packet (see `redundancy`).
+//!
+//! Sends are queued on each member. `BroadcastBonding::spawn_queue_workers`
+//! drains every queue from a thread of its own; event loops call
+//! `SocketGroup::service_queues` instead.
+
+use crate::alignment::{drain_in_order, PathTracker};
+use crate::group::{GroupError, SocketGroup};
+use crate::queue::MemberQueueWorker;
+use crate::redundancy::{RedundancyConfig, RedundancyController, RedundancyLevel};
+use crate::resync::ResyncMessage;
+use crate::status::{PathStatus, StatusReport, MAX_REPORTED_PATHS};
 use bytes::Bytes;
-use parking_lot::RwLock;
-use srt_protocol::{DataPacket, MsgNumber, SeqNumber};
+use parking_lot::{Mutex, RwLock};
+use srt_protocol::{Connection, DataPacket, DroppedRanges, LossRange, SeqNumber};
 use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::Instant;
 use thiserror::Error;
@@ -21,6 +39,11 @@ pub enum BroadcastError {
     #[error("Group error: {0}")]
     Group(#[from] GroupError),
 
+    /// Never returned: sends queue on every active member
+    #[deprecated(
+        since = "0.1.4",
+        note = "never returned; sends fail with `NoActiveMembers`"
+    )]
     #[error("Failed to send on all paths")]
     AllPathsFailed,
 
@@ -33,23 +56,149 @@ pub enum BroadcastError {
 pub struct BroadcastSendResult {
     /// Number of members packet was sent to
     pub sent_count: usize,
-    /// Number of successful sends
+    /// Number of members that left the packet out (adaptive redundancy)
+    pub skipped_count: usize,
+    /// Number of members that queued the packet
+    #[deprecated(since = "0.1.4", note = "always equal to `sent_count`")]
     pub success_count: usize,
-    /// IDs of members that failed
+    /// IDs of members whose queue overflowed (oldest packet dropped)
     pub failed_members: Vec<u32>,
     /// Sequence number used
     pub sequence: SeqNumber,
 }
 
-/// Received packet info
-#[derive(Debug, Clone)]
-struct ReceivedPacketInfo {
-    /// Packet data
-    packet: DataPacket,
-    /// Which member received it
-    _member_id: u32,
-    /// When it was received
-    _received_at: Instant,
+/// Packets waiting for in-order delivery, in slots indexed by sequence
+///
+/// Buffered packets always span fewer than `window` sequence numbers, so
+/// each has a slot of its own and no hashing is needed. An empty ring
+/// anchors on the next packet however far ahead it is (e.g. a sender
+/// that is well ahead of a restarted receiver, until they resync).
+struct PendingPackets {
+    /// Slot storage, `seq & mask`
+    slots: Vec<Option<DataPacket>>,
+    mask: usize,
+    /// Maximum span of buffered sequence numbers
+    window: usize,
+    /// Number of packets buffered
+    len: usize,
+    /// Lower bound of the buffered sequences (while not empty)
+    low: SeqNumber,
+    /// Highest buffered sequence (while not empty)
+    high: SeqNumber,
+}
+
+impl PendingPackets {
+    fn new(window: usize) -> Self {
+        let capacity = window.next_power_of_two();
+        PendingPackets {
+            slots: vec![None; capacity],
+            mask: capacity - 1,
+            window,
+            len: 0,
+            low: SeqNumber::new(0),
+            high: SeqNumber::new(0),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn slot(&self, seq: SeqNumber) -> usize {
+        seq.as_raw() as usize & self.mask
+    }
+
+    fn contains(&self, seq: SeqNumber) -> bool {
+        self.slots[self.slot(seq)]
+            .as_ref()
+            .is_some_and(|packet| packet.seq_number() == seq)
+    }
+
+    /// Store a packet, returning false if it does not fit the window
+    fn insert(&mut self, packet: DataPacket) -> bool {
+        let seq = packet.seq_number();
+        let (low, high) = if self.is_empty() {
+            (seq, seq)
+        } else {
+            let low = if seq.distance_to(self.low) > 0 {
+                seq
+            } else {
+                self.low
+            };
+            let high = if self.high.distance_to(seq) > 0 {
+                seq
+            } else {
+                self.high
+            };
+            (low, high)
+        };
+        if low.distance_to(high) as usize >= self.window {
+            return false;
+        }
+
+        let slot = self.slot(seq);
+        self.slots[slot] = Some(packet);
+        self.len += 1;
+        self.low = low;
+        self.high = high;
+        true
+    }
+
+    fn remove(&mut self, seq: SeqNumber) -> Option<DataPacket> {
+        if !self.contains(seq) {
+            return None;
+        }
+        let slot = self.slot(seq);
+        self.len -= 1;
+        if seq == self.low {
+            self.low = seq.next();
+        }
+        self.slots[slot].take()
+    }
+
+    /// Get the oldest buffered sequence
+    fn first(&self) -> Option<SeqNumber> {
+        if self.is_empty() {
+            return None;
+        }
+        let mut seq = self.low;
+        while !self.contains(seq) {
+            seq = seq.next();
+        }
+        Some(seq)
+    }
+
+    /// Remove the packets before `seq`, in sequence order
+    fn remove_before(&mut self, seq: SeqNumber) -> Vec<(SeqNumber, DataPacket)> {
+        let mut removed = Vec::new();
+        if self.is_empty() || self.low.distance_to(seq) <= 0 {
+            return removed;
+        }
+        let end = if self.high.distance_to(seq) > 0 {
+            self.high.next()
+        } else {
+            seq
+        };
+        let mut next = self.low;
+        while next != end {
+            if let Some(packet) = self.remove(next) {
+                removed.push((next, packet));
+            }
+            next = next.next();
+        }
+        self.low = end;
+        removed
+    }
+
+    /// Remove every packet, in sequence order
+    fn drain(&mut self) -> Vec<(SeqNumber, DataPacket)> {
+        let high = self.high;
+        self.remove_before(high.next())
+    }
 }
 
 /// Broadcast receiver state
@@ -57,24 +206,39 @@ struct ReceivedPacketInfo {
 /// Tracks packets received from multiple paths to deliver only once
 /// (from the fastest path).
 pub struct BroadcastReceiver {
-    /// Packets received, indexed by sequence number
-    received: Arc<RwLock<HashMap<SeqNumber, ReceivedPacketInfo>>>,
+    /// Packets received ahead of the next expected one
+    received: Arc<RwLock<PendingPackets>>,
     /// Next expected sequence number
     next_expected: Arc<RwLock<SeqNumber>>,
     /// Ordered packets ready for delivery
     ready_queue: Arc<RwLock<VecDeque<DataPacket>>>,
-    /// Maximum buffer size
-    max_buffer_size: usize,
+    /// Packets delivered without reordering (single-path bypass)
+    bypassed: AtomicU64,
+    /// Missing packets given up on by the bypass
+    skipped: AtomicU64,
+    /// Ranges a sender dropped, skipped instead of waited for
+    dropped: Arc<RwLock<DroppedRanges>>,
+    /// Dropped ranges reported by senders
+    dropped_ranges: AtomicU64,
+    /// Missing packets skipped because their sender dropped them
+    dropped_packets: AtomicU64,
 }
 
 impl BroadcastReceiver {
     /// Create a new broadcast receiver
+    ///
+    /// Up to `max_buffer_size` consecutive sequence numbers are buffered
+    /// while waiting for a missing packet; later packets are dropped.
     pub fn new(max_buffer_size: usize) -> Self {
         BroadcastReceiver {
-            received: Arc::new(RwLock::new(HashMap::new())),
+            received: Arc::new(RwLock::new(PendingPackets::new(max_buffer_size))),
             next_expected: Arc::new(RwLock::new(SeqNumber::new(0))),
             ready_queue: Arc::new(RwLock::new(VecDeque::new())),
-            max_buffer_size,
+            bypassed: AtomicU64::new(0),
+            skipped: AtomicU64::new(0),
+            dropped: Arc::new(RwLock::new(DroppedRanges::new())),
+            dropped_ranges: AtomicU64::new(0),
+            dropped_packets: AtomicU64::new(0),
         }
     }
 
@@ -87,6 +251,8 @@ impl BroadcastReceiver {
         member_id: u32,
     ) -> Result<bool, BroadcastError> {
         let seq = packet.seq_number();
+        // Held throughout, so next_expected cannot move underneath us
+        let mut received = self.received.write();
 
         // Check if packet has already been delivered (seq < next_expected)
         let next_expected = *self.next_expected.read();
@@ -107,58 +273,146 @@ impl BroadcastReceiver {
             return Err(BroadcastError::DuplicatePacket);
         }
 
-        let mut received = self.received.write();
-
         // Check if we already received this packet (buffered but not yet delivered)
-        if received.contains_key(&seq) {
+        if received.contains(seq) {
             tracing::debug!("Packet {} already in buffer, rejecting", seq.as_raw());
             return Err(BroadcastError::DuplicatePacket);
         }
 
-        // Check buffer size
-        if received.len() >= self.max_buffer_size {
-            // Buffer full, drop this packet
+        if seq == next_expected {
+            // In order: straight to the ready queue
+            *self.next_expected.write() = seq.next();
+            self.ready_queue.write().push_back(packet);
+            self.deliver_ready_packets(&mut received);
+            return Ok(true);
+        }
+
+        // Store the packet, unless it is too far ahead of those buffered
+        tracing::debug!("Storing packet {} in buffer", seq.as_raw());
+        if !received.insert(packet) {
             tracing::warn!(
-                "Buffer full ({}/{}), dropping packet {}",
+                "Buffer full ({} packets, window {}), dropping packet {}",
                 received.len(),
-                self.max_buffer_size,
+                received.window,
                 seq.as_raw()
             );
             return Ok(false);
         }
 
-        // Store the packet
-        tracing::debug!("Storing packet {} in buffer", seq.as_raw());
-        received.insert(
-            seq,
-            ReceivedPacketInfo {
-                packet: packet.clone(),
-                _member_id: member_id,
-                _received_at: Instant::now(),
-            },
-        );
-
         // Try to deliver in-order packets
         self.deliver_ready_packets(&mut received);
 
         Ok(true)
     }
 
+    /// Process a packet from the only active path, without reordering
+    ///
+    /// The packet is delivered at once. Packets still missing before it are
+    /// given up: with a single path they could only arrive late, and loss
+    /// recovery belongs to the connection. Packets buffered while reordering
+    /// was engaged are flushed ahead of it.
+    pub fn on_packet_bypass(&self, packet: DataPacket) -> Result<bool, BroadcastError> {
+        let seq = packet.seq_number();
+        let mut received = self.received.write();
+        {
+            let mut next_expected = self.next_expected.write();
+            if seq.distance_to(*next_expected) > 0 || received.contains(seq) {
+                return Err(BroadcastError::DuplicatePacket);
+            }
+
+            let mut ready_queue = self.ready_queue.write();
+            self.bypassed.fetch_add(1, Ordering::Relaxed);
+            if received.is_empty() && seq == *next_expected {
+                ready_queue.push_back(packet);
+                *next_expected = seq.next();
+                return Ok(true);
+            }
+
+            // Flush everything buffered up to this packet, skipping the gaps
+            let mut buffered = received.remove_before(seq);
+            buffered.push((seq, packet));
+
+            let (packets, gaps) = drain_in_order(&mut next_expected, buffered);
+            let skipped: usize = gaps.iter().map(|g| g.len()).sum();
+            if skipped > 0 {
+                tracing::debug!(
+                    "Bypass: skipped {} missing packets before {}",
+                    skipped,
+                    seq.as_raw()
+                );
+                self.skipped.fetch_add(skipped as u64, Ordering::Relaxed);
+            }
+            ready_queue.extend(packets);
+        }
+
+        // Buffered packets that now follow on in order
+        self.deliver_ready_packets(&mut received);
+        Ok(true)
+    }
+
+    /// Stop waiting for a range a member's sender dropped (DropReq)
+    ///
+    /// Missing packets in the range are skipped once delivery reaches
+    /// them; packets from it that another path delivers are kept. Returns
+    /// false if the range was already passed.
+    pub fn on_drop_request(&self, range: LossRange) -> bool {
+        let mut received = self.received.write();
+        let next_expected = *self.next_expected.read();
+        if !self.dropped.write().add(range, next_expected) {
+            return false;
+        }
+        self.dropped_ranges.fetch_add(1, Ordering::Relaxed);
+        tracing::debug!(
+            "Sender dropped {}-{}, next_expected {}",
+            range.start.as_raw(),
+            range.end.as_raw(),
+            next_expected.as_raw()
+        );
+
+        self.deliver_ready_packets(&mut received);
+        true
+    }
+
+    /// Apply the ranges a member connection's sender dropped
+    ///
+    /// Call after the member processed a DropReq. Returns the number of
+    /// ranges taken from the connection.
+    pub fn take_member_drops(&self, conn: &Connection) -> usize {
+        let mut count = 0;
+        while let Some(range) = conn.take_dropped_range() {
+            self.on_drop_request(range);
+            count += 1;
+        }
+        count
+    }
+
     /// Deliver packets that are ready (in sequence order)
-    fn deliver_ready_packets(&self, received: &mut HashMap<SeqNumber, ReceivedPacketInfo>) {
+    ///
+    /// Missing packets in dropped ranges are skipped on the way.
+    fn deliver_ready_packets(&self, received: &mut PendingPackets) {
         let mut next_expected = self.next_expected.write();
         let mut ready_queue = self.ready_queue.write();
+        let mut dropped = self.dropped.write();
 
         let mut delivered_count = 0;
-        while let Some(info) = received.remove(&*next_expected) {
+        let mut skipped = 0;
+        loop {
+            skipped += dropped.skip(&mut next_expected, |seq| received.contains(seq));
+            let Some(packet) = received.remove(*next_expected) else {
+                break;
+            };
             tracing::debug!(
                 "Delivering packet {} to ready queue",
                 next_expected.as_raw()
             );
-            ready_queue.push_back(info.packet);
+            ready_queue.push_back(packet);
             *next_expected = next_expected.next();
             delivered_count += 1;
         }
+        if skipped > 0 {
+            self.dropped_packets
+                .fetch_add(skipped as u64, Ordering::Relaxed);
+        }
 
         if delivered_count > 0 {
             tracing::debug!(
@@ -169,11 +423,74 @@ impl BroadcastReceiver {
         }
     }
 
+    /// Build a report of the next expected sequence for the sender
+    pub fn resync_report(&self) -> ResyncMessage {
+        ResyncMessage::Report(*self.next_expected.read())
+    }
+
+    /// Handle a resync message from the sender
+    ///
+    /// Returns true if the receiver fast-forwarded.
+    pub fn on_resync(&self, message: ResyncMessage) -> bool {
+        match message {
+            ResyncMessage::Announce(seq) => {
+                self.fast_forward(seq);
+                true
+            }
+            ResyncMessage::Report(_) => false,
+        }
+    }
+
+    /// Jump to `seq` as the next expected sequence
+    ///
+    /// Buffered packets before `seq` are discarded; packets from `seq`
+    /// onwards are kept and delivered if now in order.
+    pub fn fast_forward(&self, seq: SeqNumber) {
+        let mut received = self.received.write();
+        let before = received.len();
+        received.remove_before(seq);
+
+        let previous = std::mem::replace(&mut *self.next_expected.write(), seq);
+        tracing::warn!(
+            "Resync: next_expected {} -> {}, discarded {} buffered packets",
+            previous.as_raw(),
+            seq.as_raw(),
+            before - received.len()
+        );
+
+        self.deliver_ready_packets(&mut received);
+    }
+
+    /// Give up on the gap before the oldest buffered packet
+    ///
+    /// Recovers a stalled receiver without waiting for the sender to
+    /// announce a resync. Returns the sequence skipped to, if any packet
+    /// was buffered.
+    pub fn skip_to_buffered(&self) -> Option<SeqNumber> {
+        let first = self.received.read().first()?;
+        self.fast_forward(first);
+        Some(first)
+    }
+
     /// Get next ready packet for delivery
     pub fn pop_ready_packet(&self) -> Option<DataPacket> {
         self.ready_queue.write().pop_front()
     }
 
+    /// Flush ready and buffered packets in sequence order, skipping gaps
+    ///
+    /// For use on group teardown, when missing packets will no longer
+    /// arrive.
+    pub fn drain(&self) -> BroadcastDrain {
+        let mut packets: Vec<_> = self.ready_queue.write().drain(..).collect();
+
+        let buffered = self.received.write().drain();
+        let (remaining, gaps) = drain_in_order(&mut self.next_expected.write(), buffered);
+        packets.extend(remaining);
+
+        BroadcastDrain { packets, gaps }
+    }
+
     /// Get number of ready packets
     pub fn ready_packet_count(&self) -> usize {
         self.ready_queue.read().len()
@@ -188,10 +505,23 @@ impl BroadcastReceiver {
             buffered_packets: received.len(),
             ready_packets: ready_queue.len(),
             next_expected: *self.next_expected.read(),
+            bypassed_packets: self.bypassed.load(Ordering::Relaxed),
+            skipped_packets: self.skipped.load(Ordering::Relaxed),
+            dropped_ranges: self.dropped_ranges.load(Ordering::Relaxed),
+            dropped_packets: self.dropped_packets.load(Ordering::Relaxed),
         }
     }
 }
 
+/// Data flushed from a broadcast receiver on teardown
+#[derive(Debug, Clone, Default)]
+pub struct BroadcastDrain {
+    /// Remaining packets, in sequence order
+    pub packets: Vec<DataPacket>,
+    /// Sequence ranges that never arrived and cannot be recovered
+    pub gaps: Vec<LossRange>,
+}
+
 /// Broadcast receiver statistics
 #[derive(Debug, Clone)]
 pub struct BroadcastReceiverStats {
@@ -201,23 +531,79 @@ pub struct BroadcastReceiverStats {
     pub ready_packets: usize,
     /// Next expected sequence number
     pub next_expected: SeqNumber,
+    /// Packets delivered by the single-path bypass
+    pub bypassed_packets: u64,
+    /// Missing packets the bypass gave up on
+    pub skipped_packets: u64,
+    /// Dropped ranges reported by senders (DropReq)
+    pub dropped_ranges: u64,
+    /// Missing packets skipped because their sender dropped them
+    pub dropped_packets: u64,
 }
 
 /// Broadcast sender
 ///
-/// Sends packets to all active group members.
+/// Queues packets on all active group members. Member queues are drained by
+/// `BroadcastBonding::spawn_queue_workers`, `SocketGroup::service_queues` or
+/// a `MemberQueueWorker` per member.
 pub struct BroadcastSender {
     /// The socket group
     group: Arc<SocketGroup>,
+    /// Adaptive redundancy, when enabled
+    redundancy: Mutex<Option<RedundancyController>>,
+    /// Member sends left out by adaptive redundancy
+    sends_skipped: AtomicU64,
 }
 
 impl BroadcastSender {
     /// Create a new broadcast sender
     pub fn new(group: Arc<SocketGroup>) -> Self {
-        BroadcastSender { group }
+        BroadcastSender {
+            group,
+            redundancy: Mutex::new(None),
+            sends_skipped: AtomicU64::new(0),
+        }
+    }
+
+    /// Enable adaptive redundancy
+    ///
+    /// Starts at full broadcast; call `update_redundancy` periodically to
+    /// thin the duplicates while one path does the work.
+    pub fn set_adaptive_redundancy(&self, config: RedundancyConfig) {
+        *self.redundancy.lock() = Some(RedundancyController::new(config));
+    }
+
+    /// Disable adaptive redundancy, returning to full broadcast
+    pub fn clear_adaptive_redundancy(&self) {
+        *self.redundancy.lock() = None;
+    }
+
+    /// Measure the paths and adjust the redundancy level
+    ///
+    /// Apply the receiver's latest status report to the group first.
+    /// Returns the new level when it changed; does nothing unless adaptive
+    /// redundancy is enabled.
+    pub fn update_redundancy(&self) -> Option<RedundancyLevel> {
+        self.redundancy.lock().as_mut()?.update(&self.group)
+    }
+
+    /// Get the current redundancy level
+    pub fn redundancy_level(&self) -> RedundancyLevel {
+        self.redundancy
+            .lock()
+            .as_ref()
+            .map_or(RedundancyLevel::FULL, |c| c.level())
+    }
+
+    /// Get the member sends left out by adaptive redundancy
+    pub fn sends_skipped(&self) -> u64 {
+        self.sends_skipped.load(Ordering::Relaxed)
     }
 
     /// Send data to all active members
+    ///
+    /// With adaptive redundancy thinning the paths, members other than the
+    /// primary leave the packet out unless its sequence is due a duplicate.
     pub fn send(&self, data: &[u8]) -> Result<BroadcastSendResult, BroadcastError> {
         let members = self.group.get_active_members();
 
@@ -225,47 +611,51 @@ impl BroadcastSender {
             return Err(BroadcastError::NoActiveMembers);
         }
 
+        self.group.check_payload_size(data.len())?;
+
         let sequence = self.group.next_sequence();
-        let mut success_count = 0;
+        let mut skipped_count = 0;
         let mut failed_members = Vec::new();
 
-        // Create packet (will be sent to all members with same sequence number)
-        let msg_number = MsgNumber::new(sequence.as_raw());
+        // Thinning needs the primary: without it every member carries
+        let mut level = self.redundancy_level();
+        if !members
+            .iter()
+            .any(|m| level.primary == Some(m.connection.local_socket_id()))
+        {
+            level = RedundancyLevel::FULL;
+        }
 
+        // Queue on every member; each queue is serviced independently so a
+        // slow path cannot stall the others. Members leaving the packet out
+        // queue a skip, keeping their sequence numbers in step
+        let payload = Bytes::copy_from_slice(data);
         for member in &members {
-            let _packet = DataPacket::new(
-                sequence,
-                msg_number,
-                0, // Timestamp will be set by connection
-                member.connection.remote_socket_id().unwrap_or(0),
-                Bytes::copy_from_slice(data),
-            );
-
-            match member.connection.send(data) {
-                Ok(_) => {
-                    member.record_sent(data.len());
-                    success_count += 1;
-                }
-                Err(_) => {
-                    failed_members.push(member.connection.local_socket_id());
-                    // Mark member as potentially broken
-                    let mut stats = member.stats.write();
-                    stats.failure_count += 1;
-
-                    if stats.failure_count > 3 {
-                        stats.status = MemberStatus::Broken;
-                    }
+            let member_id = member.connection.local_socket_id();
+            if !level.carries(member_id, sequence) {
+                if !member.enqueue_skip() {
+                    failed_members.push(member_id);
                 }
+                skipped_count += 1;
+                continue;
+            }
+            if !member.enqueue(payload.clone()) {
+                // Queue overflowed, the member is falling behind
+                failed_members.push(member.connection.local_socket_id());
             }
         }
 
-        if success_count == 0 {
-            return Err(BroadcastError::AllPathsFailed);
+        if skipped_count > 0 {
+            self.sends_skipped
+                .fetch_add(skipped_count as u64, Ordering::Relaxed);
         }
 
+        let sent_count = members.len() - skipped_count;
+        #[allow(deprecated)]
         Ok(BroadcastSendResult {
-            sent_count: members.len(),
-            success_count,
+            sent_count,
+            skipped_count,
+            success_count: sent_count,
             failed_members,
             sequence,
         })
@@ -285,37 +675,175 @@ pub struct BroadcastBonding {
     pub receiver: BroadcastReceiver,
     /// Socket group
     pub group: Arc<SocketGroup>,
+    /// Skip reordering while only one member is active
+    single_path_bypass: AtomicBool,
+    /// Whether the last packet took the bypass
+    bypassing: AtomicBool,
+    /// Per-path arrivals, for status reports to the sender
+    paths: RwLock<PathTracker>,
+    /// Queue workers by member ID, once spawned
+    workers: Mutex<Option<HashMap<u32, MemberQueueWorker>>>,
 }
 
 impl BroadcastBonding {
     /// Create new broadcast bonding
     pub fn new(group: Arc<SocketGroup>) -> Self {
+        Self::with_receive_buffer(group, 8192)
+    }
+
+    /// Create new broadcast bonding with a receive buffer of `packets`
+    pub fn with_receive_buffer(group: Arc<SocketGroup>, packets: usize) -> Self {
         BroadcastBonding {
             sender: BroadcastSender::new(group.clone()),
-            receiver: BroadcastReceiver::new(8192),
+            receiver: BroadcastReceiver::new(packets),
             group,
+            single_path_bypass: AtomicBool::new(false),
+            bypassing: AtomicBool::new(false),
+            paths: RwLock::new(PathTracker::new()),
+            workers: Mutex::new(None),
+        }
+    }
+
+    /// Drain each member's outbound queue from a worker thread of its own
+    ///
+    /// Members added later get a worker on the next `send`, and workers of
+    /// removed members stop. Without workers, call
+    /// `SocketGroup::service_queues` to send what `send` queued.
+    pub fn spawn_queue_workers(&self) {
+        self.workers.lock().get_or_insert_with(HashMap::new);
+        self.sync_queue_workers();
+    }
+
+    /// Stop the queue workers, then send what is still queued
+    ///
+    /// Returns the number of packets flushed.
+    pub fn shutdown_queue_workers(&self) -> usize {
+        let Some(workers) = self.workers.lock().take() else {
+            return 0;
+        };
+        workers.into_values().map(MemberQueueWorker::shutdown).sum()
+    }
+
+    /// Check if queue workers are running
+    pub fn has_queue_workers(&self) -> bool {
+        self.workers.lock().is_some()
+    }
+
+    /// Give every member a worker, and stop those of removed members
+    fn sync_queue_workers(&self) {
+        let mut workers = self.workers.lock();
+        let Some(workers) = workers.as_mut() else {
+            return;
+        };
+        let members = self.group.get_all_members();
+        workers.retain(|member_id, _| {
+            members
+                .iter()
+                .any(|m| m.connection.local_socket_id() == *member_id)
+        });
+        for member in members {
+            workers
+                .entry(member.connection.local_socket_id())
+                .or_insert_with(|| MemberQueueWorker::spawn(member));
         }
     }
 
+    /// Enable or disable the single-path bypass
+    ///
+    /// When enabled, packets received while exactly one member is active are
+    /// delivered immediately instead of waiting in the reorder buffer.
+    pub fn set_single_path_bypass(&self, enabled: bool) {
+        self.single_path_bypass.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Check if the single-path bypass is enabled
+    pub fn single_path_bypass(&self) -> bool {
+        self.single_path_bypass.load(Ordering::Relaxed)
+    }
+
+    /// Check if packets are currently bypassing the reorder buffer
+    pub fn is_bypassing(&self) -> bool {
+        self.bypassing.load(Ordering::Relaxed)
+    }
+
     /// Send data on all paths
+    ///
+    /// Queued on each member, for the queue workers or
+    /// `SocketGroup::service_queues` to send.
     pub fn send(&self, data: &[u8]) -> Result<BroadcastSendResult, BroadcastError> {
+        self.sync_queue_workers();
         self.sender.send(data)
     }
 
     /// Process received packet from any member
     pub fn on_receive(&self, packet: DataPacket, member_id: u32) -> Result<bool, BroadcastError> {
-        let result = self.receiver.on_packet_received(packet, member_id);
+        let bypass = self.single_path_bypass() && self.group.active_member_count() == 1;
+        if self.bypassing.swap(bypass, Ordering::Relaxed) != bypass {
+            if bypass {
+                tracing::info!("Single active path: bypassing reorder buffer");
+            } else {
+                tracing::info!("Multiple active paths: reordering re-engaged");
+            }
+        }
+
+        let timestamp = packet.header.timestamp;
+        let retransmitted = packet.msg_number().retransmitted;
+        let result = if bypass {
+            self.receiver.on_packet_bypass(packet)
+        } else {
+            self.receiver.on_packet_received(packet, member_id)
+        };
 
         // Update member stats
+        let mut rtt_us = 0;
         if let Some(member) = self.group.get_member(member_id) {
             if result.is_ok() {
                 member.record_received(1456); // Approximate packet size
             }
+            rtt_us = member.get_stats().rtt_us;
+        }
+
+        // Duplicates count too: they show how far behind a path runs
+        let mut paths = self.paths.write();
+        paths.record_packet(member_id, matches!(result, Ok(true)), rtt_us);
+        if !retransmitted {
+            paths.record_arrival(member_id, timestamp, Instant::now());
         }
 
         result
     }
 
+    /// Build a status report of each member's deliveries for the sender
+    ///
+    /// Members are identified by the sender's socket ID; those not yet
+    /// handshaked are left out.
+    pub fn status_report(&self) -> StatusReport {
+        let paths = self.paths.read();
+        let reported = self
+            .group
+            .get_all_members()
+            .into_iter()
+            .filter_map(|member| {
+                let member_id = member.connection.local_socket_id();
+                let stats = paths.get_stats(member_id);
+                Some(PathStatus {
+                    socket_id: member.connection.remote_socket_id()?,
+                    packets_received: stats.map_or(0, |s| s.packets_received),
+                    packets_first: stats.map_or(0, |s| s.packets_first),
+                    relative_delay_us: paths
+                        .one_way_delay_us(member_id)
+                        .map(|delay| delay.min(u32::MAX as u64 - 1) as u32),
+                })
+            })
+            .take(MAX_REPORTED_PATHS)
+            .collect();
+
+        StatusReport {
+            buffered_packets: self.receiver.stats().buffered_packets as u32,
+            paths: reported,
+        }
+    }
+
     /// Get next ready packet
     pub fn receive(&self) -> Option<DataPacket> {
         self.receiver.pop_ready_packet()
@@ -326,6 +854,8 @@ impl BroadcastBonding {
         BroadcastBondingStats {
             group_stats: self.group.get_stats(),
             receiver_stats: self.receiver.stats(),
+            redundancy: self.sender.redundancy_level(),
+            sends_skipped: self.sender.sends_skipped(),
         }
     }
 }
@@ -337,13 +867,19 @@ pub struct BroadcastBondingStats {
     pub group_stats: crate::group::GroupStats,
     /// Receiver statistics
     pub receiver_stats: BroadcastReceiverStats,
+    /// Current duplication (full broadcast unless adaptive redundancy
+    /// thinned it)
+    pub redundancy: RedundancyLevel,
+    /// Member sends left out by adaptive redundancy
+    pub sends_skipped: u64,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::group::GroupType;
-    use srt_protocol::Connection;
+    use crate::group::{GroupType, MemberStatus};
+    use srt_protocol::packet::ControlType;
+    use srt_protocol::{ControlPacket, DropReqInfo, MsgNumber};
 
     fn create_test_group() -> Arc<SocketGroup> {
         Arc::new(SocketGroup::new(1, GroupType::Broadcast, 10))
@@ -443,4 +979,539 @@ mod tests {
         let stats = bonding.stats();
         assert_eq!(stats.group_stats.member_count, 2);
     }
+
+    /// A group with two connected, active members
+    fn create_active_group(queue_capacity: usize) -> Arc<SocketGroup> {
+        let mut group = SocketGroup::new(1, GroupType::Broadcast, 10);
+        group.set_member_queue_capacity(queue_capacity);
+        let group = Arc::new(group);
+
+        for id in 1..=2 {
+            let mut conn = Connection::new(
+                id,
+                "127.0.0.1:9000".parse().unwrap(),
+                "127.0.0.1:9001".parse().unwrap(),
+                SeqNumber::new(1000),
+                120,
+            );
+            let handshake = conn.create_handshake();
+            conn.process_handshake(handshake).unwrap();
+            group
+                .add_member(Arc::new(conn), "127.0.0.1:9001".parse().unwrap())
+                .unwrap();
+            group
+                .update_member_status(id, MemberStatus::Active)
+                .unwrap();
+        }
+        group
+    }
+
+    #[test]
+    fn test_broadcast_send_queues_per_member() {
+        let group = create_active_group(2);
+        let sender = BroadcastSender::new(group.clone());
+        assert!(sender.send(b"one").unwrap().failed_members.is_empty());
+        assert!(sender.send(b"two").unwrap().failed_members.is_empty());
+
+        // Third send overflows both queues, dropping the oldest packet
+        let result = sender.send(b"three").unwrap();
+        assert_eq!(result.sent_count, 2);
+        assert_eq!(result.failed_members.len(), 2);
+
+        let stats = group.get_member(1).unwrap().get_stats();
+        assert_eq!(stats.queue_depth, 2);
+        assert_eq!(stats.queue_dropped, 1);
+        assert_eq!(stats.packets_sent, 0);
+
+        // Servicing drains each member independently
+        assert_eq!(group.service_queues(16), 4);
+        let stats = group.get_member(2).unwrap().get_stats();
+        assert_eq!(stats.queue_depth, 0);
+        assert_eq!(stats.packets_sent, 2);
+    }
+
+    #[test]
+    fn test_queue_workers_drain_members() {
+        let group = create_active_group(16);
+        let bonding = BroadcastBonding::new(group.clone());
+        bonding.spawn_queue_workers();
+        assert!(bonding.has_queue_workers());
+
+        let sent = |member_id: u32| {
+            group
+                .get_member(member_id)
+                .unwrap()
+                .get_stats()
+                .packets_sent
+        };
+        let wait_for = |member_id: u32, packets: u64| {
+            for _ in 0..200 {
+                if sent(member_id) == packets {
+                    break;
+                }
+                std::thread::sleep(std::time::Duration::from_millis(5));
+            }
+            assert_eq!(sent(member_id), packets);
+        };
+        for i in 0..10u8 {
+            bonding.send(&[i]).unwrap();
+        }
+        wait_for(1, 10);
+        wait_for(2, 10);
+
+        // A member added later gets a worker with the next send
+        let mut conn = Connection::new(
+            3,
+            "127.0.0.1:9000".parse().unwrap(),
+            "127.0.0.1:9001".parse().unwrap(),
+            SeqNumber::new(1000),
+            120,
+        );
+        let handshake = conn.create_handshake();
+        conn.process_handshake(handshake).unwrap();
+        group
+            .add_member(Arc::new(conn), "127.0.0.1:9003".parse().unwrap())
+            .unwrap();
+        group.update_member_status(3, MemberStatus::Active).unwrap();
+        bonding.send(b"late").unwrap();
+        wait_for(3, 1);
+
+        bonding.shutdown_queue_workers();
+        assert!(!bonding.has_queue_workers());
+        assert_eq!(sent(1), 11);
+        assert!(group.get_member(1).unwrap().queue.is_empty());
+    }
+
+    #[test]
+    fn test_broadcast_send_shares_payload() {
+        let group = create_active_group(16);
+        let sender = BroadcastSender::new(group.clone());
+        sender.send(b"payload").unwrap();
+        assert_eq!(group.service_queues(16), 2);
+
+        let packets: Vec<_> = [1, 2]
+            .iter()
+            .map(|id| group.get_member(*id).unwrap().connection.packets_to_send())
+            .collect();
+        assert_eq!(
+            packets[0][0].payload.as_ptr(),
+            packets[1][0].payload.as_ptr()
+        );
+
+        // What goes on the wire is a full packet the receiver can parse
+        for member_packets in &packets {
+            let wire = member_packets[0].to_bytes();
+            let parsed = DataPacket::from_bytes(&wire).unwrap();
+            assert_eq!(parsed.payload.as_ref(), b"payload");
+        }
+    }
+
+    #[test]
+    fn test_adaptive_redundancy_thins_secondary() {
+        let group = create_active_group(64);
+        let sender = BroadcastSender::new(group.clone());
+        sender.set_adaptive_redundancy(RedundancyConfig {
+            max_interval: 4,
+            clean_period: std::time::Duration::ZERO,
+            ..RedundancyConfig::default()
+        });
+        let report = |packets: u64| StatusReport {
+            buffered_packets: 0,
+            paths: vec![
+                PathStatus {
+                    socket_id: 1,
+                    packets_received: packets,
+                    packets_first: packets,
+                    relative_delay_us: Some(0),
+                },
+                PathStatus {
+                    socket_id: 2,
+                    packets_received: packets,
+                    packets_first: 0,
+                    relative_delay_us: Some(5000),
+                },
+            ],
+        };
+
+        // Member 1 delivers everything first, without loss
+        assert!(sender.update_redundancy().is_none());
+        group.apply_status_report(&report(100));
+        assert!(sender.update_redundancy().is_none());
+        group.apply_status_report(&report(200));
+        let level = sender.update_redundancy().unwrap();
+        assert_eq!(level.primary, Some(1));
+        assert_eq!(level.duplicate_interval, 2);
+
+        let results: Vec<_> = (0..4u8).map(|i| sender.send(&[i]).unwrap()).collect();
+        assert_eq!(results.iter().map(|r| r.sent_count).sum::<usize>(), 6);
+        assert_eq!(results.iter().map(|r| r.skipped_count).sum::<usize>(), 2);
+        assert_eq!(sender.sends_skipped(), 2);
+        group.service_queues(16);
+
+        // Member 2 carries every other packet under the same sequence
+        // numbers, and tells the receiver about the rest
+        let primary = group.get_member(1).unwrap().connection.packets_to_send();
+        let member = group.get_member(2).unwrap();
+        let secondary = member.connection.packets_to_send();
+        assert_eq!(primary.len(), 4);
+        assert_eq!(secondary.len(), 2);
+        for packet in &secondary {
+            let same = primary
+                .iter()
+                .find(|p| p.seq_number() == packet.seq_number())
+                .unwrap();
+            assert_eq!(same.payload, packet.payload);
+        }
+        assert_eq!(member.connection.create_drop_requests().len(), 2);
+        assert_eq!(member.get_stats().packets_skipped, 2);
+
+        // Without its primary the group broadcasts in full again
+        group.update_member_status(1, MemberStatus::Broken).unwrap();
+        assert_eq!(sender.send(b"x").unwrap().skipped_count, 0);
+        sender.clear_adaptive_redundancy();
+        assert_eq!(sender.redundancy_level(), RedundancyLevel::FULL);
+    }
+
+    #[test]
+    fn test_broadcast_receiver_resync() {
+        let group = create_test_group();
+        let receiver = BroadcastReceiver::new(1024);
+
+        // Sender is well ahead; the (restarted) receiver still expects 0
+        for _ in 0..10_000 {
+            group.next_sequence();
+        }
+        let packets: Vec<_> = (0..3)
+            .map(|_| {
+                let seq = group.next_sequence();
+                DataPacket::new(seq, MsgNumber::new(1), 0, 0, Bytes::from("x"))
+            })
+            .collect();
+
+        for packet in &packets {
+            receiver.on_packet_received(packet.clone(), 1).unwrap();
+        }
+        assert_eq!(receiver.ready_packet_count(), 0); // Stalled
+
+        // Receiver reports, sender detects drift and announces
+        let ResyncMessage::Report(expected) = receiver.resync_report() else {
+            panic!("expected a report");
+        };
+        assert!(group.on_resync_report(SeqNumber::new(10_003)).is_none());
+        let announce = group.on_resync_report(expected).unwrap();
+        assert_eq!(announce, ResyncMessage::Announce(SeqNumber::new(10_003)));
+
+        // Fast-forwarding to the oldest buffered packet delivers the backlog
+        assert!(receiver.on_resync(ResyncMessage::Announce(SeqNumber::new(10_000))));
+        assert_eq!(receiver.ready_packet_count(), 3);
+        assert_eq!(receiver.stats().next_expected, SeqNumber::new(10_003));
+    }
+
+    #[test]
+    fn test_broadcast_receiver_skip_to_buffered() {
+        let receiver = BroadcastReceiver::new(1024);
+        assert_eq!(receiver.skip_to_buffered(), None);
+
+        // 0-2 never arrive
+        for seq in [5, 3, 6] {
+            let packet = DataPacket::new(
+                SeqNumber::new(seq),
+                MsgNumber::new(1),
+                0,
+                0,
+                Bytes::from("x"),
+            );
+            receiver.on_packet_received(packet, 1).unwrap();
+        }
+        assert_eq!(receiver.ready_packet_count(), 0);
+
+        assert_eq!(receiver.skip_to_buffered(), Some(SeqNumber::new(3)));
+        assert_eq!(receiver.ready_packet_count(), 1);
+        assert_eq!(receiver.skip_to_buffered(), Some(SeqNumber::new(5)));
+        assert_eq!(receiver.ready_packet_count(), 3);
+        assert_eq!(receiver.stats().next_expected, SeqNumber::new(7));
+    }
+
+    #[test]
+    fn test_broadcast_receiver_drain() {
+        let receiver = BroadcastReceiver::new(1024);
+        for seq in [0, 1, 4, 2, 7] {
+            let packet = DataPacket::new(
+                SeqNumber::new(seq),
+                MsgNumber::new(seq),
+                0,
+                0,
+                Bytes::from("x"),
+            );
+            receiver.on_packet_received(packet, 1).unwrap();
+        }
+        assert_eq!(receiver.ready_packet_count(), 3);
+
+        let drain = receiver.drain();
+        let seqs: Vec<u32> = drain
+            .packets
+            .iter()
+            .map(|p| p.seq_number().as_raw())
+            .collect();
+        assert_eq!(seqs, vec![0, 1, 2, 4, 7]);
+        assert_eq!(
+            drain.gaps,
+            vec![
+                LossRange::single(SeqNumber::new(3)),
+                LossRange::new(SeqNumber::new(5), SeqNumber::new(6)),
+            ]
+        );
+
+        let stats = receiver.stats();
+        assert_eq!(stats.buffered_packets, 0);
+        assert_eq!(stats.ready_packets, 0);
+        assert_eq!(stats.next_expected, SeqNumber::new(8));
+    }
+
+    fn seq_packet(seq: u32) -> DataPacket {
+        DataPacket::new(
+            SeqNumber::new(seq),
+            MsgNumber::new(seq),
+            0,
+            0,
+            Bytes::from("x"),
+        )
+    }
+
+    fn ready_seqs(receiver: &BroadcastReceiver) -> Vec<u32> {
+        std::iter::from_fn(|| receiver.pop_ready_packet())
+            .map(|p| p.seq_number().as_raw())
+            .collect()
+    }
+
+    #[test]
+    fn test_broadcast_receiver_bypass() {
+        let receiver = BroadcastReceiver::new(1024);
+        receiver.on_packet_received(seq_packet(0), 1).unwrap();
+        receiver.on_packet_received(seq_packet(2), 1).unwrap();
+        assert_eq!(ready_seqs(&receiver), vec![0]);
+
+        // Buffered packets are flushed ahead of the bypassed one
+        receiver.on_packet_bypass(seq_packet(4)).unwrap();
+        assert_eq!(ready_seqs(&receiver), vec![2, 4]);
+
+        // Late packets are not delivered out of order
+        assert!(matches!(
+            receiver.on_packet_bypass(seq_packet(3)),
+            Err(BroadcastError::DuplicatePacket)
+        ));
+        receiver.on_packet_bypass(seq_packet(5)).unwrap();
+        assert_eq!(ready_seqs(&receiver), vec![5]);
+
+        let stats = receiver.stats();
+        assert_eq!(stats.buffered_packets, 0);
+        assert_eq!(stats.next_expected, SeqNumber::new(6));
+        assert_eq!(stats.bypassed_packets, 2);
+        assert_eq!(stats.skipped_packets, 2);
+    }
+
+    #[test]
+    fn test_broadcast_receiver_window() {
+        let receiver = BroadcastReceiver::new(4);
+
+        // Buffered packets may span fewer than four sequence numbers
+        assert!(receiver.on_packet_received(seq_packet(1), 1).unwrap());
+        assert!(receiver.on_packet_received(seq_packet(4), 1).unwrap());
+        assert!(!receiver.on_packet_received(seq_packet(5), 1).unwrap());
+        assert!(matches!(
+            receiver.on_packet_received(seq_packet(4), 2),
+            Err(BroadcastError::DuplicatePacket)
+        ));
+        assert_eq!(receiver.stats().buffered_packets, 2);
+
+        // Filling the gap frees the window
+        receiver.on_packet_received(seq_packet(0), 1).unwrap();
+        assert_eq!(ready_seqs(&receiver), vec![0, 1]);
+        receiver.on_packet_received(seq_packet(5), 1).unwrap();
+        receiver.on_packet_received(seq_packet(3), 1).unwrap();
+        receiver.on_packet_received(seq_packet(2), 1).unwrap();
+        assert_eq!(ready_seqs(&receiver), vec![2, 3, 4, 5]);
+        assert_eq!(receiver.stats().buffered_packets, 0);
+    }
+
+    #[test]
+    fn test_broadcast_receiver_drop_request() {
+        let receiver = BroadcastReceiver::new(64);
+        for seq in [0, 4, 6] {
+            receiver.on_packet_received(seq_packet(seq), 1).unwrap();
+        }
+        assert_eq!(ready_seqs(&receiver), vec![0]);
+
+        // The sender gave up on 1-3: 4 follows at once, 5 is still awaited
+        let range = |start, end| LossRange::new(SeqNumber::new(start), SeqNumber::new(end));
+        assert!(receiver.on_drop_request(range(1, 3)));
+        assert_eq!(ready_seqs(&receiver), vec![4]);
+        assert!(!receiver.on_drop_request(range(1, 3)));
+
+        // Another path still delivers part of a range dropped ahead
+        assert!(receiver.on_drop_request(range(7, 9)));
+        receiver.on_packet_received(seq_packet(8), 2).unwrap();
+        receiver.on_packet_received(seq_packet(5), 2).unwrap();
+        assert_eq!(ready_seqs(&receiver), vec![5, 6, 8]);
+
+        let stats = receiver.stats();
+        assert_eq!(stats.next_expected, SeqNumber::new(10));
+        assert_eq!(stats.dropped_ranges, 2);
+        assert_eq!(stats.dropped_packets, 5);
+
+        // Ranges reach the receiver through the member connection
+        let member = create_test_connection(1);
+        assert_eq!(receiver.take_member_drops(&member), 0);
+        let drop = ControlPacket::new(
+            ControlType::DropReq,
+            0,
+            0,
+            0,
+            1,
+            DropReqInfo::new(range(10, 11)).to_bytes(),
+        );
+        member.process_control(&drop).unwrap();
+        receiver.on_packet_received(seq_packet(12), 1).unwrap();
+        assert_eq!(receiver.take_member_drops(&member), 1);
+        assert_eq!(ready_seqs(&receiver), vec![12]);
+    }
+
+    #[test]
+    fn test_broadcast_receiver_wraparound() {
+        let receiver = BroadcastReceiver::new(1024);
+        let start = SeqNumber::new(srt_protocol::sequence::MAX_SEQ_NUMBER - 1);
+        receiver.fast_forward(start);
+
+        let seqs: Vec<SeqNumber> = (0..5).map(|i| start + i).collect();
+        for &seq in seqs.iter().rev() {
+            receiver
+                .on_packet_received(seq_packet(seq.as_raw()), 1)
+                .unwrap();
+        }
+        let delivered: Vec<u32> = ready_seqs(&receiver);
+        let expected: Vec<u32> = seqs.iter().map(|s| s.as_raw()).collect();
+        assert_eq!(delivered, expected);
+        assert_eq!(receiver.stats().next_expected, start + 5);
+    }
+
+    #[test]
+    fn test_bonding_single_path_bypass() {
+        let group = create_test_group();
+        let bonding = BroadcastBonding::new(group.clone());
+        bonding.set_single_path_bypass(true);
+
+        let add_member = |id: u32| {
+            let mut conn = Connection::new(
+                id,
+                "127.0.0.1:9000".parse().unwrap(),
+                "127.0.0.1:9001".parse().unwrap(),
+                SeqNumber::new(0),
+                120,
+            );
+            let handshake = conn.create_handshake();
+            conn.process_handshake(handshake).unwrap();
+            group
+                .add_member(Arc::new(conn), "127.0.0.1:9001".parse().unwrap())
+                .unwrap();
+            group
+                .update_member_status(id, MemberStatus::Active)
+                .unwrap();
+        };
+
+        // One path: a gap does not hold back later packets
+        add_member(1);
+        bonding.on_receive(seq_packet(0), 1).unwrap();
+        bonding.on_receive(seq_packet(2), 1).unwrap();
+        assert!(bonding.is_bypassing());
+        assert_eq!(ready_seqs(&bonding.receiver), vec![0, 2]);
+
+        // A second path re-engages reordering
+        add_member(2);
+        bonding.on_receive(seq_packet(4), 2).unwrap();
+        assert!(!bonding.is_bypassing());
+        assert!(ready_seqs(&bonding.receiver).is_empty());
+        bonding.on_receive(seq_packet(3), 1).unwrap();
+        assert_eq!(ready_seqs(&bonding.receiver), vec![3, 4]);
+
+        // Back to one path
+        group.update_member_status(2, MemberStatus::Broken).unwrap();
+        bonding.on_receive(seq_packet(6), 1).unwrap();
+        assert!(bonding.is_bypassing());
+        assert_eq!(ready_seqs(&bonding.receiver), vec![6]);
+        assert_eq!(bonding.receiver.stats().skipped_packets, 2);
+    }
+
+    #[test]
+    fn test_bonding_status_report() {
+        let group = create_test_group();
+        let bonding = BroadcastBonding::new(group.clone());
+        for id in [1, 2, 3] {
+            let mut conn = Connection::new(
+                id,
+                "127.0.0.1:9000".parse().unwrap(),
+                "127.0.0.1:9001".parse().unwrap(),
+                SeqNumber::new(0),
+                120,
+            );
+            // Member 3 never completes its handshake
+            if id != 3 {
+                let handshake = conn.create_handshake();
+                conn.process_handshake(handshake).unwrap();
+            }
+            group
+                .add_member(Arc::new(conn), "127.0.0.1:9001".parse().unwrap())
+                .unwrap();
+        }
+
+        // Member 1 delivers first, member 2 repeats it
+        for seq in 0..4 {
+            bonding.on_receive(seq_packet(seq), 1).unwrap();
+            assert!(bonding.on_receive(seq_packet(seq), 2).is_err());
+        }
+        bonding.on_receive(seq_packet(6), 2).unwrap();
+
+        let report = bonding.status_report();
+        assert_eq!(report.buffered_packets, 1);
+        assert_eq!(report.paths.len(), 2);
+        let path1 = report.path(1).unwrap();
+        assert_eq!((path1.packets_received, path1.packets_first), (4, 4));
+        let path2 = report.path(2).unwrap();
+        assert_eq!((path2.packets_received, path2.packets_first), (5, 1));
+        assert!(path2.relative_delay_us.is_some());
+
+        // The sending group takes the receiver's view per member
+        let sender_group = create_test_group();
+        sender_group
+            .add_member(create_test_connection(2), "127.0.0.1:9001".parse().unwrap())
+            .unwrap();
+        assert_eq!(sender_group.apply_status_report(&report), 1);
+        let stats = sender_group.get_member(2).unwrap().get_stats();
+        assert_eq!(stats.peer_packets_first, 1);
+        assert_eq!(stats.peer_delay_us, path2.relative_delay_us);
+        assert_eq!(sender_group.get_stats().peer_buffered_packets, Some(1));
+    }
+
+    #[test]
+    fn test_retransmissions_skip_delay_estimate() {
+        let bonding = BroadcastBonding::new(create_test_group());
+        bonding.on_receive(seq_packet(0), 1).unwrap();
+        bonding.on_receive(seq_packet(2), 1).unwrap();
+        let offset = || bonding.paths.read().get_stats(1).unwrap().offset_us;
+        let before = offset();
+
+        // Recovered long after it was first sent, with its old timestamp
+        std::thread::sleep(std::time::Duration::from_millis(30));
+        let mut resent = seq_packet(1);
+        resent.header.msg_or_info = MsgNumber {
+            retransmitted: true,
+            ..MsgNumber::new(1)
+        }
+        .to_raw();
+        assert!(bonding.on_receive(resent, 1).unwrap());
+        assert_eq!(offset(), before);
+        assert_eq!(
+            bonding.paths.read().get_stats(1).unwrap().packets_received,
+            3
+        );
+    }
 }
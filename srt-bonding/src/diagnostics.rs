@@ -0,0 +1,106 @@
+//! Diagnostics bundles for bonded groups
+//!
+//! `SocketGroup::diagnostics` extends the per-connection bundle
+//! (`srt_protocol::ConnectionDiagnostics`) with the group's own state and
+//! each member's status, so a single dump describes every path of a
+//! bonded stream. With the `serde` feature `SocketGroup::dump_diagnostics`
+//! renders it as JSON.
+
+use crate::group::{GroupMember, SocketGroup};
+use srt_protocol::{ConnectionDiagnostics, Labels};
+
+/// A member's diagnostics
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct MemberDiagnostics {
+    /// Member ID (socket ID)
+    pub member_id: u32,
+    /// Member address
+    pub address: String,
+    /// Member status
+    pub status: String,
+    /// Weight for load balancing
+    pub weight: f64,
+    /// Packets waiting in the outbound queue
+    pub queued_packets: usize,
+    /// Packets dropped from the outbound queue because it was full
+    pub queue_dropped: u64,
+    /// Number of failures
+    pub failure_count: u32,
+    /// Liveness score from keep-alives (1.0 = answering, 0.0 = gone)
+    pub liveness: f64,
+    /// Member labels, merged with the group's
+    pub labels: Labels,
+    /// The member connection's bundle
+    pub connection: ConnectionDiagnostics,
+}
+
+impl MemberDiagnostics {
+    /// Gather a member's diagnostics
+    pub fn from_member(member: &GroupMember) -> Self {
+        let stats = member.get_stats();
+        MemberDiagnostics {
+            member_id: stats.member_id,
+            address: stats.address.to_string(),
+            status: format!("{:?}", stats.status),
+            weight: member.weight,
+            queued_packets: stats.queue_depth,
+            queue_dropped: stats.queue_dropped,
+            failure_count: stats.failure_count,
+            liveness: stats.liveness,
+            labels: stats.labels,
+            connection: member.connection.diagnostics(),
+        }
+    }
+}
+
+/// A group's diagnostics bundle
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct GroupDiagnostics {
+    /// Crate version that produced the bundle
+    pub version: &'static str,
+    /// Group ID
+    pub group_id: u32,
+    /// Group type
+    pub group_type: String,
+    /// Group labels
+    pub labels: Labels,
+    /// Milliseconds since the group was created
+    pub uptime_ms: u64,
+    /// Last sequence number assigned
+    pub current_sequence: u32,
+    /// Maximum segment size usable on every member
+    pub mss: usize,
+    /// Peers refused by the admission hook
+    pub admission_rejects: u64,
+    /// Packets in the peer's reorder buffer, once it has reported
+    pub peer_buffered_packets: Option<u32>,
+    /// Per-member diagnostics
+    pub members: Vec<MemberDiagnostics>,
+}
+
+impl GroupDiagnostics {
+    /// Gather a group's diagnostics and those of each member
+    pub fn from_group(group: &SocketGroup) -> Self {
+        let stats = group.get_stats();
+        let mut members: Vec<_> = group
+            .get_all_members()
+            .iter()
+            .map(|member| MemberDiagnostics::from_member(member))
+            .collect();
+        members.sort_by_key(|member| member.member_id);
+        GroupDiagnostics {
+            version: env!("CARGO_PKG_VERSION"),
+            group_id: stats.group_id,
+            group_type: format!("{:?}", stats.group_type),
+            labels: stats.labels,
+            uptime_ms: stats.uptime.as_millis() as u64,
+            current_sequence: group.current_sequence().as_raw(),
+            mss: stats.mss,
+            admission_rejects: stats.admission_rejects,
+            peer_buffered_packets: stats.peer_buffered_packets,
+            members,
+        }
+    }
+}
@@ -2,14 +2,27 @@
 //!
 //! Manages groups of SRT connections for bonding multiple network paths.
 
+use crate::diagnostics::GroupDiagnostics;
+use crate::queue::{MemberQueue, Outbound, DEFAULT_MEMBER_QUEUE_CAPACITY};
+use crate::resync::{is_drifted, ResyncMessage, DEFAULT_DRIFT_TOLERANCE};
+use crate::status::{PathStatus, StatusReport};
+use bytes::Bytes;
 use parking_lot::RwLock;
-use srt_protocol::{Connection, SeqNumber};
+use srt_protocol::packet::{max_payload_for_mss, DEFAULT_MSS, MAX_MSS, MIN_MSS};
+use srt_protocol::sequence::MAX_SEQ_RANGE_LEN;
+use srt_protocol::{
+    Connection, ControlPacket, Labels, SeqNumber, SeqRange, StallAction, StallEvent,
+};
 use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use thiserror::Error;
 
+/// How often `SocketGroup::shutdown` checks whether members have drained
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(5);
+
 /// Group errors
 #[derive(Error, Debug)]
 pub enum GroupError {
@@ -25,10 +38,33 @@ pub enum GroupError {
     #[error("Invalid group state")]
     InvalidState,
 
+    #[error("Invalid MSS: {0} (must be between {MIN_MSS} and {MAX_MSS})")]
+    InvalidMss(usize),
+
+    #[error("Payload too large: {size} bytes (max {max})")]
+    PayloadTooLarge { size: usize, max: usize },
+
     #[error("Connection error: {0}")]
     Connection(String),
+
+    #[error("Admission denied for {address}: {reason}")]
+    AdmissionDenied { address: SocketAddr, reason: String },
 }
 
+/// Peer asking to join a group on the receiving side
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdmissionRequest {
+    /// Peer address
+    pub address: SocketAddr,
+    /// Peer's socket ID from the handshake
+    pub socket_id: Option<u32>,
+    /// Stream ID (access token) from the handshake
+    pub stream_id: Option<String>,
+}
+
+/// Admission hook: return `Err(reason)` to refuse the peer
+pub type AdmissionHook = Arc<dyn Fn(&AdmissionRequest) -> Result<(), String> + Send + Sync>;
+
 /// Group type/mode
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum GroupType {
@@ -64,20 +100,42 @@ pub struct MemberStats {
     pub status: MemberStatus,
     /// Packets sent on this member
     pub packets_sent: u64,
+    /// Group packets left off this member (adaptive broadcast redundancy)
+    pub packets_skipped: u64,
     /// Packets received on this member
     pub packets_received: u64,
     /// Bytes sent
     pub bytes_sent: u64,
     /// Bytes received
     pub bytes_received: u64,
-    /// Estimated RTT (microseconds)
+    /// Estimated RTT (microseconds), measured by keep-alive echoes once
+    /// the peer answers them
     pub rtt_us: u32,
     /// Estimated bandwidth (bytes per second)
     pub bandwidth_bps: u64,
-    /// Last activity timestamp
+    /// Maximum segment size negotiated on this member
+    pub mss: usize,
+    /// Last activity timestamp (sent, received or heard from the peer)
     pub last_activity: Instant,
     /// Number of failures
     pub failure_count: u32,
+    /// Keep-alives sent in a row without hearing from the peer
+    pub missed_keepalives: u32,
+    /// Liveness score from keep-alives (1.0 = answering, 0.0 = gone)
+    pub liveness: f64,
+    /// Packets waiting in the outbound queue
+    pub queue_depth: usize,
+    /// Packets dropped from the outbound queue because it was full
+    pub queue_dropped: u64,
+    /// Packets the peer received on this member, from its status reports
+    pub peer_packets_received: u64,
+    /// Packets this member delivered to the peer before any other member
+    pub peer_packets_first: u64,
+    /// One-way delay above the peer's fastest member (microseconds), once
+    /// reported
+    pub peer_delay_us: Option<u32>,
+    /// Group, connection and member labels combined
+    pub labels: Labels,
 }
 
 impl MemberStats {
@@ -87,13 +145,23 @@ impl MemberStats {
             address,
             status: MemberStatus::Pending,
             packets_sent: 0,
+            packets_skipped: 0,
             packets_received: 0,
             bytes_sent: 0,
             bytes_received: 0,
             rtt_us: 0,
             bandwidth_bps: 0,
+            mss: 0,
             last_activity: Instant::now(),
             failure_count: 0,
+            missed_keepalives: 0,
+            liveness: 1.0,
+            queue_depth: 0,
+            queue_dropped: 0,
+            peer_packets_received: 0,
+            peer_packets_first: 0,
+            peer_delay_us: None,
+            labels: Labels::new(),
         }
     }
 }
@@ -106,29 +174,85 @@ pub struct GroupMember {
     pub stats: Arc<RwLock<MemberStats>>,
     /// Weight for load balancing (0.0 to 1.0)
     pub weight: f64,
+    /// Outbound packet queue
+    pub queue: MemberQueue,
+    /// Labels of the owning group
+    group_labels: Arc<RwLock<Labels>>,
+    /// Labels specific to this member (e.g. the path name)
+    labels: RwLock<Labels>,
 }
 
 impl GroupMember {
-    fn new(connection: Arc<Connection>, member_id: u32, address: SocketAddr) -> Self {
+    fn new(
+        connection: Arc<Connection>,
+        member_id: u32,
+        address: SocketAddr,
+        queue_capacity: usize,
+        group_labels: Arc<RwLock<Labels>>,
+    ) -> Self {
         GroupMember {
             connection,
             stats: Arc::new(RwLock::new(MemberStats::new(member_id, address))),
             weight: 1.0,
+            queue: MemberQueue::new(queue_capacity),
+            group_labels,
+            labels: RwLock::new(Labels::new()),
         }
     }
 
+    /// Get the member's labels
+    ///
+    /// Group labels, overridden by the connection's labels, overridden by
+    /// labels set on the member.
+    pub fn labels(&self) -> Labels {
+        self.group_labels
+            .read()
+            .merged(self.connection.labels())
+            .merged(&self.labels.read())
+    }
+
+    /// Set labels specific to this member
+    pub fn set_labels(&self, labels: Labels) {
+        *self.labels.write() = labels;
+    }
+
+    /// Create a tracing span carrying this member's labels
+    pub fn span(&self) -> tracing::Span {
+        tracing::info_span!(
+            "srt_member",
+            member_id = self.connection.local_socket_id(),
+            labels = %self.labels()
+        )
+    }
+
     /// Check if member is active
     pub fn is_active(&self) -> bool {
         self.stats.read().status == MemberStatus::Active && self.connection.is_connected()
     }
 
+    /// Check if member is broken
+    pub fn is_broken(&self) -> bool {
+        self.stats.read().status == MemberStatus::Broken
+    }
+
+    /// Packets queued for or sent on this member but not yet acknowledged
+    pub fn undelivered_packets(&self) -> u64 {
+        self.queue.len() as u64 + self.connection.stats().send_buffer_packets
+    }
+
     /// Update member status
+    ///
+    /// Changes are recorded in the member connection's diagnostics.
     pub fn set_status(&self, status: MemberStatus) {
-        self.stats.write().status = status;
+        let previous = std::mem::replace(&mut self.stats.write().status, status);
+        if previous != status {
+            self.connection
+                .record_event(format!("Member status {:?} -> {:?}", previous, status));
+        }
     }
 
     /// Record packet sent
-    pub fn record_sent(&self, bytes: usize) {
+    pub(crate) fn record_sent(&self, bytes: usize) {
         let mut stats = self.stats.write();
         stats.packets_sent += 1;
         stats.bytes_sent += bytes as u64;
@@ -136,7 +260,7 @@ impl GroupMember {
     }
 
     /// Record packet received
-    pub fn record_received(&self, bytes: usize) {
+    pub(crate) fn record_received(&self, bytes: usize) {
         let mut stats = self.stats.write();
         stats.packets_received += 1;
         stats.bytes_received += bytes as u64;
@@ -153,9 +277,109 @@ impl GroupMember {
         self.stats.write().bandwidth_bps = bps;
     }
 
+    /// Record the peer's view of this member from a status report
+    pub fn apply_path_status(&self, status: &PathStatus) {
+        let mut stats = self.stats.write();
+        stats.peer_packets_received = status.packets_received;
+        stats.peer_packets_first = status.packets_first;
+        stats.peer_delay_us = status.relative_delay_us;
+    }
+
     /// Get member statistics
+    ///
+    /// The connection's keep-alive liveness fills in the RTT, last activity
+    /// and missed keep-alives, so health checks see the link without
+    /// manual `update_rtt` calls.
     pub fn get_stats(&self) -> MemberStats {
-        self.stats.read().clone()
+        let queue_stats = self.queue.stats();
+        let liveness = self.connection.liveness();
+        let stats = self.stats.read().clone();
+        MemberStats {
+            queue_depth: queue_stats.depth,
+            queue_dropped: queue_stats.dropped,
+            mss: self.connection.mss(),
+            labels: self.labels(),
+            rtt_us: liveness.rtt_us.unwrap_or(stats.rtt_us),
+            last_activity: liveness
+                .last_heard
+                .map_or(stats.last_activity, |heard| heard.max(stats.last_activity)),
+            missed_keepalives: liveness.missed_keepalives,
+            liveness: liveness.score,
+            ..stats
+        }
+    }
+
+    /// Queue data for sending on this member
+    ///
+    /// Returns false if the oldest queued packet was dropped to make room.
+    pub fn enqueue(&self, data: Bytes) -> bool {
+        self.queue.push(data)
+    }
+
+    /// Queue a group packet this member leaves unsent
+    ///
+    /// Returns false if the oldest queued packet was dropped to make room.
+    pub fn enqueue_skip(&self) -> bool {
+        self.queue.push_skip()
+    }
+
+    /// Send a queued entry, skipping its sequence number if it is a skip
+    pub fn send_outbound(&self, entry: Outbound) -> bool {
+        match entry {
+            Outbound::Data(data) => self.send_bytes(data),
+            Outbound::Skip => {
+                let skipped = self.connection.skip_sequence().is_ok();
+                if skipped {
+                    self.stats.write().packets_skipped += 1;
+                }
+                skipped
+            }
+        }
+    }
+
+    /// Send data on this member's connection immediately
+    ///
+    /// Records failures and marks the member broken after repeated errors.
+    pub fn send_now(&self, data: &[u8]) -> bool {
+        self.send_bytes(Bytes::copy_from_slice(data))
+    }
+
+    /// Send data held in `Bytes` on this member's connection immediately
+    ///
+    /// The packet shares `data`'s allocation, so queued payloads broadcast
+    /// to every member are not copied per member.
+    pub fn send_bytes(&self, data: Bytes) -> bool {
+        let size = data.len();
+        match self.connection.send_bytes(data) {
+            Ok(_) => {
+                self.record_sent(size);
+                true
+            }
+            Err(_) => {
+                let mut stats = self.stats.write();
+                stats.failure_count += 1;
+
+                if stats.failure_count > 3 {
+                    stats.status = MemberStatus::Broken;
+                }
+                false
+            }
+        }
+    }
+
+    /// Send up to `budget` queued packets
+    ///
+    /// Returns the number of packets taken from the queue.
+    pub fn service_queue(&self, budget: usize) -> usize {
+        let mut serviced = 0;
+        while serviced < budget {
+            let Some(entry) = self.queue.pop() else {
+                break;
+            };
+            self.send_outbound(entry);
+            serviced += 1;
+        }
+        serviced
     }
 }
 
@@ -165,16 +389,31 @@ impl GroupMember {
 pub struct SocketGroup {
     /// Group ID
     group_id: u32,
-    /// Group type/mode
-    group_type: GroupType,
+    /// Group type/mode (may change at runtime, see `AdaptiveModeController`)
+    group_type: Arc<RwLock<GroupType>>,
     /// Group members indexed by socket ID
     members: Arc<RwLock<HashMap<u32, Arc<GroupMember>>>>,
     /// Maximum number of members
     max_members: usize,
     /// Next sequence number for group send operations
-    next_seq: Arc<RwLock<SeqNumber>>,
+    ///
+    /// Counts past 31 bits and is masked on read; 2^32 is a multiple of
+    /// the sequence space, so the wrap stays correct.
+    next_seq: Arc<AtomicU32>,
     /// Group creation time
     created_at: Instant,
+    /// Maximum segment size for group sends (bytes, including IP/UDP headers)
+    mss: usize,
+    /// Outbound queue capacity for new members (packets)
+    member_queue_capacity: usize,
+    /// Operator-assigned labels, shared with members
+    labels: Arc<RwLock<Labels>>,
+    /// Hook consulted before admitting a peer as a member
+    admission: Arc<RwLock<Option<AdmissionHook>>>,
+    /// Peers refused by the admission hook
+    admission_rejects: Arc<AtomicU64>,
+    /// Packets in the peer's reorder buffer, from its last status report
+    peer_buffered_packets: Arc<RwLock<Option<u32>>>,
 }
 
 impl SocketGroup {
@@ -182,22 +421,121 @@ impl SocketGroup {
     pub fn new(group_id: u32, group_type: GroupType, max_members: usize) -> Self {
         SocketGroup {
             group_id,
-            group_type,
+            group_type: Arc::new(RwLock::new(group_type)),
             members: Arc::new(RwLock::new(HashMap::new())),
             max_members,
-            next_seq: Arc::new(RwLock::new(SeqNumber::new(0))),
+            next_seq: Arc::new(AtomicU32::new(0)),
             created_at: Instant::now(),
+            mss: DEFAULT_MSS,
+            member_queue_capacity: DEFAULT_MEMBER_QUEUE_CAPACITY,
+            labels: Arc::new(RwLock::new(Labels::new())),
+            admission: Arc::new(RwLock::new(None)),
+            admission_rejects: Arc::new(AtomicU64::new(0)),
+            peer_buffered_packets: Arc::new(RwLock::new(None)),
         }
     }
 
+    /// Set the maximum segment size for the group
+    ///
+    /// Caps the payload size of group sends regardless of what individual
+    /// members negotiated.
+    pub fn set_mss(&mut self, mss: usize) -> Result<(), GroupError> {
+        if !(MIN_MSS..=MAX_MSS).contains(&mss) {
+            return Err(GroupError::InvalidMss(mss));
+        }
+        self.mss = mss;
+        Ok(())
+    }
+
+    /// Get the group's configured maximum segment size
+    pub fn mss(&self) -> usize {
+        self.mss
+    }
+
+    /// Get the maximum segment size usable on every member
+    ///
+    /// This is the minimum of the group cap and each member's negotiated
+    /// MSS, so a packet sized for it fits on any path.
+    pub fn negotiated_mss(&self) -> usize {
+        self.members
+            .read()
+            .values()
+            .map(|m| m.connection.mss())
+            .fold(self.mss, usize::min)
+    }
+
+    /// Get the maximum payload size usable on every member
+    pub fn max_payload_size(&self) -> usize {
+        max_payload_for_mss(self.negotiated_mss())
+    }
+
+    /// Check that a payload fits within the group's maximum payload size
+    pub fn check_payload_size(&self, size: usize) -> Result<(), GroupError> {
+        let max = self.max_payload_size();
+        if size > max {
+            return Err(GroupError::PayloadTooLarge { size, max });
+        }
+        Ok(())
+    }
+
+    /// Set the outbound queue capacity (packets) for members added afterwards
+    pub fn set_member_queue_capacity(&mut self, capacity: usize) {
+        self.member_queue_capacity = capacity.max(1);
+    }
+
+    /// Get the outbound queue capacity for new members
+    pub fn member_queue_capacity(&self) -> usize {
+        self.member_queue_capacity
+    }
+
+    /// Service every member's outbound queue once
+    ///
+    /// For event-loop driven senders; sends up to `budget` packets per member
+    /// and returns the total sent.
+    pub fn service_queues(&self, budget: usize) -> usize {
+        self.get_all_members()
+            .iter()
+            .map(|member| member.service_queue(budget))
+            .sum()
+    }
+
     /// Get group ID
     pub fn group_id(&self) -> u32 {
         self.group_id
     }
 
+    /// Get the group's labels
+    pub fn labels(&self) -> Labels {
+        self.labels.read().clone()
+    }
+
+    /// Set labels attached to the group's stats, events and log spans
+    ///
+    /// Members inherit these labels, including members already added.
+    pub fn set_labels(&self, labels: Labels) {
+        *self.labels.write() = labels;
+    }
+
+    /// Create a tracing span carrying the group's labels
+    pub fn span(&self) -> tracing::Span {
+        tracing::info_span!(
+            "srt_group",
+            group_id = self.group_id,
+            labels = %self.labels.read()
+        )
+    }
+
     /// Get group type
     pub fn group_type(&self) -> GroupType {
-        self.group_type
+        *self.group_type.read()
+    }
+
+    /// Change the group type/mode
+    ///
+    /// Members are kept; callers switch to the bonding strategy matching the
+    /// new mode for subsequent sends.
+    pub fn set_group_type(&self, group_type: GroupType) {
+        *self.group_type.write() = group_type;
     }
 
     /// Add a member to the group
@@ -215,13 +553,78 @@ impl SocketGroup {
         }
 
         let member_id = connection.local_socket_id();
-        let member = Arc::new(GroupMember::new(connection, member_id, address));
+        let member = Arc::new(GroupMember::new(
+            connection,
+            member_id,
+            address,
+            self.member_queue_capacity,
+            self.labels.clone(),
+        ));
 
         members.insert(member_id, member);
 
         Ok(member_id)
     }
 
+    /// Set the hook consulted by `admit_member`
+    ///
+    /// Any address that completes a handshake could otherwise join, so a
+    /// receiving group should vet peers (address, socket ID, stream ID)
+    /// before they contribute packets.
+    pub fn set_admission_hook<F>(&self, hook: F)
+    where
+        F: Fn(&AdmissionRequest) -> Result<(), String> + Send + Sync + 'static,
+    {
+        *self.admission.write() = Some(Arc::new(hook));
+    }
+
+    /// Remove the admission hook, admitting every peer
+    pub fn clear_admission_hook(&self) {
+        *self.admission.write() = None;
+    }
+
+    /// Check a peer against the admission hook
+    ///
+    /// Refusals are counted and logged in the group's span.
+    pub fn check_admission(&self, request: &AdmissionRequest) -> Result<(), GroupError> {
+        let hook = self.admission.read().clone();
+        let Some(hook) = hook else {
+            return Ok(());
+        };
+
+        hook(request).map_err(|reason| {
+            self.admission_rejects.fetch_add(1, Ordering::Relaxed);
+            let _span = self.span().entered();
+            tracing::debug!("Refused member {}: {}", request.address, reason);
+            GroupError::AdmissionDenied {
+                address: request.address,
+                reason,
+            }
+        })
+    }
+
+    /// Add a member after checking it with the admission hook
+    ///
+    /// For the receiving side, once the connection has processed the peer's
+    /// handshake (so its socket ID and stream ID are known).
+    pub fn admit_member(
+        &self,
+        connection: Arc<Connection>,
+        address: SocketAddr,
+    ) -> Result<u32, GroupError> {
+        self.check_admission(&AdmissionRequest {
+            address,
+            socket_id: connection.remote_socket_id(),
+            stream_id: connection.stream_id().map(str::to_string),
+        })?;
+        self.add_member(connection, address)
+    }
+
+    /// Get the number of peers refused by the admission hook
+    pub fn admission_rejects(&self) -> u64 {
+        self.admission_rejects.load(Ordering::Relaxed)
+    }
+
     /// Remove a member from the group
     pub fn remove_member(&self, member_id: u32) -> Result<(), GroupError> {
         let mut members = self.members.write();
@@ -283,10 +686,77 @@ impl SocketGroup {
 
     /// Get next sequence number for group operations
     pub fn next_sequence(&self) -> SeqNumber {
-        let mut seq = self.next_seq.write();
-        let current = *seq;
-        *seq = seq.next();
-        current
+        SeqNumber::new_unchecked(self.next_seq.fetch_add(1, Ordering::Relaxed))
+    }
+
+    /// Reserve `n` consecutive sequence numbers for group operations
+    ///
+    /// One atomic operation however many are taken, so a high-rate sender
+    /// can assign a block of packets at once. The range may wrap.
+    ///
+    /// # Panics
+    /// Panics if `n` exceeds `MAX_SEQ_RANGE_LEN`, before reserving anything
+    pub fn next_sequence_batch(&self, n: u32) -> SeqRange {
+        assert!(
+            n <= MAX_SEQ_RANGE_LEN,
+            "Cannot reserve {} sequence numbers at once",
+            n
+        );
+        let start = self.next_seq.fetch_add(n, Ordering::Relaxed);
+        SeqRange::new(SeqNumber::new_unchecked(start), n)
+    }
+
+    /// Peek at the next sequence number without consuming it
+    pub fn current_sequence(&self) -> SeqNumber {
+        SeqNumber::new_unchecked(self.next_seq.load(Ordering::Relaxed))
+    }
+
+    /// Check a receiver's reported expected sequence for drift
+    pub fn check_drift(&self, receiver_expected: SeqNumber, tolerance: u32) -> bool {
+        is_drifted(self.current_sequence(), receiver_expected, tolerance)
+    }
+
+    /// Start a resync: announce the current group sequence to the receiver
+    ///
+    /// The returned message should be sent on every active member; the
+    /// receiver fast-forwards to the announced sequence.
+    pub fn resync(&self) -> ResyncMessage {
+        let seq = self.current_sequence();
+        let _span = self.span().entered();
+        tracing::warn!(
+            "Group {}: resyncing receiver to sequence {}",
+            self.group_id,
+            seq.as_raw()
+        );
+        ResyncMessage::Announce(seq)
+    }
+
+    /// Handle a receiver's expected-sequence report
+    ///
+    /// Returns the announcement to send if the receiver has drifted.
+    pub fn on_resync_report(&self, receiver_expected: SeqNumber) -> Option<ResyncMessage> {
+        if self.check_drift(receiver_expected, DEFAULT_DRIFT_TOLERANCE) {
+            Some(self.resync())
+        } else {
+            None
+        }
+    }
+
+    /// Apply a status report from the receiving peer
+    ///
+    /// Entries are matched to members by socket ID. Returns the number of
+    /// members updated.
+    pub fn apply_status_report(&self, report: &StatusReport) -> usize {
+        *self.peer_buffered_packets.write() = Some(report.buffered_packets);
+        let members = self.members.read();
+        let mut updated = 0;
+        for status in &report.paths {
+            if let Some(member) = members.get(&status.socket_id) {
+                member.apply_path_status(status);
+                updated += 1;
+            }
+        }
+        updated
     }
 
     /// Get group statistics
@@ -303,19 +773,144 @@ impl SocketGroup {
             .iter()
             .filter(|s| s.status == MemberStatus::Active)
             .count();
+        let mss = member_stats
+            .iter()
+            .map(|s| s.mss)
+            .fold(self.mss, usize::min);
 
         GroupStats {
             group_id: self.group_id,
-            group_type: self.group_type,
+            group_type: self.group_type(),
             member_count: members.len(),
             active_member_count: active_count,
             total_packets_sent: total_sent,
             total_packets_received: total_received,
             total_bytes_sent,
             total_bytes_received,
+            mss,
+            admission_rejects: self.admission_rejects(),
+            peer_buffered_packets: *self.peer_buffered_packets.read(),
             member_stats,
             uptime: self.created_at.elapsed(),
+            labels: self.labels(),
+        }
+    }
+
+    /// Shut the group down, letting each member drain first
+    ///
+    /// Waits up to `timeout` (shared by all members) for every member's
+    /// queued and unacknowledged data to be acknowledged, then closes each
+    /// connection. The ACKs must keep being processed meanwhile (e.g. by
+    /// the receive loop); stop sending before calling. Broken members are
+    /// closed without waiting.
+    ///
+    /// Each result carries the Shutdown control packet to send on that
+    /// member's path, so the peer knows the stream ended.
+    pub fn shutdown(&self, timeout: Duration) -> Vec<MemberShutdown> {
+        let members = self.get_all_members();
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            let pending = members
+                .iter()
+                .any(|m| !m.is_broken() && m.undelivered_packets() > 0);
+            let now = Instant::now();
+            if !pending || now >= deadline {
+                break;
+            }
+            std::thread::sleep(DRAIN_POLL_INTERVAL.min(deadline - now));
+        }
+
+        let mut results: Vec<_> = members
+            .iter()
+            .map(|member| {
+                let member_id = member.connection.local_socket_id();
+                let undelivered = member.undelivered_packets();
+                let drain = if member.is_broken() {
+                    DrainResult::Broken { undelivered }
+                } else if undelivered > 0 {
+                    DrainResult::TimedOut { undelivered }
+                } else {
+                    DrainResult::Drained
+                };
+
+                let shutdown = member.connection.close();
+
+                let _span = member.span().entered();
+                match drain {
+                    DrainResult::Drained => tracing::debug!("Member {} drained", member_id),
+                    _ => tracing::warn!("Member {} closed undrained: {:?}", member_id, drain),
+                }
+                MemberShutdown {
+                    member_id,
+                    address: member.get_stats().address,
+                    drain,
+                    shutdown,
+                }
+            })
+            .collect();
+        results.sort_by_key(|result| result.member_id);
+        results
+    }
+
+    /// Mark members whose peer timed out as broken
+    ///
+    /// Checks each member's connection against its peer idle timeout
+    /// (`Connection::check_timeout`) and returns the IDs of members newly
+    /// marked broken. Call periodically, e.g. when sending keep-alives.
+    pub fn check_timeouts(&self) -> Vec<u32> {
+        let mut timed_out = Vec::new();
+        for (id, member) in self.members.read().iter() {
+            if member.is_broken() {
+                continue;
+            }
+            if let Err(e) = member.connection.check_timeout() {
+                tracing::warn!("Member {} broken: {}", id, e);
+                member.set_status(MemberStatus::Broken);
+                timed_out.push(*id);
+            }
+        }
+        timed_out.sort_unstable();
+        timed_out
+    }
+
+    /// Check each member's delivery for a stall
+    ///
+    /// Runs `Connection::check_stall` on every member that is not broken.
+    /// Members whose stall action is `Failover` or `Close` are marked
+    /// broken, so backup mode switches to another path. Returns the stall
+    /// events, for members that deliver through their own connection.
+    pub fn check_stalls(&self) -> Vec<StallEvent> {
+        let mut stalls = Vec::new();
+        for (id, member) in self.members.read().iter() {
+            if member.is_broken() {
+                continue;
+            }
+            let Some(stall) = member.connection.check_stall() else {
+                continue;
+            };
+            if matches!(stall.action, StallAction::Failover | StallAction::Close) {
+                tracing::warn!(
+                    "Member {} broken: delivery stalled for {:?}",
+                    id,
+                    stall.stalled_for
+                );
+                member.set_status(MemberStatus::Broken);
+            }
+            stalls.push(stall);
         }
+        stalls
+    }
+
+    /// Gather the group's diagnostics and those of each member
+    pub fn diagnostics(&self) -> GroupDiagnostics {
+        GroupDiagnostics::from_group(self)
+    }
+
+    /// Dump the group's diagnostics as a JSON blob for support cases
+    #[cfg(feature = "serde")]
+    pub fn dump_diagnostics(&self) -> String {
+        serde_json::to_string_pretty(&self.diagnostics()).expect("diagnostics serialize to JSON")
     }
 
     /// Health check: remove broken members
@@ -343,6 +938,36 @@ impl SocketGroup {
     }
 }
 
+/// How a member's data fared when the group shut down
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DrainResult {
+    /// Everything sent was acknowledged
+    Drained,
+    /// Packets were still queued or unacknowledged at the timeout
+    TimedOut {
+        /// Packets dropped
+        undelivered: u64,
+    },
+    /// The member was broken; it was not waited for
+    Broken {
+        /// Packets dropped
+        undelivered: u64,
+    },
+}
+
+/// Result of shutting down one member
+#[derive(Debug, Clone)]
+pub struct MemberShutdown {
+    /// Member ID (socket ID)
+    pub member_id: u32,
+    /// Member address
+    pub address: SocketAddr,
+    /// Whether the member drained before closing
+    pub drain: DrainResult,
+    /// Shutdown control packet to send to the member's peer
+    pub shutdown: ControlPacket,
+}
+
 /// Group statistics
 #[derive(Debug, Clone)]
 pub struct GroupStats {
@@ -362,15 +987,24 @@ pub struct GroupStats {
     pub total_bytes_sent: u64,
     /// Total bytes received
     pub total_bytes_received: u64,
+    /// Maximum segment size usable on every member
+    pub mss: usize,
+    /// Peers refused by the admission hook
+    pub admission_rejects: u64,
+    /// Packets in the peer's reorder buffer, once it has reported
+    pub peer_buffered_packets: Option<u32>,
     /// Individual member statistics
     pub member_stats: Vec<MemberStats>,
     /// Group uptime
     pub uptime: std::time::Duration,
+    /// Group labels
+    pub labels: Labels,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use srt_protocol::packet::ControlType;
 
     fn create_test_connection(id: u32) -> Arc<Connection> {
         Arc::new(Connection::new(
@@ -382,6 +1016,19 @@ mod tests {
         ))
     }
 
+    fn create_connected_connection(id: u32) -> Arc<Connection> {
+        let mut conn = Connection::new(
+            id,
+            "127.0.0.1:9000".parse().unwrap(),
+            "127.0.0.1:9001".parse().unwrap(),
+            SeqNumber::new(1000),
+            120,
+        );
+        let handshake = conn.create_handshake();
+        conn.process_handshake(handshake).unwrap();
+        Arc::new(conn)
+    }
+
     #[test]
     fn test_group_creation() {
         let group = SocketGroup::new(1, GroupType::Broadcast, 10);
@@ -404,6 +1051,187 @@ mod tests {
         assert_eq!(group.member_count(), 1);
     }
 
+    #[test]
+    fn test_member_liveness() {
+        let group = SocketGroup::new(1, GroupType::Backup, 10);
+        let conn = create_test_connection(12345);
+        let peer = create_test_connection(54321);
+        conn.set_keepalive_interval(std::time::Duration::ZERO);
+        group
+            .add_member(conn.clone(), "127.0.0.1:9001".parse().unwrap())
+            .unwrap();
+        let member = group.get_member(12345).unwrap();
+        member.update_rtt(80_000);
+        let stats = member.get_stats();
+        assert_eq!(stats.rtt_us, 80_000);
+        assert_eq!(stats.liveness, 1.0);
+
+        for _ in 0..3 {
+            conn.create_keepalive().unwrap();
+        }
+        let stats = member.get_stats();
+        assert_eq!(stats.missed_keepalives, 2);
+        assert_eq!(stats.liveness, 0.5);
+
+        // An echoed keep-alive replaces the manual RTT
+        let request = conn.create_keepalive().unwrap();
+        peer.process_control(&request).unwrap();
+        conn.process_control(&peer.create_keepalive().unwrap())
+            .unwrap();
+        let stats = member.get_stats();
+        assert_eq!(stats.missed_keepalives, 0);
+        assert_eq!(stats.liveness, 1.0);
+        assert!(stats.rtt_us < 80_000);
+        assert_eq!(Some(stats.last_activity), conn.liveness().last_heard);
+    }
+
+    #[test]
+    fn test_timed_out_members_marked_broken() {
+        let group = SocketGroup::new(1, GroupType::Broadcast, 10);
+        for id in [1, 2] {
+            let conn = create_connected_connection(id);
+            group
+                .add_member(conn, "127.0.0.1:9001".parse().unwrap())
+                .unwrap();
+            group
+                .update_member_status(id, MemberStatus::Active)
+                .unwrap();
+        }
+        let silent = group.get_member(2).unwrap();
+        silent
+            .connection
+            .set_peer_idle_timeout(std::time::Duration::from_millis(1));
+        std::thread::sleep(std::time::Duration::from_millis(5));
+
+        assert_eq!(group.check_timeouts(), vec![2]);
+        assert!(silent.is_broken());
+        assert!(!silent.connection.is_connected());
+        assert_eq!(group.active_member_count(), 1);
+        // Reported once
+        assert!(group.check_timeouts().is_empty());
+    }
+
+    #[test]
+    fn test_stalled_members_fail_over() {
+        let group = SocketGroup::new(1, GroupType::Backup, 10);
+        let sender = create_connected_connection(9);
+        for _ in 0..3 {
+            sender.send(b"data").unwrap();
+        }
+        let packets = sender.packets_to_send();
+        for (id, action) in [(1, StallAction::Log), (2, StallAction::Failover)] {
+            let conn = create_connected_connection(id);
+            conn.set_stall_timeout(std::time::Duration::from_millis(1));
+            conn.set_stall_action(action);
+            // The first packet never arrives
+            for packet in &packets[1..] {
+                conn.process_data_packet(packet.clone()).unwrap();
+            }
+            group
+                .add_member(conn, "127.0.0.1:9001".parse().unwrap())
+                .unwrap();
+            group
+                .update_member_status(id, MemberStatus::Active)
+                .unwrap();
+        }
+
+        assert!(group.check_stalls().is_empty());
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        let mut stalled: Vec<_> = group
+            .check_stalls()
+            .iter()
+            .map(|stall| stall.action)
+            .collect();
+        stalled.sort_by_key(|action| action.as_str());
+        assert_eq!(stalled, [StallAction::Failover, StallAction::Log]);
+        assert!(group.get_member(2).unwrap().is_broken());
+        assert_eq!(group.active_member_count(), 1);
+        assert!(group.check_stalls().is_empty());
+    }
+
+    #[test]
+    fn test_sequence_batch() {
+        let group = SocketGroup::new(1, GroupType::Broadcast, 4);
+        assert_eq!(group.next_sequence().as_raw(), 0);
+        let batch = group.next_sequence_batch(3);
+        assert_eq!((batch.start().as_raw(), batch.len()), (1, 3));
+        assert_eq!(group.current_sequence().as_raw(), 4);
+        assert!(group.next_sequence_batch(0).is_empty());
+        assert_eq!(group.next_sequence().as_raw(), 4);
+
+        // Blocks wrap with the sequence space
+        group
+            .next_seq
+            .store(srt_protocol::sequence::MAX_SEQ_NUMBER, Ordering::Relaxed);
+        let seqs: Vec<u32> = group.next_sequence_batch(3).map(|s| s.as_raw()).collect();
+        assert_eq!(seqs, vec![srt_protocol::sequence::MAX_SEQ_NUMBER, 0, 1]);
+        assert_eq!(group.next_sequence().as_raw(), 2);
+    }
+
+    #[test]
+    fn test_sequence_batches_disjoint_across_threads() {
+        let group = Arc::new(SocketGroup::new(1, GroupType::Broadcast, 4));
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let group = group.clone();
+                std::thread::spawn(move || {
+                    (0..100)
+                        .flat_map(|_| group.next_sequence_batch(10))
+                        .map(|s| s.as_raw())
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        let mut seqs: Vec<u32> = handles
+            .into_iter()
+            .flat_map(|h| h.join().unwrap())
+            .collect();
+        seqs.sort_unstable();
+        assert_eq!(seqs, (0..4000).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_admission_hook() {
+        let group = SocketGroup::new(1, GroupType::Broadcast, 10);
+        group.set_admission_hook(|request| match request.stream_id.as_deref() {
+            Some("secret") if request.address.ip().is_loopback() => Ok(()),
+            _ => Err("bad token".to_string()),
+        });
+
+        let mut conn = Connection::new(
+            1,
+            "127.0.0.1:9000".parse().unwrap(),
+            "127.0.0.1:9001".parse().unwrap(),
+            SeqNumber::new(1000),
+            120,
+        );
+        conn.set_stream_id(Some("secret".to_string())).unwrap();
+        let conn = Arc::new(conn);
+        group
+            .admit_member(conn.clone(), "127.0.0.1:9001".parse().unwrap())
+            .unwrap();
+
+        let err = group
+            .admit_member(conn, "192.0.2.1:9001".parse().unwrap())
+            .unwrap_err();
+        assert!(
+            matches!(err, GroupError::AdmissionDenied { ref reason, .. } if reason == "bad token")
+        );
+        let err = group
+            .admit_member(create_test_connection(2), "127.0.0.1:9002".parse().unwrap())
+            .unwrap_err();
+        assert!(matches!(err, GroupError::AdmissionDenied { .. }));
+        assert_eq!(group.member_count(), 1);
+        assert_eq!(group.get_stats().admission_rejects, 2);
+
+        // Without a hook every peer is admitted
+        group.clear_admission_hook();
+        group
+            .admit_member(create_test_connection(2), "192.0.2.1:9001".parse().unwrap())
+            .unwrap();
+        assert_eq!(group.admission_rejects(), 2);
+    }
+
     #[test]
     fn test_remove_member() {
         let group = SocketGroup::new(1, GroupType::Broadcast, 10);
@@ -456,6 +1284,42 @@ mod tests {
         assert_eq!(stats.bytes_received, 1456);
     }
 
+    #[test]
+    fn test_group_max_payload_size() {
+        let mut group = SocketGroup::new(1, GroupType::Broadcast, 10);
+        assert_eq!(group.max_payload_size(), 1456);
+
+        assert!(matches!(group.set_mss(10), Err(GroupError::InvalidMss(10))));
+        group.set_mss(1400).unwrap();
+        assert_eq!(group.max_payload_size(), 1356);
+
+        // A member with a smaller MSS lowers the group limit
+        let mut conn = Connection::new(
+            7,
+            "127.0.0.1:9000".parse().unwrap(),
+            "127.0.0.1:9001".parse().unwrap(),
+            SeqNumber::new(1000),
+            120,
+        );
+        conn.set_mss(1200).unwrap();
+        group
+            .add_member(Arc::new(conn), "127.0.0.1:9001".parse().unwrap())
+            .unwrap();
+
+        assert_eq!(group.negotiated_mss(), 1200);
+        assert_eq!(group.get_stats().mss, 1200);
+        assert_eq!(group.get_stats().member_stats[0].mss, 1200);
+        assert_eq!(group.max_payload_size(), 1156);
+        assert!(group.check_payload_size(1156).is_ok());
+        assert!(matches!(
+            group.check_payload_size(1157),
+            Err(GroupError::PayloadTooLarge {
+                size: 1157,
+                max: 1156
+            })
+        ));
+    }
+
     #[test]
     fn test_group_stats() {
         let group = SocketGroup::new(1, GroupType::Broadcast, 10);
@@ -480,4 +1344,150 @@ mod tests {
         assert_eq!(stats.member_count, 2);
         assert_eq!(stats.total_bytes_sent, 3000);
     }
+
+    #[test]
+    fn test_labels() {
+        let group = SocketGroup::new(1, GroupType::Broadcast, 10);
+        let mut conn = Connection::new(
+            1,
+            "127.0.0.1:9000".parse().unwrap(),
+            "127.0.0.1:9001".parse().unwrap(),
+            SeqNumber::new(0),
+            120,
+        );
+        conn.set_labels("path=cell1,camera=2".parse().unwrap());
+        group
+            .add_member(Arc::new(conn), "127.0.0.1:9001".parse().unwrap())
+            .unwrap();
+
+        // Labels set after members joined still reach them
+        group.set_labels("site=nyc,camera=1".parse().unwrap());
+        let member = group.get_member(1).unwrap();
+        assert_eq!(member.labels().to_string(), "camera=2,path=cell1,site=nyc");
+
+        member.set_labels("path=cell3".parse().unwrap());
+        let stats = group.get_stats();
+        assert_eq!(stats.labels.to_string(), "camera=1,site=nyc");
+        assert_eq!(
+            stats.member_stats[0].labels.to_string(),
+            "camera=2,path=cell3,site=nyc"
+        );
+        assert_eq!(
+            member.connection.stats().labels.to_string(),
+            "camera=2,path=cell1"
+        );
+    }
+
+    #[test]
+    fn test_shutdown_drains_members() {
+        let group = SocketGroup::new(1, GroupType::Broadcast, 10);
+        for id in 1..=3 {
+            group
+                .add_member(
+                    create_connected_connection(id),
+                    "127.0.0.1:9001".parse().unwrap(),
+                )
+                .unwrap();
+        }
+        let acked = group.get_member(1).unwrap();
+        let unacked = group.get_member(2).unwrap();
+        let broken = group.get_member(3).unwrap();
+        for member in [&acked, &unacked, &broken] {
+            for _ in 0..4 {
+                member.connection.send(&[0; 100]).unwrap();
+            }
+        }
+        broken.set_status(MemberStatus::Broken);
+
+        // The peer acknowledges member 1 while the group drains
+        let ack_thread = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            let ack = ControlPacket::new(
+                ControlType::Ack,
+                0,
+                1004,
+                0,
+                1,
+                srt_protocol::AckInfo::new(SeqNumber::new(1004)).to_bytes(),
+            );
+            acked.connection.process_control(&ack).unwrap();
+        });
+
+        let results = group.shutdown(Duration::from_millis(200));
+        ack_thread.join().unwrap();
+
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].drain, DrainResult::Drained);
+        assert_eq!(results[1].drain, DrainResult::TimedOut { undelivered: 4 });
+        assert_eq!(results[2].drain, DrainResult::Broken { undelivered: 4 });
+        for result in &results {
+            assert_eq!(result.shutdown.control_type(), ControlType::Shutdown);
+            let member = group.get_member(result.member_id).unwrap();
+            assert!(!member.connection.is_connected());
+        }
+        assert!(unacked.undelivered_packets() > 0);
+    }
+
+    #[test]
+    fn test_shutdown_idle_group_returns_immediately() {
+        let group = SocketGroup::new(1, GroupType::Backup, 10);
+        group
+            .add_member(create_test_connection(1), "127.0.0.1:9001".parse().unwrap())
+            .unwrap();
+
+        let start = Instant::now();
+        let results = group.shutdown(Duration::from_secs(5));
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert_eq!(results[0].drain, DrainResult::Drained);
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let group = SocketGroup::new(7, GroupType::Backup, 10);
+        group.set_labels(Labels::new().with("site", "nyc").unwrap());
+        for id in [2, 1] {
+            group
+                .add_member(
+                    create_connected_connection(id),
+                    "127.0.0.1:9001".parse().unwrap(),
+                )
+                .unwrap();
+        }
+        group
+            .get_member(2)
+            .unwrap()
+            .set_status(MemberStatus::Broken);
+
+        let diagnostics = group.diagnostics();
+        assert_eq!(diagnostics.group_id, 7);
+        assert_eq!(diagnostics.group_type, "Backup");
+        assert_eq!(diagnostics.labels.get("site"), Some("nyc"));
+        let ids: Vec<_> = diagnostics.members.iter().map(|m| m.member_id).collect();
+        assert_eq!(ids, vec![1, 2]);
+
+        let broken = &diagnostics.members[1];
+        assert_eq!(broken.status, "Broken");
+        assert_eq!(broken.connection.state, "Connected");
+        assert!(broken
+            .connection
+            .events
+            .iter()
+            .any(|e| e.message == "Member status Pending -> Broken"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_dump_diagnostics() {
+        let group = SocketGroup::new(7, GroupType::Broadcast, 10);
+        group
+            .add_member(
+                create_connected_connection(1),
+                "127.0.0.1:9001".parse().unwrap(),
+            )
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_str(&group.dump_diagnostics()).unwrap();
+        assert_eq!(json["group_id"], 7);
+        assert_eq!(json["members"][0]["member_id"], 1);
+        assert_eq!(json["members"][0]["connection"]["state"], "Connected");
+    }
 }
@@ -0,0 +1,265 @@
+//! Multi-Producer Alignment Ingestion
+//!
+//! Lets each member's receive thread hand packets to the alignment buffer
+//! without contending on a shared lock. Producers push into a bounded
+//! lock-free channel; a single consumer owns the `AlignmentBuffer` and does
+//! all ordering and duplicate detection. Socket reads and packet parsing
+//! stay on the receive threads, so they scale with the number of cores.
+
+use crate::alignment::{AlignedPacket, AlignmentBuffer, AlignmentDrain, AlignmentError};
+use crossbeam::channel::{self, Receiver, RecvTimeoutError, Sender, TrySendError};
+use srt_protocol::DataPacket;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Default ingestion channel capacity (packets)
+pub const DEFAULT_INGEST_CAPACITY: usize = 8192;
+
+/// Packet in flight from a receive thread to the consumer
+struct Ingested {
+    packet: DataPacket,
+    member_id: u32,
+    rtt_us: u32,
+}
+
+/// Ingestion statistics
+#[derive(Debug, Clone, Default)]
+pub struct IngestStats {
+    /// Packets handed to the alignment buffer
+    pub ingested: u64,
+    /// Packets rejected by the alignment buffer (too old or buffer full)
+    pub rejected: u64,
+    /// Packets dropped by producers because the channel was full
+    pub dropped: u64,
+}
+
+/// Producer side of an ingestion channel
+///
+/// Cheap to clone; give one to each receive thread.
+#[derive(Clone)]
+pub struct IngestHandle {
+    sender: Sender<Ingested>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl IngestHandle {
+    /// Queue a packet received on `member_id`
+    ///
+    /// Never blocks. Returns false if the channel was full or the consumer
+    /// is gone, in which case the packet is dropped.
+    pub fn push(&self, packet: DataPacket, member_id: u32, rtt_us: u32) -> bool {
+        let ingested = Ingested {
+            packet,
+            member_id,
+            rtt_us,
+        };
+        match self.sender.try_send(ingested) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) | Err(TrySendError::Disconnected(_)) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                false
+            }
+        }
+    }
+}
+
+/// Single-consumer alignment stage fed by any number of producers
+pub struct AlignmentIngest {
+    sender: Sender<Ingested>,
+    receiver: Receiver<Ingested>,
+    buffer: AlignmentBuffer,
+    dropped: Arc<AtomicU64>,
+    ingested: u64,
+    rejected: u64,
+}
+
+impl AlignmentIngest {
+    /// Create an ingestion stage in front of `buffer`
+    ///
+    /// `capacity` bounds the packets queued between producers and the
+    /// consumer.
+    pub fn new(buffer: AlignmentBuffer, capacity: usize) -> Self {
+        let (sender, receiver) = channel::bounded(capacity.max(1));
+        AlignmentIngest {
+            sender,
+            receiver,
+            buffer,
+            dropped: Arc::new(AtomicU64::new(0)),
+            ingested: 0,
+            rejected: 0,
+        }
+    }
+
+    /// Get a producer handle
+    pub fn handle(&self) -> IngestHandle {
+        IngestHandle {
+            sender: self.sender.clone(),
+            dropped: self.dropped.clone(),
+        }
+    }
+
+    /// Move the packets queued so far into the alignment buffer
+    ///
+    /// Packets pushed while this runs are left for the next call, so busy
+    /// producers cannot hold off delivery. Returns the number of packets
+    /// taken off the channel.
+    pub fn process(&mut self) -> usize {
+        let queued = self.receiver.len();
+        let mut count = 0;
+        while count < queued {
+            let Ok(ingested) = self.receiver.try_recv() else {
+                break;
+            };
+            self.align(ingested);
+            count += 1;
+        }
+        count
+    }
+
+    /// Like `process`, but wait up to `timeout` for the first packet
+    pub fn process_timeout(&mut self, timeout: Duration) -> usize {
+        match self.receiver.recv_timeout(timeout) {
+            Ok(ingested) => {
+                self.align(ingested);
+                1 + self.process()
+            }
+            Err(RecvTimeoutError::Timeout) | Err(RecvTimeoutError::Disconnected) => 0,
+        }
+    }
+
+    /// Process queued packets and return those ready for delivery, in order
+    pub fn pop_ready_packets(&mut self) -> Vec<AlignedPacket> {
+        self.process();
+        self.buffer.pop_ready_packets()
+    }
+
+    /// Process queued packets and flush the alignment buffer, skipping gaps
+    pub fn drain(&mut self) -> AlignmentDrain {
+        self.process();
+        self.buffer.drain()
+    }
+
+    /// Get the number of packets waiting on the channel
+    pub fn pending(&self) -> usize {
+        self.receiver.len()
+    }
+
+    /// Get the alignment buffer
+    pub fn buffer(&self) -> &AlignmentBuffer {
+        &self.buffer
+    }
+
+    /// Get the alignment buffer mutably (e.g. to resynchronize)
+    pub fn buffer_mut(&mut self) -> &mut AlignmentBuffer {
+        &mut self.buffer
+    }
+
+    /// Get ingestion statistics
+    pub fn stats(&self) -> IngestStats {
+        IngestStats {
+            ingested: self.ingested,
+            rejected: self.rejected,
+            dropped: self.dropped.load(Ordering::Relaxed),
+        }
+    }
+
+    fn align(&mut self, ingested: Ingested) {
+        match self
+            .buffer
+            .add_packet(ingested.packet, ingested.member_id, ingested.rtt_us)
+        {
+            Ok(_) => self.ingested += 1,
+            Err(AlignmentError::TooOld)
+            | Err(AlignmentError::BufferFull)
+            | Err(AlignmentError::InvalidSequence) => self.rejected += 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use srt_protocol::{MsgNumber, SeqNumber};
+    use std::thread;
+
+    fn create_test_packet(seq: u32) -> DataPacket {
+        DataPacket::new(
+            SeqNumber::new(seq),
+            MsgNumber::new(seq),
+            0,
+            0,
+            Bytes::from(seq.to_be_bytes().to_vec()),
+        )
+    }
+
+    #[test]
+    fn test_parallel_producers_in_order() {
+        const PRODUCERS: u32 = 4;
+        const PACKETS: u32 = 20_000;
+
+        let buffer = AlignmentBuffer::new(PACKETS as usize, Duration::from_secs(10));
+        let mut ingest = AlignmentIngest::new(buffer, PACKETS as usize * 2);
+
+        // Each producer delivers every sequence number it owns, and every
+        // producer also duplicates its neighbour's share
+        let threads: Vec<_> = (0..PRODUCERS)
+            .map(|id| {
+                let handle = ingest.handle();
+                thread::spawn(move || {
+                    for seq in 0..PACKETS {
+                        let owner = seq % PRODUCERS;
+                        if owner == id || owner == (id + 1) % PRODUCERS {
+                            assert!(handle.push(create_test_packet(seq), id, 0));
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        let mut delivered = Vec::new();
+        while delivered.len() < PACKETS as usize {
+            ingest.process_timeout(Duration::from_millis(10));
+            delivered.extend(
+                ingest
+                    .buffer_mut()
+                    .pop_ready_packets()
+                    .into_iter()
+                    .map(|p| p.packet.seq_number().as_raw()),
+            );
+        }
+        for t in threads {
+            t.join().unwrap();
+        }
+        ingest.process();
+
+        assert_eq!(delivered, (0..PACKETS).collect::<Vec<_>>());
+        assert_eq!(ingest.buffer().stats().packets_delivered, PACKETS as u64);
+        let stats = ingest.stats();
+        assert_eq!(stats.dropped, 0);
+        // Duplicates of already-delivered packets are rejected as too old
+        assert_eq!(
+            stats.ingested + stats.rejected,
+            2 * PACKETS as u64,
+            "{:?}",
+            stats
+        );
+    }
+
+    #[test]
+    fn test_full_channel_drops() {
+        let buffer = AlignmentBuffer::new(16, Duration::from_secs(10));
+        let mut ingest = AlignmentIngest::new(buffer, 2);
+        let handle = ingest.handle();
+
+        assert!(handle.push(create_test_packet(0), 1, 0));
+        assert!(handle.push(create_test_packet(1), 1, 0));
+        assert!(!handle.push(create_test_packet(2), 1, 0));
+        assert_eq!(ingest.pending(), 2);
+
+        assert_eq!(ingest.pop_ready_packets().len(), 2);
+        assert_eq!(ingest.stats().dropped, 1);
+        assert_eq!(ingest.stats().ingested, 2);
+    }
+}
@@ -2,28 +2,59 @@
 //!
 //! This crate implements multi-path bonding for SRT, including socket groups,
 //! broadcast mode, backup mode, load balancing, and packet alignment.
+#![deny(unsafe_code)]
 
+pub mod adaptive;
+pub mod advisory;
 pub mod alignment;
 pub mod backup;
 pub mod balancing;
 pub mod broadcast;
+pub mod diagnostics;
 pub mod group;
+pub mod ingest;
+pub mod libsrt;
+pub mod partition;
+pub mod queue;
+pub mod redundancy;
+pub mod resync;
+pub mod status;
+#[cfg(feature = "test-support")]
+pub mod testing;
+pub mod tuning;
 
+pub use adaptive::{AdaptiveConfig, AdaptiveModeController, ModeSwitchEvent, ModeSwitchReason};
+pub use advisory::{AdvisoryConfig, BitrateAdvisor, BitrateAdvisory};
 pub use alignment::{
-    AlignedPacket, AlignmentBuffer, AlignmentError, AlignmentStats, PacketSource, PathStats,
-    PathTracker,
+    AlignedPacket, AlignmentBuffer, AlignmentConfig, AlignmentDrain, AlignmentError,
+    AlignmentStats, ConflictPolicy, PacketSource, PathStats, PathTracker, RedundancyStats,
 };
 pub use backup::{
-    BackupBonding, BackupBondingStats, BackupError, BackupRole, FailoverEvent, FailoverReason,
+    BackupBonding, BackupBondingStats, BackupConfig, BackupError, BackupRole, FailoverEvent,
+    FailoverReason,
 };
 pub use balancing::{
-    BalancingAlgorithm, BalancingError, BalancingSendResult, BalancingStats, LoadBalancer,
-    PathCapacity,
+    BalancerConfig, BalancingAlgorithm, BalancingError, BalancingSendResult, BalancingStats,
+    LoadBalancer, PathCapacity,
 };
 pub use broadcast::{
-    BroadcastBonding, BroadcastBondingStats, BroadcastError, BroadcastReceiver,
+    BroadcastBonding, BroadcastBondingStats, BroadcastDrain, BroadcastError, BroadcastReceiver,
     BroadcastReceiverStats, BroadcastSendResult, BroadcastSender,
 };
+pub use diagnostics::{GroupDiagnostics, MemberDiagnostics};
 pub use group::{
-    GroupError, GroupMember, GroupStats, GroupType, MemberStats, MemberStatus, SocketGroup,
+    AdmissionHook, AdmissionRequest, DrainResult, GroupError, GroupMember, GroupStats, GroupType,
+    MemberShutdown, MemberStats, MemberStatus, SocketGroup,
 };
+pub use ingest::{AlignmentIngest, IngestHandle, IngestStats, DEFAULT_INGEST_CAPACITY};
+pub use libsrt::{LibsrtGroupStats, LibsrtMemberStats};
+pub use partition::{PartitionAnnounce, SequencePartition};
+pub use queue::{
+    MemberQueue, MemberQueueWorker, Outbound, QueueStats, DEFAULT_MEMBER_QUEUE_CAPACITY,
+};
+pub use redundancy::{RedundancyConfig, RedundancyController, RedundancyLevel};
+pub use resync::{is_drifted, ResyncMessage, DEFAULT_DRIFT_TOLERANCE};
+pub use status::{PathStatus, StatusReport, MAX_REPORTED_PATHS};
+#[cfg(feature = "test-support")]
+pub use testing::{Delivery, FakeGroup, FakeLinkStats, MemberEvent, DEFAULT_FAKE_RTT_US};
+pub use tuning::{ConfigChange, ConfigChangeHook};
@@ -0,0 +1,298 @@
+//! Group statistics under libsrt's field names
+//!
+//! Dashboards built for libsrt socket groups read `SRT_TRACEBSTATS`
+//! fields such as `pktRcvDrop`, `pktSndLoss` and `msRTT`. The structs
+//! here carry the same figures as `GroupStats`, `MemberStats` and
+//! `AlignmentStats`, renamed and converted to libsrt's units, so those
+//! dashboards can read our stats unchanged. With the `serde` feature they
+//! serialize under the libsrt names.
+//!
+//! Counters are totals since the connection started, as libsrt reports
+//! them when its stats are never cleared.
+
+use crate::alignment::AlignmentStats;
+use crate::group::{GroupMember, MemberStats, SocketGroup};
+use srt_protocol::ConnectionStats;
+
+/// A group member's stats under libsrt's names
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct LibsrtMemberStats {
+    /// Socket ID (`id`)
+    pub id: u32,
+    /// Packets sent (`pktSent`)
+    #[cfg_attr(feature = "serde", serde(rename = "pktSent"))]
+    pub pkt_sent: u64,
+    /// Packets received (`pktRecv`)
+    #[cfg_attr(feature = "serde", serde(rename = "pktRecv"))]
+    pub pkt_recv: u64,
+    /// Packets the peer reported lost (`pktSndLoss`)
+    #[cfg_attr(feature = "serde", serde(rename = "pktSndLoss"))]
+    pub pkt_snd_loss: u64,
+    /// Packets retransmitted (`pktRetrans`)
+    #[cfg_attr(feature = "serde", serde(rename = "pktRetrans"))]
+    pub pkt_retrans: u64,
+    /// Retransmitted packets received (`pktRcvRetrans`)
+    #[cfg_attr(feature = "serde", serde(rename = "pktRcvRetrans"))]
+    pub pkt_rcv_retrans: u64,
+    /// Packets the packet filter added (`pktSndFilterExtra`)
+    #[cfg_attr(feature = "serde", serde(rename = "pktSndFilterExtra"))]
+    pub pkt_snd_filter_extra: u64,
+    /// Packets of the peer's packet filter received (`pktRcvFilterExtra`)
+    #[cfg_attr(feature = "serde", serde(rename = "pktRcvFilterExtra"))]
+    pub pkt_rcv_filter_extra: u64,
+    /// Lost packets the packet filter rebuilt (`pktRcvFilterSupply`)
+    #[cfg_attr(feature = "serde", serde(rename = "pktRcvFilterSupply"))]
+    pub pkt_rcv_filter_supply: u64,
+    /// ACKs received (`pktRecvACK`)
+    #[cfg_attr(feature = "serde", serde(rename = "pktRecvACK"))]
+    pub pkt_recv_ack: u64,
+    /// Packets dropped from the send buffer unacknowledged (`pktSndDrop`)
+    #[cfg_attr(feature = "serde", serde(rename = "pktSndDrop"))]
+    pub pkt_snd_drop: u64,
+    /// Packets given up on by the receiver (`pktRcvDrop`)
+    #[cfg_attr(feature = "serde", serde(rename = "pktRcvDrop"))]
+    pub pkt_rcv_drop: u64,
+    /// Bytes sent (`byteSent`)
+    #[cfg_attr(feature = "serde", serde(rename = "byteSent"))]
+    pub byte_sent: u64,
+    /// Bytes received (`byteRecv`)
+    #[cfg_attr(feature = "serde", serde(rename = "byteRecv"))]
+    pub byte_recv: u64,
+    /// Round-trip time in milliseconds (`msRTT`)
+    #[cfg_attr(feature = "serde", serde(rename = "msRTT"))]
+    pub ms_rtt: f64,
+    /// Estimated bandwidth in megabits per second (`mbpsBandwidth`)
+    #[cfg_attr(feature = "serde", serde(rename = "mbpsBandwidth"))]
+    pub mbps_bandwidth: f64,
+    /// Maximum segment size (`byteMSS`)
+    #[cfg_attr(feature = "serde", serde(rename = "byteMSS"))]
+    pub byte_mss: usize,
+    /// Packets in the send buffer (`pktSndBuf`)
+    #[cfg_attr(feature = "serde", serde(rename = "pktSndBuf"))]
+    pub pkt_snd_buf: u64,
+    /// Payload bytes in the send buffer (`byteSndBuf`)
+    #[cfg_attr(feature = "serde", serde(rename = "byteSndBuf"))]
+    pub byte_snd_buf: u64,
+    /// Age of the oldest packet in the send buffer in milliseconds
+    /// (`msSndBuf`)
+    #[cfg_attr(feature = "serde", serde(rename = "msSndBuf"))]
+    pub ms_snd_buf: u64,
+}
+
+impl LibsrtMemberStats {
+    /// Combine a member's stats with those of its connection
+    ///
+    /// The RTT is the member's keep-alive RTT, or the connection's ACK RTT
+    /// until keep-alives have measured one.
+    pub fn new(member: &MemberStats, connection: &ConnectionStats) -> Self {
+        let rtt_us = if member.rtt_us > 0 {
+            member.rtt_us
+        } else {
+            connection.rtt_us
+        };
+        LibsrtMemberStats {
+            id: member.member_id,
+            pkt_sent: member.packets_sent,
+            pkt_recv: member.packets_received,
+            pkt_snd_loss: connection.packets_reported_lost,
+            pkt_retrans: connection.packets_retransmitted,
+            pkt_rcv_retrans: connection.packets_received_retransmitted,
+            pkt_snd_filter_extra: connection.packets_filter_extra_sent,
+            pkt_rcv_filter_extra: connection.packets_filter_extra_received,
+            pkt_rcv_filter_supply: connection.packets_filter_supplied,
+            pkt_recv_ack: connection.acks_received,
+            pkt_snd_drop: connection.send_ttl_drops,
+            pkt_rcv_drop: connection.packets_lost,
+            byte_sent: member.bytes_sent,
+            byte_recv: member.bytes_received,
+            ms_rtt: rtt_us as f64 / 1_000.0,
+            mbps_bandwidth: member.bandwidth_bps as f64 * 8.0 / 1_000_000.0,
+            byte_mss: member.mss,
+            pkt_snd_buf: connection.send_buffer_packets,
+            byte_snd_buf: connection.send_buffer_bytes,
+            ms_snd_buf: connection.send_buffer_age.as_millis() as u64,
+        }
+    }
+
+    /// Get a group member's stats
+    pub fn from_member(member: &GroupMember) -> Self {
+        LibsrtMemberStats::new(&member.get_stats(), &member.connection.stats())
+    }
+}
+
+/// A socket group's stats under libsrt's names
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct LibsrtGroupStats {
+    /// Time since the group was created in milliseconds (`msTimeStamp`)
+    #[cfg_attr(feature = "serde", serde(rename = "msTimeStamp"))]
+    pub ms_time_stamp: u64,
+    /// Packets sent across all members (`pktSent`)
+    #[cfg_attr(feature = "serde", serde(rename = "pktSent"))]
+    pub pkt_sent: u64,
+    /// Packets received across all members (`pktRecv`)
+    #[cfg_attr(feature = "serde", serde(rename = "pktRecv"))]
+    pub pkt_recv: u64,
+    /// Packets delivered once each, after duplicates were removed
+    /// (`pktRecvUnique`); 0 without alignment stats
+    #[cfg_attr(feature = "serde", serde(rename = "pktRecvUnique"))]
+    pub pkt_recv_unique: u64,
+    /// Packets the peer reported lost on any member (`pktSndLoss`)
+    #[cfg_attr(feature = "serde", serde(rename = "pktSndLoss"))]
+    pub pkt_snd_loss: u64,
+    /// Packets retransmitted on any member (`pktRetrans`)
+    #[cfg_attr(feature = "serde", serde(rename = "pktRetrans"))]
+    pub pkt_retrans: u64,
+    /// Retransmitted packets received on any member (`pktRcvRetrans`)
+    #[cfg_attr(feature = "serde", serde(rename = "pktRcvRetrans"))]
+    pub pkt_rcv_retrans: u64,
+    /// Packets dropped from member send buffers (`pktSndDrop`)
+    #[cfg_attr(feature = "serde", serde(rename = "pktSndDrop"))]
+    pub pkt_snd_drop: u64,
+    /// Packets the group gave up on (`pktRcvDrop`): expired or dropped by
+    /// the sender; 0 without alignment stats
+    #[cfg_attr(feature = "serde", serde(rename = "pktRcvDrop"))]
+    pub pkt_rcv_drop: u64,
+    /// Bytes sent across all members (`byteSent`)
+    #[cfg_attr(feature = "serde", serde(rename = "byteSent"))]
+    pub byte_sent: u64,
+    /// Bytes received across all members (`byteRecv`)
+    #[cfg_attr(feature = "serde", serde(rename = "byteRecv"))]
+    pub byte_recv: u64,
+    /// Maximum segment size usable on every member (`byteMSS`)
+    #[cfg_attr(feature = "serde", serde(rename = "byteMSS"))]
+    pub byte_mss: usize,
+    /// Per-member stats (`members`)
+    pub members: Vec<LibsrtMemberStats>,
+}
+
+impl LibsrtGroupStats {
+    /// Get a group's stats and those of each member
+    pub fn from_group(group: &SocketGroup) -> Self {
+        let stats = group.get_stats();
+        let members: Vec<_> = group
+            .get_all_members()
+            .iter()
+            .map(|member| LibsrtMemberStats::from_member(member))
+            .collect();
+        LibsrtGroupStats {
+            ms_time_stamp: stats.uptime.as_millis() as u64,
+            pkt_sent: stats.total_packets_sent,
+            pkt_recv: stats.total_packets_received,
+            pkt_recv_unique: 0,
+            pkt_snd_loss: members.iter().map(|m| m.pkt_snd_loss).sum(),
+            pkt_retrans: members.iter().map(|m| m.pkt_retrans).sum(),
+            pkt_rcv_retrans: members.iter().map(|m| m.pkt_rcv_retrans).sum(),
+            pkt_snd_drop: members.iter().map(|m| m.pkt_snd_drop).sum(),
+            pkt_rcv_drop: 0,
+            byte_sent: stats.total_bytes_sent,
+            byte_recv: stats.total_bytes_received,
+            byte_mss: stats.mss,
+            members,
+        }
+    }
+
+    /// Fill in the receive-side counters from the group's alignment
+    /// buffer
+    pub fn with_alignment(mut self, alignment: &AlignmentStats) -> Self {
+        self.pkt_recv_unique = alignment.packets_delivered;
+        self.pkt_rcv_drop = alignment.packets_expired + alignment.dropped_packets;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::group::{GroupType, MemberStatus};
+    use srt_protocol::packet::ControlType;
+    use srt_protocol::{Connection, ControlPacket, LossRange, NakInfo, SeqNumber};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn add_member(group: &SocketGroup, id: u32) -> Arc<GroupMember> {
+        let conn = Arc::new(Connection::new(
+            id,
+            "127.0.0.1:9000".parse().unwrap(),
+            "127.0.0.1:9001".parse().unwrap(),
+            SeqNumber::new(0),
+            120,
+        ));
+        group
+            .add_member(conn, "127.0.0.1:9001".parse().unwrap())
+            .unwrap();
+        group.get_member(id).unwrap()
+    }
+
+    #[test]
+    fn test_member_units() {
+        let group = SocketGroup::new(1, GroupType::Broadcast, 2);
+        let member = add_member(&group, 7);
+        member.update_rtt(12_500);
+        member.update_bandwidth(1_250_000);
+        member.record_sent(1316);
+        let nak = NakInfo::new(vec![LossRange::new(SeqNumber::new(3), SeqNumber::new(5))]);
+        let nak = ControlPacket::new(ControlType::Nak, 0, 0, 0, 7, nak.to_bytes());
+        member.connection.process_control(&nak).unwrap();
+
+        let stats = LibsrtMemberStats::from_member(&member);
+        assert_eq!(stats.id, 7);
+        assert_eq!(stats.pkt_sent, 1);
+        assert_eq!(stats.byte_sent, 1316);
+        assert_eq!(stats.pkt_snd_loss, 3);
+        assert_eq!(stats.ms_rtt, 12.5);
+        assert_eq!(stats.mbps_bandwidth, 10.0);
+    }
+
+    #[test]
+    fn test_group_with_alignment() {
+        let group = SocketGroup::new(1, GroupType::Broadcast, 2);
+        for id in [1, 2] {
+            let member = add_member(&group, id);
+            member.set_status(MemberStatus::Active);
+            member.record_received(100);
+            member.record_received(100);
+        }
+        std::thread::sleep(Duration::from_millis(2));
+
+        let alignment = AlignmentStats {
+            packets_received: 4,
+            packets_delivered: 2,
+            duplicates_detected: 2,
+            packets_expired: 1,
+            dropped_packets: 3,
+            ..Default::default()
+        };
+        let stats = LibsrtGroupStats::from_group(&group);
+        assert_eq!(stats.pkt_recv_unique, 0);
+        let stats = stats.with_alignment(&alignment);
+        assert_eq!(stats.members.len(), 2);
+        assert_eq!(stats.pkt_recv, 4);
+        assert_eq!(stats.byte_recv, 400);
+        assert_eq!(stats.pkt_recv_unique, 2);
+        assert_eq!(stats.pkt_rcv_drop, 4);
+        assert!(stats.ms_time_stamp >= 2);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serialized_names() {
+        let stats = LibsrtGroupStats {
+            pkt_rcv_drop: 4,
+            members: vec![LibsrtMemberStats {
+                id: 7,
+                ms_rtt: 12.5,
+                pkt_recv_ack: 3,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let json = serde_json::to_value(&stats).unwrap();
+        assert_eq!(json["pktRcvDrop"], 4);
+        assert_eq!(json["members"][0]["id"], 7);
+        assert_eq!(json["members"][0]["msRTT"], 12.5);
+        assert_eq!(json["members"][0]["pktRecvACK"], 3);
+        assert!(json.get("pkt_rcv_drop").is_none());
+    }
+}
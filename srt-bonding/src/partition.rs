@@ -0,0 +1,237 @@
+//! Sequence-Space Partitioning for Balancing
+//!
+//! In balancing mode each packet travels on one path, so a gap in the group
+//! sequence does not say which path lost it. With a partition, path `k` of
+//! `N` carries exactly the sequences with `seq % N == k`: the receiver can
+//! attribute every missing sequence to the path that should have delivered
+//! it, and NAK it on that path.
+//!
+//! The sender announces each path's slot on that path as a user-defined
+//! control packet; the subtype is in the type-specific field, the slot in
+//! the additional info and the stride in the body.
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use srt_protocol::packet::ControlType;
+use srt_protocol::{ControlPacket, LossRange, SeqNumber};
+use std::collections::HashMap;
+
+/// Control subtype: sender announces a path's slot in the partition
+pub const PARTITION_ANNOUNCE: u16 = srt_protocol::wire::SUBTYPE_PARTITION_ANNOUNCE;
+
+/// A path's place in the partition, as announced on that path
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PartitionAnnounce {
+    /// Number of paths sharing the sequence space
+    pub stride: u32,
+    /// Remainder of the sequences this path carries
+    pub slot: u32,
+}
+
+impl PartitionAnnounce {
+    /// Build the control packet for this announcement
+    pub fn to_control_packet(&self, timestamp: u32, dest_socket_id: u32) -> ControlPacket {
+        let mut body = BytesMut::with_capacity(4);
+        body.put_u32(self.stride);
+        ControlPacket::new(
+            ControlType::UserDefined,
+            PARTITION_ANNOUNCE,
+            self.slot,
+            timestamp,
+            dest_socket_id,
+            body.freeze(),
+        )
+    }
+
+    /// Parse a control packet, returning None if it is not an announcement
+    pub fn from_control_packet(packet: &ControlPacket) -> Option<Self> {
+        if packet.control_type() != ControlType::UserDefined
+            || packet.header.type_specific_info()? != PARTITION_ANNOUNCE
+        {
+            return None;
+        }
+
+        let mut body: Bytes = packet.control_info.clone();
+        if body.len() < 4 {
+            return None;
+        }
+        let stride = body.get_u32();
+        let slot = packet.header.additional_info()?;
+        (slot < stride).then_some(PartitionAnnounce { stride, slot })
+    }
+}
+
+/// Assignment of interleaved sequence strides to group members
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SequencePartition {
+    /// Member IDs in slot order
+    members: Vec<u32>,
+}
+
+impl SequencePartition {
+    /// Partition the sequence space between `members`, in slot order
+    pub fn new(members: Vec<u32>) -> Self {
+        SequencePartition { members }
+    }
+
+    /// Rebuild a partition from the announcements heard on each member
+    ///
+    /// Returns None until every slot is announced by exactly one member
+    /// and all agree on the stride.
+    pub fn from_announcements(announced: &HashMap<u32, PartitionAnnounce>) -> Option<Self> {
+        let stride = announced.values().next()?.stride;
+        let mut members = vec![None; stride as usize];
+        for (&member_id, announce) in announced {
+            if announce.stride != stride || announce.slot >= stride {
+                return None;
+            }
+            let slot = &mut members[announce.slot as usize];
+            if slot.is_some() {
+                return None;
+            }
+            *slot = Some(member_id);
+        }
+        members
+            .into_iter()
+            .collect::<Option<Vec<_>>>()
+            .map(Self::new)
+    }
+
+    /// Get the number of paths sharing the sequence space
+    pub fn stride(&self) -> u32 {
+        self.members.len() as u32
+    }
+
+    /// Get the member IDs in slot order
+    pub fn members(&self) -> &[u32] {
+        &self.members
+    }
+
+    /// Get the member that carries `seq`
+    pub fn owner(&self, seq: SeqNumber) -> Option<u32> {
+        if self.members.is_empty() {
+            return None;
+        }
+        Some(self.members[(seq.as_raw() % self.stride()) as usize])
+    }
+
+    /// Get the announcement to send on `member_id`
+    pub fn announcement(&self, member_id: u32) -> Option<PartitionAnnounce> {
+        let slot = self.members.iter().position(|&id| id == member_id)?;
+        Some(PartitionAnnounce {
+            stride: self.stride(),
+            slot: slot as u32,
+        })
+    }
+
+    /// Attribute missing sequences to the members that lost them
+    ///
+    /// Returns each member's losses as ranges ready for a NAK, in slot
+    /// order; members with no losses are left out.
+    pub fn attribute(&self, missing: &[SeqNumber]) -> Vec<(u32, Vec<LossRange>)> {
+        let mut losses: Vec<Vec<LossRange>> = vec![Vec::new(); self.members.len()];
+        if self.members.is_empty() {
+            return Vec::new();
+        }
+        for &seq in missing {
+            let ranges = &mut losses[(seq.as_raw() % self.stride()) as usize];
+            match ranges.last_mut() {
+                Some(last) if last.end.next() == seq => last.end = seq,
+                _ => ranges.push(LossRange::single(seq)),
+            }
+        }
+
+        self.members
+            .iter()
+            .zip(losses)
+            .filter(|(_, ranges)| !ranges.is_empty())
+            .map(|(&id, ranges)| (id, ranges))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use srt_protocol::sequence::MAX_SEQ_NUMBER;
+
+    fn seqs(raw: &[u32]) -> Vec<SeqNumber> {
+        raw.iter().map(|&s| SeqNumber::new(s)).collect()
+    }
+
+    #[test]
+    fn test_owner_interleaves() {
+        let partition = SequencePartition::new(vec![10, 20, 30]);
+        let owners: Vec<_> = (0..6)
+            .map(|s| partition.owner(SeqNumber::new(s)).unwrap())
+            .collect();
+        assert_eq!(owners, vec![10, 20, 30, 10, 20, 30]);
+        assert_eq!(
+            partition.announcement(30),
+            Some(PartitionAnnounce { stride: 3, slot: 2 })
+        );
+        assert_eq!(partition.announcement(40), None);
+        assert_eq!(
+            SequencePartition::new(Vec::new()).owner(SeqNumber::new(1)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_attribute_losses() {
+        let partition = SequencePartition::new(vec![10, 20]);
+        let attributed = partition.attribute(&seqs(&[4, 5, 7, 9]));
+        assert_eq!(
+            attributed,
+            vec![
+                (10, vec![LossRange::single(SeqNumber::new(4))]),
+                (
+                    20,
+                    vec![
+                        LossRange::single(SeqNumber::new(5)),
+                        LossRange::single(SeqNumber::new(7)),
+                        LossRange::single(SeqNumber::new(9)),
+                    ]
+                ),
+            ]
+        );
+
+        // One path: consecutive losses form one range, across the wrap too
+        let single = SequencePartition::new(vec![10]);
+        let missing = seqs(&[MAX_SEQ_NUMBER - 1, MAX_SEQ_NUMBER, 0, 1]);
+        assert_eq!(
+            single.attribute(&missing),
+            vec![(
+                10,
+                vec![LossRange::new(
+                    SeqNumber::new(MAX_SEQ_NUMBER - 1),
+                    SeqNumber::new(1)
+                )]
+            )]
+        );
+    }
+
+    #[test]
+    fn test_announcements() {
+        let partition = SequencePartition::new(vec![7, 3, 5]);
+        let mut heard = HashMap::new();
+        for &id in partition.members() {
+            let packet = partition.announcement(id).unwrap().to_control_packet(0, 1);
+            let packet = ControlPacket::from_bytes(&packet.to_bytes()).unwrap();
+            heard.insert(id, PartitionAnnounce::from_control_packet(&packet).unwrap());
+            if heard.len() < 3 {
+                assert_eq!(SequencePartition::from_announcements(&heard), None);
+            }
+        }
+        assert_eq!(
+            SequencePartition::from_announcements(&heard),
+            Some(partition)
+        );
+
+        // Conflicting slots are not trusted
+        heard.insert(9, PartitionAnnounce { stride: 3, slot: 0 });
+        assert_eq!(SequencePartition::from_announcements(&heard), None);
+
+        let bad_slot = PartitionAnnounce { stride: 2, slot: 2 }.to_control_packet(0, 0);
+        assert_eq!(PartitionAnnounce::from_control_packet(&bad_slot), None);
+    }
+}
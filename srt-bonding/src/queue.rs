@@ -0,0 +1,345 @@
+//! Per-Member Outbound Queues
+//!
+//! Decouples group sends from individual paths. Each member has a bounded
+//! queue serviced independently, so a slow path cannot stall the others.
+//! When a queue is full the oldest packet is dropped.
+//!
+//! Besides data, a queue can hold a skip: a group sequence number the
+//! member leaves unsent (see `Connection::skip_sequence`), kept in order
+//! with the data around it.
+
+use crate::group::GroupMember;
+use bytes::Bytes;
+use parking_lot::{Condvar, Mutex};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Default per-member queue capacity (packets)
+pub const DEFAULT_MEMBER_QUEUE_CAPACITY: usize = 1024;
+
+/// Outbound queue statistics
+#[derive(Debug, Clone, Default)]
+pub struct QueueStats {
+    /// Packets currently queued
+    pub depth: usize,
+    /// Highest depth observed
+    pub max_depth: usize,
+    /// Packets enqueued
+    pub enqueued: u64,
+    /// Packets dropped (oldest first) because the queue was full
+    pub dropped: u64,
+}
+
+/// An entry in a member's outbound queue
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outbound {
+    /// Data to send
+    Data(Bytes),
+    /// A sequence number to leave unsent
+    Skip,
+}
+
+struct QueueState {
+    packets: VecDeque<Outbound>,
+    stats: QueueStats,
+}
+
+/// Bounded outbound queue with drop-oldest policy
+pub struct MemberQueue {
+    state: Mutex<QueueState>,
+    not_empty: Condvar,
+    capacity: usize,
+}
+
+impl MemberQueue {
+    /// Create a new queue holding at most `capacity` packets
+    pub fn new(capacity: usize) -> Self {
+        MemberQueue {
+            state: Mutex::new(QueueState {
+                packets: VecDeque::new(),
+                stats: QueueStats::default(),
+            }),
+            not_empty: Condvar::new(),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Enqueue a packet
+    ///
+    /// Returns false if the oldest packet had to be dropped to make room.
+    pub fn push(&self, data: Bytes) -> bool {
+        self.push_entry(Outbound::Data(data))
+    }
+
+    /// Enqueue a skipped sequence number
+    ///
+    /// Returns false if the oldest packet had to be dropped to make room.
+    pub fn push_skip(&self) -> bool {
+        self.push_entry(Outbound::Skip)
+    }
+
+    fn push_entry(&self, entry: Outbound) -> bool {
+        let mut state = self.state.lock();
+
+        let mut accepted_without_drop = true;
+        if state.packets.len() >= self.capacity {
+            state.packets.pop_front();
+            state.stats.dropped += 1;
+            accepted_without_drop = false;
+        }
+
+        state.packets.push_back(entry);
+        state.stats.enqueued += 1;
+        state.stats.max_depth = state.stats.max_depth.max(state.packets.len());
+        drop(state);
+
+        self.not_empty.notify_one();
+        accepted_without_drop
+    }
+
+    /// Dequeue the next packet without waiting
+    pub fn pop(&self) -> Option<Outbound> {
+        self.state.lock().packets.pop_front()
+    }
+
+    /// Dequeue the next packet, waiting up to `timeout` for one to arrive
+    pub fn pop_timeout(&self, timeout: Duration) -> Option<Outbound> {
+        let mut state = self.state.lock();
+        if state.packets.is_empty() {
+            self.not_empty.wait_for(&mut state, timeout);
+        }
+        state.packets.pop_front()
+    }
+
+    /// Discard all queued packets
+    pub fn clear(&self) {
+        self.state.lock().packets.clear();
+    }
+
+    /// Get the number of queued packets
+    pub fn len(&self) -> usize {
+        self.state.lock().packets.len()
+    }
+
+    /// Check if the queue is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the queue capacity
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Get queue statistics
+    pub fn stats(&self) -> QueueStats {
+        let state = self.state.lock();
+        QueueStats {
+            depth: state.packets.len(),
+            ..state.stats.clone()
+        }
+    }
+
+    /// Wake any thread waiting in `pop_timeout`
+    fn wake(&self) {
+        self.not_empty.notify_all();
+    }
+}
+
+/// Worker thread servicing one member's outbound queue
+///
+/// The thread stops when the worker is dropped; `shutdown` also sends
+/// what is still queued.
+pub struct MemberQueueWorker {
+    member: Arc<GroupMember>,
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl MemberQueueWorker {
+    /// Spawn a worker for `member`
+    pub fn spawn(member: Arc<GroupMember>) -> Self {
+        let running = Arc::new(AtomicBool::new(true));
+
+        let thread_member = member.clone();
+        let thread_running = running.clone();
+        let handle = thread::spawn(move || {
+            let _span = thread_member.span().entered();
+            while thread_running.load(Ordering::Relaxed) {
+                if let Some(entry) = thread_member.queue.pop_timeout(Duration::from_millis(10)) {
+                    thread_member.send_outbound(entry);
+                }
+            }
+        });
+
+        MemberQueueWorker {
+            member,
+            running,
+            handle: Some(handle),
+        }
+    }
+
+    /// Get the member this worker services
+    pub fn member(&self) -> &Arc<GroupMember> {
+        &self.member
+    }
+
+    /// Stop the thread, then send the packets still queued
+    ///
+    /// Returns the number of packets flushed. The thread has exited and
+    /// released the member when this returns.
+    pub fn shutdown(mut self) -> usize {
+        self.stop();
+        let mut flushed = 0;
+        while let Some(entry) = self.member.queue.pop() {
+            self.member.send_outbound(entry);
+            flushed += 1;
+        }
+        flushed
+    }
+
+    fn stop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        self.member.queue.wake();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for MemberQueueWorker {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::group::{GroupType, SocketGroup};
+    use srt_protocol::{Connection, SeqNumber};
+
+    #[test]
+    fn test_queue_fifo() {
+        let queue = MemberQueue::new(4);
+        queue.push(Bytes::from_static(b"a"));
+        queue.push(Bytes::from_static(b"b"));
+
+        queue.push_skip();
+        queue.push(Bytes::from_static(b"c"));
+
+        assert_eq!(queue.len(), 4);
+        assert_eq!(queue.pop(), Some(Outbound::Data(Bytes::from_static(b"a"))));
+        assert_eq!(queue.pop(), Some(Outbound::Data(Bytes::from_static(b"b"))));
+        assert_eq!(queue.pop(), Some(Outbound::Skip));
+        assert_eq!(queue.pop(), Some(Outbound::Data(Bytes::from_static(b"c"))));
+        assert!(queue.pop().is_none());
+    }
+
+    #[test]
+    fn test_queue_drop_oldest() {
+        let queue = MemberQueue::new(2);
+        assert!(queue.push(Bytes::from_static(b"1")));
+        assert!(queue.push(Bytes::from_static(b"2")));
+        assert!(!queue.push(Bytes::from_static(b"3")));
+
+        let stats = queue.stats();
+        assert_eq!(stats.depth, 2);
+        assert_eq!(stats.max_depth, 2);
+        assert_eq!(stats.enqueued, 3);
+        assert_eq!(stats.dropped, 1);
+
+        assert_eq!(queue.pop(), Some(Outbound::Data(Bytes::from_static(b"2"))));
+    }
+
+    #[test]
+    fn test_pop_timeout() {
+        let queue = Arc::new(MemberQueue::new(4));
+        assert!(queue.pop_timeout(Duration::from_millis(1)).is_none());
+
+        let producer = queue.clone();
+        let handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            producer.push(Bytes::from_static(b"late"));
+        });
+
+        let mut received = None;
+        for _ in 0..100 {
+            received = queue.pop_timeout(Duration::from_millis(10));
+            if received.is_some() {
+                break;
+            }
+        }
+        handle.join().unwrap();
+        assert_eq!(received, Some(Outbound::Data(Bytes::from_static(b"late"))));
+    }
+
+    #[test]
+    fn test_worker_drains_queue() {
+        let group = SocketGroup::new(1, GroupType::Broadcast, 4);
+        let mut conn = Connection::new(
+            1,
+            "127.0.0.1:9000".parse().unwrap(),
+            "127.0.0.1:9001".parse().unwrap(),
+            SeqNumber::new(0),
+            120,
+        );
+        let handshake = conn.create_handshake();
+        conn.process_handshake(handshake).unwrap();
+        group
+            .add_member(Arc::new(conn), "127.0.0.1:9001".parse().unwrap())
+            .unwrap();
+
+        let member = group.get_member(1).unwrap();
+        let worker = MemberQueueWorker::spawn(member.clone());
+        for _ in 0..10 {
+            member.enqueue(Bytes::from_static(b"data"));
+        }
+
+        for _ in 0..100 {
+            if member.get_stats().packets_sent == 10 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+        drop(worker);
+
+        assert_eq!(member.get_stats().packets_sent, 10);
+        assert!(member.queue.is_empty());
+    }
+
+    #[test]
+    fn test_worker_shutdown_flushes_and_joins() {
+        let group = SocketGroup::new(1, GroupType::Broadcast, 4);
+        let mut conn = Connection::new(
+            1,
+            "127.0.0.1:9000".parse().unwrap(),
+            "127.0.0.1:9001".parse().unwrap(),
+            SeqNumber::new(0),
+            120,
+        );
+        let handshake = conn.create_handshake();
+        conn.process_handshake(handshake).unwrap();
+        group
+            .add_member(Arc::new(conn), "127.0.0.1:9001".parse().unwrap())
+            .unwrap();
+        let member = group.get_member(1).unwrap();
+        let references = Arc::strong_count(&member);
+
+        // Whatever the thread has not sent yet is flushed by shutdown
+        let worker = MemberQueueWorker::spawn(member.clone());
+        assert!(Arc::strong_count(&member) > references);
+        for _ in 0..5 {
+            member.enqueue(Bytes::from_static(b"data"));
+        }
+        assert!(worker.shutdown() <= 5);
+
+        assert_eq!(member.get_stats().packets_sent, 5);
+        assert!(member.queue.is_empty());
+        // The thread and the worker let go of the member
+        assert_eq!(Arc::strong_count(&member), references);
+    }
+}
@@ -0,0 +1,478 @@
+//! Adaptive Broadcast Redundancy
+//!
+//! Full broadcast sends every packet on every path, which wastes bandwidth
+//! while one path alone delivers nearly everything. The controller watches
+//! each path's loss (from the NAKs its connection receives) and how often
+//! it delivers first (from the receiver's status reports). Once one path
+//! has stayed clean and in front for a while, the others are thinned: the
+//! primary path still carries every packet, the others only every Nth.
+//! N doubles after each further clean period, up to a limit, and falls
+//! back to 1 (full broadcast) as soon as the primary loses packets or
+//! another path starts winning the packets they share.
+//!
+//! Thinned paths leave their skipped packets' sequence numbers unsent (see
+//! `Connection::skip_sequence`), so each path stays in step with the group
+//! sequence and the receiver still matches duplicates across paths.
+
+use crate::group::SocketGroup;
+use srt_protocol::SeqNumber;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Adaptive redundancy thresholds
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RedundancyConfig {
+    /// Loss rate at or below which the primary counts as clean (0.0 to 1.0)
+    pub clean_loss: f64,
+    /// Loss rate at or above which the group returns to full broadcast
+    /// (0.0 to 1.0)
+    pub degraded_loss: f64,
+    /// Share of the packets it shares with another path that the primary
+    /// must deliver first (0.0 to 1.0)
+    pub min_first_share: f64,
+    /// Largest duplicate interval (1 = always full broadcast)
+    pub max_interval: u32,
+    /// How long the primary must stay clean before each thinning step
+    pub clean_period: Duration,
+}
+
+impl Default for RedundancyConfig {
+    fn default() -> Self {
+        RedundancyConfig {
+            clean_loss: 0.005,
+            degraded_loss: 0.02,
+            min_first_share: 0.8,
+            max_interval: 16,
+            clean_period: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Current duplication of a broadcast group
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct RedundancyLevel {
+    /// Path carrying every packet, while thinned
+    pub primary: Option<u32>,
+    /// The other paths carry every `duplicate_interval`th packet
+    /// (1 = full broadcast)
+    pub duplicate_interval: u32,
+}
+
+impl RedundancyLevel {
+    /// Every packet on every path
+    pub const FULL: RedundancyLevel = RedundancyLevel {
+        primary: None,
+        duplicate_interval: 1,
+    };
+
+    /// Check if every path carries every packet
+    pub fn is_full(&self) -> bool {
+        self.primary.is_none() || self.duplicate_interval <= 1
+    }
+
+    /// Check if a member carries the packet with group sequence `seq`
+    pub fn carries(&self, member_id: u32, seq: SeqNumber) -> bool {
+        self.is_full()
+            || self.primary == Some(member_id)
+            || seq.as_raw() % self.duplicate_interval == 0
+    }
+}
+
+impl Default for RedundancyLevel {
+    fn default() -> Self {
+        RedundancyLevel::FULL
+    }
+}
+
+/// Cumulative counters of one path
+#[derive(Debug, Clone, Copy, Default)]
+struct PathCounters {
+    member_id: u32,
+    /// Packets sent by the member connection
+    sent: u64,
+    /// Packets the peer NAKed
+    lost: u64,
+    /// Packets the peer received on the path (status reports)
+    received: u64,
+    /// Packets the path delivered first (status reports)
+    first: u64,
+}
+
+impl PathCounters {
+    fn since(&self, last: &PathCounters) -> PathCounters {
+        PathCounters {
+            member_id: self.member_id,
+            sent: self.sent.saturating_sub(last.sent),
+            lost: self.lost.saturating_sub(last.lost),
+            received: self.received.saturating_sub(last.received),
+            first: self.first.saturating_sub(last.first),
+        }
+    }
+
+    fn loss_rate(&self) -> f64 {
+        if self.sent == 0 {
+            return 0.0;
+        }
+        (self.lost as f64 / self.sent as f64).min(1.0)
+    }
+
+    /// Share of the packets received on the path that it delivered first
+    fn win_rate(&self) -> f64 {
+        if self.received == 0 {
+            return 0.0;
+        }
+        (self.first as f64 / self.received as f64).min(1.0)
+    }
+}
+
+/// Adaptive broadcast redundancy controller
+///
+/// Call `update` periodically, after the receiver's status reports have
+/// been applied to the group; its level decides which members carry each
+/// packet (see `BroadcastSender::set_adaptive_redundancy`).
+#[derive(Debug)]
+pub struct RedundancyController {
+    config: RedundancyConfig,
+    level: RedundancyLevel,
+    /// Counters at the previous update, by member
+    last: HashMap<u32, PathCounters>,
+    /// Path leading the first deliveries while at full broadcast
+    leader: Option<u32>,
+    /// Start of the current run of clean updates
+    clean_since: Option<Instant>,
+    /// Level changes so far
+    changes: u64,
+}
+
+impl RedundancyController {
+    /// Create a controller, starting at full broadcast
+    pub fn new(config: RedundancyConfig) -> Self {
+        RedundancyController {
+            config,
+            level: RedundancyLevel::FULL,
+            last: HashMap::new(),
+            leader: None,
+            clean_since: None,
+            changes: 0,
+        }
+    }
+
+    /// Get the thresholds
+    pub fn config(&self) -> &RedundancyConfig {
+        &self.config
+    }
+
+    /// Get the current level
+    pub fn level(&self) -> RedundancyLevel {
+        self.level
+    }
+
+    /// Get the number of level changes so far
+    pub fn changes(&self) -> u64 {
+        self.changes
+    }
+
+    /// Measure the group's active members, changing the level if needed
+    ///
+    /// Returns the new level when it changed.
+    pub fn update(&mut self, group: &SocketGroup) -> Option<RedundancyLevel> {
+        let paths: Vec<_> = group
+            .get_active_members()
+            .iter()
+            .map(|member| {
+                let stats = member.get_stats();
+                let connection = member.connection.stats();
+                PathCounters {
+                    member_id: stats.member_id,
+                    sent: connection.packets_sent,
+                    lost: connection.packets_reported_lost,
+                    received: stats.peer_packets_received,
+                    first: stats.peer_packets_first,
+                }
+            })
+            .collect();
+        let level = self.update_at(&paths, Instant::now());
+        if let Some(level) = level {
+            let _span = group.span().entered();
+            tracing::info!(
+                "Broadcast redundancy: primary {:?}, duplicates every {} packets",
+                level.primary,
+                level.duplicate_interval
+            );
+        }
+        level
+    }
+
+    fn update_at(&mut self, paths: &[PathCounters], now: Instant) -> Option<RedundancyLevel> {
+        // Paths new since the last update only set their baseline
+        let deltas: Vec<_> = paths
+            .iter()
+            .filter_map(|path| self.last.get(&path.member_id).map(|last| path.since(last)))
+            .collect();
+        self.last = paths.iter().map(|p| (p.member_id, *p)).collect();
+
+        let level = match self.level.primary {
+            _ if deltas.len() < 2 => RedundancyLevel::FULL,
+            Some(primary) => self.thinned_level(primary, &deltas, now),
+            None => self.full_level(&deltas, now),
+        };
+        if level == self.level {
+            return None;
+        }
+        if level.is_full() {
+            self.leader = None;
+            self.clean_since = None;
+        }
+        self.level = level;
+        self.changes += 1;
+        Some(level)
+    }
+
+    /// Decide whether to start thinning, from full broadcast
+    fn full_level(&mut self, deltas: &[PathCounters], now: Instant) -> RedundancyLevel {
+        let total_first: u64 = deltas.iter().map(|d| d.first).sum();
+        let leader = deltas
+            .iter()
+            .max_by_key(|d| d.first)
+            .filter(|d| d.first > 0)
+            .filter(|d| d.first as f64 / total_first as f64 >= self.config.min_first_share)
+            .filter(|d| d.loss_rate() <= self.config.clean_loss)
+            .map(|d| d.member_id);
+        if leader.is_none() || leader != self.leader {
+            self.leader = leader;
+            self.clean_since = leader.map(|_| now);
+            return RedundancyLevel::FULL;
+        }
+
+        match self.clean_since {
+            Some(since) if now.saturating_duration_since(since) >= self.config.clean_period => {
+                if self.config.max_interval <= 1 {
+                    return RedundancyLevel::FULL;
+                }
+                self.clean_since = Some(now);
+                RedundancyLevel {
+                    primary: leader,
+                    duplicate_interval: 2,
+                }
+            }
+            _ => RedundancyLevel::FULL,
+        }
+    }
+
+    /// Thin further, hold, or return to full broadcast
+    fn thinned_level(
+        &mut self,
+        primary: u32,
+        deltas: &[PathCounters],
+        now: Instant,
+    ) -> RedundancyLevel {
+        let Some(primary_delta) = deltas.iter().find(|d| d.member_id == primary) else {
+            return RedundancyLevel::FULL;
+        };
+        let loss = primary_delta.loss_rate();
+        let overtaken = deltas
+            .iter()
+            .filter(|d| d.member_id != primary)
+            .any(|d| d.win_rate() > 1.0 - self.config.min_first_share);
+        if loss >= self.config.degraded_loss || overtaken {
+            return RedundancyLevel::FULL;
+        }
+
+        let reported = deltas.iter().any(|d| d.received > 0);
+        if loss > self.config.clean_loss || !reported {
+            self.clean_since = None;
+            return self.level;
+        }
+        let since = *self.clean_since.get_or_insert(now);
+        if now.saturating_duration_since(since) < self.config.clean_period {
+            return self.level;
+        }
+        self.clean_since = Some(now);
+        RedundancyLevel {
+            primary: Some(primary),
+            duplicate_interval: self
+                .level
+                .duplicate_interval
+                .saturating_mul(2)
+                .min(self.config.max_interval),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Cumulative counters for two paths; `b` wins `b_first` of what
+    /// it receives
+    struct Paths {
+        a: PathCounters,
+        b: PathCounters,
+    }
+
+    impl Paths {
+        fn new() -> Self {
+            Paths {
+                a: PathCounters {
+                    member_id: 1,
+                    ..Default::default()
+                },
+                b: PathCounters {
+                    member_id: 2,
+                    ..Default::default()
+                },
+            }
+        }
+
+        /// Send `packets` on both paths with the level's thinning
+        fn step(&mut self, level: RedundancyLevel, packets: u64, a_lost: u64, b_first: u64) {
+            let b_packets = packets / u64::from(level.duplicate_interval);
+            self.a.sent += packets;
+            self.a.lost += a_lost;
+            self.a.received += packets - a_lost;
+            self.a.first += packets - b_first;
+            self.b.sent += b_packets;
+            self.b.received += b_packets;
+            self.b.first += b_first;
+        }
+
+        fn counters(&self) -> [PathCounters; 2] {
+            [self.a, self.b]
+        }
+    }
+
+    fn config() -> RedundancyConfig {
+        RedundancyConfig {
+            clean_loss: 0.005,
+            degraded_loss: 0.02,
+            min_first_share: 0.8,
+            max_interval: 4,
+            clean_period: Duration::from_secs(5),
+        }
+    }
+
+    #[test]
+    fn test_level_carries() {
+        let level = RedundancyLevel {
+            primary: Some(1),
+            duplicate_interval: 4,
+        };
+        assert!(!level.is_full());
+        assert!((0..8).all(|seq| level.carries(1, SeqNumber::new(seq))));
+        let carried: Vec<_> = (0..8)
+            .filter(|&seq| level.carries(2, SeqNumber::new(seq)))
+            .collect();
+        assert_eq!(carried, vec![0, 4]);
+        assert!(RedundancyLevel::FULL.carries(2, SeqNumber::new(3)));
+    }
+
+    #[test]
+    fn test_thins_clean_leader_stepwise() {
+        let mut controller = RedundancyController::new(config());
+        let mut paths = Paths::new();
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        assert!(controller.update_at(&paths.counters(), at(0)).is_none());
+        for secs in 1..=5 {
+            paths.step(controller.level(), 1000, 0, 50);
+            assert!(controller.update_at(&paths.counters(), at(secs)).is_none());
+        }
+        paths.step(controller.level(), 1000, 0, 50);
+        let level = controller.update_at(&paths.counters(), at(6)).unwrap();
+        assert_eq!(level.primary, Some(1));
+        assert_eq!(level.duplicate_interval, 2);
+
+        // Another clean period doubles the interval, up to the limit
+        for secs in 7..=10 {
+            paths.step(controller.level(), 1000, 0, 20);
+            assert!(controller.update_at(&paths.counters(), at(secs)).is_none());
+        }
+        // An update without reports restarts the clean period
+        assert!(controller.update_at(&paths.counters(), at(11)).is_none());
+        for secs in 12..=16 {
+            paths.step(controller.level(), 1000, 0, 20);
+            assert!(controller.update_at(&paths.counters(), at(secs)).is_none());
+        }
+        paths.step(controller.level(), 1000, 0, 20);
+        assert_eq!(
+            controller
+                .update_at(&paths.counters(), at(17))
+                .unwrap()
+                .duplicate_interval,
+            4
+        );
+        for secs in 18..=40 {
+            paths.step(controller.level(), 1000, 0, 10);
+            assert!(controller.update_at(&paths.counters(), at(secs)).is_none());
+        }
+        assert_eq!(controller.level().duplicate_interval, 4);
+        assert_eq!(controller.changes(), 2);
+    }
+
+    #[test]
+    fn test_returns_to_full_broadcast() {
+        let thinned = |paths: &mut Paths| {
+            let mut controller = RedundancyController::new(config());
+            let start = Instant::now();
+            controller.update_at(&paths.counters(), start);
+            for secs in 1..=6 {
+                paths.step(controller.level(), 1000, 0, 0);
+                controller.update_at(&paths.counters(), start + Duration::from_secs(secs));
+            }
+            assert!(!controller.level().is_full());
+            (controller, start + Duration::from_secs(7))
+        };
+
+        // Loss on the primary
+        let mut paths = Paths::new();
+        let (mut controller, now) = thinned(&mut paths);
+        paths.step(controller.level(), 1000, 30, 0);
+        assert_eq!(
+            controller.update_at(&paths.counters(), now),
+            Some(RedundancyLevel::FULL)
+        );
+
+        // The other path wins too many of the packets it carries
+        let mut paths = Paths::new();
+        let (mut controller, now) = thinned(&mut paths);
+        paths.step(controller.level(), 1000, 0, 200);
+        assert_eq!(
+            controller.update_at(&paths.counters(), now),
+            Some(RedundancyLevel::FULL)
+        );
+
+        // The primary leaves the group
+        let mut paths = Paths::new();
+        let (mut controller, now) = thinned(&mut paths);
+        paths.step(controller.level(), 1000, 0, 0);
+        let mut third = paths.b;
+        third.member_id = 3;
+        controller.update_at(&[paths.b, third], now);
+        assert!(controller.level().is_full());
+    }
+
+    #[test]
+    fn test_no_thinning_without_clear_leader() {
+        let mut controller = RedundancyController::new(config());
+        let mut paths = Paths::new();
+        let start = Instant::now();
+        controller.update_at(&paths.counters(), start);
+
+        // Paths split first deliveries evenly
+        for secs in 1..=20 {
+            paths.step(controller.level(), 1000, 0, 500);
+            controller.update_at(&paths.counters(), start + Duration::from_secs(secs));
+        }
+        assert!(controller.level().is_full());
+
+        // A single path has nothing to thin
+        let mut controller = RedundancyController::new(config());
+        for secs in 0..=20 {
+            paths.step(controller.level(), 1000, 0, 0);
+            controller.update_at(&paths.counters()[..1], start + Duration::from_secs(secs));
+        }
+        assert!(controller.level().is_full());
+    }
+}
@@ -0,0 +1,109 @@
+//! Group Sequence Resynchronization
+//!
+//! The receiver periodically reports the sequence number it expects next.
+//! If that has drifted away from the sender's group sequence (e.g. after a
+//! receiver restart), the stream would otherwise stall silently. On drift
+//! the sender announces its current sequence and the receiver fast-forwards
+//! to it.
+//!
+//! Both messages travel as user-defined control packets; the subtype is in
+//! the type-specific field and the sequence number in the additional info.
+
+use bytes::Bytes;
+use srt_protocol::packet::ControlType;
+use srt_protocol::wire;
+use srt_protocol::{ControlPacket, SeqNumber};
+
+/// Control subtype: receiver reports its expected sequence
+pub const RESYNC_REPORT: u16 = wire::SUBTYPE_RESYNC_REPORT;
+
+/// Control subtype: sender announces its current sequence
+pub const RESYNC_ANNOUNCE: u16 = wire::SUBTYPE_RESYNC_ANNOUNCE;
+
+/// Default drift tolerance (packets)
+///
+/// A receiver lagging further behind than this cannot be served by
+/// retransmission and is resynchronized.
+pub const DEFAULT_DRIFT_TOLERANCE: u32 = 8192;
+
+/// Resynchronization control message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResyncMessage {
+    /// Receiver's next expected sequence number
+    Report(SeqNumber),
+    /// Sender's next sequence number
+    Announce(SeqNumber),
+}
+
+impl ResyncMessage {
+    /// Build the control packet for this message
+    pub fn to_control_packet(&self, timestamp: u32, dest_socket_id: u32) -> ControlPacket {
+        let (subtype, seq) = match *self {
+            ResyncMessage::Report(seq) => (RESYNC_REPORT, seq),
+            ResyncMessage::Announce(seq) => (RESYNC_ANNOUNCE, seq),
+        };
+        ControlPacket::new(
+            ControlType::UserDefined,
+            subtype,
+            seq.as_raw(),
+            timestamp,
+            dest_socket_id,
+            Bytes::new(),
+        )
+    }
+
+    /// Parse a control packet, returning None if it is not a resync message
+    pub fn from_control_packet(packet: &ControlPacket) -> Option<Self> {
+        if packet.control_type() != ControlType::UserDefined {
+            return None;
+        }
+
+        let seq = SeqNumber::new_unchecked(packet.header.additional_info()?);
+        match packet.header.type_specific_info()? {
+            RESYNC_REPORT => Some(ResyncMessage::Report(seq)),
+            RESYNC_ANNOUNCE => Some(ResyncMessage::Announce(seq)),
+            _ => None,
+        }
+    }
+}
+
+/// Check whether a receiver's expected sequence has drifted from the sender
+///
+/// The receiver is drifted if it expects a sequence the sender has not
+/// produced yet, or lags by more than `tolerance` packets.
+pub fn is_drifted(sender_next: SeqNumber, receiver_expected: SeqNumber, tolerance: u32) -> bool {
+    let lag = receiver_expected.distance_to(sender_next);
+    lag < 0 || lag as u32 > tolerance
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_roundtrip() {
+        for msg in [
+            ResyncMessage::Report(SeqNumber::new(1234)),
+            ResyncMessage::Announce(SeqNumber::new(0x7FFF_FFF0)),
+        ] {
+            let bytes = msg.to_control_packet(0, 7).to_bytes();
+            let packet = ControlPacket::from_bytes(&bytes).unwrap();
+            assert_eq!(ResyncMessage::from_control_packet(&packet), Some(msg));
+        }
+
+        let keepalive = ControlPacket::new(ControlType::KeepAlive, 0, 0, 0, 0, Bytes::new());
+        assert_eq!(ResyncMessage::from_control_packet(&keepalive), None);
+    }
+
+    #[test]
+    fn test_drift_detection() {
+        let sender = SeqNumber::new(10_000);
+
+        assert!(!is_drifted(sender, SeqNumber::new(10_000), 100));
+        assert!(!is_drifted(sender, SeqNumber::new(9_950), 100));
+        // Lagging too far behind
+        assert!(is_drifted(sender, SeqNumber::new(9_000), 100));
+        // Ahead of the sender (sender restarted)
+        assert!(is_drifted(sender, SeqNumber::new(10_001), 100));
+    }
+}
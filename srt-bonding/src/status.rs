@@ -0,0 +1,172 @@
+//! Receiver Path Status Reports
+//!
+//! The sender sees each path only through ACKs and keep-alives; it cannot
+//! tell which path actually delivers packets first, or how full the
+//! receiver's reorder buffer is. The receiver periodically sends a status
+//! report with its per-path view, which the backup and balancing policies
+//! use when choosing paths.
+//!
+//! Reports travel as user-defined control packets. The subtype is in the
+//! type-specific field and the buffered packet count in the additional
+//! info; the body holds one entry per path.
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use srt_protocol::packet::ControlType;
+use srt_protocol::ControlPacket;
+
+/// Control subtype: receiver reports its per-path delivery stats
+pub const PATH_STATUS: u16 = srt_protocol::wire::SUBTYPE_PATH_STATUS;
+
+/// Most paths carried in one report, keeping it within one datagram
+pub const MAX_REPORTED_PATHS: usize = 32;
+
+/// Encoded length of one path entry
+const PATH_ENTRY_LEN: usize = 24;
+
+/// Relative delay value meaning "not measured yet"
+const DELAY_UNKNOWN: u32 = u32::MAX;
+
+/// The receiver's view of one path
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PathStatus {
+    /// Sender's socket ID for the path (its member ID at the sender)
+    pub socket_id: u32,
+    /// Packets received on the path, duplicates included
+    pub packets_received: u64,
+    /// Packets this path delivered before any other
+    pub packets_first: u64,
+    /// One-way delay above the fastest path (microseconds)
+    pub relative_delay_us: Option<u32>,
+}
+
+/// Periodic status report from receiver to sender
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StatusReport {
+    /// Packets waiting in the receiver's reorder buffer
+    pub buffered_packets: u32,
+    /// Per-path stats, at most `MAX_REPORTED_PATHS`
+    pub paths: Vec<PathStatus>,
+}
+
+impl StatusReport {
+    /// Get the entry for a sender socket ID
+    pub fn path(&self, socket_id: u32) -> Option<&PathStatus> {
+        self.paths.iter().find(|p| p.socket_id == socket_id)
+    }
+
+    /// Build the control packet for this report
+    ///
+    /// Paths beyond `MAX_REPORTED_PATHS` are left out.
+    pub fn to_control_packet(&self, timestamp: u32, dest_socket_id: u32) -> ControlPacket {
+        let paths = &self.paths[..self.paths.len().min(MAX_REPORTED_PATHS)];
+        let mut body = BytesMut::with_capacity(paths.len() * PATH_ENTRY_LEN);
+        for path in paths {
+            body.put_u32(path.socket_id);
+            body.put_u64(path.packets_received);
+            body.put_u64(path.packets_first);
+            body.put_u32(path.relative_delay_us.unwrap_or(DELAY_UNKNOWN));
+        }
+
+        ControlPacket::new(
+            ControlType::UserDefined,
+            PATH_STATUS,
+            self.buffered_packets,
+            timestamp,
+            dest_socket_id,
+            body.freeze(),
+        )
+    }
+
+    /// Parse a control packet, returning None if it is not a status report
+    pub fn from_control_packet(packet: &ControlPacket) -> Option<Self> {
+        if packet.control_type() != ControlType::UserDefined
+            || packet.header.type_specific_info()? != PATH_STATUS
+        {
+            return None;
+        }
+
+        let mut body: Bytes = packet.control_info.clone();
+        if body.len() % PATH_ENTRY_LEN != 0 {
+            return None;
+        }
+        let mut paths = Vec::with_capacity(body.len() / PATH_ENTRY_LEN);
+        while body.has_remaining() {
+            paths.push(PathStatus {
+                socket_id: body.get_u32(),
+                packets_received: body.get_u64(),
+                packets_first: body.get_u64(),
+                relative_delay_us: Some(body.get_u32()).filter(|&d| d != DELAY_UNKNOWN),
+            });
+        }
+
+        Some(StatusReport {
+            buffered_packets: packet.header.additional_info()?,
+            paths,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_roundtrip() {
+        let report = StatusReport {
+            buffered_packets: 17,
+            paths: vec![
+                PathStatus {
+                    socket_id: 0x1234,
+                    packets_received: 5000,
+                    packets_first: 4200,
+                    relative_delay_us: Some(0),
+                },
+                PathStatus {
+                    socket_id: 0x5678,
+                    packets_received: 4990,
+                    packets_first: 790,
+                    relative_delay_us: None,
+                },
+            ],
+        };
+
+        let bytes = report.to_control_packet(0, 7).to_bytes();
+        let packet = ControlPacket::from_bytes(&bytes).unwrap();
+        let parsed = StatusReport::from_control_packet(&packet).unwrap();
+        assert_eq!(parsed, report);
+        assert_eq!(parsed.path(0x5678).unwrap().packets_first, 790);
+        assert!(parsed.path(1).is_none());
+    }
+
+    #[test]
+    fn test_report_rejects_other_packets() {
+        let resync = crate::resync::ResyncMessage::Report(srt_protocol::SeqNumber::new(5))
+            .to_control_packet(0, 0);
+        assert_eq!(StatusReport::from_control_packet(&resync), None);
+
+        let truncated = ControlPacket::new(
+            ControlType::UserDefined,
+            PATH_STATUS,
+            0,
+            0,
+            0,
+            Bytes::from_static(&[0; 10]),
+        );
+        assert_eq!(StatusReport::from_control_packet(&truncated), None);
+
+        // Oversized reports are capped
+        let report = StatusReport {
+            buffered_packets: 0,
+            paths: (0..40)
+                .map(|id| PathStatus {
+                    socket_id: id,
+                    packets_received: 0,
+                    packets_first: 0,
+                    relative_delay_us: None,
+                })
+                .collect(),
+        };
+        let parsed = StatusReport::from_control_packet(&report.to_control_packet(0, 0)).unwrap();
+        assert_eq!(parsed.paths.len(), MAX_REPORTED_PATHS);
+    }
+}
@@ -0,0 +1,510 @@
+//! Scriptable fake members for application tests
+//!
+//! `FakeGroup` builds a `SocketGroup` whose members are real `GroupMember`s
+//! over sans-IO connections, with a simulated link behind each one instead
+//! of a socket. Tests script each link's loss, RTT, status and outages on a
+//! virtual clock, hand the group to `BackupBonding`, `LoadBalancer` or
+//! `BroadcastBonding` as usual, and call `advance` to move time forward and
+//! collect the packets that made it across.
+//!
+//! Keep-alives are driven by the fake group on the virtual clock: a member
+//! that is down stops answering them, so health checks see it fail exactly
+//! as they would a real path. The fake peer acknowledges what arrives (and,
+//! when a link comes back up, everything sent before), so long scripts do
+//! not fill the send buffers; nothing is retransmitted.
+//!
+//! Enabled by the `test-support` feature.
+
+use crate::group::{GroupError, GroupType, MemberStatus, SocketGroup};
+use bytes::Bytes;
+use srt_protocol::packet::{ControlPacket, ControlType};
+use srt_protocol::{AckInfo, Connection, DataPacket, SeqNumber, DEFAULT_KEEPALIVE_INTERVAL};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Round trip of a new fake member (microseconds)
+pub const DEFAULT_FAKE_RTT_US: u32 = 20_000;
+
+/// Queued packets each member may send per `advance`
+const SERVICE_BUDGET: usize = usize::MAX;
+
+/// SRT, UDP and IPv4 header bytes counted against a link's bandwidth
+const PACKET_OVERHEAD: usize = 44;
+
+/// Longest a packet may wait for a bandwidth-limited link before it is
+/// dropped
+const MAX_QUEUE_DELAY: Duration = Duration::from_millis(500);
+
+/// A scripted change to a fake member's link
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MemberEvent {
+    /// Change the member's status
+    Status(MemberStatus),
+    /// Set the round trip time (microseconds)
+    Rtt(u32),
+    /// Set the fraction of packets lost (0.0 to 1.0)
+    Loss(f64),
+    /// Set the link bandwidth (bytes per second, 0 = unlimited); packets
+    /// queue behind each other and are dropped once the queue holds more
+    /// than 500 ms
+    Bandwidth(u64),
+    /// Cut the link: packets in flight are lost and keep-alives go
+    /// unanswered
+    Down,
+    /// Restore the link
+    Up,
+}
+
+/// A packet that crossed a fake member's link
+#[derive(Debug, Clone)]
+pub struct Delivery {
+    /// Member that carried the packet
+    pub member_id: u32,
+    /// Virtual time of arrival
+    pub at: Duration,
+    /// The packet as the member's connection sent it
+    pub packet: DataPacket,
+}
+
+/// Counters for a fake member's link
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FakeLinkStats {
+    /// Packets the member sent
+    pub packets_sent: u64,
+    /// Packets lost to scripted loss, a cut link or a full queue
+    pub packets_lost: u64,
+    /// Packets delivered by `advance`
+    pub packets_delivered: u64,
+}
+
+/// Simulated link behind a fake member
+#[derive(Debug)]
+struct FakeLink {
+    rtt_us: u32,
+    loss: f64,
+    up: bool,
+    /// Bytes per second, 0 = unlimited
+    bandwidth: u64,
+    /// When the link finishes sending what is queued
+    busy_until: Duration,
+    /// Highest sequence sent, acknowledged when the link comes back up
+    last_sent: Option<SeqNumber>,
+    /// Packets in flight with their arrival time
+    in_flight: VecDeque<(Duration, DataPacket)>,
+    stats: FakeLinkStats,
+}
+
+impl FakeLink {
+    fn new() -> Self {
+        FakeLink {
+            rtt_us: DEFAULT_FAKE_RTT_US,
+            loss: 0.0,
+            up: true,
+            bandwidth: 0,
+            busy_until: Duration::ZERO,
+            last_sent: None,
+            in_flight: VecDeque::new(),
+            stats: FakeLinkStats::default(),
+        }
+    }
+
+    fn one_way_delay(&self) -> Duration {
+        Duration::from_micros(u64::from(self.rtt_us) / 2)
+    }
+
+    /// Time the link takes to put `len` payload bytes on the wire
+    fn serialization_delay(&self, len: usize) -> Duration {
+        if self.bandwidth == 0 {
+            return Duration::ZERO;
+        }
+        let bytes = (len + PACKET_OVERHEAD) as u64;
+        Duration::from_nanos(bytes * 1_000_000_000 / self.bandwidth)
+    }
+}
+
+/// Socket group with scriptable fake members and a virtual clock
+pub struct FakeGroup {
+    group: Arc<SocketGroup>,
+    now: Duration,
+    links: HashMap<u32, FakeLink>,
+    /// Scripted events by (time, order scheduled)
+    script: BTreeMap<(Duration, u64), (u32, MemberEvent)>,
+    scheduled: u64,
+    keepalive_interval: Duration,
+    next_keepalive: Duration,
+    next_socket_id: u32,
+    /// Loss PRNG state (xorshift64*)
+    rng: u64,
+}
+
+impl FakeGroup {
+    /// Create a fake group with no members
+    pub fn new(group_type: GroupType, max_members: usize) -> Self {
+        FakeGroup {
+            group: Arc::new(SocketGroup::new(1, group_type, max_members)),
+            now: Duration::ZERO,
+            links: HashMap::new(),
+            script: BTreeMap::new(),
+            scheduled: 0,
+            keepalive_interval: DEFAULT_KEEPALIVE_INTERVAL,
+            next_keepalive: Duration::ZERO,
+            next_socket_id: 1,
+            rng: 0x5EED_CAFE_F00D_D00D,
+        }
+    }
+
+    /// Get the group, to hand to a bonding mode
+    pub fn group(&self) -> Arc<SocketGroup> {
+        self.group.clone()
+    }
+
+    /// Get the virtual time since the group was created
+    pub fn now(&self) -> Duration {
+        self.now
+    }
+
+    /// Seed the loss PRNG (the same seed and script give the same losses)
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = seed.max(1);
+    }
+
+    /// Get the virtual time between keep-alives
+    pub fn keepalive_interval(&self) -> Duration {
+        self.keepalive_interval
+    }
+
+    /// Change the virtual time between keep-alives
+    pub fn set_keepalive_interval(&mut self, interval: Duration) {
+        self.keepalive_interval = interval;
+    }
+
+    /// Add a connected, active member with a clean link
+    pub fn add_member(&mut self) -> Result<u32, GroupError> {
+        let socket_id = self.next_socket_id;
+        self.next_socket_id += 1;
+
+        let local = SocketAddr::from((Ipv4Addr::LOCALHOST, 8000));
+        let remote = SocketAddr::from((Ipv4Addr::new(10, 0, 0, socket_id as u8), 9000));
+        let mut connection = Connection::new(socket_id, local, remote, SeqNumber::new(0), 120);
+        // Answer our own handshake to reach the connected state
+        let handshake = connection.create_handshake();
+        connection
+            .process_handshake(handshake)
+            .map_err(|e| GroupError::Connection(e.to_string()))?;
+        // The fake group paces keep-alives on the virtual clock
+        connection.set_keepalive_interval(Duration::ZERO);
+
+        let member_id = self.group.add_member(Arc::new(connection), remote)?;
+        self.group
+            .update_member_status(member_id, MemberStatus::Active)?;
+        self.links.insert(member_id, FakeLink::new());
+        Ok(member_id)
+    }
+
+    /// Apply an event to a member's link now
+    pub fn apply(&mut self, member_id: u32, event: MemberEvent) -> Result<(), GroupError> {
+        let link = self
+            .links
+            .get_mut(&member_id)
+            .ok_or(GroupError::MemberNotFound(member_id))?;
+
+        match event {
+            MemberEvent::Status(status) => self.group.update_member_status(member_id, status)?,
+            MemberEvent::Rtt(rtt_us) => {
+                link.rtt_us = rtt_us;
+                if let Some(member) = self.group.get_member(member_id) {
+                    member.update_rtt(rtt_us);
+                }
+            }
+            MemberEvent::Loss(loss) => link.loss = loss.clamp(0.0, 1.0),
+            MemberEvent::Bandwidth(bytes_per_sec) => link.bandwidth = bytes_per_sec,
+            MemberEvent::Down => {
+                link.up = false;
+                link.stats.packets_lost += link.in_flight.len() as u64;
+                link.in_flight.clear();
+            }
+            MemberEvent::Up => {
+                if !link.up {
+                    link.up = true;
+                    // The peer is back: what was sent during the outage is
+                    // given up rather than retransmitted
+                    if let (Some(seq), Some(member)) =
+                        (link.last_sent, self.group.get_member(member_id))
+                    {
+                        acknowledge(&member.connection, member_id, seq);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Schedule an event at a virtual time
+    ///
+    /// Events due at the same time apply in the order scheduled.
+    pub fn schedule(&mut self, at: Duration, member_id: u32, event: MemberEvent) {
+        self.scheduled += 1;
+        self.script.insert((at, self.scheduled), (member_id, event));
+    }
+
+    /// Get a member's link counters
+    pub fn link_stats(&self, member_id: u32) -> Option<FakeLinkStats> {
+        self.links.get(&member_id).map(|link| link.stats)
+    }
+
+    /// Move the virtual clock forward by `step`
+    ///
+    /// Packets the bonding mode has queued leave now. Scripted events and
+    /// keep-alives then run in time order up to the new time, and every
+    /// packet that arrived by then is returned in arrival order.
+    pub fn advance(&mut self, step: Duration) -> Vec<Delivery> {
+        let until = self.now + step;
+        self.transmit();
+
+        loop {
+            let next_event = self.script.keys().next().map(|(at, _)| *at);
+            let next = match next_event {
+                Some(at) if at <= self.next_keepalive => at,
+                _ => self.next_keepalive,
+            };
+            if next > until {
+                break;
+            }
+            self.now = self.now.max(next);
+
+            if next_event == Some(next) {
+                let (_, (member_id, event)) = self.script.pop_first().unwrap();
+                // Members removed from the group since are skipped
+                let _ = self.apply(member_id, event);
+            } else {
+                self.exchange_keepalives();
+                self.next_keepalive =
+                    self.now + self.keepalive_interval.max(Duration::from_millis(1));
+            }
+        }
+        self.now = until;
+
+        let mut delivered = Vec::new();
+        for (&member_id, link) in self.links.iter_mut() {
+            let mut highest = None;
+            while link.in_flight.front().is_some_and(|(at, _)| *at <= until) {
+                let (at, packet) = link.in_flight.pop_front().unwrap();
+                link.stats.packets_delivered += 1;
+                highest = Some(packet.seq_number());
+                delivered.push(Delivery {
+                    member_id,
+                    at,
+                    packet,
+                });
+            }
+            if let (Some(seq), Some(member)) = (highest, self.group.get_member(member_id)) {
+                acknowledge(&member.connection, member_id, seq);
+            }
+        }
+        delivered.sort_by_key(|delivery| (delivery.at, delivery.member_id));
+        delivered
+    }
+
+    /// Send what the members have queued over their links
+    fn transmit(&mut self) {
+        self.group.service_queues(SERVICE_BUDGET);
+
+        for member in self.group.get_all_members() {
+            let member_id = member.connection.local_socket_id();
+            let packets = member.connection.packets_to_send();
+            let Some(link) = self.links.get_mut(&member_id) else {
+                continue;
+            };
+
+            for packet in packets {
+                link.stats.packets_sent += 1;
+                link.last_sent = Some(packet.seq_number());
+                let departure = link.busy_until.max(self.now);
+                let lost = !link.up
+                    || departure - self.now > MAX_QUEUE_DELAY
+                    || chance(&mut self.rng, link.loss);
+                if lost {
+                    link.stats.packets_lost += 1;
+                    continue;
+                }
+                link.busy_until = departure + link.serialization_delay(packet.payload.len());
+                let arrival = link.busy_until + link.one_way_delay();
+                link.in_flight.push_back((arrival, packet));
+            }
+        }
+    }
+
+    /// Send each member's keep-alive; the peers of live links answer
+    fn exchange_keepalives(&mut self) {
+        for member in self.group.get_all_members() {
+            let member_id = member.connection.local_socket_id();
+            let Some(link) = self.links.get(&member_id) else {
+                continue;
+            };
+
+            if member.connection.create_keepalive().is_some() && link.up {
+                // A keep-alive from the peer without a token: heard, but
+                // no RTT sample to override the scripted RTT
+                let reply =
+                    ControlPacket::new(ControlType::KeepAlive, 0, 0, 0, member_id, Bytes::new());
+                let _ = member.connection.process_control(&reply);
+            }
+        }
+    }
+}
+
+/// Acknowledge everything up to `seq` as the member's peer
+fn acknowledge(connection: &Connection, member_id: u32, seq: SeqNumber) {
+    let ack = ControlPacket::new(
+        ControlType::Ack,
+        0,
+        seq.as_raw(),
+        0,
+        member_id,
+        AckInfo::new(seq).to_bytes(),
+    );
+    let _ = connection.process_control(&ack);
+}
+
+/// Draw true with probability `p` (xorshift64*)
+fn chance(state: &mut u64, p: f64) -> bool {
+    if p <= 0.0 {
+        return false;
+    }
+    *state ^= *state >> 12;
+    *state ^= *state << 25;
+    *state ^= *state >> 27;
+    let value = state.wrapping_mul(0x2545_F491_4F6C_DD1D);
+    let unit = (value >> 11) as f64 / (1u64 << 53) as f64;
+    unit < p
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backup::{BackupBonding, FailoverReason};
+    use crate::broadcast::BroadcastBonding;
+
+    const MS: Duration = Duration::from_millis(1);
+
+    #[test]
+    fn test_fake_link_delay_and_loss() {
+        let mut fake = FakeGroup::new(GroupType::Broadcast, 2);
+        let clean = fake.add_member().unwrap();
+        let lossy = fake.add_member().unwrap();
+        fake.apply(clean, MemberEvent::Rtt(40_000)).unwrap();
+        fake.apply(lossy, MemberEvent::Loss(1.0)).unwrap();
+
+        let bonding = BroadcastBonding::new(fake.group());
+        bonding.send(b"hello").unwrap();
+
+        assert!(fake.advance(19 * MS).is_empty());
+        let delivered = fake.advance(MS);
+        assert_eq!(delivered.len(), 1);
+        assert_eq!(delivered[0].member_id, clean);
+        assert_eq!(delivered[0].at, 20 * MS);
+        assert_eq!(&delivered[0].packet.payload[..], b"hello");
+
+        assert_eq!(fake.link_stats(lossy).unwrap().packets_lost, 1);
+        assert_eq!(fake.link_stats(clean).unwrap().packets_delivered, 1);
+        assert_eq!(
+            fake.group().get_member(clean).unwrap().get_stats().rtt_us,
+            40_000
+        );
+        assert!(matches!(
+            fake.apply(99, MemberEvent::Up),
+            Err(GroupError::MemberNotFound(99))
+        ));
+    }
+
+    #[test]
+    fn test_scripted_outage_fails_over() {
+        let mut fake = FakeGroup::new(GroupType::Backup, 2);
+        let primary = fake.add_member().unwrap();
+        let backup_id = fake.add_member().unwrap();
+
+        let backup = BackupBonding::new(fake.group(), Duration::ZERO, 3);
+        backup.set_primary(primary).unwrap();
+        backup.add_backup(backup_id).unwrap();
+
+        fake.schedule(Duration::from_secs(2), primary, MemberEvent::Down);
+        let mut failed_over_at = None;
+        for _ in 0..100 {
+            fake.advance(100 * MS);
+            if !backup.health_check().unwrap() {
+                failed_over_at = Some(fake.now());
+                break;
+            }
+        }
+
+        // Four unanswered keep-alives after the outage
+        let failed_over_at = failed_over_at.expect("no failover");
+        assert!(failed_over_at > Duration::from_secs(5));
+        assert!(failed_over_at <= Duration::from_secs(7));
+        assert_eq!(backup.get_primary_id(), Some(backup_id));
+        assert_eq!(
+            backup.failover_history()[0].reason,
+            FailoverReason::PrimaryFailed
+        );
+    }
+
+    #[test]
+    fn test_bandwidth_queues_and_drops() {
+        let mut fake = FakeGroup::new(GroupType::Broadcast, 1);
+        let member_id = fake.add_member().unwrap();
+        // 100 byte packets (56 payload) at 10 kB/s: 10 ms each
+        fake.apply(member_id, MemberEvent::Bandwidth(10_000))
+            .unwrap();
+
+        let bonding = BroadcastBonding::new(fake.group());
+        for _ in 0..60 {
+            bonding.send(&[0; 56]).unwrap();
+        }
+        let delivered = fake.advance(Duration::from_secs(1));
+        // 10 ms one way after each packet's 10 ms on the wire; the queue
+        // holds 500 ms, so packets beyond the 51st are dropped
+        assert_eq!(delivered.len(), 51);
+        assert_eq!(delivered[0].at, 20 * MS);
+        assert_eq!(delivered[1].at, 30 * MS);
+        let stats = fake.link_stats(member_id).unwrap();
+        assert_eq!(stats.packets_lost, 9);
+    }
+
+    #[test]
+    fn test_peer_acknowledges_deliveries_and_outages() {
+        let mut fake = FakeGroup::new(GroupType::Broadcast, 1);
+        let member_id = fake.add_member().unwrap();
+        let member = fake.group().get_member(member_id).unwrap();
+        let bonding = BroadcastBonding::new(fake.group());
+
+        bonding.send(b"one").unwrap();
+        fake.advance(20 * MS);
+        assert_eq!(member.connection.stats().send_buffer_packets, 0);
+
+        fake.apply(member_id, MemberEvent::Down).unwrap();
+        bonding.send(b"two").unwrap();
+        fake.advance(20 * MS);
+        assert_eq!(member.connection.stats().send_buffer_packets, 1);
+
+        fake.apply(member_id, MemberEvent::Up).unwrap();
+        assert_eq!(member.connection.stats().send_buffer_packets, 0);
+    }
+
+    #[test]
+    fn test_scripted_status_change() {
+        let mut fake = FakeGroup::new(GroupType::Broadcast, 2);
+        let member_id = fake.add_member().unwrap();
+        fake.schedule(
+            50 * MS,
+            member_id,
+            MemberEvent::Status(MemberStatus::Broken),
+        );
+
+        fake.advance(49 * MS);
+        assert_eq!(fake.group().active_member_count(), 1);
+        fake.advance(MS);
+        assert_eq!(fake.group().active_member_count(), 0);
+    }
+}
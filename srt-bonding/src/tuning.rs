@@ -0,0 +1,38 @@
+//! Runtime retuning of bonding parameters
+//!
+//! Failover thresholds, health-check intervals, the balancing algorithm
+//! and alignment limits can change while a stream runs, from an operator
+//! API or a config reload, without rebuilding the bonding objects.
+//! `BackupBonding`, `LoadBalancer` and `AlignmentBuffer` each hold their
+//! tunables in one config (`BackupConfig`, `BalancerConfig`,
+//! `AlignmentConfig`) that `reconfigure` validates and swaps in whole, so
+//! a send never sees half a change. Every change that takes effect is
+//! returned as a `ConfigChange` and passed to the object's change hook.
+
+use std::sync::Arc;
+use std::time::Instant;
+
+/// A configuration change that took effect
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigChange<C> {
+    /// When the change was applied
+    pub timestamp: Instant,
+    /// Configuration before the change
+    pub old: C,
+    /// Configuration after the change
+    pub new: C,
+}
+
+impl<C: PartialEq> ConfigChange<C> {
+    /// Build a change from `old` to `new`, or `None` if they are equal
+    pub(crate) fn between(old: C, new: C) -> Option<Self> {
+        (old != new).then(|| ConfigChange {
+            timestamp: Instant::now(),
+            old,
+            new,
+        })
+    }
+}
+
+/// Callback run after a configuration change takes effect
+pub type ConfigChangeHook<C> = Arc<dyn Fn(&ConfigChange<C>) + Send + Sync>;
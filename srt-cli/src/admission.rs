@@ -0,0 +1,219 @@
+//! Address allowlist and denylist for receiving tools
+//!
+//! Any address that completes a handshake would otherwise become a group
+//! member. `--allow` and `--deny` (or `allow`/`deny` in the config file)
+//! restrict which peers are admitted; the policy is installed as the
+//! group's admission hook.
+
+use clap::Args;
+use serde::{Deserialize, Serialize};
+use srt_bonding::{AdmissionRequest, SocketGroup};
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// Address or network, e.g. `192.0.2.7` or `10.0.0.0/8`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct AddrRule {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl AddrRule {
+    /// Check whether `ip` is in the network
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for AddrRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s, None),
+        };
+        let addr: IpAddr = addr
+            .parse()
+            .map_err(|_| format!("Invalid address '{}'", s))?;
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix
+                .parse()
+                .ok()
+                .filter(|p| *p <= max)
+                .ok_or_else(|| format!("Invalid prefix length in '{}' (0 to {})", s, max))?,
+            None => max,
+        };
+        Ok(AddrRule { addr, prefix })
+    }
+}
+
+impl TryFrom<String> for AddrRule {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<AddrRule> for String {
+    fn from(rule: AddrRule) -> Self {
+        rule.to_string()
+    }
+}
+
+impl fmt::Display for AddrRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let max = if self.addr.is_ipv4() { 32 } else { 128 };
+        if self.prefix == max {
+            write!(f, "{}", self.addr)
+        } else {
+            write!(f, "{}/{}", self.addr, self.prefix)
+        }
+    }
+}
+
+/// Which peer addresses may join
+///
+/// A denied address is always refused. If the allowlist is not empty, only
+/// addresses on it are admitted.
+#[derive(Args, Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AdmissionPolicy {
+    /// Only admit peers from this address or network (repeatable)
+    #[arg(long = "allow", value_name = "ADDR[/PREFIX]")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allow: Vec<AddrRule>,
+
+    /// Refuse peers from this address or network (repeatable)
+    #[arg(long = "deny", value_name = "ADDR[/PREFIX]")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deny: Vec<AddrRule>,
+}
+
+impl AdmissionPolicy {
+    /// Check if the policy admits everyone
+    pub fn is_empty(&self) -> bool {
+        self.allow.is_empty() && self.deny.is_empty()
+    }
+
+    /// Add the rules of another policy (e.g. command-line flags to a config)
+    pub fn extend(&mut self, other: &AdmissionPolicy) {
+        self.allow.extend_from_slice(&other.allow);
+        self.deny.extend_from_slice(&other.deny);
+    }
+
+    /// Check a peer, returning the reason for a refusal
+    pub fn check(&self, request: &AdmissionRequest) -> Result<(), String> {
+        let ip = request.address.ip();
+        if let Some(rule) = self.deny.iter().find(|rule| rule.contains(ip)) {
+            return Err(format!("{} is denied by {}", ip, rule));
+        }
+        if !self.allow.is_empty() && !self.allow.iter().any(|rule| rule.contains(ip)) {
+            return Err(format!("{} is not on the allowlist", ip));
+        }
+        Ok(())
+    }
+
+    /// Install the policy as the group's admission hook
+    ///
+    /// An empty policy leaves the group without a hook.
+    pub fn install(&self, group: &SocketGroup) {
+        if self.is_empty() {
+            return;
+        }
+        let policy = self.clone();
+        group.set_admission_hook(move |request| policy.check(request));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use srt_bonding::GroupType;
+
+    fn request(address: &str) -> AdmissionRequest {
+        AdmissionRequest {
+            address: address.parse().unwrap(),
+            socket_id: Some(1),
+            stream_id: None,
+        }
+    }
+
+    #[test]
+    fn test_addr_rule() {
+        let rule: AddrRule = "10.1.0.0/16".parse().unwrap();
+        assert!(rule.contains("10.1.200.3".parse().unwrap()));
+        assert!(!rule.contains("10.2.0.1".parse().unwrap()));
+        assert!(!rule.contains("::1".parse().unwrap()));
+        assert_eq!(rule.to_string(), "10.1.0.0/16");
+
+        let host: AddrRule = "192.0.2.7".parse().unwrap();
+        assert!(host.contains("192.0.2.7".parse().unwrap()));
+        assert!(!host.contains("192.0.2.8".parse().unwrap()));
+        assert_eq!(host.to_string(), "192.0.2.7");
+
+        let any: AddrRule = "0.0.0.0/0".parse().unwrap();
+        assert!(any.contains("203.0.113.9".parse().unwrap()));
+        let v6: AddrRule = "2001:db8::/32".parse().unwrap();
+        assert!(v6.contains("2001:db8:1::5".parse().unwrap()));
+        assert!(!v6.contains("2001:db9::5".parse().unwrap()));
+
+        assert!("10.0.0.0/33".parse::<AddrRule>().is_err());
+        assert!("10.0.0/8".parse::<AddrRule>().is_err());
+        assert!("10.0.0.0/x".parse::<AddrRule>().is_err());
+    }
+
+    #[test]
+    fn test_policy() {
+        let policy = AdmissionPolicy {
+            allow: vec!["10.0.0.0/8".parse().unwrap()],
+            deny: vec!["10.0.0.66".parse().unwrap()],
+        };
+        assert!(policy.check(&request("10.1.2.3:9000")).is_ok());
+        assert_eq!(
+            policy.check(&request("10.0.0.66:9000")).unwrap_err(),
+            "10.0.0.66 is denied by 10.0.0.66"
+        );
+        assert_eq!(
+            policy.check(&request("192.0.2.1:9000")).unwrap_err(),
+            "192.0.2.1 is not on the allowlist"
+        );
+
+        // Denylist only: everyone else is admitted
+        let policy = AdmissionPolicy {
+            allow: Vec::new(),
+            deny: vec!["192.0.2.0/24".parse().unwrap()],
+        };
+        assert!(policy.check(&request("198.51.100.1:9000")).is_ok());
+        assert!(policy.check(&request("192.0.2.1:9000")).is_err());
+    }
+
+    #[test]
+    fn test_install() {
+        let group = SocketGroup::new(1, GroupType::Broadcast, 4);
+        AdmissionPolicy::default().install(&group);
+        assert!(group.check_admission(&request("192.0.2.1:9000")).is_ok());
+
+        let policy = AdmissionPolicy {
+            allow: vec!["127.0.0.1".parse().unwrap()],
+            deny: Vec::new(),
+        };
+        policy.install(&group);
+        assert!(group.check_admission(&request("127.0.0.1:9000")).is_ok());
+        assert!(group.check_admission(&request("192.0.2.1:9000")).is_err());
+        assert_eq!(group.admission_rejects(), 1);
+    }
+}
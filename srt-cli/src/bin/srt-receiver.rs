@@ -3,16 +3,28 @@
 //! Receives bonded SRT streams and writes to stdout or file.
 
 use clap::Parser;
+use srt::listener::{ListenerEvent, SrtListener};
 use srt_bonding::*;
-use srt_io::SrtSocket;
-use srt_protocol::{Connection, DataPacket, SeqNumber, SrtHandshake};
-use std::collections::HashMap;
-use std::fs::File;
-use std::io::{self, BufWriter, Write};
-use std::net::SocketAddr;
+use srt_cli::admission::AdmissionPolicy;
+#[cfg(feature = "otel")]
+use srt_cli::otel::{self, Metric, OtelArgs, OtelExporter, SpanBuilder};
+use srt_cli::shaping::{CbrShaper, ShapedUdpOutput};
+use srt_cli::summary::{interrupt_flag, ConfigResultExt, RunError};
+use srt_cli::transport::TransportArgs;
+use srt_cli::{CheckReport, Outcome, PeriodicTask, RunSummary, WriteAheadArgs};
+use srt_io::multicast::{self, MulticastOptions};
+use srt_io::{PlaneReader, SocketError, Timer};
+use srt_protocol::packet::ControlType;
+use srt_protocol::srctime::{self, SourceClock};
+use srt_protocol::{
+    is_probe_packet, Connection, DataPacket, FilterConfig, Labels, SeqNumber, StallAction,
+};
+use std::io::{self, Write};
+use std::net::{AddrParseError, IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::thread;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime};
 
 #[derive(Parser, Debug)]
 #[command(name = "srt-receiver")]
@@ -30,9 +42,21 @@ struct Args {
     #[arg(short, long)]
     listen: u16,
 
-    /// Bind address
+    /// Bind address; repeat to listen on each link of a multi-homed host
+    ///
+    /// An address without a port uses the listen port.
     #[arg(short, long, default_value = "0.0.0.0")]
-    bind: String,
+    bind: Vec<String>,
+
+    /// Maximum segment size in bytes (including IP/UDP headers)
+    #[arg(long, default_value = "1500")]
+    mss: usize,
+
+    /// Packet filter configuration (e.g. "fec,cols:10,rows:5")
+    ///
+    /// Parameters the sender also sets must match or the path is rejected.
+    #[arg(long)]
+    packet_filter: Option<String>,
 
     /// Expected number of paths
     #[arg(long, default_value = "1")]
@@ -42,12 +66,66 @@ struct Args {
     #[arg(long, default_value = "1")]
     stats: u64,
 
+    /// Give up if no sender connects within this many seconds (0 = wait forever)
+    #[arg(long, default_value = "0")]
+    connect_timeout: u64,
+
+    /// Shape UDP output to a constant bitrate (bits per second)
+    #[arg(long)]
+    shape_bitrate: Option<u64>,
+
+    /// Shaping buffer size in milliseconds of output at the target bitrate
+    #[arg(long, default_value = "200")]
+    shape_buffer_ms: u64,
+
+    /// TTL for a multicast UDP output
+    #[arg(long, default_value = "1")]
+    multicast_ttl: u32,
+
+    /// Local interface address for a multicast UDP output
+    #[arg(long)]
+    multicast_iface: Option<Ipv4Addr>,
+
+    #[command(flatten)]
+    transport: TransportArgs,
+
+    #[command(flatten)]
+    admission: AdmissionPolicy,
+
+    #[command(flatten)]
+    write_ahead: WriteAheadArgs,
+
+    #[cfg(feature = "otel")]
+    #[command(flatten)]
+    otel: OtelArgs,
+
+    /// Deliver packets without reordering while only one path is active
+    ///
+    /// Lowest latency for single-path streams; reordering resumes as soon
+    /// as a second path connects.
+    #[arg(long)]
+    single_path_bypass: bool,
+
+    /// Labels for stats, logs and the run summary (e.g. "site=nyc,camera=3")
+    #[arg(long, value_name = "KEY=VALUE,...")]
+    labels: Vec<Labels>,
+
+    /// Write a JSON run summary to this file on exit
+    #[arg(long, value_name = "PATH")]
+    summary_json: Option<String>,
+
+    /// Check the configuration and exit: bind the listen address and UDP
+    /// output for a moment and print the resolved settings without
+    /// accepting senders
+    #[arg(long)]
+    check: bool,
+
     /// Verbose output
     #[arg(short, long)]
     verbose: bool,
 }
 
-fn main() -> anyhow::Result<()> {
+fn main() {
     let args = Args::parse();
 
     // Initialize logging based on verbose flag
@@ -62,56 +140,211 @@ fn main() -> anyhow::Result<()> {
     tracing::info!("SRT Receiver starting...");
     tracing::info!("Output target: {}", args.output);
 
+    let interrupted = interrupt_flag();
+    let mut summary = RunSummary::new("srt-receiver");
+    let start_time = Instant::now();
+    let result = if args.check {
+        check(&args)
+    } else {
+        run(&args, &interrupted, &mut summary)
+    };
+    summary.finish(&result, start_time.elapsed());
+
+    if let Err(e) = &result {
+        eprintln!("Error: {:#}", e);
+    }
+    if let Some(path) = &args.summary_json {
+        if let Err(e) = summary.write(path) {
+            tracing::error!("Failed to write summary to {}: {}", path, e);
+        }
+    }
+    std::process::exit(summary.exit_code);
+}
+
+/// Resolve the configuration and bind the listen address and any UDP
+/// output briefly, without accepting senders (`--check`)
+fn check(args: &Args) -> anyhow::Result<Outcome> {
+    let mut report = CheckReport::new();
+    match args.group.as_str() {
+        "broadcast" | "backup" | "balancing" => report.entry("group", &args.group),
+        group => report.problem(format_args!("group: invalid group mode '{}'", group)),
+    }
+    if let Some(listen_addrs) = report.check("listen", listen_addrs(args)) {
+        for listen_addr in listen_addrs {
+            report.bind("listen", listen_addr);
+        }
+    }
+    report.entry("num_paths", args.num_paths);
+    if args.connect_timeout > 0 {
+        report.entry(
+            "connect_timeout",
+            format_args!("{} s", args.connect_timeout),
+        );
+    }
+    for rule in &args.admission.allow {
+        report.entry("allow", rule);
+    }
+    for rule in &args.admission.deny {
+        report.entry("deny", rule);
+    }
+
+    if args.output == "-" {
+        report.entry("output", "stdout");
+    } else if let Some(addr_str) = args.output.strip_prefix("udp://") {
+        if let Some(target_addr) = report.check("output", addr_str.parse::<SocketAddr>()) {
+            let multicast_opts = MulticastOptions {
+                ttl: args.multicast_ttl,
+                interface: args.multicast_iface,
+                ..Default::default()
+            };
+            let socket = multicast::bind_sender(target_addr, &multicast_opts);
+            if report.check("output", socket).is_some() {
+                report.entry("output", &args.output);
+            }
+        }
+        if let Some(rate) = args.shape_bitrate {
+            report.entry(
+                "shaping",
+                format_args!("{} bps ({} ms buffer)", rate, args.shape_buffer_ms),
+            );
+        }
+    } else {
+        report.output_file("output", &args.output);
+    }
+
+    args.transport.check(&mut report, args.mss);
+    if let Some(filter) = &args.packet_filter {
+        if let Some(filter) = report.check("packet_filter", FilterConfig::parse_builtin(filter)) {
+            report.entry("packet_filter", filter);
+        }
+    }
+    #[cfg(feature = "otel")]
+    if let Some(Some(config)) = report.check("otel", args.otel.config("srt-receiver")) {
+        report.entry("otel", config.service_name);
+    }
+    let labels = args
+        .labels
+        .iter()
+        .fold(Labels::new(), |acc, l| acc.merged(l));
+    if !labels.is_empty() {
+        report.entry("labels", labels);
+    }
+
+    Ok(report.finish()?)
+}
+
+fn run(
+    args: &Args,
+    interrupted: &Arc<AtomicBool>,
+    summary: &mut RunSummary,
+) -> anyhow::Result<Outcome> {
     // Parse group mode
     let group_type = match args.group.as_str() {
         "broadcast" => GroupType::Broadcast,
         "backup" => GroupType::Backup,
         "balancing" => GroupType::Broadcast,
-        _ => anyhow::bail!("Invalid group mode: {}", args.group),
+        _ => return Err(RunError::Config(format!("Invalid group mode: {}", args.group)).into()),
     };
 
-    // Create socket
-    let listen_addr: SocketAddr = format!("{}:{}", args.bind, args.listen).parse()?;
-    let socket = SrtSocket::bind(listen_addr)?;
-    tracing::info!("Listening on: {}", socket.local_addr()?);
+    // Create one socket per bind address
+    let mut listener = SrtListener::bind_all(&listen_addrs(args).config()?)?;
+    for socket in listener.sockets_mut() {
+        args.transport.trace_socket(socket);
+    }
+    for local in 0..listener.sockets().len() {
+        tracing::info!("Listening on: {}", listener.local_addr_at(local)?);
+    }
 
     // Create socket group
-    let group = Arc::new(SocketGroup::new(1, group_type, args.num_paths));
+    let mut group = SocketGroup::new(1, group_type, args.num_paths);
+    group.set_mss(args.mss).config()?;
+    let labels = args
+        .labels
+        .iter()
+        .fold(Labels::new(), |acc, l| acc.merged(l));
+    group.set_labels(labels.clone());
+    args.admission.install(&group);
+    summary.set_labels(&labels);
+    let group = Arc::new(group);
+    let _span = group.span().entered();
 
     // Create bonding
-    let bonding = Arc::new(BroadcastBonding::new(group.clone()));
-
-    // Track remote addresses to member IDs
-    let mut addr_to_member: HashMap<SocketAddr, u32> = HashMap::new();
-    let mut next_member_id = 1u32;
+    let bonding = Arc::new(BroadcastBonding::with_receive_buffer(
+        group.clone(),
+        args.transport.rcvbuf_packets(args.mss)?,
+    ));
+    bonding.set_single_path_bypass(args.single_path_bypass);
+    #[cfg(feature = "otel")]
+    let telemetry = {
+        let bonding = bonding.clone();
+        args.otel
+            .start("srt-receiver", move || {
+                let stats = bonding.receiver.stats();
+                let mut metrics = otel::socket_group_metrics(&bonding.group);
+                metrics.extend([
+                    Metric::gauge(
+                        "srt.receiver.buffered",
+                        "{packet}",
+                        stats.buffered_packets as f64,
+                    ),
+                    Metric::counter("srt.receiver.skipped", "{packet}", stats.skipped_packets),
+                    Metric::counter("srt.receiver.dropped", "{packet}", stats.dropped_packets),
+                ]);
+                metrics
+            })
+            .config()?
+    };
 
     // Open output
+    let mut shaper_stats = None;
+    let mut write_ahead = None;
     let mut writer: Box<dyn Write> = if args.output == "-" {
         tracing::info!("Writing to stdout");
         Box::new(io::stdout())
     } else if args.output.starts_with("udp://") {
         let addr_str = &args.output[6..];
-        let target_addr: SocketAddr = addr_str
-            .parse()
-            .map_err(|e| anyhow::anyhow!("Invalid UDP output address '{}': {}", addr_str, e))?;
+        let target_addr: SocketAddr = addr_str.parse().map_err(|e| {
+            RunError::Config(format!("Invalid UDP output address '{}': {}", addr_str, e))
+        })?;
 
         tracing::info!("Relaying to UDP: {}", target_addr);
-        let socket = std::net::UdpSocket::bind("0.0.0.0:0")?;
-        socket.connect(target_addr)?;
-        Box::new(UdpWriter::new(socket))
+        let multicast_opts = MulticastOptions {
+            ttl: args.multicast_ttl,
+            interface: args.multicast_iface,
+            ..Default::default()
+        };
+        let socket = multicast::bind_sender(target_addr, &multicast_opts)?;
+        if let Some(rate) = args.shape_bitrate {
+            tracing::info!(
+                "Shaping UDP output to {} bps ({} ms buffer)",
+                rate,
+                args.shape_buffer_ms
+            );
+            let shaper =
+                CbrShaper::with_buffer_duration(rate, Duration::from_millis(args.shape_buffer_ms));
+            let output = ShapedUdpOutput::new(socket, target_addr, shaper);
+            shaper_stats = Some(output.stats_handle());
+            Box::new(output)
+        } else {
+            socket.connect(target_addr)?;
+            Box::new(UdpWriter::new(socket))
+        }
     } else {
         tracing::info!("Writing to file: {}", args.output);
-        let file = File::create(&args.output)
+        let (writer, handle) = args
+            .write_ahead
+            .create_file(&args.output)
             .map_err(|e| anyhow::anyhow!("Failed to create file '{}': {}", args.output, e))?;
-        Box::new(BufWriter::new(file))
+        write_ahead = handle;
+        writer
     };
 
-    // Statistics thread
+    // Statistics thread, stopped when the run ends
     let bonding_stats = bonding.clone();
+    let write_ahead_stats = write_ahead.clone();
     let stats_interval = args.stats;
-    if stats_interval > 0 {
-        thread::spawn(move || loop {
-            thread::sleep(Duration::from_secs(stats_interval));
+    let stats_task = (stats_interval > 0).then(|| {
+        PeriodicTask::spawn(Duration::from_secs(stats_interval), move || {
             let stats = bonding_stats.stats();
             tracing::info!(
                 "Stats: {} members, buffered={}, ready={}",
@@ -119,132 +352,300 @@ fn main() -> anyhow::Result<()> {
                 stats.receiver_stats.buffered_packets,
                 stats.receiver_stats.ready_packets
             );
-        });
-    }
-
-    // Main receive loop
-    let mut buffer = vec![0u8; 2048];
+            if let Some(ref handle) = shaper_stats {
+                let shaper = handle.stats();
+                tracing::info!(
+                    "Shaper: sent={} packets, buffered={} bytes, underflows={}, overflows={}",
+                    shaper.packets_sent,
+                    shaper.buffered_bytes,
+                    shaper.underflows,
+                    shaper.overflows
+                );
+            }
+            if let Some(ref handle) = write_ahead_stats {
+                tracing::info!("File output: {}", handle.stats());
+            }
+        })
+    });
+
+    // Main receive loop; control packets in each batch are handled before
+    // the data read with them
+    let mut reader = PlaneReader::default();
+    // Listening socket the reader's batch came from
+    let mut local = 0;
+    let mut stream_ended = false;
     let mut total_bytes = 0u64;
     let mut packet_count = 0u64;
+    let mut delivered_count = 0u64;
     let start_time = Instant::now();
+    let connect_timeout = Duration::from_secs(args.connect_timeout);
+
+    // Maps packet timestamps to the sender's capture times
+    let mut source_clock: Option<SourceClock> = None;
+    let mut source_delay = None;
+
+    // Periodic expected-sequence reports let the sender detect drift;
+    // path status reports show it which paths deliver first
+    let mut resync_timer = Timer::new(Duration::from_secs(1));
+    // Delivery out of the group, checked with the reports
+    let mut watchdog = args.transport.stall_watchdog();
 
     tracing::info!("Ready to receive packets...");
 
-    loop {
-        // Receive packet
-        let (n, remote_addr) = match socket.recv_from(&mut buffer) {
-            Ok(result) => result,
-            Err(e) => {
-                if let srt_io::SocketError::Io(ref io_err) = e {
-                    if io_err.kind() == io::ErrorKind::WouldBlock {
-                        // No data available, sleep briefly
-                        thread::sleep(Duration::from_millis(10));
-
-                        // Try to pop ready packets from bonding
-                        while let Some(packet) = bonding.receiver.pop_ready_packet() {
-                            let _ = writer.write_all(&packet.payload);
-                            total_bytes += packet.payload.len() as u64;
-                        }
+    let outcome = loop {
+        if interrupted.load(Ordering::Relaxed) {
+            break Outcome::Interrupted;
+        }
+        // Shutdown overtakes data read in the same batch; deliver that first
+        if stream_ended && reader.is_empty() {
+            break Outcome::StreamEnded;
+        }
+        if args.connect_timeout > 0
+            && listener.is_empty()
+            && start_time.elapsed() >= connect_timeout
+        {
+            return Err(RunError::ConnectTimeout(format!(
+                "no sender connected within {} seconds",
+                args.connect_timeout
+            ))
+            .into());
+        }
 
-                        continue;
+        if resync_timer.try_fire() {
+            group.check_timeouts();
+            let receiver_stats = bonding.receiver.stats();
+            if let Some(stalled_for) = watchdog.observe(
+                receiver_stats.next_expected,
+                total_bytes,
+                receiver_stats.buffered_packets > 0,
+                Instant::now(),
+            ) {
+                tracing::warn!(
+                    "Delivery stalled for {:?} at {} ({} packets buffered behind it, {} paths active); action: {}",
+                    stalled_for,
+                    receiver_stats.next_expected.as_raw(),
+                    receiver_stats.buffered_packets,
+                    group.active_member_count(),
+                    watchdog.action()
+                );
+                match watchdog.action() {
+                    StallAction::Resync => {
+                        bonding.receiver.skip_to_buffered();
                     }
+                    StallAction::Close => {
+                        tracing::error!("Closing: delivery stalled for {:?}", stalled_for);
+                        break Outcome::Error;
+                    }
+                    // The sender picks the paths; nothing to fail over to here
+                    StallAction::Log | StallAction::Failover => {}
                 }
-                tracing::error!("Receive error: {}", e);
-                continue;
             }
+            let report = bonding.receiver.resync_report().to_control_packet(0, 0);
+            let status = bonding.status_report().to_control_packet(0, 0);
+            for (id, _) in listener.peers() {
+                let _ = listener.send_to(id, &report.to_bytes());
+                let _ = listener.send_to(id, &status.to_bytes());
+                if let Some(member) = group.get_member(id) {
+                    while let Some(keepalive) = member.connection.create_keepalive() {
+                        let _ = listener.send_to(id, &keepalive.to_bytes());
+                    }
+                    if let Some(report) = member.connection.create_probe_report() {
+                        let _ = listener.send_to(id, &report.to_bytes());
+                    }
+                }
+            }
+        }
+
+        // Receive packets
+        if reader.is_empty() {
+            match fill_from_any(&mut reader, &listener, &mut local) {
+                Ok(0) => {
+                    // No data available, sleep briefly
+                    thread::sleep(Duration::from_millis(10));
+
+                    // Try to pop ready packets from bonding
+                    while let Some(packet) = bonding.receiver.pop_ready_packet() {
+                        source_delay = delay_since_source(&mut source_clock, &packet);
+                        let _ = writer.write_all(&packet.payload);
+                        total_bytes += packet.payload.len() as u64;
+                        delivered_count += 1;
+                    }
+
+                    continue;
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    tracing::error!("Receive error: {}", e);
+                    continue;
+                }
+            }
+        }
+        let Some(datagram) = reader.next_datagram() else {
+            continue;
         };
+        let (buffer, remote_addr) = (datagram.data, datagram.from);
+        let n = buffer.len();
 
-        // Deserialize SRT packet
-        if n >= 16 && (buffer[0] & 0x80) != 0 {
-            tracing::info!("Received control packet ({} bytes) from {}", n, remote_addr);
-            // Control packet - skip 16-byte header for handshake body
-            if let Ok(hs) = SrtHandshake::from_bytes(&buffer[16..n]) {
+        let member_id = match listener.handle_on(local, &buffer, remote_addr) {
+            ListenerEvent::Packet { socket_id, .. } => socket_id,
+            ListenerEvent::Handshake(request) => {
                 tracing::info!(
                     "Received handshake request from {}, sender_socket_id={}",
                     remote_addr,
-                    hs.udt.socket_id
+                    request.handshake().udt.socket_id
                 );
+                let member_id = request.socket_id();
+
+                // Refused peers get no reply
+                let admission = AdmissionRequest {
+                    address: remote_addr,
+                    socket_id: Some(request.handshake().udt.socket_id),
+                    stream_id: request.stream_id().map(str::to_string),
+                };
+                if let Err(e) = group.check_admission(&admission) {
+                    tracing::warn!("Rejecting handshake from {}: {}", remote_addr, e);
+                    #[cfg(feature = "otel")]
+                    record_handshake(&telemetry, remote_addr, member_id, Err(e.to_string()));
+                    continue;
+                }
 
-                // Get or create member ID for this remote address
-                let member_id = *addr_to_member.entry(remote_addr).or_insert_with(|| {
-                    let id = next_member_id;
-                    next_member_id += 1;
-                    tracing::info!(
-                        "New path detected (handshake): {} (member {})",
-                        remote_addr,
-                        id
-                    );
-                    id
-                });
-
-                // Store sender's socket_id for later use
-                let _sender_socket_id = hs.udt.socket_id;
-
-                let mut resp_hs = hs.clone();
-                resp_hs.udt.handshake_type = -2; // Agreement
-                resp_hs.udt.socket_id = 999;
-
-                let hs_body = resp_hs.to_bytes();
-                let resp_packet = srt_protocol::ControlPacket::new(
-                    srt_protocol::packet::ControlType::Handshake,
-                    0,
-                    0,
-                    0,
-                    0,
-                    bytes::Bytes::copy_from_slice(&hs_body),
+                let mut conn = Connection::new(
+                    member_id, // Our socket ID (also the group member ID)
+                    listener.local_addr_at(request.local_index())?,
+                    remote_addr,
+                    SeqNumber::new(0),
+                    args.transport.latency_ms(),
+                );
+                conn.set_mss(args.mss).config()?;
+                args.transport.apply(&mut conn)?;
+                conn.set_packet_filter(args.packet_filter.as_deref())
+                    .config()?;
+                conn.set_labels(labels.clone().with("path", &remote_addr.to_string())?);
+                let conn = match listener.accept(request, conn) {
+                    Ok(conn) => conn,
+                    Err(e) => {
+                        tracing::warn!("Rejecting handshake from {}: {}", remote_addr, e);
+                        #[cfg(feature = "otel")]
+                        record_handshake(&telemetry, remote_addr, member_id, Err(e.to_string()));
+                        continue;
+                    }
+                };
+                tracing::info!(
+                    "New path {} (member {}), remote_socket_id={:?}",
+                    remote_addr,
+                    member_id,
+                    conn.remote_socket_id()
                 );
 
-                let resp_bytes = resp_packet.to_bytes();
-                match socket.send_to(&resp_bytes, remote_addr) {
-                    Ok(n) => {
-                        tracing::info!("Sent {} bytes of handshake agreement to {}", n, remote_addr)
+                // A new origin means the sender restarted
+                if let Some(origin) = conn.peer_time_origin() {
+                    if source_clock.as_ref().map(SourceClock::origin) != Some(origin) {
+                        tracing::info!(
+                            "Sender time origin: {} us since epoch",
+                            srctime::to_unix_micros(origin)
+                        );
+                        source_clock = Some(SourceClock::new(origin));
                     }
-                    Err(e) => tracing::error!(
-                        "Failed to send handshake agreement to {}: {}",
-                        remote_addr,
-                        e
-                    ),
                 }
 
-                // Ensure member is in group and active
-                if group.get_member(member_id).is_none() {
-                    let mut conn = Connection::new(
-                        999, // Our socket ID
-                        socket.local_addr().unwrap(),
-                        remote_addr,
-                        SeqNumber::new(0),
-                        120,
-                    );
-                    // Set remote socket ID to sender's socket ID
-                    let _ = conn.process_handshake(hs.clone());
-                    tracing::info!(
-                        "Created connection for member {}, remote_socket_id={:?}",
-                        member_id,
-                        conn.remote_socket_id()
-                    );
-
-                    let conn_arc = Arc::new(conn);
-                    let _ = group.add_member(conn_arc, remote_addr);
-                    let _ = group.update_member_status(member_id, MemberStatus::Active);
+                if let Err(e) = group.add_member(conn, remote_addr) {
+                    tracing::warn!("Rejecting handshake from {}: {}", remote_addr, e);
+                    #[cfg(feature = "otel")]
+                    record_handshake(&telemetry, remote_addr, member_id, Err(e.to_string()));
+                    listener.close(member_id);
+                    continue;
                 }
+                let _ = group.update_member_status(member_id, MemberStatus::Active);
+                #[cfg(feature = "otel")]
+                record_handshake(&telemetry, remote_addr, member_id, Ok(()));
                 continue;
             }
-        }
-
-        // Get member ID for this remote address - reject if not handshaked
-        let member_id = match addr_to_member.get(&remote_addr) {
-            Some(id) => *id,
-            None => {
+            ListenerEvent::Repeated { socket_id } => {
+                tracing::debug!("Repeated handshake agreement for member {}", socket_id);
+                continue;
+            }
+            // Early data is not enabled here
+            ListenerEvent::Early { peer } | ListenerEvent::Unknown { peer } => {
                 tracing::warn!(
-                    "Received data from {} without handshake, ignoring packet",
-                    remote_addr
+                    "Received packet from {} without handshake, ignoring it",
+                    peer
                 );
                 continue;
             }
         };
 
+        if n >= 16 && (buffer[0] & 0x80) != 0 {
+            let control = srt_protocol::ControlPacket::from_bytes(&buffer[..n]).ok();
+
+            // Sender resync announcement: fast-forward to its sequence
+            if let Some(message) = control
+                .as_ref()
+                .and_then(ResyncMessage::from_control_packet)
+            {
+                bonding.receiver.on_resync(message);
+                continue;
+            }
+
+            // Sender gave up on a range; stop waiting for it
+            if let Some(drop) = control
+                .as_ref()
+                .filter(|c| c.control_type() == ControlType::DropReq)
+            {
+                if let Some(member) = group.get_member(member_id) {
+                    if let Err(e) = member.connection.process_control(drop) {
+                        tracing::debug!("Ignoring DropReq from {}: {}", remote_addr, e);
+                    }
+                    bonding.receiver.take_member_drops(&member.connection);
+                }
+                continue;
+            }
+
+            // Keep-alive from the sender: echo it for its RTT measurement
+            if let Some(keepalive) = control
+                .as_ref()
+                .filter(|c| c.control_type() == ControlType::KeepAlive)
+            {
+                if let Some(member) = group.get_member(member_id) {
+                    let _ = member.connection.process_control(keepalive);
+                    while let Some(reply) = member.connection.create_keepalive() {
+                        let _ = listener.send_to(member_id, &reply.to_bytes());
+                    }
+                }
+                continue;
+            }
+
+            // Probe burst from the sender: time it and report once it is over
+            if let Some(probe) = control.as_ref().filter(|c| is_probe_packet(c)) {
+                if let Some(member) = group.get_member(member_id) {
+                    let _ = member.connection.process_control(probe);
+                    if let Some(report) = member.connection.create_probe_report() {
+                        let _ = listener.send_to(member_id, &report.to_bytes());
+                    }
+                }
+                continue;
+            }
+
+            // Sender finished on this path; stop once all paths are done
+            if control
+                .as_ref()
+                .is_some_and(|c| c.control_type() == ControlType::Shutdown)
+            {
+                if let Some(member) = group.get_member(member_id) {
+                    tracing::info!("Sender shut down path {}", remote_addr);
+                    member.connection.begin_close();
+                    member.set_status(MemberStatus::Broken);
+                }
+                stream_ended = group.active_member_count() == 0;
+                continue;
+            }
+
+            tracing::debug!("Ignoring control packet ({} bytes) from {}", n, remote_addr);
+            continue;
+        }
+
         // Deserialize Data packet
-        if let Ok(packet) = DataPacket::from_bytes(&buffer[..n]) {
+        // The datagram is ours: the payload is sliced from it, not copied
+        if let Ok(packet) = DataPacket::parse(bytes::Bytes::from(buffer)) {
             if packet_count == 0 {
                 tracing::info!(
                     "Received first data packet: seq={}, dest_socket_id={}, size={}",
@@ -253,7 +654,10 @@ fn main() -> anyhow::Result<()> {
                     packet.payload.len()
                 );
             }
-            match bonding.receiver.on_packet_received(packet, member_id) {
+            if packet.msg_number().retransmitted {
+                summary.retransmissions += 1;
+            }
+            match bonding.on_receive(packet, member_id) {
                 Ok(_) => {}
                 Err(e) => tracing::error!("Error processing data packet: {}", e),
             }
@@ -261,11 +665,17 @@ fn main() -> anyhow::Result<()> {
 
             let mut popped_count = 0;
             while let Some(ready_packet) = bonding.receiver.pop_ready_packet() {
-                tracing::debug!("Popped ready packet, size={}", ready_packet.payload.len());
+                source_delay = delay_since_source(&mut source_clock, &ready_packet);
+                tracing::debug!(
+                    "Popped ready packet, size={}, source delay={:?}",
+                    ready_packet.payload.len(),
+                    source_delay
+                );
                 match writer.write_all(&ready_packet.payload) {
                     Ok(_) => {
                         total_bytes += ready_packet.payload.len() as u64;
                         popped_count += 1;
+                        delivered_count += 1;
                     }
                     Err(e) => tracing::error!("Error writing packet: {}", e),
                 }
@@ -283,14 +693,135 @@ fn main() -> anyhow::Result<()> {
             if packet_count % 100 == 0 {
                 let elapsed = start_time.elapsed().as_secs_f64();
                 let mbps = (total_bytes as f64 * 8.0) / (elapsed * 1_000_000.0);
-                tracing::debug!("Received {} packets, {:.2} Mbps", packet_count, mbps);
+                tracing::debug!(
+                    "Received {} packets, {:.2} Mbps, source delay={:?}",
+                    packet_count,
+                    mbps,
+                    source_delay
+                );
             }
         }
 
         if packet_count % 50 == 0 {
             let _ = writer.flush();
         }
+    };
+
+    if let Some(task) = stats_task {
+        task.shutdown();
     }
+
+    // Write out the tail: packets held back behind gaps that will not be filled
+    let drain = bonding.receiver.drain();
+    for packet in &drain.packets {
+        writer.write_all(&packet.payload)?;
+        total_bytes += packet.payload.len() as u64;
+        delivered_count += 1;
+    }
+    writer.flush()?;
+    if let Some(handle) = &write_ahead {
+        handle.sync()?;
+        tracing::info!("File output: {}", handle.stats());
+    }
+    let lost: usize = drain.gaps.iter().map(|g| g.len()).sum();
+    if lost > 0 {
+        tracing::warn!(
+            "{} packets unrecoverable in {} gaps: {:?}",
+            lost,
+            drain.gaps.len(),
+            drain.gaps
+        );
+    }
+    tracing::info!(
+        "Stream ended: {} packets, {} bytes written",
+        packet_count,
+        total_bytes
+    );
+    let plane = reader.stats();
+    tracing::debug!(
+        "Control plane: {} packets, wait avg={:?} max={:?}",
+        plane.control_packets,
+        plane.control_latency_avg().unwrap_or_default(),
+        plane.control_latency_max
+    );
+
+    summary.bytes = total_bytes;
+    summary.packets = delivered_count;
+    // Gaps skipped by the single-path bypass or dropped by the sender are
+    // lost too
+    let receiver_stats = bonding.receiver.stats();
+    summary.lost_packets =
+        lost as u64 + receiver_stats.skipped_packets + receiver_stats.dropped_packets;
+
+    #[cfg(feature = "otel")]
+    if let Some(telemetry) = telemetry {
+        let stats = telemetry.shutdown();
+        tracing::debug!(
+            "OpenTelemetry: {} exports, {} failures",
+            stats.exports,
+            stats.failures
+        );
+    }
+    Ok(outcome)
+}
+
+/// Resolve the `--bind` addresses; those without a port use `--listen`
+fn listen_addrs(args: &Args) -> Result<Vec<SocketAddr>, String> {
+    args.bind
+        .iter()
+        .map(|bind| {
+            bind.parse::<SocketAddr>()
+                .or_else(|_| {
+                    let ip = bind.parse::<IpAddr>()?;
+                    Ok::<_, AddrParseError>(SocketAddr::new(ip, args.listen))
+                })
+                .map_err(|e| format!("invalid bind address '{}': {}", bind, e))
+        })
+        .collect()
+}
+
+/// Read a batch from the next listening socket with data waiting, starting
+/// after `local` and leaving `local` at the socket read
+fn fill_from_any(
+    reader: &mut PlaneReader,
+    listener: &SrtListener,
+    local: &mut usize,
+) -> Result<usize, SocketError> {
+    let sockets = listener.sockets();
+    for _ in 0..sockets.len() {
+        *local = (*local + 1) % sockets.len();
+        let count = reader.fill(&sockets[*local])?;
+        if count > 0 {
+            return Ok(count);
+        }
+    }
+    Ok(0)
+}
+
+/// Record an accepted or rejected handshake as a span
+#[cfg(feature = "otel")]
+fn record_handshake(
+    telemetry: &Option<OtelExporter>,
+    peer: SocketAddr,
+    member_id: u32,
+    result: Result<(), String>,
+) {
+    let Some(telemetry) = telemetry else {
+        return;
+    };
+    let mut span = SpanBuilder::start("srt.handshake")
+        .attribute("srt.peer", &peer.to_string())
+        .attribute("srt.member.id", &member_id.to_string());
+    if let Err(e) = &result {
+        span = span.attribute("error", e);
+    }
+    telemetry.record(span.finish(result.is_ok()));
+}
+
+/// Time from the sender's capture of a delivered packet until now
+fn delay_since_source(clock: &mut Option<SourceClock>, packet: &DataPacket) -> Option<Duration> {
+    let source_time = clock.as_mut()?.source_time(packet.header.timestamp);
+    SystemTime::now().duration_since(source_time).ok()
 }
 
 struct UdpWriter {
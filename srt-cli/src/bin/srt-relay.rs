@@ -6,15 +6,34 @@
 //!   • Receive SRT → Output UDP to 3 destinations
 //!   • Receive UDP → Output to file + UDP + stdout
 //!   • Receive bonded SRT → Output single stream to multiple servers
+//!   • Receive several SRT streams on one port → Route each by Stream ID
 
 use clap::Parser;
+use srt::listener::{ListenerEvent, SrtListener};
 use srt_bonding::*;
-use srt_io::SrtSocket;
-use srt_protocol::DataPacket;
+use srt_cli::admission::AdmissionPolicy;
+use srt_cli::caller::{CallerArgs, CallerOptions, CallerOutput, CallerPool};
+use srt_cli::config::{Config, RelayConfig, StreamRoute, WILDCARD_STREAM_ID};
+use srt_cli::jitter::{JitterBuffer, JitterMode, JitterStats};
+#[cfg(feature = "otel")]
+use srt_cli::otel::{self, OtelArgs, OtelConfig, OtelExporter, SpanBuilder};
+use srt_cli::shaping::{CbrShaper, ShapedUdpOutput, ShaperStatsHandle};
+use srt_cli::summary::{interrupt_flag, ConfigResultExt, RunError};
+use srt_cli::transport::TransportArgs;
+use srt_cli::writeahead::{WriteAheadArgs, WriteAheadHandle};
+use srt_cli::{CheckReport, Outcome, RunSummary};
+use srt_io::multicast::{self, MulticastOptions};
+use srt_io::Timer;
+use srt_protocol::packet::{ControlType, DEFAULT_MSS, MAX_MSS};
+use srt_protocol::{
+    Connection, DataPacket, DeliveryWatchdog, FilterConfig, Labels, SeqNumber, StallAction,
+};
 use std::collections::HashMap;
 use std::fs::File;
-use std::io::{self, BufWriter, Write};
-use std::net::{SocketAddr, UdpSocket};
+use std::io::{self, Write};
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::thread;
 use std::time::{Duration, Instant};
@@ -28,23 +47,39 @@ struct Args {
     /// Examples:
     ///   --input srt://:9000           (receive bonded SRT on port 9000)
     ///   --input udp://:5000           (receive UDP on port 5000)
+    ///   --input udp://239.1.1.1:5000  (join multicast group)
     ///   --input video.ts              (read from file)
     ///   --input -                     (read from stdin)
     #[arg(short, long)]
     input: String,
 
-    /// Output destinations: 'udp://host:port', 'file:path', or '-' for stdout
+    /// Output destinations: 'udp://host:port', 'srt://host:port', 'file:path',
+    /// or '-' for stdout
     /// Can be specified multiple times for multiple outputs
     ///
     /// Examples:
     ///   --output udp://192.168.1.10:5000
     ///   --output udp://192.168.1.11:5000
+    ///   --output udp://239.1.1.2:5000 (multicast)
+    ///   --output srt://192.168.1.20:9000 (SRT caller, reconnects)
+    ///   --output srt://192.168.1.20:9000?streamid=live/cam1
     ///   --output file:/tmp/recorded.ts
     ///   --output -
     #[arg(short, long)]
     output: Vec<String>,
 
-    /// Number of expected input paths (for SRT input)
+    /// Relay configuration with per-Stream ID output routes (SRT input only)
+    ///
+    /// Each caller is matched by its Stream ID to a route in the [relay]
+    /// section and gets its own outputs and statistics.
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+
+    /// Packet filter configuration for SRT input (e.g. "fec,cols:10,rows:5")
+    #[arg(long)]
+    packet_filter: Option<String>,
+
+    /// Maximum number of input paths per stream (for SRT input)
     #[arg(long, default_value = "1")]
     num_paths: usize,
 
@@ -52,6 +87,84 @@ struct Args {
     #[arg(long, default_value = "2")]
     stats: u64,
 
+    /// Shape UDP outputs to a constant bitrate (bits per second)
+    #[arg(long)]
+    shape_bitrate: Option<u64>,
+
+    /// Shaping buffer size in milliseconds of output at the target bitrate
+    #[arg(long, default_value = "200")]
+    shape_buffer_ms: u64,
+
+    /// TTL for multicast UDP outputs
+    #[arg(long, default_value = "1")]
+    multicast_ttl: u32,
+
+    /// Local interface address for multicast UDP inputs and outputs
+    #[arg(long)]
+    multicast_iface: Option<Ipv4Addr>,
+
+    /// Hold UDP input in a jitter buffer this deep (milliseconds)
+    ///
+    /// RTP input is put back in sequence order, waiting this long for
+    /// missing packets; plain TS is delayed this long and released at its
+    /// average arrival rate. Off by default.
+    #[arg(long, value_name = "MS")]
+    jitter_buffer_ms: Option<u64>,
+
+    /// Jitter buffer mode: auto, rtp or ts
+    #[arg(long, default_value = "auto")]
+    jitter_mode: JitterMode,
+
+    /// Latency, buffer and bandwidth options for SRT input and outputs
+    ///
+    /// --latency overrides the latency in the relay configuration.
+    #[command(flatten)]
+    transport: TransportArgs,
+
+    /// Reconnect options for SRT outputs
+    #[command(flatten)]
+    caller: CallerArgs,
+
+    /// Caller addresses admitted on SRT input, added to any in the relay
+    /// configuration
+    #[command(flatten)]
+    admission: AdmissionPolicy,
+
+    /// Write-ahead queue for file outputs
+    #[command(flatten)]
+    write_ahead: WriteAheadArgs,
+
+    /// OpenTelemetry export, overriding any [telemetry] configuration
+    #[cfg(feature = "otel")]
+    #[command(flatten)]
+    otel: OtelArgs,
+
+    /// Deliver packets without reordering while only one path is active
+    ///
+    /// Lowest latency for single-path streams; reordering resumes as soon
+    /// as a second path connects.
+    #[arg(long)]
+    single_path_bypass: bool,
+
+    /// Data packets to hold per caller that arrive before its handshake is
+    /// accepted, delivered once it is (0 = drop them)
+    #[arg(long, value_name = "PACKETS", default_value = "64")]
+    early_data_packets: usize,
+
+    /// Labels for stats, logs and the run summary (e.g. "site=nyc,camera=3")
+    #[arg(long, value_name = "KEY=VALUE,...")]
+    labels: Vec<Labels>,
+
+    /// Write a JSON run summary to this file on exit
+    #[arg(long, value_name = "PATH")]
+    summary_json: Option<String>,
+
+    /// Check the configuration and exit: load --config, bind the input and
+    /// UDP outputs for a moment and print the resolved settings without
+    /// relaying
+    #[arg(long)]
+    check: bool,
+
     /// Verbose output
     #[arg(short, long)]
     verbose: bool,
@@ -59,17 +172,18 @@ struct Args {
 
 /// Input source type
 enum InputSource {
-    Srt(u16),     // SRT listen port
-    Udp(u16),     // UDP listen port
-    File(String), // File path
-    Stdin,        // Stdin
+    Srt(u16),        // SRT listen port
+    Udp(SocketAddr), // UDP listen address (multicast groups are joined)
+    File(String),    // File path
+    Stdin,           // Stdin
 }
 
 /// Output destination type
 enum OutputDest {
-    Udp(SocketAddr), // UDP destination
-    File(String),    // File path
-    Stdout,          // Stdout
+    Udp(SocketAddr),                 // UDP destination
+    Srt(SocketAddr, Option<String>), // SRT listener and Stream ID
+    File(String),                    // File path
+    Stdout,                          // Stdout
 }
 
 /// Parse input string
@@ -93,7 +207,7 @@ fn parse_input(input: &str) -> anyhow::Result<InputSource> {
             addr_str.to_string()
         };
         let addr: SocketAddr = addr_str.parse()?;
-        Ok(InputSource::Udp(addr.port()))
+        Ok(InputSource::Udp(addr))
     } else {
         Ok(InputSource::File(input.to_string()))
     }
@@ -109,6 +223,16 @@ fn parse_output(output: &str) -> anyhow::Result<OutputDest> {
             .parse()
             .map_err(|e| anyhow::anyhow!("Invalid UDP address '{}': {}", addr_str, e))?;
         Ok(OutputDest::Udp(addr))
+    } else if output.starts_with("srt://") {
+        let target = output.strip_prefix("srt://").unwrap();
+        let (addr_str, stream_id) = match target.split_once("?streamid=") {
+            Some((addr_str, stream_id)) => (addr_str, Some(stream_id.to_string())),
+            None => (target, None),
+        };
+        let addr: SocketAddr = addr_str
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Invalid SRT address '{}': {}", addr_str, e))?;
+        Ok(OutputDest::Srt(addr, stream_id))
     } else if output.starts_with("file:") {
         let path = output.strip_prefix("file:").unwrap();
         Ok(OutputDest::File(path.to_string()))
@@ -118,30 +242,62 @@ fn parse_output(output: &str) -> anyhow::Result<OutputDest> {
     }
 }
 
+/// Options applied to every output
+#[derive(Clone)]
+struct OutputOptions {
+    /// CBR shaping for UDP outputs as (bitrate, buffer duration)
+    shaping: Option<(u64, Duration)>,
+    /// Options for multicast UDP outputs
+    multicast: MulticastOptions,
+    /// SRT outputs, kept connected across sessions
+    callers: Arc<CallerPool>,
+    /// Write-ahead queue for file outputs
+    write_ahead: WriteAheadArgs,
+}
+
 /// Output writer that can write to multiple destinations
 struct MultiWriter {
     udp_outputs: Vec<(UdpSocket, SocketAddr)>,
-    file_outputs: Vec<BufWriter<File>>,
+    shaped_outputs: Vec<ShapedUdpOutput>,
+    srt_outputs: Vec<Arc<CallerOutput>>,
+    file_outputs: Vec<Box<dyn Write>>,
+    /// Write-ahead queues of file outputs, by path
+    write_ahead: Vec<(String, WriteAheadHandle)>,
     stdout_output: Option<io::Stdout>,
 }
 
 impl MultiWriter {
-    fn new(outputs: Vec<OutputDest>) -> anyhow::Result<Self> {
+    /// Create a writer; UDP outputs are CBR-shaped when shaping is set
+    fn new(outputs: Vec<OutputDest>, opts: &OutputOptions) -> anyhow::Result<Self> {
         let mut udp_outputs = Vec::new();
+        let mut shaped_outputs = Vec::new();
+        let mut srt_outputs = Vec::new();
         let mut file_outputs = Vec::new();
+        let mut write_ahead = Vec::new();
         let mut stdout_output = None;
 
         for output in outputs {
             match output {
                 OutputDest::Udp(addr) => {
                     tracing::info!("Adding UDP output: {}", addr);
-                    let socket = UdpSocket::bind("0.0.0.0:0")?;
-                    udp_outputs.push((socket, addr));
+                    let socket = multicast::bind_sender(addr, &opts.multicast)?;
+                    match opts.shaping {
+                        Some((rate_bps, buffer)) => {
+                            let shaper = CbrShaper::with_buffer_duration(rate_bps, buffer);
+                            shaped_outputs.push(ShapedUdpOutput::new(socket, addr, shaper));
+                        }
+                        None => udp_outputs.push((socket, addr)),
+                    }
+                }
+                OutputDest::Srt(addr, stream_id) => {
+                    tracing::info!("Adding SRT output: {}", addr);
+                    srt_outputs.push(opts.callers.get(addr, stream_id.as_deref())?);
                 }
                 OutputDest::File(path) => {
                     tracing::info!("Adding file output: {}", path);
-                    let file = File::create(&path)?;
-                    file_outputs.push(BufWriter::new(file));
+                    let (file, handle) = opts.write_ahead.create_file(&path)?;
+                    file_outputs.push(file);
+                    write_ahead.extend(handle.map(|handle| (path, handle)));
                 }
                 OutputDest::Stdout => {
                     tracing::info!("Adding stdout output");
@@ -152,7 +308,10 @@ impl MultiWriter {
 
         Ok(MultiWriter {
             udp_outputs,
+            shaped_outputs,
+            srt_outputs,
             file_outputs,
+            write_ahead,
             stdout_output,
         })
     }
@@ -163,6 +322,16 @@ impl MultiWriter {
             socket.send_to(data, addr)?;
         }
 
+        // Queue for all shaped UDP outputs
+        for output in &self.shaped_outputs {
+            output.send(data);
+        }
+
+        // Queue for all SRT outputs
+        for output in &self.srt_outputs {
+            output.send(data);
+        }
+
         // Write to all file outputs
         for file in &mut self.file_outputs {
             file.write_all(data)?;
@@ -185,9 +354,95 @@ impl MultiWriter {
         }
         Ok(())
     }
+
+    /// Block until shaped and SRT outputs can accept `len` bytes (for
+    /// non-live inputs)
+    fn wait_for_space(&self, len: usize) {
+        for output in &self.shaped_outputs {
+            output.wait_for_space(len);
+        }
+        for output in &self.srt_outputs {
+            output.wait_for_space();
+        }
+    }
+
+    /// Block until shaped, SRT and file outputs have sent everything
+    /// buffered
+    fn drain(&self) -> io::Result<()> {
+        for output in &self.shaped_outputs {
+            output.drain();
+        }
+        for output in &self.srt_outputs {
+            output.drain();
+        }
+        self.sync_files()
+    }
+
+    /// Block until file outputs have written everything queued
+    fn sync_files(&self) -> io::Result<()> {
+        for (_, handle) in &self.write_ahead {
+            handle.sync()?;
+        }
+        Ok(())
+    }
+
+    /// Get statistics handles for all shaped outputs
+    fn shaper_stats(&self) -> Vec<ShaperStatsHandle> {
+        self.shaped_outputs
+            .iter()
+            .map(|output| output.stats_handle())
+            .collect()
+    }
+
+    /// Get the SRT outputs
+    fn srt_outputs(&self) -> &[Arc<CallerOutput>] {
+        &self.srt_outputs
+    }
+
+    /// Log statistics for file outputs with a write-ahead queue
+    fn log_file_stats(&self) {
+        for (path, handle) in &self.write_ahead {
+            tracing::info!("File output {}: {}", path, handle.stats());
+        }
+    }
 }
 
-fn main() -> anyhow::Result<()> {
+/// Log statistics for SRT outputs
+fn log_caller_stats(outputs: &[Arc<CallerOutput>]) {
+    for output in outputs {
+        let stats = output.stats();
+        tracing::info!(
+            "SRT output {}: {}, sent={} messages, queued={}, expired={}, connects={}",
+            output.dest(),
+            if stats.connected {
+                "connected"
+            } else {
+                "reconnecting"
+            },
+            stats.messages_sent,
+            stats.queued_messages,
+            stats.expired_messages,
+            stats.connects
+        );
+    }
+}
+
+/// Log statistics for shaped outputs
+fn log_shaper_stats(shapers: &[ShaperStatsHandle]) {
+    for (i, handle) in shapers.iter().enumerate() {
+        let stats = handle.stats();
+        tracing::info!(
+            "Shaper {}: sent={} packets, buffered={} bytes, underflows={}, overflows={}",
+            i,
+            stats.packets_sent,
+            stats.buffered_bytes,
+            stats.underflows,
+            stats.overflows
+        );
+    }
+}
+
+fn main() {
     let args = Args::parse();
 
     // Initialize logging
@@ -196,124 +451,905 @@ fn main() -> anyhow::Result<()> {
 
     tracing::info!("SRT Relay starting...");
     tracing::info!("Input: {}", args.input);
-    tracing::info!("Outputs: {:?}", args.output);
 
-    if args.output.is_empty() {
-        anyhow::bail!("At least one output is required (use --output)");
+    let interrupted = interrupt_flag();
+    let mut summary = RunSummary::new("srt-relay");
+    let start_time = Instant::now();
+    let result = if args.check {
+        check(&args)
+    } else {
+        run(&args, &interrupted, &mut summary)
+    };
+    summary.finish(&result, start_time.elapsed());
+
+    if let Err(e) = &result {
+        eprintln!("Error: {:#}", e);
     }
+    if let Some(path) = &args.summary_json {
+        if let Err(e) = summary.write(path) {
+            tracing::error!("Failed to write summary to {}: {}", path, e);
+        }
+    }
+    std::process::exit(summary.exit_code);
+}
 
-    // Parse input
-    let input_source = parse_input(&args.input)?;
+/// Resolve the configuration and bind the input and UDP outputs briefly,
+/// without relaying (`--check`)
+fn check(args: &Args) -> anyhow::Result<Outcome> {
+    let mut report = CheckReport::new();
+    let input_source = report.check("input", parse_input(&args.input));
+    match &input_source {
+        Some(InputSource::Srt(port)) => {
+            report.bind("input", (Ipv4Addr::UNSPECIFIED, *port).into());
+        }
+        Some(InputSource::Udp(addr)) if multicast::is_multicast(addr) => {
+            let opts = MulticastOptions {
+                interface: args.multicast_iface,
+                ..Default::default()
+            };
+            if report
+                .check("input", multicast::bind_multicast(*addr, &opts))
+                .is_some()
+            {
+                report.entry("input", &args.input);
+            }
+        }
+        Some(InputSource::Udp(addr)) => {
+            report.bind("input", *addr);
+        }
+        Some(InputSource::File(path)) => {
+            let opened = File::open(path).map_err(|e| format!("cannot open '{}': {}", path, e));
+            if report.check("input", opened).is_some() {
+                report.entry("input", path);
+            }
+        }
+        Some(InputSource::Stdin) => report.entry("input", "stdin"),
+        None => {}
+    }
+    let srt_input = matches!(input_source, Some(InputSource::Srt(_)));
+    if args.config.is_some() && !srt_input {
+        report.problem("config: stream routes require SRT input");
+    }
+    if args.config.is_none() && args.output.is_empty() {
+        report.problem("output: at least one output is required (use --output or --config)");
+    }
+    if args.jitter_buffer_ms.is_some() && !matches!(input_source, Some(InputSource::Udp(_))) {
+        report.problem("jitter_buffer_ms: requires UDP input");
+    }
 
-    // Parse outputs
-    let output_dests: Vec<OutputDest> = args
+    let multicast_opts = MulticastOptions {
+        ttl: args.multicast_ttl,
+        interface: args.multicast_iface,
+        ..Default::default()
+    };
+    let mut outputs: Vec<(String, String)> = args
         .output
         .iter()
-        .map(|s| parse_output(s))
-        .collect::<Result<_, _>>()?;
+        .enumerate()
+        .map(|(i, output)| (format!("output.{}", i), output.clone()))
+        .collect();
+    if let Some(path) = &args.config {
+        let key = format!("config {}", path.display());
+        if let Some(config) = report.check(&key, Config::from_file(path)) {
+            report.extend(config.validate());
+            match &config.relay {
+                Some(relay) => {
+                    for route in &relay.streams {
+                        for (i, output) in route.outputs.iter().enumerate() {
+                            let key = format!("relay.streams.{}.{}", route.stream_id, i);
+                            outputs.push((key, output.clone()));
+                        }
+                    }
+                }
+                None => report.problem(format_args!("{}: no [relay] section", key)),
+            }
+        }
+    }
+    check_outputs(&mut report, &outputs, &multicast_opts);
+    if let Some(rate) = args.shape_bitrate {
+        report.entry(
+            "shaping",
+            format_args!("{} bps ({} ms buffer)", rate, args.shape_buffer_ms),
+        );
+    }
+
+    args.transport.check(&mut report, DEFAULT_MSS);
+    if let Some(filter) = &args.packet_filter {
+        if let Some(filter) = report.check("packet_filter", FilterConfig::parse_builtin(filter)) {
+            report.entry("packet_filter", filter);
+        }
+    }
+    for rule in &args.admission.allow {
+        report.entry("allow", rule);
+    }
+    for rule in &args.admission.deny {
+        report.entry("deny", rule);
+    }
+    #[cfg(feature = "otel")]
+    if let Some(Some(config)) = report.check("otel", args.otel.config("srt-relay")) {
+        report.entry("otel", config.service_name);
+    }
+    let labels = args
+        .labels
+        .iter()
+        .fold(Labels::new(), |acc, l| acc.merged(l));
+    if !labels.is_empty() {
+        report.entry("labels", labels);
+    }
 
-    // Create multi-writer
-    let mut writer = MultiWriter::new(output_dests)?;
+    Ok(report.finish()?)
+}
+
+/// Parse each output, binding UDP outputs briefly
+fn check_outputs(report: &mut CheckReport, outputs: &[(String, String)], opts: &MulticastOptions) {
+    for (key, output) in outputs {
+        match report.check(key, parse_output(output)) {
+            Some(OutputDest::Udp(addr)) => {
+                let socket = multicast::bind_sender(addr, opts);
+                if report.check(key, socket).is_some() {
+                    report.entry(key.as_str(), output);
+                }
+            }
+            Some(OutputDest::Srt(..)) => report.entry(key.as_str(), output),
+            Some(OutputDest::File(path)) => report.output_file(key, &path),
+            Some(OutputDest::Stdout) => report.entry(key.as_str(), "stdout"),
+            None => {}
+        }
+    }
+}
+
+fn run(args: &Args, interrupted: &AtomicBool, summary: &mut RunSummary) -> anyhow::Result<Outcome> {
+    // Parse input
+    let input_source = parse_input(&args.input).config()?;
+
+    if args.config.is_some() && !matches!(input_source, InputSource::Srt(_)) {
+        return Err(RunError::Config("Stream routes (--config) require SRT input".into()).into());
+    }
+    if args.config.is_none() && args.output.is_empty() {
+        return Err(RunError::Config(
+            "At least one output is required (use --output or --config)".into(),
+        )
+        .into());
+    }
+    if args.jitter_buffer_ms.is_some() && !matches!(input_source, InputSource::Udp(_)) {
+        return Err(RunError::Config("--jitter-buffer-ms requires UDP input".into()).into());
+    }
+
+    let labels = args
+        .labels
+        .iter()
+        .fold(Labels::new(), |acc, l| acc.merged(l));
+    summary.set_labels(&labels);
+    let _span = labels.span().entered();
+    let file_config = args.config.as_ref().map(Config::from_file).transpose()?;
+    let telemetry = RelayTelemetry::start(args, file_config.as_ref())?;
+
+    let output_opts = OutputOptions {
+        shaping: args
+            .shape_bitrate
+            .map(|rate| (rate, Duration::from_millis(args.shape_buffer_ms))),
+        multicast: MulticastOptions {
+            ttl: args.multicast_ttl,
+            interface: args.multicast_iface,
+            ..Default::default()
+        },
+        callers: Arc::new(CallerPool::new(CallerOptions {
+            transport: args.transport.clone(),
+            policy: args.caller.policy(),
+            peer_cache: Arc::default(),
+        })),
+        write_ahead: args.write_ahead.clone(),
+    };
+    if let Some(rate) = args.shape_bitrate {
+        tracing::info!(
+            "Shaping UDP outputs to {} bps ({} ms buffer)",
+            rate,
+            args.shape_buffer_ms
+        );
+    }
 
     // Handle input based on type
-    match input_source {
+    let result = match input_source {
         InputSource::Srt(port) => {
+            let mut relay_config = match args.config {
+                Some(ref path) => file_config.and_then(|config| config.relay).ok_or_else(|| {
+                    RunError::Config(format!("No [relay] section in {}", path.display()))
+                })?,
+                // Single stream: every caller goes to the command-line outputs
+                None => RelayConfig {
+                    streams: vec![StreamRoute {
+                        stream_id: WILDCARD_STREAM_ID.to_string(),
+                        outputs: args.output.clone(),
+                        labels: Default::default(),
+                    }],
+                    latency_ms: args.transport.latency_ms(),
+                    stats_interval_secs: args.stats,
+                    packet_filter: args.packet_filter.clone(),
+                    single_path_bypass: args.single_path_bypass,
+                    admission: AdmissionPolicy::default(),
+                    passphrase: None,
+                },
+            };
+            relay_config.admission.extend(&args.admission);
+            if let Some(latency) = args.transport.latency {
+                relay_config.latency_ms = latency;
+            }
+            relay_config.single_path_bypass |= args.single_path_bypass;
+            // Routes inherit the command-line labels
+            for route in &mut relay_config.streams {
+                for (key, value) in labels.iter() {
+                    route
+                        .labels
+                        .entry(key.to_string())
+                        .or_insert_with(|| value.to_string());
+                }
+                route.labels().config()?;
+                tracing::info!("Route: '{}' -> {:?}", route.stream_id, route.outputs);
+            }
+
             tracing::info!("Receiving bonded SRT on port {}", port);
-            relay_srt_input(port, args.num_paths, &mut writer, args.stats)?;
+            relay_srt_input(
+                port,
+                args,
+                &relay_config,
+                &output_opts,
+                &telemetry,
+                interrupted,
+                summary,
+            )
+        }
+        input_source => {
+            tracing::info!("Outputs: {:?}", args.output);
+
+            // Parse outputs
+            let output_dests: Vec<OutputDest> = args
+                .output
+                .iter()
+                .map(|s| parse_output(s))
+                .collect::<Result<_, _>>()
+                .config()?;
+            let mut writer = MultiWriter::new(output_dests, &output_opts)?;
+
+            match input_source {
+                InputSource::Udp(addr) => {
+                    tracing::info!("Receiving UDP on {}", addr);
+                    let jitter = args.jitter_buffer_ms.map(|ms| {
+                        tracing::info!("Input jitter buffer: {} ms", ms);
+                        JitterBuffer::new(args.jitter_mode, Duration::from_millis(ms))
+                    });
+                    relay_udp_input(
+                        addr,
+                        &output_opts.multicast,
+                        jitter,
+                        &mut writer,
+                        args.stats,
+                        interrupted,
+                        summary,
+                    )
+                }
+                InputSource::File(path) => {
+                    tracing::info!("Reading from file: {}", path);
+                    let file = File::open(path)?;
+                    relay_read_input(file, &mut writer, interrupted, summary)
+                }
+                InputSource::Stdin => {
+                    tracing::info!("Reading from stdin");
+                    relay_read_input(io::stdin(), &mut writer, interrupted, summary)
+                }
+                InputSource::Srt(_) => unreachable!(),
+            }
+        }
+    };
+
+    // Output held through an outage longer than --reconnect-buffer is lost
+    summary.lost_packets += output_opts
+        .callers
+        .outputs()
+        .iter()
+        .map(|output| output.stats().expired_messages)
+        .sum::<u64>();
+    telemetry.shutdown();
+    result
+}
+
+/// Session metrics and lifecycle spans for OpenTelemetry export
+///
+/// Without the `otel` feature, or without an endpoint, this does nothing.
+#[derive(Default)]
+struct RelayTelemetry {
+    #[cfg(feature = "otel")]
+    exporter: Option<OtelExporter>,
+    /// Groups of the open sessions by route
+    #[cfg(feature = "otel")]
+    groups: Arc<parking_lot::Mutex<HashMap<String, Arc<SocketGroup>>>>,
+}
+
+impl RelayTelemetry {
+    /// Start exporting if --otel-endpoint or a [telemetry] section is set
+    #[cfg(feature = "otel")]
+    fn start(args: &Args, file_config: Option<&Config>) -> anyhow::Result<Self> {
+        let config = match args.otel.config("srt-relay").config()? {
+            Some(config) => Some(config),
+            None => file_config
+                .and_then(|c| c.telemetry.as_ref())
+                .map(|t| OtelConfig::from_telemetry(t, "srt-relay"))
+                .transpose()
+                .config()?,
+        };
+        let groups: Arc<parking_lot::Mutex<HashMap<String, Arc<SocketGroup>>>> = Default::default();
+        let exporter = config.map(|config| {
+            let groups = groups.clone();
+            OtelExporter::start(config, "srt-relay", move || {
+                let mut metrics = Vec::new();
+                for (route, group) in groups.lock().iter() {
+                    metrics.extend(
+                        otel::socket_group_metrics(group)
+                            .into_iter()
+                            .map(|m| m.with_attribute("srt.stream_id", route)),
+                    );
+                }
+                metrics
+            })
+        });
+        Ok(RelayTelemetry { exporter, groups })
+    }
+
+    #[cfg(not(feature = "otel"))]
+    fn start(_args: &Args, _file_config: Option<&Config>) -> anyhow::Result<Self> {
+        Ok(RelayTelemetry::default())
+    }
+
+    /// Include a session's group in the exported metrics
+    fn session_opened(&self, route: &str, group: &Arc<SocketGroup>) {
+        #[cfg(feature = "otel")]
+        self.groups.lock().insert(route.to_string(), group.clone());
+        #[cfg(not(feature = "otel"))]
+        let _ = (route, group);
+    }
+
+    /// Stop exporting a closed session's metrics
+    fn session_closed(&self, route: &str) {
+        #[cfg(feature = "otel")]
+        self.groups.lock().remove(route);
+        #[cfg(not(feature = "otel"))]
+        let _ = route;
+    }
+
+    /// Record an accepted or rejected caller handshake
+    fn handshake(&self, peer: SocketAddr, stream_id: &str, result: Result<(), String>) {
+        #[cfg(feature = "otel")]
+        if let Some(exporter) = &self.exporter {
+            let mut span = SpanBuilder::start("srt.handshake")
+                .attribute("srt.peer", &peer.to_string())
+                .attribute("srt.stream_id", stream_id);
+            if let Err(e) = &result {
+                span = span.attribute("error", e);
+            }
+            exporter.record(span.finish(result.is_ok()));
+        }
+        #[cfg(not(feature = "otel"))]
+        let _ = (peer, stream_id, result);
+    }
+
+    /// Export the final metrics and spans
+    fn shutdown(self) {
+        #[cfg(feature = "otel")]
+        if let Some(exporter) = self.exporter {
+            let stats = exporter.shutdown();
+            tracing::debug!(
+                "OpenTelemetry: {} exports, {} failures",
+                stats.exports,
+                stats.failures
+            );
+        }
+    }
+}
+
+/// One inbound stream: its own bonding group, outputs and counters
+struct Session {
+    /// Route key (the configured Stream ID, or "*")
+    route: String,
+    group: Arc<SocketGroup>,
+    bonding: Arc<BroadcastBonding>,
+    writer: MultiWriter,
+    shapers: Vec<ShaperStatsHandle>,
+    packet_count: u64,
+    delivered_count: u64,
+    total_bytes: u64,
+    retransmissions: u64,
+    lost_packets: u64,
+    start_time: Instant,
+    /// Delivery out of the group, checked with the resync reports
+    watchdog: DeliveryWatchdog,
+}
+
+impl Session {
+    fn new(
+        route: &StreamRoute,
+        max_paths: usize,
+        rcvbuf_packets: usize,
+        opts: &OutputOptions,
+        watchdog: DeliveryWatchdog,
+    ) -> anyhow::Result<Self> {
+        let outputs: Vec<OutputDest> = route
+            .outputs
+            .iter()
+            .map(|s| parse_output(s))
+            .collect::<Result<_, _>>()
+            .config()?;
+        let writer = MultiWriter::new(outputs, opts)?;
+        let group = Arc::new(SocketGroup::new(1, GroupType::Broadcast, max_paths));
+        group.set_labels(route.labels().config()?);
+        let bonding = Arc::new(BroadcastBonding::with_receive_buffer(
+            group.clone(),
+            rcvbuf_packets,
+        ));
+
+        Ok(Session {
+            route: route.stream_id.clone(),
+            group,
+            bonding,
+            shapers: writer.shaper_stats(),
+            writer,
+            packet_count: 0,
+            delivered_count: 0,
+            total_bytes: 0,
+            retransmissions: 0,
+            lost_packets: 0,
+            start_time: Instant::now(),
+            watchdog,
+        })
+    }
+
+    /// Check delivery out of the group for a stall
+    ///
+    /// A stall is logged and, with `--stall-action resync`, the gap it
+    /// waits on is skipped. Returns true if the session should close.
+    fn check_stall(&mut self) -> bool {
+        let stats = self.bonding.receiver.stats();
+        let Some(stalled_for) = self.watchdog.observe(
+            stats.next_expected,
+            self.total_bytes,
+            stats.buffered_packets > 0,
+            Instant::now(),
+        ) else {
+            return false;
+        };
+        let _span = self.group.span().entered();
+        tracing::warn!(
+            "Stream '{}' stalled for {:?} at {} ({} packets buffered behind it, {} paths active); action: {}",
+            self.route,
+            stalled_for,
+            stats.next_expected.as_raw(),
+            stats.buffered_packets,
+            self.group.active_member_count(),
+            self.watchdog.action()
+        );
+        match self.watchdog.action() {
+            StallAction::Resync => {
+                self.bonding.receiver.skip_to_buffered();
+                false
+            }
+            StallAction::Close => true,
+            // The sender picks the paths; nothing to fail over to here
+            StallAction::Log | StallAction::Failover => false,
         }
-        InputSource::Udp(port) => {
-            tracing::info!("Receiving UDP on port {}", port);
-            relay_udp_input(port, &mut writer, args.stats)?;
+    }
+
+    /// Write all packets that are ready to the outputs
+    fn flush_ready(&mut self) -> io::Result<()> {
+        while let Some(packet) = self.bonding.receiver.pop_ready_packet() {
+            self.writer.write_all(&packet.payload)?;
+            self.total_bytes += packet.payload.len() as u64;
+            self.delivered_count += 1;
         }
-        InputSource::File(path) => {
-            tracing::info!("Reading from file: {}", path);
-            relay_file_input(&path, &mut writer)?;
+        Ok(())
+    }
+
+    /// Write out the remaining tail when the stream ends
+    ///
+    /// Packets held back behind gaps that will no longer be filled are
+    /// written; the gaps are reported.
+    fn finish(&mut self) -> io::Result<()> {
+        let _span = self.group.span().entered();
+        self.flush_ready()?;
+        let drain = self.bonding.receiver.drain();
+        for packet in &drain.packets {
+            self.writer.write_all(&packet.payload)?;
+            self.total_bytes += packet.payload.len() as u64;
+            self.delivered_count += 1;
         }
-        InputSource::Stdin => {
-            tracing::info!("Reading from stdin");
-            relay_stdin_input(&mut writer)?;
+        self.writer.flush()?;
+        self.writer.sync_files()?;
+
+        let lost: usize = drain.gaps.iter().map(|g| g.len()).sum();
+        let receiver_stats = self.bonding.receiver.stats();
+        self.lost_packets +=
+            lost as u64 + receiver_stats.skipped_packets + receiver_stats.dropped_packets;
+        if lost > 0 {
+            tracing::warn!(
+                "Stream '{}': {} packets unrecoverable in {} gaps",
+                self.route,
+                lost,
+                drain.gaps.len()
+            );
         }
+        self.log_stats();
+        Ok(())
+    }
+
+    /// Add this session's totals to the run summary
+    fn add_to(&self, summary: &mut RunSummary) {
+        summary.bytes += self.total_bytes;
+        summary.packets += self.delivered_count;
+        summary.retransmissions += self.retransmissions;
+        summary.lost_packets += self.lost_packets;
     }
 
-    Ok(())
+    fn log_stats(&self) {
+        let _span = self.group.span().entered();
+        let stats = self.bonding.stats();
+        let elapsed = self.start_time.elapsed().as_secs_f64();
+        let mbps = (self.total_bytes as f64 * 8.0) / (elapsed * 1_000_000.0);
+        tracing::info!(
+            "Stream '{}': {} members, {} packets, {:.2} MB, {:.2} Mbps, buffered={}, ready={}",
+            self.route,
+            stats.group_stats.member_count,
+            self.packet_count,
+            self.total_bytes as f64 / 1_000_000.0,
+            mbps,
+            stats.receiver_stats.buffered_packets,
+            stats.receiver_stats.ready_packets
+        );
+        log_shaper_stats(&self.shapers);
+        log_caller_stats(self.writer.srt_outputs());
+        self.writer.log_file_stats();
+    }
 }
 
 /// Relay SRT input to outputs
+///
+/// Each caller is routed by the Stream ID in its handshake to a session
+/// with its own bonding group and outputs. Paths of a bonded caller share
+/// a Stream ID and therefore land in the same session.
 fn relay_srt_input(
     port: u16,
-    num_paths: usize,
-    writer: &mut MultiWriter,
-    stats_interval: u64,
-) -> anyhow::Result<()> {
+    args: &Args,
+    relay_config: &RelayConfig,
+    output_opts: &OutputOptions,
+    telemetry: &RelayTelemetry,
+    interrupted: &AtomicBool,
+    summary: &mut RunSummary,
+) -> anyhow::Result<Outcome> {
+    let transport = &args.transport;
+    let max_paths = args.num_paths;
+    let rcvbuf_packets = transport.rcvbuf_packets(DEFAULT_MSS)?;
+
     // Create SRT receiver
     let listen_addr: SocketAddr = format!("0.0.0.0:{}", port).parse()?;
-    let socket = SrtSocket::bind(listen_addr)?;
-    tracing::info!("Listening on: {}", socket.local_addr()?);
-
-    // Create socket group and bonding
-    let group = Arc::new(SocketGroup::new(1, GroupType::Broadcast, num_paths));
-    let bonding = Arc::new(BroadcastBonding::new(group.clone()));
-
-    // Track remote addresses to member IDs
-    let addr_to_member: HashMap<SocketAddr, u32> = HashMap::new();
-
-    // Statistics thread
-    let bonding_stats = bonding.clone();
-    if stats_interval > 0 {
-        thread::spawn(move || loop {
-            thread::sleep(Duration::from_secs(stats_interval));
-            let stats = bonding_stats.stats();
-            tracing::info!(
-                "Stats: {} members, buffered={}, ready={}",
-                stats.group_stats.member_count,
-                stats.receiver_stats.buffered_packets,
-                stats.receiver_stats.ready_packets
-            );
-        });
-    }
+    let mut listener = SrtListener::bind(listen_addr)?;
+    listener.set_early_data_limit(args.early_data_packets);
+    transport.trace_socket(listener.socket_mut());
+    tracing::info!("Listening on: {}", listener.local_addr()?);
+
+    // Sessions by route, and accepted socket IDs (also member IDs) to route
+    let mut sessions: HashMap<String, Session> = HashMap::new();
+    let mut member_routes: HashMap<u32, String> = HashMap::new();
+
+    let stats_interval = relay_config.stats_interval();
+    let mut last_stats = Instant::now();
+
+    // Periodic expected-sequence reports let senders detect drift
+    let mut resync_timer = Timer::new(Duration::from_secs(1));
 
     // Main receive loop
-    let mut buffer = vec![0u8; 2048];
-    let mut total_bytes = 0u64;
-    let mut packet_count = 0u64;
-    let start_time = Instant::now();
+    let mut buffer = vec![0u8; MAX_MSS];
 
     tracing::info!("Ready to receive and relay packets...");
 
     loop {
-        // Receive packet
-        let (n, remote_addr) = match socket.recv_from(&mut buffer) {
-            Ok(result) => result,
-            Err(e) => {
-                if let srt_io::SocketError::Io(ref io_err) = e {
-                    if io_err.kind() == io::ErrorKind::WouldBlock {
-                        thread::sleep(Duration::from_micros(100));
-
-                        // Try to pop ready packets
-                        while let Some(packet) = bonding.receiver.pop_ready_packet() {
-                            writer.write_all(&packet.payload)?;
-                            total_bytes += packet.payload.len() as u64;
-                        }
+        // Write out every open stream before stopping
+        if interrupted.load(Ordering::Relaxed) {
+            for (_, mut session) in sessions.drain() {
+                session.finish()?;
+                session.add_to(summary);
+            }
+            return Ok(Outcome::Interrupted);
+        }
 
-                        continue;
+        if !stats_interval.is_zero() && last_stats.elapsed() >= stats_interval {
+            for session in sessions.values() {
+                session.log_stats();
+            }
+            let early = listener.stats();
+            if early.early_held > 0 {
+                tracing::info!(
+                    "Early data: {} held, {} delivered, {} expired, {} dropped",
+                    early.early_held,
+                    early.early_delivered,
+                    early.early_expired,
+                    early.early_dropped
+                );
+            }
+            last_stats = Instant::now();
+        }
+
+        if resync_timer.try_fire() {
+            for session in sessions.values() {
+                session.group.check_timeouts();
+            }
+            let stalled: Vec<String> = sessions
+                .iter_mut()
+                .filter_map(|(route, session)| session.check_stall().then(|| route.clone()))
+                .collect();
+            for route in stalled {
+                let Some(mut session) = sessions.remove(&route) else {
+                    continue;
+                };
+                tracing::info!("Closing stalled session for stream '{}'", route);
+                member_routes.retain(|id, member_route| {
+                    if *member_route != route {
+                        return true;
+                    }
+                    if let Some(member) = session.group.get_member(*id) {
+                        let shutdown = member.connection.create_shutdown();
+                        let _ = listener.send_to(*id, &shutdown.to_bytes());
+                        member.connection.begin_close();
                     }
+                    listener.remove(*id);
+                    false
+                });
+                telemetry.session_closed(&route);
+                session.finish()?;
+                session.add_to(summary);
+            }
+            for (id, route) in &member_routes {
+                if let Some(session) = sessions.get(route) {
+                    let report = session.bonding.receiver.resync_report();
+                    let _ = listener.send_to(*id, &report.to_control_packet(0, 0).to_bytes());
+                    let status = session.bonding.status_report();
+                    let _ = listener.send_to(*id, &status.to_control_packet(0, 0).to_bytes());
+                    if let Some(member) = session.group.get_member(*id) {
+                        while let Some(keepalive) = member.connection.create_keepalive() {
+                            let _ = listener.send_to(*id, &keepalive.to_bytes());
+                        }
+                    }
+                }
+            }
+        }
+
+        // Receive packet
+        let (n, event) = match listener.recv(&mut buffer) {
+            Ok(Some(received)) => received,
+            Ok(None) => {
+                thread::sleep(Duration::from_micros(100));
+
+                // Try to pop ready packets
+                for session in sessions.values_mut() {
+                    session.flush_ready()?;
                 }
+
+                continue;
+            }
+            Err(e) => {
                 tracing::error!("Receive error: {}", e);
                 continue;
             }
         };
 
-        // Get member ID for this remote address - reject if not handshaked
-        let member_id = match addr_to_member.get(&remote_addr) {
-            Some(id) => *id,
-            None => {
+        let (member_id, remote_addr) = match event {
+            ListenerEvent::Packet { socket_id, peer } => (socket_id, peer),
+            ListenerEvent::Handshake(request) => {
+                let remote_addr = request.peer();
+                let stream_id = request.stream_id().unwrap_or_default().to_string();
+                let Some(route) = relay_config.route(&stream_id) else {
+                    tracing::warn!(
+                        "Rejecting {}: no route for stream '{}'",
+                        remote_addr,
+                        stream_id
+                    );
+                    telemetry.handshake(remote_addr, &stream_id, Err("no route".into()));
+                    continue;
+                };
+
+                let session = match sessions.get_mut(&route.stream_id) {
+                    Some(session) => session,
+                    None => {
+                        tracing::info!(
+                            "Opening session for stream '{}' -> {:?}",
+                            route.stream_id,
+                            route.outputs
+                        );
+                        let session = Session::new(
+                            route,
+                            max_paths,
+                            rcvbuf_packets,
+                            output_opts,
+                            transport.stall_watchdog(),
+                        )?;
+                        session
+                            .bonding
+                            .set_single_path_bypass(relay_config.single_path_bypass);
+                        relay_config.admission.install(&session.group);
+                        telemetry.session_opened(&route.stream_id, &session.group);
+                        sessions.entry(route.stream_id.clone()).or_insert(session)
+                    }
+                };
+
+                // Refused peers get no reply
+                let admission = AdmissionRequest {
+                    address: remote_addr,
+                    socket_id: Some(request.handshake().udt.socket_id),
+                    stream_id: request.stream_id().map(str::to_string),
+                };
+                if let Err(e) = session.group.check_admission(&admission) {
+                    tracing::warn!(
+                        "Rejecting {} for stream '{}': {}",
+                        remote_addr,
+                        stream_id,
+                        e
+                    );
+                    telemetry.handshake(remote_addr, &stream_id, Err(e.to_string()));
+                    continue;
+                }
+
+                let id = request.socket_id();
+                let mut conn = Connection::new(
+                    id,
+                    listener.local_addr()?,
+                    remote_addr,
+                    SeqNumber::new(0),
+                    relay_config.latency_ms,
+                );
+                transport.apply(&mut conn)?;
+                // Configured latency, already overridden by --latency
+                conn.set_latency(relay_config.latency_ms).config()?;
+                conn.set_packet_filter(relay_config.packet_filter.as_deref())
+                    .config()?;
+                conn.set_labels(
+                    session
+                        .group
+                        .labels()
+                        .with("path", &remote_addr.to_string())?,
+                );
+                let conn = match listener.accept(request, conn) {
+                    Ok(conn) => conn,
+                    Err(e) => {
+                        tracing::warn!(
+                            "Rejecting {} for stream '{}': {}",
+                            remote_addr,
+                            stream_id,
+                            e
+                        );
+                        telemetry.handshake(remote_addr, &stream_id, Err(e.to_string()));
+                        continue;
+                    }
+                };
+
+                if let Err(e) = session.group.add_member(conn, remote_addr) {
+                    tracing::warn!(
+                        "Rejecting {} for stream '{}': {}",
+                        remote_addr,
+                        stream_id,
+                        e
+                    );
+                    telemetry.handshake(remote_addr, &stream_id, Err(e.to_string()));
+                    listener.close(id);
+                    continue;
+                }
+                let _ = session.group.update_member_status(id, MemberStatus::Active);
+                telemetry.handshake(remote_addr, &stream_id, Ok(()));
+
+                member_routes.insert(id, session.route.clone());
+                tracing::info!(
+                    "New path {} for stream '{}' (member {})",
+                    remote_addr,
+                    stream_id,
+                    id
+                );
+                continue;
+            }
+            ListenerEvent::Repeated { .. } => continue,
+            ListenerEvent::Early { peer } => {
+                tracing::debug!(
+                    "Holding packet from {} until its handshake is accepted",
+                    peer
+                );
+                continue;
+            }
+            ListenerEvent::Unknown { peer } => {
                 tracing::warn!(
-                    "Received data from {} without handshake, ignoring packet",
-                    remote_addr
+                    "Received packet from {} without handshake, ignoring it",
+                    peer
                 );
                 continue;
             }
         };
+        let Some(route) = member_routes.get(&member_id).cloned() else {
+            continue;
+        };
+
+        if n >= 16 && (buffer[0] & 0x80) != 0 {
+            let control = srt_protocol::ControlPacket::from_bytes(&buffer[..n]).ok();
+
+            // Sender resync announcement: fast-forward that stream
+            if let Some(message) = control
+                .as_ref()
+                .and_then(ResyncMessage::from_control_packet)
+            {
+                if let Some(session) = sessions.get(&route) {
+                    session.bonding.receiver.on_resync(message);
+                }
+                continue;
+            }
+
+            // Sender gave up on a range; stop waiting for it
+            if let Some(drop) = control
+                .as_ref()
+                .filter(|c| c.control_type() == ControlType::DropReq)
+            {
+                if let Some((session, member)) = sessions
+                    .get(&route)
+                    .and_then(|session| Some((session, session.group.get_member(member_id)?)))
+                {
+                    if let Err(e) = member.connection.process_control(drop) {
+                        tracing::debug!("Ignoring DropReq from {}: {}", remote_addr, e);
+                    }
+                    session
+                        .bonding
+                        .receiver
+                        .take_member_drops(&member.connection);
+                }
+                continue;
+            }
+
+            // Keep-alive from a sender: echo it for its RTT measurement
+            if let Some(keepalive) = control
+                .as_ref()
+                .filter(|c| c.control_type() == ControlType::KeepAlive)
+            {
+                if let Some(member) = sessions
+                    .get(&route)
+                    .and_then(|session| session.group.get_member(member_id))
+                {
+                    let _ = member.connection.process_control(keepalive);
+                    while let Some(reply) = member.connection.create_keepalive() {
+                        let _ = listener.send_to(member_id, &reply.to_bytes());
+                    }
+                }
+                continue;
+            }
+
+            // Sender finished on this path; close the session once all are done
+            if control
+                .as_ref()
+                .is_some_and(|c| c.control_type() == ControlType::Shutdown)
+            {
+                member_routes.remove(&member_id);
+                listener.remove(member_id);
+                tracing::info!("Path {} for stream '{}' shut down", remote_addr, route);
+                if let Some(session) = sessions.get_mut(&route) {
+                    if let Some(member) = session.group.get_member(member_id) {
+                        member.connection.begin_close();
+                    }
+                    let _ = session.group.remove_member(member_id);
+
+                    if session.group.member_count() == 0 {
+                        tracing::info!("Closing session for stream '{}'", route);
+                        if let Some(mut session) = sessions.remove(&route) {
+                            telemetry.session_closed(&route);
+                            session.finish()?;
+                            session.add_to(summary);
+                        }
+                    }
+                }
+            }
+            continue;
+        }
+
+        let Some(session) = sessions.get_mut(&route) else {
+            continue;
+        };
 
         // Deserialize and process packet
         let packet = match DataPacket::from_bytes(&buffer[..n]) {
@@ -324,24 +1360,22 @@ fn relay_srt_input(
             }
         };
 
-        match bonding.receiver.on_packet_received(packet, member_id) {
+        if packet.msg_number().retransmitted {
+            session.retransmissions += 1;
+        }
+        match session.bonding.on_receive(packet, member_id) {
             Ok(_) => {
-                packet_count += 1;
+                session.packet_count += 1;
 
                 // Pop all ready packets and write to outputs
-                while let Some(ready_packet) = bonding.receiver.pop_ready_packet() {
-                    writer.write_all(&ready_packet.payload)?;
-                    total_bytes += ready_packet.payload.len() as u64;
-                }
+                session.flush_ready()?;
 
-                if packet_count % 100 == 0 {
-                    let elapsed = start_time.elapsed().as_secs_f64();
-                    let mbps = (total_bytes as f64 * 8.0) / (elapsed * 1_000_000.0);
+                if session.packet_count % 100 == 0 {
                     tracing::debug!(
-                        "Relayed {} packets, {:.2} MB, {:.2} Mbps",
-                        packet_count,
-                        total_bytes as f64 / 1_000_000.0,
-                        mbps
+                        "Stream '{}': relayed {} packets, {:.2} MB",
+                        session.route,
+                        session.packet_count,
+                        session.total_bytes as f64 / 1_000_000.0
                     );
                 }
             }
@@ -351,113 +1385,160 @@ fn relay_srt_input(
         }
 
         // Flush periodically
-        if packet_count % 50 == 0 {
-            writer.flush()?;
+        if session.packet_count % 50 == 0 {
+            session.writer.flush()?;
         }
     }
 }
 
-/// Relay UDP input to outputs
-fn relay_udp_input(port: u16, writer: &mut MultiWriter, stats_interval: u64) -> anyhow::Result<()> {
-    let listen_addr = format!("0.0.0.0:{}", port);
-    let socket = UdpSocket::bind(&listen_addr)?;
+/// Log jitter buffer statistics
+fn log_jitter_stats(stats: &JitterStats) {
+    tracing::info!(
+        "Jitter buffer: buffered={}, late={}, dropped={}, duplicates={}",
+        stats.buffered,
+        stats.late,
+        stats.dropped,
+        stats.duplicates
+    );
+}
+
+/// Relay UDP input to outputs, optionally through a jitter buffer
+fn relay_udp_input(
+    addr: SocketAddr,
+    multicast_opts: &MulticastOptions,
+    mut jitter: Option<JitterBuffer>,
+    writer: &mut MultiWriter,
+    stats_interval: u64,
+    interrupted: &AtomicBool,
+    summary: &mut RunSummary,
+) -> anyhow::Result<Outcome> {
+    let socket = if multicast::is_multicast(&addr) {
+        multicast::bind_multicast(addr, multicast_opts)?
+    } else {
+        UdpSocket::bind(addr)?
+    };
     socket.set_nonblocking(true)?;
-    tracing::info!("UDP listening on: {}", listen_addr);
+    tracing::info!("UDP listening on: {}", socket.local_addr()?);
 
     let mut buffer = vec![0u8; 65536];
     let mut total_bytes = 0u64;
     let mut packet_count = 0u64;
     let start_time = Instant::now();
     let mut last_stats = Instant::now();
+    let shapers = writer.shaper_stats();
 
-    loop {
-        match socket.recv(&mut buffer) {
-            Ok(n) => {
-                // Write to all outputs
-                writer.write_all(&buffer[..n])?;
-
-                total_bytes += n as u64;
-                packet_count += 1;
-
-                if packet_count % 50 == 0 {
-                    writer.flush()?;
-                }
-
-                // Print stats
-                if stats_interval > 0 && last_stats.elapsed() >= Duration::from_secs(stats_interval)
-                {
-                    let elapsed = start_time.elapsed().as_secs_f64();
-                    let mbps = (total_bytes as f64 * 8.0) / (elapsed * 1_000_000.0);
-                    tracing::info!(
-                        "Relayed {} packets, {:.2} MB, {:.2} Mbps",
-                        packet_count,
-                        total_bytes as f64 / 1_000_000.0,
-                        mbps
-                    );
-                    last_stats = Instant::now();
-                }
-            }
-            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
-                thread::sleep(Duration::from_micros(100));
-            }
+    while !interrupted.load(Ordering::Relaxed) {
+        let received = match socket.recv(&mut buffer) {
+            Ok(n) => Some(n),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => None,
             Err(e) => {
                 tracing::error!("Receive error: {}", e);
                 return Err(e.into());
             }
+        };
+
+        // Datagrams due for output: straight from the socket, or whatever
+        // the jitter buffer releases
+        let mut ready = Vec::new();
+        match (&mut jitter, received) {
+            (None, Some(n)) => ready.push(buffer[..n].to_vec()),
+            (None, None) => {}
+            (Some(jitter), received) => {
+                let now = Instant::now();
+                if let Some(n) = received {
+                    jitter.push(&buffer[..n], now);
+                }
+                ready.extend(std::iter::from_fn(|| jitter.poll(now)));
+            }
         }
-    }
-}
 
-/// Relay file input to outputs
-fn relay_file_input(path: &str, writer: &mut MultiWriter) -> anyhow::Result<()> {
-    use std::io::Read;
+        for data in &ready {
+            // Write to all outputs
+            writer.write_all(data)?;
 
-    let mut file = std::fs::File::open(path)?;
-    let mut buffer = vec![0u8; 8192];
+            total_bytes += data.len() as u64;
+            packet_count += 1;
 
-    loop {
-        match file.read(&mut buffer) {
-            Ok(0) => {
-                tracing::info!("End of file reached");
-                break;
+            if packet_count % 50 == 0 {
+                writer.flush()?;
             }
-            Ok(n) => {
-                writer.write_all(&buffer[..n])?;
-            }
-            Err(e) => {
-                tracing::error!("Read error: {}", e);
-                return Err(e.into());
+        }
+
+        // Print stats
+        if stats_interval > 0 && last_stats.elapsed() >= Duration::from_secs(stats_interval) {
+            let elapsed = start_time.elapsed().as_secs_f64();
+            let mbps = (total_bytes as f64 * 8.0) / (elapsed * 1_000_000.0);
+            tracing::info!(
+                "Relayed {} packets, {:.2} MB, {:.2} Mbps",
+                packet_count,
+                total_bytes as f64 / 1_000_000.0,
+                mbps
+            );
+            if let Some(jitter) = &jitter {
+                log_jitter_stats(&jitter.stats());
             }
+            log_shaper_stats(&shapers);
+            log_caller_stats(writer.srt_outputs());
+            writer.log_file_stats();
+            last_stats = Instant::now();
+        }
+
+        if received.is_none() {
+            thread::sleep(Duration::from_micros(100));
         }
     }
 
+    // Flush what the jitter buffer still holds
+    if let Some(jitter) = &mut jitter {
+        for data in jitter.drain() {
+            writer.write_all(&data)?;
+            total_bytes += data.len() as u64;
+            packet_count += 1;
+        }
+        let stats = jitter.stats();
+        log_jitter_stats(&stats);
+        summary.lost_packets = stats.dropped + stats.late;
+    }
+
     writer.flush()?;
-    Ok(())
+    writer.drain()?;
+    summary.bytes = total_bytes;
+    summary.packets = packet_count;
+    Ok(Outcome::Interrupted)
 }
 
-/// Relay stdin to outputs
-fn relay_stdin_input(writer: &mut MultiWriter) -> anyhow::Result<()> {
-    use std::io::Read;
-
-    let mut stdin = io::stdin();
+/// Relay a file or stdin to outputs
+fn relay_read_input(
+    mut input: impl io::Read,
+    writer: &mut MultiWriter,
+    interrupted: &AtomicBool,
+    summary: &mut RunSummary,
+) -> anyhow::Result<Outcome> {
     let mut buffer = vec![0u8; 8192];
 
-    loop {
-        match stdin.read(&mut buffer) {
+    let outcome = loop {
+        if interrupted.load(Ordering::Relaxed) {
+            break Outcome::Interrupted;
+        }
+        match input.read(&mut buffer) {
             Ok(0) => {
                 tracing::info!("End of input reached");
-                break;
+                break Outcome::StreamEnded;
             }
             Ok(n) => {
+                writer.wait_for_space(n);
                 writer.write_all(&buffer[..n])?;
+                summary.bytes += n as u64;
+                summary.packets += 1;
             }
             Err(e) => {
                 tracing::error!("Read error: {}", e);
                 return Err(e.into());
             }
         }
-    }
+    };
 
     writer.flush()?;
-    Ok(())
+    writer.drain()?;
+    Ok(outcome)
 }
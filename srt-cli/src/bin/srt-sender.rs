@@ -2,17 +2,26 @@
 //!
 //! Reads from stdin, file, or UDP/SRT input and sends over multiple SRT paths with bonding.
 
-use bytes::Bytes;
 use clap::Parser;
 use srt_bonding::*;
+use srt_cli::framing::{Framing, MessageReader};
+#[cfg(feature = "otel")]
+use srt_cli::otel::{self, OtelArgs, SpanBuilder};
+use srt_cli::summary::{interrupt_flag, ConfigResultExt, RunError};
+use srt_cli::transport::TransportArgs;
+use srt_cli::{BondingMode, CheckReport, Outcome, RunSummary};
 use srt_io::SrtSocket;
-use srt_protocol::{Connection, DataPacket, MsgNumber, SeqNumber, SrtHandshake};
+use srt_protocol::packet::ControlType;
+use srt_protocol::{
+    Connection, ControlPacket, Labels, Packetizer, ProbePlan, ProbeReport, SeqNumber, SrtHandshake,
+};
 use std::fs::File;
 use std::io::{self, BufReader, Read, Write};
-use std::net::SocketAddr;
+use std::net::{AddrParseError, Ipv4Addr, SocketAddr};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::thread;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime};
 
 #[derive(Parser, Debug)]
 #[command(name = "srt-sender")]
@@ -22,6 +31,11 @@ struct Args {
     #[arg(short, long, default_value = "-")]
     input: String,
 
+    /// Split stdin or file input into messages (none, length-prefixed,
+    /// mpegts, newline) so message boundaries reach the receiver
+    #[arg(long, default_value = "none")]
+    framing: Framing,
+
     /// Bonding mode (broadcast, backup, balancing)
     #[arg(short = 'g', long, default_value = "broadcast")]
     group: String,
@@ -35,6 +49,28 @@ struct Args {
     #[arg(short, long)]
     bind: Vec<String>,
 
+    /// Maximum segment size in bytes (including IP/UDP headers)
+    #[arg(long, default_value = "1500")]
+    mss: usize,
+
+    /// Use connected UDP sockets so ICMP errors (e.g. port unreachable)
+    /// mark a path broken immediately
+    #[arg(long)]
+    connected: bool,
+
+    /// Stream ID sent in the handshake (selects the stream on a relay)
+    #[arg(long)]
+    stream_id: Option<String>,
+
+    /// Packet filter configuration (e.g. "fec,cols:10,rows:5")
+    #[arg(long)]
+    packet_filter: Option<String>,
+
+    /// Measure each path's capacity with a short probe burst right after
+    /// the handshake, so bonding starts from measured values
+    #[arg(long)]
+    probe: bool,
+
     /// FEC overhead percentage
     #[arg(long, default_value = "0")]
     fec_overhead: u8,
@@ -43,6 +79,26 @@ struct Args {
     #[arg(long, default_value = "1")]
     stats: u64,
 
+    #[command(flatten)]
+    transport: TransportArgs,
+
+    #[cfg(feature = "otel")]
+    #[command(flatten)]
+    otel: OtelArgs,
+
+    /// Labels for stats, logs and the run summary (e.g. "site=nyc,camera=3")
+    #[arg(long, value_name = "KEY=VALUE,...")]
+    labels: Vec<Labels>,
+
+    /// Write a JSON run summary to this file on exit
+    #[arg(long, value_name = "PATH")]
+    summary_json: Option<String>,
+
+    /// Check the configuration and exit: resolve each path, bind its socket
+    /// for a moment and print the resolved settings without connecting
+    #[arg(long)]
+    check: bool,
+
     /// Verbose output
     #[arg(short, long)]
     verbose: bool,
@@ -55,6 +111,122 @@ enum InputSource {
     Udp(SocketAddr),
 }
 
+/// Get the local address for path `idx`
+///
+/// A `--bind` without a port gets a random one; without `--bind` the
+/// loopback or wildcard address is used, matching the remote.
+fn bind_addr(
+    args: &Args,
+    idx: usize,
+    remote_addr: SocketAddr,
+) -> Result<SocketAddr, AddrParseError> {
+    match args.bind.get(idx) {
+        // Full address with port
+        Some(bind_str) if bind_str.contains(':') => bind_str.parse(),
+        // Just IP, use port 0 (random)
+        Some(bind_str) => format!("{}:0", bind_str).parse(),
+        None if remote_addr.ip().is_loopback() => Ok((Ipv4Addr::LOCALHOST, 0).into()),
+        None => Ok((Ipv4Addr::UNSPECIFIED, 0).into()),
+    }
+}
+
+/// Send on a path, using the connected socket's peer when connected
+fn send_on_path(
+    socket: &SrtSocket,
+    buf: &[u8],
+    remote_addr: SocketAddr,
+) -> Result<usize, srt_io::SocketError> {
+    if socket.is_connected() {
+        socket.send(buf)
+    } else {
+        socket.send_to(buf, remote_addr)
+    }
+}
+
+/// Answer receiver feedback arriving on any path
+///
+/// If a receiver's expected sequence has drifted from `next_seq`, the
+/// current sequence is announced on all paths so the receiver fast-forwards.
+/// Path status reports are applied to the group for path selection.
+/// Keep-alives are answered and sent here too, keeping each path's liveness
+/// and RTT current, and paths whose receiver has gone silent for the peer
+/// idle timeout are marked broken. A path's probe burst goes out here
+/// once its handshake agreed to one.
+fn poll_resync_reports(
+    sockets: &[(SrtSocket, SocketAddr, Arc<Connection>)],
+    group: &SocketGroup,
+    next_seq: SeqNumber,
+    buf: &mut [u8],
+) {
+    let mut announce = false;
+    for (socket, _, conn) in sockets {
+        while let Ok((n, _)) = socket.recv_from(buf) {
+            let Ok(packet) = ControlPacket::from_bytes(&buf[..n]) else {
+                continue;
+            };
+            if let Some(ResyncMessage::Report(expected)) =
+                ResyncMessage::from_control_packet(&packet)
+            {
+                if is_drifted(next_seq, expected, DEFAULT_DRIFT_TOLERANCE) {
+                    tracing::warn!(
+                        "Receiver expects sequence {}, sender is at {}; resyncing",
+                        expected.as_raw(),
+                        next_seq.as_raw()
+                    );
+                    announce = true;
+                }
+            } else if let Some(report) = StatusReport::from_control_packet(&packet) {
+                let updated = group.apply_status_report(&report);
+                tracing::debug!(
+                    "Receiver status: {} paths, {} packets buffered",
+                    updated,
+                    report.buffered_packets
+                );
+            } else if matches!(
+                packet.control_type(),
+                ControlType::UserDefined | ControlType::KeepAlive
+            ) {
+                // Rate ceiling requests, probe reports and keep-alives
+                // from the receiver
+                if let Err(e) = conn.process_control(&packet) {
+                    tracing::debug!("Ignoring control packet: {}", e);
+                }
+                if ProbeReport::from_control_packet(&packet).is_some() {
+                    if let Some(result) = conn.probe_result() {
+                        tracing::info!(
+                            "Probe to {}: {}/{} packets, bandwidth {}",
+                            conn.remote_addr(),
+                            result.packets_received,
+                            result.packets_sent,
+                            result.bandwidth_bps.map_or("unknown".to_string(), |bw| {
+                                format!("{:.1} Mbps", bw as f64 * 8.0 / 1_000_000.0)
+                            })
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    group.check_timeouts();
+    for (socket, remote_addr, conn) in sockets {
+        while let Some(keepalive) = conn.create_keepalive() {
+            let _ = send_on_path(socket, &keepalive.to_bytes(), *remote_addr);
+        }
+        for probe in conn.create_probe_packets() {
+            let _ = send_on_path(socket, &probe.to_bytes(), *remote_addr);
+        }
+    }
+
+    if announce {
+        for (socket, remote_addr, conn) in sockets {
+            let packet = ResyncMessage::Announce(next_seq)
+                .to_control_packet(0, conn.remote_socket_id().unwrap_or(0));
+            let _ = send_on_path(socket, &packet.to_bytes(), *remote_addr);
+        }
+    }
+}
+
 fn parse_input(input: &str) -> anyhow::Result<InputSource> {
     if input == "-" {
         Ok(InputSource::Stdin)
@@ -71,7 +243,10 @@ fn parse_input(input: &str) -> anyhow::Result<InputSource> {
     }
 }
 
-fn create_input_reader(source: InputSource) -> anyhow::Result<Box<dyn Read + Send>> {
+fn create_input_reader(
+    source: InputSource,
+    interrupted: &Arc<AtomicBool>,
+) -> anyhow::Result<Box<dyn Read + Send>> {
     match source {
         InputSource::Stdin => {
             tracing::info!("Creating stdin reader");
@@ -84,22 +259,24 @@ fn create_input_reader(source: InputSource) -> anyhow::Result<Box<dyn Read + Sen
         InputSource::Udp(addr) => {
             tracing::info!("Creating UDP reader for {}", addr);
             let socket = SrtSocket::bind(addr)?;
-            Ok(Box::new(UdpReader::new(socket)))
+            Ok(Box::new(UdpReader::new(socket, interrupted.clone())))
         }
     }
 }
 
 struct UdpReader {
     socket: SrtSocket,
+    interrupted: Arc<AtomicBool>,
     buffer: Vec<u8>,
     buffer_pos: usize,
     buffer_len: usize,
 }
 
 impl UdpReader {
-    fn new(socket: SrtSocket) -> Self {
+    fn new(socket: SrtSocket, interrupted: Arc<AtomicBool>) -> Self {
         UdpReader {
             socket,
+            interrupted,
             buffer: vec![0u8; 65536],
             buffer_pos: 0,
             buffer_len: 0,
@@ -130,6 +307,10 @@ impl Read for UdpReader {
                 Err(e) => {
                     if let srt_io::SocketError::Io(ref io_err) = e {
                         if io_err.kind() == io::ErrorKind::WouldBlock {
+                            // Report end of input so the send loop can stop
+                            if self.interrupted.load(Ordering::Relaxed) {
+                                return Ok(0);
+                            }
                             thread::sleep(Duration::from_micros(100));
                             continue;
                         }
@@ -141,7 +322,7 @@ impl Read for UdpReader {
     }
 }
 
-fn main() -> anyhow::Result<()> {
+fn main() {
     let args = Args::parse();
 
     // Initialize tracing based on verbose flag
@@ -155,8 +336,105 @@ fn main() -> anyhow::Result<()> {
 
     tracing::info!("SRT Sender starting...");
 
+    let interrupted = interrupt_flag();
+    let mut summary = RunSummary::new("srt-sender");
+    let start_time = Instant::now();
+    let result = if args.check {
+        check(&args)
+    } else {
+        run(&args, &interrupted, &mut summary)
+    };
+    summary.finish(&result, start_time.elapsed());
+
+    if let Err(e) = &result {
+        eprintln!("Error: {:#}", e);
+    }
+    if let Some(path) = &args.summary_json {
+        if let Err(e) = summary.write(path) {
+            tracing::error!("Failed to write summary to {}: {}", path, e);
+        }
+    }
+    std::process::exit(summary.exit_code);
+}
+
+/// Resolve the configuration and bind each path's socket briefly, without
+/// connecting or reading input (`--check`)
+fn check(args: &Args) -> anyhow::Result<Outcome> {
+    let mut report = CheckReport::new();
+    match parse_input(&args.input) {
+        Ok(InputSource::Udp(addr)) => {
+            report.bind("input", addr);
+            if args.framing != Framing::None {
+                report.problem("framing: applies to stdin and file input, not UDP");
+            }
+        }
+        Ok(InputSource::File(path)) => {
+            let opened = File::open(&path).map_err(|e| format!("cannot open '{}': {}", path, e));
+            if report.check("input", opened).is_some() {
+                report.entry("input", path);
+            }
+        }
+        Ok(InputSource::Stdin) => report.entry("input", "stdin"),
+        Err(e) => report.problem(format_args!("input: {}", e)),
+    }
+    report.entry("framing", args.framing);
+    if let Some(mode) = report.check("group", args.group.parse::<BondingMode>()) {
+        report.entry("group", mode);
+    }
+
+    if args.path.is_empty() {
+        report.problem("path: at least one output path is required");
+    }
+    for (idx, path_str) in args.path.iter().enumerate() {
+        let key = format!("path.{}", idx);
+        let Some(remote_addr) = report.check(&key, path_str.parse::<SocketAddr>()) else {
+            continue;
+        };
+        report.entry(&key, remote_addr);
+        let key = format!("{}.bind", key);
+        if let Some(local_addr) = report.check(&key, bind_addr(args, idx, remote_addr)) {
+            report.bind(&key, local_addr);
+        }
+    }
+
+    args.transport.check(&mut report, args.mss);
+    let any: SocketAddr = (Ipv4Addr::UNSPECIFIED, 0).into();
+    let mut conn = Connection::new(0, any, any, SeqNumber::new(0), 0);
+    if report
+        .check("stream_id", conn.set_stream_id(args.stream_id.clone()))
+        .is_some()
+    {
+        report.entry("stream_id", args.stream_id.as_deref().unwrap_or("none"));
+    }
+    let filter = args.packet_filter.as_deref();
+    if report
+        .check("packet_filter", conn.set_packet_filter(filter))
+        .is_some()
+    {
+        report.entry("packet_filter", filter.unwrap_or("none"));
+    }
+    #[cfg(feature = "otel")]
+    if let Some(Some(config)) = report.check("otel", args.otel.config("srt-sender")) {
+        report.entry("otel", config.service_name);
+    }
+    let labels = args
+        .labels
+        .iter()
+        .fold(Labels::new(), |acc, l| acc.merged(l));
+    if !labels.is_empty() {
+        report.entry("labels", labels);
+    }
+
+    Ok(report.finish()?)
+}
+
+fn run(
+    args: &Args,
+    interrupted: &Arc<AtomicBool>,
+    summary: &mut RunSummary,
+) -> anyhow::Result<Outcome> {
     if args.path.is_empty() {
-        anyhow::bail!("At least one output path is required");
+        return Err(RunError::Config("At least one output path is required".into()).into());
     }
 
     let group_type = match args.group.as_str() {
@@ -165,39 +443,62 @@ fn main() -> anyhow::Result<()> {
         _ => GroupType::Broadcast,
     };
 
-    let group = Arc::new(SocketGroup::new(1, group_type, args.path.len()));
+    let mut group = SocketGroup::new(1, group_type, args.path.len());
+    group.set_mss(args.mss).config()?;
+    let labels = args
+        .labels
+        .iter()
+        .fold(Labels::new(), |acc, l| acc.merged(l));
+    group.set_labels(labels.clone());
+    summary.set_labels(&labels);
+    let group = Arc::new(group);
+    let _span = group.span().entered();
+    #[cfg(feature = "otel")]
+    let telemetry = {
+        let group = group.clone();
+        args.otel
+            .start("srt-sender", move || otel::socket_group_metrics(&group))
+            .config()?
+    };
     let mut sockets = Vec::new();
+    // All paths share one packetizer, so they advertise the same time origin
+    let time_origin = SystemTime::now();
 
     for (idx, path_str) in args.path.iter().enumerate() {
-        let remote_addr: SocketAddr = path_str.parse()?;
-
-        // Determine local bind address
-        let local_addr: SocketAddr = if idx < args.bind.len() {
-            // User specified a bind address for this path
-            let bind_str = &args.bind[idx];
-            if bind_str.contains(':') {
-                // Full address with port
-                bind_str.parse()?
-            } else {
-                // Just IP, use port 0 (random)
-                format!("{}:0", bind_str).parse()?
-            }
-        } else if remote_addr.ip().is_loopback() {
-            "127.0.0.1:0".parse()?
-        } else {
-            "0.0.0.0:0".parse()?
-        };
+        let remote_addr: SocketAddr = path_str.parse().config()?;
+        let local_addr = bind_addr(args, idx, remote_addr).config()?;
 
-        let socket = SrtSocket::bind(local_addr)?;
+        let mut socket = SrtSocket::bind(local_addr)?;
+        args.transport.trace_socket(&mut socket);
+        if args.connected {
+            socket.connect(remote_addr)?;
+        }
         let actual_local = socket.local_addr()?;
         tracing::info!("Sender bound to {} for path {}", actual_local, remote_addr);
         let member_id = (idx + 1) as u32;
 
-        let mut conn =
-            Connection::new(member_id, actual_local, remote_addr, SeqNumber::new(0), 120);
+        let mut conn = Connection::new(
+            member_id,
+            actual_local,
+            remote_addr,
+            SeqNumber::new(0),
+            args.transport.latency_ms(),
+        );
+        conn.set_mss(args.mss).config()?;
+        args.transport.apply(&mut conn)?;
+        conn.set_stream_id(args.stream_id.clone()).config()?;
+        conn.set_packet_filter(args.packet_filter.as_deref())
+            .config()?;
+        conn.set_time_origin(time_origin);
+        conn.set_probe_plan(args.probe.then(ProbePlan::default));
+        conn.set_labels(labels.clone().with("path", &remote_addr.to_string())?);
 
         // Handshake
         tracing::info!("Initiating handshake with {}...", remote_addr);
+        #[cfg(feature = "otel")]
+        let handshake_span = SpanBuilder::start("srt.handshake")
+            .attribute("srt.peer", &remote_addr.to_string())
+            .attribute("srt.member.id", &member_id.to_string());
         let handshake = conn.create_handshake();
         let hs_body = handshake.to_bytes();
         let hs_packet = srt_protocol::ControlPacket::new(
@@ -208,26 +509,52 @@ fn main() -> anyhow::Result<()> {
             member_id,
             bytes::Bytes::copy_from_slice(&hs_body),
         );
-        let _ = socket.send_to(&hs_packet.to_bytes(), remote_addr);
+        let _ = send_on_path(&socket, &hs_packet.to_bytes(), remote_addr);
 
         let mut hs_buf = vec![0u8; 2048];
         let mut handshake_done = false;
         let start = Instant::now();
         while start.elapsed() < Duration::from_secs(5) {
-            if let Ok((n, addr)) = socket.recv_from(&mut hs_buf) {
+            if interrupted.load(Ordering::Relaxed) {
+                return Ok(Outcome::Interrupted);
+            }
+            let received = socket.recv_from(&mut hs_buf);
+            if let Err(ref e) = received {
+                if e.is_peer_unreachable() {
+                    anyhow::bail!("Handshake with {} failed: {}", remote_addr, e);
+                }
+            }
+            if let Ok((n, addr)) = received {
                 tracing::info!("Received {} bytes in handshake loop from {}", n, addr);
                 if n >= 16 && (hs_buf[0] & 0x80) != 0 {
                     if let Ok(resp_hs) = SrtHandshake::from_bytes(&hs_buf[16..n]) {
-                        match conn.process_handshake(resp_hs.clone()) {
+                        match conn.process_agreement(resp_hs.clone()) {
                             Ok(()) => {
                                 tracing::info!(
                                     "Handshake successful with {}, remote_socket_id={:?}",
                                     remote_addr,
                                     conn.remote_socket_id()
                                 );
+                                if let Some(filter) = conn.packet_filter() {
+                                    tracing::info!("Packet filter: {}", filter);
+                                }
+                                if let Some(rtt_us) = conn.handshake_rtt_us() {
+                                    tracing::info!(
+                                        "Handshake RTT to {}: {:.1}ms",
+                                        remote_addr,
+                                        rtt_us as f64 / 1000.0
+                                    );
+                                }
                                 handshake_done = true;
                                 break;
                             }
+                            Err(srt_protocol::ConnectionError::Rejected(reason)) => {
+                                return Err(RunError::Config(format!(
+                                    "{} rejected the handshake: {}",
+                                    remote_addr, reason
+                                ))
+                                .into());
+                            }
                             Err(e) => {
                                 tracing::error!("Handshake processing failed: {}", e);
                             }
@@ -239,14 +566,20 @@ fn main() -> anyhow::Result<()> {
                     tracing::debug!("Received non-control packet during handshake from {}", addr);
                 }
             }
-            thread::sleep(Duration::from_millis(50));
+            // Poll often enough that the handshake RTT measurement is accurate
+            thread::sleep(Duration::from_millis(1));
         }
 
+        #[cfg(feature = "otel")]
+        if let Some(telemetry) = &telemetry {
+            telemetry.record(handshake_span.finish(handshake_done));
+        }
         if !handshake_done {
-            anyhow::bail!(
-                "Handshake with {} timed out after 5 seconds. Cannot establish connection without handshake.",
+            return Err(RunError::ConnectTimeout(format!(
+                "handshake with {} timed out after 5 seconds",
                 remote_addr
-            );
+            ))
+            .into());
         }
 
         let conn_arc = Arc::new(conn);
@@ -255,59 +588,121 @@ fn main() -> anyhow::Result<()> {
         sockets.push((socket, remote_addr, conn_arc));
     }
 
-    let input_source = parse_input(&args.input)?;
-    let mut reader = create_input_reader(input_source)?;
+    let input_source = parse_input(&args.input).config()?;
+    if args.framing != Framing::None && matches!(input_source, InputSource::Udp(_)) {
+        return Err(RunError::Config(
+            "--framing applies to stdin and file input; UDP datagrams are already messages".into(),
+        )
+        .into());
+    }
+    let reader = create_input_reader(input_source, interrupted)?;
 
-    let mut buffer = vec![0u8; 1316];
+    // 1316 bytes = 7 MPEG-TS packets, reduced if the negotiated MSS is smaller
+    let mut reader = MessageReader::new(reader, args.framing, 1316.min(group.max_payload_size()));
+    if args.framing != Framing::None {
+        tracing::info!("Input framing: {}", args.framing);
+    }
     let mut total_bytes = 0u64;
     let mut packet_count = 0u64;
-    let mut seq_num = SeqNumber::new(0);
+    let mut packetizer = Packetizer::new(SeqNumber::new(0), 0, group.max_payload_size());
+    packetizer.set_time_origin(time_origin);
+    let mut feedback_buf = vec![0u8; 2048];
     let start_time = Instant::now();
 
     tracing::info!("Entering main send loop...");
-    loop {
-        let n = match reader.read(&mut buffer) {
-            Ok(0) => {
+    let outcome = loop {
+        if interrupted.load(Ordering::Relaxed) {
+            break Outcome::Interrupted;
+        }
+
+        poll_resync_reports(&sockets, &group, packetizer.next_seq(), &mut feedback_buf);
+
+        let message = match reader.next_message() {
+            Ok(None) if interrupted.load(Ordering::Relaxed) => break Outcome::Interrupted,
+            Ok(None) => {
                 tracing::info!("End of input reached");
-                break;
+                break Outcome::StreamEnded;
+            }
+            Ok(Some(message)) => message,
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof
+                ) =>
+            {
+                // The framing is lost; later messages would be garbage
+                anyhow::bail!("Malformed {} input: {}", args.framing, e);
             }
-            Ok(n) => n,
             Err(e) => {
                 tracing::error!("Read error: {}", e);
                 thread::sleep(Duration::from_millis(10));
                 continue;
             }
         };
-
-        let data = Bytes::copy_from_slice(&buffer[..n]);
-        for (socket, remote_addr, conn) in &sockets {
-            let remote_id = conn.remote_socket_id().unwrap_or(0);
-            if remote_id == 0 {
-                tracing::warn!(
-                    "Sending data packet with dest_socket_id=0 (handshake may have failed)"
-                );
+        let n = message.len();
+
+        // Stamp each message with its capture time
+        let packets = packetizer
+            .packetize_with_source_time(&message, SystemTime::now())
+            .unwrap_or_else(|_| packetizer.packetize(&message));
+
+        for packet in packets {
+            // Stay within --maxbw on every path
+            let delay = sockets
+                .iter()
+                .map(|(_, _, conn)| conn.pacing_delay(packet.size()))
+                .max()
+                .unwrap_or_default();
+            if !delay.is_zero() {
+                thread::sleep(delay);
             }
-            let packet = DataPacket::new(
-                seq_num,
-                MsgNumber::new(seq_num.as_raw()),
-                0,
-                remote_id,
-                data.clone(),
-            );
-            if packet_count == 0 {
-                tracing::info!(
-                    "Sending first data packet: seq={}, dest_socket_id={}, size={}",
-                    seq_num.as_raw(),
-                    remote_id,
-                    data.len()
-                );
+
+            for (socket, remote_addr, conn) in &sockets {
+                let member_id = conn.local_socket_id();
+                if !group.get_member(member_id).is_some_and(|m| m.is_active()) {
+                    continue;
+                }
+                let remote_id = conn.remote_socket_id().unwrap_or(0);
+                if remote_id == 0 {
+                    tracing::warn!(
+                        "Sending data packet with dest_socket_id=0 (handshake may have failed)"
+                    );
+                }
+                let mut packet = packet.clone();
+                packet.header.dest_socket_id = remote_id;
+                if packet_count == 0 {
+                    tracing::info!(
+                        "Sending first data packet: seq={}, dest_socket_id={}, size={}",
+                        packet.seq_number().as_raw(),
+                        remote_id,
+                        packet.payload.len()
+                    );
+                }
+                if let Err(e) = send_on_path(socket, &packet.to_bytes(), *remote_addr) {
+                    if e.is_peer_unreachable() {
+                        tracing::warn!(
+                            "Path {} is unreachable ({}), marking broken",
+                            remote_addr,
+                            e
+                        );
+                        let _ = group.update_member_status(member_id, MemberStatus::Broken);
+                        summary.failovers += 1;
+                        #[cfg(feature = "otel")]
+                        if let Some(telemetry) = &telemetry {
+                            telemetry.record(
+                                SpanBuilder::start("srt.failover")
+                                    .attribute("srt.peer", &remote_addr.to_string())
+                                    .attribute("error", &e.to_string())
+                                    .finish(false),
+                            );
+                        }
+                    }
+                }
             }
-            let _ = socket.send_to(&packet.to_bytes(), *remote_addr);
+            packet_count += 1;
         }
 
         total_bytes += n as u64;
-        packet_count += 1;
-        seq_num = seq_num.next();
 
         if packet_count % 100 == 0 {
             let elapsed = start_time.elapsed().as_secs_f64();
@@ -315,7 +710,37 @@ fn main() -> anyhow::Result<()> {
             tracing::info!("Sent {} packets, {:.2} Mbps", packet_count, mbps);
             let _ = io::stderr().flush();
         }
+    };
+
+    if reader.skipped_bytes() > 0 {
+        tracing::warn!(
+            "Skipped {} input bytes to regain MPEG-TS sync",
+            reader.skipped_bytes()
+        );
+    }
+
+    summary.bytes = total_bytes;
+    summary.packets = packet_count;
+    summary.retransmissions = sockets
+        .iter()
+        .map(|(_, _, conn)| conn.stats().packets_retransmitted)
+        .sum();
+
+    // Tell the receiver we are done so it can write out its buffered tail
+    for (socket, remote_addr, conn) in &sockets {
+        let shutdown = conn.close();
+        let _ = send_on_path(socket, &shutdown.to_bytes(), *remote_addr);
+    }
+
+    #[cfg(feature = "otel")]
+    if let Some(telemetry) = telemetry {
+        let stats = telemetry.shutdown();
+        tracing::debug!(
+            "OpenTelemetry: {} exports, {} failures",
+            stats.exports,
+            stats.failures
+        );
     }
 
-    Ok(())
+    Ok(outcome)
 }
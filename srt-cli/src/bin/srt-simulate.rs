@@ -0,0 +1,153 @@
+//! SRT Simulate - Replay recorded path conditions through a bonding mode
+//!
+//! Reads one CSV trace per path (time, loss, RTT, bandwidth), sends a
+//! constant bitrate stream through the chosen bonding mode over simulated
+//! links following the traces, and reports the quality the receiver would
+//! have seen.
+
+use clap::Parser;
+use srt_cli::config::BondingMode;
+use srt_cli::simulate::{
+    simulate, PathTrace, SimulationConfig, SimulationError, DEFAULT_SIM_BITRATE,
+};
+use srt_cli::summary::RunError;
+use srt_cli::transport::DEFAULT_LATENCY_MS;
+use srt_cli::{CheckReport, Outcome};
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Parser, Debug)]
+#[command(name = "srt-simulate")]
+#[command(about = "Replay path condition traces through SRT bonding", long_about = None)]
+struct Args {
+    /// Path condition trace, one per path (CSV: time_s,loss_pct,rtt_ms,bandwidth_kbps).
+    /// In backup mode the first trace is the primary
+    #[arg(short, long, required = true)]
+    trace: Vec<PathBuf>,
+
+    /// Bonding mode (broadcast, backup, balancing)
+    #[arg(short = 'g', long, default_value = "broadcast")]
+    group: BondingMode,
+
+    /// Stream bitrate in bits per second
+    #[arg(long, default_value_t = DEFAULT_SIM_BITRATE)]
+    bitrate: u64,
+
+    /// Payload per packet in bytes
+    #[arg(long, default_value = "1316")]
+    payload_size: usize,
+
+    /// Receiver latency in milliseconds; later packets count as late
+    #[arg(long, default_value_t = DEFAULT_LATENCY_MS)]
+    latency: u16,
+
+    /// Simulated time in seconds (default: until the last trace sample)
+    #[arg(long)]
+    duration: Option<f64>,
+
+    /// Seed for random packet loss
+    #[arg(long, default_value = "1")]
+    seed: u64,
+
+    /// Print the report as JSON
+    #[arg(long)]
+    json: bool,
+
+    /// Check the traces and settings and exit without simulating
+    #[arg(long)]
+    check: bool,
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let result = if args.check {
+        check(&args)
+    } else {
+        run(&args).map(|()| Outcome::StreamEnded)
+    };
+    let outcome = match &result {
+        Ok(outcome) => *outcome,
+        Err(e) => {
+            eprintln!("Error: {:#}", e);
+            Outcome::for_error(e)
+        }
+    };
+    std::process::exit(outcome.exit_code());
+}
+
+/// Load the traces and check the settings (`--check`)
+fn check(args: &Args) -> anyhow::Result<Outcome> {
+    let mut report = CheckReport::new();
+    for path in &args.trace {
+        let key = format!("trace {}", path.display());
+        if let Some(trace) = report.check(&key, PathTrace::load(path)) {
+            report.entry(
+                key,
+                format_args!(
+                    "{} samples over {:.1} s",
+                    trace.samples.len(),
+                    trace.end().as_secs_f64()
+                ),
+            );
+        }
+    }
+    report.entry("group", args.group);
+    let config = SimulationConfig {
+        bitrate_bps: args.bitrate,
+        payload_size: args.payload_size,
+        ..SimulationConfig::new(args.group)
+    };
+    let valid = config.validate().map_err(|e| match e {
+        SimulationError::Config(reason) => reason,
+        e => e.to_string(),
+    });
+    if report.check("settings", valid).is_some() {
+        report.entry("bitrate", format_args!("{} bps", args.bitrate));
+        report.entry("payload_size", args.payload_size);
+    }
+    report.entry("latency", format_args!("{} ms", args.latency));
+    if let Some(secs) = args.duration {
+        match Duration::try_from_secs_f64(secs) {
+            Ok(duration) => report.entry("duration", format_args!("{:?}", duration)),
+            Err(_) => report.problem(format_args!("duration: invalid duration {}", secs)),
+        }
+    }
+
+    Ok(report.finish()?)
+}
+
+fn run(args: &Args) -> anyhow::Result<()> {
+    let traces = args
+        .trace
+        .iter()
+        .map(|path| {
+            PathTrace::load(path)
+                .map_err(|e| RunError::Config(format!("{}: {}", path.display(), e)))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let duration = args
+        .duration
+        .map(|secs| {
+            Duration::try_from_secs_f64(secs)
+                .map_err(|_| RunError::Config(format!("Invalid duration {}", secs)))
+        })
+        .transpose()?;
+    let config = SimulationConfig {
+        bitrate_bps: args.bitrate,
+        payload_size: args.payload_size,
+        latency: Duration::from_millis(u64::from(args.latency)),
+        duration,
+        seed: args.seed,
+        ..SimulationConfig::new(args.group)
+    };
+
+    let report = simulate(&traces, &config).map_err(|e| RunError::Config(e.to_string()))?;
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print!("{}", report);
+    }
+    Ok(())
+}
@@ -0,0 +1,940 @@
+//! SRT caller outputs with reconnect
+//!
+//! An `srt://host:port` output connects to a listener downstream (another
+//! relay, or srt-receiver). If the listener goes away, the output keeps its
+//! socket and sequence numbers, retries the handshake with exponential
+//! backoff and holds up to `--reconnect-buffer` seconds of output meanwhile,
+//! so a brief outage loses nothing. A `CallerPool` keeps outputs connected
+//! between relay sessions, so a stream that returns reuses the connection
+//! instead of handshaking again.
+
+use crate::config::ConfigError;
+use crate::transport::TransportArgs;
+use bytes::Bytes;
+use clap::Args;
+use parking_lot::Mutex;
+use srt_bonding::{is_drifted, ResyncMessage, DEFAULT_DRIFT_TOLERANCE};
+use srt_io::{SocketError, SrtSocket};
+use srt_protocol::packet::ControlType;
+use srt_protocol::{
+    CapabilityChange, Connection, ControlPacket, Packetizer, PeerCapabilities, PeerCapabilityCache,
+    PeerKey, SeqNumber, SrtHandshake,
+};
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime};
+use thiserror::Error;
+
+/// Messages queued per output before `wait_for_space` blocks
+const MAX_QUEUED_MESSAGES: usize = 4096;
+
+/// Socket IDs for caller connections
+static NEXT_SOCKET_ID: AtomicU32 = AtomicU32::new(0x5000);
+
+/// Caller output errors
+#[derive(Error, Debug)]
+pub enum CallerError {
+    #[error("Socket error: {0}")]
+    Socket(#[from] SocketError),
+
+    #[error("{0}")]
+    Config(#[from] ConfigError),
+}
+
+/// Reconnect options for SRT caller outputs
+#[derive(Args, Debug, Clone)]
+pub struct CallerArgs {
+    /// Seconds of output held for an SRT output while it reconnects
+    #[arg(long, value_name = "SECS", default_value = "2")]
+    pub reconnect_buffer: u64,
+
+    /// Longest wait between SRT output reconnect attempts in milliseconds
+    #[arg(long, value_name = "MS", default_value = "5000")]
+    pub reconnect_max_backoff: u64,
+}
+
+impl CallerArgs {
+    /// Get the reconnect policy for these options
+    pub fn policy(&self) -> ReconnectPolicy {
+        let defaults = ReconnectPolicy::default();
+        let max_backoff = Duration::from_millis(self.reconnect_max_backoff);
+        ReconnectPolicy {
+            initial_backoff: defaults.initial_backoff.min(max_backoff),
+            max_backoff,
+            buffer: Duration::from_secs(self.reconnect_buffer),
+            ..defaults
+        }
+    }
+}
+
+/// When to reconnect and how much output to hold meanwhile
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReconnectPolicy {
+    /// Wait before the first retry
+    pub initial_backoff: Duration,
+    /// Longest wait between attempts
+    pub max_backoff: Duration,
+    /// Silence from the listener after which it is considered gone
+    ///
+    /// Listeners report their expected sequence every second, so a few
+    /// seconds without one means the path or the listener is down.
+    pub peer_timeout: Duration,
+    /// Output held while disconnected; older output is dropped
+    pub buffer: Duration,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        ReconnectPolicy {
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(5),
+            peer_timeout: Duration::from_secs(5),
+            buffer: Duration::from_secs(2),
+        }
+    }
+}
+
+/// Settings shared by the outputs of a pool
+#[derive(Debug, Clone, Default)]
+pub struct CallerOptions {
+    /// Latency, buffer and bandwidth options for each connection
+    pub transport: TransportArgs,
+    /// Reconnect policy
+    pub policy: ReconnectPolicy,
+    /// Settings negotiated with each listener, offered again on reconnect
+    pub peer_cache: Arc<PeerCapabilityCache>,
+}
+
+/// Caller output statistics
+#[derive(Debug, Clone, Default)]
+pub struct CallerStats {
+    /// Whether the output is connected
+    pub connected: bool,
+    /// Successful handshakes, including the first
+    pub connects: u64,
+    /// Messages sent
+    pub messages_sent: u64,
+    /// Payload bytes sent
+    pub bytes_sent: u64,
+    /// Messages waiting to be sent (e.g. while reconnecting)
+    pub queued_messages: usize,
+    /// Messages dropped for outlasting the reconnect buffer
+    pub expired_messages: u64,
+    /// Reconnects where the listener changed a critical setting
+    pub critical_changes: u64,
+}
+
+/// Exponential backoff between connection attempts
+#[derive(Debug, Clone)]
+struct Backoff {
+    initial: Duration,
+    max: Duration,
+    next: Duration,
+}
+
+impl Backoff {
+    fn new(initial: Duration, max: Duration) -> Self {
+        Backoff {
+            initial,
+            max,
+            next: initial,
+        }
+    }
+
+    /// Get the wait before the next attempt, doubling the one after
+    fn next_delay(&mut self) -> Duration {
+        let delay = self.next;
+        self.next = (self.next * 2).min(self.max);
+        delay
+    }
+
+    fn reset(&mut self) {
+        self.next = self.initial;
+    }
+}
+
+/// Output waiting to be sent, with the time it was queued
+#[derive(Debug, Default)]
+struct SendQueue {
+    messages: VecDeque<(Instant, Bytes)>,
+}
+
+impl SendQueue {
+    fn push(&mut self, now: Instant, data: Bytes) {
+        self.messages.push_back((now, data));
+    }
+
+    fn pop(&mut self) -> Option<Bytes> {
+        self.messages.pop_front().map(|(_, data)| data)
+    }
+
+    /// Put back a message that could not be sent
+    fn unpop(&mut self, now: Instant, data: Bytes) {
+        self.messages.push_front((now, data));
+    }
+
+    /// Drop messages queued more than `max_age` ago, returning how many
+    fn expire(&mut self, now: Instant, max_age: Duration) -> u64 {
+        let mut expired = 0;
+        while self
+            .messages
+            .front()
+            .is_some_and(|(queued, _)| now.saturating_duration_since(*queued) > max_age)
+        {
+            self.messages.pop_front();
+            expired += 1;
+        }
+        expired
+    }
+
+    fn len(&self) -> usize {
+        self.messages.len()
+    }
+}
+
+/// State shared with the output thread
+#[derive(Debug, Default)]
+struct Shared {
+    queue: SendQueue,
+    stats: CallerStats,
+}
+
+/// Connection state of an output
+enum Link {
+    /// Handshaking; the next attempt is due at `retry_at`
+    Down {
+        conn: Option<Connection>,
+        retry_at: Instant,
+        since: Instant,
+    },
+    /// Connected; `last_heard` is the last packet from the listener
+    Up {
+        conn: Connection,
+        last_heard: Instant,
+    },
+}
+
+/// SRT output in caller mode, sending on a dedicated thread
+pub struct CallerOutput {
+    dest: SocketAddr,
+    policy: ReconnectPolicy,
+    shared: Arc<Mutex<Shared>>,
+    running: Arc<AtomicBool>,
+    handle: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl CallerOutput {
+    /// Start an output connecting to `dest`
+    ///
+    /// The first handshake is sent at once; output queued before it
+    /// completes is held like output during a reconnect.
+    pub fn new(
+        dest: SocketAddr,
+        stream_id: Option<String>,
+        options: &CallerOptions,
+    ) -> Result<Self, CallerError> {
+        let local: SocketAddr = match dest {
+            SocketAddr::V4(v4) if v4.ip().is_loopback() => "127.0.0.1:0".parse().unwrap(),
+            SocketAddr::V4(_) => "0.0.0.0:0".parse().unwrap(),
+            SocketAddr::V6(_) => "[::]:0".parse().unwrap(),
+        };
+        let mut socket = SrtSocket::bind(local)?;
+        options.transport.trace_socket(&mut socket);
+        // Connected, so ICMP errors report a missing listener at once
+        socket.connect(dest)?;
+
+        let mut worker = Worker {
+            dest,
+            socket_id: NEXT_SOCKET_ID.fetch_add(1, Ordering::Relaxed),
+            local_addr: socket.local_addr()?,
+            socket,
+            stream_id,
+            options: options.clone(),
+            time_origin: SystemTime::now(),
+            packetizer: None,
+            backoff: Backoff::new(options.policy.initial_backoff, options.policy.max_backoff),
+            link: Link::Down {
+                conn: None,
+                retry_at: Instant::now(),
+                since: Instant::now(),
+            },
+            shared: Arc::new(Mutex::new(Shared::default())),
+        };
+        // Surface configuration errors now rather than on the thread
+        worker.connection()?;
+
+        let shared = worker.shared.clone();
+        let running = Arc::new(AtomicBool::new(true));
+        let thread_running = running.clone();
+        let handle = thread::spawn(move || worker.run(&thread_running));
+
+        Ok(CallerOutput {
+            dest,
+            policy: options.policy,
+            shared,
+            running,
+            handle: Mutex::new(Some(handle)),
+        })
+    }
+
+    /// Get the listener address
+    pub fn dest(&self) -> SocketAddr {
+        self.dest
+    }
+
+    /// Queue a message for sending
+    pub fn send(&self, data: &[u8]) {
+        self.shared
+            .lock()
+            .queue
+            .push(Instant::now(), Bytes::copy_from_slice(data));
+    }
+
+    /// Block until the queue has room (for non-live inputs)
+    ///
+    /// While disconnected the queue only empties as output outlasts the
+    /// reconnect buffer, so this waits at most that long.
+    pub fn wait_for_space(&self) {
+        while self.shared.lock().queue.len() >= MAX_QUEUED_MESSAGES {
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    /// Block until everything queued has been sent
+    ///
+    /// Gives up if the output stays disconnected for the reconnect buffer.
+    pub fn drain(&self) {
+        let mut down_since = None;
+        loop {
+            {
+                let shared = self.shared.lock();
+                if shared.queue.len() == 0 {
+                    return;
+                }
+                if shared.stats.connected {
+                    down_since = None;
+                } else if down_since
+                    .get_or_insert_with(Instant::now)
+                    .elapsed()
+                    .ge(&self.policy.buffer)
+                {
+                    return;
+                }
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    /// Send what is queued, then close the connection and stop the thread
+    ///
+    /// The listener is sent a shutdown and the socket is closed when this
+    /// returns. Later sends are queued but never sent.
+    pub fn shutdown(&self) {
+        self.drain();
+        self.stop();
+    }
+
+    fn stop(&self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(handle) = self.handle.lock().take() {
+            let _ = handle.join();
+        }
+    }
+
+    /// Get output statistics
+    pub fn stats(&self) -> CallerStats {
+        let shared = self.shared.lock();
+        CallerStats {
+            queued_messages: shared.queue.len(),
+            ..shared.stats.clone()
+        }
+    }
+}
+
+impl Drop for CallerOutput {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Output thread state
+struct Worker {
+    dest: SocketAddr,
+    socket_id: u32,
+    local_addr: SocketAddr,
+    socket: SrtSocket,
+    stream_id: Option<String>,
+    options: CallerOptions,
+    /// Time origin advertised on every connection, for continuous timestamps
+    time_origin: SystemTime,
+    /// Created on the first connection and kept for sequence continuity
+    packetizer: Option<Packetizer>,
+    backoff: Backoff,
+    link: Link,
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl Worker {
+    /// Key of this output's listener in the peer cache
+    fn peer_key(&self) -> PeerKey {
+        PeerKey::new(self.dest, self.stream_id.clone())
+    }
+
+    /// Create a connection for the next handshake
+    ///
+    /// A reconnect advertises the sequence the output continues from, so
+    /// the listener expects the held output, and the latency and MSS
+    /// agreed last time.
+    fn connection(&self) -> Result<Connection, ConfigError> {
+        let transport = &self.options.transport;
+        let initial_seq = self
+            .packetizer
+            .as_ref()
+            .map_or(SeqNumber::new(0), Packetizer::next_seq);
+        let mut conn = Connection::new(
+            self.socket_id,
+            self.local_addr,
+            self.dest,
+            initial_seq,
+            transport.latency_ms(),
+        );
+        transport.apply(&mut conn)?;
+        conn.set_stream_id(self.stream_id.clone())
+            .map_err(|e| ConfigError::Invalid(e.to_string()))?;
+        conn.set_time_origin(self.time_origin);
+        if let Some(known) = self.options.peer_cache.get(&self.peer_key()) {
+            known
+                .prepopulate(&mut conn)
+                .map_err(|e| ConfigError::Invalid(e.to_string()))?;
+        }
+        Ok(conn)
+    }
+
+    fn run(&mut self, running: &AtomicBool) {
+        let mut buf = vec![0u8; 2048];
+        while running.load(Ordering::Relaxed) {
+            let now = Instant::now();
+            self.handshake(now);
+            self.receive(now, &mut buf);
+            self.check_timeout(now);
+            self.keep_alive();
+            self.send_queued(now);
+            thread::sleep(Duration::from_millis(1));
+        }
+        self.close();
+    }
+
+    /// Send a handshake if one is due
+    fn handshake(&mut self, now: Instant) {
+        if !matches!(self.link, Link::Down { retry_at, .. } if now >= retry_at) {
+            return;
+        }
+        let delay = self.backoff.next_delay();
+
+        let next = match self.connection() {
+            Ok(next) => next,
+            Err(e) => {
+                tracing::error!("SRT output {}: {}", self.dest, e);
+                return;
+            }
+        };
+        let handshake = ControlPacket::new(
+            ControlType::Handshake,
+            0,
+            0,
+            0,
+            0,
+            next.create_handshake().to_bytes().freeze(),
+        );
+        if let Err(e) = self.socket.send(&handshake.to_bytes()) {
+            tracing::debug!("SRT output {}: handshake not sent: {}", self.dest, e);
+        }
+        // Keep the connection that sent the latest request
+        if let Link::Down { conn, retry_at, .. } = &mut self.link {
+            *conn = Some(next);
+            *retry_at = now + delay;
+        }
+    }
+
+    /// Handle everything the listener has sent
+    fn receive(&mut self, now: Instant, buf: &mut [u8]) {
+        loop {
+            let n = match self.socket.recv(buf) {
+                Ok(n) => n,
+                Err(e) if e.is_peer_unreachable() => {
+                    self.go_down(now, &e.to_string());
+                    continue;
+                }
+                // Would block
+                Err(_) => return,
+            };
+            let Ok(control) = ControlPacket::from_bytes(&buf[..n]) else {
+                continue;
+            };
+            if let Link::Up { last_heard, .. } = &mut self.link {
+                *last_heard = now;
+            }
+
+            if let Some(ResyncMessage::Report(expected)) =
+                ResyncMessage::from_control_packet(&control)
+            {
+                self.answer_resync(expected);
+                continue;
+            }
+            match control.control_type() {
+                ControlType::Shutdown => self.go_down(now, "listener shut down"),
+                ControlType::Handshake => self.complete_handshake(now, &control),
+                ControlType::UserDefined | ControlType::KeepAlive => {
+                    // Rate ceiling requests and keep-alives from the listener
+                    if let Link::Up { conn, .. } = &self.link {
+                        let _ = conn.process_control(&control);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Announce our sequence if the listener expects another
+    fn answer_resync(&self, expected: SeqNumber) {
+        let (Link::Up { conn, .. }, Some(packetizer)) = (&self.link, &self.packetizer) else {
+            return;
+        };
+        let next_seq = packetizer.next_seq();
+        if is_drifted(next_seq, expected, DEFAULT_DRIFT_TOLERANCE) {
+            tracing::info!(
+                "SRT output {} expects sequence {}, sending {}; resyncing",
+                self.dest,
+                expected.as_raw(),
+                next_seq.as_raw()
+            );
+            let packet = ResyncMessage::Announce(next_seq)
+                .to_control_packet(0, conn.remote_socket_id().unwrap_or(0));
+            let _ = self.socket.send(&packet.to_bytes());
+        }
+    }
+
+    fn complete_handshake(&mut self, now: Instant, control: &ControlPacket) {
+        let Link::Down { conn, since, .. } = &mut self.link else {
+            return;
+        };
+        let (Some(mut pending), Ok(handshake)) =
+            (conn.take(), SrtHandshake::from_bytes(&control.control_info))
+        else {
+            return;
+        };
+        if let Err(e) = pending.process_agreement(handshake) {
+            tracing::warn!("SRT output {} rejected: {}", self.dest, e);
+            return;
+        }
+
+        let outage = now.saturating_duration_since(*since);
+        let changes = self
+            .options
+            .peer_cache
+            .remember(self.peer_key(), PeerCapabilities::from_connection(&pending));
+        for change in &changes {
+            if change.is_critical() {
+                tracing::warn!("SRT output {}: listener changed {}", self.dest, change);
+            } else {
+                tracing::info!("SRT output {}: {}", self.dest, change);
+            }
+        }
+        let remote_id = pending.remote_socket_id().unwrap_or(0);
+        match &mut self.packetizer {
+            Some(packetizer) => {
+                packetizer.set_dest_socket_id(remote_id);
+                packetizer.set_max_payload_size(pending.max_payload_size());
+            }
+            None => {
+                let mut packetizer =
+                    Packetizer::new(SeqNumber::new(0), remote_id, pending.max_payload_size());
+                packetizer.set_time_origin(self.time_origin);
+                self.packetizer = Some(packetizer);
+            }
+        }
+        self.backoff.reset();
+        self.link = Link::Up {
+            conn: pending,
+            last_heard: now,
+        };
+
+        let mut shared = self.shared.lock();
+        shared.stats.connected = true;
+        shared.stats.connects += 1;
+        if changes.iter().any(CapabilityChange::is_critical) {
+            shared.stats.critical_changes += 1;
+        }
+        if shared.stats.connects == 1 {
+            tracing::info!("SRT output {} connected", self.dest);
+        } else {
+            tracing::info!(
+                "SRT output {} reconnected after {:.1}s, {} messages held",
+                self.dest,
+                outage.as_secs_f64(),
+                shared.queue.len()
+            );
+        }
+    }
+
+    /// Drop the connection if the listener has gone quiet
+    fn check_timeout(&mut self, now: Instant) {
+        let timeout = self.options.policy.peer_timeout;
+        if let Link::Up { last_heard, .. } = self.link {
+            if now.saturating_duration_since(last_heard) > timeout {
+                self.go_down(now, &format!("no response for {:?}", timeout));
+            }
+        }
+    }
+
+    /// Send keep-alives, and echo the listener's, while connected
+    fn keep_alive(&self) {
+        if let Link::Up { conn, .. } = &self.link {
+            while let Some(keepalive) = conn.create_keepalive() {
+                let _ = self.socket.send(&keepalive.to_bytes());
+            }
+        }
+    }
+
+    fn go_down(&mut self, now: Instant, reason: &str) {
+        if let Link::Up { conn, .. } = &self.link {
+            conn.close();
+            tracing::warn!(
+                "SRT output {} lost ({}); reconnecting, holding up to {:?} of output",
+                self.dest,
+                reason,
+                self.options.policy.buffer
+            );
+            self.shared.lock().stats.connected = false;
+            self.link = Link::Down {
+                conn: None,
+                retry_at: now,
+                since: now,
+            };
+        }
+    }
+
+    /// Send queued output while connected, or age it out while not
+    fn send_queued(&mut self, now: Instant) {
+        let Link::Up { conn, .. } = &self.link else {
+            let mut shared = self.shared.lock();
+            let expired = shared.queue.expire(now, self.options.policy.buffer);
+            shared.stats.expired_messages += expired;
+            return;
+        };
+        let Some(packetizer) = self.packetizer.as_mut() else {
+            return;
+        };
+
+        loop {
+            let Some(data) = self.shared.lock().queue.pop() else {
+                return;
+            };
+            for packet in packetizer.packetize(&data) {
+                // Stay within --maxbw
+                let delay = conn.pacing_delay(packet.size());
+                if !delay.is_zero() {
+                    thread::sleep(delay);
+                }
+                if let Err(e) = self.socket.send(&packet.to_bytes()) {
+                    if e.is_peer_unreachable() {
+                        // Resent whole after the reconnect
+                        self.shared.lock().queue.unpop(now, data);
+                        self.go_down(now, &e.to_string());
+                        return;
+                    }
+                }
+            }
+            let mut shared = self.shared.lock();
+            shared.stats.messages_sent += 1;
+            shared.stats.bytes_sent += data.len() as u64;
+        }
+    }
+
+    /// Send what is left and tell the listener we are done
+    fn close(&mut self) {
+        self.send_queued(Instant::now());
+        if let Link::Up { conn, .. } = &self.link {
+            let shutdown = conn.close();
+            let _ = self.socket.send(&shutdown.to_bytes());
+        }
+    }
+}
+
+/// Pool key: listener address and Stream ID
+type PoolKey = (SocketAddr, Option<String>);
+
+/// Caller outputs shared between relay sessions
+///
+/// Outputs are keyed by listener address and Stream ID and stay connected
+/// until the pool is shut down or dropped.
+pub struct CallerPool {
+    options: CallerOptions,
+    outputs: Mutex<HashMap<PoolKey, Arc<CallerOutput>>>,
+}
+
+impl CallerPool {
+    /// Create an empty pool
+    pub fn new(options: CallerOptions) -> Self {
+        CallerPool {
+            options,
+            outputs: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Get the output to `dest`, connecting it on first use
+    pub fn get(
+        &self,
+        dest: SocketAddr,
+        stream_id: Option<&str>,
+    ) -> Result<Arc<CallerOutput>, CallerError> {
+        let key: PoolKey = (dest, stream_id.map(str::to_string));
+        let mut outputs = self.outputs.lock();
+        if let Some(output) = outputs.get(&key) {
+            tracing::debug!("Reusing SRT output {}", dest);
+            return Ok(output.clone());
+        }
+        let output = Arc::new(CallerOutput::new(dest, key.1.clone(), &self.options)?);
+        outputs.insert(key, output.clone());
+        Ok(output)
+    }
+
+    /// Get every output in the pool
+    pub fn outputs(&self) -> Vec<Arc<CallerOutput>> {
+        self.outputs.lock().values().cloned().collect()
+    }
+
+    /// Shut down and forget every output
+    ///
+    /// Outputs still held elsewhere are closed too; `get` connects afresh.
+    pub fn shutdown(&self) {
+        let outputs: Vec<_> = self.outputs.lock().drain().map(|(_, o)| o).collect();
+        for output in outputs {
+            output.shutdown();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::UdpSocket;
+
+    /// Listener side of the handshake: answer one request on `socket`
+    fn accept(socket: &UdpSocket, socket_id: u32) -> SocketAddr {
+        accept_with(socket, socket_id, |_| {}).0
+    }
+
+    /// Answer one request, adjusting the reply; returns the request too
+    fn accept_with(
+        socket: &UdpSocket,
+        socket_id: u32,
+        adjust: impl Fn(&mut SrtHandshake),
+    ) -> (SocketAddr, SrtHandshake) {
+        let mut buf = [0u8; 2048];
+        loop {
+            let (n, from) = socket.recv_from(&mut buf).unwrap();
+            let control = ControlPacket::from_bytes(&buf[..n]).unwrap();
+            if control.control_type() != ControlType::Handshake {
+                continue;
+            }
+            let request = SrtHandshake::from_bytes(&control.control_info).unwrap();
+            let mut reply = request.clone();
+            reply.udt.handshake_type = -2;
+            reply.udt.socket_id = socket_id;
+            reply.time_origin = None;
+            adjust(&mut reply);
+            let packet = ControlPacket::new(
+                ControlType::Handshake,
+                0,
+                0,
+                0,
+                0,
+                reply.to_bytes().freeze(),
+            );
+            socket.send_to(&packet.to_bytes(), from).unwrap();
+            return (from, request);
+        }
+    }
+
+    /// Payloads of the next `count` data packets
+    fn receive_data(socket: &UdpSocket, count: usize) -> Vec<Vec<u8>> {
+        let mut buf = [0u8; 2048];
+        let mut payloads = Vec::new();
+        while payloads.len() < count {
+            let (n, _) = socket.recv_from(&mut buf).unwrap();
+            if let Ok(srt_protocol::Packet::Data(packet)) =
+                srt_protocol::Packet::from_bytes(&buf[..n])
+            {
+                payloads.push(packet.payload.to_vec());
+            }
+        }
+        payloads
+    }
+
+    /// Wait up to five seconds for `done`
+    fn wait_until(what: &str, done: impl Fn() -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !done() {
+            assert!(Instant::now() < deadline, "timed out waiting for {}", what);
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_backoff() {
+        let mut backoff = Backoff::new(Duration::from_millis(200), Duration::from_secs(1));
+        let delays: Vec<_> = (0..5).map(|_| backoff.next_delay().as_millis()).collect();
+        assert_eq!(delays, vec![200, 400, 800, 1000, 1000]);
+        backoff.reset();
+        assert_eq!(backoff.next_delay(), Duration::from_millis(200));
+    }
+
+    #[test]
+    fn test_send_queue_expiry() {
+        let mut queue = SendQueue::default();
+        let t0 = Instant::now();
+        queue.push(t0, Bytes::from_static(b"a"));
+        queue.push(t0 + Duration::from_secs(1), Bytes::from_static(b"b"));
+
+        assert_eq!(
+            queue.expire(t0 + Duration::from_secs(2), Duration::from_secs(2)),
+            0
+        );
+        assert_eq!(
+            queue.expire(t0 + Duration::from_millis(2500), Duration::from_secs(2)),
+            1
+        );
+        assert_eq!(queue.pop(), Some(Bytes::from_static(b"b")));
+        assert_eq!(queue.pop(), None);
+    }
+
+    #[test]
+    fn test_policy_from_args() {
+        let args = CallerArgs {
+            reconnect_buffer: 5,
+            reconnect_max_backoff: 100,
+        };
+        let policy = args.policy();
+        assert_eq!(policy.buffer, Duration::from_secs(5));
+        assert_eq!(policy.max_backoff, Duration::from_millis(100));
+        assert_eq!(policy.initial_backoff, Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_reconnect_holds_output() {
+        let listener = UdpSocket::bind("127.0.0.1:0").unwrap();
+        listener
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let options = CallerOptions {
+            policy: ReconnectPolicy {
+                initial_backoff: Duration::from_millis(10),
+                max_backoff: Duration::from_millis(50),
+                peer_timeout: Duration::from_millis(200),
+                buffer: Duration::from_secs(10),
+            },
+            ..Default::default()
+        };
+        let pool = CallerPool::new(options);
+        let dest = listener.local_addr().unwrap();
+        let output = pool.get(dest, Some("live")).unwrap();
+        assert!(Arc::ptr_eq(&output, &pool.get(dest, Some("live")).unwrap()));
+
+        // Output queued before the handshake completes is held
+        output.send(b"early");
+        accept(&listener, 7);
+        assert_eq!(receive_data(&listener, 1), vec![b"early".to_vec()]);
+        assert!(output.stats().connected);
+
+        // The listener goes quiet: the output drops the connection and
+        // holds what is sent meanwhile
+        wait_until("the outage", || !output.stats().connected);
+        output.send(b"held 1");
+        output.send(b"held 2");
+
+        // Back again: the held output follows the new handshake in order
+        accept(&listener, 8);
+        assert_eq!(
+            receive_data(&listener, 2),
+            vec![b"held 1".to_vec(), b"held 2".to_vec()]
+        );
+        wait_until("the sent count", || output.stats().messages_sent == 3);
+        let stats = output.stats();
+        assert_eq!(stats.connects, 2);
+        assert_eq!(stats.expired_messages, 0);
+    }
+
+    #[test]
+    fn test_reconnect_uses_peer_cache() {
+        let listener = UdpSocket::bind("127.0.0.1:0").unwrap();
+        listener
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let options = CallerOptions {
+            policy: ReconnectPolicy {
+                initial_backoff: Duration::from_millis(10),
+                max_backoff: Duration::from_millis(50),
+                peer_timeout: Duration::from_millis(200),
+                buffer: Duration::from_secs(10),
+            },
+            ..Default::default()
+        };
+        let cache = options.peer_cache.clone();
+        let pool = CallerPool::new(options);
+        let dest = listener.local_addr().unwrap();
+        let output = pool.get(dest, Some("live")).unwrap();
+
+        // The listener asks for more latency than the output's default
+        let (_, first) = accept_with(&listener, 7, |reply| {
+            reply.srt_ext.as_mut().unwrap().latency = (400 << 16) | 400;
+        });
+        assert!(first.srt_ext.unwrap().recv_latency_ms() < 400);
+        wait_until("the connection", || output.stats().connected);
+        let key = PeerKey::new(dest, Some("live".into()));
+        assert_eq!(cache.get(&key).unwrap().latency_ms, 400);
+
+        // The reconnect offers the agreed latency; the listener now
+        // imposes a packet filter, which is flagged
+        wait_until("the outage", || !output.stats().connected);
+        let (_, second) = accept_with(&listener, 8, |reply| {
+            reply.filter_config = Some("fec,cols:10".into());
+        });
+        assert_eq!(second.srt_ext.unwrap().recv_latency_ms(), 400);
+        wait_until("the reconnect", || output.stats().connects == 2);
+        assert_eq!(output.stats().critical_changes, 1);
+        assert!(cache.get(&key).unwrap().filter.is_some());
+    }
+
+    #[test]
+    fn test_pool_shutdown_closes_outputs() {
+        let listener = UdpSocket::bind("127.0.0.1:0").unwrap();
+        listener
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let pool = CallerPool::new(CallerOptions::default());
+        let output = pool.get(listener.local_addr().unwrap(), None).unwrap();
+        accept(&listener, 7);
+        wait_until("the connection", || output.stats().connected);
+
+        output.send(b"last");
+        pool.shutdown();
+        assert!(pool.outputs().is_empty());
+        assert_eq!(Arc::strong_count(&output), 1);
+
+        // Queued output goes first, then the listener is told we are gone
+        assert_eq!(receive_data(&listener, 1), vec![b"last".to_vec()]);
+        let mut buf = [0u8; 2048];
+        loop {
+            let (n, _) = listener.recv_from(&mut buf).unwrap();
+            if let Ok(control) = ControlPacket::from_bytes(&buf[..n]) {
+                if control.control_type() == ControlType::Shutdown {
+                    break;
+                }
+            }
+        }
+    }
+}
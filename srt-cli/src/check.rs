@@ -0,0 +1,265 @@
+//! Configuration checks (`--check`)
+//!
+//! With `--check` a tool resolves its configuration the way a run would:
+//! it parses addresses, binds its sockets for a moment and checks buffer,
+//! filter and passphrase settings. It then prints what it resolved and
+//! exits without streaming. Every problem is reported, not just the first,
+//! so one pass shows everything to fix before a deployment goes live.
+
+use crate::config::{ConfigError, PASSPHRASE_UNSUPPORTED};
+use crate::summary::{Outcome, RunError};
+use srt_crypto::SecretBytes;
+use std::fmt;
+use std::net::{SocketAddr, UdpSocket};
+use std::path::Path;
+
+/// Shortest passphrase libsrt accepts
+pub const MIN_PASSPHRASE_LEN: usize = 10;
+
+/// Longest passphrase libsrt accepts
+pub const MAX_PASSPHRASE_LEN: usize = 79;
+
+/// Settings resolved by a check and the problems it found
+#[derive(Debug, Clone, Default)]
+pub struct CheckReport {
+    entries: Vec<(String, String)>,
+    problems: Vec<String>,
+}
+
+impl CheckReport {
+    /// Create an empty report
+    pub fn new() -> Self {
+        CheckReport::default()
+    }
+
+    /// Record a resolved setting
+    pub fn entry(&mut self, key: impl Into<String>, value: impl fmt::Display) {
+        self.entries.push((key.into(), value.to_string()));
+    }
+
+    /// Record a problem
+    pub fn problem(&mut self, problem: impl fmt::Display) {
+        self.problems.push(problem.to_string());
+    }
+
+    /// Keep a result's value, or record its error as a problem with `key`
+    pub fn check<T, E: fmt::Display>(&mut self, key: &str, result: Result<T, E>) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(e) => {
+                self.problem(format_args!("{}: {}", key, e));
+                None
+            }
+        }
+    }
+
+    /// Like [`check`](Self::check), without repeating "Invalid
+    /// configuration" before each reason
+    pub fn check_config<T>(&mut self, key: &str, result: Result<T, ConfigError>) -> Option<T> {
+        self.check(
+            key,
+            result.map_err(|e| match e {
+                ConfigError::Invalid(reason) => reason,
+                e => e.to_string(),
+            }),
+        )
+    }
+
+    /// Bind a UDP socket to `addr` and close it again, recording the
+    /// address under `key`
+    ///
+    /// Catches addresses in use, missing interfaces and privileged ports.
+    /// Returns the address bound, whose port is random if `addr`'s is 0.
+    pub fn bind(&mut self, key: &str, addr: SocketAddr) -> Option<SocketAddr> {
+        let bound = UdpSocket::bind(addr).and_then(|socket| socket.local_addr());
+        let bound = self.check(
+            key,
+            bound.map_err(|e| format!("cannot bind {}: {}", addr, e)),
+        )?;
+        if addr.port() == 0 {
+            // A run gets a different port
+            self.entry(key, format_args!("{} (any port)", addr));
+        } else {
+            self.entry(key, bound);
+        }
+        Some(bound)
+    }
+
+    /// Check a file output's directory exists, recording the path under
+    /// `key`; the file itself is not created
+    pub fn output_file(&mut self, key: &str, path: &str) {
+        let dir = match Path::new(path).parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        if dir.is_dir() {
+            self.entry(key, path);
+        } else {
+            self.problem(format_args!(
+                "{}: directory '{}' does not exist",
+                key,
+                dir.display()
+            ));
+        }
+    }
+
+    /// Check a passphrase, recording whether one is set (never its value)
+    ///
+    /// A passphrase is a problem whatever its length: the tools cannot
+    /// encrypt yet.
+    pub fn passphrase(&mut self, key: &str, passphrase: Option<&SecretBytes>) {
+        match passphrase {
+            Some(passphrase) => {
+                self.check_config(key, check_passphrase(passphrase));
+                self.problem(format_args!("{}: {}", key, PASSPHRASE_UNSUPPORTED));
+            }
+            None => self.entry(key, "none (unencrypted)"),
+        }
+    }
+
+    /// Add another report's settings and problems
+    pub fn extend(&mut self, other: CheckReport) {
+        self.entries.extend(other.entries);
+        self.problems.extend(other.problems);
+    }
+
+    /// Get the resolved settings as (key, value) pairs
+    pub fn entries(&self) -> &[(String, String)] {
+        &self.entries
+    }
+
+    /// Get the value recorded for `key`, if any
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Get the problems found
+    pub fn problems(&self) -> &[String] {
+        &self.problems
+    }
+
+    /// Check if no problems were found
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    /// Print the report to stdout and turn it into the run's outcome
+    ///
+    /// A report with problems is a configuration error (exit code 2).
+    pub fn finish(&self) -> Result<Outcome, RunError> {
+        print!("{}", self);
+        match self.problems.len() {
+            0 => Ok(Outcome::Checked),
+            1 => Err(RunError::Config("1 problem found".into())),
+            n => Err(RunError::Config(format!("{} problems found", n))),
+        }
+    }
+}
+
+impl fmt::Display for CheckReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self.entries.iter().map(|(k, _)| k.len()).max().unwrap_or(0);
+        for (key, value) in &self.entries {
+            writeln!(f, "{:width$}  {}", key, value, width = width)?;
+        }
+        if self.problems.is_empty() {
+            return writeln!(f, "Configuration OK");
+        }
+        writeln!(f, "Problems:")?;
+        for problem in &self.problems {
+            writeln!(f, "  - {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+/// Check a passphrase is as long as libsrt requires
+pub fn check_passphrase(passphrase: &SecretBytes) -> Result<(), ConfigError> {
+    let len = passphrase.len();
+    if !(MIN_PASSPHRASE_LEN..=MAX_PASSPHRASE_LEN).contains(&len) {
+        return Err(ConfigError::Invalid(format!(
+            "passphrase is {} bytes; must be {} to {}",
+            len, MIN_PASSPHRASE_LEN, MAX_PASSPHRASE_LEN
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let mut report = CheckReport::new();
+        report.entry("latency", "120 ms");
+        assert_eq!(report.check("mss", "1500".parse::<usize>()), Some(1500));
+        assert_eq!(report.check("mss", "x".parse::<usize>()), None);
+        assert!(!report.is_ok());
+        assert_eq!(report.get("latency"), Some("120 ms"));
+        assert!(report.problems()[0].starts_with("mss: "));
+
+        let text = report.to_string();
+        assert!(text.contains("latency  120 ms"));
+        assert!(text.contains("Problems:\n  - mss: "));
+        assert!(matches!(report.finish(), Err(RunError::Config(_))));
+    }
+
+    #[test]
+    fn test_bind_reports_address_in_use() {
+        let mut report = CheckReport::new();
+        let bound = report
+            .bind("listen", "127.0.0.1:0".parse().unwrap())
+            .unwrap();
+        assert_ne!(bound.port(), 0);
+        assert_eq!(report.get("listen"), Some("127.0.0.1:0 (any port)"));
+        assert!(report.is_ok());
+
+        let taken = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let taken = taken.local_addr().unwrap();
+        assert!(report.bind("input", taken).is_none());
+        assert!(report.problems()[0].contains("cannot bind"));
+    }
+
+    #[test]
+    fn test_output_file() {
+        let mut report = CheckReport::new();
+        report.output_file("output", "out.ts");
+        report.output_file("output", "/nonexistent/dir/out.ts");
+        assert_eq!(report.get("output"), Some("out.ts"));
+        assert_eq!(
+            report.problems(),
+            ["output: directory '/nonexistent/dir' does not exist"]
+        );
+    }
+
+    #[test]
+    fn test_passphrase() {
+        let mut report = CheckReport::new();
+        report.passphrase("passphrase", None);
+        assert!(report.is_ok());
+        assert_eq!(report.entries()[0].1, "none (unencrypted)");
+
+        // Encryption is not implemented, so any passphrase is a problem
+        report.passphrase("passphrase", Some(&SecretBytes::from("long enough secret")));
+        assert_eq!(
+            report.problems(),
+            ["passphrase: passphrase encryption is not supported yet"]
+        );
+        // The value itself never appears
+        assert!(!report.to_string().contains("long enough"));
+
+        report.passphrase("passphrase", Some(&SecretBytes::from("short")));
+        assert_eq!(report.problems().len(), 3);
+        assert!(check_passphrase(&SecretBytes::new(vec![b'x'; 80])).is_err());
+        assert_eq!(
+            report.problems()[1],
+            "passphrase: passphrase is 5 bytes; must be 10 to 79"
+        );
+        let err = report.finish().unwrap_err();
+        assert_eq!(err.to_string(), "Invalid configuration: 3 problems found");
+    }
+}
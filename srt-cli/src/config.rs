@@ -1,9 +1,17 @@
 //! Configuration file support for SRT CLI tools
 
+use crate::admission::AdmissionPolicy;
+use crate::check::CheckReport;
 use serde::{Deserialize, Serialize};
+use srt_crypto::SecretBytes;
+use srt_protocol::packet::{MAX_MSS, MIN_MSS};
+use srt_protocol::{FilterConfig, LabelError, Labels, MAX_LATENCY_MS};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
 use std::fs;
 use std::net::SocketAddr;
 use std::path::Path;
+use std::str::FromStr;
 use std::time::Duration;
 
 /// Path configuration
@@ -18,6 +26,9 @@ pub struct PathConfig {
     /// Weight for load balancing (0.0 to 1.0)
     #[serde(default = "default_weight")]
     pub weight: f64,
+    /// Use a connected UDP socket so ICMP errors are reported for this path
+    #[serde(default)]
+    pub connected: bool,
 }
 
 fn default_weight() -> f64 {
@@ -25,7 +36,7 @@ fn default_weight() -> f64 {
 }
 
 /// Bonding mode
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum BondingMode {
     /// Broadcast to all paths
@@ -36,6 +47,32 @@ pub enum BondingMode {
     Balancing,
 }
 
+impl fmt::Display for BondingMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BondingMode::Broadcast => write!(f, "broadcast"),
+            BondingMode::Backup => write!(f, "backup"),
+            BondingMode::Balancing => write!(f, "balancing"),
+        }
+    }
+}
+
+impl FromStr for BondingMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "broadcast" => Ok(BondingMode::Broadcast),
+            "backup" => Ok(BondingMode::Backup),
+            "balancing" => Ok(BondingMode::Balancing),
+            _ => Err(format!(
+                "Invalid bonding mode '{}': use broadcast, backup or balancing",
+                s
+            )),
+        }
+    }
+}
+
 /// Load balancing algorithm
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -67,6 +104,9 @@ pub struct SenderConfig {
     pub stats_interval_secs: u64,
     /// Load balancing algorithm (for balancing mode)
     pub balancing_algorithm: Option<LoadBalancingAlgorithm>,
+    /// Encryption passphrase (never logged); refused until the tools encrypt
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub passphrase: Option<SecretBytes>,
 }
 
 fn default_mtu() -> usize {
@@ -99,12 +139,86 @@ pub struct ReceiverConfig {
     /// Statistics interval in seconds
     #[serde(default = "default_stats_interval")]
     pub stats_interval_secs: u64,
+    /// Encryption passphrase (never logged); refused until the tools encrypt
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub passphrase: Option<SecretBytes>,
 }
 
 fn default_buffer_size() -> usize {
     8192
 }
 
+/// Output pipeline for one inbound stream
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamRoute {
+    /// Stream ID sent by the caller ("*" matches any stream)
+    pub stream_id: String,
+    /// Outputs (udp://host:port, file path, or "-" for stdout)
+    pub outputs: Vec<String>,
+    /// Labels attached to this stream's stats and logs
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+}
+
+impl StreamRoute {
+    /// Get the route's labels, checking the keys
+    pub fn labels(&self) -> Result<Labels, LabelError> {
+        let mut labels = Labels::new();
+        for (key, value) in &self.labels {
+            labels.insert(key, value)?;
+        }
+        Ok(labels)
+    }
+}
+
+/// Relay configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelayConfig {
+    /// Per-stream output routes
+    pub streams: Vec<StreamRoute>,
+    /// Latency in milliseconds
+    #[serde(default = "default_latency")]
+    pub latency_ms: u16,
+    /// Statistics interval in seconds
+    #[serde(default = "default_stats_interval")]
+    pub stats_interval_secs: u64,
+    /// Packet filter configuration offered to callers (e.g. "fec,cols:10")
+    #[serde(default)]
+    pub packet_filter: Option<String>,
+    /// Skip reordering while a stream has a single active path
+    #[serde(default)]
+    pub single_path_bypass: bool,
+    /// Caller address allowlist and denylist (`allow`/`deny` keys)
+    #[serde(flatten)]
+    pub admission: AdmissionPolicy,
+    /// Encryption passphrase (never logged); refused until the tools encrypt
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub passphrase: Option<SecretBytes>,
+}
+
+/// OpenTelemetry export configuration (used with the `otel` feature)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TelemetryConfig {
+    /// OTLP/HTTP collector endpoint (e.g. "http://localhost:4318")
+    pub endpoint: String,
+    /// Service name reported to the collector (default: the tool name)
+    #[serde(default)]
+    pub service_name: Option<String>,
+    /// Seconds between metric exports
+    #[serde(default = "default_telemetry_interval")]
+    pub interval_secs: u64,
+}
+
+fn default_telemetry_interval() -> u64 {
+    10
+}
+
+/// Stream ID that matches any inbound stream
+pub const WILDCARD_STREAM_ID: &str = "*";
+
+/// Why a passphrase is refused
+pub(crate) const PASSPHRASE_UNSUPPORTED: &str = "passphrase encryption is not supported yet";
+
 /// Combined configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
@@ -112,16 +226,45 @@ pub struct Config {
     pub sender: Option<SenderConfig>,
     /// Receiver configuration
     pub receiver: Option<ReceiverConfig>,
+    /// Relay configuration
+    #[serde(default)]
+    pub relay: Option<RelayConfig>,
+    /// OpenTelemetry export
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub telemetry: Option<TelemetryConfig>,
 }
 
 impl Config {
     /// Load configuration from TOML file
+    ///
+    /// Refuses a passphrase: the tools cannot encrypt yet, and would
+    /// otherwise stream in the clear.
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
         let contents = fs::read_to_string(path)?;
         let config: Config = toml::from_str(&contents)?;
+        if let Some(key) = config.passphrase_key() {
+            return Err(ConfigError::Invalid(format!(
+                "{}: {}",
+                key, PASSPHRASE_UNSUPPORTED
+            )));
+        }
         Ok(config)
     }
 
+    /// Get the key of the first section that sets a passphrase
+    fn passphrase_key(&self) -> Option<&'static str> {
+        let sender = self.sender.as_ref().and_then(|s| s.passphrase.as_ref());
+        let receiver = self.receiver.as_ref().and_then(|r| r.passphrase.as_ref());
+        let relay = self.relay.as_ref().and_then(|r| r.passphrase.as_ref());
+        [
+            ("sender.passphrase", sender),
+            ("receiver.passphrase", receiver),
+            ("relay.passphrase", relay),
+        ]
+        .into_iter()
+        .find_map(|(key, passphrase)| passphrase.map(|_| key))
+    }
+
     /// Save configuration to TOML file
     pub fn to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), ConfigError> {
         let contents = toml::to_string_pretty(self)?;
@@ -141,20 +284,25 @@ impl Config {
                         address: "192.168.1.10:9000".parse().unwrap(),
                         bind: None,
                         weight: 1.0,
+                        connected: false,
                     },
                     PathConfig {
                         name: "wifi1".to_string(),
                         address: "192.168.2.10:9000".parse().unwrap(),
                         bind: None,
                         weight: 1.0,
+                        connected: false,
                     },
                 ],
                 mtu: 1456,
                 latency_ms: 120,
                 stats_interval_secs: 1,
                 balancing_algorithm: None,
+                passphrase: None,
             }),
             receiver: None,
+            relay: None,
+            telemetry: None,
         }
     }
 
@@ -172,8 +320,91 @@ impl Config {
                 buffer_size: 8192,
                 latency_ms: 120,
                 stats_interval_secs: 1,
+                passphrase: None,
+            }),
+            relay: None,
+            telemetry: None,
+        }
+    }
+
+    /// Create example relay configuration
+    pub fn example_relay() -> Self {
+        Config {
+            sender: None,
+            receiver: None,
+            relay: Some(RelayConfig {
+                streams: vec![
+                    StreamRoute {
+                        stream_id: "live/cam1".to_string(),
+                        outputs: vec!["udp://127.0.0.1:5001".to_string()],
+                        labels: BTreeMap::from([("camera".to_string(), "1".to_string())]),
+                    },
+                    StreamRoute {
+                        stream_id: "live/cam2".to_string(),
+                        outputs: vec!["udp://127.0.0.1:5002".to_string(), "cam2.ts".to_string()],
+                        labels: BTreeMap::new(),
+                    },
+                ],
+                latency_ms: 120,
+                stats_interval_secs: 1,
+                packet_filter: None,
+                single_path_bypass: false,
+                admission: AdmissionPolicy::default(),
+                passphrase: None,
             }),
+            telemetry: None,
+        }
+    }
+
+    /// Check the configuration without streaming
+    ///
+    /// Checks each section present: addresses (binding every bind and
+    /// listen address for a moment), input files, packet sizes, latency,
+    /// packet filter, route outputs and labels and the telemetry endpoint.
+    /// A passphrase is a problem until the tools encrypt. Output URLs are
+    /// left to the tool that opens them.
+    pub fn validate(&self) -> CheckReport {
+        let mut report = CheckReport::new();
+        if self.sender.is_none() && self.receiver.is_none() && self.relay.is_none() {
+            report.problem("no [sender], [receiver] or [relay] section");
+        }
+        if let Some(sender) = &self.sender {
+            sender.validate(&mut report);
+        }
+        if let Some(receiver) = &self.receiver {
+            receiver.validate(&mut report);
         }
+        if let Some(relay) = &self.relay {
+            relay.validate(&mut report);
+        }
+        if let Some(telemetry) = &self.telemetry {
+            telemetry.validate(&mut report);
+        }
+        report
+    }
+}
+
+/// Check a packet size is within the MSS range
+fn check_mss(report: &mut CheckReport, key: &str, mss: usize) {
+    if (MIN_MSS..=MAX_MSS).contains(&mss) {
+        report.entry(key, mss);
+    } else {
+        report.problem(format_args!(
+            "{}: {} must be between {} and {}",
+            key, mss, MIN_MSS, MAX_MSS
+        ));
+    }
+}
+
+/// Check a latency is within what the protocol can signal
+fn check_latency(report: &mut CheckReport, key: &str, latency_ms: u16) {
+    if latency_ms <= MAX_LATENCY_MS {
+        report.entry(key, format_args!("{} ms", latency_ms));
+    } else {
+        report.problem(format_args!(
+            "{}: {} ms exceeds the maximum of {} ms",
+            key, latency_ms, MAX_LATENCY_MS
+        ));
     }
 }
 
@@ -198,6 +429,108 @@ impl SenderConfig {
     pub fn stats_interval(&self) -> Duration {
         Duration::from_secs(self.stats_interval_secs)
     }
+
+    fn validate(&self, report: &mut CheckReport) {
+        report.entry("sender.input", &self.input);
+        if self.input != "-"
+            && !self.input.starts_with("udp://")
+            && !Path::new(&self.input).is_file()
+        {
+            report.problem(format_args!("sender.input: no such file '{}'", self.input));
+        }
+        report.entry("sender.mode", self.mode);
+        if self.paths.is_empty() {
+            report.problem("sender.paths: at least one path is required");
+        }
+        let mut names = HashSet::new();
+        for path in &self.paths {
+            let key = format!("sender.paths.{}", path.name);
+            if !names.insert(path.name.as_str()) {
+                report.problem(format_args!("{}: duplicate path name", key));
+            }
+            report.entry(&key, path.address);
+            if let Some(bind) = path.bind {
+                let key = format!("{}.bind", key);
+                if bind.is_ipv4() == path.address.is_ipv4() {
+                    report.bind(&key, bind);
+                } else {
+                    report.problem(format_args!(
+                        "{}: {} cannot reach {}",
+                        key, bind, path.address
+                    ));
+                }
+            }
+            if !(0.0..=1.0).contains(&path.weight) {
+                report.problem(format_args!(
+                    "{}.weight: {} is outside 0.0 to 1.0",
+                    key, path.weight
+                ));
+            }
+        }
+        check_mss(report, "sender.mtu", self.mtu);
+        check_latency(report, "sender.latency_ms", self.latency_ms);
+        report.passphrase("sender.passphrase", self.passphrase.as_ref());
+    }
+}
+
+impl RelayConfig {
+    /// Find the route for a Stream ID
+    ///
+    /// An exact match wins; otherwise the wildcard route is used if present.
+    /// A caller that sends no Stream ID is matched as the empty string.
+    pub fn route(&self, stream_id: &str) -> Option<&StreamRoute> {
+        self.streams
+            .iter()
+            .find(|r| r.stream_id == stream_id)
+            .or_else(|| {
+                self.streams
+                    .iter()
+                    .find(|r| r.stream_id == WILDCARD_STREAM_ID)
+            })
+    }
+
+    /// Get statistics interval as Duration
+    pub fn stats_interval(&self) -> Duration {
+        Duration::from_secs(self.stats_interval_secs)
+    }
+
+    fn validate(&self, report: &mut CheckReport) {
+        if self.streams.is_empty() {
+            report.problem("relay.streams: at least one route is required");
+        }
+        let mut ids = HashSet::new();
+        for route in &self.streams {
+            let key = format!("relay.streams.{}", route.stream_id);
+            if !ids.insert(route.stream_id.as_str()) {
+                report.problem(format_args!("{}: duplicate stream ID", key));
+            }
+            if route.outputs.is_empty() {
+                report.problem(format_args!("{}: at least one output is required", key));
+            } else {
+                report.entry(&key, route.outputs.join(", "));
+            }
+            if let Some(labels) = report.check(&format!("{}.labels", key), route.labels()) {
+                if !labels.is_empty() {
+                    report.entry(format!("{}.labels", key), labels);
+                }
+            }
+        }
+        check_latency(report, "relay.latency_ms", self.latency_ms);
+        if let Some(filter) = &self.packet_filter {
+            if let Some(filter) =
+                report.check("relay.packet_filter", FilterConfig::parse_builtin(filter))
+            {
+                report.entry("relay.packet_filter", filter);
+            }
+        }
+        for rule in &self.admission.allow {
+            report.entry("relay.allow", rule);
+        }
+        for rule in &self.admission.deny {
+            report.entry("relay.deny", rule);
+        }
+        report.passphrase("relay.passphrase", self.passphrase.as_ref());
+    }
 }
 
 impl ReceiverConfig {
@@ -205,6 +538,36 @@ impl ReceiverConfig {
     pub fn stats_interval(&self) -> Duration {
         Duration::from_secs(self.stats_interval_secs)
     }
+
+    fn validate(&self, report: &mut CheckReport) {
+        report.entry("receiver.output", &self.output);
+        report.entry("receiver.mode", self.mode);
+        if self.listen.is_empty() {
+            report.problem("receiver.listen: at least one listen address is required");
+        }
+        for (i, addr) in self.listen.iter().enumerate() {
+            report.bind(&format!("receiver.listen.{}", i), *addr);
+        }
+        if self.buffer_size == 0 {
+            report.problem("receiver.buffer_size: must be at least 1");
+        }
+        check_latency(report, "receiver.latency_ms", self.latency_ms);
+        report.passphrase("receiver.passphrase", self.passphrase.as_ref());
+    }
+}
+
+impl TelemetryConfig {
+    fn validate(&self, report: &mut CheckReport) {
+        // The exporter speaks plain OTLP/HTTP
+        if self.endpoint.starts_with("http://") {
+            report.entry("telemetry.endpoint", &self.endpoint);
+        } else {
+            report.problem(format_args!(
+                "telemetry.endpoint: '{}' is not an http:// URL",
+                self.endpoint
+            ));
+        }
+    }
 }
 
 #[cfg(test)]
@@ -218,6 +581,69 @@ mod tests {
 
         let receiver_config = Config::example_receiver();
         assert!(receiver_config.receiver.is_some());
+
+        let relay_config = Config::example_relay();
+        assert!(relay_config.relay.is_some());
+    }
+
+    #[test]
+    fn test_relay_routes() {
+        let toml = r#"
+            [relay]
+            allow = ["10.0.0.0/8"]
+            deny = ["10.0.0.66"]
+
+            [[relay.streams]]
+            stream_id = "live/cam1"
+            outputs = ["udp://127.0.0.1:5001"]
+            labels = { site = "nyc", camera = "1" }
+
+            [[relay.streams]]
+            stream_id = "*"
+            outputs = ["-"]
+        "#;
+        let config: Config = toml::from_str(toml).unwrap();
+        let relay = config.relay.unwrap();
+
+        assert_eq!(relay.latency_ms, 120);
+        assert_eq!(relay.admission.allow, vec!["10.0.0.0/8".parse().unwrap()]);
+        assert_eq!(relay.admission.deny[0].to_string(), "10.0.0.66");
+        assert_eq!(
+            relay.route("live/cam1").unwrap().outputs,
+            vec!["udp://127.0.0.1:5001"]
+        );
+        assert_eq!(relay.route("other").unwrap().stream_id, "*");
+        assert_eq!(
+            relay
+                .route("live/cam1")
+                .unwrap()
+                .labels()
+                .unwrap()
+                .to_string(),
+            "camera=1,site=nyc"
+        );
+        assert!(relay.route("other").unwrap().labels().unwrap().is_empty());
+
+        let strict = RelayConfig {
+            streams: relay.streams[..1].to_vec(),
+            ..relay
+        };
+        assert!(strict.route("other").is_none());
+    }
+
+    #[test]
+    fn test_telemetry_section() {
+        let toml = r#"
+            [telemetry]
+            endpoint = "http://collector:4318"
+            service_name = "edge-relay"
+        "#;
+        let config: Config = toml::from_str(toml).unwrap();
+        let telemetry = config.telemetry.unwrap();
+        assert_eq!(telemetry.endpoint, "http://collector:4318");
+        assert_eq!(telemetry.service_name.as_deref(), Some("edge-relay"));
+        assert_eq!(telemetry.interval_secs, 10);
+        assert!(Config::example_relay().telemetry.is_none());
     }
 
     #[test]
@@ -228,4 +654,129 @@ mod tests {
 
         assert!(parsed.sender.is_some());
     }
+
+    #[test]
+    fn test_validate() {
+        let mut config = Config::example_relay();
+        config.relay.as_mut().unwrap().packet_filter = Some("fec,cols:10".into());
+        config.receiver = Some(ReceiverConfig {
+            listen: vec!["127.0.0.1:0".parse().unwrap()],
+            ..Config::example_receiver().receiver.unwrap()
+        });
+        let report = config.validate();
+        assert!(report.is_ok(), "{}", report);
+        assert_eq!(
+            report.get("relay.streams.live/cam2"),
+            Some("udp://127.0.0.1:5002, cam2.ts")
+        );
+        assert_eq!(
+            report.get("relay.streams.live/cam1.labels"),
+            Some("camera=1")
+        );
+        assert_eq!(
+            report.get("receiver.passphrase"),
+            Some("none (unencrypted)")
+        );
+        assert!(report
+            .get("receiver.listen.0")
+            .unwrap()
+            .starts_with("127.0.0.1:"));
+
+        // The tools cannot encrypt, so a passphrase fails the check
+        config.receiver.as_mut().unwrap().passphrase =
+            Some(SecretBytes::from("correct horse battery"));
+        let report = config.validate();
+        assert_eq!(
+            report.problems(),
+            ["receiver.passphrase: passphrase encryption is not supported yet"]
+        );
+        assert!(!report.to_string().contains("horse"));
+    }
+
+    #[test]
+    fn test_validate_collects_problems() {
+        let empty: Config = toml::from_str("").unwrap();
+        assert_eq!(empty.validate().problems().len(), 1);
+
+        let mut config = Config::example_sender();
+        let sender = config.sender.as_mut().unwrap();
+        sender.input = "/nonexistent/input.ts".into();
+        sender.paths[1].name = "cellular1".into();
+        sender.paths[1].bind = Some("[::1]:0".parse().unwrap());
+        sender.paths[1].weight = 1.5;
+        sender.mtu = 20;
+        sender.latency_ms = u16::MAX;
+        sender.passphrase = Some(SecretBytes::from("short"));
+        config.telemetry = Some(TelemetryConfig {
+            endpoint: "collector:4318".into(),
+            service_name: None,
+            interval_secs: 10,
+        });
+
+        let report = config.validate();
+        let problems = report.problems().join("\n");
+        for expected in [
+            "sender.input: no such file",
+            "sender.paths.cellular1: duplicate path name",
+            "sender.paths.cellular1.bind: [::1]:0 cannot reach 192.168.2.10:9000",
+            "sender.paths.cellular1.weight: 1.5",
+            "sender.mtu: 20",
+            "sender.latency_ms: 65535 ms",
+            "sender.passphrase: passphrase is 5 bytes",
+            "sender.passphrase: passphrase encryption is not supported yet",
+            "telemetry.endpoint: 'collector:4318'",
+        ] {
+            assert!(
+                problems.contains(expected),
+                "missing '{}' in\n{}",
+                expected,
+                problems
+            );
+        }
+        assert_eq!(report.problems().len(), 9);
+    }
+
+    #[test]
+    fn test_passphrase_not_in_debug() {
+        let toml = r#"
+            [receiver]
+            output = "-"
+            mode = "broadcast"
+            listen = ["0.0.0.0:9000"]
+            passphrase = "correct horse battery"
+        "#;
+        let config: Config = toml::from_str(toml).unwrap();
+        let receiver = config.receiver.as_ref().unwrap();
+
+        assert_eq!(
+            receiver.passphrase.as_ref().unwrap().expose_secret(),
+            b"correct horse battery"
+        );
+        assert!(!format!("{:?}", config).contains("horse"));
+    }
+
+    #[test]
+    fn test_passphrase_refused_on_load() {
+        let path = std::env::temp_dir().join(format!("srt-passphrase-{}.toml", std::process::id()));
+        fs::write(
+            &path,
+            r#"
+            [relay]
+            passphrase = "correct horse battery"
+
+            [[relay.streams]]
+            stream_id = "*"
+            outputs = ["-"]
+        "#,
+        )
+        .unwrap();
+        let result = Config::from_file(&path);
+        fs::remove_file(&path).unwrap();
+
+        let err = result.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid configuration: relay.passphrase: passphrase encryption is not supported yet"
+        );
+    }
 }
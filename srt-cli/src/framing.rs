@@ -0,0 +1,309 @@
+//! Input framing for message mode
+//!
+//! stdin and files are byte streams, so a plain read can end anywhere in
+//! what the producer meant as one message. `MessageReader` splits the
+//! stream into discrete messages before packetization: each message is
+//! sent as one SRT message, and its boundaries reach the receiver intact.
+
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read};
+use std::str::FromStr;
+
+/// Largest length-prefixed or newline-delimited message (bytes)
+pub const MAX_MESSAGE_SIZE: usize = 1024 * 1024;
+
+/// MPEG-TS packet length
+const TS_PACKET_SIZE: usize = 188;
+
+/// MPEG-TS sync byte
+const TS_SYNC_BYTE: u8 = 0x47;
+
+/// How the input byte stream is split into messages
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Framing {
+    /// Whatever each read returns, up to one packet payload
+    #[default]
+    None,
+    /// A 4-byte big-endian length, then that many bytes
+    LengthPrefixed,
+    /// Whole 188-byte TS packets, as many as fit in one packet payload
+    Mpegts,
+    /// One line per message, newline included
+    Newline,
+}
+
+impl fmt::Display for Framing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Framing::None => write!(f, "none"),
+            Framing::LengthPrefixed => write!(f, "length-prefixed"),
+            Framing::Mpegts => write!(f, "mpegts"),
+            Framing::Newline => write!(f, "newline"),
+        }
+    }
+}
+
+impl FromStr for Framing {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Framing::None),
+            "length-prefixed" => Ok(Framing::LengthPrefixed),
+            "mpegts" => Ok(Framing::Mpegts),
+            "newline" => Ok(Framing::Newline),
+            _ => Err(format!(
+                "Invalid framing '{}': use none, length-prefixed, mpegts or newline",
+                s
+            )),
+        }
+    }
+}
+
+/// Splits a byte stream into messages
+pub struct MessageReader<R: Read> {
+    inner: BufReader<R>,
+    framing: Framing,
+    /// Payload of one packet; sizes unframed reads and TS messages
+    payload_size: usize,
+    /// Bytes skipped looking for a TS sync byte
+    skipped: u64,
+}
+
+impl<R: Read> MessageReader<R> {
+    /// Create a reader producing messages for packets of `payload_size`
+    pub fn new(inner: R, framing: Framing, payload_size: usize) -> Self {
+        MessageReader {
+            inner: BufReader::new(inner),
+            framing,
+            payload_size: payload_size.max(1),
+            skipped: 0,
+        }
+    }
+
+    /// Get the framing in use
+    pub fn framing(&self) -> Framing {
+        self.framing
+    }
+
+    /// Get the number of bytes skipped to regain TS sync
+    pub fn skipped_bytes(&self) -> u64 {
+        self.skipped
+    }
+
+    /// Read the next message, or None at end of input
+    ///
+    /// Length-prefixed messages may be empty. A malformed stream (an
+    /// oversized length prefix) fails with `InvalidData`, and input ending
+    /// inside a length-prefixed message with `UnexpectedEof`.
+    pub fn next_message(&mut self) -> io::Result<Option<Vec<u8>>> {
+        match self.framing {
+            Framing::None => self.next_chunk(),
+            Framing::LengthPrefixed => self.next_length_prefixed(),
+            Framing::Mpegts => self.next_ts_message(),
+            Framing::Newline => self.next_line(),
+        }
+    }
+
+    fn next_chunk(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let mut chunk = vec![0u8; self.payload_size];
+        let n = self.inner.read(&mut chunk)?;
+        if n == 0 {
+            return Ok(None);
+        }
+        chunk.truncate(n);
+        Ok(Some(chunk))
+    }
+
+    fn next_length_prefixed(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let mut prefix = [0u8; 4];
+        if !self.read_exact_or_eof(&mut prefix)? {
+            return Ok(None);
+        }
+
+        let len = u32::from_be_bytes(prefix) as usize;
+        if len > MAX_MESSAGE_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "length prefix of {} bytes exceeds the {} byte limit",
+                    len, MAX_MESSAGE_SIZE
+                ),
+            ));
+        }
+
+        let mut message = vec![0u8; len];
+        self.inner.read_exact(&mut message)?;
+        Ok(Some(message))
+    }
+
+    fn next_ts_message(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let packets = (self.payload_size / TS_PACKET_SIZE).max(1);
+        let mut message = Vec::with_capacity(packets * TS_PACKET_SIZE);
+        let mut packet = [0u8; TS_PACKET_SIZE];
+
+        while message.len() < packets * TS_PACKET_SIZE {
+            if !self.read_ts_packet(&mut packet)? {
+                break;
+            }
+            message.extend_from_slice(&packet);
+        }
+
+        Ok(if message.is_empty() {
+            None
+        } else {
+            Some(message)
+        })
+    }
+
+    /// Read one TS packet, skipping to the next sync byte first
+    ///
+    /// Returns false at end of input; a trailing partial packet is dropped.
+    fn read_ts_packet(&mut self, packet: &mut [u8; TS_PACKET_SIZE]) -> io::Result<bool> {
+        loop {
+            let buf = self.inner.fill_buf()?;
+            if buf.is_empty() {
+                return Ok(false);
+            }
+            match buf.iter().position(|&b| b == TS_SYNC_BYTE) {
+                Some(pos) => {
+                    self.skip(pos);
+                    break;
+                }
+                None => {
+                    let len = buf.len();
+                    self.skip(len);
+                }
+            }
+        }
+
+        match self.inner.read_exact(packet) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn skip(&mut self, n: usize) {
+        if n > 0 {
+            self.inner.consume(n);
+            self.skipped += n as u64;
+            tracing::debug!("Skipped {} bytes to regain TS sync", n);
+        }
+    }
+
+    fn next_line(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let mut line = Vec::new();
+        // Longer lines are split rather than buffered without bound
+        (&mut self.inner)
+            .take(MAX_MESSAGE_SIZE as u64)
+            .read_until(b'\n', &mut line)?;
+        Ok(if line.is_empty() { None } else { Some(line) })
+    }
+
+    /// Fill `buf`, or return false if the input ends before the first byte
+    fn read_exact_or_eof(&mut self, buf: &mut [u8]) -> io::Result<bool> {
+        if self.inner.fill_buf()?.is_empty() {
+            return Ok(false);
+        }
+        self.inner.read_exact(buf)?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(input: &[u8], framing: Framing, payload_size: usize) -> Vec<Vec<u8>> {
+        let mut reader = MessageReader::new(input, framing, payload_size);
+        std::iter::from_fn(|| reader.next_message().unwrap()).collect()
+    }
+
+    fn ts_packet(pid: u8) -> Vec<u8> {
+        let mut packet = vec![pid; TS_PACKET_SIZE];
+        packet[0] = TS_SYNC_BYTE;
+        packet
+    }
+
+    #[test]
+    fn test_framing_parse() {
+        for framing in [
+            Framing::None,
+            Framing::LengthPrefixed,
+            Framing::Mpegts,
+            Framing::Newline,
+        ] {
+            assert_eq!(framing.to_string().parse(), Ok(framing));
+        }
+        assert!("lines".parse::<Framing>().is_err());
+    }
+
+    #[test]
+    fn test_length_prefixed() {
+        let mut input = Vec::new();
+        for message in [&b"hello"[..], b"", &[7u8; 3000]] {
+            input.extend_from_slice(&(message.len() as u32).to_be_bytes());
+            input.extend_from_slice(message);
+        }
+        let messages = messages(&input, Framing::LengthPrefixed, 1316);
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[0], b"hello");
+        assert!(messages[1].is_empty());
+        assert_eq!(messages[2], vec![7u8; 3000]);
+
+        // Input ending inside a message
+        let mut reader = MessageReader::new(&[0, 0, 0, 9, 1, 2][..], Framing::LengthPrefixed, 1316);
+        let err = reader.next_message().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        let oversized = (MAX_MESSAGE_SIZE as u32 + 1).to_be_bytes();
+        let mut reader = MessageReader::new(&oversized[..], Framing::LengthPrefixed, 1316);
+        let err = reader.next_message().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_mpegts() {
+        let mut input = vec![0xFF, 0x00, 0x12];
+        for pid in 0..9 {
+            input.extend_from_slice(&ts_packet(pid));
+        }
+        input.extend_from_slice(&[TS_SYNC_BYTE, 1, 2]);
+
+        let mut reader = MessageReader::new(&input[..], Framing::Mpegts, 1316);
+        let first = reader.next_message().unwrap().unwrap();
+        assert_eq!(first.len(), 7 * TS_PACKET_SIZE);
+        assert_eq!(&first[..TS_PACKET_SIZE], &ts_packet(0)[..]);
+
+        // The rest, without the trailing partial packet
+        let second = reader.next_message().unwrap().unwrap();
+        assert_eq!(second.len(), 2 * TS_PACKET_SIZE);
+        assert_eq!(&second[TS_PACKET_SIZE..], &ts_packet(8)[..]);
+        assert!(reader.next_message().unwrap().is_none());
+        assert_eq!(reader.skipped_bytes(), 3);
+
+        // Messages shrink to fit a smaller payload
+        let input: Vec<u8> = (0..4).flat_map(ts_packet).collect();
+        let messages = messages(&input, Framing::Mpegts, 400);
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].len(), 2 * TS_PACKET_SIZE);
+    }
+
+    #[test]
+    fn test_newline_and_unframed() {
+        let lines = messages(b"one\ntwo\n\nthree", Framing::Newline, 1316);
+        assert_eq!(lines, vec![&b"one\n"[..], b"two\n", b"\n", b"three"]);
+
+        let long = vec![b'x'; MAX_MESSAGE_SIZE + 10];
+        let lines = messages(&long, Framing::Newline, 1316);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].len(), MAX_MESSAGE_SIZE);
+
+        let chunks = messages(&[1u8; 3000], Framing::None, 1316);
+        assert_eq!(
+            chunks.iter().map(Vec::len).collect::<Vec<_>>(),
+            vec![1316, 1316, 368]
+        );
+    }
+}
@@ -0,0 +1,423 @@
+//! Input jitter buffer for UDP sources
+//!
+//! Some encoders send bursty or reordered UDP. The jitter buffer sits
+//! between the input socket and the outputs: RTP input is put back in
+//! sequence-number order, waiting up to the configured depth for missing
+//! packets; plain MPEG-TS input is delayed by the depth and released at the
+//! average arrival rate, so bursts come out evenly spaced.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// Default maximum number of datagrams held
+pub const DEFAULT_JITTER_PACKETS: usize = 8192;
+
+/// MPEG-TS sync byte
+const TS_SYNC_BYTE: u8 = 0x47;
+
+/// Fixed RTP header length
+const RTP_HEADER_LEN: usize = 12;
+
+/// How the jitter buffer orders datagrams
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JitterMode {
+    /// Pick RTP or TS from the first datagram
+    Auto,
+    /// Reorder by RTP sequence number
+    Rtp,
+    /// Smooth arrival times of plain MPEG-TS
+    Ts,
+}
+
+impl fmt::Display for JitterMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JitterMode::Auto => write!(f, "auto"),
+            JitterMode::Rtp => write!(f, "rtp"),
+            JitterMode::Ts => write!(f, "ts"),
+        }
+    }
+}
+
+impl FromStr for JitterMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(JitterMode::Auto),
+            "rtp" => Ok(JitterMode::Rtp),
+            "ts" => Ok(JitterMode::Ts),
+            _ => Err(format!("Invalid jitter mode '{}': use auto, rtp or ts", s)),
+        }
+    }
+}
+
+/// Jitter buffer statistics
+#[derive(Debug, Clone, Default)]
+pub struct JitterStats {
+    /// Datagrams received from the input
+    pub received: u64,
+    /// Datagrams handed to the outputs
+    pub released: u64,
+    /// Datagrams that arrived after their slot was released (RTP only)
+    pub late: u64,
+    /// Datagrams given up on: RTP gaps skipped, or overflow when full
+    pub dropped: u64,
+    /// Duplicate datagrams discarded (RTP only)
+    pub duplicates: u64,
+    /// Datagrams currently held
+    pub buffered: usize,
+}
+
+/// Input jitter buffer
+pub struct JitterBuffer {
+    /// Configured mode (`Auto` until the first datagram)
+    mode: JitterMode,
+    /// How long to hold datagrams
+    depth: Duration,
+    /// Maximum datagrams held
+    max_packets: usize,
+    /// RTP datagrams by extended sequence number, with arrival time
+    rtp: BTreeMap<u64, (Instant, Vec<u8>)>,
+    /// Next RTP sequence number to release (None until output starts)
+    next_seq: Option<u64>,
+    /// Highest extended RTP sequence number seen
+    highest_seq: Option<u64>,
+    /// TS datagrams with their release time
+    ts: VecDeque<(Instant, Vec<u8>)>,
+    /// Release time of the last queued TS datagram
+    last_release: Option<Instant>,
+    /// Arrival time of the last datagram
+    last_arrival: Option<Instant>,
+    /// Smoothed time between arrivals
+    interval: Duration,
+    /// Datagrams that bypass ordering (non-RTP data in RTP mode)
+    passthrough: VecDeque<Vec<u8>>,
+    /// Statistics
+    stats: JitterStats,
+}
+
+impl JitterBuffer {
+    /// Create a jitter buffer holding datagrams for up to `depth`
+    pub fn new(mode: JitterMode, depth: Duration) -> Self {
+        JitterBuffer {
+            mode,
+            depth,
+            max_packets: DEFAULT_JITTER_PACKETS,
+            rtp: BTreeMap::new(),
+            next_seq: None,
+            highest_seq: None,
+            ts: VecDeque::new(),
+            last_release: None,
+            last_arrival: None,
+            interval: Duration::ZERO,
+            passthrough: VecDeque::new(),
+            stats: JitterStats::default(),
+        }
+    }
+
+    /// Set the maximum number of datagrams held
+    pub fn set_max_packets(&mut self, max_packets: usize) {
+        self.max_packets = max_packets.max(1);
+    }
+
+    /// Get the mode in use (`Auto` until the first datagram arrives)
+    pub fn mode(&self) -> JitterMode {
+        self.mode
+    }
+
+    /// Queue a datagram that arrived at `now`
+    pub fn push(&mut self, data: &[u8], now: Instant) {
+        self.stats.received += 1;
+        if self.mode == JitterMode::Auto {
+            self.mode = detect_mode(data);
+            tracing::info!("Input jitter buffer: {} mode", self.mode);
+        }
+
+        if let Some(last) = self.last_arrival.replace(now) {
+            let sample = now.saturating_duration_since(last);
+            self.interval = self.interval * 7 / 8 + sample / 8;
+        }
+
+        match self.mode {
+            JitterMode::Rtp => self.push_rtp(data, now),
+            _ => self.push_ts(data, now),
+        }
+    }
+
+    fn push_rtp(&mut self, data: &[u8], now: Instant) {
+        let Some(seq) = rtp_sequence(data) else {
+            self.passthrough.push_back(data.to_vec());
+            return;
+        };
+
+        // Extend the 16-bit sequence number around the highest seen
+        let ext = match self.highest_seq {
+            None => u64::from(seq) + (1 << 16),
+            Some(highest) => {
+                let delta = seq.wrapping_sub(highest as u16) as i16;
+                highest.saturating_add_signed(i64::from(delta))
+            }
+        };
+        self.highest_seq = Some(self.highest_seq.map_or(ext, |h| h.max(ext)));
+
+        if self.next_seq.is_some_and(|next| ext < next) {
+            self.stats.late += 1;
+            return;
+        }
+        if self.rtp.contains_key(&ext) {
+            self.stats.duplicates += 1;
+            return;
+        }
+        self.rtp.insert(ext, (now, data.to_vec()));
+
+        if self.rtp.len() > self.max_packets {
+            // Full: give up waiting for the oldest gap
+            let (&first, _) = self.rtp.first_key_value().expect("not empty");
+            self.skip_to(first);
+        }
+    }
+
+    fn push_ts(&mut self, data: &[u8], now: Instant) {
+        // Hold for the depth, spaced at least one average interval apart,
+        // but never longer than twice the depth
+        let release = self
+            .last_release
+            .map_or(now + self.depth, |last| {
+                (last + self.interval).max(now + self.depth)
+            })
+            .min(now + self.depth * 2);
+        self.last_release = Some(release);
+        self.ts.push_back((release, data.to_vec()));
+
+        if self.ts.len() > self.max_packets {
+            self.ts.pop_front();
+            self.stats.dropped += 1;
+        }
+    }
+
+    /// Move the RTP release point to `seq`, counting the skipped gap
+    fn skip_to(&mut self, seq: u64) {
+        if let Some(next) = self.next_seq {
+            self.stats.dropped += seq.saturating_sub(next);
+        }
+        self.next_seq = Some(seq);
+    }
+
+    /// Get the next datagram due at `now`, if any
+    pub fn poll(&mut self, now: Instant) -> Option<Vec<u8>> {
+        let data = if let Some(data) = self.passthrough.pop_front() {
+            data
+        } else if self.mode == JitterMode::Rtp {
+            let (&first, &(arrival, _)) = self.rtp.first_key_value()?;
+            let waited = now.saturating_duration_since(arrival) >= self.depth;
+            // In-order packets go straight out; a gap is waited on for the depth
+            if self.next_seq != Some(first) {
+                if !waited {
+                    return None;
+                }
+                self.skip_to(first);
+            }
+            self.next_seq = Some(first + 1);
+            self.rtp.remove(&first).map(|(_, data)| data)?
+        } else {
+            if self.ts.front().map_or(true, |(release, _)| *release > now) {
+                return None;
+            }
+            self.ts.pop_front().map(|(_, data)| data)?
+        };
+
+        self.stats.released += 1;
+        Some(data)
+    }
+
+    /// Get time until the next datagram may be due
+    pub fn time_until_next(&self, now: Instant) -> Option<Duration> {
+        if !self.passthrough.is_empty() {
+            return Some(Duration::ZERO);
+        }
+        let due = match self.mode {
+            JitterMode::Rtp => self.rtp.first_key_value().map(|(&seq, (arrival, _))| {
+                if self.next_seq == Some(seq) {
+                    now
+                } else {
+                    *arrival + self.depth
+                }
+            }),
+            _ => self.ts.front().map(|(release, _)| *release),
+        };
+        due.map(|t| t.saturating_duration_since(now))
+    }
+
+    /// Release everything held, in order, skipping any gaps
+    pub fn drain(&mut self) -> Vec<Vec<u8>> {
+        let mut out: Vec<_> = self.passthrough.drain(..).collect();
+        let rtp = std::mem::take(&mut self.rtp);
+        for (seq, (_, data)) in rtp {
+            self.skip_to(seq);
+            self.next_seq = Some(seq + 1);
+            out.push(data);
+        }
+        out.extend(self.ts.drain(..).map(|(_, data)| data));
+        self.stats.released += out.len() as u64;
+        out
+    }
+
+    /// Get jitter buffer statistics
+    pub fn stats(&self) -> JitterStats {
+        JitterStats {
+            buffered: self.passthrough.len() + self.rtp.len() + self.ts.len(),
+            ..self.stats.clone()
+        }
+    }
+}
+
+/// Guess the input format from a datagram
+fn detect_mode(data: &[u8]) -> JitterMode {
+    if data.first() != Some(&TS_SYNC_BYTE) && rtp_sequence(data).is_some() {
+        JitterMode::Rtp
+    } else {
+        JitterMode::Ts
+    }
+}
+
+/// Get the sequence number of an RTP version 2 datagram
+fn rtp_sequence(data: &[u8]) -> Option<u16> {
+    if data.len() < RTP_HEADER_LEN || data[0] >> 6 != 2 {
+        return None;
+    }
+    Some(u16::from_be_bytes([data[2], data[3]]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rtp(seq: u16) -> Vec<u8> {
+        let mut data = vec![0x80, 33];
+        data.extend_from_slice(&seq.to_be_bytes());
+        data.extend_from_slice(&[0; 8]);
+        data.push(TS_SYNC_BYTE);
+        data
+    }
+
+    fn seqs(out: &[Vec<u8>]) -> Vec<u16> {
+        out.iter().map(|d| rtp_sequence(d).unwrap()).collect()
+    }
+
+    fn poll_all(jitter: &mut JitterBuffer, now: Instant) -> Vec<Vec<u8>> {
+        std::iter::from_fn(|| jitter.poll(now)).collect()
+    }
+
+    #[test]
+    fn test_mode_detection() {
+        assert_eq!(detect_mode(&rtp(5)), JitterMode::Rtp);
+        assert_eq!(detect_mode(&[TS_SYNC_BYTE; 188]), JitterMode::Ts);
+        assert_eq!("rtp".parse::<JitterMode>().unwrap(), JitterMode::Rtp);
+        assert!("mp4".parse::<JitterMode>().is_err());
+
+        let mut jitter = JitterBuffer::new(JitterMode::Auto, Duration::from_millis(50));
+        jitter.push(&rtp(1), Instant::now());
+        assert_eq!(jitter.mode(), JitterMode::Rtp);
+    }
+
+    #[test]
+    fn test_rtp_reorder() {
+        let depth = Duration::from_millis(50);
+        let mut jitter = JitterBuffer::new(JitterMode::Rtp, depth);
+        let start = Instant::now();
+
+        // Output starts once the first packet has waited the depth
+        jitter.push(&rtp(11), start);
+        jitter.push(&rtp(10), start);
+        assert!(jitter.poll(start).is_none());
+        assert_eq!(seqs(&poll_all(&mut jitter, start + depth)), vec![10, 11]);
+
+        // In-order packets are not delayed; reordered ones are fixed up
+        let t = start + depth;
+        jitter.push(&rtp(13), t);
+        assert!(jitter.poll(t).is_none());
+        jitter.push(&rtp(12), t);
+        assert_eq!(seqs(&poll_all(&mut jitter, t)), vec![12, 13]);
+
+        // A gap is waited on for the depth, then skipped
+        jitter.push(&rtp(16), t);
+        assert!(jitter.poll(t + depth / 2).is_none());
+        assert_eq!(seqs(&poll_all(&mut jitter, t + depth)), vec![16]);
+
+        // The skipped packets are now late; duplicates are discarded
+        jitter.push(&rtp(14), t + depth);
+        jitter.push(&rtp(17), t + depth);
+        jitter.push(&rtp(17), t + depth);
+        assert_eq!(seqs(&poll_all(&mut jitter, t + depth)), vec![17]);
+
+        let stats = jitter.stats();
+        assert_eq!(stats.received, 8);
+        assert_eq!(stats.released, 6);
+        assert_eq!(stats.dropped, 2);
+        assert_eq!(stats.late, 1);
+        assert_eq!(stats.duplicates, 1);
+        assert_eq!(stats.buffered, 0);
+    }
+
+    #[test]
+    fn test_rtp_sequence_wrap() {
+        let mut jitter = JitterBuffer::new(JitterMode::Rtp, Duration::ZERO);
+        let now = Instant::now();
+
+        for seq in [65534, 0, 65535, 1] {
+            jitter.push(&rtp(seq), now);
+        }
+        let mut out = poll_all(&mut jitter, now);
+        out.extend(jitter.drain());
+        assert_eq!(seqs(&out), vec![65534, 65535, 0, 1]);
+        assert_eq!(jitter.stats().dropped, 0);
+    }
+
+    #[test]
+    fn test_rtp_overflow() {
+        let mut jitter = JitterBuffer::new(JitterMode::Rtp, Duration::from_secs(1));
+        jitter.set_max_packets(4);
+        let now = Instant::now();
+
+        jitter.push(&rtp(0), now);
+        assert_eq!(
+            seqs(&poll_all(&mut jitter, now + Duration::from_secs(1))),
+            vec![0]
+        );
+        for seq in 2..7 {
+            jitter.push(&rtp(seq), now);
+        }
+        // Full: the missing packet 1 is given up on
+        assert_eq!(seqs(&poll_all(&mut jitter, now)), vec![2, 3, 4, 5, 6]);
+        assert_eq!(jitter.stats().dropped, 1);
+    }
+
+    #[test]
+    fn test_ts_smoothing() {
+        let depth = Duration::from_millis(20);
+        let mut jitter = JitterBuffer::new(JitterMode::Ts, depth);
+        let start = Instant::now();
+        let ts = [TS_SYNC_BYTE; 188];
+
+        // Steady input every 10ms sets the interval, then a burst of 4
+        for i in 0..40 {
+            jitter.push(&ts, start + Duration::from_millis(10 * i));
+        }
+        let burst = start + Duration::from_millis(400);
+        for _ in 0..4 {
+            jitter.push(&ts, burst);
+        }
+        // Steady input comes out one depth after arriving
+        assert_eq!(poll_all(&mut jitter, burst + depth / 2).len(), 40);
+
+        // The burst is spread out at the input rate, not sent at once
+        assert_eq!(poll_all(&mut jitter, burst + depth).len(), 1);
+        assert_eq!(jitter.stats().buffered, 3);
+        assert_eq!(poll_all(&mut jitter, burst + depth * 2).len(), 3);
+        assert!(jitter.time_until_next(burst).is_none());
+        assert_eq!(jitter.stats().released, 44);
+    }
+}
@@ -1,9 +1,48 @@
 //! SRT CLI Library
 //!
 //! Shared functionality for SRT command-line tools.
+#![deny(unsafe_code)]
 
+pub mod admission;
+pub mod caller;
+pub mod check;
 pub mod config;
+pub mod framing;
+pub mod jitter;
+#[cfg(feature = "otel")]
+pub mod otel;
+pub mod periodic;
+pub mod shaping;
+#[cfg(feature = "simulate")]
+pub mod simulate;
 pub mod stats;
+pub mod summary;
+pub mod transport;
+pub mod writeahead;
 
-pub use config::{BondingMode, Config, PathConfig, ReceiverConfig, SenderConfig};
+pub use admission::{AddrRule, AdmissionPolicy};
+pub use caller::{
+    CallerArgs, CallerError, CallerOptions, CallerOutput, CallerPool, CallerStats, ReconnectPolicy,
+};
+pub use check::CheckReport;
+pub use config::{
+    BondingMode, Config, PathConfig, ReceiverConfig, RelayConfig, SenderConfig, StreamRoute,
+    TelemetryConfig,
+};
+pub use framing::{Framing, MessageReader};
+pub use jitter::{JitterBuffer, JitterMode, JitterStats};
+#[cfg(feature = "otel")]
+pub use otel::{OtelArgs, OtelConfig, OtelExporter, OtelHandle, OtelStats, SpanBuilder};
+pub use periodic::PeriodicTask;
+pub use shaping::{CbrShaper, ShapedUdpOutput, ShaperStats, ShaperStatsHandle};
+#[cfg(feature = "simulate")]
+pub use simulate::{
+    simulate, PathReport, PathTrace, SimulationConfig, SimulationError, SimulationReport,
+    TraceSample,
+};
 pub use stats::{display_compact_stats, display_group_stats, format_bandwidth, format_bytes};
+pub use summary::{Outcome, RunSummary};
+pub use transport::TransportArgs;
+pub use writeahead::{
+    OverflowPolicy, WriteAheadArgs, WriteAheadHandle, WriteAheadStats, WriteAheadWriter,
+};
@@ -0,0 +1,1023 @@
+//! OpenTelemetry export (feature `otel`)
+//!
+//! Exports group and connection metrics, and spans for lifecycle events
+//! such as handshakes and failovers, to an OpenTelemetry collector. The
+//! exporter speaks OTLP/HTTP with the JSON encoding over a plain HTTP/1.1
+//! connection, so the feature adds no dependencies; point it at the
+//! collector's OTLP/HTTP receiver (usually `http://host:4318`).
+//!
+//! Every span of a run belongs to one trace, under a root span covering
+//! the whole run. Metrics are collected and exported on a background
+//! thread every interval, and once more at shutdown.
+
+use crate::config::TelemetryConfig;
+use crate::periodic::PeriodicTask;
+use clap::Args;
+use parking_lot::Mutex;
+use serde_json::{json, Value};
+use srt_bonding::{GroupStats, MemberStats, MemberStatus, SocketGroup};
+use srt_protocol::{ConnectionStats, Labels};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Default export interval
+pub const DEFAULT_EXPORT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Timeout for connecting to the collector and for each request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Instrumentation scope reported with every export
+const SCOPE_NAME: &str = "srt-rust";
+
+/// OpenTelemetry export error
+#[derive(Debug, thiserror::Error)]
+pub enum OtelError {
+    #[error("Invalid OTLP endpoint '{0}': {1}")]
+    InvalidEndpoint(String, String),
+
+    #[error("Collector request failed: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Collector returned HTTP {0}")]
+    Status(u16),
+
+    #[error("Malformed collector response")]
+    BadResponse,
+}
+
+/// Collector OTLP/HTTP endpoint
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Endpoint {
+    host: String,
+    port: u16,
+    base_path: String,
+}
+
+impl Endpoint {
+    /// Parse an `http://host[:port][/path]` URL; the port defaults to 4318
+    pub fn parse(url: &str) -> Result<Self, OtelError> {
+        let invalid = |reason: &str| OtelError::InvalidEndpoint(url.to_string(), reason.into());
+        let rest = url
+            .strip_prefix("http://")
+            .ok_or_else(|| invalid("only http:// endpoints are supported"))?;
+        let (authority, path) = match rest.find('/') {
+            Some(i) => rest.split_at(i),
+            None => (rest, ""),
+        };
+        // A colon inside brackets belongs to an IPv6 address, not a port
+        let (host, port) = match authority.rfind(':') {
+            Some(i) if !authority[i..].contains(']') => (
+                &authority[..i],
+                authority[i + 1..]
+                    .parse()
+                    .map_err(|_| invalid("bad port"))?,
+            ),
+            _ => (authority, 4318),
+        };
+        if host.is_empty() {
+            return Err(invalid("missing host"));
+        }
+
+        Ok(Endpoint {
+            host: host.to_string(),
+            port,
+            base_path: path.trim_end_matches('/').to_string(),
+        })
+    }
+
+    /// Get the request path for a signal (`metrics` or `traces`)
+    fn path(&self, signal: &str) -> String {
+        format!("{}/v1/{}", self.base_path, signal)
+    }
+
+    /// POST a JSON body, returning the HTTP status on success
+    fn post(&self, path: &str, body: &[u8]) -> Result<u16, OtelError> {
+        let host = self.host.trim_start_matches('[').trim_end_matches(']');
+        let addr = (host, self.port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "host not found"))?;
+        let mut stream = TcpStream::connect_timeout(&addr, REQUEST_TIMEOUT)?;
+        stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+        stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+
+        let header = format!(
+            "POST {} HTTP/1.1\r\nHost: {}:{}\r\nContent-Type: application/json\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n",
+            path,
+            self.host,
+            self.port,
+            body.len()
+        );
+        stream.write_all(header.as_bytes())?;
+        stream.write_all(body)?;
+
+        // Only the status line matters
+        let mut response = Vec::new();
+        let mut buf = [0u8; 512];
+        while !response.contains(&b'\n') {
+            let n = stream.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            response.extend_from_slice(&buf[..n]);
+        }
+        let status = std::str::from_utf8(&response)
+            .ok()
+            .and_then(|r| r.split_whitespace().nth(1))
+            .and_then(|code| code.parse().ok())
+            .ok_or(OtelError::BadResponse)?;
+        if (200..300).contains(&status) {
+            Ok(status)
+        } else {
+            Err(OtelError::Status(status))
+        }
+    }
+}
+
+/// Exporter settings
+#[derive(Debug, Clone)]
+pub struct OtelConfig {
+    /// Collector endpoint
+    pub endpoint: Endpoint,
+    /// `service.name` resource attribute
+    pub service_name: String,
+    /// Time between metric exports
+    pub interval: Duration,
+}
+
+impl OtelConfig {
+    /// Create a config exporting every `DEFAULT_EXPORT_INTERVAL`
+    pub fn new(endpoint: &str, service_name: &str) -> Result<Self, OtelError> {
+        Ok(OtelConfig {
+            endpoint: Endpoint::parse(endpoint)?,
+            service_name: service_name.to_string(),
+            interval: DEFAULT_EXPORT_INTERVAL,
+        })
+    }
+
+    /// Build a config from a `[telemetry]` config file section
+    pub fn from_telemetry(
+        telemetry: &TelemetryConfig,
+        default_service: &str,
+    ) -> Result<Self, OtelError> {
+        let mut config = OtelConfig::new(
+            &telemetry.endpoint,
+            telemetry.service_name.as_deref().unwrap_or(default_service),
+        )?;
+        if telemetry.interval_secs > 0 {
+            config.interval = Duration::from_secs(telemetry.interval_secs);
+        }
+        Ok(config)
+    }
+}
+
+/// OpenTelemetry command-line options
+#[derive(Args, Debug, Clone, Default)]
+pub struct OtelArgs {
+    /// Export metrics and traces to this OTLP/HTTP collector
+    /// (e.g. http://localhost:4318)
+    #[arg(long, value_name = "URL")]
+    pub otel_endpoint: Option<String>,
+
+    /// Service name reported to the collector (default: the tool name)
+    #[arg(long, value_name = "NAME")]
+    pub otel_service_name: Option<String>,
+
+    /// Seconds between metric exports
+    #[arg(long, value_name = "SECS", default_value = "10")]
+    pub otel_interval: u64,
+}
+
+impl OtelArgs {
+    /// Get the exporter config, or None if no endpoint is set
+    pub fn config(&self, default_service: &str) -> Result<Option<OtelConfig>, OtelError> {
+        let Some(endpoint) = &self.otel_endpoint else {
+            return Ok(None);
+        };
+        let service = self.otel_service_name.as_deref().unwrap_or(default_service);
+        let mut config = OtelConfig::new(endpoint, service)?;
+        if self.otel_interval > 0 {
+            config.interval = Duration::from_secs(self.otel_interval);
+        }
+        Ok(Some(config))
+    }
+
+    /// Start an exporter if an endpoint is set
+    ///
+    /// `service` is the default service name and the name of the run's
+    /// root span.
+    pub fn start<F>(&self, service: &str, collect: F) -> Result<Option<OtelExporter>, OtelError>
+    where
+        F: Fn() -> Vec<Metric> + Send + Sync + 'static,
+    {
+        Ok(self
+            .config(service)?
+            .map(|config| OtelExporter::start(config, service, collect)))
+    }
+}
+
+/// Metric instrument kind
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricKind {
+    /// Point-in-time value
+    Gauge,
+    /// Monotonic cumulative total
+    Counter,
+}
+
+/// One metric data point
+#[derive(Debug, Clone, PartialEq)]
+pub struct Metric {
+    /// Metric name (e.g. `srt.member.rtt`)
+    pub name: String,
+    /// UCUM unit (e.g. `By`, `ms`, `{packet}`)
+    pub unit: String,
+    /// Instrument kind
+    pub kind: MetricKind,
+    /// Current value
+    pub value: f64,
+    /// Data point attributes
+    pub attributes: Vec<(String, String)>,
+}
+
+impl Metric {
+    /// Create a gauge data point
+    pub fn gauge(name: &str, unit: &str, value: f64) -> Self {
+        Metric {
+            name: name.to_string(),
+            unit: unit.to_string(),
+            kind: MetricKind::Gauge,
+            value,
+            attributes: Vec::new(),
+        }
+    }
+
+    /// Create a counter data point
+    pub fn counter(name: &str, unit: &str, value: u64) -> Self {
+        Metric {
+            kind: MetricKind::Counter,
+            ..Metric::gauge(name, unit, value as f64)
+        }
+    }
+
+    /// Add an attribute
+    pub fn with_attribute(mut self, key: &str, value: &str) -> Self {
+        self.attributes.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// Add every label as an attribute
+    pub fn with_labels(mut self, labels: &Labels) -> Self {
+        self.attributes
+            .extend(labels.iter().map(|(k, v)| (k.to_string(), v.to_string())));
+        self
+    }
+}
+
+/// Metrics for a socket group and each of its members
+pub fn group_metrics(stats: &GroupStats) -> Vec<Metric> {
+    let group_id = stats.group_id.to_string();
+    let group_type = format!("{:?}", stats.group_type).to_lowercase();
+    let group = |metric: Metric| {
+        metric
+            .with_attribute("srt.group.id", &group_id)
+            .with_attribute("srt.group.type", &group_type)
+            .with_labels(&stats.labels)
+    };
+
+    let mut metrics = vec![
+        group(Metric::gauge(
+            "srt.group.members",
+            "{member}",
+            stats.member_count as f64,
+        )),
+        group(Metric::gauge(
+            "srt.group.members.active",
+            "{member}",
+            stats.active_member_count as f64,
+        )),
+        group(Metric::counter(
+            "srt.group.packets.sent",
+            "{packet}",
+            stats.total_packets_sent,
+        )),
+        group(Metric::counter(
+            "srt.group.packets.received",
+            "{packet}",
+            stats.total_packets_received,
+        )),
+        group(Metric::counter(
+            "srt.group.bytes.sent",
+            "By",
+            stats.total_bytes_sent,
+        )),
+        group(Metric::counter(
+            "srt.group.bytes.received",
+            "By",
+            stats.total_bytes_received,
+        )),
+        group(Metric::counter(
+            "srt.group.admission.rejects",
+            "{peer}",
+            stats.admission_rejects,
+        )),
+        group(Metric::gauge(
+            "srt.group.uptime",
+            "s",
+            stats.uptime.as_secs_f64(),
+        )),
+    ];
+    metrics.extend(stats.member_stats.iter().flat_map(member_metrics));
+    metrics
+}
+
+/// Metrics for a socket group, its members and their connections
+pub fn socket_group_metrics(group: &SocketGroup) -> Vec<Metric> {
+    let mut metrics = group_metrics(&group.get_stats());
+    for member in group.get_all_members() {
+        let member_id = member.get_stats().member_id.to_string();
+        metrics.extend(
+            connection_metrics(&member.connection.stats())
+                .into_iter()
+                .map(|m| m.with_attribute("srt.member.id", &member_id)),
+        );
+    }
+    metrics
+}
+
+/// Metrics for one group member
+pub fn member_metrics(stats: &MemberStats) -> Vec<Metric> {
+    let member_id = stats.member_id.to_string();
+    let address = stats.address.to_string();
+    let member = |metric: Metric| {
+        metric
+            .with_attribute("srt.member.id", &member_id)
+            .with_attribute("srt.member.address", &address)
+            .with_labels(&stats.labels)
+    };
+
+    vec![
+        member(Metric::gauge(
+            "srt.member.active",
+            "1",
+            if stats.status == MemberStatus::Active {
+                1.0
+            } else {
+                0.0
+            },
+        )),
+        member(Metric::counter(
+            "srt.member.packets.sent",
+            "{packet}",
+            stats.packets_sent,
+        )),
+        member(Metric::counter(
+            "srt.member.packets.received",
+            "{packet}",
+            stats.packets_received,
+        )),
+        member(Metric::counter(
+            "srt.member.bytes.sent",
+            "By",
+            stats.bytes_sent,
+        )),
+        member(Metric::counter(
+            "srt.member.bytes.received",
+            "By",
+            stats.bytes_received,
+        )),
+        member(Metric::gauge(
+            "srt.member.rtt",
+            "ms",
+            stats.rtt_us as f64 / 1000.0,
+        )),
+        member(Metric::gauge(
+            "srt.member.bandwidth",
+            "By/s",
+            stats.bandwidth_bps as f64,
+        )),
+        member(Metric::counter(
+            "srt.member.failures",
+            "{failure}",
+            stats.failure_count as u64,
+        )),
+        member(Metric::gauge(
+            "srt.member.queue.depth",
+            "{packet}",
+            stats.queue_depth as f64,
+        )),
+    ]
+}
+
+/// Metrics for one connection
+pub fn connection_metrics(stats: &ConnectionStats) -> Vec<Metric> {
+    let connection = |metric: Metric| metric.with_labels(&stats.labels);
+    vec![
+        connection(Metric::counter(
+            "srt.connection.packets.sent",
+            "{packet}",
+            stats.packets_sent,
+        )),
+        connection(Metric::counter(
+            "srt.connection.packets.received",
+            "{packet}",
+            stats.packets_received,
+        )),
+        connection(Metric::counter(
+            "srt.connection.packets.lost",
+            "{packet}",
+            stats.packets_lost,
+        )),
+        connection(Metric::counter(
+            "srt.connection.packets.retransmitted",
+            "{packet}",
+            stats.packets_retransmitted,
+        )),
+        connection(Metric::counter(
+            "srt.connection.packets.received_retransmitted",
+            "{packet}",
+            stats.packets_received_retransmitted,
+        )),
+        connection(Metric::counter(
+            "srt.connection.bytes.sent",
+            "By",
+            stats.bytes_sent,
+        )),
+        connection(Metric::counter(
+            "srt.connection.bytes.received",
+            "By",
+            stats.bytes_received,
+        )),
+        connection(Metric::gauge(
+            "srt.connection.rtt",
+            "ms",
+            stats.rtt_us as f64 / 1000.0,
+        )),
+        connection(Metric::gauge(
+            "srt.connection.receive_rate",
+            "By/s",
+            stats.receive_rate_bps as f64,
+        )),
+        connection(Metric::gauge(
+            "srt.connection.jitter",
+            "ms",
+            stats.arrival_jitter_us as f64 / 1000.0,
+        )),
+        connection(Metric::gauge(
+            "srt.connection.send_buffer",
+            "{packet}",
+            stats.send_buffer_packets as f64,
+        )),
+    ]
+}
+
+/// A finished span
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpanRecord {
+    /// Span name (e.g. `srt.handshake`)
+    pub name: String,
+    /// Start time
+    pub start: SystemTime,
+    /// End time
+    pub end: SystemTime,
+    /// Span attributes
+    pub attributes: Vec<(String, String)>,
+    /// Whether the operation succeeded
+    pub ok: bool,
+}
+
+/// A span in progress, recorded when finished
+#[derive(Debug, Clone)]
+pub struct SpanBuilder {
+    record: SpanRecord,
+}
+
+impl SpanBuilder {
+    /// Start a span now
+    pub fn start(name: &str) -> Self {
+        let now = SystemTime::now();
+        SpanBuilder {
+            record: SpanRecord {
+                name: name.to_string(),
+                start: now,
+                end: now,
+                attributes: Vec::new(),
+                ok: true,
+            },
+        }
+    }
+
+    /// Add an attribute
+    pub fn attribute(mut self, key: &str, value: &str) -> Self {
+        self.record
+            .attributes
+            .push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// End the span now
+    pub fn finish(mut self, ok: bool) -> SpanRecord {
+        self.record.end = SystemTime::now();
+        self.record.ok = ok;
+        self.record
+    }
+}
+
+/// Exporter statistics
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OtelStats {
+    /// Successful requests to the collector
+    pub exports: u64,
+    /// Failed requests
+    pub failures: u64,
+    /// Spans delivered to the collector
+    pub spans_exported: u64,
+    /// Spans discarded after a failed export
+    pub spans_dropped: u64,
+}
+
+/// Most spans held for the next export; older ones are dropped
+const MAX_PENDING_SPANS: usize = 1024;
+
+type Collector = Box<dyn Fn() -> Vec<Metric> + Send + Sync>;
+
+struct Shared {
+    config: OtelConfig,
+    collect: Collector,
+    trace_id: u128,
+    root_span_id: u64,
+    started: SystemTime,
+    spans: Mutex<Vec<SpanRecord>>,
+    stats: Mutex<OtelStats>,
+    next_id: AtomicU64,
+}
+
+impl Shared {
+    fn span_id(&self) -> u64 {
+        random_u64(self.next_id.fetch_add(1, Ordering::Relaxed))
+    }
+
+    fn resource(&self) -> Value {
+        json!({ "attributes": [attribute("service.name", &self.config.service_name)] })
+    }
+
+    fn scope(&self) -> Value {
+        json!({ "name": SCOPE_NAME, "version": env!("CARGO_PKG_VERSION") })
+    }
+
+    fn metrics_body(&self, metrics: &[Metric]) -> Value {
+        let now = unix_nanos(SystemTime::now());
+        let start = unix_nanos(self.started);
+
+        // Data points sharing a name form one metric
+        let mut grouped: Vec<(&Metric, Vec<Value>)> = Vec::new();
+        for metric in metrics {
+            let mut point = json!({
+                "attributes": metric.attributes.iter().map(|(k, v)| attribute(k, v)).collect::<Vec<_>>(),
+                "timeUnixNano": now,
+            });
+            match metric.kind {
+                MetricKind::Gauge => point["asDouble"] = json!(metric.value),
+                MetricKind::Counter => {
+                    point["startTimeUnixNano"] = json!(start);
+                    point["asInt"] = json!((metric.value as u64).to_string());
+                }
+            }
+            match grouped.iter_mut().find(|(m, _)| m.name == metric.name) {
+                Some((_, points)) => points.push(point),
+                None => grouped.push((metric, vec![point])),
+            }
+        }
+
+        let metrics: Vec<Value> = grouped
+            .into_iter()
+            .map(|(metric, points)| {
+                let mut value = json!({ "name": metric.name, "unit": metric.unit });
+                match metric.kind {
+                    MetricKind::Gauge => value["gauge"] = json!({ "dataPoints": points }),
+                    MetricKind::Counter => {
+                        // Cumulative temporality
+                        value["sum"] = json!({
+                            "dataPoints": points,
+                            "aggregationTemporality": 2,
+                            "isMonotonic": true,
+                        })
+                    }
+                }
+                value
+            })
+            .collect();
+
+        json!({
+            "resourceMetrics": [{
+                "resource": self.resource(),
+                "scopeMetrics": [{ "scope": self.scope(), "metrics": metrics }],
+            }]
+        })
+    }
+
+    fn span_json(&self, span: &SpanRecord, span_id: u64, parent: Option<u64>) -> Value {
+        let mut value = json!({
+            "traceId": format!("{:032x}", self.trace_id),
+            "spanId": format!("{:016x}", span_id),
+            "name": span.name,
+            "kind": 1,
+            "startTimeUnixNano": unix_nanos(span.start),
+            "endTimeUnixNano": unix_nanos(span.end),
+            "attributes": span.attributes.iter().map(|(k, v)| attribute(k, v)).collect::<Vec<_>>(),
+            "status": { "code": if span.ok { 1 } else { 2 } },
+        });
+        if let Some(parent) = parent {
+            value["parentSpanId"] = json!(format!("{:016x}", parent));
+        }
+        value
+    }
+
+    fn traces_body(&self, spans: &[SpanRecord], root: Option<&SpanRecord>) -> Value {
+        let mut values: Vec<Value> = spans
+            .iter()
+            .map(|span| self.span_json(span, self.span_id(), Some(self.root_span_id)))
+            .collect();
+        if let Some(root) = root {
+            values.push(self.span_json(root, self.root_span_id, None));
+        }
+
+        json!({
+            "resourceSpans": [{
+                "resource": self.resource(),
+                "scopeSpans": [{ "scope": self.scope(), "spans": values }],
+            }]
+        })
+    }
+
+    fn post(&self, signal: &str, body: &Value) -> bool {
+        let result = self.config.endpoint.post(
+            &self.config.endpoint.path(signal),
+            body.to_string().as_bytes(),
+        );
+        let mut stats = self.stats.lock();
+        match result {
+            Ok(_) => {
+                stats.exports += 1;
+                true
+            }
+            Err(e) => {
+                // Warn once; a collector that stays down should not flood the log
+                if stats.failures == 0 {
+                    tracing::warn!("OpenTelemetry {} export failed: {}", signal, e);
+                } else {
+                    tracing::debug!("OpenTelemetry {} export failed: {}", signal, e);
+                }
+                stats.failures += 1;
+                false
+            }
+        }
+    }
+
+    fn export(&self, root: Option<&SpanRecord>) {
+        let metrics = (self.collect)();
+        if !metrics.is_empty() {
+            self.post("metrics", &self.metrics_body(&metrics));
+        }
+
+        let spans = std::mem::take(&mut *self.spans.lock());
+        if spans.is_empty() && root.is_none() {
+            return;
+        }
+        let delivered = self.post("traces", &self.traces_body(&spans, root));
+        let mut stats = self.stats.lock();
+        if delivered {
+            stats.spans_exported += spans.len() as u64 + root.is_some() as u64;
+        } else {
+            stats.spans_dropped += spans.len() as u64;
+        }
+    }
+}
+
+/// Handle for recording spans, cheap to clone
+#[derive(Clone)]
+pub struct OtelHandle {
+    shared: Arc<Shared>,
+}
+
+impl OtelHandle {
+    /// Queue a finished span for the next export
+    pub fn record(&self, span: SpanRecord) {
+        let mut spans = self.shared.spans.lock();
+        if spans.len() >= MAX_PENDING_SPANS {
+            spans.remove(0);
+            self.shared.stats.lock().spans_dropped += 1;
+        }
+        spans.push(span);
+    }
+
+    /// Get exporter statistics
+    pub fn stats(&self) -> OtelStats {
+        self.shared.stats.lock().clone()
+    }
+}
+
+/// Background OTLP exporter
+///
+/// Exports the metrics returned by its collector closure every interval,
+/// with any spans recorded since the last export. Shutting down (or
+/// dropping) the exporter ends the run's root span and exports one last
+/// time.
+pub struct OtelExporter {
+    handle: OtelHandle,
+    task: Option<PeriodicTask>,
+    root_name: String,
+}
+
+impl OtelExporter {
+    /// Start exporting; `root_name` names the span covering the run
+    pub fn start<F>(config: OtelConfig, root_name: &str, collect: F) -> Self
+    where
+        F: Fn() -> Vec<Metric> + Send + Sync + 'static,
+    {
+        let seed = unix_nanos_u64(SystemTime::now()) ^ std::process::id() as u64;
+        let trace_id = (random_u64(seed) as u128) << 64 | random_u64(seed.rotate_left(32)) as u128;
+        let interval = config.interval;
+        tracing::info!(
+            "Exporting OpenTelemetry data to {}:{} as '{}' every {}s",
+            config.endpoint.host,
+            config.endpoint.port,
+            config.service_name,
+            interval.as_secs()
+        );
+
+        let shared = Arc::new(Shared {
+            config,
+            collect: Box::new(collect),
+            trace_id,
+            root_span_id: random_u64(seed.wrapping_add(1)),
+            started: SystemTime::now(),
+            spans: Mutex::new(Vec::new()),
+            stats: Mutex::new(OtelStats::default()),
+            next_id: AtomicU64::new(seed),
+        });
+        let task_shared = shared.clone();
+        let task = PeriodicTask::spawn(interval, move || task_shared.export(None));
+
+        OtelExporter {
+            handle: OtelHandle { shared },
+            task: Some(task),
+            root_name: root_name.to_string(),
+        }
+    }
+
+    /// Get a handle for recording spans
+    pub fn handle(&self) -> OtelHandle {
+        self.handle.clone()
+    }
+
+    /// Queue a finished span for the next export
+    pub fn record(&self, span: SpanRecord) {
+        self.handle.record(span);
+    }
+
+    /// Get exporter statistics
+    pub fn stats(&self) -> OtelStats {
+        self.handle.stats()
+    }
+
+    /// Stop the export thread and export the final metrics and spans
+    pub fn shutdown(mut self) -> OtelStats {
+        self.stop();
+        self.stats()
+    }
+
+    fn stop(&mut self) {
+        let Some(task) = self.task.take() else {
+            return;
+        };
+        task.shutdown();
+        let shared = &self.handle.shared;
+        let root = SpanRecord {
+            name: self.root_name.clone(),
+            start: shared.started,
+            end: SystemTime::now(),
+            attributes: Vec::new(),
+            ok: true,
+        };
+        shared.export(Some(&root));
+    }
+}
+
+impl Drop for OtelExporter {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn attribute(key: &str, value: &str) -> Value {
+    json!({ "key": key, "value": { "stringValue": value } })
+}
+
+fn unix_nanos_u64(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
+/// OTLP/JSON encodes 64-bit integers as strings
+fn unix_nanos(time: SystemTime) -> String {
+    unix_nanos_u64(time).to_string()
+}
+
+/// Non-zero pseudo-random ID; unique enough for trace and span IDs
+fn random_u64(seed: u64) -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(seed);
+    hasher.finish().max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+    use std::thread;
+
+    /// Accept requests, answering 200 and forwarding (path, body)
+    fn collector() -> (String, mpsc::Receiver<(String, Value)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/otlp", listener.local_addr().unwrap());
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0u8; 4096];
+                let (path, body) = loop {
+                    let n = stream.read(&mut buf).unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                    let text = String::from_utf8_lossy(&request).to_string();
+                    let Some((head, body)) = text.split_once("\r\n\r\n") else {
+                        continue;
+                    };
+                    let length: usize = head
+                        .lines()
+                        .find_map(|l| l.strip_prefix("Content-Length: "))
+                        .unwrap()
+                        .parse()
+                        .unwrap();
+                    if body.len() >= length {
+                        let path = head.split_whitespace().nth(1).unwrap().to_string();
+                        break (path, serde_json::from_str(body).unwrap());
+                    }
+                };
+                stream
+                    .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                    .unwrap();
+                if tx.send((path, body)).is_err() {
+                    break;
+                }
+            }
+        });
+        (url, rx)
+    }
+
+    #[test]
+    fn test_endpoint_parse() {
+        let endpoint = Endpoint::parse("http://collector:4318").unwrap();
+        assert_eq!(endpoint.host, "collector");
+        assert_eq!(endpoint.port, 4318);
+        assert_eq!(endpoint.path("metrics"), "/v1/metrics");
+
+        let endpoint = Endpoint::parse("http://10.0.0.1/otlp/").unwrap();
+        assert_eq!(endpoint.port, 4318);
+        assert_eq!(endpoint.path("traces"), "/otlp/v1/traces");
+
+        let endpoint = Endpoint::parse("http://[::1]:9000").unwrap();
+        assert_eq!(endpoint.host, "[::1]");
+        assert_eq!(endpoint.port, 9000);
+        assert_eq!(Endpoint::parse("http://[::1]").unwrap().port, 4318);
+
+        assert!(Endpoint::parse("https://collector:4318").is_err());
+        assert!(Endpoint::parse("http://:4318").is_err());
+        assert!(Endpoint::parse("http://collector:port").is_err());
+    }
+
+    #[test]
+    fn test_args_config() {
+        let args = OtelArgs::default();
+        assert!(args.config("srt-sender").unwrap().is_none());
+
+        let args = OtelArgs {
+            otel_endpoint: Some("http://localhost:4318".into()),
+            otel_service_name: None,
+            otel_interval: 30,
+        };
+        let config = args.config("srt-sender").unwrap().unwrap();
+        assert_eq!(config.service_name, "srt-sender");
+        assert_eq!(config.interval, Duration::from_secs(30));
+
+        let telemetry = TelemetryConfig {
+            endpoint: "http://localhost:4318".into(),
+            service_name: Some("edge-relay".into()),
+            interval_secs: 0,
+        };
+        let config = OtelConfig::from_telemetry(&telemetry, "srt-relay").unwrap();
+        assert_eq!(config.service_name, "edge-relay");
+        assert_eq!(config.interval, DEFAULT_EXPORT_INTERVAL);
+    }
+
+    #[test]
+    fn test_metrics_body_groups_points() {
+        let shared = Shared {
+            config: OtelConfig::new("http://localhost", "test").unwrap(),
+            collect: Box::new(Vec::new),
+            trace_id: 1,
+            root_span_id: 2,
+            started: SystemTime::now(),
+            spans: Mutex::new(Vec::new()),
+            stats: Mutex::new(OtelStats::default()),
+            next_id: AtomicU64::new(3),
+        };
+        let metrics = vec![
+            Metric::counter("srt.member.bytes.sent", "By", 100)
+                .with_attribute("srt.member.id", "1"),
+            Metric::counter("srt.member.bytes.sent", "By", 200)
+                .with_attribute("srt.member.id", "2"),
+            Metric::gauge("srt.member.rtt", "ms", 12.5),
+        ];
+        let body = shared.metrics_body(&metrics);
+        let resource = &body["resourceMetrics"][0];
+        assert_eq!(
+            resource["resource"]["attributes"][0]["value"]["stringValue"],
+            "test"
+        );
+        let metrics = resource["scopeMetrics"][0]["metrics"].as_array().unwrap();
+        assert_eq!(metrics.len(), 2);
+        let sum = &metrics[0]["sum"];
+        assert_eq!(sum["isMonotonic"], true);
+        assert_eq!(sum["dataPoints"].as_array().unwrap().len(), 2);
+        assert_eq!(sum["dataPoints"][1]["asInt"], "200");
+        assert_eq!(metrics[1]["gauge"]["dataPoints"][0]["asDouble"], 12.5);
+    }
+
+    #[test]
+    fn test_exporter_sends_metrics_and_spans() {
+        let (url, requests) = collector();
+        let config = OtelConfig::new(&url, "srt-test").unwrap();
+        let exporter = OtelExporter::start(config, "srt.run", || {
+            vec![Metric::counter("srt.group.packets.sent", "{packet}", 42)]
+        });
+        exporter.record(
+            SpanBuilder::start("srt.handshake")
+                .attribute("srt.peer", "127.0.0.1:9000")
+                .finish(true),
+        );
+        exporter.record(SpanBuilder::start("srt.failover").finish(false));
+        let stats = exporter.shutdown();
+        assert_eq!(stats.exports, 2);
+        assert_eq!(stats.failures, 0);
+        assert_eq!(stats.spans_exported, 3);
+
+        let (path, body) = requests.recv().unwrap();
+        assert_eq!(path, "/otlp/v1/metrics");
+        let metric = &body["resourceMetrics"][0]["scopeMetrics"][0]["metrics"][0];
+        assert_eq!(metric["name"], "srt.group.packets.sent");
+        assert_eq!(metric["sum"]["dataPoints"][0]["asInt"], "42");
+
+        let (path, body) = requests.recv().unwrap();
+        assert_eq!(path, "/otlp/v1/traces");
+        let spans = body["resourceSpans"][0]["scopeSpans"][0]["spans"]
+            .as_array()
+            .unwrap();
+        assert_eq!(spans.len(), 3);
+        let root = &spans[2];
+        assert_eq!(root["name"], "srt.run");
+        assert!(root.get("parentSpanId").is_none());
+        for span in &spans[..2] {
+            assert_eq!(span["traceId"], root["traceId"]);
+            assert_eq!(span["parentSpanId"], root["spanId"]);
+        }
+        assert_eq!(spans[0]["attributes"][0]["key"], "srt.peer");
+        assert_eq!(spans[1]["status"]["code"], 2);
+    }
+
+    #[test]
+    fn test_unreachable_collector_counts_failures() {
+        // Bind then close a listener so the port refuses connections
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let config = OtelConfig::new(&format!("http://127.0.0.1:{}", port), "srt-test").unwrap();
+        let exporter = OtelExporter::start(config, "srt.run", Vec::new);
+        exporter.record(SpanBuilder::start("srt.handshake").finish(true));
+        let stats = exporter.shutdown();
+        assert_eq!(stats.exports, 0);
+        assert_eq!(stats.failures, 1);
+        assert_eq!(stats.spans_dropped, 1);
+    }
+}
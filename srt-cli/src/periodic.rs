@@ -0,0 +1,95 @@
+//! Background periodic tasks
+//!
+//! Stats reporters and similar helpers run on their own thread. A
+//! `PeriodicTask` owns that thread: dropping or shutting it down wakes the
+//! thread at once and joins it, so nothing outlives the tool or library
+//! user that started it.
+
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// A closure run on a dedicated thread every interval
+pub struct PeriodicTask {
+    stop: Option<Sender<()>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl PeriodicTask {
+    /// Run `task` every `interval`, first after one interval
+    pub fn spawn<F>(interval: Duration, mut task: F) -> Self
+    where
+        F: FnMut() + Send + 'static,
+    {
+        let (stop, stopped) = mpsc::channel::<()>();
+        let handle = thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                task();
+            }
+        });
+
+        PeriodicTask {
+            stop: Some(stop),
+            handle: Some(handle),
+        }
+    }
+
+    /// Stop the thread and wait for it to exit
+    ///
+    /// A run in progress finishes first; no further runs start.
+    pub fn shutdown(mut self) {
+        self.stop();
+    }
+
+    fn stop(&mut self) {
+        // Closing the channel wakes the thread
+        self.stop.take();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for PeriodicTask {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+    use std::time::Instant;
+
+    #[test]
+    fn test_periodic_runs_and_stops() {
+        let runs = Arc::new(AtomicU32::new(0));
+        let task_runs = runs.clone();
+        let task = PeriodicTask::spawn(Duration::from_millis(5), move || {
+            task_runs.fetch_add(1, Ordering::Relaxed);
+        });
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while runs.load(Ordering::Relaxed) < 3 {
+            assert!(Instant::now() < deadline);
+            thread::sleep(Duration::from_millis(1));
+        }
+        task.shutdown();
+
+        // The thread has exited and released the closure
+        assert_eq!(Arc::strong_count(&runs), 1);
+        let stopped_at = runs.load(Ordering::Relaxed);
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(runs.load(Ordering::Relaxed), stopped_at);
+    }
+
+    #[test]
+    fn test_drop_does_not_wait_for_interval() {
+        let started = Instant::now();
+        let task = PeriodicTask::spawn(Duration::from_secs(3600), || {});
+        drop(task);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,392 @@
+//! Constant-bitrate output shaping
+//!
+//! Smooths bursty output (retransmissions, alignment flushes) into a steady
+//! datagram stream for decoders that expect CBR UDP input.
+
+use parking_lot::Mutex;
+use srt_protocol::packet::MAX_MSS;
+use std::collections::VecDeque;
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Shaper statistics
+#[derive(Debug, Clone, Default)]
+pub struct ShaperStats {
+    /// Datagrams released to the output
+    pub packets_sent: u64,
+    /// Bytes released to the output
+    pub bytes_sent: u64,
+    /// Times the buffer ran dry while output was due
+    pub underflows: u64,
+    /// Datagrams dropped because the buffer was full
+    pub overflows: u64,
+    /// Bytes currently buffered
+    pub buffered_bytes: usize,
+}
+
+/// Constant-bitrate shaper
+///
+/// Datagrams are queued and released at the target bitrate. Output starts
+/// once the buffer is half full and restarts the same way after an underflow.
+pub struct CbrShaper {
+    /// Target output rate in bits per second
+    rate_bps: u64,
+    /// Maximum buffered bytes
+    capacity: usize,
+    /// Queued datagrams
+    queue: VecDeque<Vec<u8>>,
+    /// Bytes currently queued
+    buffered: usize,
+    /// Release time of the next datagram (None while prefilling)
+    next_send: Option<Instant>,
+    /// Statistics
+    stats: ShaperStats,
+}
+
+impl CbrShaper {
+    /// Create a new shaper
+    ///
+    /// # Arguments
+    /// * `rate_bps` - Target output rate in bits per second
+    /// * `buffer_bytes` - Maximum bytes held for smoothing
+    pub fn new(rate_bps: u64, buffer_bytes: usize) -> Self {
+        CbrShaper {
+            rate_bps: rate_bps.max(1),
+            capacity: buffer_bytes,
+            queue: VecDeque::new(),
+            buffered: 0,
+            next_send: None,
+            stats: ShaperStats::default(),
+        }
+    }
+
+    /// Create a shaper whose buffer holds `buffer` worth of data at `rate_bps`
+    ///
+    /// The buffer holds at least one datagram of `MAX_MSS` bytes, however
+    /// low the rate or short the duration.
+    pub fn with_buffer_duration(rate_bps: u64, buffer: Duration) -> Self {
+        let bytes = (rate_bps as u128 * buffer.as_micros() / 8 / 1_000_000) as usize;
+        Self::new(rate_bps, bytes.max(MAX_MSS))
+    }
+
+    /// Check if a datagram of `len` bytes fits in the buffer
+    pub fn has_space(&self, len: usize) -> bool {
+        self.buffered + len <= self.capacity
+    }
+
+    /// Check if a datagram of `len` bytes could ever fit, even in an
+    /// empty buffer
+    pub fn fits(&self, len: usize) -> bool {
+        len <= self.capacity
+    }
+
+    /// Queue a datagram
+    ///
+    /// Returns false (and counts an overflow) if the buffer is full.
+    pub fn push(&mut self, data: &[u8]) -> bool {
+        if !self.has_space(data.len()) {
+            self.stats.overflows += 1;
+            return false;
+        }
+
+        self.buffered += data.len();
+        self.queue.push_back(data.to_vec());
+        true
+    }
+
+    /// Start output immediately, without waiting for the prefill
+    pub fn start(&mut self, now: Instant) {
+        if self.next_send.is_none() {
+            self.next_send = Some(now);
+        }
+    }
+
+    /// Get the next datagram due at `now`, if any
+    pub fn poll(&mut self, now: Instant) -> Option<Vec<u8>> {
+        let next_send = match self.next_send {
+            Some(t) => t,
+            None => {
+                if self.buffered * 2 < self.capacity || self.queue.is_empty() {
+                    return None;
+                }
+                self.next_send = Some(now);
+                now
+            }
+        };
+
+        if now < next_send {
+            return None;
+        }
+
+        let Some(data) = self.queue.pop_front() else {
+            // Output was due but nothing is buffered
+            self.stats.underflows += 1;
+            self.next_send = None;
+            return None;
+        };
+
+        self.buffered -= data.len();
+        self.stats.packets_sent += 1;
+        self.stats.bytes_sent += data.len() as u64;
+
+        let interval_us = data.len() as u64 * 8 * 1_000_000 / self.rate_bps;
+        self.next_send = Some(next_send + Duration::from_micros(interval_us));
+
+        Some(data)
+    }
+
+    /// Get time until the next datagram is due
+    pub fn time_until_next(&self, now: Instant) -> Option<Duration> {
+        self.next_send
+            .map(|t| t.saturating_duration_since(now))
+            .filter(|_| !self.queue.is_empty())
+    }
+
+    /// Check if the buffer is empty
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Get shaper statistics
+    pub fn stats(&self) -> ShaperStats {
+        ShaperStats {
+            buffered_bytes: self.buffered,
+            ..self.stats.clone()
+        }
+    }
+}
+
+/// UDP output paced by a `CbrShaper` on a dedicated thread
+pub struct ShapedUdpOutput {
+    shaper: Arc<Mutex<CbrShaper>>,
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl ShapedUdpOutput {
+    /// Create a shaped output sending to `dest`
+    pub fn new(socket: UdpSocket, dest: SocketAddr, shaper: CbrShaper) -> Self {
+        let shaper = Arc::new(Mutex::new(shaper));
+        let running = Arc::new(AtomicBool::new(true));
+
+        let thread_shaper = shaper.clone();
+        let thread_running = running.clone();
+        let handle = thread::spawn(move || {
+            while thread_running.load(Ordering::Relaxed) {
+                let wait = {
+                    let mut shaper = thread_shaper.lock();
+                    let now = Instant::now();
+                    while let Some(data) = shaper.poll(now) {
+                        if let Err(e) = socket.send_to(&data, dest) {
+                            tracing::warn!("Shaped UDP send to {} failed: {}", dest, e);
+                        }
+                    }
+                    shaper.time_until_next(now)
+                };
+
+                thread::sleep(
+                    wait.unwrap_or(Duration::from_millis(1))
+                        .min(Duration::from_millis(1)),
+                );
+            }
+        });
+
+        ShapedUdpOutput {
+            shaper,
+            running,
+            handle: Some(handle),
+        }
+    }
+
+    /// Queue a datagram for paced output
+    ///
+    /// Datagrams that do not fit in the buffer are dropped and counted.
+    pub fn send(&self, data: &[u8]) {
+        self.shaper.lock().push(data);
+    }
+
+    /// Block until the buffer has room for `len` bytes
+    ///
+    /// Returns at once for a datagram larger than the whole buffer, which
+    /// `send` then drops as an overflow.
+    pub fn wait_for_space(&self, len: usize) {
+        loop {
+            {
+                let mut shaper = self.shaper.lock();
+                if shaper.has_space(len) || !shaper.fits(len) {
+                    return;
+                }
+                // A full buffer must drain, even if prefill never completed
+                shaper.start(Instant::now());
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    /// Block until all buffered data has been sent
+    pub fn drain(&self) {
+        loop {
+            {
+                let mut shaper = self.shaper.lock();
+                if shaper.is_empty() {
+                    return;
+                }
+                shaper.start(Instant::now());
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    /// Get shaper statistics
+    pub fn stats(&self) -> ShaperStats {
+        self.shaper.lock().stats()
+    }
+
+    /// Get a shared handle for reading statistics from another thread
+    pub fn stats_handle(&self) -> ShaperStatsHandle {
+        ShaperStatsHandle(self.shaper.clone())
+    }
+
+    /// Send everything buffered, then stop the thread and close the socket
+    pub fn shutdown(mut self) {
+        self.drain();
+        self.stop();
+    }
+
+    fn stop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl io::Write for ShapedUdpOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.send(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for ShapedUdpOutput {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Read-only handle to a shaper's statistics
+#[derive(Clone)]
+pub struct ShaperStatsHandle(Arc<Mutex<CbrShaper>>);
+
+impl ShaperStatsHandle {
+    /// Get shaper statistics
+    pub fn stats(&self) -> ShaperStats {
+        self.0.lock().stats()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prefill_and_pacing() {
+        // 8 Mbps = 1 byte per microsecond
+        let mut shaper = CbrShaper::new(8_000_000, 4000);
+        let t0 = Instant::now();
+
+        assert!(shaper.push(&[0u8; 1000]));
+        assert!(shaper.poll(t0).is_none()); // Below half full
+
+        assert!(shaper.push(&[0u8; 1000]));
+        assert_eq!(shaper.poll(t0).unwrap().len(), 1000);
+        assert!(shaper.poll(t0).is_none()); // Next is due 1ms later
+
+        let t1 = t0 + Duration::from_micros(1000);
+        assert!(shaper.poll(t1).is_some());
+        assert_eq!(shaper.stats().packets_sent, 2);
+    }
+
+    #[test]
+    fn test_overflow() {
+        let mut shaper = CbrShaper::new(8_000_000, 1500);
+
+        assert!(shaper.push(&[0u8; 1000]));
+        assert!(!shaper.push(&[0u8; 1000]));
+        assert_eq!(shaper.stats().overflows, 1);
+        assert_eq!(shaper.stats().buffered_bytes, 1000);
+    }
+
+    #[test]
+    fn test_underflow() {
+        let mut shaper = CbrShaper::new(8_000_000, 2000);
+        let t0 = Instant::now();
+
+        shaper.push(&[0u8; 1000]);
+        assert!(shaper.poll(t0).is_some());
+
+        // Output due again but the buffer is empty
+        assert!(shaper.poll(t0 + Duration::from_millis(2)).is_none());
+        assert_eq!(shaper.stats().underflows, 1);
+
+        // Output waits for the prefill again
+        shaper.push(&[0u8; 500]);
+        assert!(shaper.poll(t0 + Duration::from_millis(3)).is_none());
+    }
+
+    #[test]
+    fn test_buffer_duration() {
+        let shaper = CbrShaper::with_buffer_duration(8_000_000, Duration::from_millis(100));
+        assert!(shaper.has_space(100_000));
+        assert!(!shaper.has_space(100_001));
+
+        // 1 Mbps for 10 ms is 1250 bytes, less than one large datagram
+        let shaper = CbrShaper::with_buffer_duration(1_000_000, Duration::from_millis(10));
+        assert!(shaper.has_space(1316));
+        assert!(shaper.fits(MAX_MSS));
+    }
+
+    #[test]
+    fn test_oversized_datagram_does_not_block() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let dest = socket.local_addr().unwrap();
+        let output = ShapedUdpOutput::new(socket, dest, CbrShaper::new(8_000_000, 1000));
+        output.wait_for_space(1316);
+        output.send(&[0u8; 1316]);
+        assert_eq!(output.stats().overflows, 1);
+    }
+
+    #[test]
+    fn test_shutdown_flushes_and_closes() {
+        let dest = UdpSocket::bind("127.0.0.1:0").unwrap();
+        dest.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let local = socket.local_addr().unwrap();
+
+        // Below the prefill level, so nothing is sent until shutdown
+        let output = ShapedUdpOutput::new(
+            socket,
+            dest.local_addr().unwrap(),
+            CbrShaper::new(8_000_000, 100_000),
+        );
+        for _ in 0..3 {
+            output.send(&[1u8; 100]);
+        }
+        output.shutdown();
+
+        let mut buf = [0u8; 200];
+        for _ in 0..3 {
+            assert_eq!(dest.recv(&mut buf).unwrap(), 100);
+        }
+        // The thread is gone and the socket with it
+        UdpSocket::bind(local).unwrap();
+    }
+}
@@ -0,0 +1,743 @@
+//! Offline replay of recorded path conditions
+//!
+//! `srt-simulate` answers "would backup or broadcast have survived
+//! yesterday's outage?" without a network. Each path's recorded conditions
+//! drive a fake group member (see `srt_bonding::testing`), a constant
+//! bitrate stream is sent through the chosen bonding mode on a virtual
+//! clock, and the report says how much of it reached the receiver within
+//! the latency window.
+//!
+//! A trace is CSV with one sample per line:
+//!
+//! ```text
+//! time_s,loss_pct,rtt_ms,bandwidth_kbps
+//! 0,0,40,8000
+//! 312.5,100,40,8000
+//! 340,2.5,65,3000
+//! ```
+//!
+//! A sample holds until the next one. Loss of 100% is an outage: the path's
+//! keep-alives go unanswered too, so failover logic sees it as a dead link.
+//! A bandwidth of 0 is unlimited. Blank lines, `#` comments and a header
+//! line are skipped.
+
+use crate::config::BondingMode;
+use serde::Serialize;
+use srt_bonding::testing::{Delivery, FakeGroup, MemberEvent};
+use srt_bonding::{
+    BackupBonding, BalancingAlgorithm, BroadcastBonding, GroupError, GroupType, LoadBalancer,
+    MemberStatus, SocketGroup,
+};
+use srt_protocol::MAX_MISSED_KEEPALIVES;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Default stream bitrate (bits per second)
+pub const DEFAULT_SIM_BITRATE: u64 = 5_000_000;
+
+/// Default payload per packet (seven MPEG-TS packets)
+pub const DEFAULT_SIM_PAYLOAD: usize = 1316;
+
+/// Default virtual time per simulation step
+pub const DEFAULT_SIM_STEP: Duration = Duration::from_millis(10);
+
+/// Time the simulation keeps running after the traces end, so packets in
+/// flight can arrive
+const DRAIN_TIME: Duration = Duration::from_secs(2);
+
+/// Bytes of the payload holding the message index
+const INDEX_LEN: usize = 8;
+
+/// Simulation errors
+#[derive(Debug, thiserror::Error)]
+pub enum SimulationError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("{name} line {line}: {reason}")]
+    Trace {
+        name: String,
+        line: usize,
+        reason: String,
+    },
+
+    #[error("Trace {0} has no samples")]
+    EmptyTrace(String),
+
+    #[error("Invalid configuration: {0}")]
+    Config(String),
+
+    #[error("Group error: {0}")]
+    Group(#[from] GroupError),
+}
+
+/// Path conditions from one trace line
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TraceSample {
+    /// Time since the start of the trace
+    pub at: Duration,
+    /// Fraction of packets lost (0.0 to 1.0)
+    pub loss: f64,
+    /// Round trip time (microseconds)
+    pub rtt_us: u32,
+    /// Bandwidth (bits per second, 0 = unlimited)
+    pub bandwidth_bps: u64,
+}
+
+/// Recorded conditions of one path
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathTrace {
+    /// Path name used in the report
+    pub name: String,
+    /// Samples in time order
+    pub samples: Vec<TraceSample>,
+}
+
+impl PathTrace {
+    /// Parse a CSV trace
+    pub fn parse(name: impl Into<String>, text: &str) -> Result<Self, SimulationError> {
+        let name = name.into();
+        let mut samples: Vec<TraceSample> = Vec::new();
+        let mut header_allowed = true;
+
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            let error = |reason: String| SimulationError::Trace {
+                name: name.clone(),
+                line: index + 1,
+                reason,
+            };
+
+            if header_allowed && fields[0].parse::<f64>().is_err() {
+                header_allowed = false;
+                continue;
+            }
+            header_allowed = false;
+
+            if fields.len() != 4 {
+                return Err(error(format!(
+                    "expected time_s,loss_pct,rtt_ms,bandwidth_kbps, found {} fields",
+                    fields.len()
+                )));
+            }
+            let mut values = [0.0; 4];
+            for (value, (field, label)) in
+                values
+                    .iter_mut()
+                    .zip(fields.iter().zip(["time", "loss", "RTT", "bandwidth"]))
+            {
+                *value = field
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|v| v.is_finite() && *v >= 0.0)
+                    .ok_or_else(|| error(format!("invalid {} '{}'", label, field)))?;
+            }
+            let [time_s, loss_pct, rtt_ms, bandwidth_kbps] = values;
+            if loss_pct > 100.0 {
+                return Err(error(format!("loss {}% is over 100%", loss_pct)));
+            }
+
+            let sample = TraceSample {
+                at: Duration::from_secs_f64(time_s),
+                loss: loss_pct / 100.0,
+                rtt_us: (rtt_ms * 1000.0).min(u32::MAX as f64) as u32,
+                bandwidth_bps: (bandwidth_kbps * 1000.0) as u64,
+            };
+            if samples.last().is_some_and(|last| sample.at < last.at) {
+                return Err(error(format!("time {}s goes backwards", time_s)));
+            }
+            samples.push(sample);
+        }
+
+        if samples.is_empty() {
+            return Err(SimulationError::EmptyTrace(name));
+        }
+        Ok(PathTrace { name, samples })
+    }
+
+    /// Load a CSV trace, named after the file
+    pub fn load(path: &Path) -> Result<Self, SimulationError> {
+        let text = fs::read_to_string(path)?;
+        let name = path.file_stem().map_or_else(
+            || path.display().to_string(),
+            |s| s.to_string_lossy().into(),
+        );
+        Self::parse(name, &text)
+    }
+
+    /// Get the time of the last sample
+    pub fn end(&self) -> Duration {
+        self.samples.last().map_or(Duration::ZERO, |s| s.at)
+    }
+
+    /// Script the samples onto a fake member
+    fn schedule(&self, fake: &mut FakeGroup, member_id: u32) {
+        let mut down = false;
+        for sample in &self.samples {
+            if sample.loss >= 1.0 {
+                if !down {
+                    fake.schedule(sample.at, member_id, MemberEvent::Down);
+                    down = true;
+                }
+                continue;
+            }
+            if down {
+                fake.schedule(sample.at, member_id, MemberEvent::Up);
+                down = false;
+            }
+            fake.schedule(sample.at, member_id, MemberEvent::Loss(sample.loss));
+            fake.schedule(sample.at, member_id, MemberEvent::Rtt(sample.rtt_us));
+            fake.schedule(
+                sample.at,
+                member_id,
+                MemberEvent::Bandwidth(sample.bandwidth_bps / 8),
+            );
+        }
+    }
+}
+
+/// What to simulate
+#[derive(Debug, Clone)]
+pub struct SimulationConfig {
+    /// Bonding mode
+    pub mode: BondingMode,
+    /// Stream bitrate (bits per second)
+    pub bitrate_bps: u64,
+    /// Payload per packet (bytes)
+    pub payload_size: usize,
+    /// Receiver latency: packets arriving later than this are late
+    pub latency: Duration,
+    /// Virtual time per step
+    pub step: Duration,
+    /// Simulated time, or until the last trace sample
+    pub duration: Option<Duration>,
+    /// Seed for the loss PRNG
+    pub seed: u64,
+}
+
+impl SimulationConfig {
+    /// Create a configuration with the default stream for a mode
+    pub fn new(mode: BondingMode) -> Self {
+        SimulationConfig {
+            mode,
+            bitrate_bps: DEFAULT_SIM_BITRATE,
+            payload_size: DEFAULT_SIM_PAYLOAD,
+            latency: Duration::from_millis(u64::from(crate::transport::DEFAULT_LATENCY_MS)),
+            step: DEFAULT_SIM_STEP,
+            duration: None,
+            seed: 1,
+        }
+    }
+
+    /// Time between packets
+    fn packet_interval(&self) -> Duration {
+        Duration::from_secs_f64((self.payload_size * 8) as f64 / self.bitrate_bps as f64)
+    }
+
+    /// Check the payload size, bitrate and step
+    pub fn validate(&self) -> Result<(), SimulationError> {
+        let max_payload = srt_protocol::packet::MAX_PAYLOAD_SIZE;
+        if !(INDEX_LEN..=max_payload).contains(&self.payload_size) {
+            return Err(SimulationError::Config(format!(
+                "payload size must be {} to {} bytes",
+                INDEX_LEN, max_payload
+            )));
+        }
+        if self.bitrate_bps == 0 {
+            return Err(SimulationError::Config("bitrate must be positive".into()));
+        }
+        if self.step.is_zero() {
+            return Err(SimulationError::Config("step must be positive".into()));
+        }
+        Ok(())
+    }
+}
+
+/// Per-path counters of a simulation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PathReport {
+    /// Path name from the trace
+    pub name: String,
+    /// Packets the path carried
+    pub packets_sent: u64,
+    /// Packets the path lost
+    pub packets_lost: u64,
+    /// Packets the path delivered
+    pub packets_delivered: u64,
+}
+
+/// Delivered quality of a simulated stream
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SimulationReport {
+    /// Bonding mode simulated
+    pub mode: BondingMode,
+    /// Simulated stream time (seconds)
+    pub duration_secs: f64,
+    /// Packets the source produced
+    pub packets_sent: u64,
+    /// Packets delivered within the latency window
+    pub packets_on_time: u64,
+    /// Packets delivered after the latency window
+    pub packets_late: u64,
+    /// Packets never delivered
+    pub packets_lost: u64,
+    /// Extra copies delivered (broadcast)
+    pub duplicates: u64,
+    /// Fraction of packets delivered on time
+    pub delivery_ratio: f64,
+    /// Longest run of stream time with nothing delivered on time (seconds)
+    pub longest_gap_secs: f64,
+    /// Primary switches (backup)
+    pub failovers: u64,
+    /// Per-path counters, in trace order
+    pub paths: Vec<PathReport>,
+}
+
+impl fmt::Display for SimulationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Mode:          {}", self.mode)?;
+        writeln!(f, "Duration:      {:.1}s", self.duration_secs)?;
+        writeln!(f, "Packets sent:  {}", self.packets_sent)?;
+        writeln!(
+            f,
+            "On time:       {} ({:.3}%)",
+            self.packets_on_time,
+            self.delivery_ratio * 100.0
+        )?;
+        writeln!(f, "Late:          {}", self.packets_late)?;
+        writeln!(f, "Lost:          {}", self.packets_lost)?;
+        writeln!(f, "Duplicates:    {}", self.duplicates)?;
+        writeln!(f, "Longest gap:   {:.3}s", self.longest_gap_secs)?;
+        writeln!(f, "Failovers:     {}", self.failovers)?;
+        for path in &self.paths {
+            writeln!(
+                f,
+                "  {}: sent {}, lost {}, delivered {}",
+                path.name, path.packets_sent, path.packets_lost, path.packets_delivered
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The bonding mode under test
+enum Sender {
+    Broadcast(BroadcastBonding),
+    Backup(BackupBonding),
+    Balancing(LoadBalancer),
+}
+
+impl Sender {
+    fn new(mode: BondingMode, group: Arc<SocketGroup>, members: &[u32]) -> Self {
+        match mode {
+            BondingMode::Broadcast => Sender::Broadcast(BroadcastBonding::new(group)),
+            BondingMode::Backup => {
+                let backup = BackupBonding::new(group, Duration::ZERO, 3);
+                // The first trace is the primary, the rest back it up in order
+                let _ = backup.set_primary(members[0]);
+                for &member_id in &members[1..] {
+                    let _ = backup.add_backup(member_id);
+                }
+                Sender::Backup(backup)
+            }
+            BondingMode::Balancing => {
+                Sender::Balancing(LoadBalancer::new(group, BalancingAlgorithm::LeastLoaded, 0))
+            }
+        }
+    }
+
+    /// Send one message; an error means the source's packet is lost
+    fn send(&self, data: &[u8]) -> bool {
+        match self {
+            Sender::Broadcast(bonding) => bonding.send(data).is_ok(),
+            Sender::Backup(bonding) => bonding.send(data).is_ok(),
+            Sender::Balancing(bonding) => bonding.send(data).is_ok(),
+        }
+    }
+}
+
+/// Receiver-side accounting by message index
+struct Receiver {
+    latency: Duration,
+    /// Send time of each message
+    sent_at: Vec<Duration>,
+    on_time: Vec<bool>,
+    late: u64,
+    delivered: u64,
+    duplicates: u64,
+    seen: Vec<bool>,
+}
+
+impl Receiver {
+    fn new(latency: Duration) -> Self {
+        Receiver {
+            latency,
+            sent_at: Vec::new(),
+            on_time: Vec::new(),
+            late: 0,
+            delivered: 0,
+            duplicates: 0,
+            seen: Vec::new(),
+        }
+    }
+
+    fn on_sent(&mut self, at: Duration) {
+        self.sent_at.push(at);
+        self.on_time.push(false);
+        self.seen.push(false);
+    }
+
+    fn on_delivery(&mut self, delivery: &Delivery) {
+        let Some(index) = delivery
+            .packet
+            .payload
+            .get(..INDEX_LEN)
+            .and_then(|bytes| bytes.try_into().ok())
+            .map(|bytes| u64::from_be_bytes(bytes) as usize)
+            .filter(|&index| index < self.seen.len())
+        else {
+            return;
+        };
+        if self.seen[index] {
+            self.duplicates += 1;
+            return;
+        }
+        self.seen[index] = true;
+        self.delivered += 1;
+        if delivery.at - self.sent_at[index] <= self.latency {
+            self.on_time[index] = true;
+        } else {
+            self.late += 1;
+        }
+    }
+
+    /// Longest run of stream time with no message delivered on time
+    fn longest_gap(&self, interval: Duration) -> Duration {
+        let mut longest = 0u32;
+        let mut run = 0u32;
+        for &on_time in &self.on_time {
+            run = if on_time { 0 } else { run + 1 };
+            longest = longest.max(run);
+        }
+        interval * longest
+    }
+}
+
+/// Replay path traces through a bonding mode
+///
+/// Path `i` follows `traces[i]`; in backup mode the first trace is the
+/// primary. Paths found dead by keep-alives are marked broken and rejoin
+/// once they answer again, as a sender that reconnects would.
+pub fn simulate(
+    traces: &[PathTrace],
+    config: &SimulationConfig,
+) -> Result<SimulationReport, SimulationError> {
+    config.validate()?;
+    if traces.is_empty() {
+        return Err(SimulationError::Config(
+            "at least one trace is required".into(),
+        ));
+    }
+    let end = config
+        .duration
+        .unwrap_or_else(|| traces.iter().map(PathTrace::end).max().unwrap_or_default());
+    if end.is_zero() {
+        return Err(SimulationError::Config(
+            "the traces cover no time; set a duration".into(),
+        ));
+    }
+
+    let group_type = match config.mode {
+        BondingMode::Broadcast => GroupType::Broadcast,
+        BondingMode::Backup => GroupType::Backup,
+        BondingMode::Balancing => GroupType::Balancing,
+    };
+    let mut fake = FakeGroup::new(group_type, traces.len());
+    fake.set_seed(config.seed);
+    let members = traces
+        .iter()
+        .map(|_| fake.add_member())
+        .collect::<Result<Vec<_>, _>>()?;
+    for (trace, &member_id) in traces.iter().zip(&members) {
+        trace.schedule(&mut fake, member_id);
+    }
+    let group = fake.group();
+    let sender = Sender::new(config.mode, group.clone(), &members);
+    // Conditions at time zero apply before anything is sent
+    fake.advance(Duration::ZERO);
+
+    let interval = config.packet_interval();
+    let mut receiver = Receiver::new(config.latency);
+    let mut payload = vec![0u8; config.payload_size];
+    let mut next_send = Duration::ZERO;
+    let mut lost_seen: HashMap<u32, u64> = HashMap::new();
+
+    while fake.now() < end + DRAIN_TIME {
+        let now = fake.now();
+        let step_end = (now + config.step).min(end);
+        while next_send < step_end {
+            let index = receiver.sent_at.len() as u64;
+            payload[..INDEX_LEN].copy_from_slice(&index.to_be_bytes());
+            receiver.on_sent(now);
+            sender.send(&payload);
+            next_send += interval;
+        }
+
+        let deliveries = fake.advance(config.step);
+        let mut acked: HashMap<u32, u32> = HashMap::new();
+        for delivery in &deliveries {
+            receiver.on_delivery(delivery);
+            *acked.entry(delivery.member_id).or_default() += 1;
+        }
+        check_health(&sender, &group, &fake, &members, &acked, &mut lost_seen);
+    }
+
+    let packets_sent = receiver.sent_at.len() as u64;
+    let packets_on_time = receiver.delivered - receiver.late;
+    let failovers = match &sender {
+        Sender::Backup(backup) => backup.failover_history().len() as u64,
+        _ => 0,
+    };
+    let paths = traces
+        .iter()
+        .zip(&members)
+        .map(|(trace, &member_id)| {
+            let stats = fake.link_stats(member_id).unwrap_or_default();
+            PathReport {
+                name: trace.name.clone(),
+                packets_sent: stats.packets_sent,
+                packets_lost: stats.packets_lost,
+                packets_delivered: stats.packets_delivered,
+            }
+        })
+        .collect();
+
+    Ok(SimulationReport {
+        mode: config.mode,
+        duration_secs: end.as_secs_f64(),
+        packets_sent,
+        packets_on_time,
+        packets_late: receiver.late,
+        packets_lost: packets_sent - receiver.delivered,
+        duplicates: receiver.duplicates,
+        delivery_ratio: if packets_sent == 0 {
+            0.0
+        } else {
+            packets_on_time as f64 / packets_sent as f64
+        },
+        longest_gap_secs: receiver.longest_gap(interval).as_secs_f64(),
+        failovers,
+        paths,
+    })
+}
+
+/// Run the sender's health checks after a step
+fn check_health(
+    sender: &Sender,
+    group: &SocketGroup,
+    fake: &FakeGroup,
+    members: &[u32],
+    acked: &HashMap<u32, u32>,
+    lost_seen: &mut HashMap<u32, u64>,
+) {
+    match sender {
+        Sender::Backup(backup) => {
+            let failovers = backup.failover_history().len();
+            let _ = backup.health_check();
+            // A failed primary waits as a backup until its link recovers
+            for event in &backup.failover_history()[failovers..] {
+                let _ = backup.add_backup(event.old_primary);
+            }
+            // With no backup to fail over to, the primary carries on once
+            // it answers again
+            if let Some(member) = backup.get_primary_id().and_then(|id| group.get_member(id)) {
+                let stats = member.get_stats();
+                if stats.status == MemberStatus::Broken && stats.missed_keepalives == 0 {
+                    let _ = group.update_member_status(stats.member_id, MemberStatus::Active);
+                }
+            }
+        }
+        Sender::Broadcast(_) | Sender::Balancing(_) => {
+            for member in group.get_all_members() {
+                let stats = member.get_stats();
+                let status = if stats.missed_keepalives >= MAX_MISSED_KEEPALIVES {
+                    MemberStatus::Broken
+                } else if stats.missed_keepalives == 0 {
+                    MemberStatus::Active
+                } else {
+                    continue;
+                };
+                if status != stats.status {
+                    let _ = group.update_member_status(stats.member_id, status);
+                }
+            }
+        }
+    }
+
+    if let Sender::Balancing(balancer) = sender {
+        for &member_id in members {
+            if let Some(&packets) = acked.get(&member_id) {
+                balancer.on_ack(member_id, packets);
+            }
+            let lost = fake.link_stats(member_id).map_or(0, |s| s.packets_lost);
+            let seen = lost_seen.entry(member_id).or_default();
+            if lost > *seen {
+                balancer.on_loss(member_id, (lost - *seen) as u32);
+                *seen = lost;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trace(name: &str, text: &str) -> PathTrace {
+        PathTrace::parse(name, text).unwrap()
+    }
+
+    fn config(mode: BondingMode) -> SimulationConfig {
+        SimulationConfig {
+            bitrate_bps: 1_000_000,
+            ..SimulationConfig::new(mode)
+        }
+    }
+
+    #[test]
+    fn test_parse_trace() {
+        let parsed = trace(
+            "cell",
+            "time_s,loss_pct,rtt_ms,bandwidth_kbps\n\
+             # morning\n\
+             0, 0, 40, 8000\n\
+             \n\
+             12.5,100,40,0\n",
+        );
+        assert_eq!(parsed.name, "cell");
+        assert_eq!(parsed.samples.len(), 2);
+        assert_eq!(
+            parsed.samples[0],
+            TraceSample {
+                at: Duration::ZERO,
+                loss: 0.0,
+                rtt_us: 40_000,
+                bandwidth_bps: 8_000_000,
+            }
+        );
+        assert_eq!(parsed.samples[1].loss, 1.0);
+        assert_eq!(parsed.end(), Duration::from_millis(12_500));
+
+        for (text, expected) in [
+            ("0,0,40\n", "line 1: expected"),
+            ("0,0,40,0\n1,101,40,0\n", "line 2: loss 101% is over 100%"),
+            ("5,0,40,0\n1,0,40,0\n", "line 2: time 1s goes backwards"),
+            ("0,0,-1,0\n", "line 1: invalid RTT '-1'"),
+            ("0,0,40,0\nx,0,40,0\n", "line 2: invalid time 'x'"),
+        ] {
+            let err = PathTrace::parse("t", text).unwrap_err().to_string();
+            assert!(err.contains(expected), "{}: {}", text, err);
+        }
+        assert!(matches!(
+            PathTrace::parse("t", "# nothing\n"),
+            Err(SimulationError::EmptyTrace(_))
+        ));
+    }
+
+    #[test]
+    fn test_clean_path_delivers_everything() {
+        let traces = [trace("clean", "0,0,40,0\n10,0,40,0\n")];
+        let report = simulate(&traces, &config(BondingMode::Broadcast)).unwrap();
+
+        // 1 Mbps of 1316 byte packets for 10 s
+        assert_eq!(report.packets_sent, 950);
+        assert_eq!(report.packets_on_time, 950);
+        assert_eq!(report.packets_lost, 0);
+        assert_eq!(report.delivery_ratio, 1.0);
+        assert_eq!(report.longest_gap_secs, 0.0);
+        assert_eq!(report.paths[0].packets_delivered, 950);
+    }
+
+    #[test]
+    fn test_broadcast_survives_single_path_outage() {
+        let traces = [
+            trace("a", "0,0,40,0\n10,100,40,0\n20,0,40,0\n30,0,40,0\n"),
+            trace("b", "0,0,60,0\n30,0,60,0\n"),
+        ];
+        let report = simulate(&traces, &config(BondingMode::Broadcast)).unwrap();
+
+        assert_eq!(report.packets_lost, 0);
+        assert!(report.duplicates > 0);
+        assert!(report.paths[0].packets_lost > 0);
+        assert_eq!(report.failovers, 0);
+    }
+
+    #[test]
+    fn test_backup_fails_over_with_a_gap() {
+        let traces = [
+            trace("primary", "0,0,40,0\n10,100,40,0\n30,100,40,0\n"),
+            trace("backup", "0,0,60,0\n30,0,60,0\n"),
+        ];
+        let report = simulate(&traces, &config(BondingMode::Backup)).unwrap();
+
+        assert_eq!(report.failovers, 1);
+        // Nothing gets through until keep-alives declare the primary dead
+        assert!(report.longest_gap_secs > 2.0, "{}", report);
+        assert!(report.longest_gap_secs < 8.0, "{}", report);
+        assert!(report.packets_on_time > report.packets_sent / 2);
+        assert!(report.paths[1].packets_delivered > 0);
+        assert_eq!(report.duplicates, 0);
+    }
+
+    #[test]
+    fn test_bandwidth_limit_drops_excess() {
+        // 1 Mbps stream over a 500 kbps path
+        let traces = [trace("thin", "0,0,40,500\n10,0,40,500\n")];
+        let report = simulate(&traces, &config(BondingMode::Broadcast)).unwrap();
+
+        assert!(report.packets_lost > 0);
+        assert!(report.delivery_ratio < 0.6, "{}", report);
+    }
+
+    #[test]
+    fn test_balancing_spreads_load() {
+        let traces = [
+            trace("a", "0,0,40,0\n10,0,40,0\n"),
+            trace("b", "0,0,40,0\n10,0,40,0\n"),
+        ];
+        let report = simulate(&traces, &config(BondingMode::Balancing)).unwrap();
+
+        assert_eq!(report.packets_lost, 0);
+        assert!(report.paths.iter().all(|path| path.packets_sent > 0));
+    }
+
+    #[test]
+    fn test_rejects_bad_config() {
+        let traces = [trace("a", "0,0,40,0\n")];
+        assert!(matches!(
+            simulate(&traces, &config(BondingMode::Broadcast)),
+            Err(SimulationError::Config(_))
+        ));
+        let config = SimulationConfig {
+            payload_size: 4,
+            duration: Some(Duration::from_secs(1)),
+            ..config(BondingMode::Broadcast)
+        };
+        assert!(matches!(
+            simulate(&traces, &config),
+            Err(SimulationError::Config(_))
+        ));
+        assert!(matches!(
+            simulate(&[], &SimulationConfig::new(BondingMode::Backup)),
+            Err(SimulationError::Config(_))
+        ));
+    }
+}
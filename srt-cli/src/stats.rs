@@ -81,6 +81,16 @@ pub fn display_group_stats(stats: &GroupStats) {
         "│ Uptime: {}                                              ",
         format_duration(stats.uptime)
     );
+    println!(
+        "│ MSS: {} bytes                                           ",
+        stats.mss
+    );
+    if stats.admission_rejects > 0 {
+        println!("│ Refused peers: {}", stats.admission_rejects);
+    }
+    if !stats.labels.is_empty() {
+        println!("│ Labels: {}", stats.labels);
+    }
     println!("├─────────────────────────────────────────────────────────────┤");
     println!("│ AGGREGATE STATISTICS                                        │");
     println!("├─────────────────────────────────────────────────────────────┤");
@@ -140,8 +150,14 @@ pub fn display_compact_stats(stats: &GroupStats, elapsed: Duration) {
         0
     };
 
+    let labels = if stats.labels.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}} ", stats.labels)
+    };
     print!(
-        "\r[{:8}] Active: {}/{} | Sent: {} | Rate: {} | Packets: {}         ",
+        "\r{}[{:8}] Active: {}/{} | Sent: {} | Rate: {} | Packets: {}         ",
+        labels,
         format_duration(stats.uptime),
         stats.active_member_count,
         stats.member_count,
@@ -0,0 +1,234 @@
+//! Exit codes and machine-readable run summaries
+//!
+//! Every CLI tool exits with one of the codes below, so scripts and
+//! supervisors can tell a finished stream from a failure without parsing
+//! logs. With `--summary-json <path>` the tool also writes its totals as
+//! JSON on the way out.
+
+use crate::config::ConfigError;
+use serde::Serialize;
+use srt_protocol::Labels;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How a run ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    /// The stream ended normally (end of input or peer shutdown)
+    StreamEnded,
+    /// A runtime error (socket failure, I/O error, ...)
+    Error,
+    /// Invalid arguments or configuration
+    ConfigError,
+    /// No peer completed the handshake in time
+    ConnectTimeout,
+    /// Stopped by SIGINT/SIGTERM
+    Interrupted,
+    /// `--check` found the configuration valid
+    Checked,
+}
+
+impl Outcome {
+    /// Get the process exit code
+    ///
+    /// Configuration errors use 2 like clap's usage errors, and an
+    /// interrupted run uses the shell's 128 + SIGINT convention.
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Outcome::StreamEnded | Outcome::Checked => 0,
+            Outcome::Error => 1,
+            Outcome::ConfigError => 2,
+            Outcome::ConnectTimeout => 3,
+            Outcome::Interrupted => 130,
+        }
+    }
+
+    /// Classify an error returned from a tool's run loop
+    pub fn for_error(err: &anyhow::Error) -> Self {
+        if let Some(err) = err.downcast_ref::<RunError>() {
+            match err {
+                RunError::Config(_) => Outcome::ConfigError,
+                RunError::ConnectTimeout(_) => Outcome::ConnectTimeout,
+            }
+        } else if err.downcast_ref::<ConfigError>().is_some() {
+            Outcome::ConfigError
+        } else {
+            Outcome::Error
+        }
+    }
+}
+
+/// Errors with a dedicated exit code
+#[derive(Debug, thiserror::Error)]
+pub enum RunError {
+    #[error("Invalid configuration: {0}")]
+    Config(String),
+
+    #[error("Connection timed out: {0}")]
+    ConnectTimeout(String),
+}
+
+/// Mark setup errors as configuration errors
+pub trait ConfigResultExt<T> {
+    /// Convert the error into `RunError::Config`
+    fn config(self) -> Result<T, RunError>;
+}
+
+impl<T, E: std::fmt::Display> ConfigResultExt<T> for Result<T, E> {
+    fn config(self) -> Result<T, RunError> {
+        self.map_err(|e| RunError::Config(e.to_string()))
+    }
+}
+
+/// Totals written by `--summary-json`
+#[derive(Debug, Clone, Serialize)]
+pub struct RunSummary {
+    /// Tool name (srt-sender, srt-receiver, srt-relay)
+    pub tool: String,
+    /// Labels given with `--labels`
+    pub labels: BTreeMap<String, String>,
+    /// How the run ended
+    pub outcome: Outcome,
+    /// Process exit code
+    pub exit_code: i32,
+    /// Error message, if the run failed
+    pub error: Option<String>,
+    /// Wall-clock duration of the run in seconds
+    pub duration_secs: f64,
+    /// Payload bytes sent or delivered
+    pub bytes: u64,
+    /// Data packets sent or delivered
+    pub packets: u64,
+    /// Retransmitted packets sent or received
+    pub retransmissions: u64,
+    /// Paths that failed while the stream was running
+    pub failovers: u64,
+    /// Packets skipped as lost (receiving tools only)
+    pub lost_packets: u64,
+}
+
+impl RunSummary {
+    /// Create an empty summary for `tool`
+    pub fn new(tool: &str) -> Self {
+        RunSummary {
+            tool: tool.to_string(),
+            labels: BTreeMap::new(),
+            outcome: Outcome::StreamEnded,
+            exit_code: 0,
+            error: None,
+            duration_secs: 0.0,
+            bytes: 0,
+            packets: 0,
+            retransmissions: 0,
+            failovers: 0,
+            lost_packets: 0,
+        }
+    }
+
+    /// Set the labels reported with the totals
+    pub fn set_labels(&mut self, labels: &Labels) {
+        self.labels = labels
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+    }
+
+    /// Record how the run ended and how long it took
+    pub fn finish(&mut self, result: &anyhow::Result<Outcome>, duration: Duration) {
+        self.error = result.as_ref().err().map(|err| format!("{:#}", err));
+        self.outcome = match result {
+            Ok(outcome) => *outcome,
+            Err(err) => Outcome::for_error(err),
+        };
+        self.exit_code = self.outcome.exit_code();
+        self.duration_secs = duration.as_secs_f64();
+    }
+
+    /// Serialize as pretty-printed JSON
+    pub fn to_json(&self) -> String {
+        // A struct of plain fields cannot fail to serialize
+        serde_json::to_string_pretty(self).expect("summary serializes")
+    }
+
+    /// Write the summary to `path`
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        fs::write(path, self.to_json() + "\n")
+    }
+}
+
+/// Install a SIGINT/SIGTERM handler and return the flag it sets
+///
+/// The first signal asks the run loop to stop so the summary is written; a
+/// second one exits immediately, for loops stuck in a blocking read.
+pub fn interrupt_flag() -> Arc<AtomicBool> {
+    let flag = Arc::new(AtomicBool::new(false));
+    let handler_flag = flag.clone();
+    let result = ctrlc::set_handler(move || {
+        if handler_flag.swap(true, Ordering::SeqCst) {
+            std::process::exit(Outcome::Interrupted.exit_code());
+        }
+        tracing::info!("Interrupted, shutting down");
+    });
+    if let Err(e) = result {
+        tracing::warn!("Failed to install signal handler: {}", e);
+    }
+    flag
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_codes() {
+        assert_eq!(Outcome::StreamEnded.exit_code(), 0);
+        assert_eq!(Outcome::Error.exit_code(), 1);
+        assert_eq!(Outcome::ConfigError.exit_code(), 2);
+        assert_eq!(Outcome::ConnectTimeout.exit_code(), 3);
+        assert_eq!(Outcome::Interrupted.exit_code(), 130);
+        assert_eq!(Outcome::Checked.exit_code(), 0);
+
+        let err = anyhow::Error::from(RunError::ConnectTimeout("127.0.0.1:9000".into()));
+        assert_eq!(Outcome::for_error(&err), Outcome::ConnectTimeout);
+        let err = anyhow::Error::from("x".parse::<u16>().config().unwrap_err());
+        assert_eq!(Outcome::for_error(&err), Outcome::ConfigError);
+        let err = anyhow::Error::from(ConfigError::Invalid("bad".into()));
+        assert_eq!(Outcome::for_error(&err), Outcome::ConfigError);
+        let err = anyhow::anyhow!("socket closed");
+        assert_eq!(Outcome::for_error(&err), Outcome::Error);
+    }
+
+    #[test]
+    fn test_summary_json() {
+        let mut summary = RunSummary::new("srt-receiver");
+        summary.bytes = 1316;
+        summary.packets = 1;
+        summary.failovers = 2;
+        summary.set_labels(&"site=nyc".parse().unwrap());
+        summary.finish(
+            &Err(RunError::ConnectTimeout("no handshake".into()).into()),
+            Duration::from_millis(1500),
+        );
+
+        let json: serde_json::Value = serde_json::from_str(&summary.to_json()).unwrap();
+        assert_eq!(json["tool"], "srt-receiver");
+        assert_eq!(json["labels"]["site"], "nyc");
+        assert_eq!(json["outcome"], "connect_timeout");
+        assert_eq!(json["exit_code"], 3);
+        assert_eq!(json["error"], "Connection timed out: no handshake");
+        assert_eq!(json["duration_secs"], 1.5);
+        assert_eq!(json["bytes"], 1316);
+        assert_eq!(json["packets"], 1);
+        assert_eq!(json["retransmissions"], 0);
+        assert_eq!(json["failovers"], 2);
+
+        summary.finish(&Ok(Outcome::Interrupted), Duration::ZERO);
+        assert_eq!(summary.exit_code, 130);
+        assert!(summary.error.is_none());
+    }
+}
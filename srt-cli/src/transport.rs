@@ -0,0 +1,409 @@
+//! Transport options shared by the CLI tools
+//!
+//! Latency, buffer and bandwidth flags with the names and units operators
+//! know from srt-live-transmit: buffers in bytes, bandwidth in bytes per
+//! second. `--trace-packets` logs the packets each socket exchanges, and
+//! `--stall-timeout` watches for delivery that stops without an error.
+
+use crate::check::CheckReport;
+use crate::config::ConfigError;
+use clap::Args;
+use srt_io::{PacketDirection, SrtSocket};
+use srt_protocol::{
+    BufferSizing, CongestionMode, Connection, DeliveryWatchdog, PacketTracer, SeqNumber,
+    StallAction, TraceConfig, DEFAULT_BUFFER_PACKETS, DEFAULT_OVERHEAD_PERCENT, MAX_BUFFER_PACKETS,
+    MAX_LATENCY_MS, MIN_BUFFER_PACKETS,
+};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Default TSBPD latency in milliseconds
+pub const DEFAULT_LATENCY_MS: u16 = 120;
+
+/// Per-packet IP/UDP header bytes not counted as buffer payload
+const UDP_IP_OVERHEAD: usize = 28;
+
+/// Stream bitrate assumed when sizing default buffers without --maxbw
+const DEFAULT_SIZING_BITRATE_BPS: u64 = 20_000_000;
+
+/// Latency, buffer and bandwidth flags
+#[derive(Args, Debug, Clone, Default)]
+pub struct TransportArgs {
+    /// TSBPD latency in milliseconds [default: 120]
+    #[arg(long, value_parser = clap::value_parser!(u16).range(0..=MAX_LATENCY_MS as i64))]
+    pub latency: Option<u16>,
+
+    /// Receive buffer size in bytes [default: sized from latency and --maxbw]
+    #[arg(long)]
+    pub rcvbuf: Option<usize>,
+
+    /// Send buffer size in bytes [default: sized from latency and --maxbw]
+    #[arg(long)]
+    pub sndbuf: Option<usize>,
+
+    /// Maximum sending bandwidth in bytes per second (0 = unlimited)
+    #[arg(long, default_value = "0")]
+    pub maxbw: u64,
+
+    /// Ask the peer to cap its sending rate in bytes per second (0 = no limit)
+    #[arg(long, value_name = "BYTES/S", default_value = "0")]
+    pub peer_maxbw: u64,
+
+    /// Bytes of idle credit pacing may send back to back (0 = none)
+    #[arg(long, value_name = "BYTES", default_value = "0")]
+    pub pacing_burst: usize,
+
+    /// Congestion control (live or file); both ends must agree
+    #[arg(long, value_name = "MODE", default_value = "live")]
+    pub congestion: CongestionMode,
+
+    /// Input rate live mode paces by, in bytes per second (0 = measure it)
+    #[arg(long, value_name = "BYTES/S", default_value = "0")]
+    pub inputbw: u64,
+
+    /// How far above the input rate live mode sends, in percent [default: 25]
+    #[arg(long, value_name = "PERCENT")]
+    #[arg(value_parser = clap::value_parser!(u32).range(5..=100))]
+    pub oheadbw: Option<u32>,
+
+    /// Share of the sending bandwidth for retransmissions in percent (0 = unlimited)
+    #[arg(long, value_name = "PERCENT", default_value = "0")]
+    #[arg(value_parser = clap::value_parser!(u8).range(0..=100))]
+    pub rexmit_budget: u8,
+
+    /// Minimum milliseconds before a lost packet is reported again (0 = every NAK)
+    #[arg(long, value_name = "MS", default_value = "0")]
+    pub nak_interval: u64,
+
+    /// Ignore NAKs for packets retransmitted less than one RTT ago
+    #[arg(long)]
+    pub rexmit_suppress: bool,
+
+    /// Milliseconds delivery may wait behind missing packets before it
+    /// counts as stalled (0 = no watchdog)
+    #[arg(long, value_name = "MS", default_value = "0")]
+    pub stall_timeout: u64,
+
+    /// What to do when delivery stalls (log, resync, failover or close)
+    #[arg(long, value_name = "ACTION", default_value = "log")]
+    pub stall_action: StallAction,
+
+    /// Log a line per packet (data, control or all), sampling one in RATE
+    #[arg(long, value_name = "KIND[:RATE]", num_args = 0..=1, default_missing_value = "all")]
+    pub trace_packets: Option<TraceConfig>,
+}
+
+impl TransportArgs {
+    /// Get the latency, or the default if not given
+    pub fn latency_ms(&self) -> u16 {
+        self.latency.unwrap_or(DEFAULT_LATENCY_MS)
+    }
+
+    /// Get the live mode overhead, or the default if not given
+    pub fn oheadbw(&self) -> u32 {
+        self.oheadbw.unwrap_or(DEFAULT_OVERHEAD_PERCENT)
+    }
+
+    /// Get the stall timeout (zero if the watchdog is off)
+    pub fn stall_timeout(&self) -> Duration {
+        Duration::from_millis(self.stall_timeout)
+    }
+
+    /// Build a watchdog for delivery above the connections (e.g. a
+    /// bonding receiver) with the stall flags
+    pub fn stall_watchdog(&self) -> DeliveryWatchdog {
+        DeliveryWatchdog::new(self.stall_timeout(), self.stall_action)
+    }
+
+    /// Get the buffer sizes for the latency, at the `--maxbw` rate if set
+    /// or 20 Mbps otherwise
+    pub fn sizing(&self, mss: usize) -> BufferSizing {
+        let bitrate = match self.maxbw {
+            0 => DEFAULT_SIZING_BITRATE_BPS,
+            bytes_per_sec => bytes_per_sec.saturating_mul(8),
+        };
+        let latency = Duration::from_millis(self.latency_ms() as u64);
+        BufferSizing::for_stream(bitrate, latency, mss)
+    }
+
+    /// Get the receive buffer size in packets for the given MSS
+    ///
+    /// Without `--rcvbuf`, large enough for the latency and never below
+    /// the default.
+    pub fn rcvbuf_packets(&self, mss: usize) -> Result<usize, ConfigError> {
+        let default = self.sizing(mss).recv_capacity.max(DEFAULT_BUFFER_PACKETS);
+        buffer_packets("rcvbuf", self.rcvbuf, mss, default)
+    }
+
+    /// Get the send buffer size in packets for the given MSS
+    ///
+    /// Without `--sndbuf`, large enough for the latency and never below
+    /// the default.
+    pub fn sndbuf_packets(&self, mss: usize) -> Result<usize, ConfigError> {
+        let default = self.sizing(mss).send_capacity.max(DEFAULT_BUFFER_PACKETS);
+        buffer_packets("sndbuf", self.sndbuf, mss, default)
+    }
+
+    /// Apply latency, buffer sizes, bandwidth cap and loss recovery limits
+    /// to a connection
+    ///
+    /// Must be called before the handshake and after the MSS is set, since
+    /// buffer sizes are converted to packets of that size.
+    pub fn apply(&self, conn: &mut Connection) -> Result<(), ConfigError> {
+        let invalid = |e: srt_protocol::ConnectionError| ConfigError::Invalid(e.to_string());
+        conn.set_latency(self.latency_ms()).map_err(invalid)?;
+        conn.set_recv_buffer_size(self.rcvbuf_packets(conn.mss())?)
+            .map_err(invalid)?;
+        conn.set_send_buffer_size(self.sndbuf_packets(conn.mss())?)
+            .map_err(invalid)?;
+        conn.set_max_bandwidth(self.maxbw);
+        conn.set_requested_max_bandwidth(self.peer_maxbw);
+        conn.set_pacing_burst(self.pacing_burst);
+        conn.set_congestion_mode(self.congestion);
+        conn.set_input_bandwidth(self.inputbw);
+        conn.set_overhead_bandwidth(self.oheadbw());
+        conn.set_retransmit_budget(f64::from(self.rexmit_budget) / 100.0);
+        conn.set_nak_interval(Duration::from_millis(self.nak_interval));
+        conn.set_retransmit_suppression(self.rexmit_suppress);
+        conn.set_stall_timeout(self.stall_timeout());
+        conn.set_stall_action(self.stall_action);
+        Ok(())
+    }
+
+    /// Record the resolved settings for `--check`
+    ///
+    /// They are applied to a scratch connection with MSS `mss`, so anything
+    /// `apply` would refuse is reported.
+    pub fn check(&self, report: &mut CheckReport, mss: usize) {
+        let addr = SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0));
+        let mut conn = Connection::new(0, addr, addr, SeqNumber::new(0), self.latency_ms());
+        if report.check("mss", conn.set_mss(mss)).is_none()
+            || report
+                .check_config("transport", self.apply(&mut conn))
+                .is_none()
+        {
+            return;
+        }
+        report.entry("mss", mss);
+        report.entry("latency", format_args!("{} ms", self.latency_ms()));
+        report.entry(
+            "rcvbuf",
+            format_args!("{} packets", conn.recv_buffer_size()),
+        );
+        report.entry(
+            "sndbuf",
+            format_args!("{} packets", conn.send_buffer_size()),
+        );
+        report.entry("maxbw", bytes_per_sec(self.maxbw, "unlimited"));
+        report.entry("peer_maxbw", bytes_per_sec(self.peer_maxbw, "no limit"));
+        report.entry("pacing_burst", format_args!("{} bytes", self.pacing_burst));
+        report.entry("congestion", self.congestion);
+        if self.congestion == CongestionMode::Live {
+            report.entry("inputbw", bytes_per_sec(self.inputbw, "measured"));
+            report.entry("oheadbw", format_args!("{}%", self.oheadbw()));
+        }
+        match self.stall_timeout {
+            0 => report.entry("stall_timeout", "off"),
+            ms => report.entry(
+                "stall_timeout",
+                format_args!("{} ms ({})", ms, self.stall_action),
+            ),
+        }
+    }
+
+    /// Trace the packets `socket` sends and receives, if `--trace-packets`
+    /// is set
+    ///
+    /// Each socket samples on its own, so every path shows up in the trace.
+    pub fn trace_socket(&self, socket: &mut SrtSocket) {
+        let Some(config) = self.trace_packets else {
+            return;
+        };
+        let tracer = PacketTracer::new(config);
+        socket.set_packet_hook(Some(Arc::new(
+            move |direction, bytes, peer| match direction {
+                PacketDirection::Sent => tracer.on_sent(bytes, peer),
+                PacketDirection::Received => tracer.on_received(bytes, peer),
+            },
+        )));
+    }
+}
+
+/// Format a rate in bytes per second, with `zero` for 0
+fn bytes_per_sec(rate: u64, zero: &str) -> String {
+    match rate {
+        0 => zero.to_string(),
+        rate => format!("{} bytes/s", rate),
+    }
+}
+
+/// Convert a buffer size in bytes to packets of `mss`, checking the range
+fn buffer_packets(
+    flag: &str,
+    bytes: Option<usize>,
+    mss: usize,
+    default: usize,
+) -> Result<usize, ConfigError> {
+    let Some(bytes) = bytes else {
+        return Ok(default);
+    };
+
+    let packet_bytes = mss.saturating_sub(UDP_IP_OVERHEAD).max(1);
+    let packets = bytes / packet_bytes + usize::from(bytes % packet_bytes != 0);
+    if !(MIN_BUFFER_PACKETS..=MAX_BUFFER_PACKETS).contains(&packets) {
+        return Err(ConfigError::Invalid(format!(
+            "--{} {} bytes is {} packets at MSS {}; must be between {} and {} bytes",
+            flag,
+            bytes,
+            packets,
+            mss,
+            // u64: the maximum exceeds usize on 32-bit targets
+            MIN_BUFFER_PACKETS as u64 * packet_bytes as u64,
+            MAX_BUFFER_PACKETS as u64 * packet_bytes as u64
+        )));
+    }
+    Ok(packets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use srt_protocol::SeqNumber;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        transport: TransportArgs,
+    }
+
+    #[test]
+    fn test_parse_flags() {
+        let cli = Cli::parse_from([
+            "test",
+            "--latency",
+            "500",
+            "--rcvbuf",
+            "1472000",
+            "--maxbw",
+            "1000000",
+            "--congestion",
+            "file",
+        ]);
+        assert_eq!(cli.transport.latency_ms(), 500);
+        assert_eq!(cli.transport.congestion, CongestionMode::File);
+        assert_eq!(cli.transport.rcvbuf_packets(1500).unwrap(), 1000);
+        assert_eq!(
+            cli.transport.sndbuf_packets(1500).unwrap(),
+            DEFAULT_BUFFER_PACKETS
+        );
+
+        assert!(Cli::try_parse_from(["test", "--latency", "60001"]).is_err());
+        assert!(Cli::try_parse_from(["test", "--rexmit-budget", "101"]).is_err());
+        assert!(Cli::try_parse_from(["test", "--congestion", "vegas"]).is_err());
+        assert!(Cli::try_parse_from(["test", "--oheadbw", "101"]).is_err());
+        assert!(Cli::try_parse_from(["test", "--stall-action", "restart"]).is_err());
+        assert!(Cli::try_parse_from(["test", "--trace-packets", "acks"]).is_err());
+        assert_eq!(
+            Cli::parse_from(["test"]).transport.latency_ms(),
+            DEFAULT_LATENCY_MS
+        );
+    }
+
+    #[test]
+    fn test_trace_packets_flag() {
+        let cli = Cli::parse_from(["test"]);
+        assert_eq!(cli.transport.trace_packets, None);
+
+        // A bare flag traces everything
+        let cli = Cli::parse_from(["test", "--trace-packets"]);
+        assert_eq!(cli.transport.trace_packets, Some(TraceConfig::default()));
+
+        let cli = Cli::parse_from(["test", "--trace-packets", "control:10"]);
+        assert_eq!(
+            cli.transport.trace_packets.unwrap().to_string(),
+            "control:10"
+        );
+    }
+
+    #[test]
+    fn test_buffer_range() {
+        let args = TransportArgs {
+            rcvbuf: Some(1000),
+            ..Default::default()
+        };
+        let err = args.rcvbuf_packets(1500).unwrap_err().to_string();
+        assert!(err.contains("--rcvbuf 1000 bytes is 1 packets"), "{}", err);
+    }
+
+    #[test]
+    fn test_default_sizing() {
+        // Short latencies keep the default
+        let args = TransportArgs::default();
+        assert_eq!(args.rcvbuf_packets(1500).unwrap(), DEFAULT_BUFFER_PACKETS);
+
+        // 8 s at 20 Mbps needs more than the default
+        let args = TransportArgs {
+            latency: Some(8000),
+            ..Default::default()
+        };
+        let sizing = args.sizing(1500);
+        assert!(sizing.recv_capacity > DEFAULT_BUFFER_PACKETS);
+        assert_eq!(args.rcvbuf_packets(1500).unwrap(), sizing.recv_capacity);
+        assert_eq!(args.sndbuf_packets(1500).unwrap(), sizing.send_capacity);
+
+        // A lower --maxbw means fewer packets in flight
+        let args = TransportArgs {
+            latency: Some(8000),
+            maxbw: 250_000,
+            ..Default::default()
+        };
+        assert_eq!(args.rcvbuf_packets(1500).unwrap(), DEFAULT_BUFFER_PACKETS);
+    }
+
+    #[test]
+    fn test_apply() {
+        let args = TransportArgs {
+            latency: Some(300),
+            rcvbuf: Some(100_000),
+            sndbuf: None,
+            maxbw: 5_000_000,
+            peer_maxbw: 1_000_000,
+            pacing_burst: 15_000,
+            congestion: CongestionMode::File,
+            inputbw: 2_000_000,
+            oheadbw: Some(10),
+            rexmit_budget: 25,
+            nak_interval: 40,
+            rexmit_suppress: true,
+            stall_timeout: 2000,
+            stall_action: StallAction::Resync,
+            trace_packets: None,
+        };
+        let mut conn = Connection::new(
+            1,
+            "127.0.0.1:9000".parse().unwrap(),
+            "127.0.0.1:9001".parse().unwrap(),
+            SeqNumber::new(0),
+            120,
+        );
+        args.apply(&mut conn).unwrap();
+
+        assert_eq!(conn.latency_ms(), 300);
+        // 100000 bytes / 1472 = 68 packets, rounded up to a power of two
+        assert_eq!(conn.recv_buffer_size(), 128);
+        assert_eq!(conn.send_buffer_size(), DEFAULT_BUFFER_PACKETS);
+        assert_eq!(conn.max_bandwidth(), 5_000_000);
+        assert_eq!(conn.requested_max_bandwidth(), 1_000_000);
+        assert_eq!(conn.pacing_burst(), 15_000);
+        assert_eq!(conn.congestion_mode(), CongestionMode::File);
+        assert_eq!(conn.input_bandwidth(), 2_000_000);
+        assert_eq!(conn.overhead_bandwidth(), 10);
+        assert_eq!(conn.retransmit_budget(), 0.25);
+        assert_eq!(conn.nak_interval(), Duration::from_millis(40));
+        assert!(conn.retransmit_suppression());
+        assert_eq!(conn.stall_timeout(), Duration::from_secs(2));
+        assert_eq!(conn.stall_action(), StallAction::Resync);
+    }
+}
@@ -0,0 +1,487 @@
+//! Write-ahead buffering for file outputs
+//!
+//! A write to slow storage can block for hundreds of milliseconds; done in
+//! the receive loop, the socket buffer overflows meanwhile and packets are
+//! lost. `WriteAheadWriter` queues output in memory and writes it from a
+//! dedicated thread, so the receive loop only blocks (or drops, by policy)
+//! once the queue is full.
+
+use clap::Args;
+use parking_lot::{Condvar, Mutex};
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Default write-ahead queue size in bytes
+pub const DEFAULT_WRITE_AHEAD_BYTES: usize = 16 * 1024 * 1024;
+
+/// A single write taking longer than this counts as a stall
+const WRITE_STALL: Duration = Duration::from_millis(100);
+
+/// What a write does when the queue is full
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Wait for the writer thread to make room
+    #[default]
+    Block,
+    /// Discard the data and count it
+    Drop,
+}
+
+impl fmt::Display for OverflowPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OverflowPolicy::Block => write!(f, "block"),
+            OverflowPolicy::Drop => write!(f, "drop"),
+        }
+    }
+}
+
+impl FromStr for OverflowPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "block" => Ok(OverflowPolicy::Block),
+            "drop" => Ok(OverflowPolicy::Drop),
+            _ => Err(format!(
+                "Invalid overflow policy '{}': use block or drop",
+                s
+            )),
+        }
+    }
+}
+
+/// Write-ahead queue statistics
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WriteAheadStats {
+    /// Bytes written to the output
+    pub bytes_written: u64,
+    /// Bytes currently queued
+    pub queued_bytes: usize,
+    /// Most bytes queued at once
+    pub max_queued_bytes: usize,
+    /// Output writes slower than 100ms
+    pub write_stalls: u64,
+    /// Writes that waited for room in the queue
+    pub blocked_writes: u64,
+    /// Writes discarded because the queue was full
+    pub dropped_writes: u64,
+    /// Bytes discarded because the queue was full
+    pub dropped_bytes: u64,
+}
+
+impl fmt::Display for WriteAheadStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "written={} bytes, queued={} bytes (max {}), stalls={}, blocked={}, dropped={}",
+            self.bytes_written,
+            self.queued_bytes,
+            self.max_queued_bytes,
+            self.write_stalls,
+            self.blocked_writes,
+            self.dropped_writes
+        )
+    }
+}
+
+/// Queue state shared with the writer thread
+struct Shared {
+    queue: VecDeque<Vec<u8>>,
+    capacity: usize,
+    /// The writer thread holds a batch taken from the queue
+    writing: bool,
+    /// No more data is coming; the thread exits once the queue is empty
+    closed: bool,
+    /// First output error; later writes fail with it
+    error: Option<(io::ErrorKind, String)>,
+    stats: WriteAheadStats,
+}
+
+impl Shared {
+    fn check_error(&self) -> io::Result<()> {
+        match &self.error {
+            Some((kind, message)) => Err(io::Error::new(*kind, message.clone())),
+            None => Ok(()),
+        }
+    }
+}
+
+type SharedState = Arc<(Mutex<Shared>, Condvar)>;
+
+/// Output written from a dedicated thread through a bounded queue
+pub struct WriteAheadWriter {
+    shared: SharedState,
+    policy: OverflowPolicy,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl WriteAheadWriter {
+    /// Queue up to `capacity` bytes for `inner`
+    pub fn new<W: Write + Send + 'static>(
+        mut inner: W,
+        capacity: usize,
+        policy: OverflowPolicy,
+    ) -> Self {
+        let shared: SharedState = Arc::new((
+            Mutex::new(Shared {
+                queue: VecDeque::new(),
+                capacity,
+                writing: false,
+                closed: false,
+                error: None,
+                stats: WriteAheadStats::default(),
+            }),
+            Condvar::new(),
+        ));
+
+        let thread_shared = shared.clone();
+        let handle = thread::spawn(move || {
+            let (lock, cond) = &*thread_shared;
+            loop {
+                let batch = {
+                    let mut state = lock.lock();
+                    while state.queue.is_empty() && !state.closed {
+                        cond.wait(&mut state);
+                    }
+                    if state.queue.is_empty() {
+                        break;
+                    }
+                    state.writing = true;
+                    std::mem::take(&mut state.queue)
+                };
+
+                let mut result = Ok(());
+                let mut written = 0u64;
+                let mut stalls = 0;
+                for data in &batch {
+                    let started = Instant::now();
+                    result = inner.write_all(data);
+                    if started.elapsed() > WRITE_STALL {
+                        stalls += 1;
+                    }
+                    if result.is_err() {
+                        break;
+                    }
+                    written += data.len() as u64;
+                }
+                // Caught up: hand what was written to the OS
+                if result.is_ok() && lock.lock().queue.is_empty() {
+                    result = inner.flush();
+                }
+
+                let mut state = lock.lock();
+                let batch_bytes: usize = batch.iter().map(Vec::len).sum();
+                state.stats.queued_bytes -= batch_bytes;
+                state.stats.bytes_written += written;
+                state.stats.write_stalls += stalls;
+                state.writing = false;
+                if let Err(e) = result {
+                    tracing::error!("Output write failed: {}", e);
+                    state.error = Some((e.kind(), e.to_string()));
+                    let discarded: usize = state.queue.drain(..).map(|d| d.len()).sum();
+                    state.stats.queued_bytes -= discarded;
+                    state.closed = true;
+                }
+                cond.notify_all();
+                if state.error.is_some() {
+                    break;
+                }
+            }
+        });
+
+        WriteAheadWriter {
+            shared,
+            policy,
+            handle: Some(handle),
+        }
+    }
+
+    /// Queue `data` for output
+    ///
+    /// When the queue is full, blocks or drops `data` according to the
+    /// overflow policy; data larger than the whole queue is accepted once
+    /// the queue is empty. Fails once an output write has failed.
+    pub fn send(&self, data: &[u8]) -> io::Result<()> {
+        let (lock, cond) = &*self.shared;
+        let mut state = lock.lock();
+        state.check_error()?;
+
+        let fits = |state: &Shared| {
+            state.stats.queued_bytes == 0 || state.stats.queued_bytes + data.len() <= state.capacity
+        };
+        if !fits(&state) {
+            match self.policy {
+                OverflowPolicy::Drop => {
+                    state.stats.dropped_writes += 1;
+                    state.stats.dropped_bytes += data.len() as u64;
+                    return Ok(());
+                }
+                OverflowPolicy::Block => {
+                    state.stats.blocked_writes += 1;
+                    while !fits(&state) && state.error.is_none() {
+                        cond.wait(&mut state);
+                    }
+                    state.check_error()?;
+                }
+            }
+        }
+
+        state.queue.push_back(data.to_vec());
+        state.stats.queued_bytes += data.len();
+        state.stats.max_queued_bytes = state.stats.max_queued_bytes.max(state.stats.queued_bytes);
+        cond.notify_all();
+        Ok(())
+    }
+
+    /// Get a shared handle for statistics and syncing from elsewhere
+    pub fn handle(&self) -> WriteAheadHandle {
+        WriteAheadHandle(self.shared.clone())
+    }
+
+    /// Get queue statistics
+    pub fn stats(&self) -> WriteAheadStats {
+        self.handle().stats()
+    }
+
+    /// Write out everything queued, then stop the thread
+    ///
+    /// Returns the first output error, if any.
+    pub fn shutdown(mut self) -> io::Result<()> {
+        self.stop();
+        self.shared.0.lock().check_error()
+    }
+
+    fn stop(&mut self) {
+        {
+            let (lock, cond) = &*self.shared;
+            lock.lock().closed = true;
+            cond.notify_all();
+        }
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Write for WriteAheadWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.send(buf)?;
+        Ok(buf.len())
+    }
+
+    /// Does not wait: the writer thread flushes whenever it catches up.
+    /// Reports an earlier output error. Use `WriteAheadHandle::sync` to wait.
+    fn flush(&mut self) -> io::Result<()> {
+        self.shared.0.lock().check_error()
+    }
+}
+
+impl Drop for WriteAheadWriter {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Shared handle to a write-ahead queue
+#[derive(Clone)]
+pub struct WriteAheadHandle(SharedState);
+
+impl WriteAheadHandle {
+    /// Get queue statistics
+    pub fn stats(&self) -> WriteAheadStats {
+        self.0 .0.lock().stats.clone()
+    }
+
+    /// Block until everything queued so far is written and flushed
+    ///
+    /// Returns the first output error, if any.
+    pub fn sync(&self) -> io::Result<()> {
+        let (lock, cond) = &*self.0;
+        let mut state = lock.lock();
+        while (!state.queue.is_empty() || state.writing) && state.error.is_none() {
+            cond.wait(&mut state);
+        }
+        state.check_error()
+    }
+}
+
+/// Write-ahead options for file outputs
+#[derive(Args, Debug, Clone)]
+pub struct WriteAheadArgs {
+    /// Bytes of file output queued for a writer thread (0 = write inline)
+    #[arg(long, value_name = "BYTES", default_value_t = DEFAULT_WRITE_AHEAD_BYTES)]
+    pub write_ahead: usize,
+
+    /// When the write-ahead queue is full: block or drop
+    #[arg(long, value_name = "POLICY", default_value_t = OverflowPolicy::Block)]
+    pub write_overflow: OverflowPolicy,
+}
+
+impl Default for WriteAheadArgs {
+    fn default() -> Self {
+        WriteAheadArgs {
+            write_ahead: DEFAULT_WRITE_AHEAD_BYTES,
+            write_overflow: OverflowPolicy::Block,
+        }
+    }
+}
+
+impl WriteAheadArgs {
+    /// Create `path` for output, behind a write-ahead queue unless disabled
+    ///
+    /// Returns the writer and, with a queue, its handle.
+    pub fn create_file(
+        &self,
+        path: &str,
+    ) -> io::Result<(Box<dyn Write>, Option<WriteAheadHandle>)> {
+        let file = BufWriter::new(File::create(path)?);
+        if self.write_ahead == 0 {
+            return Ok((Box::new(file), None));
+        }
+        let writer = WriteAheadWriter::new(file, self.write_ahead, self.write_overflow);
+        let handle = writer.handle();
+        Ok((Box::new(writer), Some(handle)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Output that blocks each write until released
+    #[derive(Clone)]
+    struct GatedOutput {
+        data: Arc<Mutex<Vec<u8>>>,
+        gate: Arc<(Mutex<bool>, Condvar)>,
+    }
+
+    impl GatedOutput {
+        fn new(open: bool) -> Self {
+            GatedOutput {
+                data: Arc::new(Mutex::new(Vec::new())),
+                gate: Arc::new((Mutex::new(open), Condvar::new())),
+            }
+        }
+
+        fn open(&self) {
+            *self.gate.0.lock() = true;
+            self.gate.1.notify_all();
+        }
+    }
+
+    impl Write for GatedOutput {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let mut open = self.gate.0.lock();
+            while !*open {
+                self.gate.1.wait(&mut open);
+            }
+            self.data.lock().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_policy_parse() {
+        for policy in [OverflowPolicy::Block, OverflowPolicy::Drop] {
+            assert_eq!(policy.to_string().parse(), Ok(policy));
+        }
+        assert!("wait".parse::<OverflowPolicy>().is_err());
+    }
+
+    #[test]
+    fn test_writes_in_order_and_syncs() {
+        let output = GatedOutput::new(true);
+        let mut writer = WriteAheadWriter::new(output.clone(), 1000, OverflowPolicy::Block);
+        for i in 0..50u8 {
+            writer.write_all(&[i; 10]).unwrap();
+        }
+        writer.flush().unwrap();
+        writer.handle().sync().unwrap();
+
+        let expected: Vec<u8> = (0..50u8).flat_map(|i| [i; 10]).collect();
+        assert_eq!(*output.data.lock(), expected);
+        let stats = writer.stats();
+        assert_eq!(stats.bytes_written, 500);
+        assert_eq!(stats.queued_bytes, 0);
+        writer.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_drop_policy_when_output_stalls() {
+        let output = GatedOutput::new(false);
+        let writer = WriteAheadWriter::new(output.clone(), 100, OverflowPolicy::Drop);
+        let handle = writer.handle();
+
+        // The first write is taken by the (stalled) thread or queued; the
+        // queue then fills and the rest are dropped without blocking
+        for _ in 0..20 {
+            writer.send(&[7u8; 40]).unwrap();
+        }
+        let stats = writer.stats();
+        assert!(stats.dropped_writes > 0);
+        assert_eq!(stats.dropped_bytes, stats.dropped_writes * 40);
+        assert!(stats.max_queued_bytes <= 120);
+
+        // Everything not dropped reaches the output
+        output.open();
+        writer.shutdown().unwrap();
+        let written = output.data.lock().len() as u64;
+        assert_eq!(written + handle.stats().dropped_bytes, 20 * 40);
+        assert_eq!(handle.stats().bytes_written, written);
+    }
+
+    #[test]
+    fn test_block_policy_waits_for_room() {
+        let output = GatedOutput::new(false);
+        let writer = WriteAheadWriter::new(output.clone(), 100, OverflowPolicy::Block);
+        let handle = writer.handle();
+
+        let opener = output.clone();
+        let release = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            opener.open();
+        });
+        for _ in 0..10 {
+            writer.send(&[1u8; 40]).unwrap();
+        }
+        release.join().unwrap();
+        handle.sync().unwrap();
+
+        assert_eq!(output.data.lock().len(), 400);
+        let stats = handle.stats();
+        assert!(stats.blocked_writes > 0);
+        assert_eq!(stats.dropped_writes, 0);
+        writer.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_output_error_is_reported() {
+        struct Failing;
+        impl Write for Failing {
+            fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+                Err(io::Error::new(io::ErrorKind::Other, "disk full"))
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let writer = WriteAheadWriter::new(Failing, 1000, OverflowPolicy::Block);
+        writer.send(b"data").unwrap();
+        let err = writer.handle().sync().unwrap_err();
+        assert_eq!(err.to_string(), "disk full");
+        assert!(writer.send(b"more").is_err());
+        assert!(writer.shutdown().is_err());
+    }
+}
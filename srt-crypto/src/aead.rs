@@ -0,0 +1,257 @@
+//! Packet header binding for authenticated encryption
+//!
+//! With AES-GCM, the 16-byte SRT data packet header is the additional
+//! authenticated data (AAD) of each packet, as in libsrt. Ciphertext
+//! spliced onto another sequence number, message number, timestamp or
+//! destination socket then fails authentication instead of decrypting to
+//! the wrong place in the stream.
+//!
+//! The retransmission flag is cleared in the AAD: it is the one header
+//! field that changes between the original send and a retransmission of
+//! the same ciphertext. AES-CTR authenticates nothing and uses no AAD.
+//!
+//! The cipher mode is carried in the key material cipher field (2 for
+//! AES-CTR, 4 for AES-GCM) and both peers must agree on it.
+
+use std::fmt;
+use thiserror::Error;
+
+/// Length of the SRT packet header bound as AAD
+pub const SRT_HEADER_LEN: usize = 16;
+
+/// Length of the AES-GCM authentication tag appended to each payload
+pub const GCM_TAG_LEN: usize = 16;
+
+/// Key material cipher field for AES-CTR
+const KM_CIPHER_AES_CTR: u8 = 2;
+
+/// Key material cipher field for AES-GCM
+const KM_CIPHER_AES_GCM: u8 = 4;
+
+/// Retransmission flag in the first byte of the message number word
+const REXMIT_FLAG: u8 = 0x04;
+
+/// Additional authenticated data of one packet
+pub type HeaderAad = [u8; SRT_HEADER_LEN];
+
+/// Cipher mode errors
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum AeadError {
+    #[error("Packet too short for an SRT header: {0} bytes")]
+    ShortPacket(usize),
+
+    #[error("Control packets are not encrypted")]
+    ControlPacket,
+
+    #[error("Unsupported key material cipher: {0}")]
+    UnsupportedCipher(u8),
+
+    #[error("Cipher mode mismatch: {local} here, {peer} at the peer")]
+    ModeMismatch { local: CipherMode, peer: CipherMode },
+}
+
+/// Payload cipher mode
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CipherMode {
+    /// AES counter mode, unauthenticated
+    #[default]
+    Ctr,
+    /// AES-GCM, authenticating the payload and packet header
+    Gcm,
+}
+
+impl CipherMode {
+    /// Parse the key material cipher field
+    pub fn from_km_cipher(cipher: u8) -> Result<Self, AeadError> {
+        match cipher {
+            KM_CIPHER_AES_CTR => Ok(CipherMode::Ctr),
+            KM_CIPHER_AES_GCM => Ok(CipherMode::Gcm),
+            _ => Err(AeadError::UnsupportedCipher(cipher)),
+        }
+    }
+
+    /// Get the key material cipher field
+    pub fn km_cipher(self) -> u8 {
+        match self {
+            CipherMode::Ctr => KM_CIPHER_AES_CTR,
+            CipherMode::Gcm => KM_CIPHER_AES_GCM,
+        }
+    }
+
+    /// Check if the packet header is authenticated
+    pub fn binds_header(self) -> bool {
+        self == CipherMode::Gcm
+    }
+
+    /// Get the bytes added to each encrypted payload
+    pub fn overhead(self) -> usize {
+        match self {
+            CipherMode::Ctr => 0,
+            CipherMode::Gcm => GCM_TAG_LEN,
+        }
+    }
+
+    /// Agree on a mode with the peer's key material
+    ///
+    /// Modes are not downgraded: a GCM peer talking to a CTR peer is
+    /// refused rather than left unauthenticated.
+    pub fn negotiate(self, peer: CipherMode) -> Result<CipherMode, AeadError> {
+        if self == peer {
+            Ok(self)
+        } else {
+            Err(AeadError::ModeMismatch { local: self, peer })
+        }
+    }
+
+    /// Get the AAD for a data packet, or None if the mode uses none
+    pub fn aad(self, packet: &[u8]) -> Result<Option<HeaderAad>, AeadError> {
+        if self.binds_header() {
+            header_aad(packet).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl fmt::Display for CipherMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CipherMode::Ctr => write!(f, "AES-CTR"),
+            CipherMode::Gcm => write!(f, "AES-GCM"),
+        }
+    }
+}
+
+/// Build the AAD for a data packet from its wire bytes
+///
+/// The header as sent, with the retransmission flag cleared.
+pub fn header_aad(packet: &[u8]) -> Result<HeaderAad, AeadError> {
+    let header = packet
+        .get(..SRT_HEADER_LEN)
+        .ok_or(AeadError::ShortPacket(packet.len()))?;
+    if header[0] & 0x80 != 0 {
+        return Err(AeadError::ControlPacket);
+    }
+
+    let mut aad = [0u8; SRT_HEADER_LEN];
+    aad.copy_from_slice(header);
+    aad[4] &= !REXMIT_FLAG;
+    Ok(aad)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_128_GCM};
+
+    /// Data packet header: seq, message word, timestamp, destination
+    fn packet(seq: u32, rexmit: bool, payload: &[u8]) -> Vec<u8> {
+        // Solo packet, even key, message 7
+        let mut msg: u32 = (0b11 << 30) | (0b01 << 27) | 7;
+        if rexmit {
+            msg |= 1 << 26;
+        }
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&seq.to_be_bytes());
+        bytes.extend_from_slice(&msg.to_be_bytes());
+        bytes.extend_from_slice(&1000u32.to_be_bytes());
+        bytes.extend_from_slice(&42u32.to_be_bytes());
+        bytes.extend_from_slice(payload);
+        bytes
+    }
+
+    fn key() -> LessSafeKey {
+        LessSafeKey::new(UnboundKey::new(&AES_128_GCM, &[7; 16]).unwrap())
+    }
+
+    /// Encrypt the payload in place, binding the header
+    fn seal(mut packet: Vec<u8>) -> Vec<u8> {
+        let aad = header_aad(&packet).unwrap();
+        let mut payload = packet.split_off(SRT_HEADER_LEN);
+        let nonce = Nonce::assume_unique_for_key([1; 12]);
+        key()
+            .seal_in_place_append_tag(nonce, Aad::from(aad), &mut payload)
+            .unwrap();
+        packet.extend_from_slice(&payload);
+        packet
+    }
+
+    /// Decrypt against the header the packet arrived with
+    fn open(mut packet: Vec<u8>) -> Option<Vec<u8>> {
+        let aad = header_aad(&packet).unwrap();
+        let mut payload = packet.split_off(SRT_HEADER_LEN);
+        let nonce = Nonce::assume_unique_for_key([1; 12]);
+        key()
+            .open_in_place(nonce, Aad::from(aad), &mut payload)
+            .ok()
+            .map(|plain| plain.to_vec())
+    }
+
+    #[test]
+    fn test_header_aad() {
+        let original = packet(5, false, b"payload");
+        let aad = header_aad(&original).unwrap();
+        assert_eq!(&aad[..], &original[..SRT_HEADER_LEN]);
+
+        // Only the retransmission flag is masked
+        let rexmit = packet(5, true, b"payload");
+        assert_ne!(rexmit[4], original[4]);
+        assert_eq!(header_aad(&rexmit).unwrap(), aad);
+        assert_ne!(header_aad(&packet(6, false, b"payload")).unwrap(), aad);
+
+        assert_eq!(header_aad(&[0; 8]), Err(AeadError::ShortPacket(8)));
+        let mut control = original.clone();
+        control[0] |= 0x80;
+        assert_eq!(header_aad(&control), Err(AeadError::ControlPacket));
+    }
+
+    #[test]
+    fn test_gcm_rejects_spliced_header() {
+        let sealed = seal(packet(5, false, b"payload"));
+        assert_eq!(sealed.len(), SRT_HEADER_LEN + 7 + GCM_TAG_LEN);
+        assert_eq!(open(sealed.clone()).unwrap(), b"payload");
+
+        // The same ciphertext moved to another sequence number
+        let mut spliced = sealed.clone();
+        spliced[..4].copy_from_slice(&6u32.to_be_bytes());
+        assert!(open(spliced).is_none());
+
+        // ...or another destination socket
+        let mut spliced = sealed.clone();
+        spliced[12..16].copy_from_slice(&43u32.to_be_bytes());
+        assert!(open(spliced).is_none());
+
+        // A retransmission of the same ciphertext still authenticates
+        let mut rexmit = sealed;
+        rexmit[4] |= REXMIT_FLAG;
+        assert_eq!(open(rexmit).unwrap(), b"payload");
+    }
+
+    #[test]
+    fn test_cipher_mode_negotiation() {
+        assert_eq!(CipherMode::from_km_cipher(2), Ok(CipherMode::Ctr));
+        assert_eq!(CipherMode::from_km_cipher(4), Ok(CipherMode::Gcm));
+        assert_eq!(
+            CipherMode::from_km_cipher(1),
+            Err(AeadError::UnsupportedCipher(1))
+        );
+        assert_eq!(CipherMode::Gcm.km_cipher(), 4);
+        assert_eq!(CipherMode::Gcm.overhead(), GCM_TAG_LEN);
+
+        assert_eq!(
+            CipherMode::Gcm.negotiate(CipherMode::Gcm),
+            Ok(CipherMode::Gcm)
+        );
+        assert_eq!(
+            CipherMode::Gcm.negotiate(CipherMode::Ctr),
+            Err(AeadError::ModeMismatch {
+                local: CipherMode::Gcm,
+                peer: CipherMode::Ctr
+            })
+        );
+
+        let packet = packet(5, false, b"payload");
+        assert_eq!(CipherMode::Ctr.aad(&packet), Ok(None));
+        assert!(CipherMode::Gcm.aad(&packet).unwrap().is_some());
+    }
+}
@@ -0,0 +1,385 @@
+//! Cipher Backends and Packet Encryption
+//!
+//! A `CryptoBackend` encrypts packet payloads with one stream-encrypting
+//! key (SEK). [`ring_impl`](crate::ring_impl) is the built-in backend;
+//! others (e.g. hardware offload) implement the same trait.
+//!
+//! `PacketCipher` holds the even and odd SEKs of a stream and applies them
+//! to data packets, selecting the key from the encryption key bits of the
+//! message number as libsrt does. The per-packet IV is derived from the
+//! key material salt and the packet index (the sequence number).
+
+use crate::aead::{header_aad, AeadError, CipherMode, HeaderAad};
+use crate::km::KmError;
+use bytes::{Bytes, BytesMut};
+use srt_protocol::packet::EncryptionKeySpec;
+use srt_protocol::DataPacket;
+use thiserror::Error;
+
+/// Length of the key material salt
+pub const SALT_LEN: usize = 16;
+
+/// Key material salt
+pub type Salt = [u8; SALT_LEN];
+
+/// AES-CTR counter block
+pub type CtrIv = [u8; 16];
+
+/// AES-GCM nonce
+pub type GcmIv = [u8; 12];
+
+/// Encryption errors
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum CryptoError {
+    #[error("Invalid key length: {0} bytes (expected 16 or 32)")]
+    InvalidKeyLength(usize),
+
+    #[error("Payload too long for AES-CTR: {0} bytes")]
+    PayloadTooLong(usize),
+
+    #[error("Payload too short for an authentication tag: {0} bytes")]
+    ShortPayload(usize),
+
+    #[error("Payload failed authentication")]
+    AuthenticationFailed,
+
+    #[error("No {0:?} key installed")]
+    MissingKey(EncryptionKeySpec),
+
+    #[error("Packet is not encrypted")]
+    NotEncrypted,
+
+    #[error("Cipher mode: {0}")]
+    Aead(#[from] AeadError),
+
+    #[error("Key material: {0}")]
+    KeyMaterial(#[from] KmError),
+
+    #[error("Failed to generate random key material")]
+    Random,
+
+    #[error("Invalid key refresh: pre-announce {pre_announce} must be 1 to (refresh rate {refresh_rate} - 1) / 2")]
+    InvalidRefresh {
+        refresh_rate: u64,
+        pre_announce: u64,
+    },
+}
+
+/// Payload encryption with one stream-encrypting key
+pub trait CryptoBackend: Send + Sync {
+    /// Get the cipher mode
+    fn mode(&self) -> CipherMode;
+
+    /// Encrypt a payload in place, appending the tag in GCM mode
+    ///
+    /// `pki` is the packet index; `aad` is required in GCM mode.
+    fn encrypt(
+        &self,
+        pki: u32,
+        aad: Option<&HeaderAad>,
+        payload: &mut Vec<u8>,
+    ) -> Result<(), CryptoError>;
+
+    /// Decrypt a payload in place, checking and removing the tag in GCM mode
+    fn decrypt(
+        &self,
+        pki: u32,
+        aad: Option<&HeaderAad>,
+        payload: &mut Vec<u8>,
+    ) -> Result<(), CryptoError>;
+}
+
+/// Build the AES-CTR counter block for a packet
+///
+/// As in libsrt: the first 112 bits of the salt with the packet index
+/// XORed into bytes 10..14. The last 16 bits count blocks from zero.
+pub fn ctr_iv(salt: &Salt, pki: u32) -> CtrIv {
+    let mut iv = [0u8; 16];
+    iv[..14].copy_from_slice(&salt[..14]);
+    for (b, p) in iv[10..14].iter_mut().zip(pki.to_be_bytes()) {
+        *b ^= p;
+    }
+    iv
+}
+
+/// Build the AES-GCM nonce for a packet
+///
+/// As in libsrt: the first 96 bits of the salt with the packet index
+/// XORed into bytes 8..12.
+pub fn gcm_iv(salt: &Salt, pki: u32) -> GcmIv {
+    let mut iv = [0u8; 12];
+    iv.copy_from_slice(&salt[..12]);
+    for (b, p) in iv[8..12].iter_mut().zip(pki.to_be_bytes()) {
+        *b ^= p;
+    }
+    iv
+}
+
+/// Even and odd keys of a stream, applied to data packets
+///
+/// Packets are encrypted with the active key, marked in their message
+/// number; received packets are decrypted with the key they are marked
+/// with, so the sender can switch keys without losing packets in flight.
+pub struct PacketCipher {
+    mode: CipherMode,
+    even: Option<Box<dyn CryptoBackend>>,
+    odd: Option<Box<dyn CryptoBackend>>,
+    active: EncryptionKeySpec,
+}
+
+impl PacketCipher {
+    /// Create a cipher with no keys; the even key becomes active
+    pub fn new(mode: CipherMode) -> Self {
+        PacketCipher {
+            mode,
+            even: None,
+            odd: None,
+            active: EncryptionKeySpec::Even,
+        }
+    }
+
+    /// Get the cipher mode
+    pub fn mode(&self) -> CipherMode {
+        self.mode
+    }
+
+    /// Get the payload bytes added per packet
+    pub fn overhead(&self) -> usize {
+        self.mode.overhead()
+    }
+
+    /// Install the even or odd key
+    ///
+    /// The backend must use this cipher's mode.
+    pub fn set_key(
+        &mut self,
+        spec: EncryptionKeySpec,
+        backend: Box<dyn CryptoBackend>,
+    ) -> Result<(), CryptoError> {
+        self.mode.negotiate(backend.mode())?;
+        *self.slot(spec)? = Some(backend);
+        Ok(())
+    }
+
+    /// Remove the even or odd key (e.g. after a key switch completes)
+    pub fn remove_key(&mut self, spec: EncryptionKeySpec) {
+        if let Ok(slot) = self.slot(spec) {
+            *slot = None;
+        }
+    }
+
+    /// Get the key used for sending
+    pub fn active(&self) -> EncryptionKeySpec {
+        self.active
+    }
+
+    /// Switch the key used for sending; it must be installed
+    pub fn set_active(&mut self, spec: EncryptionKeySpec) -> Result<(), CryptoError> {
+        self.key(spec)?;
+        self.active = spec;
+        Ok(())
+    }
+
+    /// Encrypt a packet's payload with the active key
+    pub fn encrypt(&self, packet: &mut DataPacket) -> Result<(), CryptoError> {
+        let backend = self.key(self.active)?;
+        let mut msg = packet.msg_number();
+        msg.encryption_key = self.active;
+        packet.header.msg_or_info = msg.to_raw();
+
+        // The key bits are part of the authenticated header
+        let aad = self.aad(packet)?;
+        let mut payload = packet.payload.to_vec();
+        backend.encrypt(packet.seq_number().as_raw(), aad.as_ref(), &mut payload)?;
+        packet.payload = Bytes::from(payload);
+        Ok(())
+    }
+
+    /// Decrypt a packet's payload with the key it is marked with
+    ///
+    /// On success the packet is marked unencrypted. Unencrypted packets
+    /// are refused rather than passed through.
+    pub fn decrypt(&self, packet: &mut DataPacket) -> Result<(), CryptoError> {
+        let mut msg = packet.msg_number();
+        if msg.encryption_key == EncryptionKeySpec::None {
+            return Err(CryptoError::NotEncrypted);
+        }
+        let backend = self.key(msg.encryption_key)?;
+
+        let aad = self.aad(packet)?;
+        let mut payload = packet.payload.to_vec();
+        backend.decrypt(packet.seq_number().as_raw(), aad.as_ref(), &mut payload)?;
+        packet.payload = Bytes::from(payload);
+        msg.encryption_key = EncryptionKeySpec::None;
+        packet.header.msg_or_info = msg.to_raw();
+        Ok(())
+    }
+
+    fn aad(&self, packet: &DataPacket) -> Result<Option<HeaderAad>, CryptoError> {
+        if !self.mode.binds_header() {
+            return Ok(None);
+        }
+        let mut header = BytesMut::with_capacity(crate::aead::SRT_HEADER_LEN);
+        packet.header.to_bytes(&mut header);
+        Ok(Some(header_aad(&header)?))
+    }
+
+    fn key(&self, spec: EncryptionKeySpec) -> Result<&dyn CryptoBackend, CryptoError> {
+        let key = match spec {
+            EncryptionKeySpec::Even => &self.even,
+            EncryptionKeySpec::Odd => &self.odd,
+            EncryptionKeySpec::None => &None,
+        };
+        key.as_deref().ok_or(CryptoError::MissingKey(spec))
+    }
+
+    fn slot(
+        &mut self,
+        spec: EncryptionKeySpec,
+    ) -> Result<&mut Option<Box<dyn CryptoBackend>>, CryptoError> {
+        match spec {
+            EncryptionKeySpec::Even => Ok(&mut self.even),
+            EncryptionKeySpec::Odd => Ok(&mut self.odd),
+            EncryptionKeySpec::None => Err(CryptoError::MissingKey(spec)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ring_impl::RingBackend;
+    use crate::secret::Sek;
+    use srt_protocol::{MsgNumber, SeqNumber};
+
+    const SALT: Salt = [0x11; SALT_LEN];
+
+    fn backend(mode: CipherMode, key_byte: u8) -> Box<dyn CryptoBackend> {
+        let sek = Sek::from(vec![key_byte; 16]);
+        Box::new(RingBackend::new(mode, &sek, SALT).unwrap())
+    }
+
+    fn packet(seq: u32, payload: &[u8]) -> DataPacket {
+        DataPacket::new(
+            SeqNumber::new(seq),
+            MsgNumber::new(7),
+            1000,
+            42,
+            Bytes::copy_from_slice(payload),
+        )
+    }
+
+    #[test]
+    fn test_iv_derivation() {
+        let salt: Salt = core::array::from_fn(|i| i as u8);
+        let iv = ctr_iv(&salt, 0x0102_0304);
+        assert_eq!(&iv[..10], &salt[..10]);
+        assert_eq!(&iv[10..14], &[10 ^ 1, 11 ^ 2, 12 ^ 3, 13 ^ 4]);
+        assert_eq!(&iv[14..], &[0, 0]);
+
+        let iv = gcm_iv(&salt, 0x0102_0304);
+        assert_eq!(&iv[..8], &salt[..8]);
+        assert_eq!(&iv[8..], &[8 ^ 1, 9 ^ 2, 10 ^ 3, 11 ^ 4]);
+    }
+
+    #[test]
+    fn test_packet_roundtrip() {
+        for mode in [CipherMode::Ctr, CipherMode::Gcm] {
+            let mut cipher = PacketCipher::new(mode);
+            cipher
+                .set_key(EncryptionKeySpec::Even, backend(mode, 1))
+                .unwrap();
+
+            let mut sent = packet(5, b"payload bytes");
+            cipher.encrypt(&mut sent).unwrap();
+            assert_eq!(sent.msg_number().encryption_key, EncryptionKeySpec::Even);
+            assert_ne!(&sent.payload[..13], b"payload bytes");
+            assert_eq!(sent.payload.len(), 13 + mode.overhead());
+
+            // Through the wire, as a retransmission
+            let mut received = DataPacket::from_bytes(&sent.to_bytes()).unwrap();
+            let mut msg = received.msg_number();
+            msg.retransmitted = true;
+            received.header.msg_or_info = msg.to_raw();
+            cipher.decrypt(&mut received).unwrap();
+            assert_eq!(&received.payload[..], b"payload bytes");
+            assert_eq!(
+                received.msg_number().encryption_key,
+                EncryptionKeySpec::None
+            );
+            assert_eq!(
+                cipher.decrypt(&mut received),
+                Err(CryptoError::NotEncrypted)
+            );
+        }
+    }
+
+    #[test]
+    fn test_key_switch() {
+        let mode = CipherMode::Gcm;
+        let mut cipher = PacketCipher::new(mode);
+        cipher
+            .set_key(EncryptionKeySpec::Even, backend(mode, 1))
+            .unwrap();
+        assert_eq!(
+            cipher.set_active(EncryptionKeySpec::Odd),
+            Err(CryptoError::MissingKey(EncryptionKeySpec::Odd))
+        );
+
+        let mut old = packet(1, b"before");
+        cipher.encrypt(&mut old).unwrap();
+
+        cipher
+            .set_key(EncryptionKeySpec::Odd, backend(mode, 2))
+            .unwrap();
+        cipher.set_active(EncryptionKeySpec::Odd).unwrap();
+        let mut new = packet(2, b"after");
+        cipher.encrypt(&mut new).unwrap();
+        assert_eq!(new.msg_number().encryption_key, EncryptionKeySpec::Odd);
+
+        // Packets in flight under the old key still decrypt
+        cipher.decrypt(&mut old).unwrap();
+        assert_eq!(&old.payload[..], b"before");
+
+        cipher.remove_key(EncryptionKeySpec::Odd);
+        assert_eq!(
+            cipher.decrypt(&mut new),
+            Err(CryptoError::MissingKey(EncryptionKeySpec::Odd))
+        );
+    }
+
+    #[test]
+    fn test_gcm_rejects_tampering() {
+        let mode = CipherMode::Gcm;
+        let mut cipher = PacketCipher::new(mode);
+        cipher
+            .set_key(EncryptionKeySpec::Even, backend(mode, 1))
+            .unwrap();
+        let mut sent = packet(5, b"payload");
+        cipher.encrypt(&mut sent).unwrap();
+
+        // Moved to another sequence number
+        let mut moved = sent.clone();
+        moved.header.seq_or_control = 6;
+        assert_eq!(
+            cipher.decrypt(&mut moved),
+            Err(CryptoError::AuthenticationFailed)
+        );
+
+        // Marked with the other key
+        let mut other = PacketCipher::new(mode);
+        other
+            .set_key(EncryptionKeySpec::Even, backend(mode, 2))
+            .unwrap();
+        assert_eq!(
+            other.decrypt(&mut sent.clone()),
+            Err(CryptoError::AuthenticationFailed)
+        );
+
+        // CTR keys cannot be installed in a GCM cipher
+        assert!(matches!(
+            cipher.set_key(EncryptionKeySpec::Odd, backend(CipherMode::Ctr, 1)),
+            Err(CryptoError::Aead(AeadError::ModeMismatch { .. }))
+        ));
+    }
+}
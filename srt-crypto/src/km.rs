@@ -0,0 +1,416 @@
+//! Key Material Messages
+//!
+//! The stream-encrypting keys (SEKs) travel to the receiver in key
+//! material (KM) messages, wrapped (RFC 3394 AES key wrap) with a
+//! key-encrypting key (KEK) derived from the passphrase. The layout is
+//! libsrt's: a 16-byte header, the salt, then the wrapped even and/or odd
+//! key.
+//!
+//! KM messages are exchanged in the handshake and, when keys rotate, in
+//! the stream as user-defined control packets with subtype `KM_REQUEST`,
+//! answered with `KM_RESPONSE`.
+
+use crate::aead::{AeadError, CipherMode};
+use crate::backend::{Salt, SALT_LEN};
+use crate::secret::{Kek, Passphrase, Sek};
+use aes::{Aes128, Aes192, Aes256};
+use bytes::{BufMut, Bytes, BytesMut};
+use ring::pbkdf2;
+use srt_protocol::packet::ControlType;
+use srt_protocol::wire;
+use srt_protocol::ControlPacket;
+use std::num::NonZeroU32;
+use thiserror::Error;
+use zeroize::Zeroizing;
+
+/// Control subtype: key material request (libsrt `SRT_CMD_KMREQ`)
+pub const KM_REQUEST: u16 = wire::SUBTYPE_KMREQ;
+
+/// Control subtype: key material response (libsrt `SRT_CMD_KMRSP`)
+pub const KM_RESPONSE: u16 = wire::SUBTYPE_KMRSP;
+
+/// PBKDF2-HMAC-SHA1 iterations deriving the KEK
+pub const PBKDF2_ITERATIONS: u32 = 2048;
+
+/// Trailing salt bytes used as the PBKDF2 salt
+const PBKDF2_SALT_LEN: usize = 8;
+
+/// Fixed header before the salt
+const KM_HEADER_LEN: usize = 16;
+
+/// Version 1, packet type 2 (key material)
+const KM_VERSION_TYPE: u8 = 0x12;
+
+/// HaiCrypt signature ("HAI" in PnP Vendor ID format)
+const KM_SIGN: u16 = 0x2029;
+
+/// Stream encapsulation: SRT
+const KM_SE_SRT: u8 = 2;
+
+/// Authentication field for AES-GCM
+const KM_AUTH_GCM: u8 = 1;
+
+/// Key flags: even key present
+const KK_EVEN: u8 = 0b01;
+
+/// Key flags: odd key present
+const KK_ODD: u8 = 0b10;
+
+/// Integrity check value prepended by the key wrap
+const WRAP_ICV_LEN: usize = 8;
+
+/// Key material errors
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum KmError {
+    #[error("Key material too short: {0} bytes")]
+    Short(usize),
+
+    #[error("Not a key material message")]
+    NotKeyMaterial,
+
+    #[error("Key material carries no key")]
+    NoKey,
+
+    #[error("Invalid key length: {0} bytes (expected 16, 24 or 32)")]
+    InvalidKeyLength(usize),
+
+    #[error("Invalid salt length: {0} bytes (expected {SALT_LEN})")]
+    InvalidSaltLength(usize),
+
+    #[error("Keys failed to unwrap (wrong passphrase?)")]
+    UnwrapFailed,
+
+    #[error("Cipher mode: {0}")]
+    Aead(#[from] AeadError),
+}
+
+/// Derive the KEK from the passphrase and the key material salt
+///
+/// PBKDF2-HMAC-SHA1 over the last 8 salt bytes, as libsrt does; the KEK is
+/// as long as the SEKs it wraps.
+pub fn derive_kek(passphrase: &Passphrase, salt: &Salt, key_len: usize) -> Kek {
+    let mut kek = vec![0u8; key_len];
+    pbkdf2::derive(
+        pbkdf2::PBKDF2_HMAC_SHA1,
+        NonZeroU32::new(PBKDF2_ITERATIONS).expect("non-zero iterations"),
+        &salt[SALT_LEN - PBKDF2_SALT_LEN..],
+        passphrase.expose_secret(),
+        &mut kek,
+    );
+    Kek::new(kek)
+}
+
+/// Key material: the wrapped SEKs of a stream and their salt
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyMaterial {
+    /// Cipher mode the keys are for
+    pub mode: CipherMode,
+    /// Salt for IVs and KEK derivation
+    pub salt: Salt,
+    /// Length of each SEK (16, 24 or 32)
+    pub key_len: usize,
+    /// The even key is included
+    pub even: bool,
+    /// The odd key is included
+    pub odd: bool,
+    /// Wrapped keys: integrity check, then the even and odd keys present
+    pub wrapped: Vec<u8>,
+}
+
+impl KeyMaterial {
+    /// Wrap the even and/or odd SEK with the KEK
+    pub fn wrap(
+        kek: &Kek,
+        mode: CipherMode,
+        salt: Salt,
+        even: Option<&Sek>,
+        odd: Option<&Sek>,
+    ) -> Result<Self, KmError> {
+        let keys: Vec<&Sek> = even.into_iter().chain(odd).collect();
+        let key_len = keys.first().ok_or(KmError::NoKey)?.len();
+        if !matches!(key_len, 16 | 24 | 32) || keys.iter().any(|k| k.len() != key_len) {
+            return Err(KmError::InvalidKeyLength(key_len));
+        }
+
+        let mut plain = Zeroizing::new(Vec::with_capacity(key_len * keys.len()));
+        for key in &keys {
+            plain.extend_from_slice(key.expose_secret());
+        }
+        let mut wrapped = vec![0u8; plain.len() + WRAP_ICV_LEN];
+        Wrapper::new(kek)?
+            .wrap(&plain, &mut wrapped)
+            .map_err(|_| KmError::InvalidKeyLength(key_len))?;
+
+        Ok(KeyMaterial {
+            mode,
+            salt,
+            key_len,
+            even: even.is_some(),
+            odd: odd.is_some(),
+            wrapped,
+        })
+    }
+
+    /// Unwrap the keys with the KEK, returning the even and odd SEK
+    pub fn unwrap(&self, kek: &Kek) -> Result<(Option<Sek>, Option<Sek>), KmError> {
+        let count = usize::from(self.even) + usize::from(self.odd);
+        if count == 0 {
+            return Err(KmError::NoKey);
+        }
+        if self.wrapped.len() != count * self.key_len + WRAP_ICV_LEN {
+            return Err(KmError::Short(self.wrapped.len()));
+        }
+
+        let mut plain = Zeroizing::new(vec![0u8; count * self.key_len]);
+        Wrapper::new(kek)?
+            .unwrap(&self.wrapped, &mut plain)
+            .map_err(|_| KmError::UnwrapFailed)?;
+
+        let mut keys = plain.chunks(self.key_len).map(Sek::from);
+        let even = if self.even { keys.next() } else { None };
+        let odd = if self.odd { keys.next() } else { None };
+        Ok((even, odd))
+    }
+
+    /// Serialize the message
+    pub fn to_bytes(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(KM_HEADER_LEN + SALT_LEN + self.wrapped.len());
+        buf.put_u8(KM_VERSION_TYPE);
+        buf.put_u16(KM_SIGN);
+        let mut flags = 0;
+        if self.even {
+            flags |= KK_EVEN;
+        }
+        if self.odd {
+            flags |= KK_ODD;
+        }
+        buf.put_u8(flags);
+        buf.put_u32(0); // KEK index
+        buf.put_u8(self.mode.km_cipher());
+        buf.put_u8(if self.mode == CipherMode::Gcm {
+            KM_AUTH_GCM
+        } else {
+            0
+        });
+        buf.put_u8(KM_SE_SRT);
+        buf.put_u8(0);
+        buf.put_u16(0);
+        buf.put_u8((SALT_LEN / 4) as u8);
+        buf.put_u8((self.key_len / 4) as u8);
+        buf.put_slice(&self.salt);
+        buf.put_slice(&self.wrapped);
+        buf.freeze()
+    }
+
+    /// Parse a message
+    pub fn from_bytes(data: &[u8]) -> Result<Self, KmError> {
+        if data.len() < KM_HEADER_LEN {
+            return Err(KmError::Short(data.len()));
+        }
+        if data[0] != KM_VERSION_TYPE || u16::from_be_bytes([data[1], data[2]]) != KM_SIGN {
+            return Err(KmError::NotKeyMaterial);
+        }
+        let flags = data[3];
+        let mode = CipherMode::from_km_cipher(data[8])?;
+        let salt_len = usize::from(data[14]) * 4;
+        let key_len = usize::from(data[15]) * 4;
+        if salt_len != SALT_LEN {
+            return Err(KmError::InvalidSaltLength(salt_len));
+        }
+        if !matches!(key_len, 16 | 24 | 32) {
+            return Err(KmError::InvalidKeyLength(key_len));
+        }
+
+        let even = flags & KK_EVEN != 0;
+        let odd = flags & KK_ODD != 0;
+        let wrapped_len = (usize::from(even) + usize::from(odd)) * key_len + WRAP_ICV_LEN;
+        let body = &data[KM_HEADER_LEN..];
+        if body.len() < SALT_LEN + wrapped_len {
+            return Err(KmError::Short(data.len()));
+        }
+
+        Ok(KeyMaterial {
+            mode,
+            salt: body[..SALT_LEN].try_into().expect("salt length"),
+            key_len,
+            even,
+            odd,
+            wrapped: body[SALT_LEN..SALT_LEN + wrapped_len].to_vec(),
+        })
+    }
+
+    /// Build a `KM_REQUEST` or `KM_RESPONSE` control packet
+    pub fn to_control_packet(
+        &self,
+        subtype: u16,
+        timestamp: u32,
+        dest_socket_id: u32,
+    ) -> ControlPacket {
+        ControlPacket::new(
+            ControlType::UserDefined,
+            subtype,
+            0,
+            timestamp,
+            dest_socket_id,
+            self.to_bytes(),
+        )
+    }
+
+    /// Parse a control packet carrying key material
+    ///
+    /// Returns the subtype and message, or None if the packet is not a
+    /// `KM_REQUEST` or `KM_RESPONSE`.
+    pub fn from_control_packet(
+        packet: &ControlPacket,
+    ) -> Option<Result<(u16, KeyMaterial), KmError>> {
+        if packet.control_type() != ControlType::UserDefined {
+            return None;
+        }
+        let subtype = packet.header.type_specific_info()?;
+        if subtype != KM_REQUEST && subtype != KM_RESPONSE {
+            return None;
+        }
+        Some(Self::from_bytes(&packet.control_info).map(|km| (subtype, km)))
+    }
+}
+
+/// AES key wrap with a 16, 24 or 32 byte KEK
+enum Wrapper {
+    Aes128(aes_kw::Kek<Aes128>),
+    Aes192(aes_kw::Kek<Aes192>),
+    Aes256(aes_kw::Kek<Aes256>),
+}
+
+impl Wrapper {
+    fn new(kek: &Kek) -> Result<Self, KmError> {
+        let bytes = kek.expose_secret();
+        let invalid = |_| KmError::InvalidKeyLength(bytes.len());
+        Ok(match bytes.len() {
+            16 => Wrapper::Aes128(bytes.try_into().map_err(invalid)?),
+            24 => Wrapper::Aes192(bytes.try_into().map_err(invalid)?),
+            32 => Wrapper::Aes256(bytes.try_into().map_err(invalid)?),
+            len => return Err(KmError::InvalidKeyLength(len)),
+        })
+    }
+
+    fn wrap(&self, data: &[u8], out: &mut [u8]) -> aes_kw::Result<()> {
+        match self {
+            Wrapper::Aes128(kek) => kek.wrap(data, out),
+            Wrapper::Aes192(kek) => kek.wrap(data, out),
+            Wrapper::Aes256(kek) => kek.wrap(data, out),
+        }
+    }
+
+    fn unwrap(&self, data: &[u8], out: &mut [u8]) -> aes_kw::Result<()> {
+        match self {
+            Wrapper::Aes128(kek) => kek.unwrap(data, out),
+            Wrapper::Aes192(kek) => kek.unwrap(data, out),
+            Wrapper::Aes256(kek) => kek.unwrap(data, out),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SALT: Salt = [0x5A; SALT_LEN];
+
+    #[test]
+    fn test_kek_derivation() {
+        let passphrase = Passphrase::from("correct horse battery");
+        let kek = derive_kek(&passphrase, &SALT, 16);
+        assert_eq!(kek.len(), 16);
+        assert_eq!(kek, derive_kek(&passphrase, &SALT, 16));
+
+        // Only the last 8 salt bytes are used
+        let mut salt = SALT;
+        salt[0] = 0;
+        assert_eq!(kek, derive_kek(&passphrase, &salt, 16));
+        salt[SALT_LEN - 1] = 0;
+        assert_ne!(kek, derive_kek(&passphrase, &salt, 16));
+        assert_eq!(derive_kek(&passphrase, &SALT, 32).len(), 32);
+    }
+
+    #[test]
+    fn test_wrap_roundtrip() {
+        let kek = derive_kek(&Passphrase::from("passphrase"), &SALT, 32);
+        let even = Sek::from(vec![1; 32]);
+        let odd = Sek::from(vec![2; 32]);
+
+        let km = KeyMaterial::wrap(&kek, CipherMode::Gcm, SALT, Some(&even), Some(&odd)).unwrap();
+        assert_eq!(km.wrapped.len(), 8 + 64);
+        let parsed = KeyMaterial::from_bytes(&km.to_bytes()).unwrap();
+        assert_eq!(parsed, km);
+        assert_eq!(
+            parsed.unwrap(&kek).unwrap(),
+            (Some(even), Some(odd.clone()))
+        );
+
+        let km = KeyMaterial::wrap(&kek, CipherMode::Gcm, SALT, None, Some(&odd)).unwrap();
+        assert_eq!(km.unwrap(&kek).unwrap(), (None, Some(odd)));
+
+        let wrong = derive_kek(&Passphrase::from("wrong"), &SALT, 32);
+        assert_eq!(km.unwrap(&wrong), Err(KmError::UnwrapFailed));
+        assert_eq!(
+            KeyMaterial::wrap(&kek, CipherMode::Ctr, SALT, None, None),
+            Err(KmError::NoKey)
+        );
+    }
+
+    #[test]
+    fn test_header_layout() {
+        let kek = derive_kek(&Passphrase::from("passphrase"), &SALT, 16);
+        let km = KeyMaterial::wrap(
+            &kek,
+            CipherMode::Ctr,
+            SALT,
+            Some(&Sek::from(vec![7; 16])),
+            None,
+        )
+        .unwrap();
+        let bytes = km.to_bytes();
+        assert_eq!(&bytes[..4], &[0x12, 0x20, 0x29, 0x01]);
+        assert_eq!(&bytes[8..12], &[2, 0, 2, 0]);
+        assert_eq!(&bytes[14..16], &[4, 4]);
+        assert_eq!(&bytes[16..32], &SALT);
+        assert_eq!(bytes.len(), 32 + 24);
+
+        assert_eq!(
+            KeyMaterial::from_bytes(&bytes[..10]),
+            Err(KmError::Short(10))
+        );
+        assert_eq!(
+            KeyMaterial::from_bytes(&bytes[..40]),
+            Err(KmError::Short(40))
+        );
+        let mut bad = bytes.to_vec();
+        bad[8] = 9;
+        assert_eq!(
+            KeyMaterial::from_bytes(&bad),
+            Err(KmError::Aead(AeadError::UnsupportedCipher(9)))
+        );
+    }
+
+    #[test]
+    fn test_control_packet() {
+        let kek = derive_kek(&Passphrase::from("passphrase"), &SALT, 16);
+        let km = KeyMaterial::wrap(
+            &kek,
+            CipherMode::Gcm,
+            SALT,
+            Some(&Sek::from(vec![7; 16])),
+            None,
+        )
+        .unwrap();
+        let packet = km.to_control_packet(KM_REQUEST, 0, 9);
+        let packet = ControlPacket::from_bytes(&packet.to_bytes()).unwrap();
+        assert_eq!(
+            KeyMaterial::from_control_packet(&packet),
+            Some(Ok((KM_REQUEST, km)))
+        );
+
+        let keepalive = ControlPacket::new(ControlType::KeepAlive, 0, 0, 0, 0, Bytes::new());
+        assert_eq!(KeyMaterial::from_control_packet(&keepalive), None);
+    }
+}
@@ -2,7 +2,18 @@
 //!
 //! This crate provides encryption capabilities for SRT using a pluggable
 //! backend architecture. Initially supports AES-CTR/GCM via the Ring library.
+#![deny(unsafe_code)]
 
-// Future modules (to be implemented in Phase 7)
-// pub mod backend;
-// pub mod ring_impl;
+pub mod aead;
+pub mod backend;
+pub mod km;
+pub mod rekey;
+pub mod ring_impl;
+pub mod secret;
+
+pub use aead::{header_aad, AeadError, CipherMode, HeaderAad, GCM_TAG_LEN, SRT_HEADER_LEN};
+pub use backend::{ctr_iv, gcm_iv, CryptoBackend, CryptoError, PacketCipher, Salt, SALT_LEN};
+pub use km::{derive_kek, KeyMaterial, KmError, KM_REQUEST, KM_RESPONSE};
+pub use rekey::{KeyManager, KeyManagerStats, DEFAULT_KM_PRE_ANNOUNCE, DEFAULT_KM_REFRESH_RATE};
+pub use ring_impl::RingBackend;
+pub use secret::{Kek, Passphrase, SecretBytes, Sek};
@@ -0,0 +1,454 @@
+//! Key Rotation
+//!
+//! A stream-encrypting key should only protect so many packets. Like
+//! libsrt, `KeyManager` rotates the even and odd keys by packet count
+//! without interrupting the stream:
+//!
+//! 1. `pre_announce` packets before the key's lifetime (`refresh_rate`)
+//!    ends, a fresh key is installed in the idle slot and announced along
+//!    with the current one
+//! 2. after `refresh_rate` packets, sending switches to the new key; the
+//!    encryption key bits of each packet say which key it uses
+//! 3. `pre_announce` packets later the old key is decommissioned: the
+//!    active key is announced alone and the receiver drops the old one
+//!
+//! Announcements are returned by `encrypt` as key material to send in the
+//! stream (`KM_REQUEST`). Packets retransmitted under the old key still
+//! decrypt until step 3.
+
+use crate::aead::CipherMode;
+use crate::backend::{CryptoError, PacketCipher, Salt, SALT_LEN};
+use crate::km::{derive_kek, KeyMaterial};
+use crate::ring_impl::RingBackend;
+use crate::secret::{Kek, Passphrase, Sek};
+use srt_protocol::packet::EncryptionKeySpec;
+use srt_protocol::DataPacket;
+
+/// Default packets encrypted with one key (libsrt `SRTO_KMREFRESHRATE`)
+pub const DEFAULT_KM_REFRESH_RATE: u64 = 1 << 24;
+
+/// Default packets between announcing, switching and decommissioning a
+/// key (libsrt `SRTO_KMPREANNOUNCE`)
+pub const DEFAULT_KM_PRE_ANNOUNCE: u64 = 1 << 12;
+
+/// Key rotation counters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct KeyManagerStats {
+    /// Packets encrypted
+    pub packets_encrypted: u64,
+    /// Packets decrypted
+    pub packets_decrypted: u64,
+    /// Switches to a new key
+    pub rotations: u64,
+    /// Key material messages produced or applied
+    pub key_material_messages: u64,
+}
+
+/// Even/odd stream keys with rotation by packet count
+pub struct KeyManager {
+    cipher: PacketCipher,
+    passphrase: Passphrase,
+    kek: Kek,
+    salt: Salt,
+    key_len: usize,
+    even: Option<Sek>,
+    odd: Option<Sek>,
+    /// Packets per key, 0 = never rotate
+    refresh_rate: u64,
+    pre_announce: u64,
+    /// Packets encrypted with the active key
+    packets: u64,
+    /// The previous key is still installed
+    retiring: bool,
+    stats: KeyManagerStats,
+}
+
+impl KeyManager {
+    /// Create a sender's key manager with a random salt and even key
+    ///
+    /// `key_len` is the SEK length in bytes (16 or 32).
+    pub fn new(
+        mode: CipherMode,
+        passphrase: Passphrase,
+        key_len: usize,
+    ) -> Result<Self, CryptoError> {
+        let salt: Salt = random(SALT_LEN)?
+            .expose_secret()
+            .try_into()
+            .expect("salt length");
+        let kek = derive_kek(&passphrase, &salt, key_len);
+        let mut manager = KeyManager {
+            cipher: PacketCipher::new(mode),
+            passphrase,
+            kek,
+            salt,
+            key_len,
+            even: None,
+            odd: None,
+            refresh_rate: DEFAULT_KM_REFRESH_RATE,
+            pre_announce: DEFAULT_KM_PRE_ANNOUNCE,
+            packets: 0,
+            retiring: false,
+            stats: KeyManagerStats::default(),
+        };
+        manager.install(EncryptionKeySpec::Even, random(key_len)?)?;
+        Ok(manager)
+    }
+
+    /// Create a receiver's key manager from the sender's key material
+    pub fn from_key_material(
+        passphrase: Passphrase,
+        km: &KeyMaterial,
+    ) -> Result<Self, CryptoError> {
+        let mut manager = KeyManager {
+            cipher: PacketCipher::new(km.mode),
+            kek: derive_kek(&passphrase, &km.salt, km.key_len),
+            passphrase,
+            salt: km.salt,
+            key_len: km.key_len,
+            even: None,
+            odd: None,
+            refresh_rate: DEFAULT_KM_REFRESH_RATE,
+            pre_announce: DEFAULT_KM_PRE_ANNOUNCE,
+            packets: 0,
+            retiring: false,
+            stats: KeyManagerStats::default(),
+        };
+        manager.apply_key_material(km)?;
+        Ok(manager)
+    }
+
+    /// Get the cipher mode
+    pub fn mode(&self) -> CipherMode {
+        self.cipher.mode()
+    }
+
+    /// Get the key used for sending
+    pub fn active(&self) -> EncryptionKeySpec {
+        self.cipher.active()
+    }
+
+    /// Get the packets per key (0 = never rotate)
+    pub fn refresh_rate(&self) -> u64 {
+        self.refresh_rate
+    }
+
+    /// Get the packets between announcing, switching and decommissioning
+    pub fn pre_announce(&self) -> u64 {
+        self.pre_announce
+    }
+
+    /// Change the rotation schedule
+    ///
+    /// `pre_announce` must be at least 1 and at most
+    /// `(refresh_rate - 1) / 2`, so a key is decommissioned before the next
+    /// one is announced. A `refresh_rate` of 0 never rotates.
+    pub fn set_refresh(&mut self, refresh_rate: u64, pre_announce: u64) -> Result<(), CryptoError> {
+        if refresh_rate != 0 && (pre_announce == 0 || pre_announce > (refresh_rate - 1) / 2) {
+            return Err(CryptoError::InvalidRefresh {
+                refresh_rate,
+                pre_announce,
+            });
+        }
+        self.refresh_rate = refresh_rate;
+        self.pre_announce = pre_announce;
+        Ok(())
+    }
+
+    /// Get the rotation counters
+    pub fn stats(&self) -> KeyManagerStats {
+        self.stats
+    }
+
+    /// Get key material for the installed keys (e.g. for the handshake or
+    /// to repeat an unanswered announcement)
+    pub fn key_material(&self) -> Result<KeyMaterial, CryptoError> {
+        Ok(KeyMaterial::wrap(
+            &self.kek,
+            self.mode(),
+            self.salt,
+            self.even.as_ref(),
+            self.odd.as_ref(),
+        )?)
+    }
+
+    /// Encrypt a packet with the active key, advancing the rotation
+    ///
+    /// Returns key material to send to the peer when a new key is
+    /// announced or the old one decommissioned.
+    pub fn encrypt(&mut self, packet: &mut DataPacket) -> Result<Option<KeyMaterial>, CryptoError> {
+        self.cipher.encrypt(packet)?;
+        self.stats.packets_encrypted += 1;
+        self.packets += 1;
+        if self.refresh_rate == 0 {
+            return Ok(None);
+        }
+
+        let active = self.active();
+        let idle = other(active);
+        if self.retiring {
+            if self.packets >= self.pre_announce {
+                self.retiring = false;
+                self.remove(idle);
+                return self.announce().map(Some);
+            }
+        } else if !self.has_key(idle) {
+            // Also catches up when `set_refresh` moved the announcement
+            // point behind the packets already sent
+            if self.packets >= self.refresh_rate - self.pre_announce {
+                self.install(idle, random(self.key_len)?)?;
+                return self.announce().map(Some);
+            }
+        } else if self.packets >= self.refresh_rate {
+            self.cipher.set_active(idle)?;
+            self.packets = 0;
+            self.retiring = true;
+            self.stats.rotations += 1;
+        }
+        Ok(None)
+    }
+
+    /// Decrypt a packet with the key it is marked with
+    pub fn decrypt(&mut self, packet: &mut DataPacket) -> Result<(), CryptoError> {
+        self.cipher.decrypt(packet)?;
+        self.stats.packets_decrypted += 1;
+        Ok(())
+    }
+
+    /// Apply key material from the peer
+    ///
+    /// The keys it carries are installed and any key it leaves out is
+    /// dropped, following the sender's announce and decommission steps.
+    pub fn apply_key_material(&mut self, km: &KeyMaterial) -> Result<(), CryptoError> {
+        if km.salt != self.salt || km.key_len != self.key_len {
+            self.kek = derive_kek(&self.passphrase, &km.salt, km.key_len);
+            self.salt = km.salt;
+            self.key_len = km.key_len;
+        }
+        self.mode().negotiate(km.mode)?;
+
+        let (even, odd) = km.unwrap(&self.kek)?;
+        for (spec, key) in [
+            (EncryptionKeySpec::Even, even),
+            (EncryptionKeySpec::Odd, odd),
+        ] {
+            match key {
+                Some(key) => self.install(spec, key)?,
+                None => self.remove(spec),
+            }
+        }
+        // Send with the only key left, or the newer of two
+        let active = match (&self.even, &self.odd) {
+            (Some(_), None) => EncryptionKeySpec::Even,
+            (None, Some(_)) => EncryptionKeySpec::Odd,
+            _ => self.active(),
+        };
+        self.cipher.set_active(active)?;
+        self.stats.key_material_messages += 1;
+        Ok(())
+    }
+
+    fn announce(&mut self) -> Result<KeyMaterial, CryptoError> {
+        self.stats.key_material_messages += 1;
+        self.key_material()
+    }
+
+    fn install(&mut self, spec: EncryptionKeySpec, key: Sek) -> Result<(), CryptoError> {
+        let backend = RingBackend::new(self.mode(), &key, self.salt)?;
+        self.cipher.set_key(spec, Box::new(backend))?;
+        match spec {
+            EncryptionKeySpec::Odd => self.odd = Some(key),
+            _ => self.even = Some(key),
+        }
+        Ok(())
+    }
+
+    fn has_key(&self, spec: EncryptionKeySpec) -> bool {
+        match spec {
+            EncryptionKeySpec::Even => self.even.is_some(),
+            EncryptionKeySpec::Odd => self.odd.is_some(),
+            EncryptionKeySpec::None => false,
+        }
+    }
+
+    fn remove(&mut self, spec: EncryptionKeySpec) {
+        self.cipher.remove_key(spec);
+        match spec {
+            EncryptionKeySpec::Even => self.even = None,
+            EncryptionKeySpec::Odd => self.odd = None,
+            EncryptionKeySpec::None => {}
+        }
+    }
+}
+
+/// The other key slot
+fn other(spec: EncryptionKeySpec) -> EncryptionKeySpec {
+    match spec {
+        EncryptionKeySpec::Even => EncryptionKeySpec::Odd,
+        _ => EncryptionKeySpec::Even,
+    }
+}
+
+fn random(len: usize) -> Result<Sek, CryptoError> {
+    Sek::random(len).map_err(|_| CryptoError::Random)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use srt_protocol::{MsgNumber, SeqNumber};
+
+    fn packet(seq: u32) -> DataPacket {
+        DataPacket::new(
+            SeqNumber::new(seq),
+            MsgNumber::new(1),
+            0,
+            9,
+            Bytes::from(format!("packet {}", seq)),
+        )
+    }
+
+    fn pair(mode: CipherMode) -> (KeyManager, KeyManager) {
+        let sender = KeyManager::new(mode, Passphrase::from("rotate me please"), 16).unwrap();
+        let km = sender.key_material().unwrap();
+        let receiver =
+            KeyManager::from_key_material(Passphrase::from("rotate me please"), &km).unwrap();
+        (sender, receiver)
+    }
+
+    #[test]
+    fn test_rotation_schedule() {
+        let (mut sender, mut receiver) = pair(CipherMode::Gcm);
+        sender.set_refresh(10, 2).unwrap();
+
+        let mut events = Vec::new();
+        let mut in_flight = Vec::new();
+        for seq in 1..=25 {
+            let mut sent = packet(seq);
+            if let Some(km) = sender.encrypt(&mut sent).unwrap() {
+                events.push((seq, km.even, km.odd));
+                // Key material reaches the receiver as a control packet
+                receiver.apply_key_material(&km).unwrap();
+            }
+            in_flight.push((seq, sent));
+        }
+
+        // Announce at 8, switch after 10, decommission 2 packets later,
+        // then again for the next key
+        assert_eq!(
+            events,
+            vec![
+                (8, true, true),
+                (12, false, true),
+                (18, true, true),
+                (22, true, false),
+            ]
+        );
+        assert_eq!(sender.stats().rotations, 2);
+        let keys: Vec<_> = in_flight
+            .iter()
+            .map(|(_, p)| p.msg_number().encryption_key)
+            .collect();
+        assert!(keys[..10].iter().all(|k| *k == EncryptionKeySpec::Even));
+        assert!(keys[10..20].iter().all(|k| *k == EncryptionKeySpec::Odd));
+        assert!(keys[20..].iter().all(|k| *k == EncryptionKeySpec::Even));
+
+        // A late retransmission under the decommissioned key does not
+        // decrypt; everything after the last decommission does
+        let mut stale = in_flight[19].1.clone();
+        assert_eq!(
+            receiver.decrypt(&mut stale),
+            Err(CryptoError::MissingKey(EncryptionKeySpec::Odd))
+        );
+        for (seq, mut p) in in_flight.split_off(21) {
+            receiver.decrypt(&mut p).unwrap();
+            assert_eq!(p.payload, Bytes::from(format!("packet {}", seq)));
+        }
+    }
+
+    #[test]
+    fn test_packets_in_flight_survive_switch() {
+        let (mut sender, mut receiver) = pair(CipherMode::Ctr);
+        sender.set_refresh(6, 2).unwrap();
+
+        let mut before = packet(1);
+        sender.encrypt(&mut before).unwrap();
+        let mut announced = None;
+        let mut after = packet(0);
+        for seq in 2..=7 {
+            after = packet(seq);
+            if let Some(km) = sender.encrypt(&mut after).unwrap() {
+                announced = Some(km);
+            }
+        }
+        assert_eq!(after.msg_number().encryption_key, EncryptionKeySpec::Odd);
+        receiver.apply_key_material(&announced.unwrap()).unwrap();
+
+        // Old and new key packets both decrypt during the overlap
+        receiver.decrypt(&mut before).unwrap();
+        receiver.decrypt(&mut after).unwrap();
+        assert_eq!(&after.payload[..], b"packet 7");
+    }
+
+    #[test]
+    fn test_schedule_lowered_mid_stream() {
+        let (mut sender, mut receiver) = pair(CipherMode::Gcm);
+        sender.set_refresh(100, 10).unwrap();
+        for seq in 1..=50 {
+            assert!(sender.encrypt(&mut packet(seq)).unwrap().is_none());
+        }
+
+        // Already past the new announcement and switch points: announce
+        // with the next packet, switch with the one after
+        sender.set_refresh(10, 2).unwrap();
+        let mut announced = packet(51);
+        let km = sender.encrypt(&mut announced).unwrap().unwrap();
+        assert!(km.even && km.odd);
+        receiver.apply_key_material(&km).unwrap();
+
+        let mut switched = packet(52);
+        assert!(sender.encrypt(&mut switched).unwrap().is_none());
+        assert_eq!(sender.active(), EncryptionKeySpec::Odd);
+        assert_eq!(sender.stats().rotations, 1);
+
+        let mut next = packet(53);
+        sender.encrypt(&mut next).unwrap();
+        for mut p in [announced, switched, next] {
+            receiver.decrypt(&mut p).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_refresh_validation_and_off() {
+        let (mut sender, _) = pair(CipherMode::Ctr);
+        assert_eq!(sender.refresh_rate(), DEFAULT_KM_REFRESH_RATE);
+        assert!(matches!(
+            sender.set_refresh(10, 5),
+            Err(CryptoError::InvalidRefresh { .. })
+        ));
+        assert!(sender.set_refresh(10, 0).is_err());
+
+        sender.set_refresh(0, 0).unwrap();
+        for seq in 0..100 {
+            assert!(sender.encrypt(&mut packet(seq)).unwrap().is_none());
+        }
+        assert_eq!(sender.active(), EncryptionKeySpec::Even);
+    }
+
+    #[test]
+    fn test_wrong_passphrase_and_mode() {
+        let sender = KeyManager::new(CipherMode::Gcm, Passphrase::from("right one"), 32).unwrap();
+        let km = sender.key_material().unwrap();
+        assert!(matches!(
+            KeyManager::from_key_material(Passphrase::from("wrong one"), &km),
+            Err(CryptoError::KeyMaterial(_))
+        ));
+
+        let (_, mut receiver) = pair(CipherMode::Ctr);
+        assert!(matches!(
+            receiver.apply_key_material(&km),
+            Err(CryptoError::Aead(_))
+        ));
+    }
+}
@@ -0,0 +1,210 @@
+//! Ring Cipher Backend
+//!
+//! AES-128/256 in GCM mode through ring's AEAD. ring does not expose
+//! AES-CTR or raw block encryption, so in CTR mode the keystream comes
+//! from the `aes` block cipher, laid out as libsrt does: a 112-bit IV
+//! from the salt and packet index, then a 16-bit block counter.
+
+use crate::aead::{CipherMode, HeaderAad, GCM_TAG_LEN};
+use crate::backend::{ctr_iv, gcm_iv, CryptoBackend, CryptoError, Salt};
+use crate::secret::Sek;
+use aes::cipher::{generic_array::GenericArray, BlockEncrypt, KeyInit};
+use aes::{Aes128, Aes256};
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_128_GCM, AES_256_GCM};
+
+/// Largest payload the 16-bit CTR block counter covers
+const MAX_CTR_PAYLOAD: usize = 16 << 16;
+
+/// AES block cipher for CTR mode
+enum CtrKey {
+    Aes128(Box<Aes128>),
+    Aes256(Box<Aes256>),
+}
+
+impl CtrKey {
+    fn encrypt_block(&self, block: &mut [u8; 16]) {
+        let block = GenericArray::from_mut_slice(block);
+        match self {
+            CtrKey::Aes128(aes) => aes.encrypt_block(block),
+            CtrKey::Aes256(aes) => aes.encrypt_block(block),
+        }
+    }
+}
+
+enum Key {
+    Ctr(CtrKey),
+    Gcm(Box<LessSafeKey>),
+}
+
+/// AES-CTR/GCM backend for one stream-encrypting key
+pub struct RingBackend {
+    key: Key,
+    salt: Salt,
+}
+
+impl RingBackend {
+    /// Create a backend for a 16 or 32 byte SEK and the key material salt
+    pub fn new(mode: CipherMode, sek: &Sek, salt: Salt) -> Result<Self, CryptoError> {
+        let bytes = sek.expose_secret();
+        let key = match (mode, bytes.len()) {
+            (CipherMode::Ctr, 16) => Key::Ctr(CtrKey::Aes128(Box::new(
+                Aes128::new_from_slice(bytes).expect("16 byte key"),
+            ))),
+            (CipherMode::Ctr, 32) => Key::Ctr(CtrKey::Aes256(Box::new(
+                Aes256::new_from_slice(bytes).expect("32 byte key"),
+            ))),
+            (CipherMode::Gcm, 16 | 32) => {
+                let algorithm = if bytes.len() == 16 {
+                    &AES_128_GCM
+                } else {
+                    &AES_256_GCM
+                };
+                let key = UnboundKey::new(algorithm, bytes)
+                    .map_err(|_| CryptoError::InvalidKeyLength(bytes.len()))?;
+                Key::Gcm(Box::new(LessSafeKey::new(key)))
+            }
+            (_, len) => return Err(CryptoError::InvalidKeyLength(len)),
+        };
+        Ok(RingBackend { key, salt })
+    }
+
+    /// XOR the packet's keystream into `data`
+    fn apply_keystream(key: &CtrKey, iv: [u8; 16], data: &mut [u8]) -> Result<(), CryptoError> {
+        if data.len() > MAX_CTR_PAYLOAD {
+            return Err(CryptoError::PayloadTooLong(data.len()));
+        }
+        let base = u16::from_be_bytes([iv[14], iv[15]]);
+        for (counter, chunk) in data.chunks_mut(16).enumerate() {
+            let mut block = iv;
+            block[14..].copy_from_slice(&base.wrapping_add(counter as u16).to_be_bytes());
+            key.encrypt_block(&mut block);
+            for (b, k) in chunk.iter_mut().zip(block) {
+                *b ^= k;
+            }
+        }
+        Ok(())
+    }
+
+    fn nonce(&self, pki: u32) -> Nonce {
+        Nonce::assume_unique_for_key(gcm_iv(&self.salt, pki))
+    }
+}
+
+impl CryptoBackend for RingBackend {
+    fn mode(&self) -> CipherMode {
+        match self.key {
+            Key::Ctr(_) => CipherMode::Ctr,
+            Key::Gcm(_) => CipherMode::Gcm,
+        }
+    }
+
+    fn encrypt(
+        &self,
+        pki: u32,
+        aad: Option<&HeaderAad>,
+        payload: &mut Vec<u8>,
+    ) -> Result<(), CryptoError> {
+        match &self.key {
+            Key::Ctr(key) => Self::apply_keystream(key, ctr_iv(&self.salt, pki), payload),
+            Key::Gcm(key) => {
+                let aad = Aad::from(aad.copied().unwrap_or_default());
+                key.seal_in_place_append_tag(self.nonce(pki), aad, payload)
+                    .map_err(|_| CryptoError::PayloadTooLong(payload.len()))
+            }
+        }
+    }
+
+    fn decrypt(
+        &self,
+        pki: u32,
+        aad: Option<&HeaderAad>,
+        payload: &mut Vec<u8>,
+    ) -> Result<(), CryptoError> {
+        match &self.key {
+            Key::Ctr(key) => Self::apply_keystream(key, ctr_iv(&self.salt, pki), payload),
+            Key::Gcm(key) => {
+                if payload.len() < GCM_TAG_LEN {
+                    return Err(CryptoError::ShortPayload(payload.len()));
+                }
+                let aad = Aad::from(aad.copied().unwrap_or_default());
+                let plain_len = key
+                    .open_in_place(self.nonce(pki), aad, payload)
+                    .map_err(|_| CryptoError::AuthenticationFailed)?
+                    .len();
+                payload.truncate(plain_len);
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_ctr_known_answer() {
+        // NIST SP 800-38A F.5.1 and F.5.5 (CTR-AES128/256.Encrypt), first two blocks
+        let iv: [u8; 16] = hex("f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff").try_into().unwrap();
+        let plain = hex("6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51");
+        let cases = [
+            (
+                "2b7e151628aed2a6abf7158809cf4f3c",
+                "874d6191b620e3261bef6864990db6ce9806f66b7970fdff8617187bb9fffdff",
+            ),
+            (
+                "603deb1015ca71be2b73aef0857d77811f352c073b6108d72d9810a30914dff4",
+                "601ec313775789a5b7a7f504bbf3d228f443e3ca4d62b59aca84e990cacaf5c5",
+            ),
+        ];
+        for (key, cipher) in cases {
+            let backend = RingBackend::new(CipherMode::Ctr, &Sek::new(hex(key)), [0; 16]).unwrap();
+            let Key::Ctr(key) = &backend.key else {
+                unreachable!()
+            };
+            let mut data = plain.clone();
+            RingBackend::apply_keystream(key, iv, &mut data).unwrap();
+            assert_eq!(data, hex(cipher));
+        }
+    }
+
+    #[test]
+    fn test_roundtrip_and_key_lengths() {
+        let salt = [9; 16];
+        for mode in [CipherMode::Ctr, CipherMode::Gcm] {
+            for len in [16, 32] {
+                let backend = RingBackend::new(mode, &Sek::new(vec![3; len]), salt).unwrap();
+                assert_eq!(backend.mode(), mode);
+                let aad = [1; 16];
+                let mut payload = b"some payload".to_vec();
+                backend.encrypt(77, Some(&aad), &mut payload).unwrap();
+                assert_eq!(payload.len(), 12 + mode.overhead());
+
+                // The packet index changes the keystream
+                let mut other = b"some payload".to_vec();
+                backend.encrypt(78, Some(&aad), &mut other).unwrap();
+                assert_ne!(payload, other);
+
+                backend.decrypt(77, Some(&aad), &mut payload).unwrap();
+                assert_eq!(payload, b"some payload");
+            }
+            assert_eq!(
+                RingBackend::new(mode, &Sek::new(vec![3; 24]), salt).err(),
+                Some(CryptoError::InvalidKeyLength(24))
+            );
+        }
+
+        let gcm = RingBackend::new(CipherMode::Gcm, &Sek::new(vec![3; 16]), salt).unwrap();
+        assert_eq!(
+            gcm.decrypt(1, None, &mut vec![0; 4]),
+            Err(CryptoError::ShortPayload(4))
+        );
+    }
+}
@@ -0,0 +1,187 @@
+//! Secret Key Material
+//!
+//! `SecretBytes` holds passphrases and keys. Its contents are zeroized on
+//! drop, compared in constant time, and never printed by `Debug`.
+
+use ring::rand::{SecureRandom, SystemRandom};
+use std::fmt;
+use subtle::ConstantTimeEq;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// Secret byte string, zeroized on drop
+#[derive(Clone, Default)]
+pub struct SecretBytes(Vec<u8>);
+
+/// Connection passphrase
+pub type Passphrase = SecretBytes;
+
+/// Key-encrypting key (derived from the passphrase)
+pub type Kek = SecretBytes;
+
+/// Stream-encrypting key
+pub type Sek = SecretBytes;
+
+/// Error generating random key material
+#[derive(Debug, thiserror::Error)]
+#[error("Failed to generate random key material")]
+pub struct RandomError;
+
+impl SecretBytes {
+    /// Wrap bytes as a secret, taking ownership
+    pub fn new(bytes: Vec<u8>) -> Self {
+        SecretBytes(bytes)
+    }
+
+    /// Generate `len` random bytes (e.g. a fresh SEK or salt)
+    pub fn random(len: usize) -> Result<Self, RandomError> {
+        let mut bytes = vec![0u8; len];
+        SystemRandom::new()
+            .fill(&mut bytes)
+            .map_err(|_| RandomError)?;
+        Ok(SecretBytes(bytes))
+    }
+
+    /// Access the secret contents
+    ///
+    /// Callers must not copy the bytes into storage that outlives this value.
+    pub fn expose_secret(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Get the length in bytes
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Check if the secret is empty
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl Zeroize for SecretBytes {
+    fn zeroize(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl Drop for SecretBytes {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for SecretBytes {}
+
+impl From<Vec<u8>> for SecretBytes {
+    fn from(bytes: Vec<u8>) -> Self {
+        SecretBytes(bytes)
+    }
+}
+
+impl From<&[u8]> for SecretBytes {
+    fn from(bytes: &[u8]) -> Self {
+        SecretBytes(bytes.to_vec())
+    }
+}
+
+impl From<String> for SecretBytes {
+    fn from(s: String) -> Self {
+        SecretBytes(s.into_bytes())
+    }
+}
+
+impl From<&str> for SecretBytes {
+    fn from(s: &str) -> Self {
+        SecretBytes(s.as_bytes().to_vec())
+    }
+}
+
+impl ConstantTimeEq for SecretBytes {
+    fn ct_eq(&self, other: &Self) -> subtle::Choice {
+        self.0.as_slice().ct_eq(other.0.as_slice())
+    }
+}
+
+/// Constant-time comparison (length is not secret)
+impl PartialEq for SecretBytes {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl Eq for SecretBytes {}
+
+impl fmt::Debug for SecretBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretBytes([REDACTED])")
+    }
+}
+
+/// Secrets in config files are UTF-8 strings (passphrases)
+#[cfg(feature = "serde")]
+impl serde::Serialize for SecretBytes {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let s = std::str::from_utf8(&self.0)
+            .map_err(|_| serde::ser::Error::custom("secret is not valid UTF-8"))?;
+        serializer.serialize_str(s)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for SecretBytes {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Ok(SecretBytes::from(s))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_debug_is_redacted() {
+        let secret = SecretBytes::from("hunter2-passphrase");
+        let debug = format!("{:?}", secret);
+        assert!(!debug.contains("hunter2"));
+        assert_eq!(debug, "SecretBytes([REDACTED])");
+
+        // Also when nested in a derived Debug
+        #[derive(Debug)]
+        #[allow(dead_code)]
+        struct Settings {
+            passphrase: Option<SecretBytes>,
+        }
+        let debug = format!(
+            "{:?}",
+            Settings {
+                passphrase: Some(secret)
+            }
+        );
+        assert!(!debug.contains("hunter2"));
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        let a = SecretBytes::from(&[1u8, 2, 3][..]);
+        assert_eq!(a, SecretBytes::from(vec![1, 2, 3]));
+        assert_ne!(a, SecretBytes::from(vec![1, 2, 4]));
+        assert_ne!(a, SecretBytes::from(vec![1, 2]));
+    }
+
+    #[test]
+    fn test_zeroize() {
+        let mut secret = SecretBytes::from("passphrase");
+        secret.zeroize();
+        assert!(secret.is_empty());
+    }
+
+    #[test]
+    fn test_random() {
+        let a = SecretBytes::random(16).unwrap();
+        let b = SecretBytes::random(16).unwrap();
+        assert_eq!(a.len(), 16);
+        assert_ne!(a, b);
+    }
+}
@@ -0,0 +1,695 @@
+//! Async sockets and connection driver on Tokio
+//!
+//! `AsyncSrtSocket` is the Tokio counterpart of [`SrtSocket`]: the same UDP
+//! socket and packet hook, with sends and receives as futures.
+//!
+//! `AsyncConnection` runs a [`Connection`] from a spawned task rather than a
+//! thread. The task reads the socket, transmits queued data, and sends
+//! ACKs, NAKs and keep-alives on a timer, while the application awaits
+//! `send` and `recv`. Thousands of connections can share one runtime.
+//!
+//! Each connection owns its socket, connected to the peer once the
+//! handshake completes. Only available with the `tokio` feature.
+
+use crate::epoll::{EpollNotifier, Events, Readiness};
+use crate::socket::{map_io_error, PacketDirection, PacketHook, SocketError, SrtSocket};
+use bytes::Bytes;
+use parking_lot::Mutex;
+use srt_protocol::buffer::BufferError;
+use srt_protocol::packet::ControlType;
+use srt_protocol::{
+    Connection, ConnectionError, ConnectionStats, ControlPacket, Packet, SendAction, SenderWorker,
+    SrtHandshake, StallAction, StallEvent,
+};
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::net::UdpSocket;
+use tokio::sync::Notify;
+use tokio::task::{JoinError, JoinHandle};
+use tokio::time::{self, Instant, MissedTickBehavior};
+
+/// Period of the driver's timers (ACK, NAK, keep-alive, retransmission)
+pub const DRIVER_TICK: Duration = Duration::from_millis(10);
+
+/// Time between handshake requests while connecting
+const HANDSHAKE_RETRY: Duration = Duration::from_millis(250);
+
+/// Largest UDP payload
+const MAX_DATAGRAM: usize = 65_507;
+
+/// Stall events kept for `AsyncConnection::take_stall`; older ones are dropped
+const MAX_PENDING_STALLS: usize = 16;
+
+/// Async connection errors
+#[derive(Error, Debug)]
+pub enum AsyncError {
+    #[error("Socket error: {0}")]
+    Socket(#[from] SocketError),
+
+    #[error("Connection error: {0}")]
+    Connection(#[from] ConnectionError),
+
+    #[error("Handshake timed out")]
+    HandshakeTimeout,
+
+    #[error("Peer stopped answering keep-alives")]
+    PeerLost,
+
+    #[error("Connection closed")]
+    Closed,
+
+    #[error("Driver task failed: {0}")]
+    Driver(#[from] JoinError),
+}
+
+/// SRT socket on the Tokio reactor
+pub struct AsyncSrtSocket {
+    inner: UdpSocket,
+    /// Observer of sent and received datagrams (e.g. a packet tracer)
+    hook: Option<PacketHook>,
+}
+
+impl AsyncSrtSocket {
+    /// Create a new socket bound to the given address
+    ///
+    /// Must be called from within a Tokio runtime.
+    pub fn bind(addr: SocketAddr) -> Result<Self, SocketError> {
+        Self::from_socket(SrtSocket::bind(addr)?)
+    }
+
+    /// Register a configured socket with the runtime, keeping its hook
+    ///
+    /// Must be called from within a Tokio runtime.
+    pub fn from_socket(socket: SrtSocket) -> Result<Self, SocketError> {
+        let (socket, hook) = socket.into_parts();
+        socket.set_nonblocking(true)?;
+        Ok(AsyncSrtSocket {
+            inner: UdpSocket::from_std(socket)?,
+            hook,
+        })
+    }
+
+    /// Install a hook that sees every datagram sent or received
+    pub fn set_packet_hook(&mut self, hook: Option<PacketHook>) {
+        self.hook = hook;
+    }
+
+    /// Pass a datagram to the hook, if any
+    fn observe(&self, direction: PacketDirection, buf: &[u8], peer: Option<SocketAddr>) {
+        let Some(hook) = &self.hook else {
+            return;
+        };
+        let peer = peer.or_else(|| self.inner.peer_addr().ok());
+        if let Some(peer) = peer {
+            hook(direction, buf, peer);
+        }
+    }
+
+    /// Get the local address this socket is bound to
+    pub fn local_addr(&self) -> Result<SocketAddr, SocketError> {
+        Ok(self.inner.local_addr()?)
+    }
+
+    /// Get the remote address of a connected socket
+    pub fn peer_addr(&self) -> Result<SocketAddr, SocketError> {
+        Ok(self.inner.peer_addr()?)
+    }
+
+    /// Connect the socket to a remote address
+    ///
+    /// As with [`SrtSocket::connect`], ICMP errors for the peer then
+    /// surface from sends and receives.
+    pub async fn connect(&self, addr: SocketAddr) -> Result<(), SocketError> {
+        self.inner.connect(addr).await.map_err(map_io_error)
+    }
+
+    /// Send data to the given address
+    pub async fn send_to(&self, buf: &[u8], target: SocketAddr) -> Result<usize, SocketError> {
+        let n = self
+            .inner
+            .send_to(buf, target)
+            .await
+            .map_err(map_io_error)?;
+        self.observe(PacketDirection::Sent, &buf[..n], Some(target));
+        Ok(n)
+    }
+
+    /// Send data on a connected socket
+    pub async fn send(&self, buf: &[u8]) -> Result<usize, SocketError> {
+        let n = self.inner.send(buf).await.map_err(map_io_error)?;
+        self.observe(PacketDirection::Sent, &buf[..n], None);
+        Ok(n)
+    }
+
+    /// Receive a datagram and its source address
+    pub async fn recv_from(&self, buf: &mut [u8]) -> Result<(usize, SocketAddr), SocketError> {
+        let (n, addr) = self.inner.recv_from(buf).await.map_err(map_io_error)?;
+        self.observe(PacketDirection::Received, &buf[..n], Some(addr));
+        Ok((n, addr))
+    }
+
+    /// Receive a datagram on a connected socket
+    pub async fn recv(&self, buf: &mut [u8]) -> Result<usize, SocketError> {
+        let n = self.inner.recv(buf).await.map_err(map_io_error)?;
+        self.observe(PacketDirection::Received, &buf[..n], None);
+        Ok(n)
+    }
+}
+
+/// State shared between an `AsyncConnection` and its driver task
+struct Shared {
+    conn: Connection,
+    /// Data was queued for transmission
+    queued: Notify,
+    /// Messages may be ready, or the driver stopped
+    readable: Notify,
+    /// Send buffer space may have been freed, or the driver stopped
+    writable: Notify,
+    /// The application asked to close
+    close: Notify,
+    /// Messages flushed when the peer shut down
+    drained: Mutex<VecDeque<Bytes>>,
+    /// Delivery stalls not yet taken by the application
+    stalls: Mutex<VecDeque<StallEvent>>,
+    /// Set once the driver task has stopped
+    stopped: AtomicBool,
+    /// Woken whenever the driver may have changed readiness
+    notifier: Mutex<Option<EpollNotifier>>,
+}
+
+impl Shared {
+    fn notify_epoll(&self) {
+        if let Some(notifier) = &*self.notifier.lock() {
+            notifier.notify();
+        }
+    }
+}
+
+/// A connection driven by a Tokio task
+///
+/// Created by [`AsyncConnection::connect`] (caller) or
+/// [`AsyncConnection::accept`] (listener). Dropping it without `close`
+/// still shuts the connection down, in the background.
+pub struct AsyncConnection {
+    shared: Arc<Shared>,
+    driver: Option<JoinHandle<Result<(), AsyncError>>>,
+}
+
+impl AsyncConnection {
+    /// Connect to the connection's remote address as a caller
+    ///
+    /// Repeats the handshake request until the listener answers or
+    /// `timeout` passes.
+    pub async fn connect(
+        socket: AsyncSrtSocket,
+        mut conn: Connection,
+        timeout: Duration,
+    ) -> Result<Self, AsyncError> {
+        socket.connect(conn.remote_addr()).await?;
+        let deadline = Instant::now() + timeout;
+        let mut buf = vec![0u8; MAX_DATAGRAM];
+
+        while !conn.is_connected() {
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(AsyncError::HandshakeTimeout);
+            }
+            socket
+                .send(&handshake_packet(&conn, conn.create_handshake()))
+                .await?;
+
+            let retry_at = deadline.min(now + HANDSHAKE_RETRY);
+            while let Ok(received) = time::timeout_at(retry_at, socket.recv(&mut buf)).await {
+                if let Some(handshake) = SrtHandshake::from_datagram(&buf[..received?]) {
+                    conn.process_agreement(handshake)?;
+                    break;
+                }
+            }
+        }
+
+        Ok(Self::spawn(socket, conn, false))
+    }
+
+    /// Wait for one caller's handshake and answer it as a listener
+    ///
+    /// `configure` builds the connection once the caller's address is
+    /// known. The socket is then connected to the caller, so each accepted
+    /// connection needs its own socket.
+    pub async fn accept(
+        socket: AsyncSrtSocket,
+        timeout: Duration,
+        configure: impl FnOnce(SocketAddr) -> Connection,
+    ) -> Result<Self, AsyncError> {
+        let mut buf = vec![0u8; MAX_DATAGRAM];
+        let wait = async {
+            loop {
+                let (n, from) = socket.recv_from(&mut buf).await?;
+                if let Some(handshake) = SrtHandshake::from_datagram(&buf[..n]) {
+                    return Ok::<_, AsyncError>((handshake, from));
+                }
+            }
+        };
+        let (handshake, peer) = time::timeout(timeout, wait)
+            .await
+            .map_err(|_| AsyncError::HandshakeTimeout)??;
+
+        let mut conn = configure(peer);
+        conn.process_handshake(handshake.clone())?;
+        socket.connect(peer).await?;
+        let agreement = conn.create_agreement(handshake);
+        socket.send(&handshake_packet(&conn, agreement)).await?;
+
+        Ok(Self::spawn(socket, conn, true))
+    }
+
+    fn spawn(socket: AsyncSrtSocket, conn: Connection, listener: bool) -> Self {
+        let shared = Arc::new(Shared {
+            conn,
+            queued: Notify::new(),
+            readable: Notify::new(),
+            writable: Notify::new(),
+            close: Notify::new(),
+            drained: Mutex::new(VecDeque::new()),
+            stalls: Mutex::new(VecDeque::new()),
+            stopped: AtomicBool::new(false),
+            notifier: Mutex::new(None),
+        });
+        let driver = tokio::spawn(drive(socket, Arc::clone(&shared), listener));
+        AsyncConnection {
+            shared,
+            driver: Some(driver),
+        }
+    }
+
+    /// Get the underlying connection
+    pub fn connection(&self) -> &Connection {
+        &self.shared.conn
+    }
+
+    /// Get connection statistics
+    pub fn stats(&self) -> ConnectionStats {
+        self.shared.conn.stats()
+    }
+
+    /// Check if the driver has stopped (closed, or the peer left)
+    pub fn is_closed(&self) -> bool {
+        self.shared.stopped.load(Ordering::Acquire)
+    }
+
+    /// Wake an epoll whenever the driver may have changed readiness
+    ///
+    /// Register the connection with the same `Epoll` to wait on it.
+    pub fn set_notifier(&self, notifier: Option<EpollNotifier>) {
+        *self.shared.notifier.lock() = notifier;
+        self.shared.notify_epoll();
+    }
+
+    /// Queue a message, waiting while the send buffer is full
+    ///
+    /// Messages larger than a packet are split and delivered whole.
+    pub async fn send(&self, data: &[u8]) -> Result<usize, AsyncError> {
+        loop {
+            let writable = self.shared.writable.notified();
+            if self.is_closed() {
+                return Err(AsyncError::Closed);
+            }
+            match self.shared.conn.send_message(data) {
+                Ok(n) => {
+                    self.shared.queued.notify_one();
+                    return Ok(n);
+                }
+                Err(ConnectionError::Buffer(BufferError::Full)) => writable.await,
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// Receive the next message in order
+    ///
+    /// Returns None once the connection has closed and every message
+    /// received before the peer's shutdown has been delivered. Meant for
+    /// a single receiving task.
+    pub async fn recv(&self) -> Option<Bytes> {
+        loop {
+            let readable = self.shared.readable.notified();
+            if let Ok(Some(data)) = self.shared.conn.recv() {
+                return Some(data);
+            }
+            if let Some(data) = self.shared.drained.lock().pop_front() {
+                return Some(data);
+            }
+            if self.is_closed() {
+                return None;
+            }
+            readable.await;
+        }
+    }
+
+    /// Take the oldest delivery stall the driver detected
+    ///
+    /// Only raised once the connection has a stall timeout. A stall whose
+    /// action is `Close` also stops the driver with
+    /// `ConnectionError::Stalled`.
+    pub fn take_stall(&self) -> Option<StallEvent> {
+        self.shared.stalls.lock().pop_front()
+    }
+
+    /// Send what is queued, tell the peer we are done and stop the driver
+    ///
+    /// Data the peer has not acknowledged is not retransmitted. Returns
+    /// the final statistics, or the error that stopped the driver.
+    pub async fn close(mut self) -> Result<ConnectionStats, AsyncError> {
+        self.shared.close.notify_one();
+        if let Some(driver) = self.driver.take() {
+            driver.await??;
+        }
+        Ok(self.shared.conn.stats())
+    }
+}
+
+impl Readiness for AsyncConnection {
+    fn readiness(&self) -> Events {
+        let mut events = self.shared.conn.readiness();
+        if !self.shared.drained.lock().is_empty() {
+            events |= Events::READ;
+        }
+        if self.is_closed() {
+            events |= Events::ERROR;
+        }
+        events
+    }
+}
+
+impl Drop for AsyncConnection {
+    fn drop(&mut self) {
+        if self.driver.is_some() {
+            self.shared.close.notify_one();
+        }
+    }
+}
+
+/// Serialize a handshake from the connection to its peer
+fn handshake_packet(conn: &Connection, handshake: SrtHandshake) -> Bytes {
+    let body = handshake.to_bytes().freeze();
+    let dest = conn.remote_socket_id().unwrap_or(0);
+    ControlPacket::new(ControlType::Handshake, 0, 0, 0, dest, body)
+        .to_bytes()
+        .freeze()
+}
+
+/// Driver task: run the connection until closed, then wake any waiters
+async fn drive(
+    socket: AsyncSrtSocket,
+    shared: Arc<Shared>,
+    listener: bool,
+) -> Result<(), AsyncError> {
+    let result = run(&socket, &shared, listener).await;
+    if let Err(e) = &result {
+        tracing::debug!(
+            "SRT connection to {} stopped: {}",
+            shared.conn.remote_addr(),
+            e
+        );
+    }
+    shared.conn.close();
+    shared.stopped.store(true, Ordering::Release);
+    shared.readable.notify_one();
+    shared.writable.notify_waiters();
+    shared.notify_epoll();
+    result
+}
+
+async fn run(socket: &AsyncSrtSocket, shared: &Shared, listener: bool) -> Result<(), AsyncError> {
+    let conn = &shared.conn;
+    let mut tick = time::interval(DRIVER_TICK);
+    tick.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut buf = vec![0u8; MAX_DATAGRAM];
+    let mut sender = SenderWorker::new();
+
+    loop {
+        // Timers before the socket, so a data flood cannot delay feedback
+        tokio::select! {
+            biased;
+
+            _ = shared.close.notified() => {
+                transmit(socket, conn, &mut sender).await?;
+                let shutdown = conn.create_shutdown();
+                let _ = socket.send(&shutdown.to_bytes()).await;
+                return Ok(());
+            }
+            _ = tick.tick() => {
+                if !conn.liveness().is_alive() {
+                    return Err(AsyncError::PeerLost);
+                }
+                conn.check_timeout()?;
+                if let Some(stall) = conn.check_stall() {
+                    let (action, stalled_for) = (stall.action, stall.stalled_for);
+                    let mut stalls = shared.stalls.lock();
+                    if stalls.len() >= MAX_PENDING_STALLS {
+                        stalls.pop_front();
+                    }
+                    stalls.push_back(stall);
+                    if action == StallAction::Close {
+                        return Err(ConnectionError::Stalled(stalled_for).into());
+                    }
+                }
+                transmit(socket, conn, &mut sender).await?;
+                let mut feedback: Vec<ControlPacket> = Vec::new();
+                feedback.extend(conn.create_ack_if_due());
+                feedback.extend(conn.create_nak());
+                while let Some(keepalive) = conn.create_keepalive() {
+                    feedback.push(keepalive);
+                }
+                feedback.extend(conn.create_drop_requests());
+                feedback.extend(conn.create_probe_packets());
+                feedback.extend(conn.create_probe_report());
+                for packet in feedback {
+                    send_datagram(socket, &packet.to_bytes()).await?;
+                }
+                shared.notify_epoll();
+            }
+            received = socket.recv(&mut buf) => {
+                let n = match received {
+                    Ok(n) => n,
+                    // Reported ahead of queued datagrams, such as a shutdown
+                    Err(e) if e.is_peer_unreachable() => continue,
+                    Err(e) => return Err(e.into()),
+                };
+                let Ok(packet) = Packet::from_bytes(&buf[..n]) else {
+                    continue;
+                };
+                match packet {
+                    Packet::Data(packet) => {
+                        if conn.process_data_packet(packet).is_ok() {
+                            shared.readable.notify_one();
+                            shared.notify_epoll();
+                        }
+                    }
+                    Packet::Control(control) => match control.control_type() {
+                        // Repeat the reply in case the previous one was lost
+                        ControlType::Handshake if listener => {
+                            if let Ok(request) = SrtHandshake::from_bytes(&control.control_info) {
+                                let agreement = conn.create_agreement(request);
+                                send_datagram(socket, &handshake_packet(conn, agreement)).await?;
+                            }
+                        }
+                        ControlType::Handshake => {}
+                        ControlType::Shutdown => {
+                            conn.begin_close();
+                            if let Ok(drain) = conn.drain() {
+                                shared.drained.lock().extend(drain.messages);
+                            }
+                            return Ok(());
+                        }
+                        _ => {
+                            let _ = conn.process_control(&control);
+                            shared.writable.notify_waiters();
+                            shared.readable.notify_one();
+                            shared.notify_epoll();
+                        }
+                    },
+                }
+            }
+            _ = shared.queued.notified() => transmit(socket, conn, &mut sender).await?,
+        }
+    }
+}
+
+/// Send due retransmissions, then new data, paced to `max_bandwidth`
+async fn transmit(
+    socket: &AsyncSrtSocket,
+    conn: &Connection,
+    sender: &mut SenderWorker,
+) -> Result<(), AsyncError> {
+    loop {
+        match sender.poll(conn) {
+            SendAction::Send(packet) => send_datagram(socket, &packet.to_bytes()).await?,
+            SendAction::Wait(delay) => time::sleep(delay).await,
+            SendAction::Idle => return Ok(()),
+        }
+    }
+}
+
+/// Send a datagram to the peer
+///
+/// ICMP errors for a peer that went away are ignored; missed keep-alives
+/// decide when it is gone.
+async fn send_datagram(socket: &AsyncSrtSocket, buf: &[u8]) -> Result<(), AsyncError> {
+    match socket.send(buf).await {
+        Err(e) if !e.is_peer_unreachable() => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use srt_protocol::SeqNumber;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    fn localhost() -> SocketAddr {
+        "127.0.0.1:0".parse().unwrap()
+    }
+
+    /// Connect a caller to a listener over loopback
+    async fn pair(id: u32) -> (AsyncConnection, AsyncConnection) {
+        let listen = AsyncSrtSocket::bind(localhost()).unwrap();
+        let listen_addr = listen.local_addr().unwrap();
+        let listener = tokio::spawn(AsyncConnection::accept(listen, TIMEOUT, move |peer| {
+            Connection::new(id + 1, listen_addr, peer, SeqNumber::new(0), 120)
+        }));
+
+        let socket = AsyncSrtSocket::bind(localhost()).unwrap();
+        let conn = Connection::new(
+            id,
+            socket.local_addr().unwrap(),
+            listen_addr,
+            SeqNumber::new(0),
+            120,
+        );
+        let caller = AsyncConnection::connect(socket, conn, TIMEOUT)
+            .await
+            .unwrap();
+        (caller, listener.await.unwrap().unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_messages_in_order() {
+        let (caller, listener) = pair(10).await;
+        assert_eq!(listener.connection().remote_socket_id(), Some(10));
+
+        let sent: Vec<Bytes> = (0..500u32)
+            .map(|i| Bytes::from(i.to_be_bytes().repeat(100)))
+            .collect();
+        let sender = {
+            let sent = sent.clone();
+            tokio::spawn(async move {
+                for message in &sent {
+                    caller.send(message).await.unwrap();
+                }
+                caller
+            })
+        };
+
+        let mut received = Vec::new();
+        while received.len() < sent.len() {
+            let message = time::timeout(TIMEOUT, listener.recv()).await.unwrap();
+            received.push(message.unwrap());
+        }
+        assert_eq!(received, sent);
+
+        // Closing the caller ends the listener's stream
+        let caller = sender.await.unwrap();
+        let stats = caller.close().await.unwrap();
+        assert_eq!(stats.packets_sent, sent.len() as u64);
+        let end = time::timeout(TIMEOUT, listener.recv()).await.unwrap();
+        assert_eq!(end, None);
+        assert!(listener.is_closed());
+        assert!(matches!(
+            listener.send(b"late").await,
+            Err(AsyncError::Closed)
+        ));
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_many_connections_one_thread() {
+        let mut tasks = Vec::new();
+        for i in 0..50u32 {
+            tasks.push(tokio::spawn(async move {
+                let (caller, listener) = pair(1000 + 2 * i).await;
+                for n in 0..20u32 {
+                    caller.send(&(i * 100 + n).to_be_bytes()).await.unwrap();
+                }
+                for n in 0..20u32 {
+                    let message = listener.recv().await.unwrap();
+                    assert_eq!(message.as_ref(), (i * 100 + n).to_be_bytes());
+                }
+                caller.close().await.unwrap();
+                listener.close().await.unwrap();
+            }));
+        }
+        for task in tasks {
+            time::timeout(TIMEOUT, task).await.unwrap().unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_driver_wakes_epoll_waiter() {
+        use crate::epoll::{Epoll, ReadyEvent, Trigger};
+
+        let (caller, listener) = pair(20).await;
+        let epoll = Arc::new(Epoll::new());
+        let listener = Arc::new(listener);
+        listener.set_notifier(Some(epoll.notifier()));
+        epoll
+            .add(1, listener.clone(), Events::READ, Trigger::Level)
+            .unwrap();
+
+        // Blocks a thread without a timeout until the driver wakes it
+        let waiter = {
+            let epoll = Arc::clone(&epoll);
+            tokio::task::spawn_blocking(move || {
+                let mut ready = Vec::new();
+                epoll.wait(&mut ready, None);
+                ready
+            })
+        };
+        caller.send(b"wake").await.unwrap();
+        let ready = time::timeout(TIMEOUT, waiter).await.unwrap().unwrap();
+        assert_eq!(
+            ready,
+            vec![ReadyEvent {
+                token: 1,
+                events: Events::READ
+            }]
+        );
+
+        epoll.remove(1).unwrap();
+        let Ok(listener) = Arc::try_unwrap(listener) else {
+            panic!("listener still shared");
+        };
+        caller.close().await.unwrap();
+        listener.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_handshake_timeout() {
+        // Nobody answers on this socket
+        let silent = AsyncSrtSocket::bind(localhost()).unwrap();
+        let socket = AsyncSrtSocket::bind(localhost()).unwrap();
+        let conn = Connection::new(
+            1,
+            socket.local_addr().unwrap(),
+            silent.local_addr().unwrap(),
+            SeqNumber::new(0),
+            120,
+        );
+        let result = AsyncConnection::connect(socket, conn, Duration::from_millis(300)).await;
+        assert!(matches!(result, Err(AsyncError::HandshakeTimeout)));
+    }
+}
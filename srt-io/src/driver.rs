@@ -0,0 +1,454 @@
+//! Control and data plane separation for connection drivers
+//!
+//! One socket carries both bulk data and the control packets (ACK, NAK,
+//! keep-alive) that keep a connection healthy. Handled in arrival order,
+//! a data flood queues control packets behind it until ACKs arrive late
+//! and keep-alives look missed.
+//!
+//! `PlaneReader` reads waiting datagrams a batch at a time and hands out
+//! control packets before data, so a driver loop services control and
+//! timers first. `ControlPlane` goes further: a dedicated thread, at a
+//! raised scheduling priority if configured, reads the socket and handles
+//! control packets and timers itself, passing data on through a channel.
+
+use crate::socket::{SocketError, SrtSocket};
+use std::collections::VecDeque;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+/// Datagrams read per batch unless configured otherwise
+pub const DEFAULT_BATCH_SIZE: usize = 64;
+
+/// Largest UDP payload
+const MAX_DATAGRAM: usize = 65_507;
+
+/// Time the control thread waits when the socket has nothing to read
+const IDLE_WAIT: Duration = Duration::from_millis(1);
+
+/// A datagram read from the socket
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Datagram {
+    /// Packet bytes
+    pub data: Vec<u8>,
+    /// Sender address
+    pub from: SocketAddr,
+    /// When the datagram was read
+    pub received: Instant,
+}
+
+impl Datagram {
+    /// Check if this is an SRT control packet (first header bit set)
+    pub fn is_control(&self) -> bool {
+        self.data.first().is_some_and(|b| b & 0x80 != 0)
+    }
+}
+
+/// Control plane timing and volume
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PlaneStats {
+    /// Control packets handed out
+    pub control_packets: u64,
+    /// Data packets handed out
+    pub data_packets: u64,
+    /// Longest time a control packet waited between read and handling
+    pub control_latency_max: Duration,
+    /// Total wait of all control packets
+    pub control_latency_total: Duration,
+    /// Timer services run by the control thread
+    pub ticks: u64,
+}
+
+impl PlaneStats {
+    /// Get the mean time a control packet waited, once any were handled
+    pub fn control_latency_avg(&self) -> Option<Duration> {
+        u32::try_from(self.control_packets)
+            .ok()
+            .filter(|&n| n > 0)
+            .map(|n| self.control_latency_total / n)
+    }
+
+    fn record_control(&mut self, waited: Duration) {
+        self.control_packets += 1;
+        self.control_latency_total += waited;
+        self.control_latency_max = self.control_latency_max.max(waited);
+    }
+}
+
+/// Reads datagrams in batches and hands out control packets first
+pub struct PlaneReader {
+    batch_size: usize,
+    buf: Vec<u8>,
+    control: VecDeque<Datagram>,
+    data: VecDeque<Datagram>,
+    stats: PlaneStats,
+}
+
+impl PlaneReader {
+    /// Create a reader taking up to `batch_size` datagrams per read
+    pub fn new(batch_size: usize) -> Self {
+        PlaneReader {
+            batch_size: batch_size.max(1),
+            buf: vec![0u8; MAX_DATAGRAM],
+            control: VecDeque::new(),
+            data: VecDeque::new(),
+            stats: PlaneStats::default(),
+        }
+    }
+
+    /// Read up to one batch of waiting datagrams
+    ///
+    /// Returns the number read; 0 if the socket had nothing waiting.
+    pub fn fill(&mut self, socket: &SrtSocket) -> Result<usize, SocketError> {
+        let mut count = 0;
+        while count < self.batch_size {
+            match socket.recv_from(&mut self.buf) {
+                Ok((n, from)) => {
+                    self.push(Datagram {
+                        data: self.buf[..n].to_vec(),
+                        from,
+                        received: Instant::now(),
+                    });
+                    count += 1;
+                }
+                Err(SocketError::Io(e)) if e.kind() == io::ErrorKind::WouldBlock => break,
+                // Datagrams already read are still handed out
+                Err(e) if count == 0 => return Err(e),
+                Err(_) => break,
+            }
+        }
+        Ok(count)
+    }
+
+    /// Queue a datagram obtained elsewhere
+    pub fn push(&mut self, datagram: Datagram) {
+        if datagram.is_control() {
+            self.control.push_back(datagram);
+        } else {
+            self.data.push_back(datagram);
+        }
+    }
+
+    /// Take the next control packet
+    pub fn next_control(&mut self) -> Option<Datagram> {
+        let datagram = self.control.pop_front()?;
+        self.stats.record_control(datagram.received.elapsed());
+        Some(datagram)
+    }
+
+    /// Take the next data packet
+    pub fn next_data(&mut self) -> Option<Datagram> {
+        let datagram = self.data.pop_front()?;
+        self.stats.data_packets += 1;
+        Some(datagram)
+    }
+
+    /// Take the next datagram, control packets before any data
+    pub fn next_datagram(&mut self) -> Option<Datagram> {
+        self.next_control().or_else(|| self.next_data())
+    }
+
+    /// Get the number of control and data packets queued
+    pub fn pending(&self) -> (usize, usize) {
+        (self.control.len(), self.data.len())
+    }
+
+    /// Check whether nothing is queued
+    pub fn is_empty(&self) -> bool {
+        self.control.is_empty() && self.data.is_empty()
+    }
+
+    /// Get the control plane stats
+    pub fn stats(&self) -> PlaneStats {
+        self.stats
+    }
+}
+
+impl Default for PlaneReader {
+    fn default() -> Self {
+        Self::new(DEFAULT_BATCH_SIZE)
+    }
+}
+
+/// Control packet and timer handling run on the control thread
+pub trait ControlHandler: Send + 'static {
+    /// Handle a control packet
+    fn on_control(&mut self, datagram: Datagram);
+
+    /// Service timers (keep-alives, ACKs, NAKs); called every tick
+    fn on_tick(&mut self) {}
+}
+
+/// Control thread configuration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ControlPlaneConfig {
+    /// Datagrams read per batch
+    pub batch_size: usize,
+    /// Time between `on_tick` calls
+    pub tick_interval: Duration,
+    /// Nice value for the control thread (lower runs first; None = inherit)
+    pub priority: Option<i32>,
+}
+
+impl Default for ControlPlaneConfig {
+    fn default() -> Self {
+        ControlPlaneConfig {
+            batch_size: DEFAULT_BATCH_SIZE,
+            tick_interval: Duration::from_millis(10),
+            priority: None,
+        }
+    }
+}
+
+/// A dedicated thread reading the socket and handling the control plane
+///
+/// Data packets are passed on in arrival order through `recv_data`.
+pub struct ControlPlane {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+    data: Receiver<Datagram>,
+    stats: Arc<Mutex<PlaneStats>>,
+}
+
+impl ControlPlane {
+    /// Start the control thread on `socket`
+    ///
+    /// Fails if the priority cannot be applied (raising it usually needs
+    /// privileges); the thread is not started then.
+    pub fn spawn<H: ControlHandler>(
+        socket: SrtSocket,
+        config: ControlPlaneConfig,
+        mut handler: H,
+    ) -> Result<Self, SocketError> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stats = Arc::new(Mutex::new(PlaneStats::default()));
+        let (data_tx, data) = mpsc::channel();
+        let (started_tx, started) = mpsc::channel();
+
+        let thread_stop = stop.clone();
+        let thread_stats = stats.clone();
+        let handle = thread::spawn(move || {
+            let priority = config.priority.map_or(Ok(()), set_current_thread_priority);
+            let failed = priority.is_err();
+            let _ = started_tx.send(priority);
+            if failed {
+                return;
+            }
+
+            let mut reader = PlaneReader::new(config.batch_size);
+            let mut ticks = 0;
+            let mut next_tick = Instant::now() + config.tick_interval;
+            while !thread_stop.load(Ordering::Relaxed) {
+                let read = match reader.fill(&socket) {
+                    Ok(n) => n,
+                    Err(e) => {
+                        tracing::debug!("Control plane receive error: {}", e);
+                        0
+                    }
+                };
+
+                while let Some(datagram) = reader.next_control() {
+                    handler.on_control(datagram);
+                }
+                if Instant::now() >= next_tick {
+                    handler.on_tick();
+                    ticks += 1;
+                    next_tick += config.tick_interval;
+                    next_tick = next_tick.max(Instant::now());
+                }
+                while let Some(datagram) = reader.next_data() {
+                    // The consumer has gone; keep servicing control
+                    let _ = data_tx.send(datagram);
+                }
+
+                *thread_stats.lock() = PlaneStats {
+                    ticks,
+                    ..reader.stats()
+                };
+                if read == 0 {
+                    let until_tick = next_tick.saturating_duration_since(Instant::now());
+                    thread::sleep(IDLE_WAIT.min(until_tick));
+                }
+            }
+        });
+
+        let mut plane = ControlPlane {
+            stop,
+            handle: Some(handle),
+            data,
+            stats,
+        };
+        match started.recv() {
+            Ok(Ok(())) => Ok(plane),
+            Ok(Err(e)) => {
+                plane.stop();
+                Err(e)
+            }
+            Err(_) => {
+                plane.stop();
+                Err(SocketError::Io(io::Error::new(
+                    io::ErrorKind::Other,
+                    "control thread exited",
+                )))
+            }
+        }
+    }
+
+    /// Wait up to `timeout` for the next data packet
+    pub fn recv_data(&self, timeout: Duration) -> Option<Datagram> {
+        self.data.recv_timeout(timeout).ok()
+    }
+
+    /// Take the next data packet if one is waiting
+    pub fn try_recv_data(&self) -> Option<Datagram> {
+        self.data.try_recv().ok()
+    }
+
+    /// Get the control plane stats
+    pub fn stats(&self) -> PlaneStats {
+        *self.stats.lock()
+    }
+
+    /// Stop the control thread and wait for it to exit
+    pub fn shutdown(mut self) {
+        self.stop();
+    }
+
+    fn stop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for ControlPlane {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Set the scheduling priority (nice value) of the calling thread
+///
+/// Lower values run first; going below the current value usually needs
+/// privileges. Supported on Linux only.
+pub fn set_current_thread_priority(nice: i32) -> Result<(), SocketError> {
+    #[cfg(target_os = "linux")]
+    {
+        crate::sys::set_thread_nice(nice).map_err(SocketError::Io)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = nice;
+        Err(SocketError::UnsupportedOption)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn datagram(first_byte: u8) -> Datagram {
+        Datagram {
+            data: vec![first_byte, 0, 0, 0],
+            from: "127.0.0.1:9000".parse().unwrap(),
+            received: Instant::now(),
+        }
+    }
+
+    #[test]
+    fn test_control_before_data() {
+        let mut reader = PlaneReader::new(8);
+        for first in [0x00, 0x01, 0x80, 0x02, 0x81] {
+            reader.push(datagram(first));
+        }
+        assert_eq!(reader.pending(), (2, 3));
+
+        let order: Vec<u8> = std::iter::from_fn(|| reader.next_datagram())
+            .map(|d| d.data[0])
+            .collect();
+        assert_eq!(order, vec![0x80, 0x81, 0x00, 0x01, 0x02]);
+        assert!(reader.is_empty());
+
+        let stats = reader.stats();
+        assert_eq!(stats.control_packets, 2);
+        assert_eq!(stats.data_packets, 3);
+        assert!(stats.control_latency_avg().unwrap() <= stats.control_latency_max);
+    }
+
+    #[test]
+    fn test_fill_reads_in_batches() {
+        let socket = SrtSocket::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let peer = SrtSocket::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let addr = socket.local_addr().unwrap();
+        for i in 0..5u8 {
+            peer.send_to(&[i | if i == 4 { 0x80 } else { 0 }], addr)
+                .unwrap();
+        }
+
+        let mut reader = PlaneReader::new(3);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while reader.pending().0 + reader.pending().1 < 5 {
+            assert!(Instant::now() < deadline);
+            assert!(reader.fill(&socket).unwrap() <= 3);
+        }
+        // The control packet sent last is handled first
+        assert_eq!(reader.next_datagram().unwrap().data, vec![0x84]);
+        assert_eq!(reader.next_datagram().unwrap().data, vec![0]);
+    }
+
+    struct Recorder(Arc<Mutex<Vec<Vec<u8>>>>);
+
+    impl ControlHandler for Recorder {
+        fn on_control(&mut self, datagram: Datagram) {
+            self.0.lock().push(datagram.data);
+        }
+    }
+
+    #[test]
+    fn test_control_plane_thread() {
+        let socket = SrtSocket::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let peer = SrtSocket::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let addr = socket.local_addr().unwrap();
+
+        let handled = Arc::new(Mutex::new(Vec::new()));
+        let config = ControlPlaneConfig {
+            tick_interval: Duration::from_millis(2),
+            ..Default::default()
+        };
+        let plane = ControlPlane::spawn(socket, config, Recorder(handled.clone())).unwrap();
+        peer.send_to(&[0x01, 1], addr).unwrap();
+        peer.send_to(&[0x80, 2], addr).unwrap();
+
+        let data = plane.recv_data(Duration::from_secs(5)).unwrap();
+        assert_eq!(data.data, vec![0x01, 1]);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while handled.lock().is_empty() {
+            assert!(Instant::now() < deadline);
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(*handled.lock(), vec![vec![0x80, 2]]);
+        while plane.stats().ticks == 0 {
+            assert!(Instant::now() < deadline);
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(plane.stats().control_packets, 1);
+        plane.shutdown();
+        assert_eq!(Arc::strong_count(&handled), 1);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_lower_thread_priority() {
+        // Lowering priority needs no privileges
+        thread::spawn(|| set_current_thread_priority(10).unwrap())
+            .join()
+            .unwrap();
+    }
+}
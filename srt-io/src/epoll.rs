@@ -0,0 +1,560 @@
+//! Readiness events for many connections
+//!
+//! An application serving many SRT connections or groups should not spin
+//! over each one asking whether it can read or write. `Epoll` holds a set
+//! of registered sources, each under a caller-chosen token, and `wait`
+//! blocks until one of them is ready or the timeout passes.
+//!
+//! Sources report their own readiness through `Readiness`; connections
+//! implement it with the `protocol` feature, and anything else (a socket
+//! group, an application queue) can register a closure. Whoever changes a
+//! source's state, usually the driver that feeds it packets, calls
+//! `EpollNotifier::notify` so a waiting thread rechecks. `ERROR` is always
+//! reported, as with epoll(7), whatever the interest.
+//!
+//! Level-triggered registrations report every wait while a source stays
+//! ready. Edge-triggered ones report an event once when it becomes ready,
+//! then not again until it has gone unready in between.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::{BitAnd, BitOr, BitOrAssign};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use parking_lot::{Condvar, Mutex};
+use thiserror::Error;
+
+/// A set of readiness events
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Events(u8);
+
+impl Events {
+    /// No events
+    pub const NONE: Events = Events(0);
+    /// Data is waiting to be received
+    pub const READ: Events = Events(0b001);
+    /// There is room to send
+    pub const WRITE: Events = Events(0b010);
+    /// The source is closing, closed or broken
+    pub const ERROR: Events = Events(0b100);
+    /// Every event
+    pub const ALL: Events = Events(0b111);
+
+    /// Check if no event is set
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Check if every event in `other` is set
+    pub fn contains(self, other: Events) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Check if any event in `other` is set
+    pub fn intersects(self, other: Events) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    /// Events set here but not in `other`
+    pub fn difference(self, other: Events) -> Events {
+        Events(self.0 & !other.0)
+    }
+}
+
+impl BitOr for Events {
+    type Output = Events;
+
+    fn bitor(self, rhs: Events) -> Events {
+        Events(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for Events {
+    fn bitor_assign(&mut self, rhs: Events) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl BitAnd for Events {
+    type Output = Events;
+
+    fn bitand(self, rhs: Events) -> Events {
+        Events(self.0 & rhs.0)
+    }
+}
+
+impl fmt::Debug for Events {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<_> = [
+            (Events::READ, "READ"),
+            (Events::WRITE, "WRITE"),
+            (Events::ERROR, "ERROR"),
+        ]
+        .iter()
+        .filter(|(event, _)| self.contains(*event))
+        .map(|(_, name)| *name)
+        .collect();
+        if names.is_empty() {
+            write!(f, "Events(NONE)")
+        } else {
+            write!(f, "Events({})", names.join(" | "))
+        }
+    }
+}
+
+/// When a registration reports a ready event
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Trigger {
+    /// Every wait while the event stays ready
+    #[default]
+    Level,
+    /// Once each time the event becomes ready
+    Edge,
+}
+
+/// Something whose readiness can be waited on
+///
+/// Called with the registration set locked, so implementations must not
+/// touch the `Epoll` they are registered with.
+pub trait Readiness: Send + Sync {
+    /// Events the source is ready for right now
+    fn readiness(&self) -> Events;
+}
+
+impl<F> Readiness for F
+where
+    F: Fn() -> Events + Send + Sync,
+{
+    fn readiness(&self) -> Events {
+        self()
+    }
+}
+
+#[cfg(feature = "protocol")]
+impl Readiness for srt_protocol::Connection {
+    fn readiness(&self) -> Events {
+        use srt_protocol::ConnectionState;
+
+        let mut events = Events::NONE;
+        if self.recv_ready() {
+            events |= Events::READ;
+        }
+        if self.send_ready() {
+            events |= Events::WRITE;
+        }
+        if self.is_timed_out()
+            || matches!(
+                self.state(),
+                ConnectionState::Closing | ConnectionState::Closed
+            )
+        {
+            events |= Events::ERROR;
+        }
+        events
+    }
+}
+
+/// A ready registration returned by `Epoll::wait`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReadyEvent {
+    /// Token the source was registered under
+    pub token: u64,
+    /// Events ready for it
+    pub events: Events,
+}
+
+/// Epoll errors
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum EpollError {
+    #[error("Token {0} is already registered")]
+    DuplicateToken(u64),
+
+    #[error("Token {0} is not registered")]
+    UnknownToken(u64),
+}
+
+struct Registration {
+    source: Arc<dyn Readiness>,
+    interest: Events,
+    trigger: Trigger,
+    /// Events ready at the last check, for edge triggering
+    last: Events,
+}
+
+impl Registration {
+    fn poll(&mut self) -> Events {
+        let ready = self.source.readiness() & (self.interest | Events::ERROR);
+        let reported = match self.trigger {
+            Trigger::Level => ready,
+            Trigger::Edge => ready.difference(self.last),
+        };
+        self.last = ready;
+        reported
+    }
+}
+
+/// Wake-up state shared with notifiers
+#[derive(Default)]
+struct Signal {
+    generation: Mutex<u64>,
+    changed: Condvar,
+}
+
+/// Handle that wakes threads waiting on an `Epoll`
+#[derive(Clone)]
+pub struct EpollNotifier {
+    signal: Arc<Signal>,
+}
+
+impl EpollNotifier {
+    /// Tell waiters that some source's readiness may have changed
+    pub fn notify(&self) {
+        *self.signal.generation.lock() += 1;
+        self.signal.changed.notify_all();
+    }
+}
+
+impl fmt::Debug for EpollNotifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EpollNotifier").finish_non_exhaustive()
+    }
+}
+
+/// A set of sources to wait on
+#[derive(Default)]
+pub struct Epoll {
+    registrations: Mutex<BTreeMap<u64, Registration>>,
+    signal: Arc<Signal>,
+}
+
+impl Epoll {
+    /// Create an empty set
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a source under `token`
+    pub fn add(
+        &self,
+        token: u64,
+        source: Arc<dyn Readiness>,
+        interest: Events,
+        trigger: Trigger,
+    ) -> Result<(), EpollError> {
+        let mut registrations = self.registrations.lock();
+        if registrations.contains_key(&token) {
+            return Err(EpollError::DuplicateToken(token));
+        }
+        registrations.insert(
+            token,
+            Registration {
+                source,
+                interest,
+                trigger,
+                last: Events::NONE,
+            },
+        );
+        drop(registrations);
+        self.notify();
+        Ok(())
+    }
+
+    /// Change the events and trigger of a registered source
+    ///
+    /// Resets edge tracking, so an event that is already ready reports again.
+    pub fn modify(&self, token: u64, interest: Events, trigger: Trigger) -> Result<(), EpollError> {
+        let mut registrations = self.registrations.lock();
+        let registration = registrations
+            .get_mut(&token)
+            .ok_or(EpollError::UnknownToken(token))?;
+        registration.interest = interest;
+        registration.trigger = trigger;
+        registration.last = Events::NONE;
+        drop(registrations);
+        self.notify();
+        Ok(())
+    }
+
+    /// Unregister a source
+    pub fn remove(&self, token: u64) -> Result<(), EpollError> {
+        self.registrations
+            .lock()
+            .remove(&token)
+            .map(|_| ())
+            .ok_or(EpollError::UnknownToken(token))
+    }
+
+    /// Number of registered sources
+    pub fn len(&self) -> usize {
+        self.registrations.lock().len()
+    }
+
+    /// Check if no source is registered
+    pub fn is_empty(&self) -> bool {
+        self.registrations.lock().is_empty()
+    }
+
+    /// Handle for drivers to wake waiters when readiness changes
+    pub fn notifier(&self) -> EpollNotifier {
+        EpollNotifier {
+            signal: self.signal.clone(),
+        }
+    }
+
+    /// Wake waiters so they recheck every source
+    pub fn notify(&self) {
+        self.notifier().notify();
+    }
+
+    /// Wait until a source is ready or `timeout` passes
+    ///
+    /// Fills `ready` with the sources that have events, in token order, and
+    /// returns how many there are; 0 means the wait timed out. `None` waits
+    /// without limit and `Some(Duration::ZERO)` checks once without blocking.
+    pub fn wait(&self, ready: &mut Vec<ReadyEvent>, timeout: Option<Duration>) -> usize {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            let seen = *self.signal.generation.lock();
+            self.collect(ready);
+            if !ready.is_empty() {
+                return ready.len();
+            }
+
+            let mut generation = self.signal.generation.lock();
+            if *generation != seen {
+                continue;
+            }
+            match deadline {
+                Some(deadline) => {
+                    if Instant::now() >= deadline
+                        || self
+                            .signal
+                            .changed
+                            .wait_until(&mut generation, deadline)
+                            .timed_out()
+                    {
+                        drop(generation);
+                        self.collect(ready);
+                        return ready.len();
+                    }
+                }
+                None => self.signal.changed.wait(&mut generation),
+            }
+        }
+    }
+
+    fn collect(&self, ready: &mut Vec<ReadyEvent>) {
+        ready.clear();
+        for (&token, registration) in self.registrations.lock().iter_mut() {
+            let events = registration.poll();
+            if !events.is_empty() {
+                ready.push(ReadyEvent { token, events });
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Epoll {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Epoll")
+            .field("registrations", &self.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU8, Ordering};
+    use std::thread;
+
+    /// A source whose readiness the test sets directly
+    #[derive(Default)]
+    struct Flag(AtomicU8);
+
+    impl Flag {
+        fn set(&self, events: Events) {
+            self.0.store(events.0, Ordering::SeqCst);
+        }
+    }
+
+    impl Readiness for Flag {
+        fn readiness(&self) -> Events {
+            Events(self.0.load(Ordering::SeqCst))
+        }
+    }
+
+    fn poll(epoll: &Epoll) -> Vec<ReadyEvent> {
+        let mut ready = Vec::new();
+        epoll.wait(&mut ready, Some(Duration::ZERO));
+        ready
+    }
+
+    #[test]
+    fn test_events_set_operations() {
+        let both = Events::READ | Events::WRITE;
+        assert!(both.contains(Events::READ));
+        assert!(!both.contains(Events::ALL));
+        assert!(both.intersects(Events::WRITE | Events::ERROR));
+        assert_eq!(both & Events::WRITE, Events::WRITE);
+        assert_eq!(both.difference(Events::READ), Events::WRITE);
+        assert!(Events::NONE.is_empty());
+        assert_eq!(format!("{:?}", both), "Events(READ | WRITE)");
+        assert_eq!(format!("{:?}", Events::NONE), "Events(NONE)");
+    }
+
+    #[test]
+    fn test_register_modify_remove() {
+        let epoll = Epoll::new();
+        let source = Arc::new(Flag::default());
+        epoll
+            .add(1, source.clone(), Events::READ, Trigger::Level)
+            .unwrap();
+        assert_eq!(
+            epoll.add(1, source, Events::READ, Trigger::Level),
+            Err(EpollError::DuplicateToken(1))
+        );
+        assert_eq!(epoll.len(), 1);
+        assert_eq!(
+            epoll.modify(2, Events::WRITE, Trigger::Level),
+            Err(EpollError::UnknownToken(2))
+        );
+        epoll.remove(1).unwrap();
+        assert_eq!(epoll.remove(1), Err(EpollError::UnknownToken(1)));
+        assert!(epoll.is_empty());
+    }
+
+    #[test]
+    fn test_level_reports_while_ready() {
+        let epoll = Epoll::new();
+        let source = Arc::new(Flag::default());
+        epoll
+            .add(7, source.clone(), Events::READ, Trigger::Level)
+            .unwrap();
+        assert!(poll(&epoll).is_empty());
+
+        // WRITE is outside the interest; ERROR always counts
+        source.set(Events::READ | Events::WRITE);
+        let expected = vec![ReadyEvent {
+            token: 7,
+            events: Events::READ,
+        }];
+        assert_eq!(poll(&epoll), expected);
+        assert_eq!(poll(&epoll), expected);
+
+        source.set(Events::ERROR);
+        assert_eq!(poll(&epoll)[0].events, Events::ERROR);
+    }
+
+    #[test]
+    fn test_edge_reports_once_per_transition() {
+        let epoll = Epoll::new();
+        let source = Arc::new(Flag::default());
+        epoll
+            .add(
+                3,
+                source.clone(),
+                Events::READ | Events::WRITE,
+                Trigger::Edge,
+            )
+            .unwrap();
+
+        source.set(Events::WRITE);
+        assert_eq!(poll(&epoll)[0].events, Events::WRITE);
+        assert!(poll(&epoll).is_empty());
+
+        // Only the newly ready event reports
+        source.set(Events::READ | Events::WRITE);
+        assert_eq!(poll(&epoll)[0].events, Events::READ);
+
+        // Going unready rearms the edge
+        source.set(Events::READ);
+        assert!(poll(&epoll).is_empty());
+        source.set(Events::READ | Events::WRITE);
+        assert_eq!(poll(&epoll)[0].events, Events::WRITE);
+
+        // Modifying rearms everything
+        epoll.modify(3, Events::READ, Trigger::Edge).unwrap();
+        assert_eq!(poll(&epoll)[0].events, Events::READ);
+    }
+
+    #[test]
+    fn test_closure_sources_in_token_order() {
+        let epoll = Epoll::new();
+        epoll
+            .add(9, Arc::new(|| Events::READ), Events::READ, Trigger::Level)
+            .unwrap();
+        epoll
+            .add(2, Arc::new(|| Events::WRITE), Events::WRITE, Trigger::Level)
+            .unwrap();
+        let tokens: Vec<_> = poll(&epoll).iter().map(|event| event.token).collect();
+        assert_eq!(tokens, vec![2, 9]);
+    }
+
+    #[test]
+    fn test_wait_times_out() {
+        let epoll = Epoll::new();
+        epoll
+            .add(1, Arc::new(Flag::default()), Events::ALL, Trigger::Level)
+            .unwrap();
+        let mut ready = vec![ReadyEvent {
+            token: 0,
+            events: Events::READ,
+        }];
+        let start = Instant::now();
+        assert_eq!(epoll.wait(&mut ready, Some(Duration::from_millis(20))), 0);
+        assert!(start.elapsed() >= Duration::from_millis(20));
+        assert!(ready.is_empty());
+    }
+
+    #[test]
+    fn test_notify_wakes_waiter() {
+        let epoll = Arc::new(Epoll::new());
+        let source = Arc::new(Flag::default());
+        epoll
+            .add(5, source.clone(), Events::READ, Trigger::Edge)
+            .unwrap();
+
+        let notifier = epoll.notifier();
+        let setter = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            source.set(Events::READ);
+            notifier.notify();
+        });
+
+        let mut ready = Vec::new();
+        let start = Instant::now();
+        assert_eq!(epoll.wait(&mut ready, Some(Duration::from_secs(10))), 1);
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(ready[0].token, 5);
+        setter.join().unwrap();
+    }
+
+    #[cfg(feature = "protocol")]
+    #[test]
+    fn test_connection_readiness() {
+        use srt_protocol::{Connection, SeqNumber, SrtHandshake, SrtOptions};
+
+        let mut conn = Connection::new(
+            1,
+            "127.0.0.1:9000".parse().unwrap(),
+            "127.0.0.1:9001".parse().unwrap(),
+            SeqNumber::new(0),
+            120,
+        );
+        assert_eq!(conn.readiness(), Events::NONE);
+        let peer = SrtHandshake::new_request(
+            0,
+            2,
+            "127.0.0.1:9000".parse().unwrap(),
+            SrtOptions::default_capabilities(),
+            120,
+            120,
+        );
+        conn.process_handshake(peer).unwrap();
+        assert_eq!(conn.readiness(), Events::WRITE);
+        conn.close();
+        assert_eq!(conn.readiness(), Events::ERROR);
+    }
+}
@@ -2,12 +2,25 @@
 //!
 //! This crate provides network I/O and platform-specific abstractions,
 //! including UDP socket wrappers, event loops, and timing utilities.
+//!
+//! Unsafe code is confined to the audited `sys` module.
+#![deny(unsafe_code)]
 
+#[cfg(feature = "tokio")]
+pub mod async_socket;
+pub mod driver;
+pub mod epoll;
+pub mod multicast;
 pub mod socket;
+mod sys;
 pub mod time;
 
-// Future modules
-// pub mod epoll;
-
-pub use socket::{SocketError, SrtSocket};
+#[cfg(feature = "tokio")]
+pub use async_socket::{AsyncConnection, AsyncError, AsyncSrtSocket};
+pub use driver::{
+    ControlHandler, ControlPlane, ControlPlaneConfig, Datagram, PlaneReader, PlaneStats,
+};
+pub use epoll::{Epoll, EpollError, EpollNotifier, Events, Readiness, ReadyEvent, Trigger};
+pub use multicast::MulticastOptions;
+pub use socket::{KernelSocketStats, PacketDirection, PacketHook, SocketError, SrtSocket};
 pub use time::{RateLimiter, Timer, Timestamp};
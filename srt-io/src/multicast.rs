@@ -0,0 +1,199 @@
+//! Multicast UDP sockets
+//!
+//! Helpers for plain UDP inputs and outputs that ingest from or emit to
+//! multicast groups (e.g. MPEG-TS distribution in broadcast facilities).
+
+use crate::socket::SocketError;
+use socket2::{Domain, Protocol, Socket, Type};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+
+/// Multicast socket options
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MulticastOptions {
+    /// TTL (IPv4) or hop limit (IPv6) for outgoing multicast
+    pub ttl: u32,
+    /// Local interface address for joining and sending (IPv4)
+    pub interface: Option<Ipv4Addr>,
+    /// Interface index for joining and sending (IPv6, 0 = default)
+    pub interface_index: u32,
+    /// Deliver our own multicast output back to local listeners
+    pub loopback: bool,
+}
+
+impl Default for MulticastOptions {
+    fn default() -> Self {
+        MulticastOptions {
+            ttl: 1,
+            interface: None,
+            interface_index: 0,
+            loopback: true,
+        }
+    }
+}
+
+/// Check if an address is a multicast group
+pub fn is_multicast(addr: &SocketAddr) -> bool {
+    addr.ip().is_multicast()
+}
+
+/// Bind a UDP socket on the group's port and join the group
+///
+/// Address reuse is enabled so several receivers on the same host can
+/// listen to one group.
+pub fn bind_multicast(
+    group: SocketAddr,
+    opts: &MulticastOptions,
+) -> Result<UdpSocket, SocketError> {
+    if !is_multicast(&group) {
+        return Err(SocketError::InvalidAddress);
+    }
+
+    let (domain, bind_addr) = match group.ip() {
+        IpAddr::V4(_) => (
+            Domain::IPV4,
+            SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), group.port()),
+        ),
+        IpAddr::V6(_) => (
+            Domain::IPV6,
+            SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), group.port()),
+        ),
+    };
+
+    let socket = Socket::new(domain, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    socket.bind(&bind_addr.into())?;
+
+    let socket: UdpSocket = socket.into();
+    join(&socket, group.ip(), opts)?;
+    Ok(socket)
+}
+
+/// Join a multicast group
+pub fn join(socket: &UdpSocket, group: IpAddr, opts: &MulticastOptions) -> Result<(), SocketError> {
+    match group {
+        IpAddr::V4(group) => {
+            socket.join_multicast_v4(&group, &opts.interface.unwrap_or(Ipv4Addr::UNSPECIFIED))?
+        }
+        IpAddr::V6(group) => socket.join_multicast_v6(&group, opts.interface_index)?,
+    }
+    tracing::info!("Joined multicast group {}", group);
+    Ok(())
+}
+
+/// Leave a multicast group
+///
+/// Closing the socket also leaves any joined groups.
+pub fn leave(
+    socket: &UdpSocket,
+    group: IpAddr,
+    opts: &MulticastOptions,
+) -> Result<(), SocketError> {
+    match group {
+        IpAddr::V4(group) => {
+            socket.leave_multicast_v4(&group, &opts.interface.unwrap_or(Ipv4Addr::UNSPECIFIED))?
+        }
+        IpAddr::V6(group) => socket.leave_multicast_v6(&group, opts.interface_index)?,
+    }
+    tracing::info!("Left multicast group {}", group);
+    Ok(())
+}
+
+/// Create a UDP socket for sending to `dest`
+///
+/// If `dest` is a multicast group, the TTL, outgoing interface and
+/// loopback options are applied.
+pub fn bind_sender(dest: SocketAddr, opts: &MulticastOptions) -> Result<UdpSocket, SocketError> {
+    let (domain, bind_addr) = match dest {
+        SocketAddr::V4(_) => (
+            Domain::IPV4,
+            SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0),
+        ),
+        SocketAddr::V6(_) => (
+            Domain::IPV6,
+            SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), 0),
+        ),
+    };
+
+    let socket = Socket::new(domain, Type::DGRAM, Some(Protocol::UDP))?;
+    if is_multicast(&dest) {
+        if dest.is_ipv4() {
+            socket.set_multicast_ttl_v4(opts.ttl)?;
+            socket.set_multicast_loop_v4(opts.loopback)?;
+            if let Some(interface) = opts.interface {
+                socket.set_multicast_if_v4(&interface)?;
+            }
+        } else {
+            socket.set_multicast_hops_v6(opts.ttl)?;
+            socket.set_multicast_loop_v6(opts.loopback)?;
+            if opts.interface_index != 0 {
+                socket.set_multicast_if_v6(opts.interface_index)?;
+            }
+        }
+    }
+    socket.bind(&bind_addr.into())?;
+
+    Ok(socket.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_is_multicast() {
+        assert!(is_multicast(&"239.1.1.1:5000".parse().unwrap()));
+        assert!(is_multicast(&"[ff02::1]:5000".parse().unwrap()));
+        assert!(!is_multicast(&"127.0.0.1:5000".parse().unwrap()));
+        assert!(matches!(
+            bind_multicast(
+                "127.0.0.1:5000".parse().unwrap(),
+                &MulticastOptions::default()
+            ),
+            Err(SocketError::InvalidAddress)
+        ));
+    }
+
+    #[test]
+    fn test_sender_options() {
+        let opts = MulticastOptions {
+            ttl: 8,
+            ..Default::default()
+        };
+        let socket = bind_sender("239.255.0.1:5000".parse().unwrap(), &opts).unwrap();
+        assert_eq!(socket.multicast_ttl_v4().unwrap(), 8);
+
+        // Unicast destinations keep default options
+        let socket = bind_sender("127.0.0.1:5000".parse().unwrap(), &opts).unwrap();
+        assert!(socket.local_addr().unwrap().ip().is_unspecified());
+    }
+
+    #[test]
+    fn test_multicast_loopback() {
+        let group: SocketAddr = "239.255.42.99:0".parse().unwrap();
+        let opts = MulticastOptions {
+            interface: Some(Ipv4Addr::LOCALHOST),
+            ..Default::default()
+        };
+
+        // Multicast may be unavailable in sandboxed environments
+        let Ok(receiver) = bind_multicast(group, &opts) else {
+            return;
+        };
+        receiver
+            .set_read_timeout(Some(Duration::from_millis(500)))
+            .unwrap();
+        let dest = SocketAddr::new(group.ip(), receiver.local_addr().unwrap().port());
+
+        let sender = bind_sender(dest, &opts).unwrap();
+        if sender.send_to(b"hello", dest).is_err() {
+            return;
+        }
+
+        let mut buf = [0u8; 16];
+        if let Ok((n, _)) = receiver.recv_from(&mut buf) {
+            assert_eq!(&buf[..n], b"hello");
+        }
+        leave(&receiver, group.ip(), &opts).unwrap();
+    }
+}
@@ -2,9 +2,12 @@
 //!
 //! Provides cross-platform UDP socket abstraction with SRT-specific options.
 
+use crate::sys;
 use socket2::{Domain, Protocol, Socket, Type};
 use std::io::{self, ErrorKind};
 use std::net::{SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use thiserror::Error;
 
 /// Socket configuration errors
@@ -18,13 +21,83 @@ pub enum SocketError {
 
     #[error("Socket option not supported on this platform")]
     UnsupportedOption,
+
+    #[error("Connection refused by peer (ICMP port unreachable)")]
+    ConnectionRefused,
+
+    #[error("Peer host or network unreachable")]
+    HostUnreachable,
+}
+
+impl SocketError {
+    /// Check if this error reports the peer as unreachable
+    ///
+    /// These errors are only reported for connected sockets, where the kernel
+    /// can associate incoming ICMP errors with the socket.
+    pub fn is_peer_unreachable(&self) -> bool {
+        matches!(
+            self,
+            SocketError::ConnectionRefused | SocketError::HostUnreachable
+        )
+    }
+}
+
+/// OS error codes for an unreachable network or host
+#[cfg(unix)]
+const UNREACHABLE_ERRNOS: &[i32] = &[libc::ENETUNREACH, libc::EHOSTUNREACH];
+/// WSAENETUNREACH and WSAEHOSTUNREACH
+#[cfg(windows)]
+const UNREACHABLE_ERRNOS: &[i32] = &[10051, 10065];
+#[cfg(not(any(unix, windows)))]
+const UNREACHABLE_ERRNOS: &[i32] = &[];
+
+/// Map an I/O error, surfacing ICMP-reported errors as dedicated variants
+pub(crate) fn map_io_error(e: io::Error) -> SocketError {
+    if e.kind() == ErrorKind::ConnectionRefused {
+        return SocketError::ConnectionRefused;
+    }
+
+    match e.raw_os_error() {
+        Some(code) if UNREACHABLE_ERRNOS.contains(&code) => SocketError::HostUnreachable,
+        _ => SocketError::Io(e),
+    }
+}
+
+/// Kernel-level UDP socket statistics
+///
+/// Counters maintained by the operating system for this socket. A growing
+/// `drops` value means datagrams reached the host but were discarded because
+/// the socket receive queue was full, as opposed to being lost on the network.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct KernelSocketStats {
+    /// Bytes currently queued for transmission
+    pub tx_queue_bytes: u64,
+    /// Bytes currently queued for reception (not yet read by the application)
+    pub rx_queue_bytes: u64,
+    /// Datagrams dropped by the kernel for this socket
+    pub drops: u64,
+}
+
+/// Direction of a datagram passed to a packet hook
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketDirection {
+    Sent,
+    Received,
 }
 
+/// Hook called with every datagram a socket sends or receives, and the peer
+/// address
+pub type PacketHook = Arc<dyn Fn(PacketDirection, &[u8], SocketAddr) + Send + Sync>;
+
 /// SRT socket wrapper
 ///
 /// Wraps a UDP socket with SRT-specific configuration.
 pub struct SrtSocket {
     inner: Socket,
+    /// Whether `connect` succeeded, so sends need not ask the kernel
+    connected: AtomicBool,
+    /// Observer of sent and received datagrams (e.g. a packet tracer)
+    hook: Option<PacketHook>,
 }
 
 impl SrtSocket {
@@ -38,8 +111,11 @@ impl SrtSocket {
 
         let socket = Socket::new(domain, Type::DGRAM, Some(Protocol::UDP))?;
 
-        // Set socket options
-        socket.set_reuse_address(true)?;
+        // Set socket options; on Linux a reusable UDP socket can be handed
+        // an ephemeral port another one already has, so only for fixed ports
+        if addr.port() != 0 {
+            socket.set_reuse_address(true)?;
+        }
         // #[cfg(unix)]
         // socket.set_reuse_port(true)?;
 
@@ -49,7 +125,11 @@ impl SrtSocket {
         // Set non-blocking mode
         socket.set_nonblocking(true)?;
 
-        Ok(SrtSocket { inner: socket })
+        Ok(SrtSocket {
+            inner: socket,
+            connected: AtomicBool::new(false),
+            hook: None,
+        })
     }
 
     /// Create a new unbound SRT socket
@@ -59,7 +139,30 @@ impl SrtSocket {
 
         socket.set_nonblocking(true)?;
 
-        Ok(SrtSocket { inner: socket })
+        Ok(SrtSocket {
+            inner: socket,
+            connected: AtomicBool::new(false),
+            hook: None,
+        })
+    }
+
+    /// Install a hook that sees every datagram sent or received
+    ///
+    /// `None` removes it. Clones made with `try_clone` keep the hook.
+    pub fn set_packet_hook(&mut self, hook: Option<PacketHook>) {
+        self.hook = hook;
+    }
+
+    /// Pass a datagram to the hook, if any
+    fn observe(&self, direction: PacketDirection, buf: &[u8], peer: Option<SocketAddr>) {
+        let Some(hook) = &self.hook else {
+            return;
+        };
+        // Connected sockets: look the peer up only when someone is watching
+        let peer = peer.or_else(|| self.peer_addr().ok());
+        if let Some(peer) = peer {
+            hook(direction, buf, peer);
+        }
     }
 
     /// Set the send buffer size
@@ -92,15 +195,52 @@ impl SrtSocket {
             .ok_or(SocketError::InvalidAddress)
     }
 
+    /// Connect the socket to a remote address
+    ///
+    /// A connected socket only exchanges datagrams with `addr`, and the kernel
+    /// reports ICMP errors (e.g. port unreachable) for it. These surface as
+    /// [`SocketError::ConnectionRefused`] or [`SocketError::HostUnreachable`]
+    /// from subsequent send/receive calls.
+    pub fn connect(&self, addr: SocketAddr) -> Result<(), SocketError> {
+        self.inner.connect(&addr.into()).map_err(map_io_error)?;
+        self.connected.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Get the remote address of a connected socket
+    pub fn peer_addr(&self) -> Result<SocketAddr, SocketError> {
+        self.inner
+            .peer_addr()?
+            .as_socket()
+            .ok_or(SocketError::InvalidAddress)
+    }
+
+    /// Check if the socket is connected
+    ///
+    /// Remembered from `connect`, so it is cheap enough to ask per packet.
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+
     /// Send data to the given address
     ///
     /// Returns the number of bytes sent, or WouldBlock if the socket is not ready.
     pub fn send_to(&self, buf: &[u8], target: SocketAddr) -> Result<usize, SocketError> {
-        match self.inner.send_to(buf, &target.into()) {
-            Ok(n) => Ok(n),
-            Err(e) if e.kind() == ErrorKind::WouldBlock => Err(SocketError::Io(e)),
-            Err(e) => Err(SocketError::Io(e)),
-        }
+        let n = self
+            .inner
+            .send_to(buf, &target.into())
+            .map_err(map_io_error)?;
+        self.observe(PacketDirection::Sent, &buf[..n], Some(target));
+        Ok(n)
+    }
+
+    /// Send data on a connected socket
+    ///
+    /// Returns the number of bytes sent, or WouldBlock if the socket is not ready.
+    pub fn send(&self, buf: &[u8]) -> Result<usize, SocketError> {
+        let n = self.inner.send(buf).map_err(map_io_error)?;
+        self.observe(PacketDirection::Sent, &buf[..n], None);
+        Ok(n)
     }
 
     /// Receive data from the socket
@@ -108,17 +248,66 @@ impl SrtSocket {
     /// Returns the number of bytes received and the source address,
     /// or WouldBlock if the socket is not ready.
     pub fn recv_from(&self, buf: &mut [u8]) -> Result<(usize, SocketAddr), SocketError> {
-        // socket2 recv_from needs MaybeUninit, but we can use recv_from directly on the UdpSocket
-        // For now, use unsafe to transmute the buffer
-        use std::mem::MaybeUninit;
-        let uninit_buf = unsafe {
-            std::slice::from_raw_parts_mut(buf.as_mut_ptr() as *mut MaybeUninit<u8>, buf.len())
-        };
+        match sys::recv_from(&self.inner, buf) {
+            Ok((n, addr)) => {
+                let addr = addr.as_socket().ok_or(SocketError::InvalidAddress)?;
+                self.observe(PacketDirection::Received, &buf[..n], Some(addr));
+                Ok((n, addr))
+            }
+            Err(e) => Err(map_io_error(e)),
+        }
+    }
+
+    /// Receive data on a connected socket
+    ///
+    /// Returns the number of bytes received, or WouldBlock if the socket is not ready.
+    pub fn recv(&self, buf: &mut [u8]) -> Result<usize, SocketError> {
+        let n = sys::recv(&self.inner, buf).map_err(map_io_error)?;
+        self.observe(PacketDirection::Received, &buf[..n], None);
+        Ok(n)
+    }
 
-        match self.inner.recv_from(uninit_buf) {
-            Ok((n, addr)) => Ok((n, addr.as_socket().ok_or(SocketError::InvalidAddress)?)),
-            Err(e) if e.kind() == ErrorKind::WouldBlock => Err(SocketError::Io(e)),
-            Err(e) => Err(SocketError::Io(e)),
+    /// Get kernel-level statistics for this socket
+    ///
+    /// On Linux the counters are read from `/proc/net/udp` (or `udp6`), matched
+    /// against this socket's inode. Other platforms return
+    /// [`SocketError::UnsupportedOption`].
+    pub fn kernel_stats(&self) -> Result<KernelSocketStats, SocketError> {
+        #[cfg(target_os = "linux")]
+        {
+            use std::os::unix::io::AsRawFd;
+
+            let link = std::fs::read_link(format!("/proc/self/fd/{}", self.inner.as_raw_fd()))?;
+            let inode = link
+                .to_str()
+                .and_then(|l| l.strip_prefix("socket:["))
+                .and_then(|l| l.strip_suffix(']'))
+                .and_then(|l| l.parse::<u64>().ok())
+                .ok_or(SocketError::UnsupportedOption)?;
+
+            for table in ["/proc/net/udp", "/proc/net/udp6"] {
+                let contents = match std::fs::read_to_string(table) {
+                    Ok(contents) => contents,
+                    Err(_) => continue,
+                };
+                if let Some(stats) = contents
+                    .lines()
+                    .skip(1)
+                    .find_map(|line| parse_proc_net_udp_line(line, inode))
+                {
+                    return Ok(stats);
+                }
+            }
+
+            Err(SocketError::Io(io::Error::new(
+                ErrorKind::NotFound,
+                "socket not found in /proc/net/udp",
+            )))
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        {
+            Err(SocketError::UnsupportedOption)
         }
     }
 
@@ -126,6 +315,8 @@ impl SrtSocket {
     pub fn try_clone(&self) -> Result<Self, SocketError> {
         Ok(SrtSocket {
             inner: self.inner.try_clone()?,
+            connected: AtomicBool::new(self.is_connected()),
+            hook: self.hook.clone(),
         })
     }
 
@@ -138,9 +329,35 @@ impl SrtSocket {
     pub fn into_udp_socket(self) -> UdpSocket {
         self.inner.into()
     }
+
+    /// Split into a standard UDP socket and the packet hook
+    #[cfg_attr(not(feature = "tokio"), allow(dead_code))]
+    pub(crate) fn into_parts(self) -> (UdpSocket, Option<PacketHook>) {
+        (self.inner.into(), self.hook)
+    }
+}
+
+/// Parse a single `/proc/net/udp` entry, returning its stats if the inode matches
+///
+/// Entry layout: `sl local rem st tx_queue:rx_queue tr:tm retrnsmt uid timeout inode ref pointer drops`
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_proc_net_udp_line(line: &str, inode: u64) -> Option<KernelSocketStats> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    if fields.len() < 13 || fields[9].parse::<u64>().ok()? != inode {
+        return None;
+    }
+
+    let (tx_queue, rx_queue) = fields[4].split_once(':')?;
+
+    Some(KernelSocketStats {
+        tx_queue_bytes: u64::from_str_radix(tx_queue, 16).ok()?,
+        rx_queue_bytes: u64::from_str_radix(rx_queue, 16).ok()?,
+        drops: fields[12].parse().ok()?,
+    })
 }
 
 /// Socket poll result
+#[deprecated(since = "0.1.4", note = "use `epoll::Events`")]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PollEvent {
     /// Socket is readable
@@ -205,6 +422,108 @@ mod tests {
         panic!("Failed to receive data");
     }
 
+    #[test]
+    fn test_packet_hook() {
+        use parking_lot::Mutex;
+
+        let mut sender = SrtSocket::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let receiver = SrtSocket::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let receiver_addr = receiver.local_addr().unwrap();
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let log = seen.clone();
+        sender.set_packet_hook(Some(Arc::new(move |direction, buf, peer| {
+            log.lock().push((direction, buf.to_vec(), peer));
+        })));
+        sender.send_to(b"traced", receiver_addr).unwrap();
+        sender.connect(receiver_addr).unwrap();
+        sender.send(b"connected").unwrap();
+
+        assert_eq!(
+            *seen.lock(),
+            vec![
+                (PacketDirection::Sent, b"traced".to_vec(), receiver_addr),
+                (PacketDirection::Sent, b"connected".to_vec(), receiver_addr),
+            ]
+        );
+
+        // Clones keep the hook; removing it stops the calls
+        let clone = sender.try_clone().unwrap();
+        sender.set_packet_hook(None);
+        sender.send(b"quiet").unwrap();
+        clone.send(b"clone").unwrap();
+        assert_eq!(seen.lock().len(), 3);
+    }
+
+    #[test]
+    fn test_socket_connect() {
+        let socket = SrtSocket::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let peer = SrtSocket::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let peer_addr = peer.local_addr().unwrap();
+
+        assert!(!socket.is_connected());
+        socket.connect(peer_addr).unwrap();
+        assert!(socket.is_connected());
+        assert_eq!(socket.peer_addr().unwrap(), peer_addr);
+
+        socket.send(b"connected").unwrap();
+        let mut buf = [0u8; 64];
+        for _ in 0..10 {
+            if let Ok((n, from)) = peer.recv_from(&mut buf) {
+                assert_eq!(&buf[..n], b"connected");
+                assert_eq!(from, socket.local_addr().unwrap());
+                return;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        panic!("Failed to receive data");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_connected_socket_reports_refused() {
+        // Find a port with nothing listening on it
+        let closed_addr = {
+            let tmp = SrtSocket::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+            tmp.local_addr().unwrap()
+        };
+
+        let socket = SrtSocket::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        socket.connect(closed_addr).unwrap();
+
+        // The ICMP error from the first datagram is reported on a later call
+        let _ = socket.send(b"probe");
+        for _ in 0..10 {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            if let Err(e) = socket.send(b"probe") {
+                assert!(e.is_peer_unreachable(), "unexpected error: {}", e);
+                return;
+            }
+        }
+        panic!("ICMP port unreachable was not reported");
+    }
+
+    #[test]
+    fn test_parse_proc_net_udp_line() {
+        let line = "  123: 0100007F:1F90 00000000:0000 07 00000010:00000200 00:00000000 00000000  1000        0 4242 2 0000000000000000 7";
+
+        let stats = parse_proc_net_udp_line(line, 4242).unwrap();
+        assert_eq!(stats.tx_queue_bytes, 0x10);
+        assert_eq!(stats.rx_queue_bytes, 0x200);
+        assert_eq!(stats.drops, 7);
+
+        // Different inode should not match
+        assert!(parse_proc_net_udp_line(line, 4243).is_none());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_socket_kernel_stats() {
+        let socket = SrtSocket::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let stats = socket.kernel_stats().unwrap();
+        assert_eq!(stats.drops, 0);
+    }
+
     #[test]
     fn test_socket_ipv6() {
         // May fail on systems without IPv6
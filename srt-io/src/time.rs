@@ -145,11 +145,13 @@ impl Timer {
 /// Rate limiter using token bucket algorithm
 ///
 /// Used for pacing packet transmission according to congestion control.
+/// Tokens accumulate fractionally from the monotonic clock, so output stays
+/// smooth at high rates instead of releasing whole-byte bursts per tick.
 pub struct RateLimiter {
-    /// Maximum tokens (burst size)
-    capacity: u64,
-    /// Current token count
-    tokens: u64,
+    /// Maximum tokens (burst size, bytes)
+    capacity: f64,
+    /// Current token count (bytes, fractional)
+    tokens: f64,
     /// Tokens added per microsecond
     rate: f64,
     /// Last update time
@@ -163,38 +165,71 @@ impl RateLimiter {
     /// * `rate_bps` - Rate in bits per second
     /// * `burst_bytes` - Maximum burst size in bytes
     pub fn new(rate_bps: u64, burst_bytes: u64) -> Self {
-        let rate_bytes_per_us = (rate_bps as f64) / 8.0 / 1_000_000.0;
-
         RateLimiter {
-            capacity: burst_bytes,
-            tokens: burst_bytes,
-            rate: rate_bytes_per_us,
+            capacity: burst_bytes as f64,
+            tokens: burst_bytes as f64,
+            rate: Self::bytes_per_us(rate_bps),
             last_update: Timestamp::now(),
         }
     }
 
+    /// Convert bits per second to bytes per microsecond
+    fn bytes_per_us(rate_bps: u64) -> f64 {
+        (rate_bps as f64) / 8.0 / 1_000_000.0
+    }
+
     /// Update the rate
+    ///
+    /// Tokens accumulated at the previous rate are kept, so the congestion
+    /// controller can adjust the rate at any time without a burst or stall.
     pub fn set_rate(&mut self, rate_bps: u64) {
+        self.set_rate_at(rate_bps, Timestamp::now());
+    }
+
+    /// Update the rate, integrating tokens at the old rate up to `now`
+    fn set_rate_at(&mut self, rate_bps: u64, now: Timestamp) {
+        self.refill_at(now);
+        self.rate = Self::bytes_per_us(rate_bps);
+    }
+
+    /// Get the current rate in bits per second
+    pub fn rate_bps(&self) -> u64 {
+        (self.rate * 8.0 * 1_000_000.0).round() as u64
+    }
+
+    /// Update the maximum burst size
+    pub fn set_burst(&mut self, burst_bytes: u64) {
         self.refill();
-        self.rate = (rate_bps as f64) / 8.0 / 1_000_000.0;
+        self.capacity = burst_bytes as f64;
+        self.tokens = self.tokens.min(self.capacity);
+    }
+
+    /// Get the currently available tokens (bytes, fractional)
+    pub fn available(&mut self) -> f64 {
+        self.refill();
+        self.tokens
     }
 
     /// Refill tokens based on elapsed time
     fn refill(&mut self) {
-        let now = Timestamp::now();
-        let elapsed_us = now.as_micros_since(self.last_update) as f64;
-        let new_tokens = (elapsed_us * self.rate) as u64;
+        self.refill_at(Timestamp::now());
+    }
 
-        if new_tokens > 0 {
-            self.tokens = (self.tokens + new_tokens).min(self.capacity);
-            self.last_update = now;
+    /// Refill tokens for the time elapsed up to `now`
+    fn refill_at(&mut self, now: Timestamp) {
+        if now <= self.last_update {
+            return;
         }
+
+        let elapsed_us = now.duration_since(self.last_update).as_secs_f64() * 1_000_000.0;
+        self.tokens = (self.tokens + elapsed_us * self.rate).min(self.capacity);
+        self.last_update = now;
     }
 
     /// Check if we can send `bytes` worth of data
     pub fn check(&mut self, bytes: usize) -> bool {
         self.refill();
-        self.tokens >= bytes as u64
+        self.tokens >= bytes as f64
     }
 
     /// Consume tokens for sending `bytes` worth of data
@@ -202,8 +237,8 @@ impl RateLimiter {
     /// Returns true if successful, false if insufficient tokens
     pub fn consume(&mut self, bytes: usize) -> bool {
         self.refill();
-        if self.tokens >= bytes as u64 {
-            self.tokens -= bytes as u64;
+        if self.tokens >= bytes as f64 {
+            self.tokens -= bytes as f64;
             true
         } else {
             false
@@ -211,16 +246,20 @@ impl RateLimiter {
     }
 
     /// Get time to wait before `bytes` will be available
+    ///
+    /// Returns `Duration::MAX` if the rate is zero.
     pub fn time_to_available(&mut self, bytes: usize) -> Duration {
         self.refill();
 
-        if self.tokens >= bytes as u64 {
+        let needed = bytes as f64 - self.tokens;
+        if needed <= 0.0 {
             return Duration::ZERO;
         }
+        if self.rate <= 0.0 {
+            return Duration::MAX;
+        }
 
-        let needed = (bytes as u64) - self.tokens;
-        let micros = (needed as f64 / self.rate).ceil() as u64;
-        Duration::from_micros(micros)
+        Duration::from_secs_f64(needed / self.rate / 1_000_000.0)
     }
 }
 
@@ -310,4 +349,41 @@ mod tests {
         assert!(wait_time > Duration::ZERO);
         assert!(wait_time <= Duration::from_millis(1000)); // Should be around 800ms
     }
+
+    #[test]
+    fn test_rate_limiter_fractional_tokens() {
+        // 1 Mbps = 0.125 bytes per microsecond
+        let mut limiter = RateLimiter::new(1_000_000, 1500);
+        let start = limiter.last_update;
+        limiter.tokens = 0.0;
+
+        // 4 microseconds yield half a byte, which must not be lost
+        for i in 1..=4 {
+            limiter.refill_at(start + Duration::from_micros(i * 4));
+        }
+        assert!((limiter.tokens - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_rate_limiter_set_rate_keeps_tokens() {
+        let mut limiter = RateLimiter::new(8_000_000, 10_000);
+        let start = limiter.last_update;
+        limiter.tokens = 0.0;
+
+        // 1000us at 1 byte/us
+        limiter.set_rate_at(16_000_000, start + Duration::from_micros(1000));
+        assert!((limiter.tokens - 1000.0).abs() < 1e-6);
+
+        // Next 1000us at 2 bytes/us accumulate on top
+        limiter.refill_at(start + Duration::from_micros(2000));
+        assert!((limiter.tokens - 3000.0).abs() < 1e-6);
+        assert_eq!(limiter.rate_bps(), 16_000_000);
+    }
+
+    #[test]
+    fn test_rate_limiter_zero_rate() {
+        let mut limiter = RateLimiter::new(0, 100);
+        assert!(limiter.consume(100));
+        assert_eq!(limiter.time_to_available(1), Duration::MAX);
+    }
 }
@@ -1,6 +1,6 @@
 use bytes::Bytes;
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use srt_protocol::packet::{ControlPacket, ControlType, DataPacket, MsgNumber};
+use srt_protocol::packet::{ControlPacket, ControlType, DataPacket, MsgNumber, Packet};
 use srt_protocol::sequence::SeqNumber;
 
 fn bench_data_packet_serialize(c: &mut Criterion) {
@@ -34,6 +34,31 @@ fn bench_data_packet_deserialize(c: &mut Criterion) {
     });
 }
 
+/// Copying parse from a slice against the zero-copy parse of owned bytes
+fn bench_packet_parse(c: &mut Criterion) {
+    let seq = SeqNumber::new(1000);
+    let msg = MsgNumber::new(100);
+    let payload = Bytes::from(vec![0u8; 1316]);
+
+    let packet = DataPacket::new(seq, msg, 5000, 9999, payload);
+    let bytes = packet.to_bytes().freeze();
+
+    let mut group = c.benchmark_group("packet_parse");
+    group.bench_function("from_bytes", |b| {
+        b.iter(|| {
+            let packet = Packet::from_bytes(black_box(&bytes)).unwrap();
+            black_box(packet);
+        });
+    });
+    group.bench_function("parse", |b| {
+        b.iter(|| {
+            let packet = Packet::parse(black_box(bytes.clone())).unwrap();
+            black_box(packet);
+        });
+    });
+    group.finish();
+}
+
 fn bench_control_packet_serialize(c: &mut Criterion) {
     let control_info = Bytes::from(vec![0u8; 100]);
     let packet = ControlPacket::new(ControlType::Ack, 0x1234, 5000, 10000, 9999, control_info);
@@ -101,6 +126,7 @@ criterion_group!(
     benches,
     bench_data_packet_serialize,
     bench_data_packet_deserialize,
+    bench_packet_parse,
     bench_control_packet_serialize,
     bench_seq_number_ops,
     bench_msg_number_encode_decode
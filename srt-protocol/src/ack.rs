@@ -1,14 +1,19 @@
-//! ACK and NAK packet generation
+//! ACK, NAK and DropReq packet generation
 //!
 //! Implements the generation of ACK (acknowledgment) and NAK (negative acknowledgment)
-//! control packets for reliable data transfer.
+//! control packets for reliable data transfer, and the DropReq a sender
+//! uses to tell the receiver it has given up on a range.
 
 use crate::loss::LossRange;
 use crate::packet::{ControlPacket, ControlType};
 use crate::sequence::SeqNumber;
 use bytes::{BufMut, Bytes, BytesMut};
+use std::collections::VecDeque;
 use std::time::{Duration, Instant};
 
+/// Packets received between ACKs unless configured otherwise
+pub const DEFAULT_ACK_PACKETS: u32 = 64;
+
 /// ACK packet information
 #[derive(Debug, Clone)]
 pub struct AckInfo {
@@ -153,9 +158,50 @@ impl NakInfo {
     }
 }
 
+/// DropReq packet information
+///
+/// Sent when the sender has discarded packets it was asked to retransmit;
+/// the receiver should stop waiting for them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DropReqInfo {
+    /// Dropped packet range
+    pub range: LossRange,
+}
+
+impl DropReqInfo {
+    /// Create a new DropReq info
+    pub fn new(range: LossRange) -> Self {
+        DropReqInfo { range }
+    }
+
+    /// Serialize DropReq info to control packet data
+    pub fn to_bytes(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(8);
+        buf.put_u32(self.range.start.as_raw());
+        buf.put_u32(self.range.end.as_raw());
+        buf.freeze()
+    }
+
+    /// Parse DropReq info from bytes
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < 8 {
+            return None;
+        }
+
+        let mut buf = bytes;
+        use bytes::Buf;
+
+        let start = SeqNumber::new_unchecked(buf.get_u32() & 0x7FFF_FFFF);
+        let end = SeqNumber::new_unchecked(buf.get_u32() & 0x7FFF_FFFF);
+        Some(DropReqInfo::new(LossRange::new(start, end)))
+    }
+}
+
 /// ACK generator
 ///
-/// Generates periodic ACK packets based on received data.
+/// Generates ACK packets on a timer, and after a volume of received data:
+/// at high bitrates the timer alone lets too much data go unacknowledged
+/// between ACKs for the sender's flow control.
 pub struct AckGenerator {
     /// Last ACK sequence number sent
     last_ack_seq: SeqNumber,
@@ -165,6 +211,14 @@ pub struct AckGenerator {
     ack_interval: Duration,
     /// ACK sequence number (increments with each ACK sent)
     ack_number: u32,
+    /// Packets received before an ACK is due (0 = no packet trigger)
+    ack_every_packets: u32,
+    /// Bytes received before an ACK is due (0 = no byte trigger)
+    ack_every_bytes: u64,
+    /// Packets received since the last ACK
+    packets_since_ack: u32,
+    /// Payload bytes received since the last ACK
+    bytes_since_ack: u64,
 }
 
 impl AckGenerator {
@@ -175,24 +229,67 @@ impl AckGenerator {
             last_ack_time: Instant::now(),
             ack_interval,
             ack_number: 0,
+            ack_every_packets: DEFAULT_ACK_PACKETS,
+            ack_every_bytes: 0,
+            packets_since_ack: 0,
+            bytes_since_ack: 0,
         }
     }
 
+    /// Get the ACK timer interval
+    pub fn ack_interval(&self) -> Duration {
+        self.ack_interval
+    }
+
+    /// Set the ACK timer interval
+    pub fn set_ack_interval(&mut self, interval: Duration) {
+        self.ack_interval = interval;
+    }
+
+    /// Get the packets and bytes received before an ACK is due (0 = off)
+    pub fn volume_trigger(&self) -> (u32, u64) {
+        (self.ack_every_packets, self.ack_every_bytes)
+    }
+
+    /// Set the packets and bytes received before an ACK is due
+    ///
+    /// Either trigger fires independently of the timer; 0 disables it.
+    pub fn set_volume_trigger(&mut self, packets: u32, bytes: u64) {
+        self.ack_every_packets = packets;
+        self.ack_every_bytes = bytes;
+    }
+
+    /// Record a received data packet with `bytes` of payload
+    pub fn on_data(&mut self, bytes: usize) {
+        self.packets_since_ack = self.packets_since_ack.saturating_add(1);
+        self.bytes_since_ack = self.bytes_since_ack.saturating_add(bytes as u64);
+    }
+
+    /// Check whether enough data arrived since the last ACK to send one
+    pub fn volume_reached(&self) -> bool {
+        (self.ack_every_packets > 0 && self.packets_since_ack >= self.ack_every_packets)
+            || (self.ack_every_bytes > 0 && self.bytes_since_ack >= self.ack_every_bytes)
+    }
+
     /// Check if ACK should be sent
     pub fn should_send_ack(&self, current_seq: SeqNumber) -> bool {
         // Send ACK if:
         // 1. Enough time has passed since last ACK
         // 2. OR sequence number has advanced significantly
+        // 3. OR enough data has been received
         let time_elapsed = self.last_ack_time.elapsed() >= self.ack_interval;
-        let seq_advanced = current_seq.distance_to(self.last_ack_seq).abs() >= 64;
+        let seq_advanced = self.ack_every_packets > 0
+            && current_seq.distance_to(self.last_ack_seq).unsigned_abs() >= self.ack_every_packets;
 
-        time_elapsed || seq_advanced
+        time_elapsed || seq_advanced || self.volume_reached()
     }
 
     /// Generate an ACK packet
     pub fn generate_ack(&mut self, ack_info: AckInfo, dest_socket_id: u32) -> ControlPacket {
         self.last_ack_seq = ack_info.ack_seq;
         self.last_ack_time = Instant::now();
+        self.packets_since_ack = 0;
+        self.bytes_since_ack = 0;
 
         let ack_data = ack_info.to_bytes();
 
@@ -308,6 +405,20 @@ impl RttEstimator {
         self.sample_count += 1;
     }
 
+    /// Seed the estimator with a measured RTT, e.g. from the handshake
+    ///
+    /// Replaces the 100ms default; ignored once samples have been taken.
+    pub fn seed(&mut self, rtt_us: u32) {
+        if self.sample_count == 0 && rtt_us > 0 {
+            self.update(rtt_us);
+        }
+    }
+
+    /// Get the number of samples taken (including a seed)
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
     /// Get smoothed RTT in microseconds
     pub fn srtt(&self) -> u32 {
         self.srtt as u32
@@ -333,6 +444,80 @@ impl Default for RttEstimator {
     }
 }
 
+/// Arrival intervals kept for the arrival rate (as libsrt's 16)
+const ARRIVAL_WINDOW: usize = 16;
+
+/// Arrival rate and jitter estimator
+///
+/// Measures the intervals between arriving data packets for the arrival
+/// and receive rates reported in ACKs, and the interarrival jitter of
+/// RFC 3550: how far arrival spacing strays from the sender's timestamp
+/// spacing. Feed it original packets only; retransmissions arrive out of
+/// step with the stream and would read as bursts and jitter.
+#[derive(Debug, Clone, Default)]
+pub struct ArrivalEstimator {
+    /// Arrival time and sender timestamp of the last packet
+    last: Option<(Instant, u32)>,
+    /// Recent intervals between arrivals (microseconds) and the payload
+    /// size of the later packet
+    intervals: VecDeque<(u64, usize)>,
+    /// Smoothed interarrival jitter (microseconds)
+    jitter_us: f64,
+}
+
+impl ArrivalEstimator {
+    /// Create a new arrival estimator
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a data packet that arrived at `now` with sender timestamp
+    /// `timestamp` and `bytes` of payload
+    pub fn on_packet(&mut self, now: Instant, timestamp: u32, bytes: usize) {
+        if let Some((last_at, last_ts)) = self.last {
+            let interval = now.saturating_duration_since(last_at).as_micros() as u64;
+            if self.intervals.len() == ARRIVAL_WINDOW {
+                self.intervals.pop_front();
+            }
+            self.intervals.push_back((interval, bytes));
+
+            let spacing = timestamp.wrapping_sub(last_ts) as i32 as f64;
+            let deviation = (interval as f64 - spacing).abs();
+            self.jitter_us += (deviation - self.jitter_us) / 16.0;
+        }
+        self.last = Some((now, timestamp));
+    }
+
+    /// Get the median interval between arrivals (microseconds)
+    fn median_interval(&self) -> Option<u64> {
+        let mut intervals: Vec<_> = self.intervals.iter().map(|(us, _)| *us).collect();
+        intervals.sort_unstable();
+        intervals.get(intervals.len() / 2).copied()
+    }
+
+    /// Get the packet arrival rate (packets per second, 0 = unknown)
+    pub fn packet_arrival_rate(&self) -> u32 {
+        self.median_interval()
+            .and_then(|median| 1_000_000u64.checked_div(median))
+            .map_or(0, |rate| rate.min(u32::MAX as u64) as u32)
+    }
+
+    /// Get the receive rate (bytes per second, 0 = unknown)
+    pub fn receive_rate_bps(&self) -> u64 {
+        if self.intervals.is_empty() {
+            return 0;
+        }
+        let bytes: usize = self.intervals.iter().map(|(_, bytes)| bytes).sum();
+        let average = bytes as u64 / self.intervals.len() as u64;
+        self.packet_arrival_rate() as u64 * average
+    }
+
+    /// Get the interarrival jitter (microseconds)
+    pub fn jitter_us(&self) -> u32 {
+        self.jitter_us as u32
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -378,6 +563,15 @@ mod tests {
         assert_eq!(decoded.loss_ranges[0].end, SeqNumber::new(105));
     }
 
+    #[test]
+    fn test_drop_req_info() {
+        let drop = DropReqInfo::new(LossRange::new(SeqNumber::new(100), SeqNumber::new(105)));
+
+        let decoded = DropReqInfo::from_bytes(&drop.to_bytes()).unwrap();
+        assert_eq!(decoded, drop);
+        assert!(DropReqInfo::from_bytes(&[0; 4]).is_none());
+    }
+
     #[test]
     fn test_ack_generator() {
         let mut gen = AckGenerator::new(Duration::from_millis(10));
@@ -391,6 +585,37 @@ mod tests {
         assert!(!gen.should_send_ack(SeqNumber::new(101)));
     }
 
+    #[test]
+    fn test_ack_generator_volume_trigger() {
+        let mut gen = AckGenerator::new(Duration::from_secs(60));
+        gen.set_volume_trigger(0, 10_000);
+        gen.generate_ack(AckInfo::new(SeqNumber::new(0)), 1);
+
+        // Retransmissions and reordering leave the sequence behind the volume
+        for _ in 0..7 {
+            gen.on_data(1316);
+            assert!(!gen.should_send_ack(SeqNumber::new(1)));
+        }
+        gen.on_data(1316);
+        assert!(gen.volume_reached());
+        assert!(gen.should_send_ack(SeqNumber::new(1)));
+
+        gen.generate_ack(AckInfo::new(SeqNumber::new(8)), 1);
+        assert!(!gen.volume_reached());
+
+        gen.set_volume_trigger(4, 0);
+        for _ in 0..4 {
+            assert!(!gen.volume_reached());
+            gen.on_data(100);
+        }
+        assert!(gen.should_send_ack(SeqNumber::new(8)));
+        assert_eq!(gen.volume_trigger(), (4, 0));
+
+        // Both triggers off leaves only the timer
+        gen.set_volume_trigger(0, 0);
+        assert!(!gen.should_send_ack(SeqNumber::new(5000)));
+    }
+
     #[test]
     fn test_nak_generator() {
         let mut gen = NakGenerator::new(Duration::from_millis(10));
@@ -421,4 +646,44 @@ mod tests {
         let rto = estimator.rto();
         assert!(rto > Duration::from_millis(100));
     }
+
+    #[test]
+    fn test_rtt_seed() {
+        let mut estimator = RttEstimator::new();
+        estimator.seed(20_000);
+        assert_eq!(estimator.srtt(), 20_000);
+        assert_eq!(estimator.rtt_var(), 10_000);
+        assert_eq!(estimator.rto(), Duration::from_millis(60));
+
+        // Only the first seed counts; later samples smooth as usual
+        estimator.seed(500_000);
+        assert_eq!(estimator.srtt(), 20_000);
+        estimator.update(28_000);
+        assert_eq!(estimator.srtt(), 21_000);
+        assert_eq!(estimator.sample_count(), 2);
+    }
+
+    #[test]
+    fn test_arrival_estimator() {
+        let mut estimator = ArrivalEstimator::new();
+        assert_eq!(estimator.packet_arrival_rate(), 0);
+        assert_eq!(estimator.receive_rate_bps(), 0);
+
+        // 1000 packets per second, arriving as spaced as they were sent
+        let base = Instant::now();
+        for i in 0..20u32 {
+            estimator.on_packet(base + Duration::from_millis(i as u64), i * 1000, 1000);
+        }
+        assert_eq!(estimator.packet_arrival_rate(), 1000);
+        assert_eq!(estimator.receive_rate_bps(), 1_000_000);
+        assert_eq!(estimator.jitter_us(), 0);
+
+        // Arrivals alternating 0.5 ms early and late add jitter
+        for i in 20..40u32 {
+            let skew = if i % 2 == 0 { 500 } else { 0 };
+            let at = base + Duration::from_micros(i as u64 * 1000 + skew);
+            estimator.on_packet(at, i * 1000, 1000);
+        }
+        assert!(estimator.jitter_us() > 300);
+    }
 }
@@ -3,13 +3,29 @@
 //! SRT uses circular buffers indexed by sequence numbers for efficient
 //! packet storage and retrieval.
 
-use crate::packet::DataPacket;
-use crate::sequence::SeqNumber;
-use bytes::Bytes;
+use crate::loss::{DroppedRanges, LossRange};
+use crate::packet::{max_payload_for_mss, DataPacket, PacketBoundary};
+use crate::sequence::{SeqNumber, MAX_SEQ_NUMBER};
+use bytes::{Bytes, BytesMut};
 use std::collections::VecDeque;
 use std::time::{Duration, Instant};
 use thiserror::Error;
 
+/// Default send and receive buffer size in packets
+pub const DEFAULT_BUFFER_PACKETS: usize = 8192;
+
+/// Minimum send and receive buffer size in packets
+pub const MIN_BUFFER_PACKETS: usize = 32;
+
+/// Maximum send and receive buffer size in packets
+pub const MAX_BUFFER_PACKETS: usize = 1 << 20;
+
+/// Round-trip allowance when sizing buffers from the latency
+const SIZING_RTT_ALLOWANCE: Duration = Duration::from_millis(100);
+
+/// Margin for bitrate peaks when sizing buffers
+const SIZING_MARGIN: f64 = 1.25;
+
 /// Buffer errors
 #[derive(Error, Debug)]
 pub enum BufferError {
@@ -26,6 +42,42 @@ pub enum BufferError {
     InvalidMessage,
 }
 
+/// Buffer capacities (packets) for a stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BufferSizing {
+    /// Send buffer capacity
+    pub send_capacity: usize,
+    /// Receive buffer capacity
+    pub recv_capacity: usize,
+    /// Bonding alignment buffer capacity
+    pub alignment_capacity: usize,
+}
+
+impl BufferSizing {
+    /// Size buffers to hold `latency` worth of a stream at `bitrate_bps`
+    ///
+    /// The receiver holds a packet for up to the latency, plus a round trip
+    /// for its retransmission; the alignment buffer covers the same window
+    /// across paths. The sender keeps each packet until it is acknowledged,
+    /// another round trip later. A quarter is added for bitrate peaks, and
+    /// each capacity is clamped to the valid buffer range.
+    pub fn for_stream(bitrate_bps: u64, latency: Duration, mss: usize) -> Self {
+        let payload = max_payload_for_mss(mss).max(1);
+        let packets_per_sec = bitrate_bps as f64 / 8.0 / payload as f64;
+        let packets = |window: Duration| {
+            let packets = (packets_per_sec * window.as_secs_f64() * SIZING_MARGIN).ceil();
+            (packets as usize).clamp(MIN_BUFFER_PACKETS, MAX_BUFFER_PACKETS)
+        };
+
+        let recv_window = latency + SIZING_RTT_ALLOWANCE;
+        BufferSizing {
+            send_capacity: packets(recv_window + SIZING_RTT_ALLOWANCE),
+            recv_capacity: packets(recv_window),
+            alignment_capacity: packets(recv_window),
+        }
+    }
+}
+
 /// Stored packet with metadata
 #[derive(Clone)]
 struct StoredPacket {
@@ -59,6 +111,16 @@ pub struct SendBuffer {
     oldest_in_buffer: SeqNumber,
     /// Time-to-live for packets (packets older than this are dropped)
     ttl: Duration,
+    /// Unacknowledged packets dropped after their TTL
+    expired: u64,
+    /// Ranges of unacknowledged packets dropped since last taken
+    expired_ranges: Vec<LossRange>,
+    /// Payload bytes held in the buffer
+    bytes: usize,
+    /// Smoothed rate at which ACKs drain the buffer (bytes per second)
+    drain_rate: Option<f64>,
+    /// Time of the last flush, for measuring the drain rate
+    last_flush: Option<Instant>,
 }
 
 impl SendBuffer {
@@ -80,6 +142,11 @@ impl SendBuffer {
             oldest_unacked: SeqNumber::new(0),
             oldest_in_buffer: SeqNumber::new(0),
             ttl,
+            expired: 0,
+            expired_ranges: Vec::new(),
+            bytes: 0,
+            drain_rate: None,
+            last_flush: None,
         }
     }
 
@@ -89,6 +156,37 @@ impl SendBuffer {
         (seq.as_raw() as usize) & self.mask
     }
 
+    /// Change the capacity, keeping the buffered packets
+    ///
+    /// `capacity` is rounded up to a power of two. Fails with
+    /// `BufferError::Full`, leaving the buffer unchanged, if the packets
+    /// held would not fit.
+    pub fn resize(&mut self, capacity: usize) -> Result<(), BufferError> {
+        let capacity = capacity.next_power_of_two();
+        let held = self.oldest_in_buffer.distance_to(self.next_seq).max(0) as usize;
+        if held > capacity {
+            return Err(BufferError::Full);
+        }
+
+        let mut buffer = vec![None; capacity];
+        let mut seq = self.oldest_in_buffer;
+        while seq.lt(self.next_seq) {
+            let idx = self.index(seq);
+            if let Some(stored) = self.buffer[idx]
+                .take()
+                .filter(|s| s.packet.seq_number() == seq)
+            {
+                buffer[(seq.as_raw() as usize) & (capacity - 1)] = Some(stored);
+            }
+            seq = seq.next();
+        }
+
+        self.buffer = buffer;
+        self.capacity = capacity;
+        self.mask = capacity - 1;
+        Ok(())
+    }
+
     /// Add a packet to the buffer
     ///
     /// Returns the sequence number assigned to the packet.
@@ -110,6 +208,10 @@ impl SendBuffer {
         let idx = self.index(seq);
         let now = Instant::now();
 
+        self.bytes += packet.payload.len();
+        if let Some(old) = &self.buffer[idx] {
+            self.bytes -= old.packet.payload.len();
+        }
         self.buffer[idx] = Some(StoredPacket {
             packet,
             first_sent: now,
@@ -123,15 +225,45 @@ impl SendBuffer {
         Ok(seq)
     }
 
+    /// Take the next sequence number without storing a packet for it
+    ///
+    /// The slot stays empty, like one whose packet expired, so a NAK for
+    /// it is answered with a DropReq.
+    pub fn skip(&mut self) -> Result<SeqNumber, BufferError> {
+        if self.available_space() == 0 {
+            self.drop_expired();
+            if self.available_space() == 0 {
+                return Err(BufferError::Full);
+            }
+        }
+
+        let seq = self.next_seq;
+        let idx = self.index(seq);
+        if let Some(old) = self.buffer[idx].take() {
+            self.bytes -= old.packet.payload.len();
+        }
+        self.next_seq = seq.next();
+
+        Ok(seq)
+    }
+
     /// Get a packet for transmission
     ///
     /// Returns a reference to the packet and updates send statistics.
     pub fn get_for_send(&mut self, seq: SeqNumber) -> Result<DataPacket, BufferError> {
+        self.get_for_send_at(seq, Instant::now())
+    }
+
+    pub(crate) fn get_for_send_at(
+        &mut self,
+        seq: SeqNumber,
+        now: Instant,
+    ) -> Result<DataPacket, BufferError> {
         let idx = self.index(seq);
 
         match &mut self.buffer[idx] {
             Some(stored) if stored.packet.seq_number() == seq => {
-                stored.last_sent = Instant::now();
+                stored.last_sent = now;
                 stored.send_count += 1;
 
                 // Mark as retransmitted if sent more than once
@@ -184,15 +316,25 @@ impl SendBuffer {
     }
 
     /// Remove acknowledged packets from the buffer
+    ///
+    /// Also updates the drain rate from the bytes flushed since the last
+    /// call.
     pub fn flush_acknowledged(&mut self) -> usize {
+        self.flush_acknowledged_at(Instant::now())
+    }
+
+    fn flush_acknowledged_at(&mut self, now: Instant) -> usize {
         let mut count = 0;
+        let mut flushed_bytes = 0;
         let mut current = self.oldest_in_buffer;
+        let had_data = self.bytes > 0;
 
         while current.lt(self.next_seq) {
             let idx = self.index(current);
 
             if let Some(stored) = &self.buffer[idx] {
                 if stored.acknowledged {
+                    flushed_bytes += stored.packet.payload.len();
                     self.buffer[idx] = None;
                     count += 1;
                     current = current.next();
@@ -208,17 +350,44 @@ impl SendBuffer {
         // Update oldest_in_buffer to the first non-flushed packet
         self.oldest_in_buffer = current;
         self.oldest_unacked = current;
+        self.bytes -= flushed_bytes;
+
+        // Only intervals with queued data say anything about the drain rate
+        let elapsed = self
+            .last_flush
+            .map(|last| now.saturating_duration_since(last).as_secs_f64());
+        if let Some(elapsed) = elapsed.filter(|e| had_data && *e > 0.0) {
+            let sample = flushed_bytes as f64 / elapsed;
+            self.drain_rate = Some(match self.drain_rate {
+                Some(rate) => rate * 7.0 / 8.0 + sample / 8.0,
+                None => sample,
+            });
+        }
+        self.last_flush = Some(now);
+
         count
     }
 
     /// Drop packets that have exceeded TTL
     pub fn drop_expired(&mut self) -> usize {
+        self.drop_expired_at(Instant::now())
+    }
+
+    pub(crate) fn drop_expired_at(&mut self, now: Instant) -> usize {
         let mut count = 0;
-        let now = Instant::now();
 
         for slot in &mut self.buffer {
             if let Some(stored) = slot {
-                if now.duration_since(stored.first_sent) > self.ttl {
+                if now.saturating_duration_since(stored.first_sent) > self.ttl {
+                    if !stored.acknowledged {
+                        self.expired += 1;
+                        let seq = stored.packet.seq_number();
+                        match self.expired_ranges.last_mut() {
+                            Some(range) if range.end.next() == seq => range.end = seq,
+                            _ => self.expired_ranges.push(LossRange::single(seq)),
+                        }
+                    }
+                    self.bytes -= stored.packet.payload.len();
                     *slot = None;
                     count += 1;
                 }
@@ -228,11 +397,57 @@ impl SendBuffer {
         count
     }
 
+    /// Get the time-to-live for packets
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Set the time-to-live for packets, including those already held
+    pub fn set_ttl(&mut self, ttl: Duration) {
+        self.ttl = ttl;
+    }
+
+    /// Get the number of unacknowledged packets dropped after their TTL
+    pub fn expired(&self) -> u64 {
+        self.expired
+    }
+
+    /// Take the ranges of unacknowledged packets dropped after their TTL
+    ///
+    /// The receiver may still be waiting for them; each range becomes a
+    /// DropReq.
+    pub fn take_expired_ranges(&mut self) -> Vec<LossRange> {
+        std::mem::take(&mut self.expired_ranges)
+    }
+
+    /// Get unacknowledged packets last sent at least `rto` before `now`
+    pub fn timed_out(&self, now: Instant, rto: Duration) -> Vec<SeqNumber> {
+        let mut expired = Vec::new();
+        let mut current = self.oldest_unacked;
+
+        while current.lt(self.next_seq) {
+            if let Some(stored) = &self.buffer[self.index(current)] {
+                if stored.packet.seq_number() == current
+                    && !stored.acknowledged
+                    && now.saturating_duration_since(stored.last_sent) >= rto
+                {
+                    expired.push(current);
+                }
+            }
+            current = current.next();
+        }
+
+        expired
+    }
+
     /// Get the number of packets currently in the buffer
     pub fn len(&self) -> usize {
-        self.next_seq
+        // Mask to 31 bits so the length stays correct across sequence wrap
+        (self
+            .next_seq
             .as_raw()
-            .wrapping_sub(self.oldest_unacked.as_raw()) as usize
+            .wrapping_sub(self.oldest_unacked.as_raw())
+            & MAX_SEQ_NUMBER) as usize
     }
 
     /// Check if the buffer is empty
@@ -240,11 +455,63 @@ impl SendBuffer {
         self.len() == 0
     }
 
+    /// Get the payload bytes held in the buffer
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Get how long the oldest unacknowledged packet has been queued
+    ///
+    /// Measured from when the packet was pushed, so it covers both waiting
+    /// to be sent and waiting for the ACK.
+    pub fn oldest_unacked_age(&self, now: Instant) -> Option<Duration> {
+        let mut current = self.oldest_unacked;
+
+        while current.lt(self.next_seq) {
+            if let Some(stored) = &self.buffer[self.index(current)] {
+                if stored.packet.seq_number() == current && !stored.acknowledged {
+                    return Some(now.saturating_duration_since(stored.first_sent));
+                }
+            }
+            current = current.next();
+        }
+
+        None
+    }
+
+    /// Get the oldest unacknowledged packet's sequence number and when it
+    /// was last sent (or pushed, if never sent)
+    pub fn oldest_unacked_sent(&self) -> Option<(SeqNumber, Instant)> {
+        let mut current = self.oldest_unacked;
+
+        while current.lt(self.next_seq) {
+            if let Some(stored) = &self.buffer[self.index(current)] {
+                if stored.packet.seq_number() == current && !stored.acknowledged {
+                    return Some((current, stored.last_sent));
+                }
+            }
+            current = current.next();
+        }
+
+        None
+    }
+
+    /// Get the smoothed rate at which ACKs drain the buffer
+    /// (bytes per second), once measured
+    pub fn drain_rate(&self) -> Option<u64> {
+        self.drain_rate.map(|rate| rate as u64)
+    }
+
     /// Get available space in the buffer
     pub fn available_space(&self) -> usize {
         self.capacity.saturating_sub(self.len())
     }
 
+    /// Get the buffer capacity in packets
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
     /// Get the next sequence number to be used
     pub fn next_seq(&self) -> SeqNumber {
         self.next_seq
@@ -261,11 +528,22 @@ impl SendBuffer {
     }
 }
 
+/// Data flushed from a receive buffer on close
+#[derive(Debug, Clone, Default)]
+pub struct ReceiveDrain {
+    /// Remaining complete messages, in order
+    pub messages: Vec<Bytes>,
+    /// Sequence ranges that never arrived and cannot be recovered
+    pub gaps: Vec<LossRange>,
+}
+
 /// Received packet entry
 #[derive(Clone)]
 struct ReceivedPacket {
     packet: DataPacket,
     _received_at: Instant,
+    /// Delivered ahead of earlier packets; skipped when delivery reaches it
+    delivered: bool,
 }
 
 /// Circular receive buffer
@@ -282,8 +560,15 @@ pub struct ReceiveBuffer {
     next_expected: SeqNumber,
     /// Highest received sequence number
     highest_received: SeqNumber,
-    /// Queue for reassembled messages ready for delivery
-    ready_messages: VecDeque<Bytes>,
+    /// Queue for reassembled messages ready for delivery, with the
+    /// timestamp of their first packet
+    ready_messages: VecDeque<(Bytes, u32)>,
+    /// Ranges the sender dropped, skipped instead of waited for
+    dropped: DroppedRanges,
+    /// Deliver complete messages not flagged in-order ahead of gaps
+    out_of_order: bool,
+    /// Byte-stream mode: deliver payloads in order, ignoring boundaries
+    stream_mode: bool,
 }
 
 impl ReceiveBuffer {
@@ -299,15 +584,71 @@ impl ReceiveBuffer {
             next_expected: SeqNumber::new(0),
             highest_received: SeqNumber::new(0),
             ready_messages: VecDeque::new(),
+            dropped: DroppedRanges::new(),
+            out_of_order: false,
+            stream_mode: false,
         }
     }
 
+    /// Check if the buffer is in stream mode
+    pub fn stream_mode(&self) -> bool {
+        self.stream_mode
+    }
+
+    /// Deliver every payload in sequence order, ignoring message
+    /// boundaries (off by default)
+    ///
+    /// For byte streams read with [`ReceiveBuffer::read`]; out-of-order
+    /// delivery does not apply.
+    pub fn set_stream_mode(&mut self, enabled: bool) {
+        self.stream_mode = enabled;
+        self.reassemble_messages();
+    }
+
+    /// Check if out-of-order message delivery is allowed
+    pub fn out_of_order(&self) -> bool {
+        self.out_of_order
+    }
+
+    /// Allow complete messages the sender did not flag in-order to be
+    /// delivered ahead of earlier, incomplete ones (off by default)
+    pub fn set_out_of_order(&mut self, enabled: bool) {
+        self.out_of_order = enabled;
+        self.reassemble_messages();
+    }
+
     /// Get the index for a sequence number
     #[inline]
     fn index(&self, seq: SeqNumber) -> usize {
         (seq.as_raw() as usize) & self.mask
     }
 
+    /// Change the capacity, keeping the buffered packets
+    ///
+    /// `capacity` is rounded up to a power of two. Fails with
+    /// `BufferError::Full`, leaving the buffer unchanged, if the span from
+    /// the next expected packet to the highest received would not fit.
+    pub fn resize(&mut self, capacity: usize) -> Result<(), BufferError> {
+        let capacity = capacity.next_power_of_two();
+        let span = self.next_expected.distance_to(self.highest_received) + 1;
+        if span > capacity as i32 {
+            return Err(BufferError::Full);
+        }
+
+        let mut buffer = vec![None; capacity];
+        let mut seq = self.next_expected;
+        while seq.le(self.highest_received) {
+            let idx = self.index(seq);
+            buffer[(seq.as_raw() as usize) & (capacity - 1)] = self.buffer[idx].take();
+            seq = seq.next();
+        }
+
+        self.buffer = buffer;
+        self.capacity = capacity;
+        self.mask = capacity - 1;
+        Ok(())
+    }
+
     /// Add a received packet to the buffer
     pub fn push(&mut self, packet: DataPacket) -> Result<(), BufferError> {
         let seq = packet.seq_number();
@@ -325,11 +666,16 @@ impl ReceiveBuffer {
         }
 
         let idx = self.index(seq);
+        if self.buffer[idx].as_ref().is_some_and(|r| r.delivered) {
+            // Duplicate of a message already delivered out of order
+            return Ok(());
+        }
 
         // Store the packet
         self.buffer[idx] = Some(ReceivedPacket {
             packet,
             _received_at: Instant::now(),
+            delivered: false,
         });
 
         // Update highest received
@@ -343,25 +689,66 @@ impl ReceiveBuffer {
         Ok(())
     }
 
+    /// Stop waiting for a range the sender dropped
+    ///
+    /// Missing packets in the range are skipped once delivery reaches them;
+    /// packets from it that did arrive are still delivered. Returns false
+    /// if the range was already passed.
+    pub fn drop_range(&mut self, range: LossRange) -> bool {
+        if !self.dropped.add(range, self.next_expected) {
+            return false;
+        }
+        self.reassemble_messages();
+        true
+    }
+
+    /// Move `next_expected` past missing packets in dropped ranges
+    fn skip_dropped(&mut self) -> usize {
+        let (buffer, mask) = (&self.buffer, self.mask);
+        self.dropped.skip(&mut self.next_expected, |seq| {
+            buffer[(seq.as_raw() as usize) & mask].is_some()
+        })
+    }
+
     /// Reassemble complete messages from received packets
     fn reassemble_messages(&mut self) {
-        while let Some(received) = &self.buffer[self.index(self.next_expected)] {
+        loop {
+            self.skip_dropped();
+            let Some(received) = &self.buffer[self.index(self.next_expected)] else {
+                break;
+            };
+            if received.delivered {
+                let idx = self.index(self.next_expected);
+                self.buffer[idx] = None;
+                self.next_expected = self.next_expected.next();
+                continue;
+            }
             let packet = &received.packet;
             let msg_num = packet.msg_number();
 
             // Check message boundary
             match msg_num.boundary {
+                _ if self.stream_mode => {
+                    // Every payload continues the byte stream
+                    self.ready_messages
+                        .push_back((packet.payload.clone(), packet.header.timestamp));
+                    let idx = self.index(self.next_expected);
+                    self.buffer[idx] = None;
+                    self.next_expected = self.next_expected.next();
+                }
                 crate::packet::PacketBoundary::Solo => {
                     // Complete message in single packet
-                    self.ready_messages.push_back(packet.payload.clone());
+                    self.ready_messages
+                        .push_back((packet.payload.clone(), packet.header.timestamp));
                     let idx = self.index(self.next_expected);
                     self.buffer[idx] = None;
                     self.next_expected = self.next_expected.next();
                 }
                 crate::packet::PacketBoundary::First => {
                     // Start of multi-packet message
+                    let timestamp = packet.header.timestamp;
                     if let Some(message) = self.reassemble_multi_packet_message() {
-                        self.ready_messages.push_back(message);
+                        self.ready_messages.push_back((message, timestamp));
                     } else {
                         break; // Not all packets available yet
                     }
@@ -375,6 +762,72 @@ impl ReceiveBuffer {
                 }
             }
         }
+        if self.out_of_order && !self.stream_mode {
+            self.deliver_out_of_order();
+        }
+    }
+
+    /// Deliver complete messages past a gap that were not flagged in-order
+    ///
+    /// Their packets stay in place, marked delivered, so they are neither
+    /// reported lost nor delivered again.
+    fn deliver_out_of_order(&mut self) {
+        let mut seq = self.next_expected;
+        while seq.le(self.highest_received) {
+            seq = match self.complete_unordered_message(seq) {
+                Some(last) => {
+                    let mut message = BytesMut::new();
+                    let mut timestamp = None;
+                    let mut current = seq;
+                    loop {
+                        let idx = self.index(current);
+                        let Some(received) = self.buffer[idx].as_mut() else {
+                            break;
+                        };
+                        received.delivered = true;
+                        message.extend_from_slice(&received.packet.payload);
+                        timestamp.get_or_insert(received.packet.header.timestamp);
+                        if current == last {
+                            break;
+                        }
+                        current = current.next();
+                    }
+                    self.ready_messages
+                        .push_back((message.freeze(), timestamp.unwrap_or(0)));
+                    last.next()
+                }
+                None => seq.next(),
+            };
+        }
+    }
+
+    /// Find the last packet of an undelivered, complete message starting at
+    /// `seq` that may be delivered out of order
+    fn complete_unordered_message(&self, seq: SeqNumber) -> Option<SeqNumber> {
+        let first = self.buffer[self.index(seq)].as_ref()?;
+        let msg = first.packet.msg_number();
+        if first.delivered || msg.in_order {
+            return None;
+        }
+        match msg.boundary {
+            PacketBoundary::Solo => return Some(seq),
+            PacketBoundary::First => {}
+            _ => return None,
+        }
+        let mut current = seq.next();
+        while current.le(self.highest_received) {
+            let packet = &self.buffer[self.index(current)].as_ref()?.packet;
+            let next = packet.msg_number();
+            if next.seq != msg.seq {
+                return None;
+            }
+            match next.boundary {
+                PacketBoundary::Last => return Some(current),
+                PacketBoundary::Subsequent => current = current.next(),
+                _ => return None,
+            }
+        }
+        None
     }
 
     /// Reassemble a multi-packet message starting at next_expected
@@ -434,9 +887,85 @@ impl ReceiveBuffer {
 
     /// Get the next ready message
     pub fn pop_message(&mut self) -> Option<Bytes> {
+        self.ready_messages.pop_front().map(|(message, _)| message)
+    }
+
+    /// Read delivered bytes into `buf`, ignoring message boundaries
+    ///
+    /// Returns the number of bytes read, 0 if nothing is ready. A payload
+    /// that does not fit is split, its remainder read next time.
+    pub fn read(&mut self, buf: &mut [u8]) -> usize {
+        let mut read = 0;
+        while read < buf.len() {
+            let Some((payload, _)) = self.ready_messages.front_mut() else {
+                break;
+            };
+            let n = payload.len().min(buf.len() - read);
+            buf[read..read + n].copy_from_slice(&payload.split_to(n));
+            read += n;
+            if payload.is_empty() {
+                self.ready_messages.pop_front();
+            }
+        }
+        read
+    }
+
+    /// Get the number of bytes ready to read
+    pub fn ready_bytes(&self) -> usize {
+        self.ready_messages
+            .iter()
+            .map(|(payload, _)| payload.len())
+            .sum()
+    }
+
+    /// Get the next ready message with its packet timestamp
+    pub fn pop_message_with_timestamp(&mut self) -> Option<(Bytes, u32)> {
         self.ready_messages.pop_front()
     }
 
+    /// Flush everything still deliverable, skipping over gaps
+    ///
+    /// For use on close, when missing packets will no longer arrive.
+    /// Messages with missing fragments are discarded; the missing ranges
+    /// are reported as gaps.
+    pub fn drain(&mut self) -> ReceiveDrain {
+        let mut drain = ReceiveDrain::default();
+
+        loop {
+            self.reassemble_messages();
+            drain
+                .messages
+                .extend(self.ready_messages.drain(..).map(|(message, _)| message));
+
+            if self.next_expected.gt(self.highest_received) {
+                break;
+            }
+
+            if self.buffer[self.index(self.next_expected)].is_some() {
+                // Start of a message with missing fragments
+                let idx = self.index(self.next_expected);
+                self.buffer[idx] = None;
+                self.next_expected = self.next_expected.next();
+                continue;
+            }
+
+            // Skip the gap up to the next received packet
+            let start = self.next_expected;
+            let mut seq = start;
+            while seq.le(self.highest_received) && self.buffer[self.index(seq)].is_none() {
+                seq = seq.next();
+            }
+            if seq.gt(self.highest_received) {
+                // Nothing received after the gap
+                break;
+            }
+            drain.gaps.push(LossRange::new(start, seq - 1));
+            self.next_expected = seq;
+        }
+
+        drain
+    }
+
     /// Get number of ready messages
     pub fn ready_message_count(&self) -> usize {
         self.ready_messages.len()
@@ -448,7 +977,7 @@ impl ReceiveBuffer {
         let mut current = self.next_expected;
 
         while current.le(self.highest_received) {
-            if self.buffer[self.index(current)].is_none() {
+            if self.buffer[self.index(current)].is_none() && !self.dropped.contains(current) {
                 losses.push(current);
             }
             current = current.next();
@@ -457,6 +986,11 @@ impl ReceiveBuffer {
         losses
     }
 
+    /// Get the buffer capacity in packets
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
     /// Get the next expected sequence number
     pub fn next_expected(&self) -> SeqNumber {
         self.next_expected
@@ -503,6 +1037,28 @@ mod tests {
         assert_eq!(retrieved.payload, packet.payload);
     }
 
+    #[test]
+    fn test_send_buffer_skip() {
+        let mut buffer = SendBuffer::new(4, Duration::from_secs(10));
+        let first = buffer.push(create_test_packet(0, 0, b"a")).unwrap();
+        let skipped = buffer.skip().unwrap();
+        let last = buffer.push(create_test_packet(0, 1, b"b")).unwrap();
+
+        assert_eq!(skipped, first.next());
+        assert_eq!(last, skipped.next());
+        assert_eq!(buffer.len(), 3);
+        assert_eq!(buffer.bytes(), 2);
+        assert!(buffer.contains(skipped));
+        assert!(buffer.get(skipped).is_err());
+
+        buffer.push(create_test_packet(0, 2, b"c")).unwrap();
+        assert!(matches!(buffer.skip(), Err(BufferError::Full)));
+
+        buffer.acknowledge_up_to(last);
+        assert_eq!(buffer.flush_acknowledged(), 2);
+        assert_eq!(buffer.len(), 1);
+    }
+
     #[test]
     fn test_send_buffer_acknowledge() {
         let mut buffer = SendBuffer::new(16, Duration::from_secs(10));
@@ -520,6 +1076,153 @@ mod tests {
         assert!(buffer.get(seq3).is_ok());
     }
 
+    #[test]
+    fn test_send_buffer_occupancy() {
+        let mut buffer = SendBuffer::new(16, Duration::from_secs(10));
+        let start = Instant::now();
+        assert_eq!(buffer.oldest_unacked_age(start), None);
+        buffer.flush_acknowledged_at(start);
+
+        for i in 0..4 {
+            buffer.push(create_test_packet(0, i, &[0; 1000])).unwrap();
+        }
+        assert_eq!(buffer.bytes(), 4000);
+        let age = buffer
+            .oldest_unacked_age(start + Duration::from_millis(50))
+            .unwrap();
+        assert!(age > Duration::from_millis(40) && age <= Duration::from_millis(50));
+        assert_eq!(buffer.drain_rate(), None);
+
+        // Two packets acknowledged over 10ms: 200 kB/s
+        buffer.acknowledge_up_to(SeqNumber::new(1));
+        assert_eq!(
+            buffer.flush_acknowledged_at(start + Duration::from_millis(10)),
+            2
+        );
+        assert_eq!(buffer.bytes(), 2000);
+        assert_eq!(buffer.drain_rate(), Some(200_000));
+
+        buffer.acknowledge_up_to(SeqNumber::new(3));
+        buffer.flush_acknowledged_at(start + Duration::from_millis(30));
+        assert_eq!(buffer.bytes(), 0);
+        assert_eq!(buffer.drain_rate(), Some(187_500));
+        assert_eq!(buffer.oldest_unacked_age(start), None);
+
+        // Idle intervals leave the rate alone
+        buffer.flush_acknowledged_at(start + Duration::from_secs(1));
+        assert_eq!(buffer.drain_rate(), Some(187_500));
+    }
+
+    #[test]
+    fn test_send_buffer_ttl() {
+        let mut buffer = SendBuffer::new(16, Duration::from_secs(10));
+        let start = Instant::now();
+        for i in 0..3 {
+            buffer.push(create_test_packet(0, i, b"data")).unwrap();
+        }
+        buffer.acknowledge(SeqNumber::new(0)).unwrap();
+
+        // Shortening the TTL applies to the packets already held
+        buffer.set_ttl(Duration::from_millis(100));
+        assert_eq!(buffer.ttl(), Duration::from_millis(100));
+        assert_eq!(buffer.drop_expired_at(start + Duration::from_millis(50)), 0);
+        assert_eq!(
+            buffer.drop_expired_at(start + Duration::from_millis(200)),
+            3
+        );
+        assert_eq!(buffer.bytes(), 0);
+        // Only the unacknowledged packets count as drops
+        assert_eq!(buffer.expired(), 2);
+        assert_eq!(
+            buffer.take_expired_ranges(),
+            vec![LossRange::new(SeqNumber::new(1), SeqNumber::new(2))]
+        );
+        assert!(buffer.take_expired_ranges().is_empty());
+    }
+
+    #[test]
+    fn test_buffer_sizing() {
+        // 10 Mbps of 1456-byte payloads is ~858 packets/s
+        let sizing = BufferSizing::for_stream(10_000_000, Duration::from_millis(900), 1500);
+        assert_eq!(sizing.recv_capacity, 1074);
+        assert_eq!(sizing.alignment_capacity, 1074);
+        assert_eq!(sizing.send_capacity, 1181);
+
+        // Raising the latency grows every buffer
+        let longer = BufferSizing::for_stream(10_000_000, Duration::from_secs(4), 1500);
+        assert!(longer.recv_capacity > 4 * sizing.recv_capacity);
+
+        // Clamped to the valid range
+        let tiny = BufferSizing::for_stream(1_000, Duration::ZERO, 1500);
+        assert_eq!(tiny.send_capacity, MIN_BUFFER_PACKETS);
+        let huge = BufferSizing::for_stream(u64::MAX, Duration::from_secs(60), 1500);
+        assert_eq!(huge.recv_capacity, MAX_BUFFER_PACKETS);
+    }
+
+    #[test]
+    fn test_send_buffer_resize() {
+        let mut buffer = SendBuffer::new(8, Duration::from_secs(10));
+        for i in 0..8 {
+            buffer
+                .push(create_test_packet(0, i, &[i as u8; 10]))
+                .unwrap();
+        }
+        buffer.acknowledge_up_to(SeqNumber::new(1));
+        buffer.flush_acknowledged();
+        assert!(buffer.push(create_test_packet(0, 8, &[8; 10])).is_ok());
+        assert!(buffer.push(create_test_packet(0, 9, &[9; 10])).is_ok());
+        assert!(buffer.push(create_test_packet(0, 10, &[10; 10])).is_err());
+
+        // Growing keeps packets 2-9, which wrapped around the old ring
+        buffer.resize(32).unwrap();
+        assert_eq!(buffer.capacity(), 32);
+        assert_eq!(buffer.available_space(), 24);
+        for i in 2..10 {
+            assert_eq!(buffer.get(SeqNumber::new(i)).unwrap().payload[0], i as u8);
+        }
+        assert!(buffer.get(SeqNumber::new(1)).is_err());
+        assert_eq!(buffer.bytes(), 80);
+        buffer.push(create_test_packet(0, 10, &[10; 10])).unwrap();
+
+        // Shrinking below the packets held fails and changes nothing
+        assert!(matches!(buffer.resize(8), Err(BufferError::Full)));
+        assert_eq!(buffer.capacity(), 32);
+        buffer.resize(9).unwrap();
+        assert_eq!(buffer.capacity(), 16);
+        assert_eq!(buffer.get(SeqNumber::new(10)).unwrap().payload[0], 10);
+    }
+
+    #[test]
+    fn test_receive_buffer_resize() {
+        let mut buffer = ReceiveBuffer::new(8);
+        for seq in [0, 2, 5, 7] {
+            buffer
+                .push(create_test_packet(seq, seq, &[seq as u8]))
+                .unwrap();
+        }
+        assert_eq!(buffer.pop_message().unwrap().as_ref(), &[0]);
+        assert!(buffer.push(create_test_packet(9, 9, &[9])).is_err());
+
+        // Pending packets move to the new slots and the gaps stay gaps
+        buffer.resize(64).unwrap();
+        assert_eq!(buffer.capacity(), 64);
+        assert_eq!(
+            buffer.get_loss_list(),
+            vec![
+                SeqNumber::new(1),
+                SeqNumber::new(3),
+                SeqNumber::new(4),
+                SeqNumber::new(6)
+            ]
+        );
+        buffer.push(create_test_packet(40, 40, &[40])).unwrap();
+        assert!(matches!(buffer.resize(32), Err(BufferError::Full)));
+
+        let drain = buffer.drain();
+        let messages: Vec<_> = drain.messages.iter().map(|m| m[0]).collect();
+        assert_eq!(messages, vec![2, 5, 7, 40]);
+    }
+
     #[test]
     fn test_receive_buffer_in_order() {
         let mut buffer = ReceiveBuffer::new(16);
@@ -584,6 +1287,99 @@ mod tests {
         assert_eq!(buffer.ready_message_count(), 3); // All three ready
     }
 
+    #[test]
+    fn test_receive_buffer_out_of_order_delivery() {
+        let fragment = |seq: u32, msg: u32, boundary, in_order| {
+            let mut packet = create_test_packet(seq, msg, &[seq as u8]);
+            packet.header.msg_or_info = MsgNumber {
+                boundary,
+                in_order,
+                ..MsgNumber::new(msg)
+            }
+            .to_raw();
+            packet
+        };
+        let mut buffer = ReceiveBuffer::new(16);
+        buffer.set_out_of_order(true);
+
+        // Message 1 (seq 0-1) is missing its first packet; 2 and 3 may
+        // overtake it, 4 may not
+        buffer
+            .push(fragment(1, 1, PacketBoundary::Last, false))
+            .unwrap();
+        buffer
+            .push(fragment(2, 2, PacketBoundary::Solo, false))
+            .unwrap();
+        buffer
+            .push(fragment(3, 3, PacketBoundary::First, false))
+            .unwrap();
+        buffer
+            .push(fragment(5, 3, PacketBoundary::Last, false))
+            .unwrap();
+        buffer
+            .push(fragment(6, 4, PacketBoundary::Solo, true))
+            .unwrap();
+        assert_eq!(buffer.pop_message().unwrap().as_ref(), &[2]);
+        assert_eq!(buffer.ready_message_count(), 0);
+        buffer
+            .push(fragment(4, 3, PacketBoundary::Subsequent, false))
+            .unwrap();
+        assert_eq!(buffer.pop_message().unwrap().as_ref(), &[3, 4, 5]);
+        assert_eq!(buffer.ready_message_count(), 0);
+        assert_eq!(buffer.get_loss_list(), vec![SeqNumber::new(0)]);
+
+        // A duplicate of a delivered message is not delivered again
+        buffer
+            .push(fragment(2, 2, PacketBoundary::Solo, false))
+            .unwrap();
+        assert_eq!(buffer.ready_message_count(), 0);
+
+        // Filling the gap delivers the rest, in order
+        buffer
+            .push(fragment(0, 1, PacketBoundary::First, false))
+            .unwrap();
+        let messages: Vec<_> = std::iter::from_fn(|| buffer.pop_message()).collect();
+        assert_eq!(
+            messages,
+            vec![Bytes::from_static(&[0, 1]), Bytes::from_static(&[6])]
+        );
+        assert_eq!(buffer.next_expected(), SeqNumber::new(7));
+
+        // Off by default: everything waits for the gap
+        let mut buffer = ReceiveBuffer::new(16);
+        buffer
+            .push(fragment(1, 2, PacketBoundary::Solo, false))
+            .unwrap();
+        assert_eq!(buffer.ready_message_count(), 0);
+    }
+
+    #[test]
+    fn test_receive_buffer_stream_mode() {
+        let mut buffer = ReceiveBuffer::new(16);
+        buffer.set_stream_mode(true);
+
+        // A First packet with nothing after it would stall a message buffer
+        let mut first = create_test_packet(0, 1, b"hello ");
+        first.header.msg_or_info = MsgNumber {
+            boundary: PacketBoundary::First,
+            ..MsgNumber::new(1)
+        }
+        .to_raw();
+        buffer.push(create_test_packet(2, 3, b"!")).unwrap();
+        buffer.push(first).unwrap();
+        assert_eq!(buffer.ready_bytes(), 6);
+        buffer.push(create_test_packet(1, 2, b"world")).unwrap();
+        assert_eq!(buffer.ready_bytes(), 12);
+
+        // Reads cut across payloads
+        let mut buf = [0u8; 8];
+        assert_eq!(buffer.read(&mut buf), 8);
+        assert_eq!(&buf, b"hello wo");
+        assert_eq!(buffer.read(&mut buf), 4);
+        assert_eq!(&buf[..4], b"rld!");
+        assert_eq!(buffer.read(&mut buf), 0);
+    }
+
     #[test]
     fn test_receive_buffer_loss_detection() {
         let mut buffer = ReceiveBuffer::new(16);
@@ -604,4 +1400,80 @@ mod tests {
         let losses = buffer.get_loss_list();
         assert_eq!(losses, vec![SeqNumber::new(1)]);
     }
+
+    fn solo_packet(seq: u32) -> DataPacket {
+        create_test_packet(seq, seq, format!("pkt{}", seq).as_bytes())
+    }
+
+    #[test]
+    fn test_receive_buffer_drop_range() {
+        let mut buffer = ReceiveBuffer::new(32);
+
+        // 1-3 lost, 5 lost
+        for seq in [0, 4, 6] {
+            buffer.push(solo_packet(seq)).unwrap();
+        }
+        assert_eq!(buffer.ready_message_count(), 1);
+
+        // The sender gives up on 1-3: 4 follows at once, 5 is still awaited
+        assert!(buffer.drop_range(LossRange::new(SeqNumber::new(1), SeqNumber::new(3))));
+        assert_eq!(buffer.ready_message_count(), 2);
+        assert_eq!(buffer.next_expected(), SeqNumber::new(5));
+        assert_eq!(buffer.get_loss_list(), vec![SeqNumber::new(5)]);
+        assert!(!buffer.drop_range(LossRange::new(SeqNumber::new(1), SeqNumber::new(3))));
+
+        // A dropped range ahead of delivery is skipped when reached, but
+        // packets from it that arrive are kept
+        assert!(buffer.drop_range(LossRange::new(SeqNumber::new(7), SeqNumber::new(9))));
+        buffer.push(solo_packet(8)).unwrap();
+        buffer.push(solo_packet(10)).unwrap();
+        assert_eq!(buffer.get_loss_list(), vec![SeqNumber::new(5)]);
+        buffer.push(solo_packet(5)).unwrap();
+        assert_eq!(buffer.next_expected(), SeqNumber::new(11));
+
+        let mut messages = Vec::new();
+        while let Some(message) = buffer.pop_message() {
+            messages.push(message);
+        }
+        assert_eq!(
+            messages,
+            vec![&b"pkt0"[..], b"pkt4", b"pkt5", b"pkt6", b"pkt8", b"pkt10"]
+        );
+    }
+
+    #[test]
+    fn test_receive_buffer_drain() {
+        let mut buffer = ReceiveBuffer::new(32);
+
+        // 0-1 delivered normally, 2-3 lost, 4-5 buffered, 6 lost, 7 buffered
+        for seq in [0, 1, 4, 5, 7] {
+            buffer.push(solo_packet(seq)).unwrap();
+        }
+        assert_eq!(buffer.ready_message_count(), 2);
+
+        // A message whose last fragment was lost
+        let mut first = create_test_packet(8, 8, b"part");
+        first.header.msg_or_info = MsgNumber {
+            boundary: PacketBoundary::First,
+            ..MsgNumber::new(8)
+        }
+        .to_raw();
+        buffer.push(first).unwrap();
+
+        let drain = buffer.drain();
+        let messages: Vec<&[u8]> = drain.messages.iter().map(|m| &m[..]).collect();
+        assert_eq!(
+            messages,
+            vec![&b"pkt0"[..], b"pkt1", b"pkt4", b"pkt5", b"pkt7"]
+        );
+        assert_eq!(
+            drain.gaps,
+            vec![
+                LossRange::new(SeqNumber::new(2), SeqNumber::new(3)),
+                LossRange::single(SeqNumber::new(6)),
+            ]
+        );
+        assert_eq!(buffer.next_expected(), SeqNumber::new(9));
+        assert!(buffer.drain().messages.is_empty());
+    }
 }
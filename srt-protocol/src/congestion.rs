@@ -2,8 +2,78 @@
 //!
 //! Implements rate-based congestion control with bandwidth estimation
 //! and adaptive window management.
-
+//!
+//! As in libsrt, a connection runs one of two schemes, agreed in the
+//! handshake (`CongestionMode`): live mode paces packets at the input
+//! rate plus an overhead for retransmissions and never backs off on loss,
+//! since a live source cannot slow down; file mode probes for the link
+//! rate and backs off on loss (`CongestionController`).
+
+use std::fmt;
+use std::str::FromStr;
 use std::time::{Duration, Instant};
+use thiserror::Error;
+
+/// Retransmission budget that builds up while no retransmissions are due
+const RETRANSMIT_BURST: Duration = Duration::from_millis(100);
+
+/// Initial congestion window (packets)
+const INITIAL_CWND: u32 = 16;
+
+/// RTT assumed for sizing the start window until one is measured
+const DEFAULT_START_RTT_US: u32 = 100_000;
+
+/// Default sending rate above the input rate in live mode (percent), as
+/// libsrt's `SRTO_OHEADBW`
+pub const DEFAULT_OVERHEAD_PERCENT: u32 = 25;
+
+/// Interval over which live mode measures the input rate
+const INPUT_RATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Congestion control scheme of a connection (libsrt's `SRTO_CONGESTION`)
+///
+/// Both sides must use the same one; a mismatch rejects the handshake.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum CongestionMode {
+    /// Pace at the input rate plus overhead, for live streams
+    #[default]
+    Live,
+    /// Probe for the link rate and back off on loss, for bulk transfer
+    File,
+}
+
+impl CongestionMode {
+    /// Get the name used in the handshake
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CongestionMode::Live => "live",
+            CongestionMode::File => "file",
+        }
+    }
+}
+
+impl fmt::Display for CongestionMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Congestion control name this implementation does not know
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("Unknown congestion control: {0}")]
+pub struct UnknownCongestionMode(pub String);
+
+impl FromStr for CongestionMode {
+    type Err = UnknownCongestionMode;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "live" => Ok(CongestionMode::Live),
+            "file" => Ok(CongestionMode::File),
+            other => Err(UnknownCongestionMode(other.to_string())),
+        }
+    }
+}
 
 /// Congestion control state
 #[derive(Debug, Clone)]
@@ -32,6 +102,12 @@ pub struct CongestionController {
     packet_delivery_rate: f64,
     /// Last update time
     last_update: Instant,
+    /// Configured start rate (bytes per second, 0 = slow start)
+    start_bandwidth_bps: u64,
+    /// Packets still to be acknowledged before the start rate is trusted
+    fast_start_pending: u32,
+    /// Whether early loss abandoned the start rate
+    fast_start_aborted: bool,
 }
 
 impl CongestionController {
@@ -42,13 +118,11 @@ impl CongestionController {
     /// * `max_packet_size` - Maximum packet size in bytes
     /// * `flow_window` - Flow window size in packets
     pub fn new(max_bandwidth_bps: u64, max_packet_size: usize, flow_window: u32) -> Self {
-        let initial_cwnd = 16; // Initial congestion window
-
         CongestionController {
             max_bandwidth_bps,
             current_bandwidth_bps: max_bandwidth_bps / 2, // Start conservative
             flow_window,
-            congestion_window: initial_cwnd,
+            congestion_window: INITIAL_CWND,
             max_packet_size,
             ssthresh: flow_window / 2,
             slow_start: true,
@@ -57,14 +131,65 @@ impl CongestionController {
             min_congestion_interval: Duration::from_secs(1),
             packet_delivery_rate: 0.0,
             last_update: Instant::now(),
+            start_bandwidth_bps: 0,
+            fast_start_pending: 0,
+            fast_start_aborted: false,
         }
     }
 
+    /// Start at a known link rate instead of slow-starting up to it
+    ///
+    /// For links with provisioned capacity: the rate and a congestion
+    /// window sized for it apply at once, and normal growth continues from
+    /// there. Loss before one window's worth of packets is acknowledged
+    /// falls back to slow start. 0 disables the fast start.
+    pub fn set_start_bandwidth(&mut self, bytes_per_sec: u64) {
+        self.start_bandwidth_bps = bytes_per_sec;
+        self.fast_start_aborted = false;
+        self.apply_start_bandwidth(DEFAULT_START_RTT_US);
+    }
+
+    /// Get the configured start rate (bytes per second, 0 = slow start)
+    pub fn start_bandwidth(&self) -> u64 {
+        self.start_bandwidth_bps
+    }
+
+    /// Size the window for the start rate at `rtt_us`
+    fn apply_start_bandwidth(&mut self, rtt_us: u32) {
+        if self.start_bandwidth_bps == 0 {
+            self.fast_start_pending = 0;
+            return;
+        }
+
+        let rate = self.start_bandwidth_bps.min(self.max_bandwidth_bps);
+        let bytes_per_rtt = rate as u128 * rtt_us as u128 / 1_000_000;
+        let window = (bytes_per_rtt / self.max_packet_size.max(1) as u128).clamp(
+            INITIAL_CWND as u128,
+            self.flow_window.max(INITIAL_CWND) as u128,
+        ) as u32;
+
+        self.congestion_window = window.min(self.flow_window);
+        self.current_bandwidth_bps = rate;
+        self.fast_start_pending = self.congestion_window;
+    }
+
+    /// Check if the start rate is in use but not yet confirmed by ACKs
+    pub fn in_fast_start(&self) -> bool {
+        self.fast_start_pending > 0
+    }
+
     /// Get current sending rate in bytes per second
     pub fn sending_rate_bps(&self) -> u64 {
         self.current_bandwidth_bps
     }
 
+    /// Change the maximum sending rate, e.g. to a ceiling the receiver
+    /// requested; the current rate is lowered at once if above it
+    pub fn set_max_bandwidth(&mut self, max_bandwidth_bps: u64) {
+        self.max_bandwidth_bps = max_bandwidth_bps;
+        self.current_bandwidth_bps = self.current_bandwidth_bps.min(max_bandwidth_bps);
+    }
+
     /// Get current congestion window size
     pub fn congestion_window(&self) -> u32 {
         self.congestion_window
@@ -94,6 +219,7 @@ impl CongestionController {
     /// Record packet acknowledged
     pub fn on_ack(&mut self, acked_packets: u32, rtt_us: u32) {
         self.packets_in_flight = self.packets_in_flight.saturating_sub(acked_packets);
+        self.fast_start_pending = self.fast_start_pending.saturating_sub(acked_packets);
 
         // Update congestion window
         if self.slow_start {
@@ -119,6 +245,20 @@ impl CongestionController {
 
     /// Record packet loss (NAK received)
     pub fn on_loss(&mut self, lost_packets: u32) {
+        if self.in_fast_start() {
+            // The link cannot take the start rate: slow start from scratch,
+            // no higher than half of it
+            self.ssthresh = (self.congestion_window / 2).max(2);
+            self.congestion_window = INITIAL_CWND.min(self.ssthresh);
+            self.slow_start = true;
+            self.current_bandwidth_bps = self.start_bandwidth_bps.min(self.max_bandwidth_bps) / 2;
+            self.fast_start_pending = 0;
+            self.fast_start_aborted = true;
+            self.last_congestion_event = Some(Instant::now());
+            self.packets_in_flight = self.packets_in_flight.saturating_sub(lost_packets);
+            return;
+        }
+
         // Check if enough time has passed since last congestion event
         let should_reduce = match self.last_congestion_event {
             None => true,
@@ -174,6 +314,23 @@ impl CongestionController {
         self.current_bandwidth_bps = estimated_bps.min(self.max_bandwidth_bps);
     }
 
+    /// Seed the bandwidth estimate with a measured RTT
+    ///
+    /// Lets the first pacing interval reflect the path instead of half the
+    /// maximum bandwidth; normally called with the handshake RTT.
+    pub fn set_initial_rtt(&mut self, rtt_us: u32) {
+        if rtt_us == 0 || self.packet_delivery_rate != 0.0 {
+            return;
+        }
+        if self.in_fast_start() {
+            self.apply_start_bandwidth(rtt_us);
+        }
+        let rtt_sec = rtt_us as f64 / 1_000_000.0;
+        self.packet_delivery_rate = self.congestion_window as f64 / rtt_sec;
+        let estimated_bps = (self.packet_delivery_rate * self.max_packet_size as f64) as u64;
+        self.current_bandwidth_bps = estimated_bps.min(self.max_bandwidth_bps);
+    }
+
     /// Update flow window (from peer's available buffer)
     pub fn update_flow_window(&mut self, new_flow_window: u32) {
         self.flow_window = new_flow_window;
@@ -200,14 +357,18 @@ impl CongestionController {
     }
 
     /// Reset to initial state
+    ///
+    /// A configured start rate applies again.
     pub fn reset(&mut self) {
-        self.congestion_window = 16;
+        self.congestion_window = INITIAL_CWND;
         self.ssthresh = self.flow_window / 2;
         self.slow_start = true;
         self.packets_in_flight = 0;
         self.current_bandwidth_bps = self.max_bandwidth_bps / 2;
         self.packet_delivery_rate = 0.0;
         self.last_congestion_event = None;
+        self.fast_start_aborted = false;
+        self.apply_start_bandwidth(DEFAULT_START_RTT_US);
     }
 
     /// Get statistics
@@ -219,6 +380,8 @@ impl CongestionController {
             current_bandwidth_bps: self.current_bandwidth_bps,
             slow_start: self.slow_start,
             ssthresh: self.ssthresh,
+            fast_start: self.in_fast_start(),
+            fast_start_aborted: self.fast_start_aborted,
         }
     }
 }
@@ -238,6 +401,124 @@ pub struct CongestionStats {
     pub slow_start: bool,
     /// Slow start threshold
     pub ssthresh: u32,
+    /// Whether the configured start rate awaits confirmation by ACKs
+    pub fast_start: bool,
+    /// Whether early loss abandoned the configured start rate
+    pub fast_start_aborted: bool,
+}
+
+/// Live mode congestion control
+///
+/// A live source produces data at its own rate, so there is nothing to
+/// probe for: packets are paced at the input rate plus an overhead that
+/// leaves room for retransmissions, and loss does not slow the sender.
+/// The input rate is configured or measured. Data queued beyond what that
+/// rate sends within the latency budget would arrive too late to play, so
+/// the rate rises until the queue drains within the latency.
+#[derive(Debug, Clone)]
+pub struct LiveCongestionController {
+    /// Configured input rate (bytes per second, 0 = measure it)
+    input_bandwidth_bps: u64,
+    /// Sending rate above the input rate (percent)
+    overhead_percent: u32,
+    /// Latency budget queued data must be sent within
+    latency: Duration,
+    /// Measured input rate (bytes per second, 0 = not yet measured)
+    measured_bps: u64,
+    /// Start of the current measurement interval
+    window_start: Option<Instant>,
+    /// Bytes queued in the current measurement interval
+    window_bytes: u64,
+}
+
+impl LiveCongestionController {
+    /// Create a controller for a stream with `latency`
+    pub fn new(latency: Duration) -> Self {
+        LiveCongestionController {
+            input_bandwidth_bps: 0,
+            overhead_percent: DEFAULT_OVERHEAD_PERCENT,
+            latency,
+            measured_bps: 0,
+            window_start: None,
+            window_bytes: 0,
+        }
+    }
+
+    /// Get the latency budget
+    pub fn latency(&self) -> Duration {
+        self.latency
+    }
+
+    /// Set the latency budget, e.g. once the handshake has agreed on it
+    pub fn set_latency(&mut self, latency: Duration) {
+        self.latency = latency;
+    }
+
+    /// Get the configured input rate (bytes per second, 0 = measured)
+    pub fn input_bandwidth(&self) -> u64 {
+        self.input_bandwidth_bps
+    }
+
+    /// Set the input rate in bytes per second (0 = measure it), as
+    /// libsrt's `SRTO_INPUTBW`
+    pub fn set_input_bandwidth(&mut self, bytes_per_sec: u64) {
+        self.input_bandwidth_bps = bytes_per_sec;
+    }
+
+    /// Get the sending rate above the input rate (percent)
+    pub fn overhead_percent(&self) -> u32 {
+        self.overhead_percent
+    }
+
+    /// Set the sending rate above the input rate (percent)
+    pub fn set_overhead_percent(&mut self, percent: u32) {
+        self.overhead_percent = percent;
+    }
+
+    /// Get the measured input rate (bytes per second, 0 = not yet measured)
+    pub fn measured_input_bandwidth(&self) -> u64 {
+        self.measured_bps
+    }
+
+    /// Record `bytes` queued by the application
+    pub fn on_input(&mut self, bytes: usize) {
+        self.on_input_at(bytes, Instant::now());
+    }
+
+    pub(crate) fn on_input_at(&mut self, bytes: usize, now: Instant) {
+        let start = *self.window_start.get_or_insert(now);
+        let elapsed = now.saturating_duration_since(start);
+        if elapsed >= INPUT_RATE_WINDOW {
+            let sample = (self.window_bytes as f64 / elapsed.as_secs_f64()) as u64;
+            self.measured_bps = match self.measured_bps {
+                0 => sample,
+                rate => (rate * 7 + sample) / 8,
+            };
+            self.window_start = Some(now);
+            self.window_bytes = 0;
+        }
+        self.window_bytes += bytes as u64;
+    }
+
+    /// Get the sending rate in bytes per second with `backlog_bytes`
+    /// queued (0 = unlimited, until the input rate is known)
+    pub fn sending_rate_bps(&self, backlog_bytes: usize) -> u64 {
+        let input = match self.input_bandwidth_bps {
+            0 => self.measured_bps,
+            configured => configured,
+        };
+        if input == 0 {
+            return 0;
+        }
+        let rate = input.saturating_mul(100 + u64::from(self.overhead_percent)) / 100;
+
+        let latency_us = self.latency.as_micros();
+        if latency_us == 0 {
+            return rate;
+        }
+        let drain = (backlog_bytes as u128 * 1_000_000 / latency_us).min(u64::MAX as u128) as u64;
+        rate.max(drain)
+    }
 }
 
 /// Bandwidth estimator
@@ -305,9 +586,9 @@ impl BandwidthEstimator {
             total_time_us += time_diff.as_micros() as u64;
         }
 
-        if total_time_us > 0 {
-            // bytes / (time_us / 1_000_000) = bytes per second
-            self.estimated_bps = (total_bytes * 1_000_000) / total_time_us;
+        // bytes / (time_us / 1_000_000) = bytes per second
+        if let Some(bps) = (total_bytes * 1_000_000).checked_div(total_time_us) {
+            self.estimated_bps = bps;
         }
     }
 
@@ -323,6 +604,140 @@ impl Default for BandwidthEstimator {
     }
 }
 
+/// Sending rate cap
+///
+/// Paces packets so the long-run rate stays at or below the configured
+/// maximum. Idle time accumulates credit for up to the burst allowance
+/// (none by default), which is then sent back to back; it also absorbs
+/// sleeps that overshoot, which otherwise cost throughput at high rates.
+#[derive(Debug, Clone, Default)]
+pub struct BandwidthLimiter {
+    /// Maximum rate in bytes per second (0 = unlimited)
+    max_bytes_per_sec: u64,
+    /// Bytes that may be sent ahead of the rate after idle time
+    burst: usize,
+    /// Earliest time the next packet may be sent
+    next_send: Option<Instant>,
+}
+
+impl BandwidthLimiter {
+    /// Create a limiter (0 = unlimited)
+    pub fn new(max_bytes_per_sec: u64) -> Self {
+        BandwidthLimiter {
+            max_bytes_per_sec,
+            burst: 0,
+            next_send: None,
+        }
+    }
+
+    /// Get the burst allowance in bytes
+    pub fn burst(&self) -> usize {
+        self.burst
+    }
+
+    /// Set how many bytes of idle credit may be sent back to back
+    /// (0 = none)
+    pub fn set_burst(&mut self, bytes: usize) {
+        self.burst = bytes;
+    }
+
+    /// Get the maximum rate in bytes per second (0 = unlimited)
+    pub fn max_bandwidth(&self) -> u64 {
+        self.max_bytes_per_sec
+    }
+
+    /// Change the maximum rate, keeping the slots already reserved
+    pub fn set_max_bandwidth(&mut self, max_bytes_per_sec: u64) {
+        self.max_bytes_per_sec = max_bytes_per_sec;
+    }
+
+    /// Reserve a send slot for `bytes`, returning how long to wait first
+    pub fn delay(&mut self, bytes: usize) -> Duration {
+        self.delay_at(bytes, Instant::now())
+    }
+
+    fn delay_at(&mut self, bytes: usize, now: Instant) -> Duration {
+        if self.max_bytes_per_sec == 0 {
+            return Duration::ZERO;
+        }
+
+        let credit = self.send_time(self.burst);
+        let earliest = now.checked_sub(credit).unwrap_or(now);
+        let start = self.next_send.filter(|t| *t > earliest).unwrap_or(earliest);
+        self.next_send = Some(start + self.send_time(bytes));
+        start.saturating_duration_since(now)
+    }
+
+    /// Time `bytes` take at the maximum rate
+    fn send_time(&self, bytes: usize) -> Duration {
+        Duration::from_micros(bytes as u64 * 1_000_000 / self.max_bytes_per_sec)
+    }
+}
+
+/// Retransmission bandwidth budget
+///
+/// Allows retransmissions up to a fraction of the estimated link capacity,
+/// so that NAK storms under heavy loss cannot crowd out new data. Up to
+/// 100ms of budget builds up while idle.
+#[derive(Debug, Clone, Default)]
+pub struct RetransmitBudget {
+    /// Fraction of the capacity for retransmissions (0 = unlimited)
+    fraction: f64,
+    /// Bytes that may be retransmitted now
+    tokens: f64,
+    /// When the tokens were last topped up
+    last_refill: Option<Instant>,
+}
+
+impl RetransmitBudget {
+    /// Create a budget of `fraction` of the capacity (0 = unlimited)
+    pub fn new(fraction: f64) -> Self {
+        RetransmitBudget {
+            fraction: fraction.clamp(0.0, 1.0),
+            tokens: 0.0,
+            last_refill: None,
+        }
+    }
+
+    /// Get the fraction of the capacity for retransmissions (0 = unlimited)
+    pub fn fraction(&self) -> f64 {
+        self.fraction
+    }
+
+    /// Take `bytes` from the budget at a capacity of `capacity` bytes per
+    /// second, returning false if the retransmission must be suppressed
+    ///
+    /// An unknown capacity (0) does not limit retransmissions.
+    pub fn allow(&mut self, bytes: usize, capacity: u64) -> bool {
+        self.allow_at(bytes, capacity, Instant::now())
+    }
+
+    pub(crate) fn allow_at(&mut self, bytes: usize, capacity: u64, now: Instant) -> bool {
+        if self.fraction == 0.0 || capacity == 0 {
+            return true;
+        }
+
+        let rate = capacity as f64 * self.fraction;
+        // A budget below one packet would suppress that packet forever
+        let burst = (rate * RETRANSMIT_BURST.as_secs_f64()).max(bytes as f64);
+        let tokens = match self.last_refill {
+            None => burst,
+            Some(last) => {
+                let elapsed = now.saturating_duration_since(last).as_secs_f64();
+                (self.tokens + rate * elapsed).min(burst)
+            }
+        };
+        self.last_refill = Some(now);
+
+        if tokens < bytes as f64 {
+            self.tokens = tokens;
+            return false;
+        }
+        self.tokens = tokens - bytes as f64;
+        true
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -388,6 +803,58 @@ mod tests {
         assert_eq!(cc.packets_in_flight, 45); // Lost packets removed from flight
     }
 
+    #[test]
+    fn test_fast_start() {
+        let mut cc = CongestionController::new(10_000_000, 1456, 8192);
+        cc.set_start_bandwidth(5_000_000);
+
+        // One 100ms RTT's worth of packets at 5 MB/s, before any ACK
+        assert_eq!(cc.congestion_window(), 343);
+        assert_eq!(cc.sending_rate_bps(), 5_000_000);
+        assert!(cc.stats().fast_start);
+
+        // Resized for the measured RTT
+        cc.set_initial_rtt(20_000);
+        assert_eq!(cc.congestion_window(), 68);
+
+        // A window's worth of ACKs confirms the rate; later loss is ordinary
+        cc.on_ack(68, 20_000);
+        assert!(!cc.in_fast_start());
+        assert!(cc.congestion_window() > 68);
+        cc.on_loss(1);
+        assert!(!cc.stats().fast_start_aborted);
+
+        // Capped by the maximum rate and the flow window
+        let mut cc = CongestionController::new(1_000_000, 1456, 50);
+        cc.set_start_bandwidth(100_000_000);
+        assert_eq!(cc.sending_rate_bps(), 1_000_000);
+        assert_eq!(cc.congestion_window(), 50);
+    }
+
+    #[test]
+    fn test_fast_start_early_loss_falls_back() {
+        let mut cc = CongestionController::new(10_000_000, 1456, 8192);
+        cc.set_start_bandwidth(5_000_000);
+        for _ in 0..100 {
+            cc.on_packet_sent();
+        }
+        cc.on_ack(10, 50_000);
+
+        cc.on_loss(5);
+        let stats = cc.stats();
+        assert!(stats.fast_start_aborted);
+        assert!(!stats.fast_start);
+        assert!(stats.slow_start);
+        assert_eq!(stats.congestion_window, 16);
+        assert_eq!(stats.current_bandwidth_bps, 2_500_000);
+        assert_eq!(stats.packets_in_flight, 85);
+
+        // Reset tries the start rate again
+        cc.reset();
+        assert!(cc.in_fast_start());
+        assert_eq!(cc.congestion_window(), 343);
+    }
+
     #[test]
     fn test_pacing() {
         let cc = CongestionController::new(10_000_000, 1456, 8192);
@@ -397,6 +864,80 @@ mod tests {
         assert!(interval < Duration::from_millis(100));
     }
 
+    #[test]
+    fn test_initial_rtt() {
+        let mut cc = CongestionController::new(10_000_000, 1456, 8192);
+        assert_eq!(cc.sending_rate_bps(), 5_000_000);
+
+        // 16 packets per 10ms round trip
+        cc.set_initial_rtt(10_000);
+        assert_eq!(cc.sending_rate_bps(), 2_329_600);
+
+        // A short RTT is capped at the maximum bandwidth
+        let mut cc = CongestionController::new(10_000_000, 1456, 8192);
+        cc.set_initial_rtt(100);
+        assert_eq!(cc.sending_rate_bps(), 10_000_000);
+    }
+
+    #[test]
+    fn test_set_max_bandwidth() {
+        let mut cc = CongestionController::new(10_000_000, 1456, 8192);
+        assert_eq!(cc.sending_rate_bps(), 5_000_000);
+
+        // A lower ceiling takes effect at once
+        cc.set_max_bandwidth(1_000_000);
+        assert_eq!(cc.sending_rate_bps(), 1_000_000);
+
+        // Raising it again does not jump the current rate
+        cc.set_max_bandwidth(10_000_000);
+        assert_eq!(cc.sending_rate_bps(), 1_000_000);
+    }
+
+    #[test]
+    fn test_congestion_mode_names() {
+        assert_eq!(CongestionMode::default(), CongestionMode::Live);
+        for mode in [CongestionMode::Live, CongestionMode::File] {
+            assert_eq!(mode.to_string().parse::<CongestionMode>(), Ok(mode));
+        }
+        assert_eq!(
+            "vegas".parse::<CongestionMode>(),
+            Err(UnknownCongestionMode("vegas".into()))
+        );
+    }
+
+    #[test]
+    fn test_live_paces_at_input_rate() {
+        let mut cc = LiveCongestionController::new(Duration::from_millis(100));
+        // Unlimited until the input rate is known
+        assert_eq!(cc.sending_rate_bps(0), 0);
+
+        // 100 kB/s for a few seconds
+        let start = Instant::now();
+        for ms in (0..3000).step_by(10) {
+            cc.on_input_at(1000, start + Duration::from_millis(ms));
+        }
+        assert_eq!(cc.measured_input_bandwidth(), 100_000);
+        assert_eq!(cc.sending_rate_bps(0), 125_000);
+        cc.set_overhead_percent(50);
+        assert_eq!(cc.sending_rate_bps(0), 150_000);
+
+        // A configured input rate wins over the measured one
+        cc.set_input_bandwidth(1_000_000);
+        assert_eq!(cc.sending_rate_bps(0), 1_500_000);
+    }
+
+    #[test]
+    fn test_live_drains_backlog_within_latency() {
+        let mut cc = LiveCongestionController::new(Duration::from_millis(100));
+        cc.set_input_bandwidth(100_000);
+        assert_eq!(cc.sending_rate_bps(10_000), 125_000);
+        // 1 MB queued must leave within 100 ms
+        assert_eq!(cc.sending_rate_bps(1_000_000), 10_000_000);
+
+        cc.set_latency(Duration::ZERO);
+        assert_eq!(cc.sending_rate_bps(1_000_000), 125_000);
+    }
+
     #[test]
     fn test_bandwidth_estimator() {
         let mut estimator = BandwidthEstimator::new();
@@ -420,4 +961,71 @@ mod tests {
         // Congestion window should be capped at flow window
         assert_eq!(cc.congestion_window(), 1000);
     }
+
+    #[test]
+    fn test_bandwidth_limiter() {
+        let mut unlimited = BandwidthLimiter::new(0);
+        assert_eq!(unlimited.delay(1_000_000), Duration::ZERO);
+
+        // 1 MB/s: 1000-byte packets every millisecond
+        let mut limiter = BandwidthLimiter::new(1_000_000);
+        let start = Instant::now();
+        assert_eq!(limiter.delay_at(1000, start), Duration::ZERO);
+        assert_eq!(limiter.delay_at(1000, start), Duration::from_millis(1));
+        assert_eq!(limiter.delay_at(1000, start), Duration::from_millis(2));
+
+        // Idle time is not banked
+        let later = start + Duration::from_secs(1);
+        assert_eq!(limiter.delay_at(1000, later), Duration::ZERO);
+        assert_eq!(limiter.delay_at(1000, later), Duration::from_millis(1));
+    }
+
+    #[test]
+    fn test_bandwidth_limiter_burst() {
+        let mut limiter = BandwidthLimiter::new(1_000_000);
+        limiter.set_burst(3000);
+        let start = Instant::now();
+        // The first packet plus 3000 bytes of credit go at once
+        for _ in 0..4 {
+            assert_eq!(limiter.delay_at(1000, start), Duration::ZERO);
+        }
+        assert_eq!(limiter.delay_at(1000, start), Duration::from_millis(1));
+
+        // Credit builds up again while idle, but no further than the burst
+        let later = start + Duration::from_millis(4);
+        for _ in 0..3 {
+            assert_eq!(limiter.delay_at(1000, later), Duration::ZERO);
+        }
+        assert_eq!(limiter.delay_at(1000, later), Duration::from_millis(1));
+        let idle = start + Duration::from_secs(1);
+        for _ in 0..4 {
+            assert_eq!(limiter.delay_at(1000, idle), Duration::ZERO);
+        }
+        assert_eq!(limiter.delay_at(1000, idle), Duration::from_millis(1));
+    }
+
+    #[test]
+    fn test_retransmit_budget() {
+        let mut unlimited = RetransmitBudget::new(0.0);
+        assert!(unlimited.allow(1_000_000, 1000));
+
+        // 25% of 100 kB/s: 2500 bytes of burst, then 25 bytes per ms
+        let mut budget = RetransmitBudget::new(0.25);
+        let start = Instant::now();
+        assert!(budget.allow_at(1000, 0, start));
+        assert!(budget.allow_at(1000, 100_000, start));
+        assert!(budget.allow_at(1000, 100_000, start));
+        assert!(!budget.allow_at(1000, 100_000, start));
+        assert!(budget.allow_at(500, 100_000, start));
+        assert!(!budget.allow_at(1000, 100_000, start + Duration::from_millis(20)));
+        assert!(budget.allow_at(1000, 100_000, start + Duration::from_millis(40)));
+
+        // Idle time builds up to the burst only
+        let later = start + Duration::from_secs(10);
+        assert!(budget.allow_at(2500, 100_000, later));
+        assert!(!budget.allow_at(100, 100_000, later));
+
+        // A packet larger than the burst still gets through when idle
+        assert!(budget.allow_at(5000, 100_000, later + Duration::from_secs(1)));
+    }
 }
@@ -3,17 +3,84 @@
 //! Manages the lifecycle of an SRT connection from handshake through data
 //! transfer to disconnection.
 
-use crate::buffer::{ReceiveBuffer, SendBuffer};
-use crate::handshake::{SrtHandshake, SrtOptions};
-use crate::loss::{ReceiverLossList, SenderLossList};
-use crate::packet::{DataPacket, MsgNumber};
+use crate::ack::{AckGenerator, AckInfo, ArrivalEstimator, DropReqInfo, NakInfo, RttEstimator};
+use crate::buffer::{
+    BufferError, ReceiveBuffer, ReceiveDrain, SendBuffer, DEFAULT_BUFFER_PACKETS,
+    MAX_BUFFER_PACKETS, MIN_BUFFER_PACKETS,
+};
+use crate::congestion::{
+    BandwidthLimiter, CongestionController, CongestionMode, LiveCongestionController,
+    RetransmitBudget,
+};
+use crate::diagnostics::{
+    self, ConnectionDiagnostics, DiagnosticHistory, LossSummary, NegotiatedOptions,
+    RecvBufferDiagnostics, SendBufferDiagnostics,
+};
+use crate::filter::{
+    builtin_filter, is_filter_packet, ArqMode, FilterConfig, FilterError, FilterFactory,
+    FilterStats, PacketFilter,
+};
+use crate::handshake::{
+    HandshakeError, HandshakeType, RejectReason, SrtHandshake, SrtOptions, MAX_STREAM_ID_LEN,
+};
+use crate::keepalive::{KeepAlive, Liveness};
+use crate::labels::Labels;
+use crate::loss::{LossRange, ReceiverLossList, SenderLossList};
+use crate::packet::{
+    max_payload_for_mss, ControlPacket, ControlType, DataPacket, PacketError, DEFAULT_MSS, MAX_MSS,
+    MIN_MSS,
+};
+use crate::packetizer::Packetizer;
+use crate::probe::{
+    is_probe_packet, ProbePlan, ProbeReceiver, ProbeReport, ProbeResult, ProbeSender,
+};
 use crate::sequence::SeqNumber;
+use crate::srctime::{self, SourceClock, SourceTimeError};
+use crate::watchdog::{DeliveryWatchdog, StallAction, StallDiagnostics, StallEvent};
+use bytes::Bytes;
 use parking_lot::RwLock;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
 use std::net::SocketAddr;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime};
 use thiserror::Error;
 
+/// Maximum TSBPD latency in milliseconds
+pub const MAX_LATENCY_MS: u16 = 60_000;
+
+/// Default fraction of the TSBPD latency the send queue may add before
+/// `Connection::check_send_latency` raises an event
+pub const DEFAULT_SEND_LATENCY_ALARM: f64 = 0.5;
+
+/// Default multiple of the TSBPD latency a packet may wait in the send
+/// buffer for its ACK before it is dropped (as libsrt's sender drop)
+pub const DEFAULT_SEND_TTL_FACTOR: f64 = 1.25;
+
+/// Lower bound on the send buffer TTL
+///
+/// As libsrt, so short latencies still leave time for a few
+/// retransmissions.
+const MIN_SEND_TTL: Duration = Duration::from_secs(1);
+
+/// User-defined control subtype: the sender's rate ceiling
+///
+/// Carries a rate in bytes per second (0 = none) as a big-endian u64 in
+/// the control information field.
+pub const CONTROL_MAX_BW: u16 = crate::wire::SUBTYPE_MAX_BW;
+
+/// Lower bound on the retransmission timeout
+///
+/// ACKs go out every 10ms, so on very short paths a timeout derived from
+/// the RTT alone would expire before the ACK could arrive.
+const MIN_RETRANSMIT_TIMEOUT: Duration = Duration::from_millis(20);
+
+/// Dropped ranges kept for `take_dropped_range`; older ones are discarded
+const MAX_PENDING_DROPS: usize = 256;
+
+/// Link rate file mode starts probing from, as libsrt's default 1 Gbit/s
+/// (bytes per second)
+const FILE_MODE_MAX_BANDWIDTH: u64 = 125_000_000;
+
 /// Connection state
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ConnectionState {
@@ -41,15 +108,90 @@ pub enum ConnectionError {
     #[error("Buffer error: {0}")]
     Buffer(#[from] crate::buffer::BufferError),
 
+    #[error("Invalid MSS: {0} (must be between {MIN_MSS} and {MAX_MSS})")]
+    InvalidMss(usize),
+
+    #[error("Invalid latency: {0} ms (must be at most {MAX_LATENCY_MS} ms)")]
+    InvalidLatency(u16),
+
+    #[error(
+        "Invalid buffer size: {0} packets (must be between {MIN_BUFFER_PACKETS} and {MAX_BUFFER_PACKETS})"
+    )]
+    InvalidBufferSize(usize),
+
+    #[error("Payload too large: {size} bytes (max {max})")]
+    PayloadTooLarge { size: usize, max: usize },
+
+    #[error("Packet filter rejected: {0}")]
+    Filter(#[from] FilterError),
+
+    #[error("Congestion control mismatch: {local} here, {peer} at the peer")]
+    CongestionMismatch { local: CongestionMode, peer: String },
+
+    #[error("Invalid source time: {0}")]
+    SourceTime(#[from] SourceTimeError),
+
+    #[error("Malformed {0:?} control packet")]
+    MalformedControl(ControlType),
+
+    #[error("Invalid packet: {0}")]
+    Packet(#[from] PacketError),
+
     #[error("Handshake error: {0}")]
     Handshake(#[from] crate::handshake::HandshakeError),
 
+    #[error("Handshake rejected: {0}")]
+    Rejected(RejectReason),
+
+    #[error("Peer timed out: nothing heard for {0:?}")]
+    PeerTimeout(Duration),
+
+    #[error("Delivery stalled: no progress for {0:?}")]
+    Stalled(Duration),
+
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 }
 
+impl ConnectionError {
+    /// Get the reason to give a peer whose handshake failed with this error
+    pub fn reject_reason(&self) -> Option<RejectReason> {
+        match self {
+            ConnectionError::Rejected(reason) => Some(*reason),
+            ConnectionError::Filter(_) => Some(RejectReason::Filter),
+            ConnectionError::CongestionMismatch { .. } => Some(RejectReason::Congestion),
+            _ => None,
+        }
+    }
+}
+
+/// Retransmission policy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RetransmitPolicy {
+    /// Retransmit only packets reported lost by NAK (live mode)
+    #[default]
+    NakOnly,
+    /// Also retransmit packets unacknowledged after the RTO (file mode)
+    ///
+    /// Requires the rexmit flag on both sides, so the receiver can tell
+    /// retransmissions apart; otherwise falls back to `NakOnly`.
+    NakAndTimeout,
+}
+
+/// Message delivered by `Connection::recv_message`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReceivedMessage {
+    /// Message payload
+    pub data: bytes::Bytes,
+    /// Packet timestamp (microseconds since the sender's time origin)
+    pub timestamp: u32,
+    /// Sender's source time, if the peer advertised a time origin
+    pub source_time: Option<SystemTime>,
+}
+
 /// Connection statistics
 #[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct ConnectionStats {
     /// Total packets sent
     pub packets_sent: u64,
@@ -59,14 +201,89 @@ pub struct ConnectionStats {
     pub packets_lost: u64,
     /// Total packets retransmitted
     pub packets_retransmitted: u64,
+    /// Retransmissions triggered by NAK
+    pub nak_retransmissions: u64,
+    /// Retransmissions triggered by RTO expiry
+    pub timeout_retransmissions: u64,
+    /// Retransmissions skipped for exceeding the retransmission budget
+    pub retransmissions_suppressed: u64,
     /// Total bytes sent
     pub bytes_sent: u64,
     /// Total bytes received
     pub bytes_received: u64,
+    /// Data packets received with the retransmitted flag
+    pub packets_received_retransmitted: u64,
+    /// Payload bytes received in retransmitted packets
+    pub bytes_received_retransmitted: u64,
+    /// Receive rate of original packets (bytes per second)
+    pub receive_rate_bps: u64,
+    /// Interarrival jitter of original packets (microseconds)
+    pub arrival_jitter_us: u32,
     /// Round-trip time (microseconds)
     pub rtt_us: u32,
     /// Estimated bandwidth (bytes per second)
     pub bandwidth_bps: u64,
+    /// Maximum segment size (negotiated once connected)
+    pub mss: usize,
+    /// Losses that arrived while held back from NAKs for FEC
+    pub naks_avoided: u64,
+    /// Packets the packet filter added, such as FEC parity
+    pub packets_filter_extra_sent: u64,
+    /// Packets of the peer's packet filter received
+    pub packets_filter_extra_received: u64,
+    /// Lost packets the packet filter rebuilt
+    pub packets_filter_supplied: u64,
+    /// Loss reports left out of NAKs by the NAK interval
+    pub nak_reports_suppressed: u64,
+    /// DropReqs sent for packets no longer in the send buffer
+    pub drop_requests_sent: u64,
+    /// DropReqs received from the peer
+    pub drop_requests_received: u64,
+    /// ACKs received from the peer
+    pub acks_received: u64,
+    /// ACKs that acknowledged nothing new (stale, duplicate or coalesced)
+    pub acks_stale: u64,
+    /// Packets the peer reported lost in NAKs, counting repeats
+    pub packets_reported_lost: u64,
+    /// NAKed packets not resent because their retransmission was in flight
+    pub duplicate_naks_suppressed: u64,
+    /// Packets in the send buffer awaiting acknowledgement
+    pub send_buffer_packets: u64,
+    /// Payload bytes in the send buffer
+    pub send_buffer_bytes: u64,
+    /// Time the oldest unacknowledged packet has spent in the send buffer
+    pub send_buffer_age: Duration,
+    /// Unacknowledged packets dropped from the send buffer after its TTL
+    pub send_ttl_drops: u64,
+    /// Estimated time to drain the send buffer, once a drain rate is known
+    pub send_drain_time: Option<Duration>,
+    /// Delivery stalls detected by the watchdog
+    pub stalls_detected: u64,
+    /// Labels of the connection
+    pub labels: Labels,
+}
+
+/// Send queue latency event
+///
+/// Raised when the latency added by the send queue exceeds the configured
+/// fraction of the TSBPD latency; packets queued that long risk arriving
+/// too late to play.
+#[derive(Debug, Clone)]
+pub struct SendLatencyEvent {
+    /// Time of the check
+    pub timestamp: Instant,
+    /// Latency added by the send queue
+    pub queued_latency: Duration,
+    /// Negotiated TSBPD latency
+    pub latency: Duration,
+    /// Age of the oldest unacknowledged packet
+    pub oldest_unacked_age: Duration,
+    /// Estimated time to drain the send buffer
+    pub drain_time: Option<Duration>,
+    /// Payload bytes in the send buffer
+    pub buffered_bytes: u64,
+    /// Labels of the connection
+    pub labels: Labels,
 }
 
 /// SRT Connection
@@ -90,16 +307,141 @@ pub struct Connection {
     options: SrtOptions,
     /// Send buffer
     send_buffer: Arc<RwLock<SendBuffer>>,
+    /// Assigns message numbers and splits messages into packets
+    packetizer: Arc<RwLock<Packetizer>>,
+    /// Next queued sequence number not yet transmitted
+    next_to_send: Arc<RwLock<SeqNumber>>,
     /// Receive buffer
     recv_buffer: Arc<RwLock<ReceiveBuffer>>,
     /// Sender loss list
-    _sender_losses: Arc<RwLock<SenderLossList>>,
+    sender_losses: Arc<RwLock<SenderLossList>>,
     /// Receiver loss list
     _receiver_losses: Arc<RwLock<ReceiverLossList>>,
     /// Connection statistics
     stats: Arc<RwLock<ConnectionStats>>,
     /// Latency (milliseconds)
     latency_ms: u16,
+    /// Maximum segment size (bytes, including IP/UDP headers)
+    mss: usize,
+    /// Stream ID (sent by the caller, learned by the listener)
+    stream_id: Option<String>,
+    /// Operator-assigned labels
+    labels: Labels,
+    /// Packet filter configuration (negotiated after the handshake)
+    filter: Option<FilterConfig>,
+    /// Builds the packet filter (built-in filters when unset)
+    filter_factory: Option<FilterFactory>,
+    /// Packet filter running on the data path, once negotiated
+    active_filter: Arc<RwLock<Option<Box<dyn PacketFilter>>>>,
+    /// Payload bytes reserved for the packet filter's packets
+    filter_overhead: usize,
+    /// Configured retransmission policy
+    retransmit_policy: RetransmitPolicy,
+    /// RTT estimator (for the retransmission timeout)
+    rtt: Arc<RwLock<RttEstimator>>,
+    /// Sending rate cap
+    limiter: Arc<RwLock<BandwidthLimiter>>,
+    /// Our, the peer's and the requested rate caps
+    rate_ceilings: Arc<RwLock<RateCeilings>>,
+    /// Share of the sending rate cap available to retransmissions
+    retransmit_budget: Arc<RwLock<RetransmitBudget>>,
+    /// Minimum time before a loss is reported again
+    nak_interval: Duration,
+    /// When each outstanding loss was last reported
+    nak_reported: Arc<RwLock<BTreeMap<SeqNumber, Instant>>>,
+    /// Whether NAKs for retransmissions still in flight are ignored
+    retransmit_suppression: bool,
+    /// Whether peers that do not encrypt are rejected
+    enforce_encryption: bool,
+    /// Key material message sent in our handshake
+    key_material: Option<Bytes>,
+    /// Key material message from the peer's handshake
+    peer_key_material: Option<Bytes>,
+    /// When each packet was last retransmitted, within the suppression window
+    recent_retransmits: Arc<RwLock<BTreeMap<SeqNumber, Instant>>>,
+    /// ACK generator for received data
+    ack_generator: Arc<RwLock<AckGenerator>>,
+    /// Arrival rate and jitter of original (not retransmitted) packets
+    arrivals: Arc<RwLock<ArrivalEstimator>>,
+    /// Wallclock time of our timestamp zero (advertised in the handshake)
+    time_origin: SystemTime,
+    /// Maps the peer's timestamps to its source times
+    source_clock: Arc<RwLock<Option<SourceClock>>>,
+    /// Losses held back from NAKs while FEC may still repair them
+    held_losses: Arc<RwLock<BTreeSet<SeqNumber>>>,
+    /// NAKed ranges no longer in the send buffer, awaiting a DropReq
+    abandoned: Arc<RwLock<Vec<LossRange>>>,
+    /// Ranges the peer dropped, not yet taken by the application
+    dropped_ranges: Arc<RwLock<VecDeque<LossRange>>>,
+    /// When our handshake request was last sent
+    handshake_sent: Arc<RwLock<Option<HandshakeSent>>>,
+    /// Round trip measured by the handshake exchange (microseconds)
+    handshake_rtt_us: Option<u32>,
+    /// Bandwidth probe asked of the listener (caller side)
+    probe_plan: Option<ProbePlan>,
+    /// Probe burst to send, once the listener agreed to one
+    probe_sender: Arc<RwLock<Option<ProbeSender>>>,
+    /// Caller's probe burst being timed (listener side)
+    probe_receiver: Arc<RwLock<Option<ProbeReceiver>>>,
+    /// Path capacity measured by our probe burst
+    probe_result: Arc<RwLock<Option<ProbeResult>>>,
+    /// Fraction of the TSBPD latency the send queue may add (0 = no alarm)
+    send_latency_alarm: f64,
+    /// Multiple of the TSBPD latency packets stay in the send buffer
+    send_ttl_factor: f64,
+    /// Whether the send queue latency is currently over the alarm
+    send_latency_exceeded: Arc<RwLock<bool>>,
+    /// Keep-alives and the peer's liveness
+    keepalive: Arc<RwLock<KeepAlive>>,
+    /// Silence that timed the peer out, once it has
+    peer_timed_out: Arc<RwLock<Option<Duration>>>,
+    /// End of the linger of a graceful close, once one has begun
+    linger_until: Arc<RwLock<Option<Instant>>>,
+    /// Congestion control scheme, agreed in the handshake
+    congestion_mode: CongestionMode,
+    /// Live mode rate control
+    live_cc: Arc<RwLock<LiveCongestionController>>,
+    /// File mode rate control
+    file_cc: Arc<RwLock<CongestionController>>,
+    /// Paces packets at the congestion control's rate
+    pacer: Arc<RwLock<BandwidthLimiter>>,
+    /// Detects delivery that stops making progress
+    watchdog: Arc<RwLock<DeliveryWatchdog>>,
+    /// Recent events and stats samples for diagnostics bundles
+    history: Arc<RwLock<DiagnosticHistory>>,
+}
+
+/// Sending rate ceilings in bytes per second (0 = none)
+#[derive(Debug, Clone, Copy, Default)]
+struct RateCeilings {
+    /// Our own cap (`set_max_bandwidth`)
+    local: u64,
+    /// Cap the peer asked us to respect
+    peer: u64,
+    /// Cap we ask the peer to respect
+    requested: u64,
+}
+
+impl RateCeilings {
+    /// Get the lower of our own and the peer's cap
+    fn effective(&self) -> u64 {
+        match (self.local, self.peer) {
+            (0, peer) => peer,
+            (local, 0) => local,
+            (local, peer) => local.min(peer),
+        }
+    }
+}
+
+/// Handshake request sent, for measuring the round trip and checking
+/// the agreement
+#[derive(Debug, Clone, Copy)]
+struct HandshakeSent {
+    at: Instant,
+    /// Sent more than once; a reply can't be matched to a send
+    resent: bool,
+    /// SYN cookie the agreement must echo
+    cookie: u32,
 }
 
 impl Connection {
@@ -119,15 +461,70 @@ impl Connection {
             remote_addr,
             initial_seq_num,
             options: SrtOptions::default_capabilities(),
-            send_buffer: Arc::new(RwLock::new(SendBuffer::new(8192, Duration::from_secs(10)))),
-            recv_buffer: Arc::new(RwLock::new(ReceiveBuffer::new(8192))),
-            _sender_losses: Arc::new(RwLock::new(SenderLossList::new())),
+            send_buffer: Arc::new(RwLock::new(SendBuffer::new(
+                DEFAULT_BUFFER_PACKETS,
+                Self::send_ttl_for(latency_ms, DEFAULT_SEND_TTL_FACTOR),
+            ))),
+            packetizer: Arc::new(RwLock::new(Self::message_packetizer())),
+            next_to_send: Arc::new(RwLock::new(SeqNumber::new(0))),
+            recv_buffer: Arc::new(RwLock::new(ReceiveBuffer::new(DEFAULT_BUFFER_PACKETS))),
+            sender_losses: Arc::new(RwLock::new(SenderLossList::new())),
             _receiver_losses: Arc::new(RwLock::new(ReceiverLossList::new(
                 3,
                 Duration::from_millis(100),
             ))),
             stats: Arc::new(RwLock::new(ConnectionStats::default())),
             latency_ms,
+            mss: DEFAULT_MSS,
+            stream_id: None,
+            labels: Labels::new(),
+            filter: None,
+            filter_factory: None,
+            active_filter: Arc::new(RwLock::new(None)),
+            filter_overhead: 0,
+            retransmit_policy: RetransmitPolicy::default(),
+            rtt: Arc::new(RwLock::new(RttEstimator::new())),
+            limiter: Arc::new(RwLock::new(BandwidthLimiter::default())),
+            rate_ceilings: Arc::new(RwLock::new(RateCeilings::default())),
+            retransmit_budget: Arc::new(RwLock::new(RetransmitBudget::default())),
+            nak_interval: Duration::ZERO,
+            nak_reported: Arc::new(RwLock::new(BTreeMap::new())),
+            retransmit_suppression: false,
+            enforce_encryption: false,
+            key_material: None,
+            peer_key_material: None,
+            recent_retransmits: Arc::new(RwLock::new(BTreeMap::new())),
+            ack_generator: Arc::new(RwLock::new(AckGenerator::new(Duration::from_millis(10)))),
+            arrivals: Arc::new(RwLock::new(ArrivalEstimator::new())),
+            time_origin: SystemTime::now(),
+            source_clock: Arc::new(RwLock::new(None)),
+            held_losses: Arc::new(RwLock::new(BTreeSet::new())),
+            abandoned: Arc::new(RwLock::new(Vec::new())),
+            dropped_ranges: Arc::new(RwLock::new(VecDeque::new())),
+            handshake_sent: Arc::new(RwLock::new(None)),
+            handshake_rtt_us: None,
+            probe_plan: None,
+            probe_sender: Arc::new(RwLock::new(None)),
+            probe_receiver: Arc::new(RwLock::new(None)),
+            probe_result: Arc::new(RwLock::new(None)),
+            send_latency_alarm: DEFAULT_SEND_LATENCY_ALARM,
+            send_ttl_factor: DEFAULT_SEND_TTL_FACTOR,
+            send_latency_exceeded: Arc::new(RwLock::new(false)),
+            keepalive: Arc::new(RwLock::new(KeepAlive::default())),
+            peer_timed_out: Arc::new(RwLock::new(None)),
+            linger_until: Arc::new(RwLock::new(None)),
+            congestion_mode: CongestionMode::default(),
+            live_cc: Arc::new(RwLock::new(LiveCongestionController::new(
+                Duration::from_millis(u64::from(latency_ms)),
+            ))),
+            file_cc: Arc::new(RwLock::new(CongestionController::new(
+                FILE_MODE_MAX_BANDWIDTH,
+                max_payload_for_mss(DEFAULT_MSS),
+                DEFAULT_BUFFER_PACKETS as u32,
+            ))),
+            pacer: Arc::new(RwLock::new(BandwidthLimiter::default())),
+            watchdog: Arc::new(RwLock::new(DeliveryWatchdog::default())),
+            history: Arc::new(RwLock::new(DiagnosticHistory::new())),
         }
     }
 
@@ -138,7 +535,18 @@ impl Connection {
 
     /// Set connection state
     fn set_state(&self, new_state: ConnectionState) {
-        *self.state.write() = new_state;
+        let old_state = std::mem::replace(&mut *self.state.write(), new_state);
+        if old_state != new_state {
+            self.record_event(format!("State {:?} -> {:?}", old_state, new_state));
+        }
+    }
+
+    /// Add an event to the ones `diagnostics` reports
+    ///
+    /// The connection records its own (state changes, DropReqs, stalls,
+    /// timeouts); applications can add theirs to the same timeline.
+    pub fn record_event(&self, message: impl Into<String>) {
+        self.history.write().event(Instant::now(), message.into());
     }
 
     /// Get local socket ID
@@ -156,167 +564,4162 @@ impl Connection {
         self.remote_addr
     }
 
-    /// Create handshake packet for connection initiation
-    pub fn create_handshake(&self) -> SrtHandshake {
-        SrtHandshake::new_request(
-            self.initial_seq_num.as_raw(),
-            self.local_socket_id,
-            self.remote_addr,
-            self.options,
-            self.latency_ms,
-            self.latency_ms,
-        )
+    /// Get the maximum segment size
+    pub fn mss(&self) -> usize {
+        self.mss
     }
 
-    /// Process received handshake packet
-    pub fn process_handshake(&mut self, handshake: SrtHandshake) -> Result<(), ConnectionError> {
-        match self.state() {
-            ConnectionState::Init | ConnectionState::Connecting => {
-                // Store remote socket ID
-                self.remote_socket_id = Some(handshake.udt.socket_id);
+    /// Set the maximum segment size advertised in the handshake
+    ///
+    /// Must be called before the handshake; the effective value is the
+    /// minimum of this and the peer's MSS once the handshake completes.
+    pub fn set_mss(&mut self, mss: usize) -> Result<(), ConnectionError> {
+        if self.state() != ConnectionState::Init {
+            return Err(ConnectionError::InvalidState);
+        }
+        if !(MIN_MSS..=MAX_MSS).contains(&mss) {
+            return Err(ConnectionError::InvalidMss(mss));
+        }
+        self.mss = mss;
+        Ok(())
+    }
 
-                // Negotiate options (use minimum capabilities)
-                if let Some(peer_caps) = handshake.peer_capabilities() {
-                    self.options = self.negotiate_options(&peer_caps);
-                }
+    /// Get the TSBPD latency in milliseconds
+    ///
+    /// After the handshake this is the latency agreed with the peer.
+    pub fn latency_ms(&self) -> u16 {
+        self.latency_ms
+    }
 
-                // Transition to connected
-                self.set_state(ConnectionState::Connected);
-                Ok(())
-            }
-            _ => Err(ConnectionError::InvalidState),
+    /// Set the TSBPD latency advertised in the handshake
+    ///
+    /// Must be called before the handshake; the effective value is the
+    /// maximum of this and the peer's latency once the handshake completes.
+    pub fn set_latency(&mut self, latency_ms: u16) -> Result<(), ConnectionError> {
+        if self.state() != ConnectionState::Init {
+            return Err(ConnectionError::InvalidState);
+        }
+        if latency_ms > MAX_LATENCY_MS {
+            return Err(ConnectionError::InvalidLatency(latency_ms));
         }
+        self.latency_ms = latency_ms;
+        self.sync_send_ttl();
+        Ok(())
     }
 
-    /// Negotiate options with peer
-    fn negotiate_options(&self, peer: &SrtOptions) -> SrtOptions {
-        SrtOptions {
-            tsbpd_sender: self.options.tsbpd_sender && peer.tsbpd_sender,
-            tsbpd_receiver: self.options.tsbpd_receiver && peer.tsbpd_receiver,
-            encryption: self.options.encryption && peer.encryption,
-            too_late_packet_drop: self.options.too_late_packet_drop && peer.too_late_packet_drop,
-            nak_report: self.options.nak_report && peer.nak_report,
-            rexmit_flag: self.options.rexmit_flag && peer.rexmit_flag,
-            stream_mode: self.options.stream_mode && peer.stream_mode,
-            packet_filter: self.options.packet_filter && peer.packet_filter,
+    /// Get how long a packet may wait in the send buffer for its ACK
+    ///
+    /// The TSBPD latency times the TTL factor, and at least a second;
+    /// later packets could no longer be played and are dropped (counted
+    /// in `ConnectionStats::send_ttl_drops`).
+    pub fn send_ttl(&self) -> Duration {
+        self.send_buffer.read().ttl()
+    }
+
+    /// Set the send buffer TTL as a multiple of the TSBPD latency (at
+    /// least 1)
+    pub fn set_send_ttl_factor(&mut self, factor: f64) {
+        self.send_ttl_factor = factor.max(1.0);
+        self.sync_send_ttl();
+    }
+
+    fn send_ttl_for(latency_ms: u16, factor: f64) -> Duration {
+        Duration::from_secs_f64(f64::from(latency_ms) / 1000.0 * factor).max(MIN_SEND_TTL)
+    }
+
+    /// Derive the send buffer TTL from the current latency
+    fn sync_send_ttl(&self) {
+        self.send_buffer
+            .write()
+            .set_ttl(Self::send_ttl_for(self.latency_ms, self.send_ttl_factor));
+        self.live_cc
+            .write()
+            .set_latency(Duration::from_millis(u64::from(self.latency_ms)));
+    }
+
+    /// Get the send buffer capacity in packets
+    pub fn send_buffer_size(&self) -> usize {
+        self.send_buffer.read().capacity()
+    }
+
+    /// Set the send buffer capacity in packets (rounded up to a power of two)
+    ///
+    /// Must be called before the handshake.
+    pub fn set_send_buffer_size(&mut self, packets: usize) -> Result<(), ConnectionError> {
+        Self::check_buffer_size(self.state(), packets)?;
+        *self.send_buffer.write() = SendBuffer::new(packets, self.send_ttl());
+        Ok(())
+    }
+
+    /// Get the receive buffer capacity in packets
+    pub fn recv_buffer_size(&self) -> usize {
+        self.recv_buffer.read().capacity()
+    }
+
+    /// Set the receive buffer capacity in packets (rounded up to a power of two)
+    ///
+    /// Must be called before the handshake.
+    pub fn set_recv_buffer_size(&mut self, packets: usize) -> Result<(), ConnectionError> {
+        Self::check_buffer_size(self.state(), packets)?;
+        let mut recv_buffer = self.recv_buffer.write();
+        let mut resized = ReceiveBuffer::new(packets);
+        resized.set_out_of_order(recv_buffer.out_of_order());
+        resized.set_stream_mode(recv_buffer.stream_mode());
+        *recv_buffer = resized;
+        Ok(())
+    }
+
+    /// Resize the send and receive buffers in any state
+    ///
+    /// Buffered packets are kept, so a running stream can grow its buffers
+    /// when the operator raises the latency (see `BufferSizing`). Fails
+    /// without changing either buffer if a size is out of range or too
+    /// small for the packets currently held.
+    pub fn resize_buffers(
+        &self,
+        send_packets: usize,
+        recv_packets: usize,
+    ) -> Result<(), ConnectionError> {
+        for packets in [send_packets, recv_packets] {
+            if !(MIN_BUFFER_PACKETS..=MAX_BUFFER_PACKETS).contains(&packets) {
+                return Err(ConnectionError::InvalidBufferSize(packets));
+            }
+        }
+
+        let mut send_buffer = self.send_buffer.write();
+        let mut recv_buffer = self.recv_buffer.write();
+        let send_before = send_buffer.capacity();
+        send_buffer.resize(send_packets)?;
+        if let Err(e) = recv_buffer.resize(recv_packets) {
+            // Cannot fail: the send buffer held its packets at this size
+            let _ = send_buffer.resize(send_before);
+            return Err(e.into());
         }
+        Ok(())
     }
 
-    /// Send data
-    pub fn send(&self, data: &[u8]) -> Result<usize, ConnectionError> {
-        if self.state() != ConnectionState::Connected {
+    fn check_buffer_size(state: ConnectionState, packets: usize) -> Result<(), ConnectionError> {
+        if state != ConnectionState::Init {
             return Err(ConnectionError::InvalidState);
         }
+        if !(MIN_BUFFER_PACKETS..=MAX_BUFFER_PACKETS).contains(&packets) {
+            return Err(ConnectionError::InvalidBufferSize(packets));
+        }
+        Ok(())
+    }
 
-        // Create data packet
-        let mut send_buf = self.send_buffer.write();
-        let packet = DataPacket::new(
-            SeqNumber::new(0), // Will be assigned by buffer
-            MsgNumber::new(0), // Simplified for now
-            0,                 // Timestamp will be set later
-            self.remote_socket_id.unwrap_or(0),
-            bytes::Bytes::copy_from_slice(data),
+    /// Get the sending rate cap in bytes per second (0 = unlimited)
+    ///
+    /// The lower of our own cap and the one the peer requested.
+    pub fn max_bandwidth(&self) -> u64 {
+        self.limiter.read().max_bandwidth()
+    }
+
+    /// Cap the sending rate in bytes per second (0 = unlimited)
+    pub fn set_max_bandwidth(&self, bytes_per_sec: u64) {
+        let mut ceilings = self.rate_ceilings.write();
+        ceilings.local = bytes_per_sec;
+        self.reset_limiter(ceilings.effective());
+    }
+
+    fn reset_limiter(&self, bytes_per_sec: u64) {
+        let mut limiter = self.limiter.write();
+        let burst = limiter.burst();
+        *limiter = BandwidthLimiter::new(bytes_per_sec);
+        limiter.set_burst(burst);
+    }
+
+    /// Get the pacing burst allowance in bytes
+    pub fn pacing_burst(&self) -> usize {
+        self.limiter.read().burst()
+    }
+
+    /// Let up to `bytes` of idle credit go back to back when pacing (0 =
+    /// none, the default)
+    ///
+    /// Applies to both the sending rate cap and the congestion control's
+    /// rate. A driver whose sleeps overshoot catches up within the burst
+    /// instead of falling behind the rate.
+    pub fn set_pacing_burst(&self, bytes: usize) {
+        self.limiter.write().set_burst(bytes);
+        self.pacer.write().set_burst(bytes);
+    }
+
+    /// Get the sending rate cap the peer asked for (0 = none)
+    pub fn peer_max_bandwidth(&self) -> u64 {
+        self.rate_ceilings.read().peer
+    }
+
+    fn set_peer_max_bandwidth(&self, bytes_per_sec: u64) {
+        let mut ceilings = self.rate_ceilings.write();
+        if ceilings.peer == bytes_per_sec {
+            return;
+        }
+        ceilings.peer = bytes_per_sec;
+        self.reset_limiter(ceilings.effective());
+        tracing::info!(
+            "Peer requested a sending rate of at most {} bytes/s, sending at up to {} bytes/s",
+            bytes_per_sec,
+            ceilings.effective()
         );
+    }
 
-        send_buf.push(packet)?;
+    /// Get the sending rate cap we ask the peer for (0 = none)
+    pub fn requested_max_bandwidth(&self) -> u64 {
+        self.rate_ceilings.read().requested
+    }
 
-        // Update stats
-        let mut stats = self.stats.write();
-        stats.packets_sent += 1;
-        stats.bytes_sent += data.len() as u64;
+    /// Ask the peer to cap its sending rate in bytes per second (0 = none)
+    ///
+    /// Sent in the handshake. Once connected, send the packet from
+    /// `create_max_bw_request` to tell the peer about a change.
+    pub fn set_requested_max_bandwidth(&self, bytes_per_sec: u64) {
+        self.rate_ceilings.write().requested = bytes_per_sec;
+    }
 
-        Ok(data.len())
+    /// Create a control packet asking the peer to cap its sending rate at
+    /// `requested_max_bandwidth`
+    pub fn create_max_bw_request(&self) -> ControlPacket {
+        ControlPacket::new(
+            ControlType::UserDefined,
+            CONTROL_MAX_BW,
+            0,
+            0,
+            self.remote_socket_id.unwrap_or(0),
+            bytes::Bytes::copy_from_slice(&self.requested_max_bandwidth().to_be_bytes()),
+        )
     }
 
-    /// Receive data
-    pub fn recv(&self) -> Result<Option<bytes::Bytes>, ConnectionError> {
-        if self.state() != ConnectionState::Connected {
-            return Err(ConnectionError::InvalidState);
+    /// Get the fraction of the sending rate cap available to
+    /// retransmissions (0 = unlimited)
+    pub fn retransmit_budget(&self) -> f64 {
+        self.retransmit_budget.read().fraction()
+    }
+
+    /// Limit retransmissions to a fraction of the sending rate cap
+    /// (0 = unlimited)
+    ///
+    /// The cap (`set_max_bandwidth`) stands in for the link capacity;
+    /// without one retransmissions are not limited. The ACK rate is no
+    /// substitute, since it collapses under the very loss the budget is
+    /// for. Retransmissions over the budget are skipped and counted in
+    /// `retransmissions_suppressed`; the receiver reports the loss again.
+    pub fn set_retransmit_budget(&self, fraction: f64) {
+        *self.retransmit_budget.write() = RetransmitBudget::new(fraction);
+    }
+
+    /// Get the minimum time before a loss is reported again
+    pub fn nak_interval(&self) -> Duration {
+        self.nak_interval
+    }
+
+    /// Set the minimum time before a loss is reported again (0 = in every NAK)
+    ///
+    /// New losses are always reported at once. Repeating a loss sooner
+    /// than the retransmission could arrive only adds duplicates, so a
+    /// little over one RTT suits most links.
+    pub fn set_nak_interval(&mut self, interval: Duration) {
+        self.nak_interval = interval;
+    }
+
+    /// Check whether NAKs for retransmissions in flight are ignored
+    pub fn retransmit_suppression(&self) -> bool {
+        self.retransmit_suppression
+    }
+
+    /// Ignore NAKs for packets retransmitted less than one RTT ago
+    ///
+    /// A receiver that re-reports a loss before the retransmission can
+    /// have arrived would otherwise get the packet twice. Ignored NAKs are
+    /// counted in `duplicate_naks_suppressed`; if the retransmission was
+    /// lost too, the receiver's next report after the window resends it.
+    pub fn set_retransmit_suppression(&mut self, enabled: bool) {
+        self.retransmit_suppression = enabled;
+        if !enabled {
+            self.recent_retransmits.write().clear();
         }
+    }
 
-        let mut recv_buf = self.recv_buffer.write();
-        if let Some(message) = recv_buf.pop_message() {
-            let mut stats = self.stats.write();
-            stats.packets_received += 1;
-            stats.bytes_received += message.len() as u64;
-            Ok(Some(message))
-        } else {
-            Ok(None)
+    /// Get the time a retransmission is assumed to be in flight
+    ///
+    /// One smoothed RTT plus its variance.
+    pub fn retransmit_suppression_window(&self) -> Duration {
+        let rtt = self.rtt.read();
+        Duration::from_micros(u64::from(rtt.srtt()) + u64::from(rtt.rtt_var()))
+    }
+
+    /// Get the fraction of the TSBPD latency the send queue may add
+    pub fn send_latency_alarm(&self) -> f64 {
+        self.send_latency_alarm
+    }
+
+    /// Set the fraction of the TSBPD latency the send queue may add before
+    /// `check_send_latency` raises an event (0 disables the alarm)
+    pub fn set_send_latency_alarm(&mut self, fraction: f64) {
+        self.send_latency_alarm = fraction.max(0.0);
+    }
+
+    /// Reserve a send slot for a packet of `bytes`, returning how long to
+    /// wait before sending it to stay within the sending rate cap and the
+    /// congestion control's rate
+    pub fn pacing_delay(&self, bytes: usize) -> Duration {
+        let capped = self.limiter.write().delay(bytes);
+        let rate = self.congestion_rate();
+        let mut pacer = self.pacer.write();
+        pacer.set_max_bandwidth(rate);
+        capped.max(pacer.delay(bytes))
+    }
+
+    /// Get the congestion control scheme
+    pub fn congestion_mode(&self) -> CongestionMode {
+        self.congestion_mode
+    }
+
+    /// Set the congestion control scheme (live by default)
+    ///
+    /// Must be called before the handshake; a peer using another scheme is
+    /// rejected. File mode suits bulk transfers, which usually want
+    /// `RetransmitPolicy::NakAndTimeout` as well.
+    pub fn set_congestion_mode(&mut self, mode: CongestionMode) {
+        self.congestion_mode = mode;
+    }
+
+    /// Get the input rate live mode paces by (bytes per second, 0 = measured)
+    pub fn input_bandwidth(&self) -> u64 {
+        self.live_cc.read().input_bandwidth()
+    }
+
+    /// Set the input rate live mode paces by, in bytes per second
+    /// (0 = measure it)
+    pub fn set_input_bandwidth(&self, bytes_per_sec: u64) {
+        self.live_cc.write().set_input_bandwidth(bytes_per_sec);
+    }
+
+    /// Get how far above the input rate live mode sends (percent)
+    pub fn overhead_bandwidth(&self) -> u32 {
+        self.live_cc.read().overhead_percent()
+    }
+
+    /// Set how far above the input rate live mode sends (percent), leaving
+    /// room for retransmissions
+    pub fn set_overhead_bandwidth(&self, percent: u32) {
+        self.live_cc.write().set_overhead_percent(percent);
+    }
+
+    /// Get the sending rate the congestion control allows, in bytes per
+    /// second (0 = unlimited)
+    ///
+    /// Paced on top of the sending rate cap (`max_bandwidth`).
+    pub fn congestion_rate(&self) -> u64 {
+        match self.congestion_mode {
+            CongestionMode::Live => {
+                let backlog = self.send_buffer.read().bytes();
+                self.live_cc.read().sending_rate_bps(backlog)
+            }
+            CongestionMode::File => self.file_cc.read().sending_rate_bps(),
         }
     }
 
-    /// Process received data packet
-    pub fn process_data_packet(&self, packet: DataPacket) -> Result<(), ConnectionError> {
-        if self.state() != ConnectionState::Connected {
-            return Err(ConnectionError::InvalidState);
+    /// Get the Stream ID
+    pub fn stream_id(&self) -> Option<&str> {
+        self.stream_id.as_deref()
+    }
+
+    /// Get the connection's labels
+    pub fn labels(&self) -> &Labels {
+        &self.labels
+    }
+
+    /// Set labels attached to this connection's stats and log spans
+    pub fn set_labels(&mut self, labels: Labels) {
+        self.labels = labels;
+    }
+
+    /// Create a tracing span carrying this connection's labels
+    pub fn span(&self) -> tracing::Span {
+        tracing::info_span!(
+            "srt_conn",
+            socket_id = self.local_socket_id,
+            labels = %self.labels
+        )
+    }
+
+    /// Set the Stream ID sent in the connection handshake
+    pub fn set_stream_id(&mut self, stream_id: Option<String>) -> Result<(), ConnectionError> {
+        if let Some(ref id) = stream_id {
+            if id.len() > MAX_STREAM_ID_LEN {
+                return Err(HandshakeError::StreamIdTooLong(id.len()).into());
+            }
         }
+        self.stream_id = stream_id;
+        Ok(())
+    }
 
-        let mut recv_buf = self.recv_buffer.write();
-        recv_buf.push(packet)?;
+    /// Get the packet filter configuration
+    ///
+    /// After the handshake this is the configuration agreed with the peer.
+    pub fn packet_filter(&self) -> Option<&FilterConfig> {
+        self.filter.as_ref()
+    }
+
+    /// Get the SRT options
+    ///
+    /// After the handshake these are the options agreed with the peer.
+    pub fn options(&self) -> SrtOptions {
+        self.options
+    }
+
+    /// Check if the connection is in stream mode
+    pub fn stream_mode(&self) -> bool {
+        self.options.stream_mode
+    }
+
+    /// Select stream mode (a byte stream) instead of message mode (the
+    /// default)
+    ///
+    /// Must be called before the handshake; a peer in the other mode is
+    /// rejected. In stream mode [`Connection::write`] and
+    /// [`Connection::read`] move bytes with no message boundaries.
+    pub fn set_stream_mode(&mut self, enabled: bool) {
+        self.options.stream_mode = enabled;
+        self.packetizer.write().set_stream_mode(enabled);
+        self.recv_buffer.write().set_stream_mode(enabled);
+    }
 
+    /// Set the packet filter configuration string (e.g. "fec,cols:10,rows:5")
+    ///
+    /// Must be called before the handshake. Parameters the peer also sets
+    /// must match, otherwise the handshake is rejected. Parameters of the
+    /// built-in `fec` type are checked here; other types are checked by
+    /// the factory (see [`Connection::set_packet_filter_factory`]).
+    pub fn set_packet_filter(&mut self, config: Option<&str>) -> Result<(), ConnectionError> {
+        let filter = config.map(FilterConfig::parse).transpose()?;
+        if let Some(filter) = &filter {
+            filter.check_params()?;
+        }
+        self.filter = filter;
+        self.options.packet_filter = self.filter.is_some();
         Ok(())
     }
 
-    /// Get connection statistics
-    pub fn stats(&self) -> ConnectionStats {
-        self.stats.read().clone()
+    /// Build packet filters with `factory` instead of the built-in ones
+    ///
+    /// Must be called before the handshake. The factory gets the negotiated
+    /// configuration; an error rejects the handshake.
+    pub fn set_packet_filter_factory<F>(&mut self, factory: F)
+    where
+        F: Fn(&FilterConfig) -> Result<Box<dyn PacketFilter>, FilterError> + Send + Sync + 'static,
+    {
+        self.filter_factory = Some(Arc::new(factory));
     }
 
-    /// Close the connection
-    pub fn close(&self) {
-        self.set_state(ConnectionState::Closing);
-        // In a real implementation, send SHUTDOWN control packet
-        self.set_state(ConnectionState::Closed);
+    /// Go back to the built-in packet filters
+    pub fn clear_packet_filter_factory(&mut self) {
+        self.filter_factory = None;
     }
 
-    /// Check if connection is established
-    pub fn is_connected(&self) -> bool {
-        self.state() == ConnectionState::Connected
+    /// Check if a packet filter runs on the data path
+    pub fn has_active_filter(&self) -> bool {
+        self.active_filter.read().is_some()
     }
 
-    /// Check if connection is closed
-    pub fn is_closed(&self) -> bool {
-        matches!(self.state(), ConnectionState::Closed)
+    /// Get the statistics of the packet filter on the data path
+    pub fn packet_filter_stats(&self) -> Option<FilterStats> {
+        self.active_filter.read().as_ref().map(|f| f.stats())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Set the retransmission policy
+    ///
+    /// Must be called before the handshake; timeout-based retransmission is
+    /// only used if both sides negotiate the rexmit flag.
+    pub fn set_retransmit_policy(&mut self, policy: RetransmitPolicy) {
+        self.retransmit_policy = policy;
+    }
 
-    #[test]
-    fn test_connection_lifecycle() {
-        let conn = Connection::new(
-            12345,
-            "127.0.0.1:9000".parse().unwrap(),
-            "127.0.0.1:9001".parse().unwrap(),
-            SeqNumber::new(1000),
-            120,
-        );
+    /// Get the effective retransmission policy
+    pub fn retransmit_policy(&self) -> RetransmitPolicy {
+        if self.options.rexmit_flag {
+            self.retransmit_policy
+        } else {
+            RetransmitPolicy::NakOnly
+        }
+    }
 
-        assert_eq!(conn.state(), ConnectionState::Init);
-        assert!(!conn.is_connected());
+    /// Get the current retransmission timeout
+    pub fn retransmit_timeout(&self) -> Duration {
+        self.rtt.read().rto().max(MIN_RETRANSMIT_TIMEOUT)
+    }
 
-        // In a real scenario, handshake would be exchanged
-        // For now, just verify state transitions work
-        conn.close();
-        assert!(conn.is_closed());
+    /// Get the wallclock time of our timestamp zero
+    pub fn time_origin(&self) -> SystemTime {
+        self.time_origin
     }
 
-    #[test]
-    fn test_option_negotiation() {
-        let conn = Connection::new(
-            12345,
-            "127.0.0.1:9000".parse().unwrap(),
-            "127.0.0.1:9001".parse().unwrap(),
-            SeqNumber::new(1000),
-            120,
-        );
+    /// Set the wallclock time of our timestamp zero
+    ///
+    /// Connections sharing a packetizer (e.g. bonded group members) must use
+    /// the packetizer's origin. Takes effect with the next handshake.
+    pub fn set_time_origin(&mut self, origin: SystemTime) {
+        self.time_origin = origin;
+    }
 
-        let mut peer_opts = SrtOptions::default_capabilities();
-        peer_opts.encryption = false; // Peer doesn't support encryption
+    /// Check whether peers that do not encrypt are rejected
+    pub fn enforce_encryption(&self) -> bool {
+        self.enforce_encryption
+    }
 
-        let negotiated = conn.negotiate_options(&peer_opts);
-        assert!(!negotiated.encryption); // Should be disabled
+    /// Reject peers whose handshake does not encrypt
+    ///
+    /// The peer must advertise the encryption capability and send key
+    /// material with a real cipher; otherwise `process_handshake` fails
+    /// with `RejectReason::Unsecure`. This also stops a man in the middle
+    /// from stripping the key material to downgrade the stream to clear
+    /// text.
+    pub fn set_enforce_encryption(&mut self, enforce: bool) {
+        self.enforce_encryption = enforce;
+    }
+
+    /// Set the key material message sent in the handshake
+    ///
+    /// Must be called before the handshake.
+    pub fn set_key_material(&mut self, key_material: Option<Bytes>) -> Result<(), ConnectionError> {
+        if self.state() != ConnectionState::Init {
+            return Err(ConnectionError::InvalidState);
+        }
+        self.key_material = key_material;
+        Ok(())
+    }
+
+    /// Get the key material message from the peer's handshake
+    pub fn peer_key_material(&self) -> Option<&Bytes> {
+        self.peer_key_material.as_ref()
+    }
+
+    /// Get the peer's time origin, if advertised in its handshake
+    pub fn peer_time_origin(&self) -> Option<SystemTime> {
+        self.source_clock.read().as_ref().map(SourceClock::origin)
+    }
+
+    /// Get the maximum payload size for a single data packet
+    ///
+    /// A packet filter's overhead is reserved from it.
+    pub fn max_payload_size(&self) -> usize {
+        max_payload_for_mss(self.mss)
+            .saturating_sub(self.filter_overhead)
+            .max(1)
+    }
+
+    /// Get the round trip measured by the handshake, in microseconds
+    ///
+    /// Only the side that sends the first handshake measures one, and only
+    /// if the reply answers a single, unrepeated request.
+    pub fn handshake_rtt_us(&self) -> Option<u32> {
+        self.handshake_rtt_us
+    }
+
+    /// Get the bandwidth probe asked of the listener
+    pub fn probe_plan(&self) -> Option<ProbePlan> {
+        self.probe_plan
+    }
+
+    /// Ask the listener for a bandwidth probe right after the handshake
+    /// (`None`, the default, for none)
+    ///
+    /// Sent in the handshake; listeners that do not support probes ignore
+    /// it. See `create_probe_packets` and `probe_result`.
+    pub fn set_probe_plan(&mut self, plan: Option<ProbePlan>) {
+        self.probe_plan = plan;
+    }
+
+    /// Create the probe packets due now (caller side)
+    ///
+    /// Empty unless the listener agreed to a probe. The first call once
+    /// connected starts the burst; with a spacing, later calls return the
+    /// packets whose time has come. Send them as they are returned.
+    pub fn create_probe_packets(&self) -> Vec<ControlPacket> {
+        if !self.is_connected() {
+            return Vec::new();
+        }
+        match self.probe_sender.write().as_mut() {
+            Some(sender) => sender.poll(Instant::now(), self.remote_socket_id.unwrap_or(0)),
+            None => Vec::new(),
+        }
+    }
+
+    /// Create the report on the caller's probe burst once it is over
+    /// (listener side)
+    ///
+    /// Returned once, when every probe packet arrived or the burst's time
+    /// and a grace period passed since the first.
+    pub fn create_probe_report(&self) -> Option<ControlPacket> {
+        let report = self.probe_receiver.write().as_mut()?.poll(Instant::now())?;
+        Some(report.to_control_packet(self.remote_socket_id.unwrap_or(0)))
+    }
+
+    /// Get the path capacity measured by the probe, once the listener
+    /// reported on it
+    pub fn probe_result(&self) -> Option<ProbeResult> {
+        *self.probe_result.read()
+    }
+
+    /// Agree to time a caller's probe, or prepare ours once the listener
+    /// agreed to it
+    fn negotiate_probe(&self, handshake: &SrtHandshake) {
+        let Some(plan) = handshake.probe else {
+            return;
+        };
+        let plan = plan.clamped(max_payload_for_mss(self.mss));
+        if !plan.is_valid() {
+            return;
+        }
+        if handshake.udt.handshake_type == HandshakeType::Agreement as i32 {
+            if self.probe_plan.is_some() {
+                *self.probe_sender.write() = Some(ProbeSender::new(plan));
+            }
+        } else {
+            *self.probe_receiver.write() = Some(ProbeReceiver::new(plan));
+        }
+    }
+
+    fn process_probe_report(&self, report: &ProbeReport) {
+        let Some(plan) = self.probe_sender.read().as_ref().map(ProbeSender::plan) else {
+            return;
+        };
+        let result = ProbeResult::new(&plan, report, self.handshake_rtt_us);
+        self.record_event(format!(
+            "Probe: {} of {} packets arrived, bandwidth {}",
+            result.packets_received,
+            result.packets_sent,
+            result
+                .bandwidth_bps
+                .map_or("unknown".to_string(), |bw| format!("{} bytes/s", bw))
+        ));
+        *self.probe_result.write() = Some(result);
+    }
+
+    /// Create handshake packet for connection initiation
+    ///
+    /// Before the handshake completes the send time is recorded, so build
+    /// the packet just before sending it.
+    pub fn create_handshake(&self) -> SrtHandshake {
+        let mut handshake = SrtHandshake::new_request(
+            self.initial_seq_num.as_raw(),
+            self.local_socket_id,
+            self.remote_addr,
+            self.options,
+            self.latency_ms,
+            self.latency_ms,
+        );
+        handshake.udt.max_packet_size = self.mss as u32;
+        handshake.udt.max_flow_window = self.recv_buffer_size() as u32;
+        handshake.stream_id = self.stream_id.clone();
+        handshake.filter_config = self.filter.as_ref().map(|f| f.to_string());
+        handshake.congestion = self.handshake_congestion();
+        handshake.time_origin = Some(srctime::to_unix_micros(self.time_origin));
+        handshake.max_bw_request = Some(self.requested_max_bandwidth()).filter(|bw| *bw > 0);
+        handshake.probe = self.probe_plan;
+        handshake.key_material = self.key_material.clone();
+
+        if matches!(
+            self.state(),
+            ConnectionState::Init | ConnectionState::Connecting
+        ) {
+            let mut sent = self.handshake_sent.write();
+            *sent = Some(HandshakeSent {
+                at: Instant::now(),
+                resent: sent.is_some(),
+                cookie: handshake.udt.syn_cookie,
+            });
+        }
+        handshake
+    }
+
+    /// Answer a caller's handshake with an agreement
+    ///
+    /// The agreement echoes the request, with our socket ID and the
+    /// options this (already negotiated) connection uses.
+    pub fn create_agreement(&self, request: SrtHandshake) -> SrtHandshake {
+        let mut agreement = request;
+        agreement.udt.handshake_type = HandshakeType::Agreement as i32;
+        agreement.udt.socket_id = self.local_socket_id;
+        agreement.udt.max_packet_size = self.mss as u32;
+        agreement.filter_config = self.filter.as_ref().map(|f| f.to_string());
+        agreement.congestion = self.handshake_congestion();
+        agreement.time_origin = None;
+        agreement.max_bw_request = Some(self.requested_max_bandwidth()).filter(|bw| *bw > 0);
+        agreement.probe = self.probe_receiver.read().as_ref().map(ProbeReceiver::plan);
+        // Announce the negotiated latency, the larger of both sides'
+        if let Some(ext) = agreement.srt_ext.as_mut() {
+            ext.latency = (u32::from(self.latency_ms) << 16) | u32::from(self.latency_ms);
+        }
+        agreement
+    }
+
+    /// Process the listener's answer to our handshake (caller side)
+    ///
+    /// Checks that the answer is an agreement to the request we sent
+    /// before connecting: it must echo our cookie and initial sequence
+    /// number, carry the listener's socket ID and SRT extension, and agree
+    /// on a sane latency. Rejections still surface as
+    /// [`ConnectionError::Rejected`].
+    pub fn process_agreement(&mut self, handshake: SrtHandshake) -> Result<(), ConnectionError> {
+        if !matches!(
+            self.state(),
+            ConnectionState::Init | ConnectionState::Connecting
+        ) {
+            return Err(ConnectionError::InvalidState);
+        }
+        if let Some(reason) = handshake.reject_reason() {
+            return Err(ConnectionError::Rejected(reason));
+        }
+        self.check_agreement(&handshake)?;
+        self.process_handshake(handshake)
+    }
+
+    fn check_agreement(&self, handshake: &SrtHandshake) -> Result<(), HandshakeError> {
+        let udt = &handshake.udt;
+        if udt.handshake_type != HandshakeType::Agreement as i32 {
+            return Err(HandshakeError::NotAgreement(udt.handshake_type));
+        }
+        if let Some(sent) = *self.handshake_sent.read() {
+            if udt.syn_cookie != sent.cookie {
+                return Err(HandshakeError::CookieMismatch {
+                    expected: sent.cookie,
+                    actual: udt.syn_cookie,
+                });
+            }
+        }
+        if udt.initial_seq_num != self.initial_seq_num.as_raw() {
+            return Err(HandshakeError::InitialSeqMismatch {
+                expected: self.initial_seq_num.as_raw(),
+                actual: udt.initial_seq_num,
+            });
+        }
+        if udt.socket_id == 0 {
+            return Err(HandshakeError::MissingSocketId);
+        }
+        let ext = handshake
+            .srt_ext
+            .as_ref()
+            .ok_or(HandshakeError::MissingExtension)?;
+        let agreed = ext.recv_latency_ms().max(ext.send_latency_ms());
+        if agreed > MAX_LATENCY_MS {
+            return Err(HandshakeError::LatencyOutOfRange(agreed));
+        }
+        if agreed < self.latency_ms {
+            return Err(HandshakeError::LatencyBelowRequest {
+                requested: self.latency_ms,
+                agreed,
+            });
+        }
+        Ok(())
+    }
+
+    /// Process received handshake packet
+    pub fn process_handshake(&mut self, handshake: SrtHandshake) -> Result<(), ConnectionError> {
+        self.keepalive.write().on_heard(Instant::now());
+        match self.state() {
+            ConnectionState::Init | ConnectionState::Connecting => {
+                if let Some(reason) = handshake.reject_reason() {
+                    return Err(ConnectionError::Rejected(reason));
+                }
+                if self.enforce_encryption {
+                    Self::check_encryption(&handshake)?;
+                }
+
+                // Agree on the packet filter and congestion control first; a
+                // mismatch rejects the peer
+                let filter = self.negotiate_filter(&handshake)?;
+                self.check_congestion(&handshake)?;
+                self.check_stream_mode(&handshake)?;
+
+                // Store remote socket ID
+                self.remote_socket_id = Some(handshake.udt.socket_id);
+
+                // Negotiate options (use minimum capabilities)
+                if let Some(peer_caps) = handshake.peer_capabilities() {
+                    self.options = self.negotiate_options(&peer_caps);
+                }
+                self.options.packet_filter = filter.is_some();
+                let active = match (&filter, &self.filter_factory) {
+                    (Some(config), Some(factory)) => Some(factory(config)?),
+                    (Some(config), None) => Some(builtin_filter(config)?),
+                    (None, _) => None,
+                };
+                self.filter_overhead = active.as_ref().map_or(0, |f| f.overhead());
+                *self.active_filter.write() = active;
+                self.filter = filter;
+
+                // Negotiate MSS (use the smaller of both sides, ignore bogus values)
+                let peer_mss = handshake.udt.max_packet_size as usize;
+                if (MIN_MSS..=MAX_MSS).contains(&peer_mss) && peer_mss < self.mss {
+                    self.resize_buffers_for_mss(peer_mss);
+                    self.mss = peer_mss;
+                }
+
+                self.negotiate_probe(&handshake);
+
+                // TSBPD latency is the larger of both sides' latencies
+                if let Some(ext) = handshake.srt_ext.as_ref() {
+                    self.latency_ms = self
+                        .latency_ms
+                        .max(ext.recv_latency_ms())
+                        .max(ext.send_latency_ms());
+                    self.sync_send_ttl();
+                }
+
+                self.peer_key_material = handshake.key_material;
+
+                // Learn the caller's Stream ID (listener side)
+                if handshake.stream_id.is_some() {
+                    self.stream_id = handshake.stream_id;
+                }
+
+                // Learn the peer's time origin for source times
+                if let Some(origin) = handshake.time_origin {
+                    *self.source_clock.write() =
+                        Some(SourceClock::new(srctime::from_unix_micros(origin)));
+                }
+
+                // Respect the peer's ceiling on our sending rate
+                if let Some(max_bw) = handshake.max_bw_request {
+                    self.set_peer_max_bandwidth(max_bw);
+                }
+
+                // Seed the RTT estimate from our request's round trip
+                if let Some(sent) = self.handshake_sent.write().take() {
+                    if !sent.resent {
+                        let rtt_us =
+                            u32::try_from(sent.at.elapsed().as_micros()).unwrap_or(u32::MAX);
+                        let mut rtt = self.rtt.write();
+                        rtt.seed(rtt_us.max(1));
+                        self.stats.write().rtt_us = rtt.srtt();
+                        self.handshake_rtt_us = Some(rtt_us.max(1));
+                    }
+                }
+
+                if self.congestion_mode == CongestionMode::File {
+                    let mut cc = self.file_cc.write();
+                    cc.update_flow_window(handshake.udt.max_flow_window);
+                    if let Some(rtt_us) = self.handshake_rtt_us {
+                        cc.set_initial_rtt(rtt_us);
+                    }
+                }
+
+                self.record_event(format!(
+                    "Handshake with {} (socket {}): latency {} ms, MSS {}, congestion {}",
+                    self.remote_addr,
+                    handshake.udt.socket_id,
+                    self.latency_ms,
+                    self.mss,
+                    self.congestion_mode
+                ));
+
+                // Transition to connected
+                self.set_state(ConnectionState::Connected);
+                Ok(())
+            }
+            _ => Err(ConnectionError::InvalidState),
+        }
+    }
+
+    /// Grow the buffers to hold as many bytes with a smaller MSS
+    ///
+    /// Buffer sizes are chosen in packets of the configured MSS. The
+    /// buffers are still empty during the handshake, so they are replaced.
+    fn resize_buffers_for_mss(&self, mss: usize) {
+        let (old, new) = (
+            max_payload_for_mss(self.mss),
+            max_payload_for_mss(mss).max(1),
+        );
+        let scale = |packets: usize| ((packets * old + new - 1) / new).min(MAX_BUFFER_PACKETS);
+
+        // Growing always fits the packets held
+        let send = scale(self.send_buffer_size()).max(self.send_buffer_size());
+        let recv = scale(self.recv_buffer_size()).max(self.recv_buffer_size());
+        let _ = self.resize_buffers(send, recv);
+    }
+
+    /// Check that the peer's handshake encrypts: it must advertise the
+    /// capability and carry key material with a cipher, so neither can be
+    /// stripped on the way
+    fn check_encryption(handshake: &SrtHandshake) -> Result<(), ConnectionError> {
+        let capable = handshake
+            .peer_capabilities()
+            .is_some_and(|caps| caps.encryption);
+        let ciphered = handshake.key_material_cipher().is_some_and(|c| c != 0);
+        if capable && ciphered {
+            Ok(())
+        } else {
+            Err(ConnectionError::Rejected(RejectReason::Unsecure))
+        }
+    }
+
+    /// Negotiate the packet filter with the peer's handshake
+    ///
+    /// A filter configured on only one side is used as-is, provided the
+    /// peer supports filters; if both sides configure one they are merged.
+    fn negotiate_filter(
+        &self,
+        handshake: &SrtHandshake,
+    ) -> Result<Option<FilterConfig>, FilterError> {
+        let peer = handshake
+            .filter_config
+            .as_deref()
+            .map(FilterConfig::parse)
+            .transpose()?;
+
+        let filter = match (&self.filter, peer) {
+            (None, None) => return Ok(None),
+            (Some(local), Some(peer)) => local.negotiate(&peer)?,
+            (Some(local), None) => {
+                let peer_supported = handshake
+                    .peer_capabilities()
+                    .is_some_and(|caps| caps.packet_filter);
+                if !peer_supported {
+                    return Err(FilterError::PeerUnsupported);
+                }
+                local.validate()?;
+                local.clone()
+            }
+            (None, Some(peer)) => {
+                peer.validate()?;
+                peer
+            }
+        };
+
+        Ok(Some(filter))
+    }
+
+    /// Get the congestion control name for our handshake (none for live)
+    pub fn handshake_congestion(&self) -> Option<String> {
+        (self.congestion_mode != CongestionMode::Live).then(|| self.congestion_mode.to_string())
+    }
+
+    /// Check the peer uses the same congestion control
+    ///
+    /// A handshake without the extension means live, as in libsrt.
+    fn check_congestion(&self, handshake: &SrtHandshake) -> Result<(), ConnectionError> {
+        let peer = handshake
+            .congestion
+            .as_deref()
+            .unwrap_or(CongestionMode::Live.as_str());
+        if peer.parse() == Ok(self.congestion_mode) {
+            return Ok(());
+        }
+        Err(ConnectionError::CongestionMismatch {
+            local: self.congestion_mode,
+            peer: peer.to_string(),
+        })
+    }
+
+    /// Check the peer uses the same stream or message mode
+    ///
+    /// A handshake without SRT options means message mode.
+    fn check_stream_mode(&self, handshake: &SrtHandshake) -> Result<(), ConnectionError> {
+        let peer = handshake
+            .peer_capabilities()
+            .is_some_and(|caps| caps.stream_mode);
+        if peer == self.options.stream_mode {
+            Ok(())
+        } else {
+            Err(ConnectionError::Rejected(RejectReason::MessageApi))
+        }
+    }
+
+    /// Negotiate options with peer
+    fn negotiate_options(&self, peer: &SrtOptions) -> SrtOptions {
+        SrtOptions {
+            tsbpd_sender: self.options.tsbpd_sender && peer.tsbpd_sender,
+            tsbpd_receiver: self.options.tsbpd_receiver && peer.tsbpd_receiver,
+            encryption: self.options.encryption && peer.encryption,
+            too_late_packet_drop: self.options.too_late_packet_drop && peer.too_late_packet_drop,
+            nak_report: self.options.nak_report && peer.nak_report,
+            rexmit_flag: self.options.rexmit_flag && peer.rexmit_flag,
+            stream_mode: self.options.stream_mode && peer.stream_mode,
+            packet_filter: self.options.packet_filter && peer.packet_filter,
+        }
+    }
+
+    /// Packetizer for messages, flagged in-order unless changed
+    fn message_packetizer() -> Packetizer {
+        let mut packetizer = Packetizer::default();
+        packetizer.set_in_order(true);
+        packetizer
+    }
+
+    /// Send data
+    ///
+    /// The data must fit in one packet; see [`Connection::send_message`]
+    /// for larger messages.
+    pub fn send(&self, data: &[u8]) -> Result<usize, ConnectionError> {
+        // Timestamp will be set later
+        self.send_with_timestamp(Bytes::copy_from_slice(data), 0, false)
+    }
+
+    /// Send data already held in `Bytes`
+    ///
+    /// Like [`Connection::send`], but the packet payload shares `data`'s
+    /// allocation instead of copying it, so a payload sent on several
+    /// connections (e.g. by a broadcast group) is stored once.
+    pub fn send_bytes(&self, data: Bytes) -> Result<usize, ConnectionError> {
+        self.send_with_timestamp(data, 0, false)
+    }
+
+    /// Leave the next sequence number unsent
+    ///
+    /// The peer is sent a DropReq for it (see
+    /// [`Connection::create_drop_requests`]) so it does not wait for the
+    /// packet. A bonding sender that leaves a packet off one path uses this
+    /// to keep the path's sequence numbers in step with the group's.
+    pub fn skip_sequence(&self) -> Result<SeqNumber, ConnectionError> {
+        self.check_sendable()?;
+        let seq = self.send_buffer.write().skip()?;
+        let mut abandoned = self.abandoned.write();
+        match abandoned.last_mut() {
+            Some(range) if range.end.next() == seq => range.end = seq,
+            _ => abandoned.push(LossRange::single(seq)),
+        }
+        Ok(seq)
+    }
+
+    /// Send a message of any size
+    ///
+    /// A message larger than `max_payload_size` is split into First,
+    /// Subsequent and Last packets sharing a message number; the receiver
+    /// delivers it whole. The send buffer must have room for all of them,
+    /// and a message larger than the whole buffer is refused.
+    pub fn send_message(&self, data: &[u8]) -> Result<usize, ConnectionError> {
+        self.send_with_timestamp(Bytes::copy_from_slice(data), 0, true)
+    }
+
+    /// Write bytes to the stream
+    ///
+    /// In stream mode as much of `data` as the send buffer has room for is
+    /// taken, without message boundaries, and the number of bytes taken is
+    /// returned; a full buffer takes nothing and fails with
+    /// `BufferError::Full`. In message mode `data` is one message, as with
+    /// [`Connection::send_message`].
+    pub fn write(&self, data: &[u8]) -> Result<usize, ConnectionError> {
+        if !self.options.stream_mode {
+            return self.send_message(data);
+        }
+        self.check_sendable()?;
+
+        let room = {
+            let mut send_buf = self.send_buffer.write();
+            if send_buf.available_space() == 0 {
+                send_buf.drop_expired();
+            }
+            send_buf.available_space() * self.max_payload_size()
+        };
+        if room == 0 && !data.is_empty() {
+            return Err(BufferError::Full.into());
+        }
+        let len = data.len().min(room);
+        self.send_with_timestamp(Bytes::copy_from_slice(&data[..len]), 0, true)
+    }
+
+    /// Check if sent messages are flagged for in-order delivery
+    pub fn message_in_order(&self) -> bool {
+        self.packetizer.read().in_order()
+    }
+
+    /// Flag sent messages for in-order delivery (the default) or let a
+    /// receiver that allows it deliver them as soon as they are complete
+    pub fn set_message_in_order(&self, in_order: bool) {
+        self.packetizer.write().set_in_order(in_order);
+    }
+
+    /// Check if received messages may be delivered out of order
+    pub fn out_of_order_delivery(&self) -> bool {
+        self.recv_buffer.read().out_of_order()
+    }
+
+    /// Deliver complete messages the sender did not flag in-order as soon
+    /// as they arrive, ahead of earlier incomplete ones (off by default)
+    pub fn set_out_of_order_delivery(&self, enabled: bool) {
+        self.recv_buffer.write().set_out_of_order(enabled);
+    }
+
+    /// Send data stamped with its source (e.g. capture) time
+    ///
+    /// The receiver recovers the source time from the packet timestamp and
+    /// the time origin exchanged in the handshake.
+    pub fn send_with_source_time(
+        &self,
+        data: &[u8],
+        source_time: SystemTime,
+    ) -> Result<usize, ConnectionError> {
+        let timestamp = srctime::source_timestamp(self.time_origin, source_time)?;
+        self.send_with_timestamp(Bytes::copy_from_slice(data), timestamp, false)
+    }
+
+    fn send_with_timestamp(
+        &self,
+        data: Bytes,
+        timestamp: u32,
+        fragment: bool,
+    ) -> Result<usize, ConnectionError> {
+        self.check_sendable()?;
+
+        let size = data.len();
+        let max = self.max_payload_size();
+        if !fragment && size > max {
+            return Err(ConnectionError::PayloadTooLarge { size, max });
+        }
+
+        // Sequence numbers are assigned by the send buffer
+        let packets = {
+            let mut packetizer = self.packetizer.write();
+            packetizer.set_max_payload_size(max);
+            packetizer.set_dest_socket_id(self.remote_socket_id.unwrap_or(0));
+            packetizer.packetize_bytes_at(data, timestamp)
+        };
+        let mut send_buf = self.send_buffer.write();
+        if packets.len() > send_buf.capacity() {
+            // Would never fit, however long the caller waits
+            return Err(ConnectionError::PayloadTooLarge {
+                size,
+                max: send_buf.capacity() * max,
+            });
+        }
+        if packets.len() > send_buf.available_space() {
+            send_buf.drop_expired();
+            if packets.len() > send_buf.available_space() {
+                return Err(BufferError::Full.into());
+            }
+        }
+        let count = packets.len() as u64;
+        for packet in packets {
+            send_buf.push(packet)?;
+        }
+        drop(send_buf);
+        if self.congestion_mode == CongestionMode::Live {
+            self.live_cc.write().on_input(size);
+        }
+
+        // Update stats
+        let mut stats = self.stats.write();
+        stats.packets_sent += count;
+        stats.bytes_sent += size as u64;
+
+        Ok(size)
+    }
+
+    /// Check the connection can take data to send
+    fn check_sendable(&self) -> Result<(), ConnectionError> {
+        if self.state() == ConnectionState::Connected {
+            return Ok(());
+        }
+        Err(match *self.peer_timed_out.read() {
+            Some(idle) => ConnectionError::PeerTimeout(idle),
+            None => ConnectionError::InvalidState,
+        })
+    }
+
+    /// Process an ACK from the peer
+    pub(crate) fn process_ack(&self, ack: &AckInfo) {
+        self.process_acks(std::slice::from_ref(ack));
+    }
+
+    /// Process a batch of ACKs from the peer
+    ///
+    /// Only the highest acknowledged sequence touches the send buffer and
+    /// loss list; every RTT sample is still used. ACKs behind what is
+    /// already acknowledged cost no buffer work.
+    pub(crate) fn process_acks(&self, acks: &[AckInfo]) {
+        let Some(highest) = acks
+            .iter()
+            .map(|ack| ack.ack_seq)
+            .reduce(|a, b| if b.gt(a) { b } else { a })
+        else {
+            return;
+        };
+
+        let oldest_unacked = self.send_buffer.read().oldest_unacked();
+        let advanced = !highest.lt(oldest_unacked);
+        if advanced {
+            {
+                let mut send_buf = self.send_buffer.write();
+                send_buf.acknowledge_up_to(highest);
+                send_buf.flush_acknowledged();
+            }
+            self.sender_losses.write().remove_up_to(highest);
+        }
+
+        let rtt_us = {
+            let mut rtt = self.rtt.write();
+            let mut sampled = false;
+            for ack in acks.iter().filter(|ack| ack.rtt_us > 0) {
+                rtt.update(ack.rtt_us);
+                sampled = true;
+            }
+            sampled.then(|| rtt.srtt())
+        };
+
+        if advanced && self.congestion_mode == CongestionMode::File {
+            let acked = oldest_unacked.distance_to(highest) as u32 + 1;
+            let rtt_us = rtt_us.unwrap_or_else(|| self.rtt.read().srtt());
+            self.file_cc.write().on_ack(acked, rtt_us);
+        }
+
+        let mut stats = self.stats.write();
+        stats.acks_received += acks.len() as u64;
+        stats.acks_stale += acks.len() as u64 - u64::from(advanced);
+        if let Some(rtt_us) = rtt_us {
+            stats.rtt_us = rtt_us;
+        }
+    }
+
+    /// Process a NAK from the peer, scheduling the lost packets
+    pub(crate) fn process_nak(&self, loss_ranges: &[LossRange]) {
+        let mut losses = self.sender_losses.write();
+        for range in loss_ranges {
+            losses.add_range(*range);
+        }
+        let reported: usize = loss_ranges.iter().map(LossRange::len).sum();
+        self.stats.write().packets_reported_lost += reported as u64;
+        // A live source cannot slow down; only file mode backs off
+        if self.congestion_mode == CongestionMode::File {
+            self.file_cc.write().on_loss(reported as u32);
+        }
+    }
+
+    /// Collect packets due for retransmission
+    ///
+    /// NAK-reported losses come first; with `RetransmitPolicy::NakAndTimeout`
+    /// unacknowledged packets older than the RTO follow.
+    pub fn packets_to_retransmit(&self) -> Vec<DataPacket> {
+        self.packets_to_retransmit_at(Instant::now())
+    }
+
+    pub(crate) fn packets_to_retransmit_at(&self, now: Instant) -> Vec<DataPacket> {
+        let mut send_buf = self.send_buffer.write();
+        let mut budget = self.retransmit_budget.write();
+        let capacity = self.max_bandwidth();
+        let mut packets = Vec::new();
+        let (mut nak_count, mut timeout_count, mut suppressed) = (0, 0, 0);
+        let mut duplicates = 0;
+        let mut abandoned: Vec<LossRange> = Vec::new();
+
+        // Packets past their TTL could no longer be played; drop them
+        // before they are resent
+        if send_buf
+            .oldest_unacked_age(now)
+            .is_some_and(|age| age > send_buf.ttl())
+        {
+            send_buf.drop_expired_at(now);
+        }
+        // The receiver is told to skip unacknowledged packets that expired,
+        // here or when the buffer made room for new ones
+        abandoned.extend(send_buf.take_expired_ranges());
+
+        let mut recent = self.recent_retransmits.write();
+        let window = self.retransmit_suppression_window();
+        if self.retransmit_suppression {
+            recent.retain(|_, sent| now.saturating_duration_since(*sent) < window);
+        }
+
+        // Packets already acknowledged or dropped are skipped; dropped ones
+        // are reported to the receiver with a DropReq, unless they just
+        // expired and are reported already. With suppression, a
+        // NAK for a packet resent within the window is a duplicate.
+        let mut retransmit = |send_buf: &mut SendBuffer, seq: SeqNumber, nak: bool| {
+            if nak && recent.contains_key(&seq) {
+                duplicates += 1;
+                return false;
+            }
+            let Ok(bytes) = send_buf.get(seq).map(|packet| packet.payload.len()) else {
+                if send_buf.contains(seq) && !abandoned.iter().any(|r| r.contains(seq)) {
+                    match abandoned.last_mut() {
+                        Some(range) if range.end.next() == seq => range.end = seq,
+                        _ => abandoned.push(LossRange::single(seq)),
+                    }
+                }
+                return false;
+            };
+            if !budget.allow_at(bytes, capacity, now) {
+                suppressed += 1;
+                return false;
+            }
+            match send_buf.get_for_send_at(seq, now) {
+                Ok(packet) => {
+                    packets.push(packet);
+                    if self.retransmit_suppression {
+                        recent.insert(seq, now);
+                    }
+                    true
+                }
+                Err(_) => false,
+            }
+        };
+
+        {
+            let mut losses = self.sender_losses.write();
+            while let Some(seq) = losses.pop_next() {
+                if retransmit(&mut send_buf, seq, true) {
+                    nak_count += 1;
+                }
+            }
+        }
+
+        if self.retransmit_policy() == RetransmitPolicy::NakAndTimeout {
+            let rto = self.retransmit_timeout();
+            for seq in send_buf.timed_out(now, rto) {
+                if retransmit(&mut send_buf, seq, false) {
+                    timeout_count += 1;
+                }
+            }
+        }
+
+        let mut stats = self.stats.write();
+        stats.nak_retransmissions += nak_count;
+        stats.timeout_retransmissions += timeout_count;
+        stats.packets_retransmitted += nak_count + timeout_count;
+        stats.retransmissions_suppressed += suppressed;
+        stats.duplicate_naks_suppressed += duplicates;
+        if !abandoned.is_empty() {
+            self.abandoned.write().extend(abandoned);
+        }
+
+        packets
+    }
+
+    /// Resend the oldest unacknowledged packet if it went unacknowledged for
+    /// an RTO
+    ///
+    /// Under `RetransmitPolicy::NakOnly` a lost tail is never NAKed: no later
+    /// packet reaches the receiver to reveal the gap. Call this once all
+    /// packets have been sent; the receiver acknowledges up to its first
+    /// gap, so the oldest unacknowledged packet is the one it is missing.
+    /// `NakAndTimeout` already resends every timed-out packet, so this
+    /// returns `None` under it.
+    pub fn retransmit_oldest_unacked(&self) -> Option<DataPacket> {
+        self.retransmit_oldest_unacked_at(Instant::now())
+    }
+
+    pub(crate) fn retransmit_oldest_unacked_at(&self, now: Instant) -> Option<DataPacket> {
+        if self.retransmit_policy() != RetransmitPolicy::NakOnly {
+            return None;
+        }
+        let mut send_buf = self.send_buffer.write();
+        let (seq, last_sent) = send_buf.oldest_unacked_sent()?;
+        if now.saturating_duration_since(last_sent) < self.retransmit_timeout()
+            || send_buf
+                .oldest_unacked_age(now)
+                .is_some_and(|age| age > send_buf.ttl())
+        {
+            return None;
+        }
+        let packet = send_buf.get_for_send_at(seq, now).ok()?;
+
+        let mut stats = self.stats.write();
+        stats.timeout_retransmissions += 1;
+        stats.packets_retransmitted += 1;
+        Some(packet)
+    }
+
+    /// Create DropReqs for packets the send buffer no longer holds
+    ///
+    /// Packets expire from the send buffer after its TTL. Unacknowledged
+    /// ones are announced as they expire, and again if the receiver NAKs
+    /// them later; it stops waiting for them instead of NAKing forever.
+    pub fn create_drop_requests(&self) -> Vec<ControlPacket> {
+        let abandoned = std::mem::take(&mut *self.abandoned.write());
+        self.stats.write().drop_requests_sent += abandoned.len() as u64;
+        for range in &abandoned {
+            self.record_event(format!(
+                "DropReq sent for {}-{}",
+                range.start.as_raw(),
+                range.end.as_raw()
+            ));
+        }
+        abandoned
+            .into_iter()
+            .map(|range| {
+                ControlPacket::new(
+                    ControlType::DropReq,
+                    0,
+                    0,
+                    0,
+                    self.remote_socket_id.unwrap_or(0),
+                    DropReqInfo::new(range).to_bytes(),
+                )
+            })
+            .collect()
+    }
+
+    /// Stop waiting for a range the peer dropped
+    fn process_drop_request(&self, range: LossRange) {
+        self.recv_buffer.write().drop_range(range);
+        self.nak_reported
+            .write()
+            .retain(|seq, _| !range.contains(*seq));
+        self.stats.write().drop_requests_received += 1;
+        self.record_event(format!(
+            "DropReq received for {}-{}",
+            range.start.as_raw(),
+            range.end.as_raw()
+        ));
+
+        let mut dropped = self.dropped_ranges.write();
+        if dropped.len() >= MAX_PENDING_DROPS {
+            dropped.pop_front();
+        }
+        dropped.push_back(range);
+    }
+
+    /// Take the next range the peer reported dropped
+    ///
+    /// The connection's own receive buffer skips the range already; this
+    /// lets a bonding receiver above it stop waiting too.
+    pub fn take_dropped_range(&self) -> Option<LossRange> {
+        self.dropped_ranges.write().pop_front()
+    }
+
+    /// Collect queued packets that have not been transmitted yet
+    ///
+    /// Packets dropped from the send buffer before their first
+    /// transmission are skipped.
+    pub fn packets_to_send(&self) -> Vec<DataPacket> {
+        let send_buf = self.send_buffer.read();
+        let mut next = self.next_to_send.write();
+        let mut packets = Vec::new();
+
+        let mut filter = self.active_filter.write();
+        let mut extra = 0;
+        while next.lt(send_buf.next_seq()) {
+            if let Ok(packet) = send_buf.get(*next) {
+                packets.push(packet.clone());
+                if let Some(filter) = filter.as_mut() {
+                    let added = filter.on_send(packet);
+                    extra += added.len() as u64;
+                    packets.extend(added);
+                }
+            }
+            *next = next.next();
+        }
+        if extra > 0 {
+            self.stats.write().packets_filter_extra_sent += extra;
+        }
+
+        if self.congestion_mode == CongestionMode::File {
+            let mut cc = self.file_cc.write();
+            packets.iter().for_each(|_| cc.on_packet_sent());
+        }
+
+        packets
+    }
+
+    /// Get the time between keep-alives
+    pub fn keepalive_interval(&self) -> Duration {
+        self.keepalive.read().interval()
+    }
+
+    /// Change the time between keep-alives
+    pub fn set_keepalive_interval(&self, interval: Duration) {
+        self.keepalive.write().set_interval(interval);
+    }
+
+    /// Get the next keep-alive to send, if any
+    ///
+    /// Returns echoes owed to the peer first, then a keep-alive once the
+    /// keep-alive interval has passed. Call periodically until it returns
+    /// None; each keep-alive the peer leaves unanswered counts as missed.
+    pub fn create_keepalive(&self) -> Option<ControlPacket> {
+        self.keepalive
+            .write()
+            .poll(Instant::now(), self.remote_socket_id.unwrap_or(0))
+    }
+
+    /// Get the peer's liveness from keep-alives and received packets
+    pub fn liveness(&self) -> Liveness {
+        self.keepalive.read().liveness()
+    }
+
+    /// Get the silence after which the peer times out
+    pub fn peer_idle_timeout(&self) -> Duration {
+        self.keepalive.read().idle_timeout()
+    }
+
+    /// Change the silence after which the peer times out (0 = never)
+    ///
+    /// Defaults to `DEFAULT_PEER_IDLE_TIMEOUT`, as in libsrt.
+    pub fn set_peer_idle_timeout(&self, timeout: Duration) {
+        self.keepalive.write().set_idle_timeout(timeout);
+    }
+
+    /// Check whether the peer has gone silent
+    ///
+    /// Once nothing has been heard from the peer for the peer idle timeout,
+    /// the connection starts closing and this returns
+    /// `ConnectionError::PeerTimeout`, as do later sends. Call periodically,
+    /// e.g. alongside `create_keepalive`; data received before the timeout
+    /// can still be collected with `drain`.
+    pub fn check_timeout(&self) -> Result<(), ConnectionError> {
+        self.check_timeout_at(Instant::now())
+    }
+
+    fn check_timeout_at(&self, now: Instant) -> Result<(), ConnectionError> {
+        if self.history.read().sample_due(now) {
+            let stats = self.stats_at(now);
+            self.history.write().sample(now, &stats);
+        }
+        if let Some(idle) = *self.peer_timed_out.read() {
+            return Err(ConnectionError::PeerTimeout(idle));
+        }
+        if !self.is_connected() {
+            return Ok(());
+        }
+        let idle = {
+            let keepalive = self.keepalive.read();
+            if !keepalive.is_timed_out(now) {
+                return Ok(());
+            }
+            keepalive.idle_time(now)
+        };
+        tracing::warn!(
+            "Peer {} timed out: nothing heard for {:?}",
+            self.remote_addr,
+            idle
+        );
+        *self.peer_timed_out.write() = Some(idle);
+        self.record_event(format!("Peer timed out: nothing heard for {:?}", idle));
+        self.begin_close();
+        Err(ConnectionError::PeerTimeout(idle))
+    }
+
+    /// Check if the connection closed because the peer timed out
+    pub fn is_timed_out(&self) -> bool {
+        self.peer_timed_out.read().is_some()
+    }
+
+    /// Process a control packet from the peer
+    ///
+    /// Handles ACK, NAK, DropReq, keep-alive, shutdown and rate ceiling
+    /// requests; other control types are ignored.
+    pub fn process_control(&self, packet: &ControlPacket) -> Result<(), ConnectionError> {
+        let now = Instant::now();
+        self.keepalive.write().on_heard(now);
+        let control_type = packet.try_control_type()?;
+        match control_type {
+            ControlType::Ack => {
+                let ack = AckInfo::from_bytes(&packet.control_info)
+                    .ok_or(ConnectionError::MalformedControl(control_type))?;
+                self.process_ack(&ack);
+            }
+            ControlType::Nak => {
+                let nak = NakInfo::from_bytes(&packet.control_info)
+                    .ok_or(ConnectionError::MalformedControl(control_type))?;
+                self.process_nak(&nak.loss_ranges);
+            }
+            ControlType::DropReq => {
+                let drop = DropReqInfo::from_bytes(&packet.control_info)
+                    .ok_or(ConnectionError::MalformedControl(control_type))?;
+                self.process_drop_request(drop.range);
+            }
+            ControlType::KeepAlive => {
+                let sample = self.keepalive.write().on_keepalive(packet, now);
+                if let Some(rtt_us) = sample {
+                    let mut rtt = self.rtt.write();
+                    rtt.update(rtt_us);
+                    self.stats.write().rtt_us = rtt.srtt();
+                }
+            }
+            ControlType::Shutdown => self.begin_close(),
+            ControlType::UserDefined
+                if packet.header.type_specific_info() == Some(CONTROL_MAX_BW) =>
+            {
+                let max_bw = packet
+                    .control_info
+                    .get(..8)
+                    .and_then(|bytes| bytes.try_into().ok())
+                    .map(u64::from_be_bytes)
+                    .ok_or(ConnectionError::MalformedControl(control_type))?;
+                self.set_peer_max_bandwidth(max_bw);
+            }
+            ControlType::UserDefined if is_probe_packet(packet) => {
+                if let Some(receiver) = self.probe_receiver.write().as_mut() {
+                    receiver.on_probe(packet, now);
+                }
+            }
+            ControlType::UserDefined
+                if packet.header.type_specific_info()
+                    == Some(crate::wire::SUBTYPE_PROBE_REPORT) =>
+            {
+                let report = ProbeReport::from_control_packet(packet)
+                    .ok_or(ConnectionError::MalformedControl(control_type))?;
+                self.process_probe_report(&report);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Process control packets that arrived together
+    ///
+    /// Same as `process_control` on each packet, except that the ACKs are
+    /// applied once, after the others: at high ACK rates a burst read from
+    /// the socket costs one send buffer update rather than one per ACK.
+    /// Processing stops at the first malformed packet.
+    pub fn process_control_batch(&self, packets: &[ControlPacket]) -> Result<(), ConnectionError> {
+        let mut acks = Vec::new();
+        let mut result = Ok(());
+        for packet in packets {
+            if packet.control_type() != ControlType::Ack {
+                result = self.process_control(packet);
+            } else if let Some(ack) = AckInfo::from_bytes(&packet.control_info) {
+                self.keepalive.write().on_heard(Instant::now());
+                acks.push(ack);
+            } else {
+                result = Err(ConnectionError::MalformedControl(ControlType::Ack));
+            }
+            if result.is_err() {
+                break;
+            }
+        }
+        self.process_acks(&acks);
+        result
+    }
+
+    /// Receive data
+    pub fn recv(&self) -> Result<Option<bytes::Bytes>, ConnectionError> {
+        Ok(self.recv_message()?.map(|message| message.data))
+    }
+
+    /// Receive a message with its timestamp and source time
+    pub fn recv_message(&self) -> Result<Option<ReceivedMessage>, ConnectionError> {
+        if self.state() != ConnectionState::Connected {
+            return Err(ConnectionError::InvalidState);
+        }
+
+        let mut recv_buf = self.recv_buffer.write();
+        if let Some((data, timestamp)) = recv_buf.pop_message_with_timestamp() {
+            let mut stats = self.stats.write();
+            stats.packets_received += 1;
+            stats.bytes_received += data.len() as u64;
+
+            let source_time = self
+                .source_clock
+                .write()
+                .as_mut()
+                .map(|clock| clock.source_time(timestamp));
+            Ok(Some(ReceivedMessage {
+                data,
+                timestamp,
+                source_time,
+            }))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Read received bytes into `buf`, ignoring message boundaries
+    ///
+    /// Meant for stream mode, where payloads join into one byte stream.
+    /// Returns the number of bytes read, 0 if nothing has arrived.
+    pub fn read(&self, buf: &mut [u8]) -> Result<usize, ConnectionError> {
+        if self.state() != ConnectionState::Connected {
+            return Err(ConnectionError::InvalidState);
+        }
+
+        let mut recv_buf = self.recv_buffer.write();
+        let before = recv_buf.ready_message_count();
+        let read = recv_buf.read(buf);
+        let payloads = before - recv_buf.ready_message_count();
+        drop(recv_buf);
+
+        let mut stats = self.stats.write();
+        stats.packets_received += payloads as u64;
+        stats.bytes_received += read as u64;
+        Ok(read)
+    }
+
+    /// Process received data packet
+    ///
+    /// Retransmitted packets are counted apart and left out of the arrival
+    /// rate and jitter, which recovery bursts would otherwise skew.
+    pub fn process_data_packet(&self, packet: DataPacket) -> Result<(), ConnectionError> {
+        if self.state() != ConnectionState::Connected {
+            return Err(ConnectionError::InvalidState);
+        }
+        let now = Instant::now();
+        self.keepalive.write().on_heard(now);
+        packet.try_seq_number()?;
+
+        // The packet filter sees everything and may rebuild lost packets;
+        // its own packets go no further
+        let (extra, rebuilt) = match self.active_filter.write().as_mut() {
+            Some(filter) => (is_filter_packet(&packet), filter.on_receive(&packet)),
+            None => (false, Vec::new()),
+        };
+        let result = if extra {
+            self.stats.write().packets_filter_extra_received += 1;
+            Ok(())
+        } else {
+            self.receive_data(packet, now, false)
+        };
+        for packet in rebuilt {
+            if self.receive_data(packet, now, true).is_ok() {
+                self.stats.write().packets_filter_supplied += 1;
+            }
+        }
+        result
+    }
+
+    /// Store a received or rebuilt data packet
+    fn receive_data(
+        &self,
+        packet: DataPacket,
+        now: Instant,
+        rebuilt: bool,
+    ) -> Result<(), ConnectionError> {
+        let seq = packet.seq_number();
+        let bytes = packet.payload.len();
+        let (retransmitted, timestamp) =
+            (packet.msg_number().retransmitted, packet.header.timestamp);
+        self.recv_buffer.write().push(packet)?;
+        self.ack_generator.write().on_data(bytes);
+        if retransmitted {
+            let mut stats = self.stats.write();
+            stats.packets_received_retransmitted += 1;
+            stats.bytes_received_retransmitted += bytes as u64;
+        } else if !rebuilt {
+            self.arrivals.write().on_packet(now, timestamp, bytes);
+        }
+        if self.held_losses.write().remove(&seq) {
+            self.stats.write().naks_avoided += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Get the time between timer-driven ACKs
+    pub fn ack_interval(&self) -> Duration {
+        self.ack_generator.read().ack_interval()
+    }
+
+    /// Set the time between timer-driven ACKs (10 ms by default)
+    pub fn set_ack_interval(&self, interval: Duration) {
+        self.ack_generator.write().set_ack_interval(interval);
+    }
+
+    /// Get the packets and bytes received before an ACK is due (0 = off)
+    pub fn ack_volume(&self) -> (u32, u64) {
+        self.ack_generator.read().volume_trigger()
+    }
+
+    /// Set the packets and bytes received before an ACK is due
+    ///
+    /// ACKs are otherwise due every ACK interval. At high bitrates that leaves
+    /// thousands of packets unacknowledged at a time; a volume trigger
+    /// keeps the sender's buffer and flow window moving. 0 disables a
+    /// trigger.
+    pub fn set_ack_volume(&self, packets: u32, bytes: u64) {
+        self.ack_generator
+            .write()
+            .set_volume_trigger(packets, bytes);
+    }
+
+    /// Check whether an ACK is due, by timer or received volume
+    pub fn ack_due(&self) -> bool {
+        let next_expected = self.recv_buffer.read().next_expected();
+        self.ack_generator.read().should_send_ack(next_expected - 1)
+    }
+
+    /// Create an ACK if one is due
+    ///
+    /// Call after processing received data, and periodically; returns
+    /// `None` when no ACK is due yet.
+    pub fn create_ack_if_due(&self) -> Option<ControlPacket> {
+        if self.ack_due() {
+            self.create_ack()
+        } else {
+            None
+        }
+    }
+
+    /// Create an ACK for the data received in order so far
+    ///
+    /// Returns `None` until the first packet has been received in order.
+    pub fn create_ack(&self) -> Option<ControlPacket> {
+        let next_expected = self.recv_buffer.read().next_expected();
+        // Receive buffers start at sequence zero
+        if next_expected == SeqNumber::new(0) {
+            return None;
+        }
+
+        let mut ack = AckInfo::new(next_expected - 1);
+        {
+            let arrivals = self.arrivals.read();
+            ack.packet_arrival_rate = arrivals.packet_arrival_rate();
+            ack.receive_rate_bps = arrivals.receive_rate_bps().min(u32::MAX as u64) as u32;
+        }
+        Some(
+            self.ack_generator
+                .write()
+                .generate_ack(ack, self.remote_socket_id.unwrap_or(0)),
+        )
+    }
+
+    /// Create a NAK reporting every gap in the received data
+    ///
+    /// Returns `None` if nothing is missing. Losses after the highest
+    /// received packet cannot be detected here; they need the sender's
+    /// timeout retransmission.
+    ///
+    /// With FEC active, losses are held back until one FEC group interval
+    /// of packets has arrived after them (`arq:onreq`), or never reported
+    /// (`arq:never`). A loss reported less than `nak_interval` ago is left
+    /// out.
+    pub fn create_nak(&self) -> Option<ControlPacket> {
+        self.create_nak_at(Instant::now())
+    }
+
+    fn create_nak_at(&self, now: Instant) -> Option<ControlPacket> {
+        let hold = self.nak_hold_packets();
+        let mut losses = {
+            let recv_buf = self.recv_buffer.read();
+            let highest = recv_buf.highest_received();
+            let mut losses = recv_buf.get_loss_list();
+            // Nothing received at all shows up as a gap at the highest slot
+            losses.retain(|&seq| seq.lt(highest));
+
+            let mut held = self.held_losses.write();
+            held.clear();
+            losses.retain(|&seq| {
+                let report = i64::from(seq.distance_to(highest)) >= hold;
+                if !report {
+                    held.insert(seq);
+                }
+                report
+            });
+            losses
+        };
+        if !self.nak_interval.is_zero() {
+            self.rate_limit_losses(&mut losses, now);
+        }
+
+        let mut ranges: Vec<LossRange> = Vec::new();
+        for seq in losses {
+            match ranges.last_mut() {
+                Some(range) if range.end.next() == seq => range.end = seq,
+                _ => ranges.push(LossRange::single(seq)),
+            }
+        }
+        if ranges.is_empty() {
+            return None;
+        }
+
+        Some(ControlPacket::new(
+            ControlType::Nak,
+            0,
+            0,
+            0,
+            self.remote_socket_id.unwrap_or(0),
+            NakInfo::new(ranges).to_bytes(),
+        ))
+    }
+
+    /// Drop losses reported within the NAK interval, recording the rest as
+    /// reported now
+    fn rate_limit_losses(&self, losses: &mut Vec<SeqNumber>, now: Instant) {
+        let mut reported = self.nak_reported.write();
+        // Forget losses that have since been filled
+        let previous = std::mem::take(&mut *reported);
+        let mut suppressed = 0;
+        losses.retain(|seq| match previous.get(seq) {
+            Some(&at) if now.saturating_duration_since(at) < self.nak_interval => {
+                reported.insert(*seq, at);
+                suppressed += 1;
+                false
+            }
+            _ => {
+                reported.insert(*seq, now);
+                true
+            }
+        });
+        self.stats.write().nak_reports_suppressed += suppressed;
+    }
+
+    /// Packets that must follow a loss before it is NAKed
+    fn nak_hold_packets(&self) -> i64 {
+        match self.filter.as_ref().map(|f| (f.arq(), f.group_span())) {
+            None | Some((ArqMode::Always, _)) => 0,
+            Some((ArqMode::OnRequest, span)) => i64::from(span),
+            Some((ArqMode::Never, _)) => i64::MAX,
+        }
+    }
+
+    /// Get connection statistics
+    pub fn stats(&self) -> ConnectionStats {
+        self.stats_at(Instant::now())
+    }
+
+    fn stats_at(&self, now: Instant) -> ConnectionStats {
+        let send_buf = self.send_buffer.read();
+        let arrivals = self.arrivals.read();
+        ConnectionStats {
+            receive_rate_bps: arrivals.receive_rate_bps(),
+            arrival_jitter_us: arrivals.jitter_us(),
+            send_buffer_packets: send_buf.len() as u64,
+            send_buffer_bytes: send_buf.bytes() as u64,
+            send_buffer_age: send_buf.oldest_unacked_age(now).unwrap_or_default(),
+            send_ttl_drops: send_buf.expired(),
+            send_drain_time: self.drain_time(&send_buf),
+            mss: self.mss,
+            labels: self.labels.clone(),
+            ..self.stats.read().clone()
+        }
+    }
+
+    /// Time to drain the send buffer at the measured ACK rate, or at the
+    /// sending rate cap until one is measured
+    fn drain_time(&self, send_buf: &SendBuffer) -> Option<Duration> {
+        let rate = send_buf
+            .drain_rate()
+            .filter(|rate| *rate > 0)
+            .or_else(|| Some(self.max_bandwidth()).filter(|cap| *cap > 0))?;
+        Some(Duration::from_secs_f64(
+            send_buf.bytes() as f64 / rate as f64,
+        ))
+    }
+
+    /// Check the latency added by the send queue against the alarm
+    ///
+    /// The queued latency is the larger of the oldest unacknowledged
+    /// packet's age and the estimated drain time. Returns an event when it
+    /// first exceeds `send_latency_alarm` times the TSBPD latency; call
+    /// periodically (e.g. with the stats). Another event is raised only
+    /// after the latency has dropped back below the alarm.
+    pub fn check_send_latency(&self) -> Option<SendLatencyEvent> {
+        self.check_send_latency_at(Instant::now())
+    }
+
+    fn check_send_latency_at(&self, now: Instant) -> Option<SendLatencyEvent> {
+        if self.send_latency_alarm == 0.0 {
+            return None;
+        }
+
+        let (age, drain_time, buffered_bytes) = {
+            let send_buf = self.send_buffer.read();
+            (
+                send_buf.oldest_unacked_age(now).unwrap_or_default(),
+                self.drain_time(&send_buf),
+                send_buf.bytes() as u64,
+            )
+        };
+        let queued_latency = age.max(drain_time.unwrap_or_default());
+        let latency = Duration::from_millis(u64::from(self.latency_ms));
+
+        let mut exceeded = self.send_latency_exceeded.write();
+        let over = queued_latency.as_secs_f64() > latency.as_secs_f64() * self.send_latency_alarm;
+        if !over || *exceeded {
+            *exceeded = over;
+            return None;
+        }
+        *exceeded = true;
+        self.record_event(format!(
+            "Send queue adds {:?} of {:?} latency",
+            queued_latency, latency
+        ));
+
+        let _span = self.span().entered();
+        tracing::warn!(
+            "Send queue adds {:?} of {:?} latency ({} bytes buffered)",
+            queued_latency,
+            latency,
+            buffered_bytes
+        );
+
+        Some(SendLatencyEvent {
+            timestamp: now,
+            queued_latency,
+            latency,
+            oldest_unacked_age: age,
+            drain_time,
+            buffered_bytes,
+            labels: self.labels.clone(),
+        })
+    }
+
+    /// Get the time without delivery progress that counts as a stall
+    pub fn stall_timeout(&self) -> Duration {
+        self.watchdog.read().timeout()
+    }
+
+    /// Set the time without delivery progress that counts as a stall
+    /// (0 = no watchdog, the default)
+    pub fn set_stall_timeout(&self, timeout: Duration) {
+        self.watchdog.write().set_timeout(timeout);
+    }
+
+    /// Get the action taken when delivery stalls
+    pub fn stall_action(&self) -> StallAction {
+        self.watchdog.read().action()
+    }
+
+    /// Set the action taken when delivery stalls
+    pub fn set_stall_action(&self, action: StallAction) {
+        self.watchdog.write().set_action(action);
+    }
+
+    /// Check delivery for a stall
+    ///
+    /// Delivery stalls when received packets wait behind the next expected
+    /// one and neither it nor the bytes delivered have moved for the stall
+    /// timeout. Returns an event, once per stall, after logging it and
+    /// taking the stall action: `Resync` gives up on the missing packets,
+    /// `Close` starts closing (the driver should then fail with
+    /// `ConnectionError::Stalled`). `Failover` is left to the bonding
+    /// group. Call periodically, like `check_timeout`.
+    pub fn check_stall(&self) -> Option<StallEvent> {
+        self.check_stall_at(Instant::now())
+    }
+
+    fn check_stall_at(&self, now: Instant) -> Option<StallEvent> {
+        let (next_expected, highest_received) = {
+            let recv_buf = self.recv_buffer.read();
+            (recv_buf.next_expected(), recv_buf.highest_received())
+        };
+        let delivered = self.stats.read().bytes_received;
+        let (stalled_for, action) = {
+            let mut watchdog = self.watchdog.write();
+            let waiting = next_expected.lt(highest_received);
+            let stalled_for = watchdog.observe(next_expected, delivered, waiting, now)?;
+            (stalled_for, watchdog.action())
+        };
+
+        let diagnostics = {
+            let recv_buf = self.recv_buffer.read();
+            StallDiagnostics {
+                next_expected,
+                highest_received,
+                missing_packets: recv_buf.get_loss_list().len(),
+                ready_messages: recv_buf.ready_message_count(),
+                recv_buffer_utilization: recv_buf.utilization(),
+                last_heard: self
+                    .liveness()
+                    .last_heard
+                    .map(|heard| now.saturating_duration_since(heard)),
+                stats: self.stats_at(now),
+            }
+        };
+        self.stats.write().stalls_detected += 1;
+        self.record_event(format!(
+            "Delivery stalled for {:?} at {} ({} missing); action: {}",
+            stalled_for,
+            next_expected.as_raw(),
+            diagnostics.missing_packets,
+            action
+        ));
+
+        let _span = self.span().entered();
+        tracing::warn!(
+            "Delivery stalled for {:?} at {} ({} missing up to {}, last heard {:?} ago); action: {}",
+            stalled_for,
+            next_expected.as_raw(),
+            diagnostics.missing_packets,
+            highest_received.as_raw(),
+            diagnostics.last_heard,
+            action
+        );
+
+        match action {
+            StallAction::Resync => {
+                let range = LossRange::new(next_expected, highest_received.prev());
+                self.recv_buffer.write().drop_range(range);
+                self.nak_reported
+                    .write()
+                    .retain(|seq, _| !range.contains(*seq));
+                self.stats.write().packets_lost += diagnostics.missing_packets as u64;
+            }
+            StallAction::Close => self.begin_close(),
+            StallAction::Log | StallAction::Failover => {}
+        }
+
+        Some(StallEvent {
+            timestamp: now,
+            stalled_for,
+            action,
+            diagnostics,
+            labels: self.labels.clone(),
+        })
+    }
+
+    /// Gather a diagnostics bundle for a support case
+    ///
+    /// The snapshot holds the state, negotiated options, buffer occupancy,
+    /// loss lists, the last `DEFAULT_EVENT_HISTORY` events and the stats
+    /// sampled by `check_timeout` once a second over the last minute.
+    pub fn diagnostics(&self) -> ConnectionDiagnostics {
+        self.diagnostics_at(Instant::now())
+    }
+
+    fn diagnostics_at(&self, now: Instant) -> ConnectionDiagnostics {
+        let send_buffer = {
+            let send_buf = self.send_buffer.read();
+            SendBufferDiagnostics {
+                capacity: send_buf.capacity(),
+                packets: send_buf.len(),
+                bytes: send_buf.bytes(),
+                oldest_unacked: send_buf.oldest_unacked().as_raw(),
+                next_seq: send_buf.next_seq().as_raw(),
+                oldest_unacked_age_ms: send_buf
+                    .oldest_unacked_age(now)
+                    .map_or(0, |age| age.as_millis() as u64),
+            }
+        };
+        let (recv_buffer, receiver_losses) = {
+            let recv_buf = self.recv_buffer.read();
+            (
+                RecvBufferDiagnostics {
+                    capacity: recv_buf.capacity(),
+                    utilization: recv_buf.utilization(),
+                    next_expected: recv_buf.next_expected().as_raw(),
+                    highest_received: recv_buf.highest_received().as_raw(),
+                    ready_messages: recv_buf.ready_message_count(),
+                },
+                LossSummary::from_seqs(recv_buf.get_loss_list()),
+            )
+        };
+        let liveness = self.liveness();
+        let history = self.history.read();
+
+        ConnectionDiagnostics {
+            version: env!("CARGO_PKG_VERSION"),
+            socket_id: self.local_socket_id,
+            peer_socket_id: self.remote_socket_id,
+            remote_addr: self.remote_addr.to_string(),
+            state: format!("{:?}", self.state()),
+            labels: self.labels.clone(),
+            options: NegotiatedOptions {
+                latency_ms: self.latency_ms,
+                mss: self.mss,
+                congestion: self.congestion_mode.to_string(),
+                stream_id: self.stream_id.clone(),
+                packet_filter: self.filter.as_ref().map(ToString::to_string),
+                retransmit_policy: format!("{:?}", self.retransmit_policy),
+                encrypted: self.peer_key_material.is_some(),
+                max_bandwidth: self.max_bandwidth(),
+                peer_max_bandwidth: self.peer_max_bandwidth(),
+                nak_interval_ms: self.nak_interval.as_millis() as u64,
+                peer_idle_timeout_ms: self.peer_idle_timeout().as_millis() as u64,
+                stall_timeout_ms: self.stall_timeout().as_millis() as u64,
+            },
+            send_buffer,
+            recv_buffer,
+            sender_losses: LossSummary::from_ranges(self.sender_losses.read().ranges()),
+            receiver_losses,
+            last_heard_ms: liveness
+                .last_heard
+                .map(|heard| diagnostics::age_ms(now, heard)),
+            missed_keepalives: liveness.missed_keepalives,
+            stats: self.stats_at(now),
+            events: history.events(now),
+            stats_history: history.samples(now),
+        }
+    }
+
+    /// Render `diagnostics` as one JSON blob to attach to a bug report
+    #[cfg(feature = "serde")]
+    pub fn dump_diagnostics(&self) -> String {
+        serde_json::to_string_pretty(&self.diagnostics()).expect("diagnostics serialize to JSON")
+    }
+
+    /// Start closing the connection
+    ///
+    /// No further data is sent or received, but data still buffered can be
+    /// collected with `drain`.
+    pub fn begin_close(&self) {
+        if !self.is_closed() {
+            self.set_state(ConnectionState::Closing);
+        }
+    }
+
+    /// Flush undelivered received data, reporting unrecoverable gaps
+    ///
+    /// Only valid while closing.
+    pub fn drain(&self) -> Result<ReceiveDrain, ConnectionError> {
+        if self.state() != ConnectionState::Closing {
+            return Err(ConnectionError::InvalidState);
+        }
+
+        let drain = self.recv_buffer.write().drain();
+        let mut stats = self.stats.write();
+        stats.packets_received += drain.messages.len() as u64;
+        stats.bytes_received += drain.messages.iter().map(|m| m.len() as u64).sum::<u64>();
+        stats.packets_lost += drain.gaps.iter().map(|g| g.len() as u64).sum::<u64>();
+        Ok(drain)
+    }
+
+    /// Start a graceful close
+    ///
+    /// New data is refused from now on, but what was already sent is still
+    /// sent, retransmitted and acknowledged. `poll_close` finishes the
+    /// close once the peer has acknowledged everything or `linger` has
+    /// passed.
+    pub fn shutdown(&self, linger: Duration) {
+        self.begin_close();
+        self.linger_until
+            .write()
+            .get_or_insert_with(|| Instant::now() + linger);
+    }
+
+    /// Finish a graceful close once the send buffer has drained
+    ///
+    /// Returns the Shutdown packet to send the peer, closing the
+    /// connection, once everything sent has been acknowledged or the
+    /// linger has passed. Returns None while lingering, or if `shutdown`
+    /// was not called.
+    pub fn poll_close(&self) -> Option<ControlPacket> {
+        self.poll_close_at(Instant::now())
+    }
+
+    fn poll_close_at(&self, now: Instant) -> Option<ControlPacket> {
+        let deadline = (*self.linger_until.read())?;
+        let unacked = self.send_buffer.read().len();
+        if self.is_closed() || (unacked > 0 && now < deadline) {
+            return None;
+        }
+        if unacked > 0 {
+            tracing::debug!(
+                "Closing connection to {} with {} packets unacknowledged",
+                self.remote_addr,
+                unacked
+            );
+        }
+        Some(self.close())
+    }
+
+    /// Close the connection now
+    ///
+    /// Data not drained beforehand is discarded. Returns the Shutdown
+    /// packet to send the peer, unless the close was its idea.
+    pub fn close(&self) -> ControlPacket {
+        self.set_state(ConnectionState::Closed);
+        self.create_shutdown()
+    }
+
+    /// Create a Shutdown control packet telling the peer we are done
+    pub fn create_shutdown(&self) -> ControlPacket {
+        ControlPacket::new(
+            ControlType::Shutdown,
+            0,
+            0,
+            0,
+            self.remote_socket_id.unwrap_or(0),
+            Bytes::new(),
+        )
+    }
+
+    /// Check if connection is established
+    pub fn is_connected(&self) -> bool {
+        self.state() == ConnectionState::Connected
+    }
+
+    /// Check if connection is closed
+    pub fn is_closed(&self) -> bool {
+        matches!(self.state(), ConnectionState::Closed)
+    }
+
+    /// Check if a message is waiting to be received
+    pub fn recv_ready(&self) -> bool {
+        self.is_connected() && self.recv_buffer.read().ready_message_count() > 0
+    }
+
+    /// Check if the send buffer has room for another packet
+    pub fn send_ready(&self) -> bool {
+        self.is_connected() && self.send_buffer.read().available_space() > 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::BufferSizing;
+    use crate::keepalive::DEFAULT_PEER_IDLE_TIMEOUT;
+    use crate::packet::{MsgNumber, PacketBoundary};
+
+    #[test]
+    fn test_connection_lifecycle() {
+        let conn = Connection::new(
+            12345,
+            "127.0.0.1:9000".parse().unwrap(),
+            "127.0.0.1:9001".parse().unwrap(),
+            SeqNumber::new(1000),
+            120,
+        );
+
+        assert_eq!(conn.state(), ConnectionState::Init);
+        assert!(!conn.is_connected());
+
+        // In a real scenario, handshake would be exchanged
+        // For now, just verify state transitions work
+        conn.close();
+        assert!(conn.is_closed());
+    }
+
+    #[test]
+    fn test_option_negotiation() {
+        let conn = Connection::new(
+            12345,
+            "127.0.0.1:9000".parse().unwrap(),
+            "127.0.0.1:9001".parse().unwrap(),
+            SeqNumber::new(1000),
+            120,
+        );
+
+        let mut peer_opts = SrtOptions::default_capabilities();
+        peer_opts.encryption = false; // Peer doesn't support encryption
+
+        let negotiated = conn.negotiate_options(&peer_opts);
+        assert!(!negotiated.encryption); // Should be disabled
+    }
+
+    #[test]
+    fn test_mss_negotiation() {
+        let mut conn = Connection::new(
+            12345,
+            "127.0.0.1:9000".parse().unwrap(),
+            "127.0.0.1:9001".parse().unwrap(),
+            SeqNumber::new(1000),
+            120,
+        );
+
+        assert!(matches!(
+            conn.set_mss(20),
+            Err(ConnectionError::InvalidMss(20))
+        ));
+        conn.set_mss(1400).unwrap();
+        assert_eq!(conn.create_handshake().udt.max_packet_size, 1400);
+        conn.set_send_buffer_size(64).unwrap();
+        conn.set_recv_buffer_size(1024).unwrap();
+
+        // Peer advertises a smaller MSS
+        let mut peer_hs = conn.create_handshake();
+        peer_hs.udt.max_packet_size = 1300;
+        conn.process_handshake(peer_hs).unwrap();
+
+        assert_eq!(conn.mss(), 1300);
+        assert_eq!(conn.max_payload_size(), 1256);
+        assert_eq!(conn.stats().mss, 1300);
+        // Buffers grow to keep their size in bytes
+        assert_eq!(conn.send_buffer_size(), 128);
+        assert_eq!(conn.recv_buffer_size(), 2048);
+        assert!(conn.set_mss(1500).is_err()); // Too late once connected
+
+        assert!(conn.send(&[0u8; 1256]).is_ok());
+        assert!(matches!(
+            conn.send(&[0u8; 1257]),
+            Err(ConnectionError::PayloadTooLarge {
+                size: 1257,
+                max: 1256
+            })
+        ));
+    }
+
+    #[test]
+    fn test_stream_id_learned_from_handshake() {
+        let mut caller = Connection::new(
+            1,
+            "127.0.0.1:9000".parse().unwrap(),
+            "127.0.0.1:9001".parse().unwrap(),
+            SeqNumber::new(1000),
+            120,
+        );
+        caller.set_stream_id(Some("live/cam1".to_string())).unwrap();
+
+        let mut listener = Connection::new(
+            2,
+            "127.0.0.1:9001".parse().unwrap(),
+            "127.0.0.1:9000".parse().unwrap(),
+            SeqNumber::new(2000),
+            120,
+        );
+        let bytes = caller.create_handshake().to_bytes();
+        listener
+            .process_handshake(SrtHandshake::from_bytes(&bytes).unwrap())
+            .unwrap();
+
+        assert_eq!(listener.stream_id(), Some("live/cam1"));
+    }
+
+    #[test]
+    fn test_peer_max_bandwidth() {
+        let mut sender = Connection::new(
+            1,
+            "127.0.0.1:9000".parse().unwrap(),
+            "127.0.0.1:9001".parse().unwrap(),
+            SeqNumber::new(1000),
+            120,
+        );
+        sender.set_max_bandwidth(200_000);
+
+        let mut receiver = Connection::new(
+            2,
+            "127.0.0.1:9001".parse().unwrap(),
+            "127.0.0.1:9000".parse().unwrap(),
+            SeqNumber::new(2000),
+            120,
+        );
+        receiver.set_requested_max_bandwidth(100_000);
+
+        // The receiver's ceiling travels in its handshake reply
+        let request = caller_request(&sender);
+        receiver.process_handshake(request).unwrap();
+        assert_eq!(receiver.peer_max_bandwidth(), 0);
+        let reply = receiver.create_handshake().to_bytes();
+        sender
+            .process_handshake(SrtHandshake::from_bytes(&reply).unwrap())
+            .unwrap();
+        assert_eq!(sender.peer_max_bandwidth(), 100_000);
+        assert_eq!(sender.max_bandwidth(), 100_000);
+
+        // Our own lower cap still applies
+        sender.set_max_bandwidth(50_000);
+        assert_eq!(sender.max_bandwidth(), 50_000);
+        sender.set_max_bandwidth(0);
+        assert_eq!(sender.max_bandwidth(), 100_000);
+
+        // Runtime updates arrive as control packets; 0 lifts the ceiling
+        receiver.set_requested_max_bandwidth(400_000);
+        let update = receiver.create_max_bw_request();
+        assert_eq!(update.header.dest_socket_id, 1);
+        sender.process_control(&update).unwrap();
+        assert_eq!(sender.max_bandwidth(), 400_000);
+
+        receiver.set_requested_max_bandwidth(0);
+        let update =
+            ControlPacket::from_bytes(&receiver.create_max_bw_request().to_bytes()).unwrap();
+        sender.process_control(&update).unwrap();
+        assert_eq!(sender.max_bandwidth(), 0);
+
+        let truncated = ControlPacket::new(
+            ControlType::UserDefined,
+            CONTROL_MAX_BW,
+            0,
+            0,
+            1,
+            bytes::Bytes::from_static(&[0; 4]),
+        );
+        assert!(sender.process_control(&truncated).is_err());
+    }
+
+    fn caller_request(caller: &Connection) -> SrtHandshake {
+        SrtHandshake::from_bytes(&caller.create_handshake().to_bytes()).unwrap()
+    }
+
+    fn filter_pair(
+        caller_filter: Option<&str>,
+        listener_filter: Option<&str>,
+    ) -> (Connection, Connection) {
+        let mut caller = Connection::new(
+            1,
+            "127.0.0.1:9000".parse().unwrap(),
+            "127.0.0.1:9001".parse().unwrap(),
+            SeqNumber::new(1000),
+            120,
+        );
+        caller.set_packet_filter(caller_filter).unwrap();
+
+        let mut listener = Connection::new(
+            2,
+            "127.0.0.1:9001".parse().unwrap(),
+            "127.0.0.1:9000".parse().unwrap(),
+            SeqNumber::new(2000),
+            120,
+        );
+        listener.set_packet_filter(listener_filter).unwrap();
+
+        (caller, listener)
+    }
+
+    #[test]
+    fn test_stream_mode() {
+        // Both sides must use the same mode
+        let (mut caller, mut listener) = filter_pair(None, None);
+        caller.set_stream_mode(true);
+        assert!(
+            caller_request(&caller)
+                .peer_capabilities()
+                .unwrap()
+                .stream_mode
+        );
+        let err = listener
+            .process_handshake(caller_request(&caller))
+            .unwrap_err();
+        assert_eq!(err.reject_reason(), Some(RejectReason::MessageApi));
+        assert_eq!(listener.state(), ConnectionState::Init);
+
+        listener.set_stream_mode(true);
+        listener.set_recv_buffer_size(MIN_BUFFER_PACKETS).unwrap();
+        caller.set_send_buffer_size(MIN_BUFFER_PACKETS).unwrap();
+        listener.process_handshake(caller_request(&caller)).unwrap();
+        caller
+            .process_handshake(listener.create_handshake())
+            .unwrap();
+        assert!(listener.stream_mode());
+
+        // Writes take what fits in the send buffer
+        let max = caller.max_payload_size();
+        let data: Vec<u8> = (0..max * MIN_BUFFER_PACKETS + 500)
+            .map(|i| i as u8)
+            .collect();
+        let written = caller.write(&data).unwrap();
+        assert_eq!(written, max * MIN_BUFFER_PACKETS);
+        assert!(matches!(
+            caller.write(&data[written..]),
+            Err(ConnectionError::Buffer(BufferError::Full))
+        ));
+
+        // Reads join payloads whatever the buffer size
+        for packet in caller.packets_to_send() {
+            listener.process_data_packet(packet).unwrap();
+        }
+        let mut received = Vec::new();
+        let mut buf = [0u8; 1000];
+        loop {
+            let n = listener.read(&mut buf).unwrap();
+            if n == 0 {
+                break;
+            }
+            received.extend_from_slice(&buf[..n]);
+        }
+        assert_eq!(received, &data[..written]);
+        assert_eq!(listener.stats().bytes_received, written as u64);
+        assert_eq!(listener.stats().packets_received, MIN_BUFFER_PACKETS as u64);
+    }
+
+    #[test]
+    fn test_congestion_mode_negotiation() {
+        // Live is the default and goes without the extension
+        let (caller, mut listener) = filter_pair(None, None);
+        assert_eq!(caller.congestion_mode(), CongestionMode::Live);
+        assert_eq!(caller_request(&caller).congestion, None);
+        listener.process_handshake(caller_request(&caller)).unwrap();
+
+        let (mut caller, mut listener) = filter_pair(None, None);
+        caller.set_congestion_mode(CongestionMode::File);
+        assert_eq!(caller_request(&caller).congestion.as_deref(), Some("file"));
+        let err = listener
+            .process_handshake(caller_request(&caller))
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ConnectionError::CongestionMismatch {
+                local: CongestionMode::Live,
+                ..
+            }
+        ));
+        assert_eq!(err.reject_reason(), Some(RejectReason::Congestion));
+        assert_eq!(listener.state(), ConnectionState::Init);
+
+        listener.set_congestion_mode(CongestionMode::File);
+        listener.process_handshake(caller_request(&caller)).unwrap();
+        assert_eq!(listener.congestion_mode(), CongestionMode::File);
+
+        let mut request = caller_request(&caller);
+        request.congestion = Some("vegas".into());
+        let (_, mut listener) = filter_pair(None, None);
+        assert!(matches!(
+            listener.process_handshake(request),
+            Err(ConnectionError::CongestionMismatch { peer, .. }) if peer == "vegas"
+        ));
+    }
+
+    #[test]
+    fn test_live_mode_paces_at_input_rate() {
+        let conn = connected_pair(RetransmitPolicy::NakOnly, true);
+        // Unpaced until the input rate is known
+        assert_eq!(conn.congestion_rate(), 0);
+        assert_eq!(conn.pacing_delay(1000), Duration::ZERO);
+        assert_eq!(conn.overhead_bandwidth(), 25);
+
+        conn.set_input_bandwidth(100_000);
+        assert_eq!(conn.input_bandwidth(), 100_000);
+        assert_eq!(conn.congestion_rate(), 125_000);
+        conn.pacing_delay(1250);
+        let delay = conn.pacing_delay(1250);
+        assert!(
+            delay > Duration::from_millis(9) && delay <= Duration::from_millis(10),
+            "{:?}",
+            delay
+        );
+
+        // Loss does not slow a live source down
+        conn.process_nak(&[LossRange::single(SeqNumber::new(0))]);
+        assert_eq!(conn.congestion_rate(), 125_000);
+    }
+
+    #[test]
+    fn test_retransmitted_arrivals() {
+        let conn = connected_pair(RetransmitPolicy::NakOnly, true);
+        let packet = |seq: u32, retransmitted: bool| {
+            let msg = MsgNumber {
+                retransmitted,
+                ..MsgNumber::new(seq + 1)
+            };
+            DataPacket::new(
+                SeqNumber::new(seq),
+                msg,
+                seq * 2000,
+                1,
+                Bytes::from(vec![0; 100]),
+            )
+        };
+
+        // Originals a couple of milliseconds apart
+        for seq in (0..10).step_by(2) {
+            conn.process_data_packet(packet(seq, false)).unwrap();
+            std::thread::sleep(Duration::from_millis(2));
+        }
+        // A burst of recovered packets would read as a far higher rate
+        for seq in (1..10).step_by(2) {
+            conn.process_data_packet(packet(seq, true)).unwrap();
+        }
+        for _ in 0..10 {
+            conn.process_data_packet(packet(1, true)).unwrap();
+        }
+
+        let stats = conn.stats();
+        assert_eq!(stats.packets_received_retransmitted, 15);
+        assert_eq!(stats.bytes_received_retransmitted, 1500);
+        assert!(stats.receive_rate_bps > 0 && stats.receive_rate_bps <= 50_000);
+
+        let ack = conn.create_ack().unwrap();
+        let ack = AckInfo::from_bytes(&ack.control_info).unwrap();
+        assert!(ack.packet_arrival_rate > 0 && ack.packet_arrival_rate <= 500);
+        assert_eq!(ack.receive_rate_bps as u64, stats.receive_rate_bps);
+    }
+
+    #[test]
+    fn test_send_bytes_shares_payload() {
+        let a = connected_pair(RetransmitPolicy::NakOnly, true);
+        let b = connected_pair(RetransmitPolicy::NakOnly, true);
+        let payload = Bytes::from(vec![7u8; 1000]);
+        a.send_bytes(payload.clone()).unwrap();
+        b.send_bytes(payload.clone()).unwrap();
+
+        let from_a = a.packets_to_send();
+        let from_b = b.packets_to_send();
+        assert_eq!(from_a[0].payload.as_ptr(), payload.as_ptr());
+        assert_eq!(from_b[0].payload.as_ptr(), payload.as_ptr());
+
+        // Each connection still writes its own header
+        let wire = from_a[0].to_bytes();
+        assert_eq!(DataPacket::from_bytes(&wire).unwrap().payload, payload);
+
+        assert!(matches!(
+            a.send_bytes(Bytes::from(vec![0; a.max_payload_size() + 1])),
+            Err(ConnectionError::PayloadTooLarge { .. })
+        ));
+    }
+
+    #[test]
+    fn test_send_message_fragments() {
+        let sender = connected_pair(RetransmitPolicy::NakOnly, true);
+        let receiver = connected_pair(RetransmitPolicy::NakOnly, true);
+        let max = sender.max_payload_size();
+        let message: Vec<u8> = (0..max * 2 + 100).map(|i| i as u8).collect();
+        assert!(sender.send(&message).is_err());
+        assert_eq!(sender.send_message(&message).unwrap(), message.len());
+        assert_eq!(sender.stats().packets_sent, 3);
+
+        let packets = sender.packets_to_send();
+        let boundaries: Vec<_> = packets.iter().map(|p| p.msg_number().boundary).collect();
+        assert_eq!(
+            boundaries,
+            vec![
+                PacketBoundary::First,
+                PacketBoundary::Subsequent,
+                PacketBoundary::Last
+            ]
+        );
+        let msg = packets[0].msg_number();
+        assert!(msg.in_order);
+        assert!(packets.iter().all(|p| p.msg_number().seq == msg.seq));
+
+        // Reassembled whole, whatever the arrival order
+        for packet in packets.into_iter().rev() {
+            receiver.process_data_packet(packet).unwrap();
+        }
+        assert_eq!(receiver.recv().unwrap().unwrap().as_ref(), &message[..]);
+
+        // Small messages are one packet with the next message number
+        sender.send_message(b"small").unwrap();
+        let packets = sender.packets_to_send();
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0].msg_number().boundary, PacketBoundary::Solo);
+        assert_eq!(packets[0].msg_number().seq, msg.seq + 1);
+
+        // Larger than the whole send buffer
+        assert!(matches!(
+            sender.send_message(&vec![0; max * sender.send_buffer_size() + 1]),
+            Err(ConnectionError::PayloadTooLarge { .. })
+        ));
+    }
+
+    #[test]
+    fn test_out_of_order_messages() {
+        let sender = connected_pair(RetransmitPolicy::NakOnly, true);
+        let receiver = connected_pair(RetransmitPolicy::NakOnly, true);
+        sender.set_message_in_order(false);
+        receiver.set_out_of_order_delivery(true);
+        assert!(!sender.message_in_order());
+        assert!(receiver.out_of_order_delivery());
+
+        sender.send_message(b"first").unwrap();
+        sender.send_message(b"second").unwrap();
+        let mut packets = sender.packets_to_send();
+        let second = packets.pop().unwrap();
+        receiver.process_data_packet(second).unwrap();
+        assert_eq!(receiver.recv().unwrap().unwrap().as_ref(), b"second");
+        receiver
+            .process_data_packet(packets.pop().unwrap())
+            .unwrap();
+        assert_eq!(receiver.recv().unwrap().unwrap().as_ref(), b"first");
+        assert!(receiver.recv().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_recv_and_send_ready() {
+        let idle = Connection::new(
+            1,
+            "127.0.0.1:9000".parse().unwrap(),
+            "127.0.0.1:9001".parse().unwrap(),
+            SeqNumber::new(0),
+            120,
+        );
+        assert!(!idle.recv_ready());
+        assert!(!idle.send_ready());
+
+        let sender = connected_pair(RetransmitPolicy::NakOnly, true);
+        let receiver = connected_pair(RetransmitPolicy::NakOnly, true);
+        *sender.send_buffer.write() = SendBuffer::new(1, Duration::from_secs(1));
+        assert!(sender.send_ready());
+        sender.send(b"data").unwrap();
+        assert!(!sender.send_ready());
+
+        assert!(!receiver.recv_ready());
+        for packet in sender.packets_to_send() {
+            receiver.process_data_packet(packet).unwrap();
+        }
+        assert!(receiver.recv_ready());
+        receiver.recv().unwrap().unwrap();
+        assert!(!receiver.recv_ready());
+    }
+
+    #[test]
+    fn test_pacing_burst() {
+        let conn = connected_pair(RetransmitPolicy::NakOnly, true);
+        conn.set_max_bandwidth(1_000_000);
+        conn.set_pacing_burst(2000);
+        for _ in 0..3 {
+            assert_eq!(conn.pacing_delay(1000), Duration::ZERO);
+        }
+        assert!(conn.pacing_delay(1000) > Duration::ZERO);
+
+        // Kept when the cap changes
+        conn.set_max_bandwidth(2_000_000);
+        assert_eq!(conn.pacing_burst(), 2000);
+        assert_eq!(conn.pacing_delay(1000), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_file_mode_backs_off_on_loss() {
+        let mut conn = connected_pair(RetransmitPolicy::NakAndTimeout, true);
+        conn.set_congestion_mode(CongestionMode::File);
+        for _ in 0..4 {
+            conn.send(b"data").unwrap();
+        }
+        conn.packets_to_send();
+        let rate = conn.congestion_rate();
+        assert!(rate > 0);
+
+        conn.process_nak(&[LossRange::single(SeqNumber::new(1))]);
+        assert!(conn.congestion_rate() < rate);
+    }
+
+    #[test]
+    fn test_source_time_passthrough() {
+        let (mut caller, mut listener) = filter_pair(None, None);
+        let origin = srctime::from_unix_micros(1_700_000_000_000_000);
+        caller.set_time_origin(origin);
+
+        let caller_hs = caller.create_handshake().to_bytes();
+        let listener_hs = listener.create_handshake().to_bytes();
+        listener
+            .process_handshake(SrtHandshake::from_bytes(&caller_hs).unwrap())
+            .unwrap();
+        caller
+            .process_handshake(SrtHandshake::from_bytes(&listener_hs).unwrap())
+            .unwrap();
+        assert_eq!(listener.peer_time_origin(), Some(origin));
+
+        let capture = origin + Duration::from_millis(40);
+        caller.send_with_source_time(b"frame", capture).unwrap();
+        assert!(matches!(
+            caller.send_with_source_time(b"frame", origin - Duration::from_secs(1)),
+            Err(ConnectionError::SourceTime(SourceTimeError::BeforeOrigin))
+        ));
+
+        caller.process_nak(&[LossRange::single(SeqNumber::new(0))]);
+        for packet in caller.packets_to_retransmit() {
+            listener.process_data_packet(packet).unwrap();
+        }
+
+        let message = listener.recv_message().unwrap().unwrap();
+        assert_eq!(&message.data[..], b"frame");
+        assert_eq!(message.timestamp, 40_000);
+        assert_eq!(message.source_time, Some(capture));
+    }
+
+    #[test]
+    fn test_handshake_probe() {
+        let (mut caller, mut listener) = filter_pair(None, None);
+        caller.set_probe_plan(Some(ProbePlan {
+            packets: 4,
+            packet_size: 500,
+            spacing_us: 0,
+        }));
+
+        let request = SrtHandshake::from_bytes(&caller.create_handshake().to_bytes()).unwrap();
+        listener.process_handshake(request.clone()).unwrap();
+        let agreement = listener.create_agreement(request).to_bytes();
+        caller
+            .process_agreement(SrtHandshake::from_bytes(&agreement).unwrap())
+            .unwrap();
+
+        let probes = caller.create_probe_packets();
+        assert_eq!(probes.len(), 4);
+        assert!(caller.create_probe_packets().is_empty());
+        assert!(listener.create_probe_report().is_none());
+        for probe in probes {
+            let probe = ControlPacket::from_bytes(&probe.to_bytes()).unwrap();
+            listener.process_control(&probe).unwrap();
+        }
+        assert!(caller.probe_result().is_none());
+
+        let report = listener.create_probe_report().unwrap();
+        assert!(listener.create_probe_report().is_none());
+        caller.process_control(&report).unwrap();
+        let result = caller.probe_result().unwrap();
+        assert_eq!(result.packets_sent, 4);
+        assert_eq!(result.packets_received, 4);
+        assert_eq!(result.loss_rate, 0.0);
+
+        // Without a plan nothing is probed
+        let (caller, mut listener) = filter_pair(None, None);
+        listener
+            .process_handshake(caller.create_handshake())
+            .unwrap();
+        assert!(listener
+            .create_agreement(caller.create_handshake())
+            .probe
+            .is_none());
+        assert!(caller.create_probe_packets().is_empty());
+    }
+
+    #[test]
+    fn test_drain_on_close() {
+        let (caller, mut listener) = filter_pair(None, None);
+        listener
+            .process_handshake(caller.create_handshake())
+            .unwrap();
+        assert!(matches!(
+            listener.drain(),
+            Err(ConnectionError::InvalidState)
+        ));
+
+        for seq in [0u32, 1, 3] {
+            listener
+                .process_data_packet(DataPacket::new(
+                    SeqNumber::new(seq),
+                    MsgNumber::new(seq),
+                    0,
+                    1,
+                    bytes::Bytes::from(vec![seq as u8; 4]),
+                ))
+                .unwrap();
+        }
+        listener.recv().unwrap().unwrap();
+
+        listener.begin_close();
+        assert!(listener.recv().is_err());
+        let drain = listener.drain().unwrap();
+        assert_eq!(drain.messages.len(), 2);
+        assert_eq!(drain.gaps, vec![LossRange::single(SeqNumber::new(2))]);
+        assert_eq!(listener.stats().packets_lost, 1);
+
+        listener.close();
+        assert!(listener.is_closed());
+    }
+
+    #[test]
+    fn test_packet_filter_negotiation() {
+        let (caller, mut listener) = filter_pair(Some("fec,cols:10"), Some("fec,rows:5"));
+        listener
+            .process_handshake(caller.create_handshake())
+            .unwrap();
+        assert_eq!(
+            listener.packet_filter().unwrap().to_string(),
+            "fec,cols:10,rows:5"
+        );
+        assert!(listener.options.packet_filter);
+
+        // Listener without a filter adopts the caller's
+        let (caller, mut listener) = filter_pair(Some("fec,cols:4"), None);
+        listener
+            .process_handshake(caller.create_handshake())
+            .unwrap();
+        assert_eq!(listener.packet_filter().unwrap().get("cols"), Some("4"));
+    }
+
+    #[test]
+    fn test_packet_filter_rejection() {
+        let (caller, mut listener) = filter_pair(Some("fec,cols:10"), Some("fec,cols:8"));
+        let result = listener.process_handshake(caller.create_handshake());
+        assert!(matches!(
+            result,
+            Err(ConnectionError::Filter(
+                FilterError::ParameterMismatch { .. }
+            ))
+        ));
+        assert_eq!(listener.state(), ConnectionState::Init);
+
+        // Peer that does not support filters
+        let (caller, mut listener) = filter_pair(None, Some("fec,cols:10"));
+        let result = listener.process_handshake(caller.create_handshake());
+        assert!(matches!(
+            result,
+            Err(ConnectionError::Filter(FilterError::PeerUnsupported))
+        ));
+    }
+
+    #[test]
+    fn test_fec_rebuilds_lost_packet() {
+        let (mut caller, mut listener) = filter_pair(Some("fec,cols:4,arq:never"), None);
+        listener
+            .process_handshake(caller.create_handshake())
+            .unwrap();
+        caller
+            .process_handshake(listener.create_handshake())
+            .unwrap();
+        assert!(caller.has_active_filter() && listener.has_active_filter());
+        // Room for the parity header
+        assert_eq!(
+            caller.max_payload_size(),
+            max_payload_for_mss(DEFAULT_MSS) - 8
+        );
+
+        for i in 0..8u8 {
+            caller.send(&[i; 100]).unwrap();
+        }
+        let packets = caller.packets_to_send();
+        assert_eq!(packets.len(), 10);
+        assert_eq!(caller.stats().packets_filter_extra_sent, 2);
+        for packet in packets {
+            if packet.seq_number() != SeqNumber::new(2) || is_filter_packet(&packet) {
+                listener.process_data_packet(packet).unwrap();
+            }
+        }
+        for i in 0..8u8 {
+            assert_eq!(listener.recv().unwrap().unwrap(), vec![i; 100]);
+        }
+        let stats = listener.stats();
+        assert_eq!(stats.packets_filter_extra_received, 2);
+        assert_eq!(stats.packets_filter_supplied, 1);
+    }
+
+    #[test]
+    fn test_fec_columns_rebuild_burst() {
+        let (mut caller, mut listener) =
+            filter_pair(Some("fec,cols:4,rows:2,layout:staircase,arq:never"), None);
+        listener
+            .process_handshake(caller.create_handshake())
+            .unwrap();
+        caller
+            .process_handshake(listener.create_handshake())
+            .unwrap();
+
+        for i in 0..8u8 {
+            caller.send(&[i; 100]).unwrap();
+        }
+        // Two in the first row are lost; its column groups bring them back
+        for packet in caller.packets_to_send() {
+            if packet.seq_number().as_raw() >= 2 || is_filter_packet(&packet) {
+                listener.process_data_packet(packet).unwrap();
+            }
+        }
+        for i in 0..8u8 {
+            assert_eq!(listener.recv().unwrap().unwrap(), vec![i; 100]);
+        }
+        let sent = caller.packet_filter_stats().unwrap();
+        let received = listener.packet_filter_stats().unwrap();
+        assert_eq!(received.packets_received, sent.packets_sent);
+        assert_eq!(received.packets_rebuilt, 2);
+        assert_eq!(listener.stats().packets_filter_supplied, 2);
+    }
+
+    #[test]
+    fn test_packet_filter_factory() {
+        let (caller, mut listener) = filter_pair(Some("fec,cols:4"), None);
+        listener.set_packet_filter_factory(|config| {
+            Err(FilterError::UnknownType(format!(
+                "{} (disabled)",
+                config.kind
+            )))
+        });
+        assert!(matches!(
+            listener.process_handshake(caller.create_handshake()),
+            Err(ConnectionError::Filter(FilterError::UnknownType(_)))
+        ));
+
+        let (caller, mut listener) = filter_pair(Some("fec,cols:4"), None);
+        listener.set_packet_filter_factory(|_| Err(FilterError::PeerUnsupported));
+        listener.clear_packet_filter_factory();
+        listener
+            .process_handshake(caller.create_handshake())
+            .unwrap();
+        assert!(listener.has_active_filter());
+        let (caller, mut listener) = filter_pair(None, None);
+        listener
+            .process_handshake(caller.create_handshake())
+            .unwrap();
+        assert!(!listener.has_active_filter());
+    }
+
+    #[test]
+    fn test_custom_filter_type() {
+        struct Passthrough;
+        impl PacketFilter for Passthrough {
+            fn on_send(&mut self, _: &DataPacket) -> Vec<DataPacket> {
+                Vec::new()
+            }
+            fn on_receive(&mut self, _: &DataPacket) -> Vec<DataPacket> {
+                Vec::new()
+            }
+        }
+
+        // Without a factory for it the type is unknown
+        let (caller, mut listener) = filter_pair(Some("dup,copies:2"), None);
+        assert!(matches!(
+            listener.process_handshake(caller.create_handshake()),
+            Err(ConnectionError::Filter(FilterError::UnknownType(_)))
+        ));
+
+        let (caller, mut listener) = filter_pair(Some("dup,copies:2"), Some("dup,spread:3"));
+        listener.set_packet_filter_factory(|config| match config.kind.as_str() {
+            "dup" => Ok(Box::new(Passthrough) as Box<dyn PacketFilter>),
+            _ => builtin_filter(config),
+        });
+        listener
+            .process_handshake(caller.create_handshake())
+            .unwrap();
+        assert!(listener.has_active_filter());
+        assert_eq!(
+            listener.packet_filter().unwrap().to_string(),
+            "dup,copies:2,spread:3"
+        );
+    }
+
+    /// A key material message header with the given cipher, salt and an
+    /// even key's worth of wrapped bytes
+    fn key_material(cipher: u8) -> Bytes {
+        let mut km = vec![
+            0x12, 0x20, 0x29, 0x01, 0, 0, 0, 0, cipher, 0, 2, 0, 0, 0, 4, 4,
+        ];
+        km.resize(16 + 16 + 24, 0xAB);
+        Bytes::from(km)
+    }
+
+    #[test]
+    fn test_enforce_encryption_rejects_downgrades() {
+        let encrypted_pair = || {
+            let (mut caller, mut listener) = filter_pair(None, None);
+            caller.set_key_material(Some(key_material(2))).unwrap();
+            listener.set_enforce_encryption(true);
+            (caller, listener)
+        };
+        let rejected = |listener: &mut Connection, handshake: SrtHandshake| {
+            let result = listener.process_handshake(handshake);
+            assert!(matches!(
+                result,
+                Err(ConnectionError::Rejected(RejectReason::Unsecure))
+            ));
+            assert_eq!(listener.state(), ConnectionState::Init);
+        };
+
+        let (caller, mut listener) = encrypted_pair();
+        listener.process_handshake(caller_request(&caller)).unwrap();
+        assert_eq!(listener.peer_key_material(), Some(&key_material(2)));
+
+        // Key material stripped on the way
+        let (caller, mut listener) = encrypted_pair();
+        let mut handshake = caller_request(&caller);
+        handshake.key_material = None;
+        rejected(&mut listener, handshake);
+
+        // Crypto capability cleared
+        let (caller, mut listener) = encrypted_pair();
+        let mut handshake = caller_request(&caller);
+        let ext = handshake.srt_ext.as_mut().unwrap();
+        let mut options = ext.options();
+        options.encryption = false;
+        ext.srt_flags = options.to_flags();
+        rejected(&mut listener, handshake);
+
+        // Null cipher
+        let (mut caller, mut listener) = encrypted_pair();
+        caller.set_key_material(Some(key_material(0))).unwrap();
+        rejected(&mut listener, caller_request(&caller));
+
+        // Without enforcement clear-text peers are still accepted
+        let (caller, mut listener) = filter_pair(None, None);
+        listener.process_handshake(caller_request(&caller)).unwrap();
+        assert_eq!(listener.peer_key_material(), None);
+
+        // The caller learns why it was refused
+        let (mut caller, _) = encrypted_pair();
+        let mut refusal = caller_request(&caller);
+        refusal.reject(RejectReason::Unsecure);
+        let refusal = SrtHandshake::from_bytes(&refusal.to_bytes()).unwrap();
+        rejected(&mut caller, refusal);
+    }
+
+    fn connected_pair(policy: RetransmitPolicy, peer_rexmit: bool) -> Connection {
+        let mut conn = Connection::new(
+            1,
+            "127.0.0.1:9000".parse().unwrap(),
+            "127.0.0.1:9001".parse().unwrap(),
+            SeqNumber::new(0),
+            120,
+        );
+        conn.set_retransmit_policy(policy);
+
+        let mut peer_caps = SrtOptions::default_capabilities();
+        peer_caps.rexmit_flag = peer_rexmit;
+        let peer =
+            SrtHandshake::new_request(0, 2, "127.0.0.1:9000".parse().unwrap(), peer_caps, 120, 120);
+        conn.process_handshake(peer).unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_nak_retransmission() {
+        let mut conn = connected_pair(RetransmitPolicy::NakOnly, true);
+        // Keep the packets past the 10s below
+        conn.set_send_ttl_factor(100.0);
+        for _ in 0..5 {
+            conn.send(b"data").unwrap();
+        }
+
+        conn.process_nak(&[LossRange::new(SeqNumber::new(1), SeqNumber::new(2))]);
+        let packets = conn.packets_to_retransmit_at(Instant::now() + Duration::from_secs(10));
+        let seqs: Vec<u32> = packets.iter().map(|p| p.seq_number().as_raw()).collect();
+        assert_eq!(seqs, vec![1, 2]);
+
+        // NAK-only: old unacknowledged packets are not retransmitted
+        assert!(conn
+            .packets_to_retransmit_at(Instant::now() + Duration::from_secs(10))
+            .is_empty());
+
+        let stats = conn.stats();
+        assert_eq!(stats.nak_retransmissions, 2);
+        assert_eq!(stats.timeout_retransmissions, 0);
+        assert_eq!(stats.packets_retransmitted, 2);
+        assert_eq!(stats.packets_reported_lost, 2);
+    }
+
+    #[test]
+    fn test_timeout_retransmission() {
+        let conn = connected_pair(RetransmitPolicy::NakAndTimeout, true);
+        assert_eq!(conn.retransmit_policy(), RetransmitPolicy::NakAndTimeout);
+        for _ in 0..4 {
+            conn.send(b"data").unwrap();
+        }
+
+        let mut ack = AckInfo::new(SeqNumber::new(1));
+        ack.rtt_us = 10_000;
+        conn.process_ack(&ack);
+        assert_eq!(conn.stats().rtt_us, 10_000);
+
+        // Nothing due before the RTO
+        assert!(conn.packets_to_retransmit_at(Instant::now()).is_empty());
+
+        let later = Instant::now() + conn.retransmit_timeout() + Duration::from_millis(1);
+        let packets = conn.packets_to_retransmit_at(later);
+        let seqs: Vec<u32> = packets.iter().map(|p| p.seq_number().as_raw()).collect();
+        assert_eq!(seqs, vec![2, 3]);
+
+        let stats = conn.stats();
+        assert_eq!(stats.timeout_retransmissions, 2);
+        assert_eq!(stats.nak_retransmissions, 0);
+    }
+
+    #[test]
+    fn test_duplicate_nak_suppression() {
+        let mut conn = connected_pair(RetransmitPolicy::NakOnly, true);
+        conn.set_retransmit_suppression(true);
+        for _ in 0..5 {
+            conn.send(b"data").unwrap();
+        }
+        let mut ack = AckInfo::new(SeqNumber::new(0));
+        ack.rtt_us = 10_000;
+        conn.process_ack(&ack);
+        let window = conn.retransmit_suppression_window();
+        assert_eq!(window, Duration::from_millis(15));
+
+        let seqs = |packets: Vec<DataPacket>| -> Vec<u32> {
+            packets.iter().map(|p| p.seq_number().as_raw()).collect()
+        };
+        let now = Instant::now();
+        conn.process_nak(&[LossRange::new(SeqNumber::new(1), SeqNumber::new(2))]);
+        assert_eq!(seqs(conn.packets_to_retransmit_at(now)), vec![1, 2]);
+
+        // Re-reported while the retransmissions are in flight
+        conn.process_nak(&[LossRange::new(SeqNumber::new(1), SeqNumber::new(3))]);
+        let soon = now + Duration::from_millis(5);
+        assert_eq!(seqs(conn.packets_to_retransmit_at(soon)), vec![3]);
+        assert_eq!(conn.stats().duplicate_naks_suppressed, 2);
+
+        // Still missing a window later: the retransmission was lost too
+        conn.process_nak(&[LossRange::single(SeqNumber::new(1))]);
+        let later = now + window + Duration::from_millis(1);
+        assert_eq!(seqs(conn.packets_to_retransmit_at(later)), vec![1]);
+
+        let stats = conn.stats();
+        assert_eq!(stats.nak_retransmissions, 4);
+        assert_eq!(stats.duplicate_naks_suppressed, 2);
+    }
+
+    #[test]
+    fn test_timeout_retransmission_requires_rexmit_flag() {
+        let conn = connected_pair(RetransmitPolicy::NakAndTimeout, false);
+        assert_eq!(conn.retransmit_policy(), RetransmitPolicy::NakOnly);
+
+        conn.send(b"data").unwrap();
+        assert!(conn
+            .packets_to_retransmit_at(Instant::now() + Duration::from_secs(10))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_send_ttl_follows_latency() {
+        let mut conn = Connection::new(
+            1,
+            "127.0.0.1:9000".parse().unwrap(),
+            "127.0.0.1:9001".parse().unwrap(),
+            SeqNumber::new(0),
+            120,
+        );
+        // Short latencies keep the one second floor
+        assert_eq!(conn.send_ttl(), Duration::from_secs(1));
+        conn.set_latency(2000).unwrap();
+        assert_eq!(conn.send_ttl(), Duration::from_millis(2500));
+        conn.set_send_ttl_factor(2.0);
+        assert_eq!(conn.send_ttl(), Duration::from_secs(4));
+        conn.set_send_buffer_size(64).unwrap();
+        assert_eq!(conn.send_ttl(), Duration::from_secs(4));
+
+        // The peer's larger latency wins in the handshake
+        let peer = SrtHandshake::new_request(
+            0,
+            2,
+            "127.0.0.1:9000".parse().unwrap(),
+            SrtOptions::default_capabilities(),
+            3000,
+            3000,
+        );
+        conn.process_handshake(peer).unwrap();
+        assert_eq!(conn.send_ttl(), Duration::from_secs(6));
+
+        // Unacknowledged packets past the TTL are dropped, not resent
+        for _ in 0..4 {
+            conn.send(b"data").unwrap();
+        }
+        conn.packets_to_send();
+        conn.process_nak(&[LossRange::new(SeqNumber::new(0), SeqNumber::new(1))]);
+        let later = Instant::now() + Duration::from_secs(7);
+        assert!(conn.packets_to_retransmit_at(later).is_empty());
+        assert_eq!(conn.stats().send_ttl_drops, 4);
+        assert_eq!(conn.create_drop_requests().len(), 1);
+    }
+
+    #[test]
+    fn test_expired_packets_announced() {
+        let sender = connected_pair(RetransmitPolicy::NakOnly, true);
+        let receiver = connected_pair(RetransmitPolicy::NakOnly, true);
+        for _ in 0..4 {
+            sender.send(b"data").unwrap();
+        }
+        for packet in sender.packets_to_send() {
+            if packet.seq_number().as_raw() != 1 {
+                receiver.process_data_packet(packet).unwrap();
+            }
+        }
+        sender.process_ack(&AckInfo::new(SeqNumber::new(0)));
+
+        // 1-3 outlive the TTL unacknowledged and are given up on, with no
+        // NAK asking for them
+        let late = Instant::now() + sender.send_buffer.read().ttl() * 2;
+        assert!(sender.packets_to_retransmit_at(late).is_empty());
+        let drops = sender.create_drop_requests();
+        assert_eq!(drops.len(), 1);
+        assert_eq!(
+            DropReqInfo::from_bytes(&drops[0].control_info)
+                .unwrap()
+                .range,
+            LossRange::new(SeqNumber::new(1), SeqNumber::new(3))
+        );
+        assert!(sender.create_drop_requests().is_empty());
+
+        // The receiver skips the gap and stops NAKing it
+        assert!(receiver.create_nak().is_some());
+        receiver.process_control(&drops[0]).unwrap();
+        assert!(receiver.create_nak().is_none());
+        assert_eq!(receiver.recv_buffer.read().next_expected().as_raw(), 4);
+    }
+
+    #[test]
+    fn test_skip_sequence() {
+        let sender = connected_pair(RetransmitPolicy::NakOnly, true);
+        let receiver = connected_pair(RetransmitPolicy::NakOnly, true);
+
+        sender.send(b"a").unwrap();
+        assert_eq!(sender.skip_sequence().unwrap(), SeqNumber::new(1));
+        assert_eq!(sender.skip_sequence().unwrap(), SeqNumber::new(2));
+        sender.send(b"b").unwrap();
+
+        let sent: Vec<_> = sender.packets_to_send();
+        assert_eq!(
+            sent.iter()
+                .map(|p| p.seq_number().as_raw())
+                .collect::<Vec<_>>(),
+            vec![0, 3]
+        );
+        let drops = sender.create_drop_requests();
+        assert_eq!(drops.len(), 1);
+        assert_eq!(
+            DropReqInfo::from_bytes(&drops[0].control_info)
+                .unwrap()
+                .range,
+            LossRange::new(SeqNumber::new(1), SeqNumber::new(2))
+        );
+
+        // The receiver hears of the skip before the gap and never NAKs it
+        receiver.process_control(&drops[0]).unwrap();
+        for packet in sent {
+            receiver.process_data_packet(packet).unwrap();
+        }
+        assert!(receiver.create_nak().is_none());
+        assert_eq!(receiver.recv_buffer.read().next_expected().as_raw(), 4);
+    }
+
+    #[test]
+    fn test_drop_request() {
+        let sender = connected_pair(RetransmitPolicy::NakOnly, true);
+        let receiver = connected_pair(RetransmitPolicy::NakOnly, true);
+        // Packets expire from the send buffer at once
+        *sender.send_buffer.write() = SendBuffer::new(64, Duration::ZERO);
+
+        for _ in 0..6 {
+            sender.send(b"data").unwrap();
+        }
+        for packet in sender.packets_to_send() {
+            if !(1..=3).contains(&packet.seq_number().as_raw()) {
+                receiver.process_data_packet(packet).unwrap();
+            }
+        }
+        assert!(sender.create_drop_requests().is_empty());
+
+        // The expiry is announced, but the announcement is lost
+        std::thread::sleep(Duration::from_millis(1));
+        sender.send_buffer.write().drop_expired();
+        assert!(sender.packets_to_retransmit().is_empty());
+        assert_eq!(sender.create_drop_requests().len(), 1);
+
+        // 1-3 are NAKed after the sender has discarded them
+        let nak = receiver.create_nak().unwrap();
+        sender.process_control(&nak).unwrap();
+        assert!(sender.packets_to_retransmit().is_empty());
+
+        let drops = sender.create_drop_requests();
+        assert_eq!(drops.len(), 1);
+        assert_eq!(sender.stats().drop_requests_sent, 2);
+        let drop = ControlPacket::from_bytes(&drops[0].to_bytes()).unwrap();
+        assert_eq!(drop.control_type(), ControlType::DropReq);
+
+        // The receiver stops waiting and passes the range on
+        assert_eq!(receiver.recv().unwrap().unwrap().len(), 4);
+        assert!(receiver.recv().unwrap().is_none());
+        receiver.process_control(&drop).unwrap();
+        assert!(receiver.create_nak().is_none());
+        for _ in 0..2 {
+            assert!(receiver.recv().unwrap().is_some());
+        }
+        assert_eq!(
+            receiver.take_dropped_range(),
+            Some(LossRange::new(SeqNumber::new(1), SeqNumber::new(3)))
+        );
+        assert!(receiver.take_dropped_range().is_none());
+        assert_eq!(receiver.stats().drop_requests_received, 1);
+
+        let truncated = ControlPacket::new(
+            ControlType::DropReq,
+            0,
+            0,
+            0,
+            1,
+            bytes::Bytes::from_static(&[0; 4]),
+        );
+        assert!(receiver.process_control(&truncated).is_err());
+
+        // A header edited into a data header is refused, not a panic
+        let mut mislabelled = drop.clone();
+        mislabelled.header.seq_or_control = 7;
+        assert!(matches!(
+            receiver.process_control(&mislabelled),
+            Err(ConnectionError::Packet(_))
+        ));
+    }
+
+    /// A receiver with packets 0, 2 and 3 of four, 0 read
+    fn stalled_receiver(action: StallAction) -> Connection {
+        let sender = connected_pair(RetransmitPolicy::NakOnly, true);
+        let receiver = connected_pair(RetransmitPolicy::NakOnly, true);
+        receiver.set_stall_timeout(Duration::from_secs(1));
+        receiver.set_stall_action(action);
+        for _ in 0..4 {
+            sender.send(b"data").unwrap();
+        }
+        for packet in sender.packets_to_send() {
+            if packet.seq_number().as_raw() != 1 {
+                receiver.process_data_packet(packet).unwrap();
+            }
+        }
+        assert!(receiver.recv().unwrap().is_some());
+        assert!(receiver.recv().unwrap().is_none());
+        receiver
+    }
+
+    #[test]
+    fn test_stall_watchdog() {
+        let receiver = stalled_receiver(StallAction::Log);
+        let start = Instant::now();
+        assert!(receiver.check_stall_at(start).is_none());
+        let event = receiver
+            .check_stall_at(start + Duration::from_secs(2))
+            .unwrap();
+        assert_eq!(event.stalled_for, Duration::from_secs(2));
+        assert_eq!(event.action, StallAction::Log);
+        assert_eq!(event.diagnostics.next_expected, SeqNumber::new(1));
+        assert_eq!(event.diagnostics.highest_received, SeqNumber::new(3));
+        assert_eq!(event.diagnostics.missing_packets, 1);
+        assert_eq!(receiver.stats().stalls_detected, 1);
+        // Reported once per stall, and logging changes nothing
+        assert!(receiver
+            .check_stall_at(start + Duration::from_secs(3))
+            .is_none());
+        assert!(receiver.recv().unwrap().is_none());
+        assert_eq!(receiver.state(), ConnectionState::Connected);
+
+        // Off by default
+        let receiver = stalled_receiver(StallAction::Log);
+        receiver.set_stall_timeout(Duration::ZERO);
+        assert!(receiver.check_stall_at(start).is_none());
+        assert!(receiver
+            .check_stall_at(start + Duration::from_secs(60))
+            .is_none());
+    }
+
+    #[test]
+    fn test_stall_actions() {
+        let start = Instant::now();
+        let later = start + Duration::from_secs(1);
+
+        // Resync gives up on 1 and delivers what arrived after it
+        let receiver = stalled_receiver(StallAction::Resync);
+        assert!(receiver.check_stall_at(start).is_none());
+        assert!(receiver.check_stall_at(later).is_some());
+        for _ in 0..2 {
+            assert!(receiver.recv().unwrap().is_some());
+        }
+        assert!(receiver.create_nak().is_none());
+        assert!(receiver
+            .check_stall_at(later + Duration::from_secs(10))
+            .is_none());
+
+        let receiver = stalled_receiver(StallAction::Close);
+        assert!(receiver.check_stall_at(start).is_none());
+        assert!(receiver.check_stall_at(later).is_some());
+        assert_eq!(receiver.state(), ConnectionState::Closing);
+    }
+
+    #[test]
+    fn test_keepalive_liveness() {
+        let conn = connected_pair(RetransmitPolicy::NakOnly, true);
+        let peer = connected_pair(RetransmitPolicy::NakOnly, true);
+        conn.set_keepalive_interval(Duration::ZERO);
+        assert_eq!(conn.liveness().missed_keepalives, 0);
+
+        // Unanswered keep-alives count as missed
+        let request = conn.create_keepalive().unwrap();
+        assert_eq!(request.control_type(), ControlType::KeepAlive);
+        assert_eq!(request.header.dest_socket_id, 2);
+        assert!(conn.create_keepalive().is_some());
+        assert!(conn.create_keepalive().is_some());
+        assert_eq!(conn.liveness().missed_keepalives, 2);
+        assert_eq!(conn.liveness().score, 0.5);
+
+        // The peer echoes the latest; the echo resets the count and
+        // measures the round trip
+        let request = conn.create_keepalive().unwrap();
+        peer.process_control(&request).unwrap();
+        let echo = peer.create_keepalive().unwrap();
+        std::thread::sleep(Duration::from_millis(2));
+        conn.process_control(&echo).unwrap();
+
+        let liveness = conn.liveness();
+        assert_eq!(liveness.missed_keepalives, 0);
+        assert!(liveness.last_heard.is_some());
+        assert!(liveness.rtt_us.unwrap() >= 2_000);
+        assert!(conn.stats().rtt_us > 0);
+    }
+
+    #[test]
+    fn test_graceful_close() {
+        let conn = connected_pair(RetransmitPolicy::NakOnly, true);
+        assert!(conn.poll_close().is_none());
+        for _ in 0..3 {
+            conn.send(b"data").unwrap();
+        }
+        conn.shutdown(Duration::from_secs(1));
+        assert_eq!(conn.state(), ConnectionState::Closing);
+        assert!(conn.send(b"late").is_err());
+        // Already-sent data still goes out and is retransmitted
+        assert_eq!(conn.packets_to_send().len(), 3);
+        conn.process_nak(&[LossRange::single(SeqNumber::new(1))]);
+        assert_eq!(conn.packets_to_retransmit().len(), 1);
+
+        conn.process_ack(&AckInfo::new(SeqNumber::new(1)));
+        assert!(conn.poll_close().is_none());
+        conn.process_ack(&AckInfo::new(SeqNumber::new(2)));
+        let shutdown = conn.poll_close().unwrap();
+        assert_eq!(shutdown.control_type(), ControlType::Shutdown);
+        assert_eq!(shutdown.header.dest_socket_id, 2);
+        assert!(conn.is_closed());
+        assert!(conn.poll_close().is_none());
+    }
+
+    #[test]
+    fn test_close_after_linger() {
+        let conn = connected_pair(RetransmitPolicy::NakOnly, true);
+        conn.send(b"data").unwrap();
+        let start = Instant::now();
+        conn.shutdown(Duration::from_millis(500));
+        assert!(conn.poll_close_at(start).is_none());
+        assert!(conn
+            .poll_close_at(start + Duration::from_millis(600))
+            .is_some());
+        assert!(conn.is_closed());
+    }
+
+    #[test]
+    fn test_peer_idle_timeout() {
+        let conn = connected_pair(RetransmitPolicy::NakOnly, true);
+        assert_eq!(conn.peer_idle_timeout(), DEFAULT_PEER_IDLE_TIMEOUT);
+        let start = Instant::now();
+        conn.send(b"data").unwrap();
+        assert!(conn
+            .check_timeout_at(start + Duration::from_secs(4))
+            .is_ok());
+
+        // Anything heard from the peer restarts the clock
+        let peer = connected_pair(RetransmitPolicy::NakOnly, true);
+        conn.process_control(&peer.create_keepalive().unwrap())
+            .unwrap();
+        let later = Instant::now() + Duration::from_secs(4);
+        assert!(conn.check_timeout_at(later).is_ok());
+
+        let timeout = later + Duration::from_secs(2);
+        assert!(matches!(
+            conn.check_timeout_at(timeout),
+            Err(ConnectionError::PeerTimeout(idle)) if idle >= DEFAULT_PEER_IDLE_TIMEOUT
+        ));
+        assert_eq!(conn.state(), ConnectionState::Closing);
+        assert!(conn.is_timed_out());
+        assert!(matches!(
+            conn.send(b"data"),
+            Err(ConnectionError::PeerTimeout(_))
+        ));
+        // Buffered data can still be collected
+        assert!(conn.drain().is_ok());
+        assert!(conn.check_timeout().is_err());
+
+        let conn = connected_pair(RetransmitPolicy::NakOnly, true);
+        conn.set_peer_idle_timeout(Duration::ZERO);
+        assert!(conn
+            .check_timeout_at(start + Duration::from_secs(60))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_ack_nak_feedback() {
+        let sender = connected_pair(RetransmitPolicy::NakOnly, true);
+        let receiver = connected_pair(RetransmitPolicy::NakOnly, true);
+        assert!(receiver.create_ack().is_none());
+        assert!(receiver.create_nak().is_none());
+
+        for _ in 0..5 {
+            sender.send(b"data").unwrap();
+        }
+        let packets = sender.packets_to_send();
+        assert_eq!(packets.len(), 5);
+        assert!(sender.packets_to_send().is_empty());
+
+        // Lose packets 1 and 2
+        for packet in packets {
+            if !(1..=2).contains(&packet.seq_number().as_raw()) {
+                receiver.process_data_packet(packet).unwrap();
+            }
+        }
+
+        let nak = receiver.create_nak().unwrap();
+        let ranges = NakInfo::from_bytes(&nak.control_info).unwrap().loss_ranges;
+        assert_eq!(
+            ranges,
+            vec![LossRange::new(SeqNumber::new(1), SeqNumber::new(2))]
+        );
+        let ack = receiver.create_ack().unwrap();
+        assert_eq!(
+            AckInfo::from_bytes(&ack.control_info).unwrap().ack_seq,
+            SeqNumber::new(0)
+        );
+
+        sender.process_control(&ack).unwrap();
+        sender.process_control(&nak).unwrap();
+        for packet in sender.packets_to_retransmit() {
+            assert!(packet.msg_number().retransmitted);
+            receiver.process_data_packet(packet).unwrap();
+        }
+
+        assert!(receiver.create_nak().is_none());
+        let ack = receiver.create_ack().unwrap();
+        assert_eq!(
+            AckInfo::from_bytes(&ack.control_info).unwrap().ack_seq,
+            SeqNumber::new(4)
+        );
+        assert_eq!(sender.stats().nak_retransmissions, 2);
+    }
+
+    #[test]
+    fn test_ack_volume_and_batching() {
+        let sender = connected_pair(RetransmitPolicy::NakOnly, true);
+        let receiver = connected_pair(RetransmitPolicy::NakOnly, true);
+        receiver.set_ack_interval(Duration::from_secs(60));
+        receiver.set_ack_volume(0, 3000);
+        assert_eq!(receiver.ack_volume(), (0, 3000));
+
+        for _ in 0..6 {
+            sender.send(&[0u8; 1000]).unwrap();
+        }
+        let mut acks = Vec::new();
+        for packet in sender.packets_to_send() {
+            receiver.process_data_packet(packet).unwrap();
+            acks.extend(receiver.create_ack_if_due());
+        }
+        // One ACK per 3000 bytes, none in between
+        assert_eq!(acks.len(), 2);
+        assert!(!receiver.ack_due());
+        assert_eq!(
+            AckInfo::from_bytes(&acks[0].control_info).unwrap().ack_seq,
+            SeqNumber::new(2)
+        );
+
+        // A burst applies the highest ACK once; the rest are stale
+        let mut burst = vec![acks[1].clone(), acks[0].clone()];
+        burst.push(sender.create_keepalive().unwrap());
+        sender.process_control_batch(&burst).unwrap();
+        assert_eq!(sender.send_buffer.read().len(), 0);
+        sender.process_control(&acks[1]).unwrap();
+
+        let stats = sender.stats();
+        assert_eq!(stats.acks_received, 3);
+        assert_eq!(stats.acks_stale, 2);
+    }
+
+    #[test]
+    fn test_fec_nak_hold() {
+        let receiver_with = |filter: &str| {
+            let mut conn = Connection::new(
+                1,
+                "127.0.0.1:9000".parse().unwrap(),
+                "127.0.0.1:9001".parse().unwrap(),
+                SeqNumber::new(0),
+                120,
+            );
+            let mut peer = SrtHandshake::new_request(
+                0,
+                2,
+                "127.0.0.1:9000".parse().unwrap(),
+                SrtOptions::default_capabilities(),
+                120,
+                120,
+            );
+            peer.filter_config = Some(filter.to_string());
+            conn.process_handshake(peer).unwrap();
+            conn
+        };
+        let nak_ranges = |conn: &Connection| {
+            conn.create_nak()
+                .map(|nak| NakInfo::from_bytes(&nak.control_info).unwrap().loss_ranges)
+        };
+
+        let sender = connected_pair(RetransmitPolicy::NakOnly, true);
+        for _ in 0..12 {
+            sender.send(b"data").unwrap();
+        }
+        let packets = sender.packets_to_send();
+
+        // One group interval is 8 packets: loss 2 is due at 10, loss 3 isn't
+        let receiver = receiver_with("fec,cols:4,rows:2");
+        for packet in &packets[..=10] {
+            if !(2..=3).contains(&packet.seq_number().as_raw()) {
+                receiver.process_data_packet(packet.clone()).unwrap();
+            }
+        }
+        assert_eq!(
+            nak_ranges(&receiver),
+            Some(vec![LossRange::single(SeqNumber::new(2))])
+        );
+
+        // Packet 3 shows up (e.g. FEC repaired it) before it was NAKed
+        receiver.process_data_packet(packets[3].clone()).unwrap();
+        assert_eq!(receiver.stats().naks_avoided, 1);
+        receiver.process_data_packet(packets[2].clone()).unwrap();
+        assert_eq!(receiver.stats().naks_avoided, 1);
+        assert_eq!(nak_ranges(&receiver), None);
+
+        // arq:never leaves every loss to FEC
+        let receiver = receiver_with("fec,cols:4,arq:never");
+        for packet in &packets {
+            if packet.seq_number().as_raw() != 1 {
+                receiver.process_data_packet(packet.clone()).unwrap();
+            }
+        }
+        assert_eq!(nak_ranges(&receiver), None);
+        receiver.process_data_packet(packets[1].clone()).unwrap();
+        assert_eq!(receiver.stats().naks_avoided, 1);
+
+        // arq:always reports losses at once
+        let receiver = receiver_with("fec,cols:4,rows:2,arq:always");
+        receiver.process_data_packet(packets[0].clone()).unwrap();
+        receiver.process_data_packet(packets[2].clone()).unwrap();
+        assert_eq!(
+            nak_ranges(&receiver),
+            Some(vec![LossRange::single(SeqNumber::new(1))])
+        );
+    }
+
+    #[test]
+    fn test_retransmit_budget() {
+        let conn = connected_pair(RetransmitPolicy::NakOnly, true);
+        for _ in 0..20 {
+            conn.send(&[0u8; 100]).unwrap();
+        }
+        conn.packets_to_send();
+
+        // No sending rate cap: nothing is held back
+        conn.set_retransmit_budget(0.1);
+        conn.process_nak(&[LossRange::new(SeqNumber::new(0), SeqNumber::new(3))]);
+        assert_eq!(conn.packets_to_retransmit().len(), 4);
+
+        // 10% of 100 kB/s allows 1000 bytes at once, then 10 bytes per ms
+        conn.set_max_bandwidth(100_000);
+        conn.set_retransmit_budget(0.1);
+        let now = Instant::now();
+        conn.process_nak(&[LossRange::new(SeqNumber::new(0), SeqNumber::new(19))]);
+        let packets = conn.packets_to_retransmit_at(now);
+        assert_eq!(packets.len(), 10);
+        assert_eq!(packets[9].seq_number(), SeqNumber::new(9));
+        assert_eq!(conn.stats().retransmissions_suppressed, 10);
+
+        // Suppressed packets wait for the receiver to report them again
+        assert!(conn.packets_to_retransmit_at(now).is_empty());
+        conn.process_nak(&[LossRange::new(SeqNumber::new(10), SeqNumber::new(19))]);
+        let later = now + Duration::from_millis(50);
+        assert_eq!(conn.packets_to_retransmit_at(later).len(), 5);
+
+        let stats = conn.stats();
+        assert_eq!(stats.nak_retransmissions, 19);
+        assert_eq!(stats.retransmissions_suppressed, 15);
+    }
+
+    #[test]
+    fn test_nak_interval() {
+        let sender = connected_pair(RetransmitPolicy::NakOnly, true);
+        let mut receiver = connected_pair(RetransmitPolicy::NakOnly, true);
+        receiver.set_nak_interval(Duration::from_millis(50));
+        let nak_ranges = |conn: &Connection, at: Instant| {
+            conn.create_nak_at(at)
+                .map(|nak| NakInfo::from_bytes(&nak.control_info).unwrap().loss_ranges)
+        };
+
+        for _ in 0..8 {
+            sender.send(b"data").unwrap();
+        }
+        let packets = sender.packets_to_send();
+        for packet in &packets[..6] {
+            if packet.seq_number() != SeqNumber::new(2) {
+                receiver.process_data_packet(packet.clone()).unwrap();
+            }
+        }
+
+        let now = Instant::now();
+        assert_eq!(
+            nak_ranges(&receiver, now),
+            Some(vec![LossRange::single(SeqNumber::new(2))])
+        );
+        assert_eq!(nak_ranges(&receiver, now + Duration::from_millis(10)), None);
+        assert_eq!(receiver.stats().nak_reports_suppressed, 1);
+
+        // A new loss is reported at once, without repeating the old one
+        receiver.process_data_packet(packets[7].clone()).unwrap();
+        assert_eq!(
+            nak_ranges(&receiver, now + Duration::from_millis(20)),
+            Some(vec![LossRange::single(SeqNumber::new(6))])
+        );
+
+        // Both are due again once the interval has passed
+        assert_eq!(
+            nak_ranges(&receiver, now + Duration::from_millis(80)),
+            Some(vec![
+                LossRange::single(SeqNumber::new(2)),
+                LossRange::single(SeqNumber::new(6))
+            ])
+        );
+        assert_eq!(receiver.stats().nak_reports_suppressed, 2);
+    }
+
+    #[test]
+    fn test_agreement_checked_before_connecting() {
+        let new_conn = |id, latency| {
+            Connection::new(
+                id,
+                "127.0.0.1:9000".parse().unwrap(),
+                "127.0.0.1:9001".parse().unwrap(),
+                SeqNumber::new(1000),
+                latency,
+            )
+        };
+        let mut caller = new_conn(1, 120);
+        let request = caller.create_handshake();
+        let mut listener = new_conn(2, 300);
+        listener.process_handshake(request.clone()).unwrap();
+        let agreement = listener.create_agreement(request.clone());
+
+        let check = |caller: &mut Connection, adjust: &dyn Fn(&mut SrtHandshake)| {
+            let mut reply = agreement.clone();
+            adjust(&mut reply);
+            match caller.process_agreement(reply) {
+                Err(ConnectionError::Handshake(e)) => e,
+                other => panic!("agreement accepted: {:?}", other),
+            }
+        };
+        assert!(matches!(
+            check(&mut caller, &|hs| hs.udt.handshake_type = 1),
+            HandshakeError::NotAgreement(1)
+        ));
+        assert!(matches!(
+            check(&mut caller, &|hs| hs.udt.syn_cookie = 0xdead),
+            HandshakeError::CookieMismatch {
+                expected: 0,
+                actual: 0xdead
+            }
+        ));
+        assert!(matches!(
+            check(&mut caller, &|hs| hs.udt.initial_seq_num = 7),
+            HandshakeError::InitialSeqMismatch {
+                expected: 1000,
+                actual: 7
+            }
+        ));
+        assert!(matches!(
+            check(&mut caller, &|hs| hs.udt.socket_id = 0),
+            HandshakeError::MissingSocketId
+        ));
+        assert!(matches!(
+            check(&mut caller, &|hs| hs.srt_ext = None),
+            HandshakeError::MissingExtension
+        ));
+        assert!(matches!(
+            check(&mut caller, &|hs| hs.srt_ext.as_mut().unwrap().latency =
+                50 << 16 | 50),
+            HandshakeError::LatencyBelowRequest {
+                requested: 120,
+                agreed: 50
+            }
+        ));
+        assert!(matches!(
+            check(&mut caller, &|hs| hs.srt_ext.as_mut().unwrap().latency =
+                61_000),
+            HandshakeError::LatencyOutOfRange(61_000)
+        ));
+        assert!(!caller.is_connected());
+
+        // A rejection is reported as such
+        let mut refusal = request;
+        refusal.reject(RejectReason::Peer);
+        assert!(matches!(
+            caller.process_agreement(refusal),
+            Err(ConnectionError::Rejected(RejectReason::Peer))
+        ));
+
+        // The agreement carries the negotiated latency
+        caller.process_agreement(agreement.clone()).unwrap();
+        assert!(caller.is_connected());
+        assert_eq!(caller.remote_socket_id(), Some(2));
+        assert_eq!(caller.latency_ms(), 300);
+        assert!(matches!(
+            caller.process_agreement(agreement),
+            Err(ConnectionError::InvalidState)
+        ));
+    }
+
+    #[test]
+    fn test_handshake_rtt_seed() {
+        let new_conn = |id| {
+            Connection::new(
+                id,
+                "127.0.0.1:9000".parse().unwrap(),
+                "127.0.0.1:9001".parse().unwrap(),
+                SeqNumber::new(0),
+                120,
+            )
+        };
+        let mut caller = new_conn(1);
+        let mut listener = new_conn(2);
+        assert_eq!(caller.retransmit_timeout(), Duration::from_millis(300));
+
+        listener
+            .process_handshake(caller.create_handshake())
+            .unwrap();
+        std::thread::sleep(Duration::from_millis(10));
+        caller
+            .process_handshake(listener.create_handshake())
+            .unwrap();
+
+        let rtt_us = caller.handshake_rtt_us().unwrap();
+        assert!((10_000..300_000).contains(&rtt_us), "{}", rtt_us);
+        assert_eq!(caller.stats().rtt_us, rtt_us);
+        assert_eq!(
+            caller.retransmit_timeout(),
+            Duration::from_micros(3 * rtt_us as u64)
+        );
+        // The listener only answers, so it has nothing to time
+        assert_eq!(listener.handshake_rtt_us(), None);
+
+        // A reply to a repeated request is ambiguous and not used
+        let mut caller = new_conn(3);
+        let request = caller.create_handshake();
+        caller.create_handshake();
+        let mut listener = new_conn(4);
+        listener.process_handshake(request).unwrap();
+        caller
+            .process_handshake(listener.create_handshake())
+            .unwrap();
+        assert_eq!(caller.handshake_rtt_us(), None);
+        assert_eq!(caller.stats().rtt_us, 0);
+    }
+
+    #[test]
+    fn test_latency_negotiation() {
+        let mut conn = Connection::new(
+            1,
+            "127.0.0.1:9000".parse().unwrap(),
+            "127.0.0.1:9001".parse().unwrap(),
+            SeqNumber::new(0),
+            120,
+        );
+        assert!(matches!(
+            conn.set_latency(MAX_LATENCY_MS + 1),
+            Err(ConnectionError::InvalidLatency(_))
+        ));
+        conn.set_latency(200).unwrap();
+
+        let mut peer = SrtHandshake::new_request(
+            0,
+            2,
+            "127.0.0.1:9000".parse().unwrap(),
+            SrtOptions::default_capabilities(),
+            500,
+            80,
+        );
+        peer.udt.max_packet_size = 1500;
+        conn.process_handshake(peer).unwrap();
+        assert_eq!(conn.latency_ms(), 500);
+        assert!(matches!(
+            conn.set_latency(100),
+            Err(ConnectionError::InvalidState)
+        ));
+    }
+
+    #[test]
+    fn test_buffer_sizes() {
+        let mut conn = Connection::new(
+            1,
+            "127.0.0.1:9000".parse().unwrap(),
+            "127.0.0.1:9001".parse().unwrap(),
+            SeqNumber::new(0),
+            120,
+        );
+        assert_eq!(conn.recv_buffer_size(), DEFAULT_BUFFER_PACKETS);
+        assert!(matches!(
+            conn.set_recv_buffer_size(MIN_BUFFER_PACKETS - 1),
+            Err(ConnectionError::InvalidBufferSize(_))
+        ));
+        assert!(matches!(
+            conn.set_send_buffer_size(MAX_BUFFER_PACKETS + 1),
+            Err(ConnectionError::InvalidBufferSize(_))
+        ));
+
+        conn.set_recv_buffer_size(1000).unwrap();
+        conn.set_send_buffer_size(64).unwrap();
+        assert_eq!(conn.recv_buffer_size(), 1024);
+        assert_eq!(conn.send_buffer_size(), 64);
+        assert_eq!(conn.create_handshake().udt.max_flow_window, 1024);
+
+        conn.set_max_bandwidth(1_000_000);
+        assert_eq!(conn.max_bandwidth(), 1_000_000);
+        assert_eq!(conn.pacing_delay(1000), Duration::ZERO);
+        assert!(conn.pacing_delay(1000) > Duration::ZERO);
+    }
+
+    #[test]
+    fn test_resize_buffers_while_connected() {
+        let conn = connected_pair(RetransmitPolicy::NakOnly, true);
+        let sizes = (conn.send_buffer_size(), conn.recv_buffer_size());
+        for _ in 0..40 {
+            conn.send(&[0; 100]).unwrap();
+        }
+
+        assert!(matches!(
+            conn.resize_buffers(MAX_BUFFER_PACKETS + 1, 1024),
+            Err(ConnectionError::InvalidBufferSize(_))
+        ));
+        // The 40 unacknowledged packets do not fit; neither buffer changes
+        assert!(matches!(
+            conn.resize_buffers(32, 16384),
+            Err(ConnectionError::Buffer(BufferError::Full))
+        ));
+        assert_eq!((conn.send_buffer_size(), conn.recv_buffer_size()), sizes);
+
+        let sizing = BufferSizing::for_stream(50_000_000, Duration::from_secs(8), conn.mss());
+        conn.resize_buffers(sizing.send_capacity, sizing.recv_capacity)
+            .unwrap();
+        assert_eq!(conn.send_buffer_size(), 65536);
+        assert_eq!(conn.recv_buffer_size(), 65536);
+        assert_eq!(conn.stats().send_buffer_packets, 40);
+    }
+
+    #[test]
+    fn test_send_queue_latency() {
+        let mut conn = connected_pair(RetransmitPolicy::NakOnly, true);
+        conn.set_send_latency_alarm(0.5);
+        let start = Instant::now();
+
+        for _ in 0..10 {
+            conn.send(&[0; 1000]).unwrap();
+        }
+        let stats = conn.stats_at(start);
+        assert_eq!(stats.send_buffer_packets, 10);
+        assert_eq!(stats.send_buffer_bytes, 10_000);
+        assert_eq!(stats.send_drain_time, None);
+
+        // Until ACKs arrive the drain time comes from the rate cap
+        conn.set_max_bandwidth(100_000);
+        assert_eq!(
+            conn.stats_at(start).send_drain_time,
+            Some(Duration::from_millis(100))
+        );
+        conn.set_max_bandwidth(0);
+
+        // 120ms latency: alarm above 60ms in the queue, raised once
+        assert!(conn
+            .check_send_latency_at(start + Duration::from_millis(40))
+            .is_none());
+        let event = conn
+            .check_send_latency_at(start + Duration::from_millis(70))
+            .unwrap();
+        assert_eq!(event.latency, Duration::from_millis(120));
+        assert_eq!(event.buffered_bytes, 10_000);
+        assert!(event.queued_latency > Duration::from_millis(60));
+        assert!(conn
+            .check_send_latency_at(start + Duration::from_millis(80))
+            .is_none());
+
+        // Draining the queue clears the alarm so it can fire again
+        conn.process_ack(&AckInfo::new(SeqNumber::new(9)));
+        let stats = conn.stats_at(start + Duration::from_millis(90));
+        assert_eq!(stats.send_buffer_packets, 0);
+        assert_eq!(stats.send_buffer_age, Duration::ZERO);
+        assert!(conn
+            .check_send_latency_at(start + Duration::from_millis(90))
+            .is_none());
+        conn.send(&[0; 1000]).unwrap();
+        assert!(conn
+            .check_send_latency_at(Instant::now() + Duration::from_millis(70))
+            .is_some());
+
+        conn.set_send_latency_alarm(0.0);
+        assert!(conn
+            .check_send_latency_at(Instant::now() + Duration::from_secs(1))
+            .is_none());
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let receiver = stalled_receiver(StallAction::Log);
+        let start = Instant::now();
+        receiver.check_timeout_at(start).unwrap();
+        receiver
+            .check_timeout_at(start + Duration::from_millis(500))
+            .unwrap();
+        assert!(receiver.check_stall_at(start).is_none());
+        assert!(receiver
+            .check_stall_at(start + Duration::from_secs(2))
+            .is_some());
+        receiver.record_event("Operator note");
+
+        let diagnostics = receiver.diagnostics();
+        assert_eq!(diagnostics.state, "Connected");
+        assert_eq!(diagnostics.peer_socket_id, Some(2));
+        assert_eq!(diagnostics.options.latency_ms, 120);
+        assert_eq!(diagnostics.recv_buffer.next_expected, 1);
+        assert_eq!(diagnostics.recv_buffer.highest_received, 3);
+        assert_eq!(diagnostics.receiver_losses.ranges, vec![(1, 1)]);
+        assert_eq!(diagnostics.sender_losses.packets, 0);
+        // One sample per interval
+        assert_eq!(diagnostics.stats_history.len(), 1);
+
+        let events: Vec<_> = diagnostics.events.iter().map(|e| &e.message).collect();
+        assert!(events.iter().any(|e| e.starts_with("Handshake with")));
+        assert!(events.iter().any(|e| e.contains("-> Connected")));
+        assert!(events.iter().any(|e| e.starts_with("Delivery stalled")));
+        assert_eq!(events.last().unwrap().as_str(), "Operator note");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_dump_diagnostics() {
+        let receiver = stalled_receiver(StallAction::Log);
+        let json: serde_json::Value = serde_json::from_str(&receiver.dump_diagnostics()).unwrap();
+        assert_eq!(json["state"], "Connected");
+        assert_eq!(json["receiver_losses"]["packets"], 1);
+        assert_eq!(json["stats"]["packets_received"], 1);
+        assert!(json["events"].as_array().is_some_and(|e| !e.is_empty()));
     }
 }
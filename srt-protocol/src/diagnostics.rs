@@ -0,0 +1,335 @@
+//! Diagnostics bundles for support cases
+//!
+//! A bug report that describes symptoms ("the stream froze after a
+//! minute") leaves out what is needed to explain them. `Connection`
+//! keeps its last few notable events and a short stats history, and
+//! `Connection::diagnostics` gathers them with its state, negotiated
+//! options, buffer occupancy and loss lists in one `ConnectionDiagnostics`.
+//! With the `serde` feature `Connection::dump_diagnostics` renders that as
+//! a single JSON blob to attach to the report.
+//!
+//! Times are given as ages in milliseconds before the snapshot, so a dump
+//! reads the same wherever it is opened.
+
+use crate::connection::ConnectionStats;
+use crate::labels::Labels;
+use crate::loss::LossRange;
+use crate::sequence::SeqNumber;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Events a connection keeps for its diagnostics
+pub const DEFAULT_EVENT_HISTORY: usize = 32;
+
+/// Stats samples a connection keeps for its diagnostics
+pub const DEFAULT_STATS_HISTORY: usize = 60;
+
+/// Minimum time between two stats samples
+pub const STATS_SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Loss ranges listed in a `LossSummary`; the rest are only counted
+pub const MAX_SUMMARY_RANGES: usize = 16;
+
+/// A notable event in a connection's life
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DiagnosticEvent {
+    /// Milliseconds before the snapshot
+    pub age_ms: u64,
+    /// What happened
+    pub message: String,
+}
+
+/// Counters sampled for the stats history
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct StatsSample {
+    /// Milliseconds before the snapshot
+    pub age_ms: u64,
+    /// Total packets sent
+    pub packets_sent: u64,
+    /// Total packets received
+    pub packets_received: u64,
+    /// Total packets lost
+    pub packets_lost: u64,
+    /// Total packets retransmitted
+    pub packets_retransmitted: u64,
+    /// Total bytes sent
+    pub bytes_sent: u64,
+    /// Total bytes received
+    pub bytes_received: u64,
+    /// Round-trip time (microseconds)
+    pub rtt_us: u32,
+    /// Packets in the send buffer
+    pub send_buffer_packets: u64,
+}
+
+impl StatsSample {
+    fn from_stats(stats: &ConnectionStats) -> Self {
+        StatsSample {
+            age_ms: 0,
+            packets_sent: stats.packets_sent,
+            packets_received: stats.packets_received,
+            packets_lost: stats.packets_lost,
+            packets_retransmitted: stats.packets_retransmitted,
+            bytes_sent: stats.bytes_sent,
+            bytes_received: stats.bytes_received,
+            rtt_us: stats.rtt_us,
+            send_buffer_packets: stats.send_buffer_packets,
+        }
+    }
+}
+
+/// Summary of a loss list
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct LossSummary {
+    /// Packets in the list
+    pub packets: usize,
+    /// Number of contiguous ranges
+    pub range_count: usize,
+    /// The first `MAX_SUMMARY_RANGES` ranges as (first, last) sequences
+    pub ranges: Vec<(u32, u32)>,
+}
+
+impl LossSummary {
+    /// Summarize loss ranges, merging adjacent ones
+    pub fn from_ranges(ranges: impl IntoIterator<Item = LossRange>) -> Self {
+        let mut merged: Vec<LossRange> = Vec::new();
+        for range in ranges {
+            match merged.last_mut() {
+                Some(last) => match last.try_merge(&range) {
+                    Some(joined) => *last = joined,
+                    None => merged.push(range),
+                },
+                None => merged.push(range),
+            }
+        }
+        LossSummary {
+            packets: merged.iter().map(LossRange::len).sum(),
+            range_count: merged.len(),
+            ranges: merged
+                .iter()
+                .take(MAX_SUMMARY_RANGES)
+                .map(|range| (range.start.as_raw(), range.end.as_raw()))
+                .collect(),
+        }
+    }
+
+    /// Summarize missing sequence numbers, in order
+    pub fn from_seqs(seqs: impl IntoIterator<Item = SeqNumber>) -> Self {
+        Self::from_ranges(seqs.into_iter().map(LossRange::single))
+    }
+}
+
+/// Send buffer occupancy
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SendBufferDiagnostics {
+    /// Capacity in packets
+    pub capacity: usize,
+    /// Packets awaiting acknowledgement
+    pub packets: usize,
+    /// Payload bytes buffered
+    pub bytes: usize,
+    /// Oldest unacknowledged sequence
+    pub oldest_unacked: u32,
+    /// Next sequence to be assigned
+    pub next_seq: u32,
+    /// Age of the oldest unacknowledged packet in milliseconds
+    pub oldest_unacked_age_ms: u64,
+}
+
+/// Receive buffer occupancy
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct RecvBufferDiagnostics {
+    /// Capacity in packets
+    pub capacity: usize,
+    /// Share of the slots in use (0.0 - 1.0)
+    pub utilization: f32,
+    /// Sequence delivery is waiting for
+    pub next_expected: u32,
+    /// Highest sequence received
+    pub highest_received: u32,
+    /// Complete messages the application has not read
+    pub ready_messages: usize,
+}
+
+/// Negotiated and configured options
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct NegotiatedOptions {
+    /// TSBPD latency in milliseconds
+    pub latency_ms: u16,
+    /// Maximum segment size
+    pub mss: usize,
+    /// Congestion control mode
+    pub congestion: String,
+    /// Stream ID
+    pub stream_id: Option<String>,
+    /// Packet filter
+    pub packet_filter: Option<String>,
+    /// Retransmission policy
+    pub retransmit_policy: String,
+    /// Whether the peer sent key material
+    pub encrypted: bool,
+    /// Our sending rate cap in bytes per second (0 = none)
+    pub max_bandwidth: u64,
+    /// Cap the peer asked us to respect (0 = none)
+    pub peer_max_bandwidth: u64,
+    /// Minimum time before a loss is reported again in milliseconds
+    pub nak_interval_ms: u64,
+    /// Silence after which the peer times out in milliseconds (0 = never)
+    pub peer_idle_timeout_ms: u64,
+    /// Stall watchdog timeout in milliseconds (0 = off)
+    pub stall_timeout_ms: u64,
+}
+
+/// A connection's diagnostics bundle
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ConnectionDiagnostics {
+    /// Crate version that produced the bundle
+    pub version: &'static str,
+    /// Our socket ID
+    pub socket_id: u32,
+    /// The peer's socket ID, once known
+    pub peer_socket_id: Option<u32>,
+    /// The peer's address
+    pub remote_addr: String,
+    /// Connection state
+    pub state: String,
+    /// Operator-assigned labels
+    pub labels: Labels,
+    /// Negotiated and configured options
+    pub options: NegotiatedOptions,
+    /// Send buffer occupancy
+    pub send_buffer: SendBufferDiagnostics,
+    /// Receive buffer occupancy
+    pub recv_buffer: RecvBufferDiagnostics,
+    /// Packets the peer asked us to retransmit
+    pub sender_losses: LossSummary,
+    /// Packets we are missing
+    pub receiver_losses: LossSummary,
+    /// Milliseconds since anything was heard from the peer
+    pub last_heard_ms: Option<u64>,
+    /// Keep-alives sent in a row without hearing from the peer
+    pub missed_keepalives: u32,
+    /// Current statistics
+    pub stats: ConnectionStats,
+    /// Most recent events, oldest first
+    pub events: Vec<DiagnosticEvent>,
+    /// Stats samples, oldest first
+    pub stats_history: Vec<StatsSample>,
+}
+
+/// Recent events and stats samples of a connection
+#[derive(Debug, Clone)]
+pub(crate) struct DiagnosticHistory {
+    events: VecDeque<(Instant, String)>,
+    samples: VecDeque<(Instant, StatsSample)>,
+}
+
+impl DiagnosticHistory {
+    pub(crate) fn new() -> Self {
+        DiagnosticHistory {
+            events: VecDeque::with_capacity(DEFAULT_EVENT_HISTORY),
+            samples: VecDeque::with_capacity(DEFAULT_STATS_HISTORY),
+        }
+    }
+
+    /// Record an event, dropping the oldest beyond `DEFAULT_EVENT_HISTORY`
+    pub(crate) fn event(&mut self, now: Instant, message: String) {
+        if self.events.len() >= DEFAULT_EVENT_HISTORY {
+            self.events.pop_front();
+        }
+        self.events.push_back((now, message));
+    }
+
+    /// Check if a stats sample is due
+    pub(crate) fn sample_due(&self, now: Instant) -> bool {
+        self.samples.back().map_or(true, |(at, _)| {
+            now.saturating_duration_since(*at) >= STATS_SAMPLE_INTERVAL
+        })
+    }
+
+    /// Record a stats sample, dropping the oldest beyond
+    /// `DEFAULT_STATS_HISTORY`
+    pub(crate) fn sample(&mut self, now: Instant, stats: &ConnectionStats) {
+        if self.samples.len() >= DEFAULT_STATS_HISTORY {
+            self.samples.pop_front();
+        }
+        self.samples
+            .push_back((now, StatsSample::from_stats(stats)));
+    }
+
+    /// Get the events with their ages at `now`
+    pub(crate) fn events(&self, now: Instant) -> Vec<DiagnosticEvent> {
+        self.events
+            .iter()
+            .map(|(at, message)| DiagnosticEvent {
+                age_ms: age_ms(now, *at),
+                message: message.clone(),
+            })
+            .collect()
+    }
+
+    /// Get the stats samples with their ages at `now`
+    pub(crate) fn samples(&self, now: Instant) -> Vec<StatsSample> {
+        self.samples
+            .iter()
+            .map(|(at, sample)| StatsSample {
+                age_ms: age_ms(now, *at),
+                ..sample.clone()
+            })
+            .collect()
+    }
+}
+
+/// Milliseconds from `then` to `now`
+pub(crate) fn age_ms(now: Instant, then: Instant) -> u64 {
+    now.saturating_duration_since(then).as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_loss_summary() {
+        let seqs = [3, 4, 5, 9, 11, 12].map(SeqNumber::new);
+        let summary = LossSummary::from_seqs(seqs);
+        assert_eq!(summary.packets, 6);
+        assert_eq!(summary.range_count, 3);
+        assert_eq!(summary.ranges, vec![(3, 5), (9, 9), (11, 12)]);
+
+        let many = (0..100).map(|i| SeqNumber::new(i * 2));
+        let summary = LossSummary::from_seqs(many);
+        assert_eq!(summary.range_count, 100);
+        assert_eq!(summary.ranges.len(), MAX_SUMMARY_RANGES);
+    }
+
+    #[test]
+    fn test_history_bounded() {
+        let mut history = DiagnosticHistory::new();
+        let start = Instant::now();
+        for i in 0..DEFAULT_EVENT_HISTORY + 5 {
+            history.event(start, format!("event {}", i));
+        }
+        let events = history.events(start + Duration::from_millis(250));
+        assert_eq!(events.len(), DEFAULT_EVENT_HISTORY);
+        assert_eq!(events[0].message, "event 5");
+        assert_eq!(events[0].age_ms, 250);
+
+        let stats = ConnectionStats::default();
+        assert!(history.sample_due(start));
+        history.sample(start, &stats);
+        assert!(!history.sample_due(start + Duration::from_millis(500)));
+        assert!(history.sample_due(start + STATS_SAMPLE_INTERVAL));
+        for i in 1..=DEFAULT_STATS_HISTORY as u32 {
+            history.sample(start + STATS_SAMPLE_INTERVAL * i, &stats);
+        }
+        assert_eq!(history.samples(start).len(), DEFAULT_STATS_HISTORY);
+    }
+}
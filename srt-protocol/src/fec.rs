@@ -0,0 +1,722 @@
+//! SRT FEC, the built-in `fec` packet filter
+//!
+//! The sender arranges its packets in a matrix of `cols` packets per row.
+//! After each row it sends a row parity packet: the XOR of the row's
+//! payloads, lengths, message numbers and timestamps. With `rows` of 2 or
+//! more it also XORs each column of `rows` packets, `cols` apart, into a
+//! column parity packet. The receiver rebuilds a group's packet from the
+//! others and the parity packet when exactly one is lost, without waiting a
+//! round trip for a retransmission; a packet rebuilt in one group may
+//! complete another, so a burst within a row can be repaired by the
+//! columns.
+//!
+//! Negative `rows` sends column groups only. With `layout:even` columns
+//! cover the same rows, so their parity packets are all sent during the
+//! matrix's last row; `layout:staircase` starts each column one row below
+//! the one before, spreading the column parity out over the stream.
+//!
+//! Parity packets carry message number [`FILTER_MSG_NUMBER`], the group's
+//! first sequence number and the timestamp clip, and this payload:
+//!
+//! ```text
+//!  0                   1                   2                   3
+//!  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+//! +---------------+---------------+-------------------------------+
+//! |     Group     |   Reserved    |          Length clip          |
+//! +---------------+---------------+-------------------------------+
+//! |                      Message number clip                      |
+//! +---------------------------------------------------------------+
+//! |                         Payload clip ...                      |
+//! ```
+//!
+//! Group is 0xFF for a row and the column index (capped at 0xFE) for a
+//! column.
+
+use crate::filter::{FilterConfig, FilterError, FilterStats, PacketFilter, FILTER_MSG_NUMBER};
+use crate::packet::{DataPacket, MsgNumber};
+use crate::sequence::SeqNumber;
+use bytes::{BufMut, Bytes, BytesMut};
+use std::collections::{HashMap, VecDeque};
+
+/// Group index of a row parity packet
+const ROW_GROUP: u8 = 0xFF;
+
+/// Largest group index of a column parity packet
+const MAX_COLUMN_GROUP: u8 = 0xFE;
+
+/// Length of the parity payload header
+const FEC_HEADER_LEN: usize = 8;
+
+/// Rows past a group's start after which the receiver gives up on it
+const KEPT_ROWS: u32 = 8;
+
+/// XOR of a group's packets
+#[derive(Debug, Clone, Default)]
+struct Clip {
+    length: u16,
+    msg_number: u32,
+    timestamp: u32,
+    payload: Vec<u8>,
+}
+
+impl Clip {
+    fn add(&mut self, msg_number: u32, timestamp: u32, payload: &[u8]) {
+        self.length ^= payload.len() as u16;
+        self.msg_number ^= msg_number;
+        self.timestamp ^= timestamp;
+        if self.payload.len() < payload.len() {
+            self.payload.resize(payload.len(), 0);
+        }
+        for (clip, byte) in self.payload.iter_mut().zip(payload) {
+            *clip ^= byte;
+        }
+    }
+
+    fn add_packet(&mut self, packet: &DataPacket) {
+        let mut msg = packet.msg_number();
+        msg.retransmitted = false;
+        self.add(msg.to_raw(), packet.header.timestamp, &packet.payload);
+    }
+
+    fn to_payload(&self, group: u8) -> Bytes {
+        let mut buf = BytesMut::with_capacity(FEC_HEADER_LEN + self.payload.len());
+        buf.put_u8(group);
+        buf.put_u8(0);
+        buf.put_u16(self.length);
+        buf.put_u32(self.msg_number);
+        buf.put_slice(&self.payload);
+        buf.freeze()
+    }
+
+    /// Parse a parity packet, returning its group index and clip
+    fn from_packet(packet: &DataPacket) -> Option<(u8, Self)> {
+        let data = &packet.payload;
+        if data.len() < FEC_HEADER_LEN {
+            return None;
+        }
+        let clip = Clip {
+            length: u16::from_be_bytes([data[2], data[3]]),
+            msg_number: u32::from_be_bytes([data[4], data[5], data[6], data[7]]),
+            timestamp: packet.header.timestamp,
+            payload: data[FEC_HEADER_LEN..].to_vec(),
+        };
+        Some((data[0], clip))
+    }
+}
+
+/// Packets covered by one parity packet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Group {
+    base: SeqNumber,
+    /// Distance between the group's packets (1 for a row)
+    stride: u32,
+    count: u32,
+}
+
+impl Group {
+    fn seqs(self) -> impl Iterator<Item = SeqNumber> {
+        (0..self.count).map(move |i| self.base + i * self.stride)
+    }
+
+    fn contains(&self, seq: SeqNumber) -> bool {
+        let offset = self.base.distance_to(seq);
+        offset >= 0
+            && (offset as u32) < self.stride * self.count
+            && offset as u32 % self.stride == 0
+    }
+}
+
+/// Group being filled by the sender
+#[derive(Debug)]
+struct SendGroup {
+    base: SeqNumber,
+    count: u32,
+    clip: Clip,
+}
+
+impl SendGroup {
+    fn new(base: SeqNumber) -> Self {
+        SendGroup {
+            base,
+            count: 0,
+            clip: Clip::default(),
+        }
+    }
+}
+
+/// Row/column FEC filter
+#[derive(Debug)]
+pub struct FecFilter {
+    cols: u32,
+    /// Packets per column group (below 2 = no column groups)
+    rows: u32,
+    /// Whether row groups are sent (`rows` not negative)
+    row_groups: bool,
+    staircase: bool,
+    /// Sender: first packet of the matrix offsets are counted from
+    origin: Option<SeqNumber>,
+    /// Sender: whether the origin moved on since the stream (re)started,
+    /// so groups may begin before it
+    rebased: bool,
+    /// Sender: sequence number the next packet should have
+    next_seq: Option<SeqNumber>,
+    /// Sender: row being filled
+    row: Option<SendGroup>,
+    /// Sender: column groups being filled, by first sequence number
+    columns: HashMap<u32, SendGroup>,
+    /// Receiver: recent packets by sequence, oldest first
+    received: HashMap<u32, DataPacket>,
+    order: VecDeque<u32>,
+    /// Receiver: parity of groups still missing more than one packet
+    waiting: VecDeque<(Group, Clip)>,
+    stats: FilterStats,
+}
+
+impl FecFilter {
+    /// Check the FEC parameters a configuration sets
+    ///
+    /// Every key must be one FEC knows, with a valid value. Required
+    /// parameters are not checked, since the peer may supply them.
+    pub fn check_params(config: &FilterConfig) -> Result<(), FilterError> {
+        for (key, value) in &config.params {
+            let valid = match key.as_str() {
+                "cols" => value.parse::<u16>().is_ok_and(|v| v >= 1),
+                // Negative rows means column-only FEC
+                "rows" => value.parse::<i16>().is_ok_and(|v| v != 0),
+                "layout" => matches!(value.as_str(), "even" | "staircase"),
+                "arq" => matches!(value.as_str(), "always" | "onreq" | "never"),
+                _ => return Err(FilterError::Malformed(format!("{}:{}", key, value))),
+            };
+            if !valid {
+                return Err(FilterError::InvalidValue {
+                    key: key.clone(),
+                    value: value.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Create a filter for the negotiated configuration
+    pub fn new(config: &FilterConfig) -> Result<Self, FilterError> {
+        Self::check_params(config)?;
+        let invalid = |key: &str, value: &str| FilterError::InvalidValue {
+            key: key.to_string(),
+            value: value.to_string(),
+        };
+        let cols = config
+            .get("cols")
+            .ok_or(FilterError::MissingParameter("cols"))?;
+        let cols = cols
+            .parse::<u16>()
+            .ok()
+            .filter(|c| *c >= 1)
+            .ok_or_else(|| invalid("cols", cols))?;
+        let rows = match config.get("rows") {
+            Some(rows) => rows
+                .parse::<i16>()
+                .ok()
+                .filter(|r| *r != 0)
+                .ok_or_else(|| invalid("rows", rows))?,
+            None => 1,
+        };
+        Ok(FecFilter {
+            cols: u32::from(cols),
+            rows: u32::from(rows.unsigned_abs()),
+            row_groups: rows > 0,
+            staircase: config.get("layout") == Some("staircase"),
+            origin: None,
+            rebased: false,
+            next_seq: None,
+            row: None,
+            columns: HashMap::new(),
+            received: HashMap::new(),
+            order: VecDeque::new(),
+            waiting: VecDeque::new(),
+            stats: FilterStats::default(),
+        })
+    }
+
+    /// Get the number of packets per row
+    pub fn cols(&self) -> u16 {
+        self.cols as u16
+    }
+
+    /// Get the number of packets per column group (0 without columns)
+    pub fn rows(&self) -> u16 {
+        if self.has_columns() {
+            self.rows as u16
+        } else {
+            0
+        }
+    }
+
+    fn has_columns(&self) -> bool {
+        self.rows >= 2
+    }
+
+    /// Packets after a group's start before the receiver gives up on it
+    fn window(&self) -> u32 {
+        self.cols * (self.rows + KEPT_ROWS)
+    }
+
+    fn parity(&self, base: SeqNumber, group: u8, clip: &Clip, dest: u32) -> DataPacket {
+        DataPacket::new(
+            base,
+            MsgNumber::new(FILTER_MSG_NUMBER),
+            clip.timestamp,
+            dest,
+            clip.to_payload(group),
+        )
+    }
+
+    /// Add a packet to its row, returning the parity once the row is full
+    fn send_row(&mut self, offset: u32, packet: &DataPacket) -> Option<DataPacket> {
+        let seq = packet.seq_number();
+        if offset % self.cols == 0 {
+            self.row = Some(SendGroup::new(seq));
+        }
+        let row = self.row.as_mut()?;
+        row.clip.add_packet(packet);
+        row.count += 1;
+        if row.count < self.cols {
+            return None;
+        }
+        let row = self.row.take()?;
+        Some(self.parity(row.base, ROW_GROUP, &row.clip, packet.header.dest_socket_id))
+    }
+
+    /// Add a packet to its column, returning the parity once it is full
+    fn send_column(&mut self, offset: u32, packet: &DataPacket) -> Option<DataPacket> {
+        let (col, row) = (offset % self.cols, offset / self.cols);
+        let rows = i64::from(self.rows);
+        let shift = if self.staircase { col % self.rows } else { 0 };
+        let start_row = i64::from(row) - (i64::from(row) - i64::from(shift)).rem_euclid(rows);
+        if start_row < 0 && !self.rebased {
+            // The group would start before the stream
+            return None;
+        }
+        let start = start_row * i64::from(self.cols) + i64::from(col);
+        let origin = self.origin?;
+        let base = if start >= 0 {
+            origin + start as u32
+        } else {
+            origin - start.unsigned_abs() as u32
+        };
+
+        let column = self
+            .columns
+            .entry(base.as_raw())
+            .or_insert_with(|| SendGroup::new(base));
+        column.clip.add_packet(packet);
+        column.count += 1;
+        if column.count < self.rows {
+            return None;
+        }
+        let column = self.columns.remove(&base.as_raw())?;
+        let group = col.min(u32::from(MAX_COLUMN_GROUP)) as u8;
+        Some(self.parity(base, group, &column.clip, packet.header.dest_socket_id))
+    }
+
+    fn keep(&mut self, packet: &DataPacket) {
+        let seq = packet.seq_number().as_raw();
+        if self.received.insert(seq, packet.clone()).is_none() {
+            self.order.push_back(seq);
+        }
+        while self.order.len() > 2 * self.window() as usize {
+            if let Some(old) = self.order.pop_front() {
+                self.received.remove(&old);
+            }
+        }
+    }
+
+    /// Rebuild the group's missing packet if it is the only one
+    ///
+    /// Returns None while more than one is missing, and Some(None) once
+    /// the group needs nothing more.
+    fn rebuild(&self, group: Group, clip: &Clip) -> Option<Option<DataPacket>> {
+        let mut clip = clip.clone();
+        let mut missing = None;
+        let mut dest = 0;
+        for seq in group.seqs() {
+            match self.received.get(&seq.as_raw()) {
+                Some(packet) => {
+                    clip.add_packet(packet);
+                    dest = packet.header.dest_socket_id;
+                }
+                None if missing.is_none() => missing = Some(seq),
+                None => return None,
+            }
+        }
+        let Some(seq) = missing else {
+            return Some(None);
+        };
+        clip.payload.truncate(usize::from(clip.length));
+        Some(Some(DataPacket::new(
+            seq,
+            MsgNumber::from_raw(clip.msg_number),
+            clip.timestamp,
+            dest,
+            Bytes::from(clip.payload),
+        )))
+    }
+
+    /// Try the waiting groups that `arrived` completes, dropping those done
+    /// or too old to help
+    ///
+    /// Rebuilt packets are tried in turn, since one group's repair may
+    /// complete another.
+    fn retry_waiting(&mut self, arrived: SeqNumber) -> Vec<DataPacket> {
+        let window = self.window() as i32;
+        let mut rebuilt = Vec::new();
+        let mut pending = vec![arrived];
+        while let Some(seq) = pending.pop() {
+            let mut i = 0;
+            while i < self.waiting.len() {
+                let (group, ref clip) = self.waiting[i];
+                let done = if group.contains(seq) {
+                    match self.rebuild(group, clip) {
+                        Some(packet) => {
+                            if let Some(packet) = packet {
+                                self.keep(&packet);
+                                self.stats.packets_rebuilt += 1;
+                                pending.push(packet.seq_number());
+                                rebuilt.push(packet);
+                            }
+                            true
+                        }
+                        None => false,
+                    }
+                } else if group.base.distance_to(seq) >= window {
+                    self.stats.groups_unrecovered += 1;
+                    true
+                } else {
+                    false
+                };
+                if done {
+                    self.waiting.remove(i);
+                } else {
+                    i += 1;
+                }
+            }
+        }
+        rebuilt
+    }
+
+    fn wait(&mut self, group: Group, clip: Clip) {
+        self.waiting.push_back((group, clip));
+        if self.waiting.len() > (2 * self.cols + KEPT_ROWS) as usize {
+            self.waiting.pop_front();
+            self.stats.groups_unrecovered += 1;
+        }
+    }
+}
+
+impl PacketFilter for FecFilter {
+    fn overhead(&self) -> usize {
+        FEC_HEADER_LEN
+    }
+
+    fn on_send(&mut self, packet: &DataPacket) -> Vec<DataPacket> {
+        let seq = packet.seq_number();
+        // A gap (packets dropped before sending) starts a new matrix
+        if self.next_seq != Some(seq) {
+            self.origin = Some(seq);
+            self.rebased = false;
+            self.row = None;
+            self.columns.clear();
+        }
+        self.next_seq = Some(seq.next());
+        let Some(mut origin) = self.origin else {
+            return Vec::new();
+        };
+
+        // The layout repeats every `cols * rows` packets; moving the origin
+        // on by that much keeps offsets small over a long stream
+        let period = self.cols * self.rows.max(1);
+        let mut offset = origin.distance_to(seq) as u32;
+        if offset >= 2 * period {
+            origin += period;
+            offset -= period;
+            self.origin = Some(origin);
+            self.rebased = true;
+        }
+
+        let mut parity = Vec::new();
+        if self.row_groups {
+            parity.extend(self.send_row(offset, packet));
+        }
+        if self.has_columns() {
+            parity.extend(self.send_column(offset, packet));
+        }
+        self.stats.packets_sent += parity.len() as u64;
+        parity
+    }
+
+    fn on_receive(&mut self, packet: &DataPacket) -> Vec<DataPacket> {
+        let seq = packet.seq_number();
+        if packet.msg_number().seq != FILTER_MSG_NUMBER {
+            self.keep(packet);
+            if self.waiting.is_empty() {
+                return Vec::new();
+            }
+            return self.retry_waiting(seq);
+        }
+
+        let Some((index, clip)) = Clip::from_packet(packet) else {
+            return Vec::new();
+        };
+        self.stats.packets_received += 1;
+        let group = if index == ROW_GROUP {
+            Group {
+                base: seq,
+                stride: 1,
+                count: self.cols,
+            }
+        } else if self.has_columns() {
+            Group {
+                base: seq,
+                stride: self.cols,
+                count: self.rows,
+            }
+        } else {
+            return Vec::new();
+        };
+        match self.rebuild(group, &clip) {
+            Some(Some(rebuilt)) => {
+                self.keep(&rebuilt);
+                self.stats.packets_rebuilt += 1;
+                let rebuilt_seq = rebuilt.seq_number();
+                let mut packets = vec![rebuilt];
+                if !self.waiting.is_empty() {
+                    packets.extend(self.retry_waiting(rebuilt_seq));
+                }
+                packets
+            }
+            Some(None) => Vec::new(),
+            None => {
+                self.wait(group, clip);
+                Vec::new()
+            }
+        }
+    }
+
+    fn stats(&self) -> FilterStats {
+        self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::is_filter_packet;
+    use crate::packet::PacketBoundary;
+
+    fn filter(cols: u16) -> FecFilter {
+        FecFilter::new(&FilterConfig::parse(&format!("fec,cols:{}", cols)).unwrap()).unwrap()
+    }
+
+    fn matrix(config: &str) -> FecFilter {
+        FecFilter::new(&FilterConfig::parse(config).unwrap()).unwrap()
+    }
+
+    fn packets(count: u32) -> Vec<DataPacket> {
+        (0..count)
+            .map(|i| {
+                let mut msg = MsgNumber::new(i + 1);
+                msg.boundary = PacketBoundary::Solo;
+                let payload: Vec<u8> = (0..(10 + i * 7) as u8).map(|b| b ^ i as u8).collect();
+                DataPacket::new(SeqNumber::new(100 + i), msg, 1000 * i, 7, payload.into())
+            })
+            .collect()
+    }
+
+    /// Send `packets`, returning them with the parity packets in place
+    fn send(sender: &mut FecFilter, packets: &[DataPacket]) -> Vec<DataPacket> {
+        let mut wire = Vec::new();
+        for packet in packets {
+            wire.push(packet.clone());
+            wire.extend(sender.on_send(packet));
+        }
+        wire
+    }
+
+    /// Parity packets on the wire as (group index, first sequence number)
+    fn parity(wire: &[DataPacket]) -> Vec<(u8, u32)> {
+        wire.iter()
+            .filter(|p| p.msg_number().seq == FILTER_MSG_NUMBER)
+            .map(|p| (p.payload[0], p.seq_number().as_raw()))
+            .collect()
+    }
+
+    /// Receive `wire` without the packets in `lost`, returning the rebuilt
+    /// ones in sequence order
+    fn receive(receiver: &mut FecFilter, wire: &[DataPacket], lost: &[u32]) -> Vec<DataPacket> {
+        let mut rebuilt = Vec::new();
+        for packet in wire
+            .iter()
+            .filter(|p| is_filter_packet(p) || !lost.contains(&p.seq_number().as_raw()))
+        {
+            rebuilt.extend(receiver.on_receive(packet));
+        }
+        rebuilt.sort_by_key(|p| p.seq_number().as_raw());
+        rebuilt
+    }
+
+    #[test]
+    fn test_parity_after_each_row() {
+        let mut sender = filter(4);
+        let wire = send(&mut sender, &packets(10));
+        assert_eq!(wire.len(), 12);
+        assert_eq!(parity(&wire), vec![(ROW_GROUP, 100), (ROW_GROUP, 104)]);
+        assert_eq!(wire[4].payload[0], ROW_GROUP);
+    }
+
+    #[test]
+    fn test_single_loss_rebuilt() {
+        let sent = packets(8);
+        let mut sender = filter(4);
+        let mut receiver = filter(4);
+        let wire = send(&mut sender, &sent);
+
+        let mut rebuilt = Vec::new();
+        for packet in wire.iter().filter(|p| p.seq_number().as_raw() != 102) {
+            rebuilt.extend(receiver.on_receive(packet));
+        }
+        assert_eq!(rebuilt, vec![sent[2].clone()]);
+
+        // Nothing to rebuild without losses
+        let mut receiver = filter(4);
+        assert!(wire.iter().all(|p| receiver.on_receive(p).is_empty()));
+    }
+
+    #[test]
+    fn test_parity_before_late_packet() {
+        let sent = packets(4);
+        let mut sender = filter(4);
+        let mut receiver = filter(4);
+        let wire = send(&mut sender, &sent);
+
+        // Two lost when the parity arrives; a retransmission of one lets
+        // the other be rebuilt
+        receiver.on_receive(&wire[0]);
+        receiver.on_receive(&wire[3]);
+        assert!(receiver.on_receive(&wire[4]).is_empty());
+        assert_eq!(receiver.on_receive(&wire[1]), vec![sent[2].clone()]);
+        assert!(receiver.waiting.is_empty());
+    }
+
+    #[test]
+    fn test_gap_starts_new_row() {
+        let sent = packets(6);
+        let mut sender = filter(3);
+        let mut wire = send(&mut sender, &sent[..2]);
+        // 102 dropped before it was sent
+        wire.extend(send(&mut sender, &sent[3..]));
+        assert_eq!(parity(&wire), vec![(ROW_GROUP, 103)]);
+    }
+
+    #[test]
+    fn test_column_parity_even_layout() {
+        let mut sender = matrix("fec,cols:3,rows:2");
+        assert_eq!((sender.cols(), sender.rows()), (3, 2));
+        let wire = send(&mut sender, &packets(12));
+        assert_eq!(
+            parity(&wire),
+            vec![
+                (ROW_GROUP, 100),
+                (0, 100),
+                (1, 101),
+                (ROW_GROUP, 103),
+                (2, 102),
+                (ROW_GROUP, 106),
+                (0, 106),
+                (1, 107),
+                (ROW_GROUP, 109),
+                (2, 108),
+            ]
+        );
+
+        // Column only
+        let mut sender = matrix("fec,cols:3,rows:-2");
+        let wire = send(&mut sender, &packets(6));
+        assert_eq!(parity(&wire), vec![(0, 100), (1, 101), (2, 102)]);
+        assert_eq!(sender.stats().packets_sent, 3);
+    }
+
+    #[test]
+    fn test_staircase_spreads_columns() {
+        let mut sender = matrix("fec,cols:3,rows:-3,layout:staircase");
+        let wire = send(&mut sender, &packets(18));
+        // Column 0 covers rows 0-2 and column 1 rows 1-3; column 2 starts
+        // at row 2, and the packets above a column's first group (101,
+        // 102 and 105) are left out until the stream has run a full period
+        assert_eq!(parity(&wire), vec![(0, 100), (1, 104), (2, 108), (0, 109)]);
+        let positions: Vec<_> = wire
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| p.msg_number().seq == FILTER_MSG_NUMBER)
+            .map(|(i, _)| i)
+            .collect();
+        assert_eq!(positions, vec![7, 12, 17, 19]);
+
+        // The next period's groups begin before the moved origin
+        let mut sender = matrix("fec,cols:3,rows:-3,layout:staircase");
+        let sent: Vec<_> = (0..30)
+            .map(|i| {
+                DataPacket::new(
+                    SeqNumber::new(100 + i),
+                    MsgNumber::new(1),
+                    0,
+                    7,
+                    Bytes::new(),
+                )
+            })
+            .collect();
+        let columns = parity(&send(&mut sender, &sent));
+        assert!(columns.contains(&(1, 113)));
+        assert!(columns.contains(&(2, 117)));
+    }
+
+    #[test]
+    fn test_columns_rebuild_burst() {
+        let sent = packets(8);
+        let mut sender = matrix("fec,cols:4,rows:2");
+        let mut receiver = matrix("fec,cols:4,rows:2");
+        let wire = send(&mut sender, &sent);
+
+        // Two in one row: the row can't, the columns can
+        let rebuilt = receive(&mut receiver, &wire, &[100, 101]);
+        assert_eq!(rebuilt, vec![sent[0].clone(), sent[1].clone()]);
+        assert_eq!(receiver.stats().packets_rebuilt, 2);
+        assert_eq!(receiver.stats().packets_received, 6);
+    }
+
+    #[test]
+    fn test_rebuilt_packet_completes_other_groups() {
+        let sent = packets(4);
+        let mut sender = matrix("fec,cols:2,rows:2");
+        let mut receiver = matrix("fec,cols:2,rows:2");
+        let wire = send(&mut sender, &sent);
+
+        // Row 1 rebuilds 102, which lets column 0 rebuild 100, which lets
+        // row 0 rebuild 101
+        let rebuilt = receive(&mut receiver, &wire, &[100, 101, 102]);
+        assert_eq!(rebuilt, sent[..3].to_vec());
+        assert!(receiver.waiting.is_empty());
+        assert_eq!(receiver.stats().groups_unrecovered, 0);
+    }
+
+    #[test]
+    fn test_gives_up_on_old_groups() {
+        let sent = packets(40);
+        let mut sender = filter(2);
+        let mut receiver = filter(2);
+        let wire = send(&mut sender, &sent);
+
+        let rebuilt = receive(&mut receiver, &wire, &[100, 101]);
+        assert!(rebuilt.is_empty());
+        assert!(receiver.waiting.is_empty());
+        assert_eq!(receiver.stats().groups_unrecovered, 1);
+    }
+}
@@ -0,0 +1,389 @@
+//! Packet Filter Configuration
+//!
+//! Parses and negotiates the packet filter (FEC) configuration string carried
+//! in the SRT_CMD_FILTER handshake extension, e.g.
+//! `fec,cols:10,rows:5,layout:staircase,arq:onreq`.
+//!
+//! Both peers may configure a filter. Parameters set on only one side are
+//! taken as-is; parameters set on both sides must agree, otherwise the
+//! connection is rejected.
+//!
+//! Once negotiated, each side builds a [`PacketFilter`] from the agreed
+//! configuration and runs it on the data path: it sees every packet sent
+//! for the first time and may add its own (FEC parity), and it sees every
+//! packet received and may rebuild lost ones. [`builtin_filter`] provides
+//! the `fec` type (see [`crate::fec`]); a connection can be given another
+//! factory with `Connection::set_packet_filter_factory`. A filter's own
+//! counters are in [`FilterStats`] (`Connection::packet_filter_stats`).
+//!
+//! [`FilterConfig::parse`] only checks the syntax, so any filter type can
+//! be configured and negotiated. The `fec` parameters are checked by
+//! [`FecFilter`]; other types are left to their factory.
+
+use crate::fec::FecFilter;
+use crate::packet::DataPacket;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Built-in FEC filter type
+pub const FEC_FILTER: &str = "fec";
+
+/// Message number of packets a filter adds (libsrt's `SRT_MSGNO_CONTROL`)
+///
+/// Data packets never use it, so the receiver hands such packets to its
+/// filter instead of delivering them.
+pub const FILTER_MSG_NUMBER: u32 = 0;
+
+/// A packet filter on a connection's data path, such as FEC
+pub trait PacketFilter: Send + Sync {
+    /// See a data packet sent for the first time; returns packets to send
+    /// after it
+    fn on_send(&mut self, packet: &DataPacket) -> Vec<DataPacket>;
+
+    /// See a received packet, the peer filter's own included; returns lost
+    /// data packets it rebuilt
+    fn on_receive(&mut self, packet: &DataPacket) -> Vec<DataPacket>;
+
+    /// Bytes the filter's packets carry beyond a data payload
+    ///
+    /// Data payloads shrink by as much, so the filter's packets still fit
+    /// the MSS.
+    fn overhead(&self) -> usize {
+        0
+    }
+
+    /// Get the filter's statistics
+    fn stats(&self) -> FilterStats {
+        FilterStats::default()
+    }
+}
+
+/// Packet filter statistics
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FilterStats {
+    /// Filter packets sent, such as FEC parity
+    pub packets_sent: u64,
+    /// Filter packets received from the peer
+    pub packets_received: u64,
+    /// Lost packets rebuilt
+    pub packets_rebuilt: u64,
+    /// Groups given up on with too many packets missing to rebuild
+    pub groups_unrecovered: u64,
+}
+
+/// Builds the packet filter for a negotiated configuration
+pub type FilterFactory =
+    Arc<dyn Fn(&FilterConfig) -> Result<Box<dyn PacketFilter>, FilterError> + Send + Sync>;
+
+/// Build one of the filters this crate implements
+pub fn builtin_filter(config: &FilterConfig) -> Result<Box<dyn PacketFilter>, FilterError> {
+    match config.kind.as_str() {
+        FEC_FILTER => Ok(Box::new(FecFilter::new(config)?)),
+        kind => Err(FilterError::UnknownType(kind.to_string())),
+    }
+}
+
+/// Check whether a data packet was added by the peer's packet filter
+pub fn is_filter_packet(packet: &DataPacket) -> bool {
+    packet.msg_number().seq == FILTER_MSG_NUMBER
+}
+
+/// Packet filter errors
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum FilterError {
+    #[error("Empty filter configuration")]
+    Empty,
+
+    #[error("Unknown filter type: {0}")]
+    UnknownType(String),
+
+    #[error("Malformed filter parameter: {0}")]
+    Malformed(String),
+
+    #[error("Invalid value for '{key}': {value}")]
+    InvalidValue { key: String, value: String },
+
+    #[error("Missing required parameter: {0}")]
+    MissingParameter(&'static str),
+
+    #[error("Filter type mismatch: {local} vs {peer}")]
+    TypeMismatch { local: String, peer: String },
+
+    #[error("Peer does not support packet filters")]
+    PeerUnsupported,
+
+    #[error("Filter parameter '{key}' mismatch: {local} vs {peer}")]
+    ParameterMismatch {
+        key: String,
+        local: String,
+        peer: String,
+    },
+}
+
+/// When the receiver asks for retransmission while FEC is active (`arq`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ArqMode {
+    /// NAK losses immediately, alongside FEC
+    Always,
+    /// NAK only losses FEC had a chance to repair and didn't
+    #[default]
+    OnRequest,
+    /// Rely on FEC alone
+    Never,
+}
+
+/// Parsed packet filter configuration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilterConfig {
+    /// Filter type (e.g. "fec")
+    pub kind: String,
+    /// Filter parameters
+    pub params: BTreeMap<String, String>,
+}
+
+impl FilterConfig {
+    /// Parse a filter configuration string
+    ///
+    /// Only the syntax is checked: a type followed by `key:value`
+    /// parameters. See [`FilterConfig::check_params`] and
+    /// [`FilterConfig::validate`] for the parameters themselves.
+    pub fn parse(config: &str) -> Result<Self, FilterError> {
+        let mut parts = config.split(',').map(str::trim);
+
+        let kind = match parts.next() {
+            Some(kind) if !kind.is_empty() => kind.to_string(),
+            _ => return Err(FilterError::Empty),
+        };
+
+        let mut params = BTreeMap::new();
+        for part in parts {
+            let (key, value) = part
+                .split_once(':')
+                .ok_or_else(|| FilterError::Malformed(part.to_string()))?;
+            let (key, value) = (key.trim(), value.trim());
+            if key.is_empty() || value.is_empty() {
+                return Err(FilterError::Malformed(part.to_string()));
+            }
+            params.insert(key.to_string(), value.to_string());
+        }
+
+        Ok(FilterConfig { kind, params })
+    }
+
+    /// Parse a configuration for one of the built-in filters
+    ///
+    /// Fails for any other type or a parameter it does not accept.
+    pub fn parse_builtin(config: &str) -> Result<Self, FilterError> {
+        let config = Self::parse(config)?;
+        if config.kind != FEC_FILTER {
+            return Err(FilterError::UnknownType(config.kind));
+        }
+        config.check_params()?;
+        Ok(config)
+    }
+
+    /// Check the parameters set so far against the built-in filter of
+    /// this type
+    ///
+    /// Required parameters are not checked, since the peer may supply
+    /// them. Other types are left to their factory.
+    pub fn check_params(&self) -> Result<(), FilterError> {
+        match self.kind.as_str() {
+            FEC_FILTER => FecFilter::check_params(self),
+            _ => Ok(()),
+        }
+    }
+
+    /// Get a parameter value
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.params.get(key).map(String::as_str)
+    }
+
+    /// Get the retransmission mode (defaults to `onreq`)
+    pub fn arq(&self) -> ArqMode {
+        match self.get("arq") {
+            Some("always") => ArqMode::Always,
+            Some("never") => ArqMode::Never,
+            _ => ArqMode::OnRequest,
+        }
+    }
+
+    /// Get the number of packets one FEC group interval spans
+    ///
+    /// A loss can't be repaired before the column groups covering it
+    /// complete, i.e. `cols * rows` packets later (`cols` for row-only FEC).
+    pub fn group_span(&self) -> u32 {
+        let cols = self.get("cols").and_then(|v| v.parse::<u32>().ok());
+        let rows = self.get("rows").and_then(|v| v.parse::<i16>().ok());
+        cols.unwrap_or(1) * rows.map_or(1, |r| u32::from(r.unsigned_abs()))
+    }
+
+    /// Merge with the peer's configuration
+    ///
+    /// Fails if the types differ, if a parameter set on both sides differs,
+    /// or if the merged configuration is incomplete.
+    pub fn negotiate(&self, peer: &FilterConfig) -> Result<FilterConfig, FilterError> {
+        if self.kind != peer.kind {
+            return Err(FilterError::TypeMismatch {
+                local: self.kind.clone(),
+                peer: peer.kind.clone(),
+            });
+        }
+
+        let mut params = self.params.clone();
+        for (key, peer_value) in &peer.params {
+            match params.get(key) {
+                Some(value) if value != peer_value => {
+                    return Err(FilterError::ParameterMismatch {
+                        key: key.clone(),
+                        local: value.clone(),
+                        peer: peer_value.clone(),
+                    });
+                }
+                Some(_) => {}
+                None => {
+                    params.insert(key.clone(), peer_value.clone());
+                }
+            }
+        }
+
+        let merged = FilterConfig {
+            kind: self.kind.clone(),
+            params,
+        };
+        merged.validate()?;
+        Ok(merged)
+    }
+
+    /// Check that the configuration is complete and valid for the built-in
+    /// filter of its type
+    ///
+    /// Other types are left to their factory.
+    pub fn validate(&self) -> Result<(), FilterError> {
+        match self.kind.as_str() {
+            FEC_FILTER => FecFilter::new(self).map(|_| ()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for FilterConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.kind)?;
+        for (key, value) in &self.params {
+            write!(f, ",{}:{}", key, value)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_display() {
+        let config = FilterConfig::parse("fec, rows:5 ,cols:10,arq:onreq").unwrap();
+        assert_eq!(config.kind, "fec");
+        assert_eq!(config.get("cols"), Some("10"));
+        assert_eq!(config.get("rows"), Some("5"));
+        assert_eq!(config.to_string(), "fec,arq:onreq,cols:10,rows:5");
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(FilterConfig::parse(""), Err(FilterError::Empty));
+        assert!(matches!(
+            FilterConfig::parse("fec,cols"),
+            Err(FilterError::Malformed(_))
+        ));
+        assert!(matches!(
+            FilterConfig::parse("fec,cols:"),
+            Err(FilterError::Malformed(_))
+        ));
+        assert!(matches!(
+            FilterConfig::parse_builtin("rs,cols:10"),
+            Err(FilterError::UnknownType(_))
+        ));
+        assert!(matches!(
+            FilterConfig::parse_builtin("fec,cols:0"),
+            Err(FilterError::InvalidValue { .. })
+        ));
+        assert!(matches!(
+            FilterConfig::parse_builtin("fec,cols:10,layout:zigzag"),
+            Err(FilterError::InvalidValue { .. })
+        ));
+        assert!(matches!(
+            FilterConfig::parse_builtin("fec,cols:10,depth:3"),
+            Err(FilterError::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn test_other_filter_types() {
+        // Any type and parameters parse and negotiate; the factory checks
+        let local = FilterConfig::parse("rs,k:10,m:2").unwrap();
+        let peer = FilterConfig::parse("rs,interleave:4").unwrap();
+        assert!(local.check_params().is_ok());
+        let merged = local.negotiate(&peer).unwrap();
+        assert_eq!(merged.to_string(), "rs,interleave:4,k:10,m:2");
+        assert!(matches!(
+            builtin_filter(&merged),
+            Err(FilterError::UnknownType(_))
+        ));
+
+        // The FEC checks still apply to `fec`
+        let fec = FilterConfig::parse("fec,cols:0").unwrap();
+        assert!(matches!(
+            fec.check_params(),
+            Err(FilterError::InvalidValue { .. })
+        ));
+    }
+
+    #[test]
+    fn test_negotiate_merges_parameters() {
+        let local = FilterConfig::parse("fec,cols:10").unwrap();
+        let peer = FilterConfig::parse("fec,rows:5,arq:never").unwrap();
+
+        let merged = local.negotiate(&peer).unwrap();
+        assert_eq!(merged.to_string(), "fec,arq:never,cols:10,rows:5");
+        assert_eq!(peer.negotiate(&local).unwrap(), merged);
+    }
+
+    #[test]
+    fn test_arq_and_group_span() {
+        let config = FilterConfig::parse("fec,cols:10,rows:5").unwrap();
+        assert_eq!(config.arq(), ArqMode::OnRequest);
+        assert_eq!(config.group_span(), 50);
+
+        let config = FilterConfig::parse("fec,cols:8,rows:-4,arq:never").unwrap();
+        assert_eq!(config.arq(), ArqMode::Never);
+        assert_eq!(config.group_span(), 32);
+
+        let config = FilterConfig::parse("fec,cols:10,arq:always").unwrap();
+        assert_eq!(config.arq(), ArqMode::Always);
+        assert_eq!(config.group_span(), 10);
+    }
+
+    #[test]
+    fn test_negotiate_rejects_conflicts() {
+        let local = FilterConfig::parse("fec,cols:10,rows:5").unwrap();
+        let peer = FilterConfig::parse("fec,cols:8").unwrap();
+        assert!(matches!(
+            local.negotiate(&peer),
+            Err(FilterError::ParameterMismatch { .. })
+        ));
+
+        // Neither side provided cols
+        let local = FilterConfig::parse("fec,rows:5").unwrap();
+        let peer = FilterConfig::parse("fec,arq:always").unwrap();
+        assert_eq!(
+            local.negotiate(&peer),
+            Err(FilterError::MissingParameter("cols"))
+        );
+    }
+}
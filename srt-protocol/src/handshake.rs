@@ -3,8 +3,11 @@
 //! Implements the SRT connection handshake for establishing connections
 //! between peers with version negotiation and capability exchange.
 
-use bytes::{Buf, BufMut, BytesMut};
-use std::net::SocketAddr;
+use crate::packet::{ControlType, Packet};
+use crate::probe::{ProbePlan, PROBE_PLAN_LEN};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use std::fmt;
+use std::net::{Ipv4Addr, SocketAddr};
 use thiserror::Error;
 
 /// SRT protocol version
@@ -13,6 +16,56 @@ pub const SRT_VERSION: u32 = 0x00010500; // Version 1.5.0
 /// SRT magic code for handshake
 pub const SRT_MAGIC_CODE: u32 = 0x4A17;
 
+/// Handshake extension type: SRT handshake request
+pub const SRT_CMD_HSREQ: u16 = 1;
+
+/// Handshake extension type: key material request
+pub const SRT_CMD_KMREQ: u16 = 3;
+
+/// Handshake extension type: key material response
+pub const SRT_CMD_KMRSP: u16 = 4;
+
+/// Handshake extension type: Stream ID
+pub const SRT_CMD_SID: u16 = 5;
+
+/// Handshake extension type: congestion control name
+///
+/// Only sent for modes other than live, the default.
+pub const SRT_CMD_CONGESTION: u16 = 6;
+
+/// Handshake extension type: packet filter configuration
+pub const SRT_CMD_FILTER: u16 = 7;
+
+/// Handshake extension type: sender wallclock time origin
+///
+/// Not part of the reference implementation; peers that do not know it
+/// skip the block.
+pub const SRT_CMD_TIME_ORIGIN: u16 = 0x4710;
+
+/// Handshake extension type: sending rate ceiling requested of the peer
+///
+/// Not part of the reference implementation; peers that do not know it
+/// skip the block.
+pub const SRT_CMD_MAX_BW: u16 = 0x4711;
+
+/// Handshake extension type: bandwidth probe plan (see `probe`)
+///
+/// Not part of the reference implementation; peers that do not know it
+/// skip the block.
+pub const SRT_CMD_PROBE: u16 = 0x4712;
+
+/// Maximum Stream ID length in bytes
+pub const MAX_STREAM_ID_LEN: usize = 512;
+
+/// Handshake type of a rejection: this plus the reason code
+const REJECT_BASE: i32 = 1000;
+
+/// Key material header: version 1, packet type 2 (key material)
+const KM_VERSION_TYPE: u8 = 0x12;
+
+/// Offset of the cipher field in a key material message
+const KM_CIPHER_OFFSET: usize = 8;
+
 /// Handshake errors
 #[derive(Error, Debug)]
 pub enum HandshakeError {
@@ -25,9 +78,36 @@ pub enum HandshakeError {
     #[error("Extension parse error")]
     ExtensionError,
 
+    #[error("Stream ID too long: {0} bytes (max {MAX_STREAM_ID_LEN})")]
+    StreamIdTooLong(usize),
+
     #[error("Handshake rejected by peer")]
     Rejected,
 
+    #[error("Key material too long: {0} bytes")]
+    KeyMaterialTooLong(usize),
+
+    #[error("Expected an agreement, got handshake type {0}")]
+    NotAgreement(i32),
+
+    #[error("Agreement echoes cookie {actual:#010x}, expected {expected:#010x}")]
+    CookieMismatch { expected: u32, actual: u32 },
+
+    #[error("Agreement echoes initial sequence {actual}, expected {expected}")]
+    InitialSeqMismatch { expected: u32, actual: u32 },
+
+    #[error("Agreement carries no SRT extension")]
+    MissingExtension,
+
+    #[error("Agreed latency {agreed} ms is below the {requested} ms requested")]
+    LatencyBelowRequest { requested: u16, agreed: u16 },
+
+    #[error("Agreed latency {0} ms is above {max} ms", max = crate::connection::MAX_LATENCY_MS)]
+    LatencyOutOfRange(u16),
+
+    #[error("Agreement carries no peer socket ID")]
+    MissingSocketId,
+
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 }
@@ -113,6 +193,71 @@ impl SrtOptions {
     }
 }
 
+/// Why a handshake was rejected (libsrt's `SRT_REJ_*` codes)
+///
+/// Sent to the peer as handshake type 1000 + code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RejectReason {
+    /// Peer refused for an unspecified reason
+    Peer,
+    /// Passphrase mismatch
+    BadSecret,
+    /// Encryption required on one side but not provided by the other, or
+    /// downgraded (missing crypto capability, stripped key material or
+    /// null cipher)
+    Unsecure,
+    /// One side uses stream mode, the other message mode
+    MessageApi,
+    /// The sides use different congestion control
+    Congestion,
+    /// Packet filter configurations could not be agreed
+    Filter,
+    /// A code this implementation does not know
+    Other(u16),
+}
+
+impl RejectReason {
+    /// Get the libsrt reason code
+    pub fn code(&self) -> u16 {
+        match self {
+            RejectReason::Peer => 2,
+            RejectReason::BadSecret => 10,
+            RejectReason::Unsecure => 11,
+            RejectReason::MessageApi => 12,
+            RejectReason::Congestion => 13,
+            RejectReason::Filter => 14,
+            RejectReason::Other(code) => *code,
+        }
+    }
+
+    /// Get the reason for a libsrt reason code
+    pub fn from_code(code: u16) -> Self {
+        match code {
+            2 => RejectReason::Peer,
+            10 => RejectReason::BadSecret,
+            11 => RejectReason::Unsecure,
+            12 => RejectReason::MessageApi,
+            13 => RejectReason::Congestion,
+            14 => RejectReason::Filter,
+            code => RejectReason::Other(code),
+        }
+    }
+}
+
+impl fmt::Display for RejectReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RejectReason::Peer => write!(f, "rejected by peer"),
+            RejectReason::BadSecret => write!(f, "wrong passphrase"),
+            RejectReason::Unsecure => write!(f, "encryption required"),
+            RejectReason::MessageApi => write!(f, "stream/message mode mismatch"),
+            RejectReason::Congestion => write!(f, "congestion control mismatch"),
+            RejectReason::Filter => write!(f, "packet filter mismatch"),
+            RejectReason::Other(code) => write!(f, "reason {}", code),
+        }
+    }
+}
+
 /// Handshake type
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum HandshakeType {
@@ -187,15 +332,10 @@ impl UdtHandshake {
         // Peer IP address (16 bytes for IPv6, zeros for IPv4)
         match self.peer_addr {
             SocketAddr::V4(addr) => {
-                buf.put_u32(u32::from(*addr.ip()));
-                buf.put_u64(0);
-                buf.put_u32(0);
-            }
-            SocketAddr::V6(addr) => {
-                for &byte in addr.ip().octets().iter() {
-                    buf.put_u8(byte);
-                }
+                buf.put_slice(&addr.ip().octets());
+                buf.put_bytes(0, 12);
             }
+            SocketAddr::V6(addr) => buf.put_slice(&addr.ip().octets()),
         }
 
         buf
@@ -218,23 +358,15 @@ impl UdtHandshake {
         let socket_id = buf.get_u32();
         let syn_cookie = buf.get_u32();
 
-        // Parse IP address
-        let peer_addr = if buf[0..4] != [0, 0, 0, 0] || buf[4..16] == [0; 12] {
-            // IPv4
-            let ip = u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]);
-            SocketAddr::from((
-                [
-                    ((ip >> 24) & 0xFF) as u8,
-                    ((ip >> 16) & 0xFF) as u8,
-                    ((ip >> 8) & 0xFF) as u8,
-                    (ip & 0xFF) as u8,
-                ],
-                0,
-            ))
+        // Parse IP address: IPv4 is the first word with the rest zeroed.
+        // Octets are copied as-is so the result is independent of host
+        // byte order.
+        let mut octets = [0u8; 16];
+        octets.copy_from_slice(&buf[0..16]);
+        let peer_addr = if octets[4..] == [0; 12] {
+            let ip = Ipv4Addr::new(octets[0], octets[1], octets[2], octets[3]);
+            SocketAddr::from((ip, 0))
         } else {
-            // IPv6
-            let mut octets = [0u8; 16];
-            octets.copy_from_slice(&buf[0..16]);
             SocketAddr::from((octets, 0))
         };
 
@@ -280,7 +412,7 @@ impl SrtHandshakeExtension {
         let mut buf = BytesMut::with_capacity(16);
 
         // Extension header: type (HSREQ=1) and size (3 words)
-        buf.put_u16(1); // SRT_CMD_HSREQ
+        buf.put_u16(SRT_CMD_HSREQ);
         buf.put_u16(3); // Size in 32-bit words
 
         // Extension data
@@ -302,7 +434,7 @@ impl SrtHandshakeExtension {
         let ext_type = buf.get_u16();
         let ext_size = buf.get_u16();
 
-        if ext_type != 1 || ext_size != 3 {
+        if ext_type != SRT_CMD_HSREQ || ext_size != 3 {
             return Err(HandshakeError::ExtensionError);
         }
 
@@ -340,6 +472,21 @@ pub struct SrtHandshake {
     pub udt: UdtHandshake,
     /// SRT extension (if present)
     pub srt_ext: Option<SrtHandshakeExtension>,
+    /// Stream ID (if present)
+    pub stream_id: Option<String>,
+    /// Packet filter configuration string (if present)
+    pub filter_config: Option<String>,
+    /// Congestion control name (absent for live)
+    pub congestion: Option<String>,
+    /// Wallclock time of timestamp zero, microseconds since the UNIX epoch
+    pub time_origin: Option<u64>,
+    /// Rate the peer is asked not to exceed when sending, bytes per second
+    pub max_bw_request: Option<u64>,
+    /// Bandwidth probe asked for by a caller, or agreed by a listener
+    pub probe: Option<ProbePlan>,
+    /// Key material message (see `srt_crypto::km`), sent as a KMREQ in
+    /// requests and a KMRSP in agreements
+    pub key_material: Option<Bytes>,
 }
 
 impl SrtHandshake {
@@ -366,7 +513,59 @@ impl SrtHandshake {
             send_latency_ms,
         ));
 
-        SrtHandshake { udt, srt_ext }
+        SrtHandshake {
+            udt,
+            srt_ext,
+            stream_id: None,
+            filter_config: None,
+            congestion: None,
+            time_origin: None,
+            max_bw_request: None,
+            probe: None,
+            key_material: None,
+        }
+    }
+
+    /// Set the Stream ID sent with this handshake
+    pub fn set_stream_id(&mut self, stream_id: Option<String>) -> Result<(), HandshakeError> {
+        if let Some(ref id) = stream_id {
+            if id.len() > MAX_STREAM_ID_LEN {
+                return Err(HandshakeError::StreamIdTooLong(id.len()));
+            }
+        }
+        self.stream_id = stream_id;
+        Ok(())
+    }
+
+    /// Set the key material sent with this handshake
+    pub fn set_key_material(&mut self, key_material: Option<Bytes>) -> Result<(), HandshakeError> {
+        if let Some(ref km) = key_material {
+            if km.len() > usize::from(u16::MAX) * 4 {
+                return Err(HandshakeError::KeyMaterialTooLong(km.len()));
+            }
+        }
+        self.key_material = key_material;
+        Ok(())
+    }
+
+    /// Get the cipher field of the key material (0 = none), if it carries
+    /// a key material message
+    pub fn key_material_cipher(&self) -> Option<u8> {
+        self.key_material
+            .as_deref()
+            .filter(|km| km.len() > KM_CIPHER_OFFSET && km[0] == KM_VERSION_TYPE)
+            .map(|km| km[KM_CIPHER_OFFSET])
+    }
+
+    /// Turn this handshake into a rejection for `reason`
+    pub fn reject(&mut self, reason: RejectReason) {
+        self.udt.handshake_type = REJECT_BASE + i32::from(reason.code());
+    }
+
+    /// Get the rejection reason, if this handshake is a rejection
+    pub fn reject_reason(&self) -> Option<RejectReason> {
+        let code = self.udt.handshake_type.checked_sub(REJECT_BASE)?;
+        u16::try_from(code).ok().map(RejectReason::from_code)
     }
 
     /// Serialize complete handshake
@@ -377,6 +576,49 @@ impl SrtHandshake {
             buf.extend_from_slice(&ext.to_bytes());
         }
 
+        if let Some(ref km) = self.key_material {
+            let ext_type = if self.udt.handshake_type == HandshakeType::Agreement as i32 {
+                SRT_CMD_KMRSP
+            } else {
+                SRT_CMD_KMREQ
+            };
+            let words = (km.len() + 3) / 4;
+            buf.put_u16(ext_type);
+            buf.put_u16(words as u16);
+            buf.put_slice(km);
+            buf.put_bytes(0, words * 4 - km.len());
+        }
+
+        if let Some(ref stream_id) = self.stream_id {
+            put_string_ext(&mut buf, SRT_CMD_SID, stream_id);
+        }
+
+        if let Some(ref filter_config) = self.filter_config {
+            put_string_ext(&mut buf, SRT_CMD_FILTER, filter_config);
+        }
+
+        if let Some(ref congestion) = self.congestion {
+            put_string_ext(&mut buf, SRT_CMD_CONGESTION, congestion);
+        }
+
+        if let Some(time_origin) = self.time_origin {
+            buf.put_u16(SRT_CMD_TIME_ORIGIN);
+            buf.put_u16(2);
+            buf.put_u64(time_origin);
+        }
+
+        if let Some(max_bw) = self.max_bw_request {
+            buf.put_u16(SRT_CMD_MAX_BW);
+            buf.put_u16(2);
+            buf.put_u64(max_bw);
+        }
+
+        if let Some(probe) = self.probe {
+            buf.put_u16(SRT_CMD_PROBE);
+            buf.put_u16((PROBE_PLAN_LEN / 4) as u16);
+            buf.put_slice(&probe.to_bytes());
+        }
+
         buf
     }
 
@@ -384,13 +626,91 @@ impl SrtHandshake {
     pub fn from_bytes(bytes: &[u8]) -> Result<Self, HandshakeError> {
         let udt = UdtHandshake::from_bytes(bytes)?;
 
-        let srt_ext = if bytes.len() > 48 {
-            Some(SrtHandshakeExtension::from_bytes(&bytes[48..])?)
-        } else {
-            None
-        };
+        let mut srt_ext = None;
+        let mut stream_id = None;
+        let mut filter_config = None;
+        let mut congestion = None;
+        let mut time_origin = None;
+        let mut max_bw_request = None;
+        let mut probe = None;
+        let mut key_material = None;
+
+        // Walk the extension blocks: type (16 bits), size in words (16 bits), data
+        let mut rest = &bytes[48..];
+        while !rest.is_empty() {
+            if rest.len() < 4 {
+                return Err(HandshakeError::ExtensionError);
+            }
+            let ext_type = u16::from_be_bytes([rest[0], rest[1]]);
+            let ext_len = 4 + u16::from_be_bytes([rest[2], rest[3]]) as usize * 4;
+            if rest.len() < ext_len {
+                return Err(HandshakeError::ExtensionError);
+            }
+
+            match ext_type {
+                SRT_CMD_HSREQ => {
+                    srt_ext = Some(SrtHandshakeExtension::from_bytes(&rest[..ext_len])?)
+                }
+                SRT_CMD_KMREQ | SRT_CMD_KMRSP => {
+                    key_material = Some(Bytes::copy_from_slice(&rest[4..ext_len]))
+                }
+                SRT_CMD_SID => {
+                    let id = parse_string_ext(&rest[4..ext_len])?;
+                    if id.len() > MAX_STREAM_ID_LEN {
+                        return Err(HandshakeError::StreamIdTooLong(id.len()));
+                    }
+                    stream_id = Some(id);
+                }
+                SRT_CMD_FILTER => filter_config = Some(parse_string_ext(&rest[4..ext_len])?),
+                SRT_CMD_CONGESTION => congestion = Some(parse_string_ext(&rest[4..ext_len])?),
+                SRT_CMD_TIME_ORIGIN => {
+                    if ext_len != 12 {
+                        return Err(HandshakeError::ExtensionError);
+                    }
+                    time_origin = Some((&rest[4..ext_len]).get_u64());
+                }
+                SRT_CMD_MAX_BW => {
+                    if ext_len != 12 {
+                        return Err(HandshakeError::ExtensionError);
+                    }
+                    max_bw_request = Some((&rest[4..ext_len]).get_u64());
+                }
+                SRT_CMD_PROBE => {
+                    probe = Some(
+                        ProbePlan::from_bytes(&rest[4..ext_len])
+                            .ok_or(HandshakeError::ExtensionError)?,
+                    );
+                }
+                _ => {} // Unknown extensions are skipped
+            }
 
-        Ok(SrtHandshake { udt, srt_ext })
+            rest = &rest[ext_len..];
+        }
+
+        Ok(SrtHandshake {
+            udt,
+            srt_ext,
+            stream_id,
+            filter_config,
+            congestion,
+            time_origin,
+            max_bw_request,
+            probe,
+            key_material,
+        })
+    }
+
+    /// Parse a whole datagram as a handshake control packet
+    ///
+    /// Returns None for any other packet or a malformed handshake, so
+    /// drivers can skip stray datagrams while connecting.
+    pub fn from_datagram(datagram: &[u8]) -> Option<Self> {
+        match Packet::from_bytes(datagram).ok()? {
+            Packet::Control(control) if control.control_type() == ControlType::Handshake => {
+                Self::from_bytes(&control.control_info).ok()
+            }
+            _ => None,
+        }
     }
 
     /// Check if this is an SRT handshake (vs plain UDT)
@@ -409,9 +729,40 @@ impl SrtHandshake {
     }
 }
 
+/// Write a string extension block (Stream ID, filter configuration)
+///
+/// The string is zero-padded to a 4-byte boundary and each 32-bit word is
+/// byte-reversed, matching the reference implementation's wire format.
+fn put_string_ext(buf: &mut BytesMut, ext_type: u16, value: &str) {
+    let mut data = value.as_bytes().to_vec();
+    data.resize((data.len() + 3) / 4 * 4, 0);
+
+    buf.put_u16(ext_type);
+    buf.put_u16((data.len() / 4) as u16);
+    for word in data.chunks(4) {
+        buf.put_slice(&[word[3], word[2], word[1], word[0]]);
+    }
+}
+
+/// Parse the data of a string extension block
+fn parse_string_ext(data: &[u8]) -> Result<String, HandshakeError> {
+    let mut bytes = Vec::with_capacity(data.len());
+    for word in data.chunks(4) {
+        bytes.extend(word.iter().rev());
+    }
+
+    // Strip zero padding
+    while bytes.last() == Some(&0) {
+        bytes.pop();
+    }
+
+    String::from_utf8(bytes).map_err(|_| HandshakeError::ExtensionError)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::packet::ControlPacket;
 
     #[test]
     fn test_srt_options_flags() {
@@ -435,6 +786,23 @@ mod tests {
         assert_eq!(decoded.socket_id, hs.socket_id);
     }
 
+    #[test]
+    fn test_peer_addr_roundtrip() {
+        for addr in ["192.168.1.2:0", "[2001:db8::1]:0", "[::ffff:10.0.0.1]:0"] {
+            let addr: SocketAddr = addr.parse().unwrap();
+            let hs = UdtHandshake::new_request(0, 1456, 8192, 1, addr);
+            let decoded = UdtHandshake::from_bytes(&hs.to_bytes()).unwrap();
+            assert_eq!(decoded.peer_addr, addr);
+        }
+
+        // IPv4 in the first word, network byte order
+        let hs = UdtHandshake::new_request(0, 1456, 8192, 1, "192.168.1.2:0".parse().unwrap());
+        assert_eq!(
+            &hs.to_bytes()[32..],
+            &[192, 168, 1, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]
+        );
+    }
+
     #[test]
     fn test_srt_extension_roundtrip() {
         let ext = SrtHandshakeExtension::new(
@@ -472,4 +840,227 @@ mod tests {
         assert!(decoded.is_srt());
         assert_eq!(decoded.udt.socket_id, hs.udt.socket_id);
     }
+
+    #[test]
+    fn test_stream_id_roundtrip() {
+        let mut hs = SrtHandshake::new_request(
+            1000,
+            12345,
+            "127.0.0.1:9000".parse().unwrap(),
+            SrtOptions::default_capabilities(),
+            120,
+            80,
+        );
+        hs.set_stream_id(Some("#!::r=live/cam1,m=publish".to_string()))
+            .unwrap();
+
+        let bytes = hs.to_bytes();
+        // "#!::" is sent byte-reversed within its word
+        assert_eq!(&bytes[68..72], b"::!#");
+
+        let decoded = SrtHandshake::from_bytes(&bytes).unwrap();
+        assert!(decoded.is_srt());
+        assert_eq!(
+            decoded.stream_id.as_deref(),
+            Some("#!::r=live/cam1,m=publish")
+        );
+
+        // No Stream ID extension when unset
+        hs.set_stream_id(None).unwrap();
+        let decoded = SrtHandshake::from_bytes(&hs.to_bytes()).unwrap();
+        assert!(decoded.stream_id.is_none());
+    }
+
+    #[test]
+    fn test_stream_id_too_long() {
+        let mut hs = SrtHandshake::new_request(
+            1000,
+            12345,
+            "127.0.0.1:9000".parse().unwrap(),
+            SrtOptions::default_capabilities(),
+            120,
+            80,
+        );
+        let result = hs.set_stream_id(Some("x".repeat(MAX_STREAM_ID_LEN + 1)));
+        assert!(matches!(result, Err(HandshakeError::StreamIdTooLong(_))));
+    }
+
+    #[test]
+    fn test_filter_config_roundtrip() {
+        let mut hs = SrtHandshake::new_request(
+            1000,
+            12345,
+            "127.0.0.1:9000".parse().unwrap(),
+            SrtOptions::default_capabilities(),
+            120,
+            80,
+        );
+        hs.set_stream_id(Some("cam1".to_string())).unwrap();
+        hs.filter_config = Some("fec,cols:10,rows:5".to_string());
+        hs.congestion = Some("file".to_string());
+
+        let decoded = SrtHandshake::from_bytes(&hs.to_bytes()).unwrap();
+        assert_eq!(decoded.stream_id.as_deref(), Some("cam1"));
+        assert_eq!(decoded.filter_config.as_deref(), Some("fec,cols:10,rows:5"));
+        assert_eq!(decoded.congestion.as_deref(), Some("file"));
+    }
+
+    #[test]
+    fn test_time_origin_roundtrip() {
+        let mut hs = SrtHandshake::new_request(
+            1000,
+            12345,
+            "127.0.0.1:9000".parse().unwrap(),
+            SrtOptions::default_capabilities(),
+            120,
+            80,
+        );
+        let decoded = SrtHandshake::from_bytes(&hs.to_bytes()).unwrap();
+        assert_eq!(decoded.time_origin, None);
+
+        hs.time_origin = Some(1_700_000_000_123_456);
+        hs.set_stream_id(Some("cam1".to_string())).unwrap();
+        let decoded = SrtHandshake::from_bytes(&hs.to_bytes()).unwrap();
+        assert_eq!(decoded.time_origin, Some(1_700_000_000_123_456));
+        assert_eq!(decoded.stream_id.as_deref(), Some("cam1"));
+    }
+
+    #[test]
+    fn test_max_bw_request_roundtrip() {
+        let mut hs = SrtHandshake::new_request(
+            1000,
+            12345,
+            "127.0.0.1:9000".parse().unwrap(),
+            SrtOptions::default_capabilities(),
+            120,
+            80,
+        );
+        let decoded = SrtHandshake::from_bytes(&hs.to_bytes()).unwrap();
+        assert_eq!(decoded.max_bw_request, None);
+
+        hs.max_bw_request = Some(5_000_000_000);
+        hs.time_origin = Some(1);
+        let decoded = SrtHandshake::from_bytes(&hs.to_bytes()).unwrap();
+        assert_eq!(decoded.max_bw_request, Some(5_000_000_000));
+        assert_eq!(decoded.time_origin, Some(1));
+
+        // A malformed block is rejected
+        let mut bytes = SrtHandshake::new_request(
+            1000,
+            12345,
+            "127.0.0.1:9000".parse().unwrap(),
+            SrtOptions::default_capabilities(),
+            120,
+            80,
+        )
+        .to_bytes();
+        bytes.put_u16(SRT_CMD_MAX_BW);
+        bytes.put_u16(1);
+        bytes.put_u32(1);
+        assert!(SrtHandshake::from_bytes(&bytes).is_err());
+    }
+
+    #[test]
+    fn test_probe_roundtrip() {
+        let mut hs = SrtHandshake::new_request(
+            1000,
+            12345,
+            "127.0.0.1:9000".parse().unwrap(),
+            SrtOptions::default_capabilities(),
+            120,
+            80,
+        );
+        assert_eq!(
+            SrtHandshake::from_bytes(&hs.to_bytes()).unwrap().probe,
+            None
+        );
+
+        let plan = ProbePlan {
+            packets: 24,
+            packet_size: 1200,
+            spacing_us: 500,
+        };
+        hs.probe = Some(plan);
+        hs.max_bw_request = Some(1);
+        let decoded = SrtHandshake::from_bytes(&hs.to_bytes()).unwrap();
+        assert_eq!(decoded.probe, Some(plan));
+        assert_eq!(decoded.max_bw_request, Some(1));
+
+        let mut bytes = hs.to_bytes();
+        bytes.put_u16(SRT_CMD_PROBE);
+        bytes.put_u16(1);
+        bytes.put_u32(1);
+        assert!(SrtHandshake::from_bytes(&bytes).is_err());
+    }
+
+    #[test]
+    fn test_from_datagram() {
+        let hs = SrtHandshake::new_request(
+            1000,
+            12345,
+            "127.0.0.1:9000".parse().unwrap(),
+            SrtOptions::default_capabilities(),
+            120,
+            120,
+        );
+        let packet = ControlPacket::new(ControlType::Handshake, 0, 0, 0, 0, hs.to_bytes().freeze());
+        let parsed = SrtHandshake::from_datagram(&packet.to_bytes()).unwrap();
+        assert_eq!(parsed.udt.socket_id, 12345);
+
+        let keepalive =
+            ControlPacket::new(ControlType::KeepAlive, 0, 0, 0, 0, hs.to_bytes().freeze());
+        assert!(SrtHandshake::from_datagram(&keepalive.to_bytes()).is_none());
+        assert!(SrtHandshake::from_datagram(&[0x80, 0, 0, 0]).is_none());
+    }
+
+    #[test]
+    fn test_key_material_roundtrip() {
+        let mut hs = SrtHandshake::new_request(
+            1000,
+            12345,
+            "127.0.0.1:9000".parse().unwrap(),
+            SrtOptions::default_capabilities(),
+            120,
+            120,
+        );
+        assert_eq!(hs.key_material_cipher(), None);
+
+        let mut km = vec![0x12, 0x20, 0x29, 0x01, 0, 0, 0, 0, 2, 0, 2, 0, 0, 0, 4, 4];
+        km.resize(56, 0xAB);
+        hs.set_key_material(Some(Bytes::from(km.clone()))).unwrap();
+        let bytes = hs.to_bytes();
+        assert_eq!(u16::from_be_bytes([bytes[64], bytes[65]]), SRT_CMD_KMREQ);
+        let decoded = SrtHandshake::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.key_material.as_deref(), Some(&km[..]));
+        assert_eq!(decoded.key_material_cipher(), Some(2));
+
+        // Agreements answer with a KMRSP
+        hs.udt.handshake_type = HandshakeType::Agreement as i32;
+        let bytes = hs.to_bytes();
+        assert_eq!(u16::from_be_bytes([bytes[64], bytes[65]]), SRT_CMD_KMRSP);
+        let decoded = SrtHandshake::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.key_material_cipher(), Some(2));
+    }
+
+    #[test]
+    fn test_reject_reason() {
+        let mut hs = SrtHandshake::new_request(
+            1000,
+            12345,
+            "127.0.0.1:9000".parse().unwrap(),
+            SrtOptions::default_capabilities(),
+            120,
+            120,
+        );
+        assert_eq!(hs.reject_reason(), None);
+
+        hs.reject(RejectReason::Unsecure);
+        assert_eq!(hs.udt.handshake_type, 1011);
+        let decoded = SrtHandshake::from_bytes(&hs.to_bytes()).unwrap();
+        assert_eq!(decoded.reject_reason(), Some(RejectReason::Unsecure));
+
+        hs.udt.handshake_type = 1099;
+        assert_eq!(hs.reject_reason(), Some(RejectReason::Other(99)));
+        assert_eq!(RejectReason::Other(99).code(), 99);
+    }
 }
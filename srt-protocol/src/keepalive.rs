@@ -0,0 +1,297 @@
+//! Keep-alive liveness tracking
+//!
+//! A connection sends a keep-alive every interval while it is polled. Peers
+//! built on this crate echo each keep-alive back with its token, which gives
+//! an RTT sample even on an idle link; other SRT peers ignore the token and
+//! are judged only on whether anything arrives from them. Every packet from
+//! the peer counts as hearing from it. A peer silent for the idle timeout
+//! has timed out, as libsrt's peer idle timeout.
+
+use crate::packet::{ControlPacket, ControlType};
+use bytes::Bytes;
+use std::time::{Duration, Instant};
+
+/// Default time between keep-alives
+pub const DEFAULT_KEEPALIVE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Keep-alives missed in a row before the peer is considered gone
+pub const MAX_MISSED_KEEPALIVES: u32 = 4;
+
+/// Default time without hearing from the peer before it times out
+/// (libsrt's `SRTO_PEERIDLETIMEO`)
+pub const DEFAULT_PEER_IDLE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Type-specific info marking a keep-alive as an echo of ours
+const KEEPALIVE_ECHO: u16 = 1;
+
+/// Liveness of the peer, derived from keep-alives
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Liveness {
+    /// When anything was last heard from the peer
+    pub last_heard: Option<Instant>,
+    /// Keep-alives sent in a row without hearing from the peer
+    pub missed_keepalives: u32,
+    /// Round trip of the last echoed keep-alive (microseconds)
+    pub rtt_us: Option<u32>,
+    /// 1.0 while the peer answers, falling to 0.0 at
+    /// `MAX_MISSED_KEEPALIVES` missed
+    pub score: f64,
+}
+
+impl Liveness {
+    /// Check if the peer has missed fewer than `MAX_MISSED_KEEPALIVES`
+    pub fn is_alive(&self) -> bool {
+        self.missed_keepalives < MAX_MISSED_KEEPALIVES
+    }
+}
+
+/// Keep-alive sender and echo tracker
+#[derive(Debug, Clone)]
+pub struct KeepAlive {
+    interval: Duration,
+    last_sent: Option<Instant>,
+    last_heard: Option<Instant>,
+    missed: u32,
+    /// Token and send time of the keep-alive awaiting its echo
+    pending: Option<(u32, Instant)>,
+    next_token: u32,
+    /// Echoes owed to the peer, by token
+    echoes: Vec<u32>,
+    rtt_us: Option<u32>,
+    /// Silence after which the peer has timed out (0 = never)
+    idle_timeout: Duration,
+    /// Start of the silence before anything is heard
+    started: Instant,
+}
+
+impl KeepAlive {
+    /// Create a keep-alive tracker sending every `interval`
+    pub fn new(interval: Duration) -> Self {
+        KeepAlive {
+            interval,
+            last_sent: None,
+            last_heard: None,
+            missed: 0,
+            pending: None,
+            next_token: 1,
+            echoes: Vec::new(),
+            rtt_us: None,
+            idle_timeout: DEFAULT_PEER_IDLE_TIMEOUT,
+            started: Instant::now(),
+        }
+    }
+
+    /// Get the time between keep-alives
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Change the time between keep-alives
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+    }
+
+    /// Get the silence after which the peer times out
+    pub fn idle_timeout(&self) -> Duration {
+        self.idle_timeout
+    }
+
+    /// Change the silence after which the peer times out (0 = never)
+    pub fn set_idle_timeout(&mut self, timeout: Duration) {
+        self.idle_timeout = timeout;
+    }
+
+    /// Get how long nothing has been heard from the peer
+    ///
+    /// Counts from creation until the peer is first heard.
+    pub fn idle_time(&self, now: Instant) -> Duration {
+        now.saturating_duration_since(self.last_heard.unwrap_or(self.started))
+    }
+
+    /// Check if the peer has been silent for the idle timeout
+    pub fn is_timed_out(&self, now: Instant) -> bool {
+        !self.idle_timeout.is_zero() && self.idle_time(now) >= self.idle_timeout
+    }
+
+    /// Record that something arrived from the peer
+    pub fn on_heard(&mut self, now: Instant) {
+        self.last_heard = Some(now);
+        self.missed = 0;
+    }
+
+    /// Handle a keep-alive from the peer
+    ///
+    /// An echo of ours yields an RTT sample, returned in microseconds; any
+    /// other keep-alive is queued to be echoed by `poll`.
+    pub fn on_keepalive(&mut self, packet: &ControlPacket, now: Instant) -> Option<u32> {
+        self.on_heard(now);
+        let token = packet.header.additional_info().unwrap_or(0);
+        if packet.header.type_specific_info() != Some(KEEPALIVE_ECHO) {
+            // Token 0 comes from peers that do not expect an echo
+            if token != 0 {
+                self.echoes.push(token);
+            }
+            return None;
+        }
+
+        let (pending, sent_at) = self.pending?;
+        if pending != token {
+            return None;
+        }
+        self.pending = None;
+        // 0 would read as "no estimate"
+        let rtt_us = u32::try_from(now.saturating_duration_since(sent_at).as_micros())
+            .unwrap_or(u32::MAX)
+            .max(1);
+        self.rtt_us = Some(rtt_us);
+        Some(rtt_us)
+    }
+
+    /// Get the next keep-alive to send, if any
+    ///
+    /// Owed echoes come first, then a keep-alive once the interval has
+    /// passed since the last. Call until it returns None.
+    pub fn poll(&mut self, now: Instant, dest_socket_id: u32) -> Option<ControlPacket> {
+        if let Some(token) = self.echoes.pop() {
+            return Some(keepalive_packet(KEEPALIVE_ECHO, token, dest_socket_id));
+        }
+
+        if self
+            .last_sent
+            .is_some_and(|sent| now.saturating_duration_since(sent) < self.interval)
+        {
+            return None;
+        }
+        // Nothing heard since the previous keep-alive
+        if let Some(sent) = self.last_sent {
+            if self.last_heard.map_or(true, |heard| heard < sent) {
+                self.missed += 1;
+            }
+        }
+
+        let token = self.next_token;
+        self.next_token = self.next_token.wrapping_add(1).max(1);
+        self.last_sent = Some(now);
+        self.pending = Some((token, now));
+        Some(keepalive_packet(0, token, dest_socket_id))
+    }
+
+    /// Get the peer's liveness
+    pub fn liveness(&self) -> Liveness {
+        let missed = self.missed.min(MAX_MISSED_KEEPALIVES);
+        Liveness {
+            last_heard: self.last_heard,
+            missed_keepalives: self.missed,
+            rtt_us: self.rtt_us,
+            score: 1.0 - missed as f64 / MAX_MISSED_KEEPALIVES as f64,
+        }
+    }
+}
+
+impl Default for KeepAlive {
+    fn default() -> Self {
+        Self::new(DEFAULT_KEEPALIVE_INTERVAL)
+    }
+}
+
+fn keepalive_packet(type_specific_info: u16, token: u32, dest_socket_id: u32) -> ControlPacket {
+    ControlPacket::new(
+        ControlType::KeepAlive,
+        type_specific_info,
+        token,
+        0,
+        dest_socket_id,
+        Bytes::new(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    #[test]
+    fn test_keepalive_interval() {
+        let mut keepalive = KeepAlive::default();
+        let start = Instant::now();
+
+        let packet = keepalive.poll(start, 7).unwrap();
+        assert_eq!(packet.control_type(), ControlType::KeepAlive);
+        assert_eq!(packet.header.dest_socket_id, 7);
+        assert!(keepalive.poll(start + SECOND / 2, 7).is_none());
+        assert!(keepalive.poll(start + SECOND, 7).is_some());
+    }
+
+    #[test]
+    fn test_keepalive_echo_rtt() {
+        let mut ours = KeepAlive::default();
+        let mut peer = KeepAlive::default();
+        let start = Instant::now();
+
+        let request = ours.poll(start, 2).unwrap();
+        let request = ControlPacket::from_bytes(&request.to_bytes()).unwrap();
+        assert_eq!(peer.on_keepalive(&request, start), None);
+
+        // The echo goes out ahead of the peer's own keep-alive
+        let echo = peer.poll(start, 1).unwrap();
+        assert_eq!(
+            echo.header.additional_info(),
+            request.header.additional_info()
+        );
+        assert!(peer.poll(start, 1).is_some());
+        assert!(peer.poll(start, 1).is_none());
+
+        let rtt = Duration::from_millis(30);
+        assert_eq!(ours.on_keepalive(&echo, start + rtt), Some(30_000));
+        // A repeated echo is not a new sample
+        assert_eq!(ours.on_keepalive(&echo, start + SECOND), None);
+
+        let liveness = ours.liveness();
+        assert_eq!(liveness.rtt_us, Some(30_000));
+        assert_eq!(liveness.last_heard, Some(start + SECOND));
+        assert_eq!(liveness.score, 1.0);
+    }
+
+    #[test]
+    fn test_missed_keepalives() {
+        let mut keepalive = KeepAlive::default();
+        let start = Instant::now();
+        keepalive.poll(start, 2).unwrap();
+
+        for i in 1..=5 {
+            keepalive.poll(start + SECOND * i, 2).unwrap();
+        }
+        let liveness = keepalive.liveness();
+        assert_eq!(liveness.missed_keepalives, 5);
+        assert_eq!(liveness.score, 0.0);
+        assert!(!liveness.is_alive());
+
+        // Hearing anything resets the count
+        keepalive.on_heard(start + SECOND * 5);
+        keepalive.poll(start + SECOND * 6, 2).unwrap();
+        let liveness = keepalive.liveness();
+        assert_eq!(liveness.missed_keepalives, 0);
+        assert!(liveness.is_alive());
+
+        keepalive.poll(start + SECOND * 7, 2).unwrap();
+        assert_eq!(keepalive.liveness().missed_keepalives, 1);
+        assert_eq!(keepalive.liveness().score, 0.75);
+    }
+
+    #[test]
+    fn test_idle_timeout() {
+        let mut keepalive = KeepAlive::default();
+        let start = Instant::now();
+        assert!(!keepalive.is_timed_out(start));
+        assert!(keepalive.is_timed_out(start + DEFAULT_PEER_IDLE_TIMEOUT));
+
+        keepalive.on_heard(start + SECOND * 3);
+        assert!(!keepalive.is_timed_out(start + SECOND * 7));
+        assert_eq!(keepalive.idle_time(start + SECOND * 8), SECOND * 5);
+        assert!(keepalive.is_timed_out(start + SECOND * 8));
+
+        keepalive.set_idle_timeout(Duration::ZERO);
+        assert!(!keepalive.is_timed_out(start + SECOND * 60));
+    }
+}
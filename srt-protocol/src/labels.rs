@@ -0,0 +1,213 @@
+//! Operator-assigned labels
+//!
+//! Labels are key/value tags (stream name, site, camera ID) set on a
+//! connection or socket group so that stats, events and logs from many
+//! streams on one host can be told apart. Keys follow the Prometheus label
+//! name rules, so a label set can be exported unchanged.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Maximum length of a label key or value
+pub const MAX_LABEL_LEN: usize = 128;
+
+/// Label errors
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum LabelError {
+    #[error("Invalid label key '{0}': use letters, digits and '_', not starting with a digit")]
+    InvalidKey(String),
+
+    #[error("Label '{0}' is longer than {max} bytes", max = MAX_LABEL_LEN)]
+    TooLong(String),
+
+    #[error("Invalid label '{0}': expected key=value")]
+    Malformed(String),
+}
+
+/// Ordered set of key/value labels
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(transparent))]
+pub struct Labels {
+    labels: BTreeMap<String, String>,
+}
+
+impl Labels {
+    /// Create an empty label set
+    pub fn new() -> Self {
+        Labels::default()
+    }
+
+    /// Set a label, replacing any previous value for `key`
+    pub fn insert(&mut self, key: &str, value: &str) -> Result<(), LabelError> {
+        if !is_valid_key(key) {
+            return Err(LabelError::InvalidKey(key.to_string()));
+        }
+        if key.len() > MAX_LABEL_LEN || value.len() > MAX_LABEL_LEN {
+            return Err(LabelError::TooLong(key.to_string()));
+        }
+        self.labels.insert(key.to_string(), value.to_string());
+        Ok(())
+    }
+
+    /// Set a label, builder style
+    pub fn with(mut self, key: &str, value: &str) -> Result<Self, LabelError> {
+        self.insert(key, value)?;
+        Ok(self)
+    }
+
+    /// Remove a label
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        self.labels.remove(key)
+    }
+
+    /// Get a label value
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.labels.get(key).map(String::as_str)
+    }
+
+    /// Iterate over labels in key order
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.labels.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// Get the number of labels
+    pub fn len(&self) -> usize {
+        self.labels.len()
+    }
+
+    /// Check if there are no labels
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+
+    /// Combine with more specific labels; `other` wins on conflicts
+    ///
+    /// Used to attach group labels to a member's own labels.
+    pub fn merged(&self, other: &Labels) -> Labels {
+        let mut labels = self.clone();
+        labels.labels.extend(other.labels.clone());
+        labels
+    }
+
+    /// Create a tracing span carrying these labels
+    ///
+    /// Events logged while the span is entered are tagged with the labels.
+    pub fn span(&self) -> tracing::Span {
+        tracing::info_span!("srt", labels = %self)
+    }
+
+    /// Format as a Prometheus label set, e.g. `{camera="3",site="nyc"}`
+    ///
+    /// Returns an empty string when there are no labels.
+    pub fn to_prometheus(&self) -> String {
+        if self.is_empty() {
+            return String::new();
+        }
+        let pairs: Vec<_> = self
+            .iter()
+            .map(|(k, v)| {
+                let value = v
+                    .replace('\\', "\\\\")
+                    .replace('"', "\\\"")
+                    .replace('\n', "\\n");
+                format!("{}=\"{}\"", k, value)
+            })
+            .collect();
+        format!("{{{}}}", pairs.join(","))
+    }
+}
+
+/// Prometheus label names: `[a-zA-Z_][a-zA-Z0-9_]*`
+fn is_valid_key(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+impl fmt::Display for Labels {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (key, value)) in self.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{}={}", key, value)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Labels {
+    type Err = LabelError;
+
+    /// Parse comma-separated `key=value` pairs (`site=nyc,camera=3`)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut labels = Labels::new();
+        for pair in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (key, value) = pair
+                .split_once('=')
+                .ok_or_else(|| LabelError::Malformed(pair.to_string()))?;
+            labels.insert(key.trim(), value.trim())?;
+        }
+        Ok(labels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_display() {
+        let labels: Labels = "site=nyc, camera=3,stream=main".parse().unwrap();
+        assert_eq!(labels.len(), 3);
+        assert_eq!(labels.get("camera"), Some("3"));
+        // Key order, not insertion order
+        assert_eq!(labels.to_string(), "camera=3,site=nyc,stream=main");
+        assert_eq!("".parse::<Labels>().unwrap(), Labels::new());
+
+        assert!(matches!(
+            "site".parse::<Labels>(),
+            Err(LabelError::Malformed(_))
+        ));
+        assert!(matches!(
+            "3cam=x".parse::<Labels>(),
+            Err(LabelError::InvalidKey(_))
+        ));
+        assert!(matches!(
+            "site-name=x".parse::<Labels>(),
+            Err(LabelError::InvalidKey(_))
+        ));
+        let long = format!("site={}", "x".repeat(MAX_LABEL_LEN + 1));
+        assert!(matches!(
+            long.parse::<Labels>(),
+            Err(LabelError::TooLong(_))
+        ));
+    }
+
+    #[test]
+    fn test_merged() {
+        let group: Labels = "site=nyc,stream=main".parse().unwrap();
+        let member: Labels = "path=cell1,stream=backup".parse().unwrap();
+
+        let labels = group.merged(&member);
+        assert_eq!(labels.to_string(), "path=cell1,site=nyc,stream=backup");
+        assert_eq!(group.merged(&Labels::new()), group);
+    }
+
+    #[test]
+    fn test_prometheus() {
+        let labels = Labels::new()
+            .with("site", "nyc")
+            .unwrap()
+            .with("name", "cam \"A\"\\1")
+            .unwrap();
+        assert_eq!(
+            labels.to_prometheus(),
+            r#"{name="cam \"A\"\\1",site="nyc"}"#
+        );
+        assert_eq!(Labels::new().to_prometheus(), "");
+    }
+}
@@ -3,21 +3,82 @@
 //! This crate implements the core SRT (Secure Reliable Transport) protocol,
 //! including packet structures, handshake, connection state machine, buffers,
 //! loss tracking, ACK/NAK generation, and congestion control.
+#![deny(unsafe_code)]
 
 pub mod ack;
 pub mod buffer;
 pub mod congestion;
 pub mod connection;
+pub mod diagnostics;
+pub mod fec;
+pub mod filter;
 pub mod handshake;
+pub mod keepalive;
+pub mod labels;
 pub mod loss;
+pub mod mux;
 pub mod packet;
+pub mod packetizer;
+pub mod peercache;
+pub mod probe;
+pub mod sender;
 pub mod sequence;
+pub mod srctime;
+pub mod trace;
+pub mod watchdog;
+pub mod wire;
 
-pub use ack::{AckGenerator, AckInfo, NakGenerator, NakInfo, RttEstimator};
-pub use buffer::{BufferError, ReceiveBuffer, SendBuffer};
-pub use congestion::{BandwidthEstimator, CongestionController, CongestionStats};
-pub use connection::{Connection, ConnectionError, ConnectionState, ConnectionStats};
-pub use handshake::{HandshakeError, SrtHandshake, SrtOptions};
-pub use loss::{LossRange, ReceiverLossList, SenderLossList};
-pub use packet::{ControlPacket, DataPacket, MsgNumber, Packet, PacketBoundary, PacketType};
-pub use sequence::SeqNumber;
+pub use ack::{
+    AckGenerator, AckInfo, ArrivalEstimator, DropReqInfo, NakGenerator, NakInfo, RttEstimator,
+};
+pub use buffer::{
+    BufferError, BufferSizing, ReceiveBuffer, ReceiveDrain, SendBuffer, DEFAULT_BUFFER_PACKETS,
+    MAX_BUFFER_PACKETS, MIN_BUFFER_PACKETS,
+};
+pub use congestion::{
+    BandwidthEstimator, BandwidthLimiter, CongestionController, CongestionMode, CongestionStats,
+    LiveCongestionController, RetransmitBudget, UnknownCongestionMode, DEFAULT_OVERHEAD_PERCENT,
+};
+pub use connection::{
+    Connection, ConnectionError, ConnectionState, ConnectionStats, ReceivedMessage,
+    RetransmitPolicy, SendLatencyEvent, CONTROL_MAX_BW, DEFAULT_SEND_LATENCY_ALARM,
+    DEFAULT_SEND_TTL_FACTOR, MAX_LATENCY_MS,
+};
+pub use diagnostics::{
+    ConnectionDiagnostics, DiagnosticEvent, LossSummary, NegotiatedOptions, RecvBufferDiagnostics,
+    SendBufferDiagnostics, StatsSample,
+};
+pub use fec::FecFilter;
+pub use filter::{
+    builtin_filter, is_filter_packet, ArqMode, FilterConfig, FilterError, FilterFactory,
+    FilterStats, PacketFilter, FILTER_MSG_NUMBER,
+};
+pub use handshake::{HandshakeError, RejectReason, SrtHandshake, SrtOptions};
+pub use keepalive::{
+    KeepAlive, Liveness, DEFAULT_KEEPALIVE_INTERVAL, DEFAULT_PEER_IDLE_TIMEOUT,
+    MAX_MISSED_KEEPALIVES,
+};
+pub use labels::{LabelError, Labels};
+pub use loss::{DroppedRanges, LossRange, ReceiverLossList, SenderLossList};
+pub use mux::{
+    MuxError, MuxStreamStats, StreamDemux, StreamHandler, StreamMux, DEFAULT_REORDER_WINDOW,
+    MUX_HEADER_LEN,
+};
+pub use packet::{
+    ControlPacket, DataPacket, MsgNumber, Packet, PacketBoundary, PacketType, RawControlType,
+};
+pub use packetizer::{Depacketizer, Packetizer};
+pub use peercache::{
+    CapabilityChange, PeerCacheStats, PeerCapabilities, PeerCapabilityCache, PeerKey,
+    DEFAULT_PEER_CACHE_CAPACITY, DEFAULT_PEER_CACHE_TTL,
+};
+pub use probe::{
+    is_probe_packet, ProbePlan, ProbeReport, ProbeResult, MAX_PROBE_PACKETS, MAX_PROBE_SPACING_US,
+};
+pub use sender::{SendAction, SenderWorker};
+pub use sequence::{SeqNumber, SeqRange};
+pub use srctime::{SourceClock, SourceTimeError};
+pub use trace::{PacketTracer, TraceConfig, TraceError, TraceKind};
+pub use watchdog::{
+    DeliveryWatchdog, StallAction, StallDiagnostics, StallEvent, UnknownStallAction,
+};
@@ -3,7 +3,8 @@
 //! Tracks lost packets for NAK (Negative Acknowledgment) generation and
 //! retransmission scheduling.
 
-use crate::sequence::SeqNumber;
+use crate::sequence::{SeqNumber, MAX_SEQ_NUMBER};
+use std::collections::VecDeque;
 use std::time::Instant;
 
 /// Loss sequence range (inclusive)
@@ -31,12 +32,15 @@ impl LossRange {
 
     /// Check if this range contains a sequence number
     pub fn contains(&self, seq: SeqNumber) -> bool {
-        seq.ge(self.start) && seq.le(self.end)
+        // Offset from start, so the check agrees with `len` across the wrap
+        let offset = self.start.distance_to(seq);
+        offset >= 0 && (offset as usize) < self.len()
     }
 
     /// Get the length of this range
     pub fn len(&self) -> usize {
-        (self.end.as_raw().wrapping_sub(self.start.as_raw()) + 1) as usize
+        // Mask to 31 bits so ranges spanning the sequence wrap are counted
+        ((self.end.as_raw().wrapping_sub(self.start.as_raw()) & MAX_SEQ_NUMBER) + 1) as usize
     }
 
     /// Check if range is empty (always false for valid ranges)
@@ -51,8 +55,9 @@ impl LossRange {
 
     /// Merge with another range if they overlap or are adjacent
     pub fn try_merge(&self, other: &LossRange) -> Option<LossRange> {
-        // Check if ranges overlap or are adjacent
-        if other.start.le(self.end.next()) && other.end.ge(self.start - 1) {
+        // Overlapping or adjacent: each range starts no later than one past
+        // the other's end. `next()` wraps MAX_SEQ_NUMBER to 0 correctly.
+        if other.start.le(self.end.next()) && self.start.le(other.end.next()) {
             Some(LossRange {
                 start: if self.start.lt(other.start) {
                     self.start
@@ -170,13 +175,13 @@ impl LossList {
                 } else if seq == entry.range.end {
                     // Remove last packet of range
                     new_losses.push(LossEntry {
-                        range: LossRange::new(entry.range.start, entry.range.end - 1),
+                        range: LossRange::new(entry.range.start, entry.range.end.prev()),
                         ..entry
                     });
                 } else {
                     // Remove middle packet, split into two ranges
                     new_losses.push(LossEntry {
-                        range: LossRange::new(entry.range.start, seq - 1),
+                        range: LossRange::new(entry.range.start, seq.prev()),
                         detected_at: entry.detected_at,
                         last_nak_sent: entry.last_nak_sent,
                         nak_count: entry.nak_count,
@@ -288,6 +293,11 @@ impl SenderLossList {
         self.inner.remove(seq);
     }
 
+    /// Remove all packets up to and including `seq` (acknowledged)
+    pub fn remove_up_to(&mut self, seq: SeqNumber) {
+        self.inner.remove_up_to(seq);
+    }
+
     /// Get next packet to retransmit
     pub fn pop_next(&mut self) -> Option<SeqNumber> {
         if let Some(entry) = self.inner.losses.first() {
@@ -299,6 +309,11 @@ impl SenderLossList {
         }
     }
 
+    /// Get the ranges that need retransmission, in order
+    pub fn ranges(&self) -> Vec<LossRange> {
+        self.inner.ranges()
+    }
+
     /// Get all packets that need retransmission
     pub fn get_all(&self) -> Vec<SeqNumber> {
         let mut packets = Vec::new();
@@ -379,6 +394,86 @@ impl ReceiverLossList {
     }
 }
 
+/// Sequence ranges the sender has given up on (DropReq)
+///
+/// Receivers consult it when the next expected packet is missing: a packet
+/// in a dropped range will not be retransmitted, so delivery moves past it
+/// instead of waiting. Packets in the range that did arrive are still
+/// delivered.
+#[derive(Debug, Clone, Default)]
+pub struct DroppedRanges {
+    /// Ranges ordered by start
+    ranges: VecDeque<LossRange>,
+}
+
+impl DroppedRanges {
+    /// Create an empty set of dropped ranges
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a dropped range
+    ///
+    /// The part before `next_expected` is ignored. Returns false if nothing
+    /// of the range is left.
+    pub fn add(&mut self, range: LossRange, next_expected: SeqNumber) -> bool {
+        if range.end.lt(next_expected) {
+            return false;
+        }
+        let start = if range.start.lt(next_expected) {
+            next_expected
+        } else {
+            range.start
+        };
+        let range = LossRange::new(start, range.end);
+        let pos = self
+            .ranges
+            .iter()
+            .position(|r| r.start.gt(start))
+            .unwrap_or(self.ranges.len());
+        self.ranges.insert(pos, range);
+        true
+    }
+
+    /// Check if a sequence number is in a dropped range
+    pub fn contains(&self, seq: SeqNumber) -> bool {
+        self.ranges.iter().any(|r| r.contains(seq))
+    }
+
+    /// Advance `next_expected` over dropped packets that are not present
+    ///
+    /// Stops at the first packet `is_present` reports, so it can be
+    /// delivered. Returns the number of packets skipped.
+    pub fn skip<F>(&mut self, next_expected: &mut SeqNumber, mut is_present: F) -> usize
+    where
+        F: FnMut(SeqNumber) -> bool,
+    {
+        let mut skipped = 0;
+        while let Some(range) = self.ranges.front() {
+            if range.end.lt(*next_expected) {
+                self.ranges.pop_front();
+                continue;
+            }
+            if !range.contains(*next_expected) || is_present(*next_expected) {
+                break;
+            }
+            *next_expected = next_expected.next();
+            skipped += 1;
+        }
+        skipped
+    }
+
+    /// Check if no ranges are pending
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Get the number of pending ranges
+    pub fn len(&self) -> usize {
+        self.ranges.len()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -394,6 +489,13 @@ mod tests {
         assert_eq!(merged.end, SeqNumber::new(20));
     }
 
+    #[test]
+    fn test_loss_range_len_across_wrap() {
+        let range = LossRange::new(SeqNumber::new(MAX_SEQ_NUMBER - 1), SeqNumber::new(1));
+        assert_eq!(range.len(), 4);
+        assert!(range.contains(SeqNumber::new(0)));
+    }
+
     #[test]
     fn test_loss_range_no_merge() {
         let r1 = LossRange::new(SeqNumber::new(10), SeqNumber::new(15));
@@ -403,6 +505,70 @@ mod tests {
         assert!(r1.try_merge(&r2).is_none());
     }
 
+    #[test]
+    fn test_loss_range_merge_across_wrap() {
+        let max = SeqNumber::new(MAX_SEQ_NUMBER);
+        let zero = SeqNumber::new(0);
+
+        // MAX and 0 are adjacent, in either order
+        let merged = LossRange::single(max)
+            .try_merge(&LossRange::single(zero))
+            .unwrap();
+        assert_eq!(merged, LossRange::new(max, zero));
+        assert_eq!(
+            LossRange::single(zero).try_merge(&LossRange::single(max)),
+            Some(merged)
+        );
+        assert_eq!(merged.len(), 2);
+        assert!(merged.contains(max) && merged.contains(zero));
+        assert!(!merged.contains(SeqNumber::new(1)));
+        assert!(!merged.contains(max.prev()));
+
+        // A range starting at 0 does not reach back past the wrap
+        let r = LossRange::new(zero, SeqNumber::new(3));
+        assert!(r.try_merge(&LossRange::single(max.prev())).is_none());
+    }
+
+    #[test]
+    fn test_dropped_ranges_skip() {
+        let mut dropped = DroppedRanges::new();
+        let mut next = SeqNumber::new(10);
+
+        // Entirely delivered already
+        assert!(!dropped.add(LossRange::new(SeqNumber::new(2), SeqNumber::new(5)), next));
+        assert!(dropped.add(LossRange::new(SeqNumber::new(20), SeqNumber::new(22)), next));
+        assert!(dropped.add(LossRange::new(SeqNumber::new(8), SeqNumber::new(14)), next));
+        assert_eq!(dropped.len(), 2);
+        assert!(dropped.contains(SeqNumber::new(10)));
+        assert!(!dropped.contains(SeqNumber::new(8)));
+
+        // 12 arrived after all and stops the skip
+        let present = |seq: SeqNumber| seq == SeqNumber::new(12);
+        assert_eq!(dropped.skip(&mut next, present), 2);
+        assert_eq!(next, SeqNumber::new(12));
+
+        // Delivered; 13 and 14 are skipped, 15 is not dropped
+        next = next.next();
+        assert_eq!(dropped.skip(&mut next, present), 2);
+        assert_eq!(next, SeqNumber::new(15));
+        assert_eq!(dropped.len(), 1);
+
+        next = SeqNumber::new(20);
+        assert_eq!(dropped.skip(&mut next, |_| false), 3);
+        assert_eq!(next, SeqNumber::new(23));
+        assert_eq!(dropped.skip(&mut next, |_| false), 0);
+        assert!(dropped.is_empty());
+    }
+
+    #[test]
+    fn test_dropped_ranges_across_wrap() {
+        let mut dropped = DroppedRanges::new();
+        let mut next = SeqNumber::new(MAX_SEQ_NUMBER - 1);
+        assert!(dropped.add(LossRange::new(next, SeqNumber::new(1)), next));
+        assert_eq!(dropped.skip(&mut next, |_| false), 4);
+        assert_eq!(next, SeqNumber::new(2));
+    }
+
     #[test]
     fn test_loss_list_add_remove() {
         let mut list = LossList::new(3, std::time::Duration::from_millis(100));
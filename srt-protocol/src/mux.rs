@@ -0,0 +1,368 @@
+//! Several logical streams over one connection
+//!
+//! Audio, video and metadata can share one SRT session instead of opening
+//! a connection each. [`StreamMux`] prefixes every message with a 6-byte
+//! header, the stream tag and a per-stream sequence number:
+//!
+//! ```text
+//!  0                   1                   2                   3
+//!  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+//! +-------------------------------+-------------------------------+
+//! |          Stream tag           |  Stream sequence (high 16)    |
+//! +-------------------------------+-------------------------------+
+//! |  Stream sequence (low 16)     |  Payload ...
+//! +-------------------------------+
+//! ```
+//!
+//! [`StreamDemux`] strips the header and hands each message to its stream's
+//! handler, in order within the stream. With out-of-order delivery enabled
+//! on the connection, a late message of one stream does not hold back the
+//! others; within a stream, messages are held up to a reorder window and a
+//! gap that outlasts it is skipped.
+
+use bytes::{BufMut, Bytes, BytesMut};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+/// Length of the header before each message payload
+pub const MUX_HEADER_LEN: usize = 6;
+
+/// Default number of messages held per stream while waiting for a gap
+pub const DEFAULT_REORDER_WINDOW: usize = 64;
+
+/// Multiplexing errors
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum MuxError {
+    #[error("Message too short for a stream header: {0} bytes (need {MUX_HEADER_LEN})")]
+    Truncated(usize),
+}
+
+/// Delivery callback for one stream's messages
+pub type StreamHandler = Arc<dyn Fn(Bytes) + Send + Sync>;
+
+/// Tags outgoing messages with their stream
+#[derive(Debug, Default)]
+pub struct StreamMux {
+    next_seq: HashMap<u16, u32>,
+}
+
+impl StreamMux {
+    /// Create a multiplexer with every stream at sequence 0
+    pub fn new() -> Self {
+        StreamMux::default()
+    }
+
+    /// Prefix `payload` with the header for the next message of `stream`
+    ///
+    /// Send the result as one message (message mode).
+    pub fn encode(&mut self, stream: u16, payload: &[u8]) -> Bytes {
+        let seq = self.next_seq.entry(stream).or_insert(0);
+        let mut message = BytesMut::with_capacity(MUX_HEADER_LEN + payload.len());
+        message.put_u16(stream);
+        message.put_u32(*seq);
+        message.put_slice(payload);
+        *seq = seq.wrapping_add(1);
+        message.freeze()
+    }
+}
+
+/// Per-stream receive counters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct MuxStreamStats {
+    /// Messages handed to the stream's handler
+    pub delivered: u64,
+    /// Messages never received, skipped when a gap outlasted the window
+    pub lost: u64,
+    /// Messages received after the stream had moved past them
+    pub stale: u64,
+    /// Messages received while the stream had no handler
+    pub unhandled: u64,
+    /// Messages held waiting for an earlier one
+    pub pending: usize,
+}
+
+/// Reorder state of one stream
+#[derive(Debug, Default)]
+struct StreamState {
+    /// Sequence of the next message to deliver (unset until the first)
+    next: Option<u32>,
+    /// Held messages; index 0 is `next`
+    held: VecDeque<Option<Bytes>>,
+    stats: MuxStreamStats,
+}
+
+impl StreamState {
+    /// Take the next message if it is here, or skip it as lost if `force`
+    fn pop(&mut self, force: bool) -> Option<Option<Bytes>> {
+        let front = self.held.front()?;
+        if front.is_none() && !force {
+            return None;
+        }
+        let message = self.held.pop_front().flatten();
+        if message.is_none() {
+            self.stats.lost += 1;
+        }
+        self.next = self.next.map(|n| n.wrapping_add(1));
+        Some(message)
+    }
+}
+
+/// Splits received messages back into their streams
+pub struct StreamDemux {
+    streams: HashMap<u16, StreamState>,
+    handlers: HashMap<u16, StreamHandler>,
+    reorder_window: usize,
+}
+
+impl Default for StreamDemux {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StreamDemux {
+    /// Create a demultiplexer with the default reorder window
+    pub fn new() -> Self {
+        StreamDemux {
+            streams: HashMap::new(),
+            handlers: HashMap::new(),
+            reorder_window: DEFAULT_REORDER_WINDOW,
+        }
+    }
+
+    /// Get the number of messages held per stream for a missing one
+    pub fn reorder_window(&self) -> usize {
+        self.reorder_window
+    }
+
+    /// Set the number of messages held per stream for a missing one
+    ///
+    /// 0 delivers every message as it arrives, skipping any gap.
+    pub fn set_reorder_window(&mut self, window: usize) {
+        self.reorder_window = window;
+    }
+
+    /// Deliver `stream`'s messages to `handler`
+    pub fn set_handler<F>(&mut self, stream: u16, handler: F)
+    where
+        F: Fn(Bytes) + Send + Sync + 'static,
+    {
+        self.handlers.insert(stream, Arc::new(handler));
+    }
+
+    /// Stop delivering `stream`'s messages; they are counted as unhandled
+    pub fn clear_handler(&mut self, stream: u16) {
+        self.handlers.remove(&stream);
+    }
+
+    /// Get a stream's counters, once a message of it was received
+    pub fn stats(&self, stream: u16) -> Option<MuxStreamStats> {
+        self.streams.get(&stream).map(|state| MuxStreamStats {
+            pending: state.held.iter().flatten().count(),
+            ..state.stats
+        })
+    }
+
+    /// Tags of the streams received so far
+    pub fn streams(&self) -> impl Iterator<Item = u16> + '_ {
+        self.streams.keys().copied()
+    }
+
+    /// Process one received message
+    ///
+    /// Hands it, and any held messages it unblocks, to the stream's
+    /// handler. Returns the stream tag.
+    pub fn receive(&mut self, message: Bytes) -> Result<u16, MuxError> {
+        if message.len() < MUX_HEADER_LEN {
+            return Err(MuxError::Truncated(message.len()));
+        }
+        let stream = u16::from_be_bytes([message[0], message[1]]);
+        let seq = u32::from_be_bytes([message[2], message[3], message[4], message[5]]);
+        let payload = message.slice(MUX_HEADER_LEN..);
+
+        let state = self.streams.entry(stream).or_default();
+        let next = *state.next.get_or_insert(seq);
+        let offset = seq.wrapping_sub(next) as i32;
+        if offset < 0 {
+            state.stats.stale += 1;
+            return Ok(stream);
+        }
+
+        // Make room, skipping gaps that outlast the window
+        let mut ready = Vec::new();
+        let offset = offset as usize;
+        let mut behind = offset.saturating_sub(self.reorder_window);
+        while behind > 0 {
+            if let Some(message) = state.pop(true) {
+                ready.extend(message);
+            } else {
+                // Nothing held: jump straight past the gap
+                state.stats.lost += behind as u64;
+                state.next = Some(seq.wrapping_sub(self.reorder_window as u32));
+                break;
+            }
+            behind -= 1;
+        }
+        let index = seq.wrapping_sub(state.next.unwrap_or(seq)) as usize;
+        if state.held.len() <= index {
+            state.held.resize(index + 1, None);
+        }
+        if state.held[index].is_some() {
+            // Duplicate of a held message
+            state.stats.stale += 1;
+        }
+        state.held[index] = Some(payload);
+        while let Some(message) = state.pop(false) {
+            ready.extend(message);
+        }
+
+        self.deliver(stream, ready);
+        Ok(stream)
+    }
+
+    /// Deliver every held message, skipping the gaps before them
+    ///
+    /// For the end of a session, when the missing messages won't come.
+    pub fn flush(&mut self) {
+        let tags: Vec<u16> = self.streams.keys().copied().collect();
+        for stream in tags {
+            let state = self.streams.get_mut(&stream).expect("known stream");
+            let mut ready = Vec::new();
+            while !state.held.iter().all(Option::is_none) {
+                ready.extend(state.pop(true).flatten());
+            }
+            state.held.clear();
+            self.deliver(stream, ready);
+        }
+    }
+
+    fn deliver(&mut self, stream: u16, messages: Vec<Bytes>) {
+        let state = self.streams.get_mut(&stream).expect("known stream");
+        let count = messages.len() as u64;
+        match self.handlers.get(&stream) {
+            Some(handler) => {
+                state.stats.delivered += count;
+                for message in messages {
+                    handler(message);
+                }
+            }
+            None => state.stats.unhandled += count,
+        }
+    }
+}
+
+impl std::fmt::Debug for StreamDemux {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StreamDemux")
+            .field("streams", &self.streams)
+            .field("handlers", &self.handlers.keys().collect::<Vec<_>>())
+            .field("reorder_window", &self.reorder_window)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parking_lot::Mutex;
+
+    fn collect(demux: &mut StreamDemux, stream: u16) -> Arc<Mutex<Vec<Bytes>>> {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&received);
+        demux.set_handler(stream, move |message| sink.lock().push(message));
+        received
+    }
+
+    #[test]
+    fn test_streams_are_split_and_tagged() {
+        let mut mux = StreamMux::new();
+        let mut demux = StreamDemux::new();
+        let audio = collect(&mut demux, 1);
+        let video = collect(&mut demux, 2);
+
+        let first = mux.encode(1, b"a0");
+        assert_eq!(first.len(), MUX_HEADER_LEN + 2);
+        assert_eq!(&first[..MUX_HEADER_LEN], &[0, 1, 0, 0, 0, 0]);
+        assert_eq!(demux.receive(first), Ok(1));
+        assert_eq!(demux.receive(mux.encode(2, b"v0")), Ok(2));
+        assert_eq!(demux.receive(mux.encode(1, b"a1")), Ok(1));
+        assert_eq!(demux.receive(mux.encode(3, b"meta")), Ok(3));
+
+        assert_eq!(*audio.lock(), vec![&b"a0"[..], &b"a1"[..]]);
+        assert_eq!(*video.lock(), vec![&b"v0"[..]]);
+        assert_eq!(demux.stats(1).unwrap().delivered, 2);
+        assert_eq!(demux.stats(3).unwrap().unhandled, 1);
+        assert_eq!(demux.stats(4), None);
+        assert_eq!(
+            demux.receive(Bytes::from_static(b"\x00\x01")),
+            Err(MuxError::Truncated(2))
+        );
+    }
+
+    #[test]
+    fn test_late_message_holds_back_only_its_stream() {
+        let mut mux = StreamMux::new();
+        let mut demux = StreamDemux::new();
+        let audio = collect(&mut demux, 1);
+        let video = collect(&mut demux, 2);
+
+        demux.receive(mux.encode(2, b"v0")).unwrap();
+        let v1 = mux.encode(2, b"v1");
+        demux.receive(mux.encode(1, b"a0")).unwrap();
+        demux.receive(mux.encode(2, b"v2")).unwrap();
+        demux.receive(mux.encode(1, b"a1")).unwrap();
+        assert_eq!(audio.lock().len(), 2);
+        assert_eq!(video.lock().len(), 1);
+        assert_eq!(demux.stats(2).unwrap().pending, 1);
+
+        demux.receive(v1.clone()).unwrap();
+        assert_eq!(*video.lock(), vec![&b"v0"[..], &b"v1"[..], &b"v2"[..]]);
+        // Repeated after delivery
+        demux.receive(v1).unwrap();
+        assert_eq!(demux.stats(2).unwrap().stale, 1);
+    }
+
+    #[test]
+    fn test_gap_skipped_after_window() {
+        let mut mux = StreamMux::new();
+        let mut demux = StreamDemux::new();
+        demux.set_reorder_window(2);
+        let video = collect(&mut demux, 2);
+
+        let messages: Vec<Bytes> = (0..5u8).map(|i| mux.encode(2, &[i])).collect();
+        demux.receive(messages[0].clone()).unwrap();
+        // 1 is lost; 2 and 3 fit the window
+        demux.receive(messages[2].clone()).unwrap();
+        demux.receive(messages[3].clone()).unwrap();
+        assert_eq!(video.lock().len(), 1);
+        // 4 no longer fits: the gap is skipped
+        demux.receive(messages[4].clone()).unwrap();
+        let delivered: Vec<u8> = video.lock().iter().map(|m| m[0]).collect();
+        assert_eq!(delivered, vec![0, 2, 3, 4]);
+        let stats = demux.stats(2).unwrap();
+        assert_eq!((stats.lost, stats.pending), (1, 0));
+
+        // A jump far ahead skips everything between
+        demux.receive(mux.encode(2, &[99])).unwrap();
+        let far = (0..20).map(|_| mux.encode(2, &[7])).last().unwrap();
+        demux.receive(far).unwrap();
+        assert_eq!(demux.stats(2).unwrap().pending, 1);
+        demux.flush();
+        assert_eq!(video.lock().len(), 6);
+        assert_eq!(demux.stats(2).unwrap().lost, 20);
+    }
+
+    #[test]
+    fn test_sequence_wraps() {
+        let mut mux = StreamMux::new();
+        mux.next_seq.insert(5, u32::MAX);
+        let mut demux = StreamDemux::new();
+        let data = collect(&mut demux, 5);
+        let last = mux.encode(5, b"x");
+        let first = mux.encode(5, b"y");
+        demux.receive(last).unwrap();
+        demux.receive(first).unwrap();
+        assert_eq!(*data.lock(), vec![&b"x"[..], &b"y"[..]]);
+    }
+}
@@ -12,8 +12,25 @@ use thiserror::Error;
 /// Size of the SRT packet header in bytes (4 fields × 4 bytes each)
 pub const HEADER_SIZE: usize = 16;
 
+/// Size of the IPv4 + UDP headers preceding every SRT packet
+pub const UDP_IP_HEADER_SIZE: usize = 28;
+
+/// Default maximum segment size (Ethernet MTU)
+pub const DEFAULT_MSS: usize = 1500;
+
+/// Smallest MSS accepted by SRT
+pub const MIN_MSS: usize = 76;
+
+/// Largest MSS accepted (jumbo frames)
+pub const MAX_MSS: usize = 9000;
+
 /// Maximum payload size for SRT packet (MTU 1500 - IP/UDP headers - SRT header)
-pub const MAX_PAYLOAD_SIZE: usize = 1456; // 1500 - 28 (IP+UDP) - 16 (SRT header)
+pub const MAX_PAYLOAD_SIZE: usize = max_payload_for_mss(DEFAULT_MSS);
+
+/// Maximum payload size that fits in a packet of the given MSS
+pub const fn max_payload_for_mss(mss: usize) -> usize {
+    mss.saturating_sub(UDP_IP_HEADER_SIZE + HEADER_SIZE)
+}
 
 /// Maximum timestamp value (32-bit)
 pub const MAX_TIMESTAMP: u32 = 0xFFFF_FFFF;
@@ -25,6 +42,7 @@ const CONTROL_FLAG: u32 = 0x8000_0000;
 const SEQ_MASK: u32 = 0x7FFF_FFFF;
 
 /// Packet header fields (each field is 32 bits / 4 bytes)
+#[deprecated(since = "0.1.4", note = "unused; use the `PacketHeader` fields")]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
 pub enum HeaderField {
@@ -65,6 +83,22 @@ pub enum ControlType {
 }
 
 impl ControlType {
+    /// Every control type, in wire order
+    pub const ALL: [ControlType; 10] = [
+        ControlType::Handshake,
+        ControlType::KeepAlive,
+        ControlType::Ack,
+        ControlType::Nak,
+        ControlType::CongestionWarning,
+        ControlType::Shutdown,
+        ControlType::AckAck,
+        ControlType::DropReq,
+        ControlType::PeerError,
+        ControlType::UserDefined,
+    ];
+
+    /// Get the control type for a wire value, or `None` if this
+    /// implementation does not know it (see [`RawControlType`])
     pub fn from_u16(value: u16) -> Option<Self> {
         match value {
             0 => Some(ControlType::Handshake),
@@ -86,6 +120,46 @@ impl ControlType {
     }
 }
 
+/// Control type field as carried on the wire, known or not
+///
+/// `ControlType` only has the types this implementation handles. This
+/// keeps any 15-bit value, so a type from a later spec survives parsing
+/// with [`ControlPacket::parse_raw`] and is serialized back unchanged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RawControlType(u16);
+
+impl RawControlType {
+    /// Wrap a wire value; only the low 15 bits fit in the header
+    pub const fn new(value: u16) -> Self {
+        RawControlType(value & 0x7FFF)
+    }
+
+    /// Get the wire value
+    pub const fn as_u16(self) -> u16 {
+        self.0
+    }
+
+    /// Get the control type, if this implementation knows it
+    pub fn known(self) -> Option<ControlType> {
+        ControlType::from_u16(self.0)
+    }
+}
+
+impl From<ControlType> for RawControlType {
+    fn from(control_type: ControlType) -> Self {
+        RawControlType(control_type.as_u16())
+    }
+}
+
+impl fmt::Display for RawControlType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.known() {
+            Some(control_type) => write!(f, "{:?}", control_type),
+            None => write!(f, "Unknown(0x{:04x})", self.0),
+        }
+    }
+}
+
 /// Message boundary flags (bits 30-31 of message number field)
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
@@ -232,6 +306,23 @@ impl PacketHeader {
         additional_info: u32,
         timestamp: u32,
         dest_socket_id: u32,
+    ) -> Self {
+        Self::new_raw_control(
+            control_type.into(),
+            type_specific_info,
+            additional_info,
+            timestamp,
+            dest_socket_id,
+        )
+    }
+
+    /// Create a control packet header of any type, known or not
+    pub fn new_raw_control(
+        control_type: RawControlType,
+        type_specific_info: u16,
+        additional_info: u32,
+        timestamp: u32,
+        dest_socket_id: u32,
     ) -> Self {
         let seq_or_control =
             CONTROL_FLAG | ((control_type.as_u16() as u32) << 16) | (type_specific_info as u32);
@@ -268,13 +359,23 @@ impl PacketHeader {
     /// Get the control type (for control packets only)
     pub fn control_type(&self) -> Option<ControlType> {
         if self.is_control() {
-            let type_value = ((self.seq_or_control >> 16) & 0x7FFF) as u16;
-            ControlType::from_u16(type_value)
+            ControlType::from_u16(self.control_type_value())
         } else {
             None
         }
     }
 
+    /// Get the control type field, known or not (for control packets only)
+    pub fn raw_control_type(&self) -> Option<RawControlType> {
+        self.is_control()
+            .then(|| RawControlType::new(self.control_type_value()))
+    }
+
+    /// Raw control type field, whether or not it is a known type
+    fn control_type_value(&self) -> u16 {
+        ((self.seq_or_control >> 16) & 0x7FFF) as u16
+    }
+
     /// Get the type-specific information field (for control packets only)
     pub fn type_specific_info(&self) -> Option<u16> {
         if self.is_control() {
@@ -354,17 +455,41 @@ impl DataPacket {
     }
 
     /// Get the sequence number
+    ///
+    /// Never panics: if the public header was since changed to a control
+    /// header, the field is read as is. Use [`DataPacket::try_seq_number`]
+    /// to see that as an error.
     pub fn seq_number(&self) -> SeqNumber {
+        self.try_seq_number()
+            .unwrap_or_else(|_| SeqNumber::new_unchecked(self.header.seq_or_control & SEQ_MASK))
+    }
+
+    /// Get the sequence number, checking the header is still a data header
+    pub fn try_seq_number(&self) -> Result<SeqNumber, PacketError> {
         self.header
             .seq_number()
-            .expect("Data packet has seq number")
+            .ok_or(PacketError::WrongPacketType {
+                expected: "data",
+                actual: "control",
+            })
     }
 
     /// Get the message number
+    ///
+    /// Never panics; see [`DataPacket::seq_number`].
     pub fn msg_number(&self) -> MsgNumber {
+        self.try_msg_number()
+            .unwrap_or_else(|_| MsgNumber::from_raw(self.header.msg_or_info))
+    }
+
+    /// Get the message number, checking the header is still a data header
+    pub fn try_msg_number(&self) -> Result<MsgNumber, PacketError> {
         self.header
             .msg_number()
-            .expect("Data packet has msg number")
+            .ok_or(PacketError::WrongPacketType {
+                expected: "data",
+                actual: "control",
+            })
     }
 
     /// Total size of the packet (header + payload)
@@ -381,9 +506,21 @@ impl DataPacket {
     }
 
     /// Parse a data packet from bytes
+    ///
+    /// The payload is copied; see [`DataPacket::parse`] to share it.
     pub fn from_bytes(bytes: &[u8]) -> Result<Self, PacketError> {
         let header = PacketHeader::from_bytes(bytes)?;
+        Self::from_parts(header, Bytes::copy_from_slice(&bytes[HEADER_SIZE..]))
+    }
+
+    /// Parse a data packet, slicing the payload out of `bytes` without a copy
+    pub fn parse(bytes: Bytes) -> Result<Self, PacketError> {
+        let header = PacketHeader::from_bytes(&bytes)?;
+        Self::from_parts(header, bytes.slice(HEADER_SIZE..))
+    }
 
+    /// Assemble a packet from a parsed header, checking its type
+    fn from_parts(header: PacketHeader, payload: Bytes) -> Result<Self, PacketError> {
         if !header.is_data() {
             return Err(PacketError::WrongPacketType {
                 expected: "data",
@@ -391,12 +528,6 @@ impl DataPacket {
             });
         }
 
-        let payload = if bytes.len() > HEADER_SIZE {
-            Bytes::copy_from_slice(&bytes[HEADER_SIZE..])
-        } else {
-            Bytes::new()
-        };
-
         Ok(DataPacket { header, payload })
     }
 }
@@ -432,11 +563,64 @@ impl ControlPacket {
         }
     }
 
+    /// Create a control packet of any type, known or not
+    pub fn new_raw(
+        control_type: RawControlType,
+        type_specific_info: u16,
+        additional_info: u32,
+        timestamp: u32,
+        dest_socket_id: u32,
+        control_info: Bytes,
+    ) -> Self {
+        ControlPacket {
+            header: PacketHeader::new_raw_control(
+                control_type,
+                type_specific_info,
+                additional_info,
+                timestamp,
+                dest_socket_id,
+            ),
+            control_info,
+        }
+    }
+
     /// Get the control type
+    ///
+    /// Never panics: parsing rejects unknown types, so this only falls back
+    /// to `UserDefined` for packets from [`ControlPacket::parse_raw`] or
+    /// whose public header was changed afterwards. Use
+    /// [`ControlPacket::try_control_type`] to see that as an error.
     pub fn control_type(&self) -> ControlType {
-        self.header
-            .control_type()
-            .expect("Control packet has control type")
+        self.try_control_type().unwrap_or(ControlType::UserDefined)
+    }
+
+    /// Get the control type, checking the header holds a known one
+    pub fn try_control_type(&self) -> Result<ControlType, PacketError> {
+        if !self.header.is_control() {
+            return Err(PacketError::WrongPacketType {
+                expected: "control",
+                actual: "data",
+            });
+        }
+        let value = self.header.control_type_value();
+        ControlType::from_u16(value).ok_or(PacketError::InvalidControlType(value))
+    }
+
+    /// Get the control type field, known or not
+    pub fn raw_control_type(&self) -> RawControlType {
+        RawControlType::new(self.header.control_type_value())
+    }
+
+    /// Get the subtype of a `UserDefined` packet
+    ///
+    /// Extended control messages (key material, rate ceilings, bonding
+    /// messages) share the `UserDefined` type and carry their subtype in
+    /// the type-specific field; see [`crate::wire::USER_SUBTYPES`].
+    pub fn user_subtype(&self) -> Option<u16> {
+        match self.try_control_type() {
+            Ok(ControlType::UserDefined) => self.header.type_specific_info(),
+            _ => None,
+        }
     }
 
     /// Total size of the packet (header + control info)
@@ -453,27 +637,52 @@ impl ControlPacket {
     }
 
     /// Parse a control packet from bytes
+    ///
+    /// The control information is copied; see [`ControlPacket::parse`] to
+    /// share it.
     pub fn from_bytes(bytes: &[u8]) -> Result<Self, PacketError> {
         let header = PacketHeader::from_bytes(bytes)?;
+        Self::from_parts(header, Bytes::copy_from_slice(&bytes[HEADER_SIZE..]))
+    }
+
+    /// Parse a control packet, slicing the control information out of
+    /// `bytes` without a copy
+    pub fn parse(bytes: Bytes) -> Result<Self, PacketError> {
+        let header = PacketHeader::from_bytes(&bytes)?;
+        Self::from_parts(header, bytes.slice(HEADER_SIZE..))
+    }
 
+    /// Parse a control packet of any type, keeping unknown ones
+    ///
+    /// For relays, tracers and tests that must not lose control types
+    /// this implementation does not know; [`ControlPacket::raw_control_type`]
+    /// has the type. Only data packets are refused.
+    pub fn parse_raw(bytes: Bytes) -> Result<Self, PacketError> {
+        let header = PacketHeader::from_bytes(&bytes)?;
         if !header.is_control() {
             return Err(PacketError::WrongPacketType {
                 expected: "control",
                 actual: "data",
             });
         }
-
-        let control_info = if bytes.len() > HEADER_SIZE {
-            Bytes::copy_from_slice(&bytes[HEADER_SIZE..])
-        } else {
-            Bytes::new()
-        };
-
         Ok(ControlPacket {
             header,
-            control_info,
+            control_info: bytes.slice(HEADER_SIZE..),
         })
     }
+
+    /// Assemble a packet from a parsed header, checking its type
+    ///
+    /// Unknown control types are refused here, so a parsed packet always
+    /// has a control type.
+    fn from_parts(header: PacketHeader, control_info: Bytes) -> Result<Self, PacketError> {
+        let packet = ControlPacket {
+            header,
+            control_info,
+        };
+        packet.try_control_type()?;
+        Ok(packet)
+    }
 }
 
 /// Unified packet type (either data or control)
@@ -529,13 +738,28 @@ impl Packet {
     }
 
     /// Parse a packet from bytes (automatically determines type)
+    ///
+    /// The header is parsed once and the body copied; see
+    /// [`Packet::parse`] to share it.
     pub fn from_bytes(bytes: &[u8]) -> Result<Self, PacketError> {
         let header = PacketHeader::from_bytes(bytes)?;
+        Self::from_parts(header, Bytes::copy_from_slice(&bytes[HEADER_SIZE..]))
+    }
 
+    /// Parse a packet, slicing the body out of `bytes` without a copy
+    ///
+    /// For receive paths that own the datagram (e.g. a `Vec<u8>` converted
+    /// with `Bytes::from`), the packet then costs no allocation or copy.
+    pub fn parse(bytes: Bytes) -> Result<Self, PacketError> {
+        let header = PacketHeader::from_bytes(&bytes)?;
+        Self::from_parts(header, bytes.slice(HEADER_SIZE..))
+    }
+
+    fn from_parts(header: PacketHeader, body: Bytes) -> Result<Self, PacketError> {
         if header.is_data() {
-            Ok(Packet::Data(DataPacket::from_bytes(bytes)?))
+            Ok(Packet::Data(DataPacket::from_parts(header, body)?))
         } else {
-            Ok(Packet::Control(ControlPacket::from_bytes(bytes)?))
+            Ok(Packet::Control(ControlPacket::from_parts(header, body)?))
         }
     }
 }
@@ -661,6 +885,14 @@ mod tests {
         assert_eq!(decoded.control_info, control_info);
     }
 
+    #[test]
+    fn test_max_payload_for_mss() {
+        assert_eq!(max_payload_for_mss(DEFAULT_MSS), MAX_PAYLOAD_SIZE);
+        assert_eq!(max_payload_for_mss(1400), 1356);
+        assert_eq!(max_payload_for_mss(MIN_MSS), 32);
+        assert_eq!(max_payload_for_mss(10), 0);
+    }
+
     #[test]
     fn test_packet_auto_detect() {
         // Test data packet auto-detection
@@ -681,4 +913,166 @@ mod tests {
         let packet = Packet::from_bytes(&bytes).unwrap();
         assert!(packet.is_control());
     }
+
+    #[test]
+    fn test_unknown_control_type_rejected() {
+        let mut bytes =
+            ControlPacket::new(ControlType::Ack, 0, 0, 1000, 9999, Bytes::new()).to_bytes();
+        // Control type 9 is not defined
+        bytes[0..4].copy_from_slice(&(CONTROL_FLAG | (9 << 16)).to_be_bytes());
+
+        assert!(matches!(
+            ControlPacket::from_bytes(&bytes),
+            Err(PacketError::InvalidControlType(9))
+        ));
+        assert!(matches!(
+            Packet::parse(bytes.freeze()),
+            Err(PacketError::InvalidControlType(9))
+        ));
+    }
+
+    #[test]
+    fn test_every_control_type_roundtrips() {
+        for value in 0..=0x7FFFu16 {
+            let raw = RawControlType::new(value);
+            let packet = ControlPacket::new_raw(raw, 0x4701, 7, 1000, 9999, Bytes::new());
+            let bytes = packet.to_bytes().freeze();
+
+            // Lossless: any type survives a raw parse and re-serialization
+            let parsed = ControlPacket::parse_raw(bytes.clone()).unwrap();
+            assert_eq!(parsed.raw_control_type(), raw);
+            assert_eq!(parsed.header.raw_control_type(), Some(raw));
+            assert_eq!(parsed.header.type_specific_info(), Some(0x4701));
+            assert_eq!(parsed.to_bytes().freeze(), bytes);
+
+            // Strict parsing accepts exactly the known types
+            match (ControlType::from_u16(value), ControlPacket::parse(bytes)) {
+                (Some(known), Ok(parsed)) => {
+                    assert_eq!(known.as_u16(), value);
+                    assert_eq!(raw.known(), Some(known));
+                    assert_eq!(parsed.control_type(), known);
+                }
+                (None, Err(PacketError::InvalidControlType(v))) => {
+                    assert_eq!(v, value);
+                    assert_eq!(raw.known(), None);
+                }
+                (known, parsed) => panic!("type {}: {:?} / {:?}", value, known, parsed),
+            }
+        }
+
+        let known = (0..=0x7FFFu16)
+            .filter_map(ControlType::from_u16)
+            .collect::<Vec<_>>();
+        assert_eq!(known, ControlType::ALL);
+        for control_type in ControlType::ALL {
+            assert_eq!(
+                RawControlType::from(control_type).known(),
+                Some(control_type)
+            );
+        }
+        // Bit 15 belongs to the control flag
+        assert_eq!(RawControlType::new(0xFFFF).as_u16(), 0x7FFF);
+    }
+
+    #[test]
+    fn test_raw_control_packets() {
+        let unknown = RawControlType::new(9);
+        assert_eq!(unknown.to_string(), "Unknown(0x0009)");
+        assert_eq!(RawControlType::from(ControlType::Ack).to_string(), "Ack");
+
+        let packet = ControlPacket::new_raw(unknown, 0, 0, 0, 1, Bytes::from_static(b"body"));
+        let parsed = ControlPacket::parse_raw(packet.to_bytes().freeze()).unwrap();
+        assert_eq!(parsed.control_info, Bytes::from_static(b"body"));
+        assert_eq!(parsed.user_subtype(), None);
+        assert!(parsed.try_control_type().is_err());
+
+        let data = DataPacket::new(SeqNumber::new(1), MsgNumber::new(1), 0, 1, Bytes::new());
+        assert!(matches!(
+            ControlPacket::parse_raw(data.to_bytes().freeze()),
+            Err(PacketError::WrongPacketType { .. })
+        ));
+        assert_eq!(data.header.raw_control_type(), None);
+
+        let user = ControlPacket::new(ControlType::UserDefined, 0x4704, 0, 0, 1, Bytes::new());
+        assert_eq!(user.user_subtype(), Some(0x4704));
+        let ack = ControlPacket::new(ControlType::Ack, 0x4704, 0, 0, 1, Bytes::new());
+        assert_eq!(ack.user_subtype(), None);
+    }
+
+    #[test]
+    fn test_checked_accessors() {
+        let mut data = DataPacket::new(
+            SeqNumber::new(100),
+            MsgNumber::new(10),
+            1000,
+            9999,
+            Bytes::new(),
+        );
+        assert_eq!(data.try_seq_number().unwrap(), SeqNumber::new(100));
+        assert_eq!(data.try_msg_number().unwrap().seq, 10);
+
+        // A header switched to control no longer panics the old accessors
+        data.header.seq_or_control |= CONTROL_FLAG;
+        assert!(matches!(
+            data.try_seq_number(),
+            Err(PacketError::WrongPacketType { .. })
+        ));
+        assert!(data.try_msg_number().is_err());
+        assert_eq!(data.seq_number(), SeqNumber::new(100));
+        assert_eq!(data.msg_number().seq, 10);
+
+        let mut control = ControlPacket::new(ControlType::Nak, 0, 0, 1000, 9999, Bytes::new());
+        assert_eq!(control.try_control_type().unwrap(), ControlType::Nak);
+        control.header.seq_or_control = CONTROL_FLAG | (42 << 16);
+        assert!(matches!(
+            control.try_control_type(),
+            Err(PacketError::InvalidControlType(42))
+        ));
+        assert_eq!(control.control_type(), ControlType::UserDefined);
+        control.header.seq_or_control = 0;
+        assert!(matches!(
+            control.try_control_type(),
+            Err(PacketError::WrongPacketType { .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_shares_payload() {
+        let packet = DataPacket::new(
+            SeqNumber::new(100),
+            MsgNumber::new(10),
+            1000,
+            9999,
+            Bytes::from_static(b"payload"),
+        );
+        let bytes = packet.to_bytes().freeze();
+
+        let parsed = Packet::parse(bytes.clone()).unwrap();
+        assert_eq!(parsed, Packet::from_bytes(&bytes).unwrap());
+        let Packet::Data(parsed) = parsed else {
+            panic!("expected a data packet");
+        };
+        assert_eq!(parsed, packet);
+        // A view of the datagram, not a copy
+        assert_eq!(parsed.payload.as_ptr(), bytes[HEADER_SIZE..].as_ptr());
+
+        let control = ControlPacket::new(ControlType::Ack, 0, 0, 1000, 9999, Bytes::new());
+        let control_bytes = control.to_bytes().freeze();
+        assert_eq!(
+            ControlPacket::parse(control_bytes.clone()).unwrap(),
+            control
+        );
+        assert!(matches!(
+            DataPacket::parse(control_bytes),
+            Err(PacketError::WrongPacketType { .. })
+        ));
+        assert!(matches!(
+            ControlPacket::parse(bytes.clone()),
+            Err(PacketError::WrongPacketType { .. })
+        ));
+        assert!(matches!(
+            Packet::parse(bytes.slice(..8)),
+            Err(PacketError::InsufficientData { .. })
+        ));
+    }
 }
@@ -0,0 +1,446 @@
+//! Packetization of byte streams into SRT data packets
+//!
+//! The `Packetizer` splits application messages into MTU-sized data packets
+//! with sequence numbers, message numbers, boundary flags, and timestamps.
+//! The `Depacketizer` performs the reverse operation on in-order packets.
+
+use crate::packet::{DataPacket, MsgNumber, PacketBoundary, MAX_PAYLOAD_SIZE};
+use crate::sequence::SeqNumber;
+use crate::srctime::{source_timestamp, SourceTimeError};
+use bytes::{Bytes, BytesMut};
+use std::time::{Instant, SystemTime};
+
+/// Maximum message number value (26-bit)
+pub const MAX_MSG_NUMBER: u32 = 0x03FF_FFFF;
+
+/// Packetizer
+///
+/// Turns application messages into sequenced data packets.
+pub struct Packetizer {
+    /// Next sequence number to assign
+    next_seq: SeqNumber,
+    /// Next message number to assign (1..=MAX_MSG_NUMBER)
+    next_msg: u32,
+    /// Maximum payload per packet
+    max_payload_size: usize,
+    /// Destination socket ID written into each packet
+    dest_socket_id: u32,
+    /// In-order delivery flag for produced packets
+    in_order: bool,
+    /// Byte-stream mode: every packet stands alone
+    stream_mode: bool,
+    /// Reference time for packet timestamps
+    start_time: Instant,
+    /// Wallclock time corresponding to `start_time`
+    time_origin: SystemTime,
+}
+
+impl Packetizer {
+    /// Create a new packetizer
+    ///
+    /// # Arguments
+    /// * `initial_seq` - Sequence number of the first packet
+    /// * `dest_socket_id` - Destination socket ID for produced packets
+    /// * `max_payload_size` - Maximum payload per packet (bytes)
+    pub fn new(initial_seq: SeqNumber, dest_socket_id: u32, max_payload_size: usize) -> Self {
+        Packetizer {
+            next_seq: initial_seq,
+            next_msg: 1,
+            max_payload_size: max_payload_size.max(1),
+            dest_socket_id,
+            in_order: false,
+            stream_mode: false,
+            start_time: Instant::now(),
+            time_origin: SystemTime::now(),
+        }
+    }
+
+    /// Get the in-order delivery flag
+    pub fn in_order(&self) -> bool {
+        self.in_order
+    }
+
+    /// Set the in-order delivery flag for subsequent packets
+    pub fn set_in_order(&mut self, in_order: bool) {
+        self.in_order = in_order;
+    }
+
+    /// Check if the packetizer is in stream mode
+    pub fn stream_mode(&self) -> bool {
+        self.stream_mode
+    }
+
+    /// Produce stream-mode packets (off by default)
+    ///
+    /// Data is cut into packets with no message boundaries: every packet
+    /// is marked Solo with its own message number, and the receiver joins
+    /// payloads back into one byte stream.
+    pub fn set_stream_mode(&mut self, enabled: bool) {
+        self.stream_mode = enabled;
+    }
+
+    /// Set the maximum payload per packet
+    pub fn set_max_payload_size(&mut self, max_payload_size: usize) {
+        self.max_payload_size = max_payload_size.max(1);
+    }
+
+    /// Get the maximum payload per packet
+    pub fn max_payload_size(&self) -> usize {
+        self.max_payload_size
+    }
+
+    /// Set the destination socket ID for subsequent packets
+    pub fn set_dest_socket_id(&mut self, dest_socket_id: u32) {
+        self.dest_socket_id = dest_socket_id;
+    }
+
+    /// Get the wallclock time of timestamp zero
+    pub fn time_origin(&self) -> SystemTime {
+        self.time_origin
+    }
+
+    /// Set the wallclock time of timestamp zero
+    ///
+    /// Must match the time origin advertised in the handshake so the
+    /// receiver can map timestamps back to source times.
+    pub fn set_time_origin(&mut self, origin: SystemTime) {
+        let since_origin = SystemTime::now().duration_since(origin).unwrap_or_default();
+        self.start_time = Instant::now()
+            .checked_sub(since_origin)
+            .unwrap_or_else(Instant::now);
+        self.time_origin = origin;
+    }
+
+    /// Get the next sequence number to be assigned
+    pub fn next_seq(&self) -> SeqNumber {
+        self.next_seq
+    }
+
+    /// Get the next message number to be assigned
+    pub fn next_msg_number(&self) -> u32 {
+        self.next_msg
+    }
+
+    /// Packetize a message, timestamped relative to packetizer creation
+    pub fn packetize(&mut self, data: &[u8]) -> Vec<DataPacket> {
+        let timestamp = self.start_time.elapsed().as_micros() as u32;
+        self.packetize_at(data, timestamp)
+    }
+
+    /// Packetize a message, timestamped with its source (e.g. capture) time
+    pub fn packetize_with_source_time(
+        &mut self,
+        data: &[u8],
+        source_time: SystemTime,
+    ) -> Result<Vec<DataPacket>, SourceTimeError> {
+        let timestamp = source_timestamp(self.time_origin, source_time)?;
+        Ok(self.packetize_at(data, timestamp))
+    }
+
+    /// Packetize a message with an explicit timestamp (microseconds)
+    ///
+    /// Returns no packets for an empty message.
+    pub fn packetize_at(&mut self, data: &[u8], timestamp: u32) -> Vec<DataPacket> {
+        self.packetize_bytes_at(Bytes::copy_from_slice(data), timestamp)
+    }
+
+    /// Packetize a message held in `Bytes` with an explicit timestamp
+    ///
+    /// Packet payloads are slices of `data`, so nothing is copied and
+    /// callers sending the same message on several connections share one
+    /// allocation.
+    pub fn packetize_bytes_at(&mut self, data: Bytes, timestamp: u32) -> Vec<DataPacket> {
+        if data.is_empty() {
+            return Vec::new();
+        }
+
+        let mut msg_seq = self.next_message_number();
+        let chunk_count = (data.len() + self.max_payload_size - 1) / self.max_payload_size;
+        let mut packets = Vec::with_capacity(chunk_count);
+
+        for i in 0..chunk_count {
+            let start = i * self.max_payload_size;
+            let end = (start + self.max_payload_size).min(data.len());
+            if self.stream_mode && i > 0 {
+                msg_seq = self.next_message_number();
+            }
+            let boundary = match (i == 0, i == chunk_count - 1) {
+                _ if self.stream_mode => PacketBoundary::Solo,
+                (true, true) => PacketBoundary::Solo,
+                (true, false) => PacketBoundary::First,
+                (false, true) => PacketBoundary::Last,
+                (false, false) => PacketBoundary::Subsequent,
+            };
+
+            let msg_number = MsgNumber {
+                boundary,
+                in_order: self.in_order,
+                ..MsgNumber::new(msg_seq)
+            };
+
+            packets.push(DataPacket::new(
+                self.next_seq,
+                msg_number,
+                timestamp,
+                self.dest_socket_id,
+                data.slice(start..end),
+            ));
+
+            self.next_seq = self.next_seq.next();
+        }
+
+        packets
+    }
+
+    /// Take the next message number, wrapping after `MAX_MSG_NUMBER`
+    fn next_message_number(&mut self) -> u32 {
+        let msg_seq = self.next_msg;
+        self.next_msg = if self.next_msg >= MAX_MSG_NUMBER {
+            1
+        } else {
+            self.next_msg + 1
+        };
+        msg_seq
+    }
+}
+
+impl Default for Packetizer {
+    fn default() -> Self {
+        Self::new(SeqNumber::new(0), 0, MAX_PAYLOAD_SIZE)
+    }
+}
+
+/// Depacketizer
+///
+/// Reassembles messages from data packets delivered in sequence order.
+/// Incomplete messages (a gap in sequence or message number) are discarded.
+pub struct Depacketizer {
+    /// Payloads of the message currently being reassembled
+    partial: BytesMut,
+    /// Message number of the partial message
+    partial_msg: Option<u32>,
+    /// Expected sequence number of the next fragment
+    expected_seq: Option<SeqNumber>,
+    /// Number of messages discarded due to missing fragments
+    dropped_messages: u64,
+}
+
+impl Depacketizer {
+    /// Create a new depacketizer
+    pub fn new() -> Self {
+        Depacketizer {
+            partial: BytesMut::new(),
+            partial_msg: None,
+            expected_seq: None,
+            dropped_messages: 0,
+        }
+    }
+
+    /// Process the next in-order packet
+    ///
+    /// Returns a complete message when the packet finishes one.
+    pub fn push(&mut self, packet: &DataPacket) -> Option<Bytes> {
+        let msg = packet.msg_number();
+        let seq = packet.seq_number();
+
+        match msg.boundary {
+            PacketBoundary::Solo => {
+                self.discard_partial();
+                Some(packet.payload.clone())
+            }
+            PacketBoundary::First => {
+                self.discard_partial();
+                self.partial.extend_from_slice(&packet.payload);
+                self.partial_msg = Some(msg.seq);
+                self.expected_seq = Some(seq.next());
+                None
+            }
+            PacketBoundary::Subsequent | PacketBoundary::Last => {
+                if self.partial_msg != Some(msg.seq) || self.expected_seq != Some(seq) {
+                    // Fragment does not continue the current message
+                    self.discard_partial();
+                    return None;
+                }
+
+                self.partial.extend_from_slice(&packet.payload);
+                self.expected_seq = Some(seq.next());
+
+                if msg.boundary == PacketBoundary::Last {
+                    self.partial_msg = None;
+                    self.expected_seq = None;
+                    Some(self.partial.split().freeze())
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    /// Discard any partially reassembled message
+    fn discard_partial(&mut self) {
+        if self.partial_msg.take().is_some() {
+            self.dropped_messages += 1;
+        }
+        self.partial.clear();
+        self.expected_seq = None;
+    }
+
+    /// Check if a message is currently being reassembled
+    pub fn has_partial(&self) -> bool {
+        self.partial_msg.is_some()
+    }
+
+    /// Get the number of messages discarded due to missing fragments
+    pub fn dropped_messages(&self) -> u64 {
+        self.dropped_messages
+    }
+}
+
+impl Default for Depacketizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_packetize_solo() {
+        let mut packetizer = Packetizer::new(SeqNumber::new(100), 9999, 1456);
+
+        let packets = packetizer.packetize_at(b"hello", 1000);
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0].seq_number(), SeqNumber::new(100));
+        assert_eq!(packets[0].msg_number().boundary, PacketBoundary::Solo);
+        assert_eq!(packets[0].msg_number().seq, 1);
+        assert_eq!(packets[0].header.timestamp, 1000);
+        assert_eq!(packets[0].header.dest_socket_id, 9999);
+
+        assert_eq!(packetizer.next_seq(), SeqNumber::new(101));
+        assert_eq!(packetizer.next_msg_number(), 2);
+    }
+
+    #[test]
+    fn test_packetize_multi_packet() {
+        let mut packetizer = Packetizer::new(SeqNumber::new(0), 0, 4);
+        packetizer.set_in_order(true);
+
+        let packets = packetizer.packetize_at(b"abcdefghij", 0);
+        let boundaries: Vec<_> = packets.iter().map(|p| p.msg_number().boundary).collect();
+        assert_eq!(
+            boundaries,
+            vec![
+                PacketBoundary::First,
+                PacketBoundary::Subsequent,
+                PacketBoundary::Last
+            ]
+        );
+        assert!(packets.iter().all(|p| p.msg_number().in_order));
+        assert!(packets.iter().all(|p| p.msg_number().seq == 1));
+        assert_eq!(&packets[2].payload[..], b"ij");
+    }
+
+    #[test]
+    fn test_packetize_stream_mode() {
+        let mut packetizer = Packetizer::new(SeqNumber::new(0), 0, 4);
+        packetizer.set_stream_mode(true);
+
+        let packets = packetizer.packetize_at(b"abcdefghij", 0);
+        assert_eq!(packets.len(), 3);
+        assert!(packets
+            .iter()
+            .all(|p| p.msg_number().boundary == PacketBoundary::Solo));
+        let numbers: Vec<_> = packets.iter().map(|p| p.msg_number().seq).collect();
+        assert_eq!(numbers, vec![1, 2, 3]);
+        assert_eq!(packetizer.next_msg_number(), 4);
+    }
+
+    #[test]
+    fn test_packetize_bytes_shares_payload() {
+        let mut packetizer = Packetizer::new(SeqNumber::new(0), 0, 4);
+        let data = Bytes::from_static(b"abcdefghij");
+
+        let packets = packetizer.packetize_bytes_at(data.clone(), 0);
+        assert_eq!(packets.len(), 3);
+        assert_eq!(packets[0].payload.as_ptr(), data.as_ptr());
+        assert_eq!(packets[2].payload.as_ptr(), data[8..].as_ptr());
+        assert_eq!(&packets[1].payload[..], b"efgh");
+    }
+
+    #[test]
+    fn test_packetize_with_source_time() {
+        let mut packetizer = Packetizer::new(SeqNumber::new(0), 0, 1456);
+        let origin = SystemTime::now() - Duration::from_secs(2);
+        packetizer.set_time_origin(origin);
+
+        let packets = packetizer
+            .packetize_with_source_time(b"frame", origin + Duration::from_millis(1500))
+            .unwrap();
+        assert_eq!(packets[0].header.timestamp, 1_500_000);
+        assert_eq!(
+            packetizer.packetize_with_source_time(b"frame", origin - Duration::from_secs(1)),
+            Err(SourceTimeError::BeforeOrigin)
+        );
+
+        // Regular timestamps now count from the new origin
+        let packets = packetizer.packetize(b"live");
+        assert!(packets[0].header.timestamp >= 2_000_000);
+    }
+
+    #[test]
+    fn test_packetize_empty() {
+        let mut packetizer = Packetizer::default();
+        assert!(packetizer.packetize(b"").is_empty());
+        assert_eq!(packetizer.next_msg_number(), 1);
+    }
+
+    #[test]
+    fn test_msg_number_wraparound() {
+        let mut packetizer = Packetizer {
+            next_msg: MAX_MSG_NUMBER,
+            ..Packetizer::default()
+        };
+
+        packetizer.packetize(b"x");
+        assert_eq!(packetizer.next_msg_number(), 1);
+    }
+
+    #[test]
+    fn test_depacketize_roundtrip() {
+        let mut packetizer = Packetizer::new(SeqNumber::new(0), 0, 3);
+        let mut depacketizer = Depacketizer::new();
+
+        let mut messages = Vec::new();
+        for data in [&b"one"[..], b"a longer message", b"xy"] {
+            for packet in packetizer.packetize(data) {
+                if let Some(message) = depacketizer.push(&packet) {
+                    messages.push(message);
+                }
+            }
+        }
+
+        assert_eq!(messages.len(), 3);
+        assert_eq!(&messages[1][..], b"a longer message");
+        assert_eq!(depacketizer.dropped_messages(), 0);
+    }
+
+    #[test]
+    fn test_depacketize_missing_fragment() {
+        let mut packetizer = Packetizer::new(SeqNumber::new(0), 0, 2);
+        let mut depacketizer = Depacketizer::new();
+
+        let packets = packetizer.packetize(b"abcdef");
+        assert!(depacketizer.push(&packets[0]).is_none());
+        // Skip packets[1]
+        assert!(depacketizer.push(&packets[2]).is_none());
+        assert!(!depacketizer.has_partial());
+        assert_eq!(depacketizer.dropped_messages(), 1);
+
+        // Next message is still delivered
+        let next = packetizer.packetize(b"ok");
+        assert_eq!(&depacketizer.push(&next[0]).unwrap()[..], b"ok");
+    }
+}
@@ -0,0 +1,416 @@
+//! Peer Capability Cache
+//!
+//! A reconnect normally negotiates everything from scratch. The cache
+//! remembers what each peer agreed to last time (latency, MSS, options
+//! including encryption, and packet filter) so the next handshake can
+//! offer the same values up front, and so a peer that comes back with a
+//! different critical setting is noticed instead of silently accepted.
+
+use crate::connection::{Connection, ConnectionError};
+use crate::filter::FilterConfig;
+use crate::handshake::SrtOptions;
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::fmt;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+/// Default number of peers remembered
+pub const DEFAULT_PEER_CACHE_CAPACITY: usize = 256;
+
+/// Default time a cached entry stays valid
+pub const DEFAULT_PEER_CACHE_TTL: Duration = Duration::from_secs(3600);
+
+/// Cache key: peer address and the Stream ID used with it
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PeerKey {
+    /// Peer address
+    pub addr: SocketAddr,
+    /// Stream ID sent or received in the handshake
+    pub stream_id: Option<String>,
+}
+
+impl PeerKey {
+    /// Create a key for a peer and Stream ID
+    pub fn new(addr: SocketAddr, stream_id: Option<String>) -> Self {
+        PeerKey { addr, stream_id }
+    }
+}
+
+impl From<SocketAddr> for PeerKey {
+    fn from(addr: SocketAddr) -> Self {
+        PeerKey::new(addr, None)
+    }
+}
+
+/// Settings negotiated with a peer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerCapabilities {
+    /// Agreed TSBPD latency (milliseconds)
+    pub latency_ms: u16,
+    /// Agreed maximum segment size
+    pub mss: usize,
+    /// Agreed SRT options, including encryption
+    pub options: SrtOptions,
+    /// Agreed packet filter
+    pub filter: Option<FilterConfig>,
+}
+
+impl PeerCapabilities {
+    /// Capture what a connection negotiated
+    ///
+    /// Only meaningful once the handshake has completed.
+    pub fn from_connection(conn: &Connection) -> Self {
+        PeerCapabilities {
+            latency_ms: conn.latency_ms(),
+            mss: conn.mss(),
+            options: conn.options(),
+            filter: conn.packet_filter().cloned(),
+        }
+    }
+
+    /// Offer these settings in a connection's next handshake
+    ///
+    /// The latency is raised and the MSS lowered to the agreed values, so
+    /// buffers are sized for them from the start. The filter and options
+    /// are not offered: a listener adopts whatever filter a caller offers,
+    /// so offering the old one would undo a change made on purpose. They
+    /// are checked with `changes` once the handshake completes instead.
+    pub fn prepopulate(&self, conn: &mut Connection) -> Result<(), ConnectionError> {
+        if self.latency_ms > conn.latency_ms() {
+            conn.set_latency(self.latency_ms)?;
+        }
+        if self.mss < conn.mss() {
+            conn.set_mss(self.mss)?;
+        }
+        Ok(())
+    }
+
+    /// List the settings that differ in `now` from these
+    pub fn changes(&self, now: &PeerCapabilities) -> Vec<CapabilityChange> {
+        let mut changes = Vec::new();
+        if self.latency_ms != now.latency_ms {
+            changes.push(CapabilityChange::Latency {
+                was: self.latency_ms,
+                now: now.latency_ms,
+            });
+        }
+        if self.mss != now.mss {
+            changes.push(CapabilityChange::Mss {
+                was: self.mss,
+                now: now.mss,
+            });
+        }
+        if self.options.encryption != now.options.encryption {
+            changes.push(CapabilityChange::Encryption {
+                now: now.options.encryption,
+            });
+        }
+        if self.options.stream_mode != now.options.stream_mode {
+            changes.push(CapabilityChange::StreamMode {
+                now: now.options.stream_mode,
+            });
+        }
+        if self.filter != now.filter {
+            changes.push(CapabilityChange::Filter {
+                was: self.filter.as_ref().map(|f| f.to_string()),
+                now: now.filter.as_ref().map(|f| f.to_string()),
+            });
+        }
+        changes
+    }
+}
+
+/// A setting that changed since the last connection to a peer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CapabilityChange {
+    /// Agreed latency changed
+    Latency { was: u16, now: u16 },
+    /// Agreed MSS changed (e.g. a different path MTU)
+    Mss { was: usize, now: usize },
+    /// Encryption was turned on or off
+    Encryption { now: bool },
+    /// Switched between stream and message mode
+    StreamMode { now: bool },
+    /// Packet filter changed
+    Filter {
+        was: Option<String>,
+        now: Option<String>,
+    },
+}
+
+impl CapabilityChange {
+    /// Whether the change alters what the stream carries or how it is
+    /// protected, rather than just its timing or packet size
+    pub fn is_critical(&self) -> bool {
+        matches!(
+            self,
+            CapabilityChange::Encryption { .. }
+                | CapabilityChange::StreamMode { .. }
+                | CapabilityChange::Filter { .. }
+        )
+    }
+}
+
+impl fmt::Display for CapabilityChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let filter = |config: &Option<String>| config.clone().unwrap_or_else(|| "none".into());
+        match self {
+            CapabilityChange::Latency { was, now } => {
+                write!(f, "latency {}ms -> {}ms", was, now)
+            }
+            CapabilityChange::Mss { was, now } => write!(f, "MSS {} -> {}", was, now),
+            CapabilityChange::Encryption { now } => {
+                write!(
+                    f,
+                    "encryption {}",
+                    if *now { "enabled" } else { "disabled" }
+                )
+            }
+            CapabilityChange::StreamMode { now } => {
+                write!(f, "{} mode", if *now { "stream" } else { "message" })
+            }
+            CapabilityChange::Filter { was, now } => {
+                write!(f, "packet filter {} -> {}", filter(was), filter(now))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CacheEntry {
+    capabilities: PeerCapabilities,
+    stored: Instant,
+}
+
+/// Peer capability cache statistics
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PeerCacheStats {
+    /// Lookups that found a valid entry
+    pub hits: u64,
+    /// Lookups that found nothing, or an expired entry
+    pub misses: u64,
+    /// Entries evicted to stay within capacity
+    pub evictions: u64,
+    /// Reconnects where a critical setting changed
+    pub critical_changes: u64,
+}
+
+/// Negotiated settings by peer, shared between connections
+///
+/// Holds at most `capacity` peers (0 = unlimited), evicting the oldest
+/// entry; entries older than the TTL (zero = never expire) are ignored.
+#[derive(Debug)]
+pub struct PeerCapabilityCache {
+    entries: RwLock<HashMap<PeerKey, CacheEntry>>,
+    capacity: usize,
+    ttl: Duration,
+    stats: RwLock<PeerCacheStats>,
+}
+
+impl Default for PeerCapabilityCache {
+    fn default() -> Self {
+        PeerCapabilityCache::new(DEFAULT_PEER_CACHE_CAPACITY, DEFAULT_PEER_CACHE_TTL)
+    }
+}
+
+impl PeerCapabilityCache {
+    /// Create a cache holding `capacity` peers for `ttl` each
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        PeerCapabilityCache {
+            entries: RwLock::new(HashMap::new()),
+            capacity,
+            ttl,
+            stats: RwLock::new(PeerCacheStats::default()),
+        }
+    }
+
+    /// Get the settings last negotiated with a peer
+    pub fn get(&self, key: &PeerKey) -> Option<PeerCapabilities> {
+        let found = self
+            .entries
+            .read()
+            .get(key)
+            .filter(|entry| self.ttl.is_zero() || entry.stored.elapsed() < self.ttl)
+            .map(|entry| entry.capabilities.clone());
+        let mut stats = self.stats.write();
+        match found {
+            Some(_) => stats.hits += 1,
+            None => stats.misses += 1,
+        }
+        found
+    }
+
+    /// Remember what a peer negotiated, returning what changed since the
+    /// last time
+    ///
+    /// Critical changes are counted in the stats; deciding whether to keep
+    /// the connection is up to the caller.
+    pub fn remember(&self, key: PeerKey, capabilities: PeerCapabilities) -> Vec<CapabilityChange> {
+        let mut entries = self.entries.write();
+        let changes = entries
+            .get(&key)
+            .map(|entry| entry.capabilities.changes(&capabilities))
+            .unwrap_or_default();
+
+        if !entries.contains_key(&key) && self.capacity > 0 && entries.len() >= self.capacity {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.stored)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+                self.stats.write().evictions += 1;
+            }
+        }
+        entries.insert(
+            key,
+            CacheEntry {
+                capabilities,
+                stored: Instant::now(),
+            },
+        );
+
+        if changes.iter().any(CapabilityChange::is_critical) {
+            self.stats.write().critical_changes += 1;
+        }
+        changes
+    }
+
+    /// Forget a peer
+    pub fn forget(&self, key: &PeerKey) -> Option<PeerCapabilities> {
+        self.entries
+            .write()
+            .remove(key)
+            .map(|entry| entry.capabilities)
+    }
+
+    /// Get the number of peers remembered
+    pub fn len(&self) -> usize {
+        self.entries.read().len()
+    }
+
+    /// Check if no peers are remembered
+    pub fn is_empty(&self) -> bool {
+        self.entries.read().is_empty()
+    }
+
+    /// Get cache statistics
+    pub fn stats(&self) -> PeerCacheStats {
+        self.stats.read().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sequence::SeqNumber;
+
+    fn connection(latency_ms: u16) -> Connection {
+        Connection::new(
+            1,
+            "127.0.0.1:9000".parse().unwrap(),
+            "127.0.0.1:9001".parse().unwrap(),
+            SeqNumber::new(0),
+            latency_ms,
+        )
+    }
+
+    fn capabilities(latency_ms: u16, filter: Option<&str>) -> PeerCapabilities {
+        PeerCapabilities {
+            latency_ms,
+            mss: 1400,
+            options: SrtOptions::default_capabilities(),
+            filter: filter.map(|f| FilterConfig::parse(f).unwrap()),
+        }
+    }
+
+    fn key(port: u16) -> PeerKey {
+        PeerKey::new(
+            format!("10.0.0.1:{}", port).parse().unwrap(),
+            Some("live".into()),
+        )
+    }
+
+    #[test]
+    fn test_handshake_roundtrip_is_cached() {
+        let mut caller = connection(120);
+        let mut listener = connection(250);
+        listener
+            .set_packet_filter(Some("fec,cols:10,rows:5"))
+            .unwrap();
+        listener.set_mss(1400).unwrap();
+        caller
+            .process_handshake(listener.create_handshake())
+            .unwrap();
+
+        let learned = PeerCapabilities::from_connection(&caller);
+        assert_eq!(learned.latency_ms, 250);
+        assert_eq!(learned.mss, 1400);
+        assert_eq!(learned.filter, listener.packet_filter().cloned());
+
+        // The next connection offers the agreed values up front
+        let mut next = connection(120);
+        learned.prepopulate(&mut next).unwrap();
+        assert_eq!(next.latency_ms(), 250);
+        assert_eq!(next.mss(), 1400);
+        assert_eq!(next.create_handshake().filter_config, None);
+
+        // Local settings win where they already satisfy the peer
+        let mut higher = connection(500);
+        learned.prepopulate(&mut higher).unwrap();
+        assert_eq!(higher.latency_ms(), 500);
+    }
+
+    #[test]
+    fn test_remember_reports_changes() {
+        let cache = PeerCapabilityCache::default();
+        assert!(cache.get(&key(1)).is_none());
+        assert!(cache
+            .remember(key(1), capabilities(120, Some("fec,cols:10")))
+            .is_empty());
+        assert_eq!(cache.get(&key(1)).unwrap().latency_ms, 120);
+
+        let changes = cache.remember(key(1), capabilities(200, Some("fec,cols:10")));
+        assert_eq!(
+            changes,
+            vec![CapabilityChange::Latency { was: 120, now: 200 }]
+        );
+        assert!(!changes[0].is_critical());
+
+        let mut unencrypted = capabilities(200, None);
+        unencrypted.options.encryption = false;
+        let changes = cache.remember(key(1), unencrypted);
+        assert_eq!(changes.len(), 2);
+        assert!(changes.iter().all(CapabilityChange::is_critical));
+        assert_eq!(changes[0].to_string(), "encryption disabled");
+        assert_eq!(changes[1].to_string(), "packet filter fec,cols:10 -> none");
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses), (1, 1));
+        assert_eq!(stats.critical_changes, 1);
+
+        // Keys include the Stream ID
+        assert!(cache.get(&PeerKey::from(key(1).addr)).is_none());
+        assert!(cache.forget(&key(1)).is_some());
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_capacity_and_ttl() {
+        let cache = PeerCapabilityCache::new(2, Duration::ZERO);
+        cache.remember(key(1), capabilities(120, None));
+        std::thread::sleep(Duration::from_millis(2));
+        cache.remember(key(2), capabilities(120, None));
+        cache.remember(key(3), capabilities(120, None));
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(&key(1)).is_none());
+        assert!(cache.get(&key(3)).is_some());
+        assert_eq!(cache.stats().evictions, 1);
+
+        let expiring = PeerCapabilityCache::new(0, Duration::from_millis(1));
+        expiring.remember(key(1), capabilities(120, None));
+        std::thread::sleep(Duration::from_millis(5));
+        assert!(expiring.get(&key(1)).is_none());
+    }
+}
@@ -0,0 +1,432 @@
+//! Handshake bandwidth probe
+//!
+//! A fresh path's capacity is unknown until traffic has crossed it, so a
+//! bonding group would start every path from the same guess. A caller
+//! that wants a measurement asks for a probe in its handshake: the
+//! `SRT_CMD_PROBE` extension carries a `ProbePlan` (how many packets, how
+//! large, how far apart). A listener that knows the extension echoes the
+//! plan, trimmed to its limits, in the agreement. Right after connecting
+//! the caller sends the burst as `UserDefined` control packets
+//! (`SUBTYPE_PROBE`); the listener times their arrival and answers with a
+//! `ProbeReport` (`SUBTYPE_PROBE_REPORT`), from which the caller derives a
+//! `ProbeResult`.
+//!
+//! Capacity comes from the burst's dispersion: the bytes that arrived
+//! after the first packet over the time from the first arrival to the
+//! last. A burst sent back to back (spacing 0) spreads out to the
+//! bottleneck rate; a spacing caps the probe's own rate, and so the
+//! estimate. Peers that do not know the extension skip it, and no burst
+//! is sent.
+
+use crate::packet::{ControlPacket, ControlType};
+use crate::wire::{SUBTYPE_PROBE, SUBTYPE_PROBE_REPORT};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use std::time::{Duration, Instant};
+
+/// Most packets a listener accepts in a probe burst
+pub const MAX_PROBE_PACKETS: u16 = 64;
+
+/// Widest packet spacing a listener accepts, in microseconds
+///
+/// With `MAX_PROBE_PACKETS` this bounds a burst to about 630 ms, so a
+/// peer cannot make the listener hold probe state for long.
+pub const MAX_PROBE_SPACING_US: u32 = 10_000;
+
+/// Time the listener waits past the planned burst for its last packets
+const PROBE_GRACE: Duration = Duration::from_millis(250);
+
+/// Length of a probe plan in the handshake extension
+pub(crate) const PROBE_PLAN_LEN: usize = 8;
+
+/// Length of a probe report body
+const PROBE_REPORT_LEN: usize = 12;
+
+/// Shape of a probe burst, agreed in the handshake
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProbePlan {
+    /// Packets in the burst (at least 2 to measure anything)
+    pub packets: u16,
+    /// Body size of each probe packet in bytes
+    pub packet_size: u16,
+    /// Time between probe packets in microseconds (0 = back to back)
+    pub spacing_us: u32,
+}
+
+impl Default for ProbePlan {
+    fn default() -> Self {
+        ProbePlan {
+            packets: 16,
+            packet_size: 1316,
+            spacing_us: 0,
+        }
+    }
+}
+
+impl ProbePlan {
+    /// Check if the burst can measure a dispersion
+    pub fn is_valid(&self) -> bool {
+        self.packets >= 2 && self.packet_size > 0
+    }
+
+    /// Trim the plan to what a listener accepts: at most
+    /// `MAX_PROBE_PACKETS` packets of at most `max_size` bytes, at most
+    /// `MAX_PROBE_SPACING_US` apart
+    pub fn clamped(self, max_size: usize) -> Self {
+        ProbePlan {
+            packets: self.packets.min(MAX_PROBE_PACKETS),
+            packet_size: self
+                .packet_size
+                .min(u16::try_from(max_size).unwrap_or(u16::MAX)),
+            spacing_us: self.spacing_us.min(MAX_PROBE_SPACING_US),
+        }
+    }
+
+    /// Time from the first probe packet to the last, as planned
+    pub fn duration(&self) -> Duration {
+        Duration::from_micros(
+            u64::from(self.spacing_us) * u64::from(self.packets.saturating_sub(1)),
+        )
+    }
+
+    pub(crate) fn to_bytes(self) -> [u8; PROBE_PLAN_LEN] {
+        let mut bytes = [0; PROBE_PLAN_LEN];
+        bytes[..2].copy_from_slice(&self.packets.to_be_bytes());
+        bytes[2..4].copy_from_slice(&self.packet_size.to_be_bytes());
+        bytes[4..].copy_from_slice(&self.spacing_us.to_be_bytes());
+        bytes
+    }
+
+    pub(crate) fn from_bytes(mut bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != PROBE_PLAN_LEN {
+            return None;
+        }
+        Some(ProbePlan {
+            packets: bytes.get_u16(),
+            packet_size: bytes.get_u16(),
+            spacing_us: bytes.get_u32(),
+        })
+    }
+}
+
+/// What the listener measured of a probe burst
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProbeReport {
+    /// Probe packets that arrived
+    pub packets_received: u32,
+    /// Body bytes that arrived after the first probe packet
+    pub bytes_after_first: u32,
+    /// Time from the first arrival to the last in microseconds
+    pub dispersion_us: u32,
+}
+
+impl ProbeReport {
+    /// Bottleneck bandwidth in bytes per second, if at least two packets
+    /// arrived apart
+    pub fn bandwidth_bps(&self) -> Option<u64> {
+        (self.packets_received >= 2 && self.dispersion_us > 0)
+            .then(|| u64::from(self.bytes_after_first) * 1_000_000 / u64::from(self.dispersion_us))
+    }
+
+    /// Build the control packet carrying this report
+    pub fn to_control_packet(&self, dest_socket_id: u32) -> ControlPacket {
+        let mut body = BytesMut::with_capacity(PROBE_REPORT_LEN);
+        body.put_u32(self.packets_received);
+        body.put_u32(self.bytes_after_first);
+        body.put_u32(self.dispersion_us);
+        ControlPacket::new(
+            ControlType::UserDefined,
+            SUBTYPE_PROBE_REPORT,
+            0,
+            0,
+            dest_socket_id,
+            body.freeze(),
+        )
+    }
+
+    /// Parse a control packet, returning None if it is not a probe report
+    pub fn from_control_packet(packet: &ControlPacket) -> Option<Self> {
+        if packet.control_type() != ControlType::UserDefined
+            || packet.header.type_specific_info() != Some(SUBTYPE_PROBE_REPORT)
+        {
+            return None;
+        }
+        let mut body = packet.control_info.get(..PROBE_REPORT_LEN)?;
+        Some(ProbeReport {
+            packets_received: body.get_u32(),
+            bytes_after_first: body.get_u32(),
+            dispersion_us: body.get_u32(),
+        })
+    }
+}
+
+/// A path's capacity as measured by its probe burst
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ProbeResult {
+    /// Probe packets sent
+    pub packets_sent: u16,
+    /// Probe packets the listener received
+    pub packets_received: u32,
+    /// Bottleneck bandwidth in bytes per second, if measurable
+    pub bandwidth_bps: Option<u64>,
+    /// Round trip of the handshake in microseconds, if measured
+    pub rtt_us: Option<u32>,
+    /// Fraction of probe packets lost (0.0 to 1.0)
+    pub loss_rate: f64,
+}
+
+impl ProbeResult {
+    /// Combine a listener's report with what was sent
+    pub fn new(plan: &ProbePlan, report: &ProbeReport, rtt_us: Option<u32>) -> Self {
+        let sent = u32::from(plan.packets);
+        let received = report.packets_received.min(sent);
+        ProbeResult {
+            packets_sent: plan.packets,
+            packets_received: received,
+            bandwidth_bps: report.bandwidth_bps(),
+            rtt_us,
+            loss_rate: if sent == 0 {
+                0.0
+            } else {
+                f64::from(sent - received) / f64::from(sent)
+            },
+        }
+    }
+}
+
+/// Check if a control packet is part of a probe burst
+pub fn is_probe_packet(packet: &ControlPacket) -> bool {
+    packet.control_type() == ControlType::UserDefined
+        && packet.header.type_specific_info() == Some(SUBTYPE_PROBE)
+}
+
+/// Caller side: sends the burst on schedule
+#[derive(Debug, Clone)]
+pub(crate) struct ProbeSender {
+    plan: ProbePlan,
+    started: Option<Instant>,
+    sent: u16,
+}
+
+impl ProbeSender {
+    pub(crate) fn new(plan: ProbePlan) -> Self {
+        ProbeSender {
+            plan,
+            started: None,
+            sent: 0,
+        }
+    }
+
+    pub(crate) fn plan(&self) -> ProbePlan {
+        self.plan
+    }
+
+    /// Probe packets due by `now`; the first call starts the burst
+    pub(crate) fn poll(&mut self, now: Instant, dest_socket_id: u32) -> Vec<ControlPacket> {
+        let started = *self.started.get_or_insert(now);
+        let elapsed = now.saturating_duration_since(started).as_micros();
+        let due = match self.plan.spacing_us {
+            0 => self.plan.packets,
+            spacing => {
+                let due = elapsed / u128::from(spacing) + 1;
+                u16::try_from(due)
+                    .unwrap_or(u16::MAX)
+                    .min(self.plan.packets)
+            }
+        };
+        let body = Bytes::from(vec![0; usize::from(self.plan.packet_size)]);
+        let packets = (self.sent..due)
+            .map(|index| {
+                ControlPacket::new(
+                    ControlType::UserDefined,
+                    SUBTYPE_PROBE,
+                    u32::from(index),
+                    0,
+                    dest_socket_id,
+                    body.clone(),
+                )
+            })
+            .collect();
+        self.sent = self.sent.max(due);
+        packets
+    }
+}
+
+/// Listener side: times the burst's arrival and reports once it is over
+#[derive(Debug, Clone)]
+pub(crate) struct ProbeReceiver {
+    plan: ProbePlan,
+    first: Option<Instant>,
+    last: Option<Instant>,
+    received: u32,
+    bytes_after_first: u32,
+    reported: bool,
+}
+
+impl ProbeReceiver {
+    pub(crate) fn new(plan: ProbePlan) -> Self {
+        ProbeReceiver {
+            plan,
+            first: None,
+            last: None,
+            received: 0,
+            bytes_after_first: 0,
+            reported: false,
+        }
+    }
+
+    pub(crate) fn plan(&self) -> ProbePlan {
+        self.plan
+    }
+
+    /// Record a probe packet's arrival
+    pub(crate) fn on_probe(&mut self, packet: &ControlPacket, now: Instant) {
+        if self.reported || self.received >= u32::from(self.plan.packets) {
+            return;
+        }
+        if self.first.is_none() {
+            self.first = Some(now);
+        } else {
+            let size = u32::try_from(packet.control_info.len()).unwrap_or(u32::MAX);
+            self.bytes_after_first = self.bytes_after_first.saturating_add(size);
+        }
+        self.last = Some(now);
+        self.received += 1;
+    }
+
+    /// The report, once every packet arrived or the burst and a grace
+    /// period passed since the first; returned only once
+    pub(crate) fn poll(&mut self, now: Instant) -> Option<ProbeReport> {
+        let (first, last) = (self.first?, self.last?);
+        let complete = self.received >= u32::from(self.plan.packets);
+        let expired = now.saturating_duration_since(first) >= self.plan.duration() + PROBE_GRACE;
+        if self.reported || !(complete || expired) {
+            return None;
+        }
+        self.reported = true;
+        Some(ProbeReport {
+            packets_received: self.received,
+            bytes_after_first: self.bytes_after_first,
+            dispersion_us: u32::try_from(last.duration_since(first).as_micros())
+                .unwrap_or(u32::MAX),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_roundtrip_and_clamp() {
+        let plan = ProbePlan {
+            packets: 200,
+            packet_size: 1500,
+            spacing_us: 250,
+        };
+        assert_eq!(ProbePlan::from_bytes(&plan.to_bytes()), Some(plan));
+        assert_eq!(ProbePlan::from_bytes(&[0; 4]), None);
+
+        let clamped = plan.clamped(1456);
+        assert_eq!(clamped.packets, MAX_PROBE_PACKETS);
+        assert_eq!(clamped.packet_size, 1456);
+        assert_eq!(clamped.duration(), Duration::from_micros(250 * 63));
+        assert!(clamped.is_valid());
+        assert!(!ProbePlan { packets: 1, ..plan }.is_valid());
+    }
+
+    #[test]
+    fn test_clamp_oversized_spacing() {
+        // A peer asking for an hour between packets
+        let plan = ProbePlan {
+            packets: MAX_PROBE_PACKETS,
+            packet_size: 1316,
+            spacing_us: u32::MAX,
+        };
+        let clamped = plan.clamped(1456);
+        assert_eq!(clamped.spacing_us, MAX_PROBE_SPACING_US);
+        assert!(clamped.duration() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_report_roundtrip() {
+        let report = ProbeReport {
+            packets_received: 15,
+            bytes_after_first: 14 * 1000,
+            dispersion_us: 7_000,
+        };
+        let packet = ControlPacket::from_bytes(&report.to_control_packet(9).to_bytes()).unwrap();
+        assert_eq!(ProbeReport::from_control_packet(&packet), Some(report));
+        assert!(!is_probe_packet(&packet));
+        assert_eq!(report.bandwidth_bps(), Some(2_000_000));
+
+        let result = ProbeResult::new(&ProbePlan::default(), &report, Some(40_000));
+        assert_eq!(result.packets_received, 15);
+        assert_eq!(result.bandwidth_bps, Some(2_000_000));
+        assert!((result.loss_rate - 1.0 / 16.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_sender_paces_burst() {
+        let plan = ProbePlan {
+            packets: 4,
+            packet_size: 100,
+            spacing_us: 1_000,
+        };
+        let mut sender = ProbeSender::new(plan);
+        let start = Instant::now();
+        let first = sender.poll(start, 7);
+        assert_eq!(first.len(), 1);
+        assert!(is_probe_packet(&first[0]));
+        assert_eq!(first[0].control_info.len(), 100);
+
+        let next = sender.poll(start + Duration::from_micros(2_500), 7);
+        let indexes: Vec<_> = next
+            .iter()
+            .map(|p| p.header.additional_info().unwrap())
+            .collect();
+        assert_eq!(indexes, vec![1, 2]);
+        assert_eq!(sender.poll(start + Duration::from_secs(1), 7).len(), 1);
+        assert!(sender.poll(start + Duration::from_secs(2), 7).is_empty());
+
+        let mut burst = ProbeSender::new(ProbePlan::default());
+        assert_eq!(burst.poll(start, 7).len(), 16);
+    }
+
+    #[test]
+    fn test_receiver_measures_dispersion() {
+        let plan = ProbePlan {
+            packets: 4,
+            packet_size: 1000,
+            spacing_us: 0,
+        };
+        let probe = ProbeSender::new(plan).poll(Instant::now(), 1).remove(0);
+        let mut receiver = ProbeReceiver::new(plan);
+        let start = Instant::now();
+        assert!(receiver.poll(start).is_none());
+
+        // One packet lost: reported after the grace period
+        for ms in [0, 1, 2] {
+            receiver.on_probe(&probe, start + Duration::from_millis(ms));
+        }
+        assert!(receiver.poll(start + Duration::from_millis(10)).is_none());
+        let report = receiver.poll(start + PROBE_GRACE).unwrap();
+        assert_eq!(
+            report,
+            ProbeReport {
+                packets_received: 3,
+                bytes_after_first: 2000,
+                dispersion_us: 2000,
+            }
+        );
+        assert_eq!(report.bandwidth_bps(), Some(1_000_000));
+        assert!(receiver.poll(start + PROBE_GRACE).is_none());
+
+        // A complete burst reports at once
+        let mut receiver = ProbeReceiver::new(plan);
+        for ms in 0..4 {
+            receiver.on_probe(&probe, start + Duration::from_millis(ms));
+        }
+        assert_eq!(receiver.poll(start).unwrap().packets_received, 4);
+    }
+}
@@ -0,0 +1,214 @@
+//! Send scheduling
+//!
+//! `Connection` turns NAKs into entries in its sender loss list and hands
+//! out the lost packets from its send buffer, but leaves when to send them
+//! to the driver. `SenderWorker` closes that loop: each poll it collects
+//! due retransmissions ahead of new packets and spaces every packet by the
+//! connection's pacing delay, so a burst of losses cannot push the link
+//! past `max_bandwidth`.
+//!
+//! Once everything has been sent and the peer still has not acknowledged
+//! the oldest packet after an RTO, the worker resends that packet. In live
+//! mode nothing else would: a lost tail leaves the receiver no later packet
+//! to notice the gap by, so it never NAKs.
+
+use crate::connection::Connection;
+use crate::packet::DataPacket;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// What a driver should do next for a connection
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SendAction {
+    /// Send this packet now
+    Send(DataPacket),
+    /// The next packet may leave after this long
+    Wait(Duration),
+    /// Nothing to send
+    Idle,
+}
+
+/// Schedules a connection's retransmissions and new packets
+#[derive(Debug, Default)]
+pub struct SenderWorker {
+    /// Retransmissions collected but not yet sent
+    retransmits: VecDeque<DataPacket>,
+    /// New packets collected but not yet sent
+    fresh: VecDeque<DataPacket>,
+    /// Send time reserved for the next packet
+    ready_at: Option<Instant>,
+}
+
+impl SenderWorker {
+    /// Create an idle worker
+    pub fn new() -> Self {
+        SenderWorker::default()
+    }
+
+    /// Get the next action for `conn`
+    ///
+    /// Call again right after sending; on `Wait` or `Idle`, handle incoming
+    /// packets before polling again so new NAKs are picked up.
+    pub fn poll(&mut self, conn: &Connection) -> SendAction {
+        self.poll_at(conn, Instant::now())
+    }
+
+    fn poll_at(&mut self, conn: &Connection, now: Instant) -> SendAction {
+        if self.retransmits.is_empty() {
+            self.retransmits.extend(conn.packets_to_retransmit_at(now));
+        }
+        self.fresh.extend(conn.packets_to_send());
+        if self.retransmits.is_empty() && self.fresh.is_empty() {
+            self.retransmits
+                .extend(conn.retransmit_oldest_unacked_at(now));
+        }
+
+        let Some(next) = self.retransmits.front().or(self.fresh.front()) else {
+            return SendAction::Idle;
+        };
+        let size = next.size();
+        let ready_at = *self
+            .ready_at
+            .get_or_insert_with(|| now + conn.pacing_delay(size));
+        if now < ready_at {
+            return SendAction::Wait(ready_at - now);
+        }
+        self.ready_at = None;
+        match self.retransmits.pop_front() {
+            Some(packet) => SendAction::Send(packet),
+            None => self
+                .fresh
+                .pop_front()
+                .map_or(SendAction::Idle, SendAction::Send),
+        }
+    }
+
+    /// Get the number of packets collected but not yet sent
+    pub fn pending(&self) -> usize {
+        self.retransmits.len() + self.fresh.len()
+    }
+
+    /// Check if no packets are waiting to be sent
+    pub fn is_empty(&self) -> bool {
+        self.pending() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ack::AckInfo;
+    use crate::handshake::{SrtHandshake, SrtOptions};
+    use crate::loss::LossRange;
+    use crate::sequence::SeqNumber;
+
+    fn connected() -> Connection {
+        let mut conn = Connection::new(
+            1,
+            "127.0.0.1:9000".parse().unwrap(),
+            "127.0.0.1:9001".parse().unwrap(),
+            SeqNumber::new(0),
+            120,
+        );
+        let peer = SrtHandshake::new_request(
+            0,
+            2,
+            "127.0.0.1:9000".parse().unwrap(),
+            SrtOptions::default_capabilities(),
+            120,
+            120,
+        );
+        conn.process_handshake(peer).unwrap();
+        conn
+    }
+
+    fn drain(worker: &mut SenderWorker, conn: &Connection, now: Instant) -> Vec<u32> {
+        let mut seqs = Vec::new();
+        while let SendAction::Send(packet) = worker.poll_at(conn, now) {
+            seqs.push(packet.seq_number().as_raw());
+        }
+        seqs
+    }
+
+    #[test]
+    fn test_retransmissions_go_first() {
+        let conn = connected();
+        let mut worker = SenderWorker::new();
+        for _ in 0..3 {
+            conn.send(b"data").unwrap();
+        }
+        let now = Instant::now();
+        assert_eq!(drain(&mut worker, &conn, now), vec![0, 1, 2]);
+        assert_eq!(worker.poll_at(&conn, now), SendAction::Idle);
+
+        for _ in 0..2 {
+            conn.send(b"data").unwrap();
+        }
+        conn.process_nak(&[LossRange::new(SeqNumber::new(0), SeqNumber::new(1))]);
+        let SendAction::Send(first) = worker.poll_at(&conn, now) else {
+            panic!("expected a retransmission");
+        };
+        assert_eq!(first.seq_number().as_raw(), 0);
+        assert!(first.msg_number().retransmitted);
+        assert_eq!(worker.pending(), 3);
+        assert_eq!(drain(&mut worker, &conn, now), vec![1, 3, 4]);
+        assert!(worker.is_empty());
+        assert_eq!(conn.stats().nak_retransmissions, 2);
+    }
+
+    #[test]
+    fn test_paced_to_max_bandwidth() {
+        let conn = connected();
+        // 100 bytes per millisecond
+        conn.set_max_bandwidth(100_000);
+        let mut worker = SenderWorker::new();
+        for _ in 0..3 {
+            conn.send(&[0u8; 1000]).unwrap();
+        }
+        conn.process_nak(&[LossRange::single(SeqNumber::new(0))]);
+
+        let mut sent = 0;
+        let mut waited = Duration::ZERO;
+        while sent < 4 {
+            match worker.poll(&conn) {
+                SendAction::Send(_) => sent += 1,
+                SendAction::Wait(delay) => {
+                    waited += delay;
+                    std::thread::sleep(delay);
+                }
+                SendAction::Idle => panic!("packets left unsent"),
+            }
+        }
+        // Retransmissions count against the rate like new packets
+        assert!(waited >= Duration::from_millis(25), "waited {:?}", waited);
+    }
+
+    #[test]
+    fn test_oldest_unacked_resent_after_rto() {
+        let conn = connected();
+        let mut worker = SenderWorker::new();
+        for _ in 0..4 {
+            conn.send(b"data").unwrap();
+        }
+        let now = Instant::now();
+        assert_eq!(drain(&mut worker, &conn, now), vec![0, 1, 2, 3]);
+
+        // The receiver has 0 and 1; the tail was lost and is never NAKed
+        conn.process_ack(&AckInfo::new(SeqNumber::new(1)));
+        assert_eq!(worker.poll_at(&conn, now), SendAction::Idle);
+
+        let rto = conn.retransmit_timeout() + Duration::from_millis(1);
+        assert_eq!(drain(&mut worker, &conn, now + rto), vec![2]);
+        // Once per RTO, not every poll
+        assert_eq!(worker.poll_at(&conn, now + rto), SendAction::Idle);
+
+        conn.process_ack(&AckInfo::new(SeqNumber::new(2)));
+        assert_eq!(drain(&mut worker, &conn, now + rto * 3), vec![3]);
+        conn.process_ack(&AckInfo::new(SeqNumber::new(3)));
+        assert_eq!(worker.poll_at(&conn, now + rto * 5), SendAction::Idle);
+
+        let stats = conn.stats();
+        assert_eq!(stats.timeout_retransmissions, 2);
+        assert_eq!(stats.packets_retransmitted, 2);
+    }
+}
@@ -10,6 +10,10 @@ use std::ops::{Add, AddAssign, Sub, SubAssign};
 /// Maximum sequence number value (31-bit: 0x7FFFFFFF)
 pub const MAX_SEQ_NUMBER: u32 = 0x7FFF_FFFF;
 
+/// Longest `SeqRange`: half the sequence space, beyond which numbers no
+/// longer order correctly
+pub const MAX_SEQ_RANGE_LEN: u32 = MAX_SEQ_NUMBER / 2 + 1;
+
 /// Sequence number with 31-bit wraparound semantics
 ///
 /// SRT sequence numbers are 31-bit values that wrap around. The comparison
@@ -59,6 +63,12 @@ impl SeqNumber {
         SeqNumber((self.0 + 1) & MAX_SEQ_NUMBER)
     }
 
+    /// Get the previous sequence number (0 wraps to `MAX_SEQ_NUMBER`)
+    #[inline]
+    pub fn prev(self) -> Self {
+        SeqNumber(self.0.wrapping_sub(1) & MAX_SEQ_NUMBER)
+    }
+
     /// Calculate the distance from this sequence number to another
     ///
     /// Returns a signed distance that accounts for wraparound. Positive values
@@ -106,6 +116,76 @@ impl SeqNumber {
     }
 }
 
+/// Contiguous block of sequence numbers, possibly across the wrap
+///
+/// Iterating yields each number in order, wrapping from `MAX_SEQ_NUMBER`
+/// to 0.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SeqRange {
+    start: SeqNumber,
+    len: u32,
+}
+
+impl SeqRange {
+    /// Create a range of `len` numbers from `start`
+    ///
+    /// # Panics
+    /// Panics if `len` exceeds `MAX_SEQ_RANGE_LEN`
+    pub fn new(start: SeqNumber, len: u32) -> Self {
+        assert!(
+            len <= MAX_SEQ_RANGE_LEN,
+            "Sequence range of {} exceeds half the sequence space",
+            len
+        );
+        SeqRange { start, len }
+    }
+
+    /// Get the first number in the range
+    pub fn start(&self) -> SeqNumber {
+        self.start
+    }
+
+    /// Get the number just past the range
+    pub fn end(&self) -> SeqNumber {
+        self.start + self.len
+    }
+
+    /// Get the number of sequence numbers in the range
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+
+    /// Check if the range is empty
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Check if the range holds `seq`
+    pub fn contains(&self, seq: SeqNumber) -> bool {
+        (seq.0.wrapping_sub(self.start.0) & MAX_SEQ_NUMBER) < self.len
+    }
+}
+
+impl Iterator for SeqRange {
+    type Item = SeqNumber;
+
+    fn next(&mut self) -> Option<SeqNumber> {
+        if self.len == 0 {
+            return None;
+        }
+        let seq = self.start;
+        self.start = seq.next();
+        self.len -= 1;
+        Some(seq)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len as usize, Some(self.len as usize))
+    }
+}
+
+impl ExactSizeIterator for SeqRange {}
+
 impl fmt::Debug for SeqNumber {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "SeqNumber({})", self.0)
@@ -209,6 +289,32 @@ mod tests {
         assert_eq!(seq.next().as_raw(), 101);
     }
 
+    #[test]
+    fn test_range_wraparound() {
+        let range = SeqRange::new(SeqNumber::new(MAX_SEQ_NUMBER - 1), 4);
+        assert_eq!(range.len(), 4);
+        assert_eq!(range.end().as_raw(), 2);
+        assert!(range.contains(SeqNumber::new(MAX_SEQ_NUMBER)));
+        assert!(range.contains(SeqNumber::new(1)));
+        assert!(!range.contains(SeqNumber::new(2)));
+        assert!(!range.contains(SeqNumber::new(MAX_SEQ_NUMBER - 2)));
+
+        let seqs: Vec<u32> = range.map(SeqNumber::as_raw).collect();
+        assert_eq!(seqs, vec![MAX_SEQ_NUMBER - 1, MAX_SEQ_NUMBER, 0, 1]);
+
+        let empty = SeqRange::new(SeqNumber::new(5), 0);
+        assert!(empty.is_empty());
+        assert!(!empty.contains(SeqNumber::new(5)));
+        assert_eq!(empty.count(), 0);
+    }
+
+    #[test]
+    fn test_prev() {
+        assert_eq!(SeqNumber::new(100).prev().as_raw(), 99);
+        assert_eq!(SeqNumber::new(0).prev().as_raw(), MAX_SEQ_NUMBER);
+        assert_eq!(SeqNumber::new(0).prev().next().as_raw(), 0);
+    }
+
     #[test]
     fn test_distance_simple() {
         let a = SeqNumber::new(100);
@@ -0,0 +1,163 @@
+//! Source Time
+//!
+//! Carries sender-supplied wallclock timestamps (e.g. capture times) to the
+//! receiver. The sender advertises the wallclock time of timestamp zero in
+//! the handshake, and each packet timestamp is the message's source time
+//! relative to that origin in microseconds. The receiver maps timestamps
+//! back onto the sender's wallclock.
+//!
+//! Packet timestamps are 32 bits and wrap roughly every 71.6 minutes; the
+//! receiver tracks wraparound to keep source times monotonic.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+/// Timestamp range covered before wraparound
+const WRAP_PERIOD_US: u64 = 1 << 32;
+
+/// Half the timestamp range, used to tell wraps from reordering
+const HALF_RANGE: u32 = 1 << 31;
+
+/// Source time errors
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceTimeError {
+    #[error("Source time is before the connection time origin")]
+    BeforeOrigin,
+}
+
+/// Convert a wallclock time to microseconds since the UNIX epoch
+///
+/// Times before the epoch map to zero.
+pub fn to_unix_micros(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_micros() as u64)
+        .unwrap_or(0)
+}
+
+/// Convert microseconds since the UNIX epoch to a wallclock time
+pub fn from_unix_micros(micros: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_micros(micros)
+}
+
+/// Compute the packet timestamp for a source time
+pub fn source_timestamp(
+    origin: SystemTime,
+    source_time: SystemTime,
+) -> Result<u32, SourceTimeError> {
+    let offset = source_time
+        .duration_since(origin)
+        .map_err(|_| SourceTimeError::BeforeOrigin)?;
+    // Truncation is the 32-bit wraparound
+    Ok(offset.as_micros() as u32)
+}
+
+/// Maps received packet timestamps back to the sender's wallclock
+#[derive(Debug, Clone)]
+pub struct SourceClock {
+    /// Sender's time origin
+    origin: SystemTime,
+    /// Most recent in-order timestamp
+    last: Option<u32>,
+    /// Number of timestamp wraps seen
+    wraps: u64,
+}
+
+impl SourceClock {
+    /// Create a clock for the peer's time origin
+    pub fn new(origin: SystemTime) -> Self {
+        SourceClock {
+            origin,
+            last: None,
+            wraps: 0,
+        }
+    }
+
+    /// Get the sender's time origin
+    pub fn origin(&self) -> SystemTime {
+        self.origin
+    }
+
+    /// Map a packet timestamp to the sender's source time
+    ///
+    /// Timestamps far below the last one are taken as a wrap; timestamps
+    /// far above it are stragglers from before the last wrap.
+    pub fn source_time(&mut self, timestamp: u32) -> SystemTime {
+        let wraps = match self.last {
+            Some(last) if timestamp < last && last - timestamp > HALF_RANGE => {
+                self.wraps += 1;
+                self.last = Some(timestamp);
+                self.wraps
+            }
+            Some(last) if timestamp > last && timestamp - last > HALF_RANGE => {
+                self.wraps.saturating_sub(1)
+            }
+            Some(last) => {
+                self.last = Some(last.max(timestamp));
+                self.wraps
+            }
+            None => {
+                self.last = Some(timestamp);
+                self.wraps
+            }
+        };
+
+        self.origin + Duration::from_micros(wraps * WRAP_PERIOD_US + timestamp as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unix_micros_roundtrip() {
+        let time = from_unix_micros(1_700_000_000_123_456);
+        assert_eq!(to_unix_micros(time), 1_700_000_000_123_456);
+    }
+
+    #[test]
+    fn test_source_timestamp() {
+        let origin = from_unix_micros(1_000_000);
+        assert_eq!(
+            source_timestamp(origin, origin + Duration::from_millis(5)),
+            Ok(5000)
+        );
+        assert_eq!(
+            source_timestamp(origin, origin - Duration::from_millis(5)),
+            Err(SourceTimeError::BeforeOrigin)
+        );
+    }
+
+    #[test]
+    fn test_source_clock_roundtrip() {
+        let origin = from_unix_micros(1_700_000_000_000_000);
+        let mut clock = SourceClock::new(origin);
+
+        let capture = origin + Duration::from_millis(1234);
+        let ts = source_timestamp(origin, capture).unwrap();
+        assert_eq!(clock.source_time(ts), capture);
+    }
+
+    #[test]
+    fn test_source_clock_wraparound() {
+        let origin = from_unix_micros(1_700_000_000_000_000);
+        let mut clock = SourceClock::new(origin);
+
+        let before = u32::MAX - 10;
+        assert_eq!(
+            clock.source_time(before),
+            origin + Duration::from_micros(before as u64)
+        );
+
+        // Wrapped: continues past 2^32 microseconds
+        let after = origin + Duration::from_micros(WRAP_PERIOD_US + 5);
+        assert_eq!(clock.source_time(5), after);
+
+        // A reordered packet from before the wrap keeps its original time
+        assert_eq!(
+            clock.source_time(before + 2),
+            origin + Duration::from_micros(before as u64 + 2)
+        );
+        assert_eq!(clock.source_time(10), after + Duration::from_micros(5));
+    }
+}
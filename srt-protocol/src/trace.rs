@@ -0,0 +1,340 @@
+//! Wire-level packet traces
+//!
+//! `PacketTracer` logs a one-line summary of each datagram it is shown
+//! (packet type, sequence or ACK number, size, peer) under the `srt_trace`
+//! target. A `TraceConfig` limits the trace to data or control packets and
+//! samples one packet in N, so a busy stream can be traced without a packet
+//! capture and without drowning the logs.
+
+use crate::ack::{AckInfo, DropReqInfo, NakInfo};
+use crate::packet::{ControlPacket, ControlType, Packet, PacketError};
+use crate::wire::user_subtype_name;
+use std::fmt;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use thiserror::Error;
+
+/// Packet trace configuration errors
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum TraceError {
+    #[error("Unknown packet kind '{0}' (expected data, control or all)")]
+    UnknownKind(String),
+
+    #[error("Invalid sample rate '{0}' (expected a number of packets, at least 1)")]
+    InvalidSampleRate(String),
+}
+
+/// Which packets to trace
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TraceKind {
+    Data,
+    Control,
+    #[default]
+    All,
+}
+
+impl TraceKind {
+    fn matches(self, is_control: bool) -> bool {
+        match self {
+            TraceKind::Data => !is_control,
+            TraceKind::Control => is_control,
+            TraceKind::All => true,
+        }
+    }
+}
+
+impl FromStr for TraceKind {
+    type Err = TraceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "data" => Ok(TraceKind::Data),
+            "control" => Ok(TraceKind::Control),
+            "all" | "" => Ok(TraceKind::All),
+            _ => Err(TraceError::UnknownKind(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for TraceKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TraceKind::Data => write!(f, "data"),
+            TraceKind::Control => write!(f, "control"),
+            TraceKind::All => write!(f, "all"),
+        }
+    }
+}
+
+/// Packet trace settings, written `[data|control|all][:sample_rate]`
+///
+/// A sample rate of N traces one matching packet in N (1 = every packet).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceConfig {
+    /// Packets to trace
+    pub kind: TraceKind,
+    /// Trace one matching packet in this many
+    pub sample_rate: u32,
+}
+
+impl Default for TraceConfig {
+    fn default() -> Self {
+        TraceConfig {
+            kind: TraceKind::All,
+            sample_rate: 1,
+        }
+    }
+}
+
+impl FromStr for TraceConfig {
+    type Err = TraceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, rate) = match s.split_once(':') {
+            Some((kind, rate)) => (kind, Some(rate)),
+            None => (s, None),
+        };
+        let sample_rate = match rate {
+            Some(rate) => rate
+                .parse()
+                .ok()
+                .filter(|rate| *rate > 0)
+                .ok_or_else(|| TraceError::InvalidSampleRate(rate.to_string()))?,
+            None => 1,
+        };
+        Ok(TraceConfig {
+            kind: kind.parse()?,
+            sample_rate,
+        })
+    }
+}
+
+impl fmt::Display for TraceConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.sample_rate == 1 {
+            write!(f, "{}", self.kind)
+        } else {
+            write!(f, "{}:{}", self.kind, self.sample_rate)
+        }
+    }
+}
+
+/// Logs sampled one-line packet summaries
+#[derive(Debug, Default)]
+pub struct PacketTracer {
+    config: TraceConfig,
+    /// Packets that matched the kind so far
+    matched: AtomicU64,
+}
+
+impl PacketTracer {
+    /// Create a tracer
+    pub fn new(config: TraceConfig) -> Self {
+        PacketTracer {
+            config,
+            matched: AtomicU64::new(0),
+        }
+    }
+
+    /// Get the trace settings
+    pub fn config(&self) -> TraceConfig {
+        self.config
+    }
+
+    /// Trace a datagram sent to `peer`
+    pub fn on_sent(&self, bytes: &[u8], peer: SocketAddr) {
+        self.trace("->", bytes, peer);
+    }
+
+    /// Trace a datagram received from `peer`
+    pub fn on_received(&self, bytes: &[u8], peer: SocketAddr) {
+        self.trace("<-", bytes, peer);
+    }
+
+    fn trace(&self, arrow: &str, bytes: &[u8], peer: SocketAddr) {
+        if !self.should_trace(bytes) {
+            return;
+        }
+        tracing::info!(target: "srt_trace", "{} {} {}", arrow, peer, summarize(bytes));
+    }
+
+    /// Check the kind, then take every `sample_rate`th match
+    fn should_trace(&self, bytes: &[u8]) -> bool {
+        let is_control = bytes.first().is_some_and(|b| b & 0x80 != 0);
+        if !self.config.kind.matches(is_control) {
+            return false;
+        }
+        let n = self.matched.fetch_add(1, Ordering::Relaxed);
+        n % u64::from(self.config.sample_rate) == 0
+    }
+}
+
+/// Summarize a datagram in one line, e.g. `DATA seq=1042 msg=7 len=1332`
+pub fn summarize(bytes: &[u8]) -> String {
+    let len = bytes.len();
+    match Packet::from_bytes(bytes) {
+        Ok(Packet::Data(packet)) => {
+            let msg = packet.msg_number();
+            format!(
+                "DATA seq={} msg={} len={}{}",
+                packet.seq_number(),
+                msg.seq,
+                len,
+                if msg.retransmitted { " rexmit" } else { "" }
+            )
+        }
+        Ok(Packet::Control(packet)) => format!("{} len={}", summarize_control(&packet), len),
+        Err(PacketError::InvalidControlType(value)) => {
+            format!("CONTROL 0x{:04x} len={}", value, len)
+        }
+        Err(_) => format!("INVALID len={}", len),
+    }
+}
+
+fn summarize_control(packet: &ControlPacket) -> String {
+    let info = &packet.control_info;
+    match packet.control_type() {
+        ControlType::Ack => match AckInfo::from_bytes(info) {
+            Some(ack) => format!("ACK ack={} rtt={}us", ack.ack_seq, ack.rtt_us),
+            None => "ACK".to_string(),
+        },
+        ControlType::Nak => match NakInfo::from_bytes(info) {
+            Some(nak) => {
+                let lost: usize = nak.loss_ranges.iter().map(|r| r.len()).sum();
+                match nak.loss_ranges.first() {
+                    Some(first) => format!(
+                        "NAK lost={} ranges={} first={}-{}",
+                        lost,
+                        nak.loss_ranges.len(),
+                        first.start,
+                        first.end
+                    ),
+                    None => "NAK lost=0".to_string(),
+                }
+            }
+            None => "NAK".to_string(),
+        },
+        ControlType::DropReq => match DropReqInfo::from_bytes(info) {
+            Some(drop) => format!("DROPREQ {}-{}", drop.range.start, drop.range.end),
+            None => "DROPREQ".to_string(),
+        },
+        ControlType::KeepAlive if packet.header.type_specific_info() == Some(1) => {
+            "KEEPALIVE echo".to_string()
+        }
+        ControlType::UserDefined => {
+            let subtype = packet.user_subtype().unwrap_or(0);
+            match user_subtype_name(subtype) {
+                Some(name) => format!("USER 0x{:04x} {}", subtype, name),
+                None => format!("USER 0x{:04x}", subtype),
+            }
+        }
+        control_type => format!("{:?}", control_type).to_uppercase(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loss::LossRange;
+    use crate::packet::{DataPacket, MsgNumber, RawControlType};
+    use crate::sequence::SeqNumber;
+    use bytes::Bytes;
+
+    fn data_packet(seq: u32) -> Vec<u8> {
+        let mut msg = MsgNumber::new(7);
+        msg.retransmitted = seq % 2 == 1;
+        DataPacket::new(
+            SeqNumber::new(seq),
+            msg,
+            0,
+            1,
+            Bytes::from_static(&[0; 100]),
+        )
+        .to_bytes()
+        .to_vec()
+    }
+
+    fn control_packet(control_type: ControlType, info: Bytes) -> Vec<u8> {
+        ControlPacket::new(control_type, 0, 0, 0, 1, info)
+            .to_bytes()
+            .to_vec()
+    }
+
+    #[test]
+    fn test_trace_config_parse() {
+        assert_eq!("all".parse(), Ok(TraceConfig::default()));
+        assert_eq!(
+            "data:100".parse(),
+            Ok(TraceConfig {
+                kind: TraceKind::Data,
+                sample_rate: 100
+            })
+        );
+        let config: TraceConfig = "control".parse().unwrap();
+        assert_eq!(config.kind, TraceKind::Control);
+        assert_eq!(config.sample_rate, 1);
+        assert_eq!(":10".parse::<TraceConfig>().unwrap().kind, TraceKind::All);
+        assert_eq!(config.to_string(), "control");
+        assert_eq!(
+            "data:100".parse::<TraceConfig>().unwrap().to_string(),
+            "data:100"
+        );
+
+        assert_eq!(
+            "acks".parse::<TraceConfig>(),
+            Err(TraceError::UnknownKind("acks".to_string()))
+        );
+        assert!("data:0".parse::<TraceConfig>().is_err());
+        assert!("data:x".parse::<TraceConfig>().is_err());
+    }
+
+    #[test]
+    fn test_summarize() {
+        assert_eq!(summarize(&data_packet(42)), "DATA seq=42 msg=7 len=116");
+        assert_eq!(
+            summarize(&data_packet(43)),
+            "DATA seq=43 msg=7 len=116 rexmit"
+        );
+
+        let ack = AckInfo::new(SeqNumber::new(40)).to_bytes();
+        assert!(summarize(&control_packet(ControlType::Ack, ack)).starts_with("ACK ack=40 rtt="));
+
+        let nak = NakInfo::new(vec![
+            LossRange::new(SeqNumber::new(5), SeqNumber::new(7)),
+            LossRange::new(SeqNumber::new(9), SeqNumber::new(9)),
+        ])
+        .to_bytes();
+        assert_eq!(
+            summarize(&control_packet(ControlType::Nak, nak)),
+            "NAK lost=4 ranges=2 first=5-7 len=28"
+        );
+
+        let shutdown = control_packet(ControlType::Shutdown, Bytes::new());
+        assert_eq!(summarize(&shutdown), "SHUTDOWN len=16");
+        assert_eq!(summarize(&[0x80, 0]), "INVALID len=2");
+
+        let status = ControlPacket::new(ControlType::UserDefined, 0x4704, 0, 0, 1, Bytes::new());
+        assert_eq!(
+            summarize(&status.to_bytes()),
+            "USER 0x4704 PATH_STATUS len=16"
+        );
+        let unknown = ControlPacket::new_raw(RawControlType::new(9), 0, 0, 0, 1, Bytes::new());
+        assert_eq!(summarize(&unknown.to_bytes()), "CONTROL 0x0009 len=16");
+    }
+
+    #[test]
+    fn test_tracer_sampling() {
+        let tracer = PacketTracer::new("data:3".parse().unwrap());
+        let data = data_packet(1);
+        let control = control_packet(ControlType::Shutdown, Bytes::new());
+
+        let traced: Vec<bool> = (0..6).map(|_| tracer.should_trace(&data)).collect();
+        assert_eq!(traced, vec![true, false, false, true, false, false]);
+        assert!(!tracer.should_trace(&control));
+
+        let tracer = PacketTracer::new("control".parse().unwrap());
+        assert!(tracer.should_trace(&control));
+        assert!(!tracer.should_trace(&data));
+    }
+}
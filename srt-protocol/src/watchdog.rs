@@ -0,0 +1,257 @@
+//! Delivery stall detection
+//!
+//! A stream can stall without any error: the peer keeps acknowledging and
+//! sending keep-alives, yet the receiver's next expected sequence stays
+//! behind a gap nothing fills and the application gets no data. The
+//! watchdog watches delivery progress (the next expected sequence and the
+//! bytes delivered) while received data is waiting behind it. Once none
+//! has been made for the stall timeout it reports the stall once, with the
+//! action configured to recover from it; progress re-arms it.
+
+use crate::connection::ConnectionStats;
+use crate::labels::Labels;
+use crate::sequence::SeqNumber;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+/// What to do about a stalled stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum StallAction {
+    /// Log the diagnostics and wait
+    #[default]
+    Log,
+    /// Give up on the missing packets and deliver what arrived after them
+    Resync,
+    /// Mark the path broken so a bonding group moves to another
+    Failover,
+    /// Close the connection
+    Close,
+}
+
+impl StallAction {
+    /// Get the action's name
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StallAction::Log => "log",
+            StallAction::Resync => "resync",
+            StallAction::Failover => "failover",
+            StallAction::Close => "close",
+        }
+    }
+}
+
+impl fmt::Display for StallAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Stall action name this implementation does not know
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("Unknown stall action: {0} (expected log, resync, failover or close)")]
+pub struct UnknownStallAction(pub String);
+
+impl FromStr for StallAction {
+    type Err = UnknownStallAction;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "log" => Ok(StallAction::Log),
+            "resync" => Ok(StallAction::Resync),
+            "failover" => Ok(StallAction::Failover),
+            "close" => Ok(StallAction::Close),
+            other => Err(UnknownStallAction(other.to_string())),
+        }
+    }
+}
+
+/// Receiver state captured when a stall is detected
+#[derive(Debug, Clone)]
+pub struct StallDiagnostics {
+    /// Sequence delivery is waiting for
+    pub next_expected: SeqNumber,
+    /// Highest sequence received
+    pub highest_received: SeqNumber,
+    /// Packets missing between the two
+    pub missing_packets: usize,
+    /// Complete messages the application has not read
+    pub ready_messages: usize,
+    /// Share of the receive buffer in use (0.0 - 1.0)
+    pub recv_buffer_utilization: f32,
+    /// Time since anything was heard from the peer
+    pub last_heard: Option<Duration>,
+    /// Connection statistics
+    pub stats: ConnectionStats,
+}
+
+/// A stalled stream
+///
+/// Raised once per stall by `Connection::check_stall`, after the action
+/// has been taken as far as the connection can take it.
+#[derive(Debug, Clone)]
+pub struct StallEvent {
+    /// Time of the check
+    pub timestamp: Instant,
+    /// How long delivery has made no progress
+    pub stalled_for: Duration,
+    /// Action configured for the stall
+    pub action: StallAction,
+    /// Receiver state at the time
+    pub diagnostics: StallDiagnostics,
+    /// Labels of the connection
+    pub labels: Labels,
+}
+
+/// Tracks delivery progress and detects stalls
+#[derive(Debug, Clone)]
+pub struct DeliveryWatchdog {
+    /// Time without progress that counts as a stall (0 = off)
+    timeout: Duration,
+    action: StallAction,
+    /// Delivery position (next expected, bytes delivered) last seen
+    progress: Option<(SeqNumber, u64)>,
+    /// Since when the position has not moved with data waiting
+    since: Option<Instant>,
+    /// Whether the current stall has been reported
+    stalled: bool,
+}
+
+impl DeliveryWatchdog {
+    /// Create a watchdog reporting stalls of `timeout` (0 = off)
+    pub fn new(timeout: Duration, action: StallAction) -> Self {
+        DeliveryWatchdog {
+            timeout,
+            action,
+            progress: None,
+            since: None,
+            stalled: false,
+        }
+    }
+
+    /// Get the time without progress that counts as a stall (0 = off)
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Set the time without progress that counts as a stall (0 = off)
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Get the action taken on a stall
+    pub fn action(&self) -> StallAction {
+        self.action
+    }
+
+    /// Set the action taken on a stall
+    pub fn set_action(&mut self, action: StallAction) {
+        self.action = action;
+    }
+
+    /// Check if a stall has been reported and delivery has not moved since
+    pub fn is_stalled(&self) -> bool {
+        self.stalled
+    }
+
+    /// Record the delivery position
+    ///
+    /// `waiting` says whether received data is held behind
+    /// `next_expected`; without it an idle stream is not stalled. Returns
+    /// how long delivery has stalled the first time it reaches the timeout.
+    pub fn observe(
+        &mut self,
+        next_expected: SeqNumber,
+        bytes_delivered: u64,
+        waiting: bool,
+        now: Instant,
+    ) -> Option<Duration> {
+        let progress = Some((next_expected, bytes_delivered));
+        if self.timeout.is_zero() || !waiting || self.progress != progress {
+            self.progress = progress;
+            self.since = waiting.then_some(now);
+            self.stalled = false;
+            return None;
+        }
+
+        let stalled_for = now.saturating_duration_since(*self.since.get_or_insert(now));
+        if stalled_for < self.timeout || self.stalled {
+            return None;
+        }
+        self.stalled = true;
+        Some(stalled_for)
+    }
+}
+
+impl Default for DeliveryWatchdog {
+    fn default() -> Self {
+        DeliveryWatchdog::new(Duration::ZERO, StallAction::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stall_reported_once() {
+        let mut watchdog = DeliveryWatchdog::new(Duration::from_secs(2), StallAction::Resync);
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        let seq = SeqNumber::new(10);
+
+        assert_eq!(watchdog.observe(seq, 100, true, at(0)), None);
+        assert_eq!(watchdog.observe(seq, 100, true, at(1)), None);
+        assert_eq!(
+            watchdog.observe(seq, 100, true, at(3)),
+            Some(Duration::from_secs(3))
+        );
+        assert!(watchdog.is_stalled());
+        assert_eq!(watchdog.observe(seq, 100, true, at(10)), None);
+
+        // Progress re-arms it
+        assert_eq!(watchdog.observe(seq.next(), 200, true, at(11)), None);
+        assert!(!watchdog.is_stalled());
+        assert_eq!(
+            watchdog.observe(seq.next(), 200, true, at(13)),
+            Some(Duration::from_secs(2))
+        );
+    }
+
+    #[test]
+    fn test_idle_is_not_stalled() {
+        let mut watchdog = DeliveryWatchdog::new(Duration::from_secs(2), StallAction::Log);
+        let start = Instant::now();
+        let seq = SeqNumber::new(10);
+        for secs in 0..10 {
+            let now = start + Duration::from_secs(secs);
+            assert_eq!(watchdog.observe(seq, 100, false, now), None);
+        }
+        // The wait starts when data is first held back
+        let later = start + Duration::from_secs(10);
+        assert_eq!(watchdog.observe(seq, 100, true, later), None);
+        assert_eq!(
+            watchdog.observe(seq, 100, true, later + Duration::from_secs(1)),
+            None
+        );
+
+        // Off at 0
+        let mut off = DeliveryWatchdog::default();
+        assert_eq!(off.observe(seq, 0, true, start), None);
+        assert_eq!(off.observe(seq, 0, true, later), None);
+    }
+
+    #[test]
+    fn test_action_names() {
+        for action in [
+            StallAction::Log,
+            StallAction::Resync,
+            StallAction::Failover,
+            StallAction::Close,
+        ] {
+            assert_eq!(action.to_string().parse::<StallAction>(), Ok(action));
+        }
+        assert!("restart".parse::<StallAction>().is_err());
+    }
+}
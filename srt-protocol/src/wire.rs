@@ -0,0 +1,153 @@
+//! Wire constants audit
+//!
+//! Extended control messages share the `UserDefined` control type and are
+//! told apart by a 16-bit subtype in the type-specific field. The subtypes
+//! are used across crates (key material in srt-crypto, resync, path status
+//! and partition messages in srt-bonding), so they are allocated here, in
+//! one table, and each crate takes its value from it. Assertions checked
+//! at compile time fail the build if two subtypes collide or a constant
+//! fixed by the SRT specification drifts.
+
+use crate::handshake::{
+    SRT_CMD_CONGESTION, SRT_CMD_FILTER, SRT_CMD_HSREQ, SRT_CMD_KMREQ, SRT_CMD_KMRSP,
+    SRT_CMD_MAX_BW, SRT_CMD_PROBE, SRT_CMD_SID, SRT_CMD_TIME_ORIGIN, SRT_MAGIC_CODE,
+};
+use crate::packet::{max_payload_for_mss, ControlType, DEFAULT_MSS, HEADER_SIZE};
+use crate::packetizer::MAX_MSG_NUMBER;
+use crate::sequence::MAX_SEQ_NUMBER;
+
+/// Subtype: SRT handshake request (libsrt HSv4, reserved)
+pub const SUBTYPE_HSREQ: u16 = SRT_CMD_HSREQ;
+
+/// Subtype: SRT handshake response (libsrt HSv4, reserved)
+pub const SUBTYPE_HSRSP: u16 = 2;
+
+/// Subtype: key material request
+pub const SUBTYPE_KMREQ: u16 = SRT_CMD_KMREQ;
+
+/// Subtype: key material response
+pub const SUBTYPE_KMRSP: u16 = SRT_CMD_KMRSP;
+
+/// Subtype: bonding receiver reports its expected sequence
+pub const SUBTYPE_RESYNC_REPORT: u16 = 0x4701;
+
+/// Subtype: bonding sender announces its current sequence
+pub const SUBTYPE_RESYNC_ANNOUNCE: u16 = 0x4702;
+
+/// Subtype: sending rate ceiling requested of the peer
+pub const SUBTYPE_MAX_BW: u16 = 0x4703;
+
+/// Subtype: bonding receiver's per-path status report
+pub const SUBTYPE_PATH_STATUS: u16 = 0x4704;
+
+/// Subtype: balancing sender announces its sequence partition
+pub const SUBTYPE_PARTITION_ANNOUNCE: u16 = 0x4705;
+
+/// Subtype: packet of a handshake bandwidth probe burst
+pub const SUBTYPE_PROBE: u16 = 0x4706;
+
+/// Subtype: listener's measurement of a probe burst
+pub const SUBTYPE_PROBE_REPORT: u16 = 0x4707;
+
+/// Every `UserDefined` subtype in use, with its name
+pub const USER_SUBTYPES: [(u16, &str); 11] = [
+    (SUBTYPE_HSREQ, "HSREQ"),
+    (SUBTYPE_HSRSP, "HSRSP"),
+    (SUBTYPE_KMREQ, "KMREQ"),
+    (SUBTYPE_KMRSP, "KMRSP"),
+    (SUBTYPE_RESYNC_REPORT, "RESYNC_REPORT"),
+    (SUBTYPE_RESYNC_ANNOUNCE, "RESYNC_ANNOUNCE"),
+    (SUBTYPE_MAX_BW, "MAX_BW"),
+    (SUBTYPE_PATH_STATUS, "PATH_STATUS"),
+    (SUBTYPE_PARTITION_ANNOUNCE, "PARTITION_ANNOUNCE"),
+    (SUBTYPE_PROBE, "PROBE"),
+    (SUBTYPE_PROBE_REPORT, "PROBE_REPORT"),
+];
+
+/// Every handshake extension type this implementation sends or reads
+pub const HANDSHAKE_EXTENSIONS: [u16; 9] = [
+    SRT_CMD_HSREQ,
+    SRT_CMD_KMREQ,
+    SRT_CMD_KMRSP,
+    SRT_CMD_SID,
+    SRT_CMD_CONGESTION,
+    SRT_CMD_FILTER,
+    SRT_CMD_TIME_ORIGIN,
+    SRT_CMD_MAX_BW,
+    SRT_CMD_PROBE,
+];
+
+/// Get the name of a `UserDefined` subtype
+pub fn user_subtype_name(subtype: u16) -> Option<&'static str> {
+    USER_SUBTYPES
+        .iter()
+        .find(|(value, _)| *value == subtype)
+        .map(|(_, name)| *name)
+}
+
+const fn all_distinct(values: &[u16]) -> bool {
+    let mut i = 0;
+    while i < values.len() {
+        let mut j = i + 1;
+        while j < values.len() {
+            if values[i] == values[j] {
+                return false;
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+    true
+}
+
+const fn subtype_values() -> [u16; USER_SUBTYPES.len()] {
+    let mut values = [0; USER_SUBTYPES.len()];
+    let mut i = 0;
+    while i < values.len() {
+        values[i] = USER_SUBTYPES[i].0;
+        i += 1;
+    }
+    values
+}
+
+const _: () = assert!(
+    all_distinct(&subtype_values()),
+    "UserDefined subtypes collide"
+);
+const _: () = assert!(
+    all_distinct(&HANDSHAKE_EXTENSIONS),
+    "handshake extension types collide"
+);
+
+// Fixed by the specification
+const _: () = assert!(HEADER_SIZE == 16);
+const _: () = assert!(max_payload_for_mss(DEFAULT_MSS) == 1456);
+const _: () = assert!(MAX_SEQ_NUMBER == 0x7FFF_FFFF);
+const _: () = assert!(MAX_MSG_NUMBER == 0x03FF_FFFF);
+const _: () = assert!(SRT_MAGIC_CODE == 0x4A17);
+const _: () = assert!(SRT_CMD_HSREQ == 1 && SRT_CMD_KMREQ == 3 && SRT_CMD_KMRSP == 4);
+const _: () = assert!(SRT_CMD_SID == 5 && SRT_CMD_CONGESTION == 6 && SRT_CMD_FILTER == 7);
+const _: () = assert!(ControlType::Handshake as u16 == 0);
+const _: () = assert!(ControlType::KeepAlive as u16 == 1);
+const _: () = assert!(ControlType::Ack as u16 == 2);
+const _: () = assert!(ControlType::Nak as u16 == 3);
+const _: () = assert!(ControlType::CongestionWarning as u16 == 4);
+const _: () = assert!(ControlType::Shutdown as u16 == 5);
+const _: () = assert!(ControlType::AckAck as u16 == 6);
+const _: () = assert!(ControlType::DropReq as u16 == 7);
+const _: () = assert!(ControlType::PeerError as u16 == 8);
+const _: () = assert!(ControlType::UserDefined as u16 == 0x7FFF);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_user_subtype_names() {
+        assert_eq!(user_subtype_name(SUBTYPE_PATH_STATUS), Some("PATH_STATUS"));
+        assert_eq!(user_subtype_name(SUBTYPE_KMREQ), Some("KMREQ"));
+        assert_eq!(user_subtype_name(0x47FF), None);
+        assert!(all_distinct(&[1, 2, 3]));
+        assert!(!all_distinct(&[1, 2, 1]));
+    }
+}
@@ -0,0 +1,61 @@
+//! In-process sender/receiver loop
+//!
+//! Streams packets between two connections over the in-memory transport
+//! with induced loss and prints what it took to deliver them.
+//!
+//! ```text
+//! cargo run -p srt-tests --example loopback -- [packets] [loss per mille] [seed]
+//! ```
+
+use srt_tests::loopback::{self, LoopbackConfig};
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let mut config = LoopbackConfig::default();
+    if let Some(packets) = args.next() {
+        config.packets = packets.parse().expect("packets must be a number");
+    }
+    if let Some(loss) = args.next() {
+        config.loss_per_mille = loss.parse().expect("loss must be a number");
+    }
+    if let Some(seed) = args.next() {
+        config.seed = seed.parse().expect("seed must be a number");
+    }
+
+    let report = match loopback::run(&config) {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("Loopback failed: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let exact = report.received == report.sent;
+    println!(
+        "Delivered {} of {} packets ({} bytes each) in {:?} over {} ticks",
+        report.received.len(),
+        report.sent.len(),
+        config.payload_size,
+        report.elapsed,
+        report.ticks
+    );
+    println!(
+        "Link loss:   forward {}/{} dropped, reverse {}/{} dropped",
+        report.forward.dropped, report.forward.sent, report.reverse.dropped, report.reverse.sent
+    );
+    println!(
+        "Feedback:    {} ACKs, {} NAKs",
+        report.acks_sent, report.naks_sent
+    );
+    println!(
+        "Retransmits: {} ({} NAK, {} timeout)",
+        report.sender.packets_retransmitted,
+        report.sender.nak_retransmissions,
+        report.sender.timeout_retransmissions
+    );
+    println!("Byte-exact:  {}", if exact { "yes" } else { "NO" });
+
+    if !exact {
+        std::process::exit(1);
+    }
+}
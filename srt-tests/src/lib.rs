@@ -1,3 +1,10 @@
 //! SRT Integration Tests
 //!
-//! This crate contains integration tests for the SRT implementation.
+//! This crate contains integration tests for the SRT implementation, plus
+//! an in-memory transport for driving connections in-process.
+
+pub mod loopback;
+pub mod transport;
+
+pub use loopback::{LoopbackConfig, LoopbackError, LoopbackReport};
+pub use transport::{LinkStats, MemoryEndpoint};
@@ -0,0 +1,254 @@
+//! In-process sender/receiver loop
+//!
+//! Wires a real `Connection` pair through the in-memory transport:
+//! handshake, data, ACK/NAK feedback and retransmission all travel as
+//! serialized packets over lossy links. Used by the end-to-end test and the
+//! `loopback` example.
+
+use crate::transport::{LinkStats, MemoryEndpoint};
+use bytes::Bytes;
+use srt_protocol::handshake::HandshakeError;
+use srt_protocol::packet::{ControlType, PacketError};
+use srt_protocol::{
+    Connection, ConnectionError, ConnectionStats, ControlPacket, Packet, RetransmitPolicy,
+    SeqNumber, SrtHandshake,
+};
+use std::net::SocketAddr;
+use std::thread;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+const SENDER_ID: u32 = 1;
+const RECEIVER_ID: u32 = 2;
+
+/// Loopback errors
+#[derive(Error, Debug)]
+pub enum LoopbackError {
+    #[error("Connection error: {0}")]
+    Connection(#[from] ConnectionError),
+
+    #[error("Packet error: {0}")]
+    Packet(#[from] PacketError),
+
+    #[error("Handshake error: {0}")]
+    Handshake(#[from] HandshakeError),
+
+    #[error("Timed out after delivering {delivered} of {expected} messages")]
+    Timeout { delivered: usize, expected: usize },
+}
+
+/// Loopback run parameters
+#[derive(Debug, Clone)]
+pub struct LoopbackConfig {
+    /// Number of messages (one packet each) to stream
+    pub packets: usize,
+    /// Payload size per packet in bytes
+    pub payload_size: usize,
+    /// Induced loss in both directions (per mille)
+    pub loss_per_mille: u32,
+    /// Seed for payloads and loss
+    pub seed: u64,
+    /// Messages queued per tick
+    pub packets_per_tick: usize,
+    /// Ticks between ACKs
+    pub ack_interval: u64,
+    /// Ticks between NAKs
+    pub nak_interval: u64,
+    /// Minimum time before the receiver reports a loss again
+    pub nak_repeat_interval: Duration,
+    /// Wallclock limit for the whole run
+    pub timeout: Duration,
+}
+
+impl Default for LoopbackConfig {
+    fn default() -> Self {
+        LoopbackConfig {
+            packets: 10_000,
+            payload_size: 1316,
+            loss_per_mille: 50,
+            seed: 0x5EED,
+            packets_per_tick: 16,
+            ack_interval: 4,
+            nak_interval: 8,
+            nak_repeat_interval: Duration::ZERO,
+            timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Result of a loopback run
+#[derive(Debug, Clone)]
+pub struct LoopbackReport {
+    /// Messages handed to the sender
+    pub sent: Vec<Bytes>,
+    /// Messages delivered by the receiver, in order
+    pub received: Vec<Bytes>,
+    /// Ticks taken
+    pub ticks: u64,
+    /// ACKs sent by the receiver
+    pub acks_sent: u64,
+    /// NAKs sent by the receiver
+    pub naks_sent: u64,
+    /// Sender to receiver link counters
+    pub forward: LinkStats,
+    /// Receiver to sender link counters
+    pub reverse: LinkStats,
+    /// Sender connection statistics
+    pub sender: ConnectionStats,
+    /// Receiver connection statistics
+    pub receiver: ConnectionStats,
+    /// Wallclock duration of the run
+    pub elapsed: Duration,
+}
+
+/// Deterministic payload for message `index`
+///
+/// The first eight bytes hold the index so misordering is easy to spot.
+pub fn payload(seed: u64, index: usize, size: usize) -> Bytes {
+    let mut data = Vec::with_capacity(size);
+    data.extend_from_slice(&(index as u64).to_be_bytes());
+    let mut state = seed ^ (index as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    while data.len() < size {
+        state = state
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1);
+        data.push((state >> 56) as u8);
+    }
+    data.truncate(size);
+    Bytes::from(data)
+}
+
+fn connection(id: u32, local: SocketAddr, remote: SocketAddr) -> Connection {
+    let mut conn = Connection::new(id, local, remote, SeqNumber::new(0), 120);
+    conn.set_retransmit_policy(RetransmitPolicy::NakAndTimeout);
+    conn
+}
+
+fn handshake_packet(conn: &Connection, dest: u32) -> Bytes {
+    let body = conn.create_handshake().to_bytes().freeze();
+    ControlPacket::new(ControlType::Handshake, 0, 0, 0, dest, body)
+        .to_bytes()
+        .freeze()
+}
+
+/// Stream `config.packets` messages from a sender to a receiver in-process
+///
+/// The sender retransmits on NAK and on RTO expiry; the latter recovers
+/// losses at the tail of the stream, which the receiver cannot detect.
+pub fn run(config: &LoopbackConfig) -> Result<LoopbackReport, LoopbackError> {
+    let sender_addr: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+    let receiver_addr: SocketAddr = "127.0.0.1:9001".parse().unwrap();
+    let mut sender = connection(SENDER_ID, sender_addr, receiver_addr);
+    let mut receiver = connection(RECEIVER_ID, receiver_addr, sender_addr);
+    receiver.set_nak_interval(config.nak_repeat_interval);
+
+    let (tx, rx) = MemoryEndpoint::pair(config.seed);
+    tx.set_loss(config.loss_per_mille);
+    rx.set_loss(config.loss_per_mille);
+
+    let sent: Vec<Bytes> = (0..config.packets)
+        .map(|i| payload(config.seed, i, config.payload_size))
+        .collect();
+    let mut received = Vec::with_capacity(config.packets);
+    let mut queued = 0;
+    let (mut acks_sent, mut naks_sent) = (0, 0);
+    let mut ticks = 0;
+    let start = Instant::now();
+
+    while received.len() < config.packets {
+        if start.elapsed() > config.timeout {
+            return Err(LoopbackError::Timeout {
+                delivered: received.len(),
+                expected: config.packets,
+            });
+        }
+        ticks += 1;
+
+        // Sender: handshake until connected, then queue and transmit data
+        if !sender.is_connected() {
+            tx.send(handshake_packet(&sender, RECEIVER_ID));
+        } else {
+            let burst_end = (queued + config.packets_per_tick).min(sent.len());
+            while queued < burst_end {
+                match sender.send(&sent[queued]) {
+                    Ok(_) => queued += 1,
+                    // Wait for ACKs to free the send buffer
+                    Err(ConnectionError::Buffer(_)) => break,
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            for packet in sender
+                .packets_to_send()
+                .into_iter()
+                .chain(sender.packets_to_retransmit())
+            {
+                tx.send(packet.to_bytes().freeze());
+            }
+        }
+
+        // Receiver: answer handshakes, buffer data, deliver in order
+        while let Some(datagram) = rx.recv() {
+            match Packet::parse(datagram)? {
+                Packet::Data(packet) => receiver.process_data_packet(packet)?,
+                Packet::Control(control) if control.control_type() == ControlType::Handshake => {
+                    if !receiver.is_connected() {
+                        receiver
+                            .process_handshake(SrtHandshake::from_bytes(&control.control_info)?)?;
+                    }
+                    // Repeat the reply in case the previous one was lost
+                    rx.send(handshake_packet(&receiver, SENDER_ID));
+                }
+                Packet::Control(_) => {}
+            }
+        }
+        if receiver.is_connected() {
+            while let Some(message) = receiver.recv()? {
+                received.push(message);
+            }
+            if ticks % config.ack_interval == 0 {
+                if let Some(ack) = receiver.create_ack() {
+                    rx.send(ack.to_bytes().freeze());
+                    acks_sent += 1;
+                }
+            }
+            if ticks % config.nak_interval == 0 {
+                if let Some(nak) = receiver.create_nak() {
+                    rx.send(nak.to_bytes().freeze());
+                    naks_sent += 1;
+                }
+            }
+        }
+
+        // Sender: learn the handshake reply, apply ACK/NAK feedback
+        while let Some(datagram) = tx.recv() {
+            if let Packet::Control(control) = Packet::parse(datagram)? {
+                if control.control_type() == ControlType::Handshake {
+                    if !sender.is_connected() {
+                        sender
+                            .process_handshake(SrtHandshake::from_bytes(&control.control_info)?)?;
+                    }
+                } else {
+                    sender.process_control(&control)?;
+                }
+            }
+        }
+
+        // Only the RTO is left to wait for; don't spin
+        if queued == sent.len() && !tx.has_pending() && !rx.has_pending() {
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    Ok(LoopbackReport {
+        sent,
+        received,
+        ticks,
+        acks_sent,
+        naks_sent,
+        forward: tx.stats(),
+        reverse: rx.stats(),
+        sender: sender.stats(),
+        receiver: receiver.stats(),
+        elapsed: start.elapsed(),
+    })
+}
@@ -0,0 +1,164 @@
+//! In-memory transport
+//!
+//! A pair of datagram endpoints joined by two one-way links, for driving
+//! real connections in-process without sockets. Datagrams are delivered in
+//! order or dropped; drops are drawn from a seeded PRNG so runs are
+//! reproducible.
+
+use bytes::Bytes;
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+/// Small deterministic PRNG (xorshift64*)
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    /// Create a generator from a seed
+    pub fn new(seed: u64) -> Self {
+        // Zero is a fixed point of xorshift
+        Rng(seed.max(1))
+    }
+
+    /// Next raw value
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// True with probability `per_mille / 1000`
+    pub fn chance(&mut self, per_mille: u32) -> bool {
+        self.next_u64() % 1000 < per_mille as u64
+    }
+}
+
+/// Per-link counters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LinkStats {
+    /// Datagrams handed to the link
+    pub sent: u64,
+    /// Datagrams dropped by induced loss
+    pub dropped: u64,
+}
+
+/// One-way lossy link
+#[derive(Debug)]
+struct Link {
+    queue: VecDeque<Bytes>,
+    loss_per_mille: u32,
+    rng: Rng,
+    stats: LinkStats,
+}
+
+impl Link {
+    fn new(seed: u64) -> Self {
+        Link {
+            queue: VecDeque::new(),
+            loss_per_mille: 0,
+            rng: Rng::new(seed),
+            stats: LinkStats::default(),
+        }
+    }
+}
+
+/// One end of an in-memory datagram transport
+#[derive(Debug, Clone)]
+pub struct MemoryEndpoint {
+    outgoing: Arc<Mutex<Link>>,
+    incoming: Arc<Mutex<Link>>,
+}
+
+impl MemoryEndpoint {
+    /// Create two connected endpoints
+    ///
+    /// Both directions start lossless; see `set_loss`.
+    pub fn pair(seed: u64) -> (MemoryEndpoint, MemoryEndpoint) {
+        let forward = Arc::new(Mutex::new(Link::new(seed)));
+        let reverse = Arc::new(Mutex::new(Link::new(seed.rotate_left(32) ^ 0x5EED)));
+        (
+            MemoryEndpoint {
+                outgoing: forward.clone(),
+                incoming: reverse.clone(),
+            },
+            MemoryEndpoint {
+                outgoing: reverse,
+                incoming: forward,
+            },
+        )
+    }
+
+    /// Set the loss rate for datagrams sent from this endpoint (per mille)
+    pub fn set_loss(&self, per_mille: u32) {
+        self.outgoing.lock().loss_per_mille = per_mille.min(1000);
+    }
+
+    /// Send a datagram to the peer
+    ///
+    /// Returns false if the link dropped it.
+    pub fn send(&self, datagram: Bytes) -> bool {
+        let mut link = self.outgoing.lock();
+        link.stats.sent += 1;
+        let loss = link.loss_per_mille;
+        if link.rng.chance(loss) {
+            link.stats.dropped += 1;
+            return false;
+        }
+        link.queue.push_back(datagram);
+        true
+    }
+
+    /// Receive the next datagram from the peer, if any
+    pub fn recv(&self) -> Option<Bytes> {
+        self.incoming.lock().queue.pop_front()
+    }
+
+    /// Check if datagrams from the peer are waiting
+    pub fn has_pending(&self) -> bool {
+        !self.incoming.lock().queue.is_empty()
+    }
+
+    /// Counters for datagrams sent from this endpoint
+    pub fn stats(&self) -> LinkStats {
+        self.outgoing.lock().stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lossless_in_order() {
+        let (a, b) = MemoryEndpoint::pair(7);
+        for i in 0..10u8 {
+            assert!(a.send(Bytes::from(vec![i])));
+        }
+        b.send(Bytes::from_static(b"back"));
+
+        let received: Vec<u8> = std::iter::from_fn(|| b.recv()).map(|d| d[0]).collect();
+        assert_eq!(received, (0..10).collect::<Vec<_>>());
+        assert_eq!(a.recv().unwrap(), Bytes::from_static(b"back"));
+        assert!(!a.has_pending());
+    }
+
+    #[test]
+    fn test_induced_loss_is_reproducible() {
+        let run = |seed| {
+            let (a, b) = MemoryEndpoint::pair(seed);
+            a.set_loss(100);
+            for i in 0..1000u32 {
+                a.send(Bytes::copy_from_slice(&i.to_be_bytes()));
+            }
+            (a.stats(), std::iter::from_fn(|| b.recv()).count())
+        };
+
+        let (stats, delivered) = run(42);
+        assert_eq!(stats.sent, 1000);
+        assert_eq!(stats.sent - stats.dropped, delivered as u64);
+        assert!((50..150).contains(&stats.dropped));
+        assert_eq!(run(42).0, stats);
+    }
+}
@@ -559,3 +559,140 @@ fn test_packet_alignment_per_path_stats() {
     let final_stats = alignment.stats();
     assert_eq!(final_stats.packets_delivered, 50);
 }
+
+// ============================================================================
+// APPLICATION TESTS WITH FAKE MEMBERS
+// ============================================================================
+
+#[test]
+fn test_fake_members_broadcast_survives_scripted_loss() {
+    let mut fake = FakeGroup::new(GroupType::Broadcast, 2);
+    let lossy = fake.add_member().unwrap();
+    let steady = fake.add_member().unwrap();
+    fake.apply(lossy, MemberEvent::Loss(0.3)).unwrap();
+    fake.apply(steady, MemberEvent::Rtt(60_000)).unwrap();
+    fake.schedule(Duration::from_millis(50), lossy, MemberEvent::Down);
+
+    let bonding = BroadcastBonding::new(fake.group());
+    let mut delivered = Vec::new();
+    for i in 0..100u32 {
+        bonding.send(&i.to_be_bytes()).unwrap();
+        for delivery in fake.advance(Duration::from_millis(1)) {
+            let _ = bonding.on_receive(delivery.packet, delivery.member_id);
+        }
+        while let Some(packet) = bonding.receiver.pop_ready_packet() {
+            delivered.push(u32::from_be_bytes(packet.payload[..4].try_into().unwrap()));
+        }
+    }
+    for delivery in fake.advance(Duration::from_millis(100)) {
+        let _ = bonding.on_receive(delivery.packet, delivery.member_id);
+    }
+    while let Some(packet) = bonding.receiver.pop_ready_packet() {
+        delivered.push(u32::from_be_bytes(packet.payload[..4].try_into().unwrap()));
+    }
+
+    // The steady path fills every gap the lossy one left
+    assert_eq!(delivered, (0..100).collect::<Vec<_>>());
+    let lossy_stats = fake.link_stats(lossy).unwrap();
+    assert_eq!(lossy_stats.packets_sent, 100);
+    assert!(lossy_stats.packets_lost >= 50);
+    assert_eq!(fake.link_stats(steady).unwrap().packets_lost, 0);
+}
+
+/// Deepest a receiver's reorder buffer gets balancing a stream over a fast
+/// and a slow path: the most packets that arrived ahead of a late one
+fn balanced_reorder_depth(algorithm: BalancingAlgorithm) -> u32 {
+    let mut fake = FakeGroup::new(GroupType::Balancing, 2);
+    let fast = fake.add_member().unwrap();
+    let slow = fake.add_member().unwrap();
+    for (member_id, rtt_us) in [(fast, 10_000), (slow, 60_000)] {
+        fake.apply(member_id, MemberEvent::Rtt(rtt_us)).unwrap();
+        fake.apply(member_id, MemberEvent::Bandwidth(500_000))
+            .unwrap();
+        fake.group()
+            .get_member(member_id)
+            .unwrap()
+            .update_bandwidth(500_000);
+    }
+
+    let balancer = LoadBalancer::new(fake.group(), algorithm, 0);
+    let mut highest = None;
+    let mut depth = 0;
+    let mut receive = |deliveries: Vec<Delivery>| {
+        for delivery in deliveries {
+            let index = u32::from_be_bytes(delivery.packet.payload[..4].try_into().unwrap());
+            match highest {
+                Some(top) if index < top => depth = depth.max(top - index),
+                _ => highest = Some(index),
+            }
+        }
+    };
+    for i in 0..500u32 {
+        let mut payload = vec![0; 800];
+        payload[..4].copy_from_slice(&i.to_be_bytes());
+        balancer.send(&payload).unwrap();
+        receive(fake.advance(Duration::from_millis(1)));
+    }
+    receive(fake.advance(Duration::from_millis(200)));
+    depth
+}
+
+#[test]
+fn test_fake_members_arrival_equalized_reorders_less() {
+    let round_robin = balanced_reorder_depth(BalancingAlgorithm::RoundRobin);
+    let equalized = balanced_reorder_depth(BalancingAlgorithm::ArrivalEqualized);
+    // Round robin hands the slow path every other packet, so the fast
+    // path's run well ahead of it
+    assert!(
+        equalized * 2 < round_robin,
+        "equalized {} vs round robin {}",
+        equalized,
+        round_robin
+    );
+}
+
+#[test]
+fn test_fake_members_balancing_waits_for_congestion_windows() {
+    let mut fake = FakeGroup::new(GroupType::Balancing, 2);
+    fake.add_member().unwrap();
+    fake.add_member().unwrap();
+    let balancer = LoadBalancer::new(fake.group(), BalancingAlgorithm::RoundRobin, 0);
+    balancer.set_congestion_control(true);
+
+    // The receiver acknowledges each packet as it arrives
+    let mut delivered = 0;
+    let mut most_pending = 0;
+    for _ in 0..200 {
+        for _ in 0..4 {
+            balancer.enqueue(&[0u8; 1000]).unwrap();
+        }
+        most_pending = most_pending.max(balancer.pending());
+        for delivery in fake.advance(Duration::from_millis(1)) {
+            balancer.on_ack(delivery.member_id, 1);
+            delivered += 1;
+        }
+        for path_id in [1, 2] {
+            let stats = balancer.congestion_stats(path_id).unwrap();
+            assert!(stats.packets_in_flight <= stats.congestion_window);
+        }
+    }
+    for _ in 0..1000 {
+        if balancer.pending() == 0 {
+            break;
+        }
+        for delivery in fake.advance(Duration::from_millis(1)) {
+            balancer.on_ack(delivery.member_id, 1);
+            delivered += 1;
+        }
+    }
+    for delivery in fake.advance(Duration::from_millis(100)) {
+        balancer.on_ack(delivery.member_id, 1);
+        delivered += 1;
+    }
+
+    // Sending faster than the windows allow at first queues, and every
+    // packet still arrives once ACKs open them
+    assert!(most_pending > 0);
+    assert_eq!(balancer.pending(), 0);
+    assert_eq!(delivered, 800);
+}
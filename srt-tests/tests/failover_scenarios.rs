@@ -0,0 +1,420 @@
+//! Gapless failover scenarios
+//!
+//! Streams multi-packet messages through a simulated two-path network in
+//! backup mode and kills the primary path at a chosen point in the stream:
+//! in the middle of a message, on the sequence number wrap, and right as
+//! the key parity flips. Keep-alives detect the dead primary, backup
+//! bonding promotes the backup, and the sender replays everything the
+//! receiver has not acknowledged on the new primary.
+//!
+//! Each scenario asserts the seamless-switchover guarantee: every message
+//! is delivered intact, exactly once and in order, and no message waits
+//! longer than the detection time plus the path delays.
+
+use bytes::Bytes;
+use srt_bonding::*;
+use srt_protocol::packet::{ControlType, EncryptionKeySpec};
+use srt_protocol::{
+    AckInfo, Connection, ControlPacket, Depacketizer, Packet, Packetizer, SeqNumber,
+    MAX_MISSED_KEEPALIVES,
+};
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+// ============================================================================
+// PARAMETERS (in simulation ticks, 1 tick = 1 ms of virtual time)
+// ============================================================================
+
+const MESSAGES: u64 = 200;
+const PAYLOAD_SIZE: usize = 1316;
+/// Three packets per message, the last one short
+const MESSAGE_SIZE: usize = 3 * PAYLOAD_SIZE - 100;
+
+const PRIMARY_DELAY: u64 = 10;
+const BACKUP_DELAY: u64 = 25;
+
+const ACK_INTERVAL_TICKS: u64 = 10;
+const KEEPALIVE_INTERVAL_TICKS: u64 = 20;
+
+/// Longest a message may take to arrive across the failover: the primary
+/// is declared dead after `MAX_MISSED_KEEPALIVES` unanswered keep-alives,
+/// one more interval may pass before the first miss is counted, and the
+/// replay then crosses the backup path.
+const FAILOVER_DELAY_BOUND: u64 =
+    (MAX_MISSED_KEEPALIVES as u64 + 1) * KEEPALIVE_INTERVAL_TICKS + PRIMARY_DELAY + BACKUP_DELAY;
+
+const TICK_LIMIT: u64 = 10 * MESSAGES;
+
+// ============================================================================
+// SIMULATED NETWORK
+// ============================================================================
+
+/// One-way link with a fixed delay that can be cut
+struct SimLink {
+    /// Datagrams in flight with their arrival tick
+    in_flight: VecDeque<(u64, Bytes)>,
+    delay: u64,
+    alive: bool,
+}
+
+impl SimLink {
+    fn new(delay: u64) -> Self {
+        SimLink {
+            in_flight: VecDeque::new(),
+            delay,
+            alive: true,
+        }
+    }
+
+    fn send(&mut self, now: u64, data: Bytes) {
+        if self.alive {
+            self.in_flight.push_back((now + self.delay, data));
+        }
+    }
+
+    /// Cut the link, losing everything in flight
+    fn kill(&mut self) {
+        self.alive = false;
+        self.in_flight.clear();
+    }
+
+    fn deliver(&mut self, now: u64) -> Vec<Bytes> {
+        let mut arrived = Vec::new();
+        while self.in_flight.front().is_some_and(|(at, _)| *at <= now) {
+            arrived.push(self.in_flight.pop_front().unwrap().1);
+        }
+        arrived
+    }
+}
+
+/// One bonded path: the sender's member connection, the receiver's
+/// connection answering its keep-alives, and a link each way
+struct SimPath {
+    member_id: u32,
+    sender: Arc<Connection>,
+    receiver: Connection,
+    forward: SimLink,
+    reverse: SimLink,
+}
+
+impl SimPath {
+    fn new(group: &SocketGroup, id: u32, delay: u64) -> Self {
+        let sender_addr = test_addr(8000);
+        let receiver_addr = test_addr(9000 + id as u16);
+        let mut sender = Connection::new(id, sender_addr, receiver_addr, SeqNumber::new(0), 120);
+        let handshake = sender.create_handshake();
+        sender.process_handshake(handshake).unwrap();
+        // A keep-alive on every poll; the simulation sets the pace
+        sender.set_keepalive_interval(Duration::ZERO);
+        let sender = Arc::new(sender);
+
+        let member_id = group.add_member(sender.clone(), receiver_addr).unwrap();
+        SimPath {
+            member_id,
+            sender,
+            receiver: Connection::new(100 + id, receiver_addr, sender_addr, SeqNumber::new(0), 120),
+            forward: SimLink::new(delay),
+            reverse: SimLink::new(delay),
+        }
+    }
+
+    fn kill(&mut self) {
+        self.forward.kill();
+        self.reverse.kill();
+    }
+}
+
+fn test_addr(port: u16) -> SocketAddr {
+    format!("127.0.0.1:{}", port).parse().unwrap()
+}
+
+fn message_payload(index: u64) -> Vec<u8> {
+    let mut payload: Vec<u8> = (0..MESSAGE_SIZE)
+        .map(|i| (index as usize + i) as u8)
+        .collect();
+    payload[..8].copy_from_slice(&index.to_be_bytes());
+    payload
+}
+
+// ============================================================================
+// SCENARIO
+// ============================================================================
+
+/// Where the stream starts and where the primary dies
+struct Scenario {
+    initial_seq: SeqNumber,
+    /// The primary dies right after sending this fragment of this message
+    kill_message: u64,
+    kill_fragment: usize,
+    /// First message sent under the odd key, if the key is refreshed
+    key_refresh_message: Option<u64>,
+}
+
+impl Scenario {
+    fn key_for(&self, index: u64) -> EncryptionKeySpec {
+        match self.key_refresh_message {
+            Some(refresh) if index >= refresh => EncryptionKeySpec::Odd,
+            _ => EncryptionKeySpec::Even,
+        }
+    }
+}
+
+/// What a scenario run observed
+struct Outcome {
+    /// Sequence number of the last packet the primary sent
+    kill_seq: SeqNumber,
+    kill_tick: u64,
+    failover_tick: u64,
+    /// Delivery delay of each message, by index
+    delays: Vec<u64>,
+}
+
+fn run(scenario: &Scenario) -> Outcome {
+    // Sender side
+    let group = SocketGroup::new(1, GroupType::Backup, 2);
+    let mut paths = [
+        SimPath::new(&group, 1, PRIMARY_DELAY),
+        SimPath::new(&group, 2, BACKUP_DELAY),
+    ];
+    let backup = BackupBonding::new(Arc::new(group), Duration::ZERO, 3);
+    backup.set_primary(paths[0].member_id).unwrap();
+    backup.add_backup(paths[1].member_id).unwrap();
+
+    let mut packetizer = Packetizer::new(scenario.initial_seq, 0, PAYLOAD_SIZE);
+    let mut unacked: VecDeque<(SeqNumber, Bytes)> = VecDeque::new();
+
+    // Receiver side
+    let receiver = BroadcastReceiver::new(4096);
+    receiver.fast_forward(scenario.initial_seq);
+    let mut depacketizer = Depacketizer::new();
+    let mut delays = Vec::new();
+
+    let mut kill: Option<(SeqNumber, u64)> = None;
+    let mut failover_tick = None;
+    let mut tick = 0;
+
+    while (delays.len() as u64) < MESSAGES {
+        tick += 1;
+        assert!(
+            tick < TICK_LIMIT,
+            "stream stalled at {} messages",
+            delays.len()
+        );
+
+        let primary = backup.get_primary_id().unwrap();
+        let primary = paths.iter().position(|p| p.member_id == primary).unwrap();
+
+        // --- Sender: one message per tick on the primary ---
+        let index = tick - 1;
+        if index < MESSAGES {
+            let packets = packetizer.packetize_at(&message_payload(index), tick as u32);
+            assert_eq!(packets.len(), 3);
+
+            for (fragment, mut packet) in packets.into_iter().enumerate() {
+                let mut msg = packet.msg_number();
+                msg.encryption_key = scenario.key_for(index);
+                packet.header.msg_or_info = msg.to_raw();
+
+                let wire = packet.to_bytes().freeze();
+                unacked.push_back((packet.seq_number(), wire.clone()));
+                paths[primary].forward.send(tick, wire);
+
+                if index == scenario.kill_message && fragment == scenario.kill_fragment {
+                    paths[primary].kill();
+                    kill = Some((packet.seq_number(), tick));
+                }
+            }
+        }
+
+        // --- Sender: keep-alives on every path ---
+        if tick % KEEPALIVE_INTERVAL_TICKS == 0 {
+            for path in paths.iter_mut() {
+                // Owed echoes, then our own keep-alive, which with a zero
+                // interval is due on every poll
+                while let Some(keepalive) = path.sender.create_keepalive() {
+                    let own = keepalive.header.type_specific_info() == Some(0);
+                    path.forward.send(tick, keepalive.to_bytes().freeze());
+                    if own {
+                        break;
+                    }
+                }
+            }
+        }
+
+        // --- Receiver ---
+        for path in paths.iter_mut() {
+            for wire in path.forward.deliver(tick) {
+                match Packet::from_bytes(&wire).unwrap() {
+                    Packet::Data(packet) => {
+                        // Replayed packets the receiver already has are refused
+                        let _ = receiver.on_packet_received(packet, path.member_id);
+                    }
+                    Packet::Control(packet) => {
+                        path.receiver.process_control(&packet).unwrap();
+                        while let Some(reply) = path.receiver.create_keepalive() {
+                            path.reverse.send(tick, reply.to_bytes().freeze());
+                        }
+                    }
+                }
+            }
+        }
+
+        while let Some(packet) = receiver.pop_ready_packet() {
+            let sent_tick = u64::from(packet.header.timestamp);
+            let index = sent_tick - 1;
+            assert_eq!(
+                packet.msg_number().encryption_key,
+                scenario.key_for(index),
+                "key parity mismatch in message {}",
+                index
+            );
+
+            if let Some(message) = depacketizer.push(&packet) {
+                assert_eq!(index, delays.len() as u64, "out-of-order delivery");
+                assert_eq!(&message[..], &message_payload(index)[..]);
+                delays.push(tick - sent_tick);
+            }
+        }
+
+        if tick % ACK_INTERVAL_TICKS == 0 {
+            let ack = AckInfo::new(receiver.stats().next_expected - 1);
+            let ack = ControlPacket::new(ControlType::Ack, 0, 0, 0, 0, ack.to_bytes());
+            for path in paths.iter_mut() {
+                path.reverse.send(tick, ack.to_bytes().freeze());
+            }
+        }
+
+        // --- Sender: feedback ---
+        for path in paths.iter_mut() {
+            for wire in path.reverse.deliver(tick) {
+                let packet = ControlPacket::from_bytes(&wire).unwrap();
+                if packet.control_type() == ControlType::Ack {
+                    let ack = AckInfo::from_bytes(&packet.control_info).unwrap();
+                    while unacked
+                        .front()
+                        .is_some_and(|(seq, _)| (*seq).le(ack.ack_seq))
+                    {
+                        unacked.pop_front();
+                    }
+                } else {
+                    path.sender.process_control(&packet).unwrap();
+                }
+            }
+        }
+
+        // --- Sender: failover ---
+        if !backup.health_check().unwrap() {
+            assert!(failover_tick.is_none(), "failed over twice");
+            failover_tick = Some(tick);
+
+            let primary = backup.get_primary_id().unwrap();
+            let primary = paths.iter_mut().find(|p| p.member_id == primary).unwrap();
+            for (_, wire) in &unacked {
+                primary.forward.send(tick, wire.clone());
+            }
+        }
+    }
+
+    assert_eq!(depacketizer.dropped_messages(), 0, "partial message lost");
+    assert!(!depacketizer.has_partial());
+    assert_eq!(receiver.stats().skipped_packets, 0);
+    assert_eq!(receiver.stats().dropped_packets, 0);
+
+    let history = backup.failover_history();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].old_primary, paths[0].member_id);
+    assert_eq!(history[0].reason, FailoverReason::PrimaryFailed);
+    assert_eq!(history[0].new_primary, paths[1].member_id);
+
+    let (kill_seq, kill_tick) = kill.expect("primary never killed");
+    Outcome {
+        kill_seq,
+        kill_tick,
+        failover_tick: failover_tick.expect("no failover"),
+        delays,
+    }
+}
+
+/// Zero loss was checked by `run`; check the delays around the failover
+fn assert_bounded_delay(outcome: &Outcome) {
+    let detection = outcome.failover_tick - outcome.kill_tick;
+    assert!(
+        detection <= (MAX_MISSED_KEEPALIVES as u64 + 1) * KEEPALIVE_INTERVAL_TICKS,
+        "failure detected after {} ticks",
+        detection
+    );
+
+    for (index, delay) in outcome.delays.iter().enumerate() {
+        let sent_tick = index as u64 + 1;
+        assert!(
+            *delay <= FAILOVER_DELAY_BOUND,
+            "message {} delayed {} ticks",
+            index,
+            delay
+        );
+        if sent_tick + PRIMARY_DELAY < outcome.kill_tick {
+            assert_eq!(
+                *delay, PRIMARY_DELAY,
+                "message {} before the failure",
+                index
+            );
+        }
+        if sent_tick > outcome.failover_tick {
+            assert_eq!(*delay, BACKUP_DELAY, "message {} after the failover", index);
+        }
+    }
+}
+
+// ============================================================================
+// SCENARIOS
+// ============================================================================
+
+#[test]
+fn failover_mid_message() {
+    let scenario = Scenario {
+        initial_seq: SeqNumber::new(0),
+        kill_message: 40,
+        kill_fragment: 1,
+        key_refresh_message: None,
+    };
+    let outcome = run(&scenario);
+
+    // Message 40 was cut after its second packet
+    assert_eq!(outcome.kill_seq, SeqNumber::new(40 * 3 + 1));
+    assert_bounded_delay(&outcome);
+}
+
+#[test]
+fn failover_at_sequence_wrap() {
+    // Message 33 spans the wrap: MAX_SEQ_NUMBER, 0, 1
+    let scenario = Scenario {
+        initial_seq: SeqNumber::new(srt_protocol::sequence::MAX_SEQ_NUMBER - 99),
+        kill_message: 33,
+        kill_fragment: 0,
+        key_refresh_message: None,
+    };
+    let outcome = run(&scenario);
+
+    assert_eq!(
+        outcome.kill_seq,
+        SeqNumber::new(srt_protocol::sequence::MAX_SEQ_NUMBER)
+    );
+    assert_bounded_delay(&outcome);
+}
+
+#[test]
+fn failover_during_key_refresh() {
+    // The primary dies on the first packet under the new key
+    let scenario = Scenario {
+        initial_seq: SeqNumber::new(1_000),
+        kill_message: 60,
+        kill_fragment: 0,
+        key_refresh_message: Some(60),
+    };
+    let outcome = run(&scenario);
+
+    assert_eq!(outcome.kill_seq, SeqNumber::new(1_000 + 60 * 3));
+    assert_bounded_delay(&outcome);
+}
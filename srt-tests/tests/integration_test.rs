@@ -207,6 +207,51 @@ fn test_connection_lifecycle() {
     assert!(receiver.is_closed());
 }
 
+#[test]
+fn test_encrypted_handshake_enforcement() {
+    use srt::crypto::{CipherMode, KeyManager, KeyMaterial, Passphrase};
+    use srt_protocol::{ConnectionError, RejectReason};
+
+    let new_pair = || {
+        let caller = Connection::new(
+            12345,
+            "127.0.0.1:9000".parse().unwrap(),
+            "127.0.0.1:9001".parse().unwrap(),
+            SeqNumber::new(1000),
+            120,
+        );
+        let mut listener = Connection::new(
+            54321,
+            "127.0.0.1:9001".parse().unwrap(),
+            "127.0.0.1:9000".parse().unwrap(),
+            SeqNumber::new(2000),
+            120,
+        );
+        listener.set_enforce_encryption(true);
+        (caller, listener)
+    };
+    let sender_keys = KeyManager::new(CipherMode::Ctr, Passphrase::from("secret"), 16).unwrap();
+    let km = sender_keys.key_material().unwrap();
+
+    // The listener takes the caller's keys from its handshake
+    let (mut caller, mut listener) = new_pair();
+    caller.set_key_material(Some(km.to_bytes())).unwrap();
+    let request = SrtHandshake::from_bytes(&caller.create_handshake().to_bytes()).unwrap();
+    listener.process_handshake(request).unwrap();
+    let received = KeyMaterial::from_bytes(listener.peer_key_material().unwrap()).unwrap();
+    assert_eq!(received, km);
+    KeyManager::from_key_material(Passphrase::from("secret"), &received).unwrap();
+
+    // A clear-text caller is refused
+    let (caller, mut listener) = new_pair();
+    let request = SrtHandshake::from_bytes(&caller.create_handshake().to_bytes()).unwrap();
+    assert!(matches!(
+        listener.process_handshake(request),
+        Err(ConnectionError::Rejected(RejectReason::Unsecure))
+    ));
+    assert_eq!(listener.state(), ConnectionState::Init);
+}
+
 #[test]
 fn test_multi_packet_message() {
     let mut recv_buffer = ReceiveBuffer::new(1024);
@@ -346,3 +391,124 @@ fn test_bandwidth_estimation() {
     let bw = estimator.estimated_bandwidth_bps();
     assert!(bw > 0);
 }
+
+#[test]
+fn test_listener_multiplexes_callers() {
+    use srt::listener::{ListenerEvent, SrtListener};
+    use srt_protocol::packet::ControlType;
+    use srt_protocol::ControlPacket;
+    use std::net::UdpSocket;
+    use std::time::Instant;
+
+    let mut listener = SrtListener::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+    let listener_addr = listener.local_addr().unwrap();
+    let mut buf = vec![0u8; 1500];
+
+    // Poll the non-blocking listener until a datagram arrives
+    let mut next_event = |listener: &mut SrtListener| {
+        let deadline = Instant::now() + Duration::from_secs(2);
+        loop {
+            if let Some((n, event)) = listener.recv(&mut buf).unwrap() {
+                return (buf[..n].to_vec(), event);
+            }
+            assert!(Instant::now() < deadline, "no datagram received");
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    };
+
+    // Two callers on their own sockets, both sending to the one port
+    let mut callers = Vec::new();
+    for caller_id in [100, 200] {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        let mut conn = Connection::new(
+            caller_id,
+            socket.local_addr().unwrap(),
+            listener_addr,
+            SeqNumber::new(0),
+            120,
+        );
+        let request = ControlPacket::new(
+            ControlType::Handshake,
+            0,
+            0,
+            0,
+            0,
+            conn.create_handshake().to_bytes().freeze(),
+        );
+        socket.send_to(&request.to_bytes(), listener_addr).unwrap();
+
+        let ListenerEvent::Handshake(request) = next_event(&mut listener).1 else {
+            panic!("expected a handshake request");
+        };
+        assert_eq!(request.handshake().udt.socket_id, caller_id);
+        let accepted = Connection::new(
+            request.socket_id(),
+            listener_addr,
+            request.peer(),
+            SeqNumber::new(0),
+            120,
+        );
+        listener.accept(request, accepted).unwrap();
+
+        let mut reply = [0u8; 1500];
+        let n = socket.recv(&mut reply).unwrap();
+        let agreement = ControlPacket::from_bytes(&reply[..n]).unwrap();
+        conn.process_handshake(SrtHandshake::from_bytes(&agreement.control_info).unwrap())
+            .unwrap();
+        callers.push((socket, conn));
+    }
+    assert_eq!(listener.len(), 2);
+
+    // Each caller's data reaches the connection accepted for it
+    for (socket, conn) in &callers {
+        let local_id = conn.remote_socket_id().unwrap();
+        conn.send(format!("from {}", conn.local_socket_id()).as_bytes())
+            .unwrap();
+        for packet in conn.packets_to_send() {
+            socket.send_to(&packet.to_bytes(), listener_addr).unwrap();
+        }
+
+        let (datagram, event) = next_event(&mut listener);
+        let ListenerEvent::Packet { socket_id, peer } = event else {
+            panic!("expected a routed packet");
+        };
+        assert_eq!(socket_id, local_id);
+        assert_eq!(peer, socket.local_addr().unwrap());
+        let packet = DataPacket::from_bytes(&datagram).unwrap();
+        assert_eq!(
+            packet.payload,
+            Bytes::from(format!("from {}", conn.local_socket_id()))
+        );
+        assert_eq!(
+            listener.connection(socket_id).unwrap().remote_socket_id(),
+            Some(conn.local_socket_id())
+        );
+    }
+}
+
+#[test]
+fn test_stream_sender_to_receiver() {
+    use srt::stream::{SrtReceiver, SrtSender, StreamOptions};
+
+    let receiver =
+        SrtReceiver::bind("127.0.0.1:0".parse().unwrap(), StreamOptions::default()).unwrap();
+    let sender = SrtSender::connect(receiver.local_addr(), StreamOptions::default()).unwrap();
+
+    let payload = vec![0xAB; 1316];
+    for _ in 0..500 {
+        sender.send(&payload).unwrap();
+    }
+    for _ in 0..500 {
+        let data = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(data.as_ref(), &payload[..]);
+    }
+
+    let stats = sender.close().unwrap();
+    assert_eq!(stats.send_buffer_packets, 0);
+    assert_eq!(receiver.recv_timeout(Duration::from_secs(5)), None);
+    let stats = receiver.close().unwrap();
+    assert!(stats.packets_received >= 500);
+}
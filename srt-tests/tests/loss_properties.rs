@@ -0,0 +1,140 @@
+//! Property-based tests for loss list bookkeeping
+//!
+//! Random adds and removes are applied both to a `LossList` and to a plain
+//! set of sequence numbers, starting near 0 and near `MAX_SEQ_NUMBER` so the
+//! ranges regularly straddle the wrap. After every step the list must stay
+//! sorted, non-overlapping and non-adjacent, and cover exactly the set.
+
+use proptest::prelude::*;
+use srt_protocol::loss::{LossList, LossRange};
+use srt_protocol::sequence::{SeqNumber, MAX_SEQ_NUMBER};
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+/// Width of the window the operations touch
+const WINDOW: u32 = 64;
+
+#[derive(Debug, Clone)]
+enum Op {
+    Add(u32),
+    AddRange(u32, u32),
+    Remove(u32),
+    RemoveUpTo(u32),
+}
+
+fn base_strategy() -> impl Strategy<Value = u32> {
+    prop_oneof![
+        Just(0),
+        Just(MAX_SEQ_NUMBER - WINDOW / 2),
+        Just(MAX_SEQ_NUMBER - WINDOW + 1),
+        (MAX_SEQ_NUMBER - WINDOW)..=MAX_SEQ_NUMBER,
+        0..WINDOW,
+    ]
+}
+
+fn op_strategy() -> impl Strategy<Value = Op> {
+    prop_oneof![
+        3 => (0..WINDOW).prop_map(Op::Add),
+        2 => (0..WINDOW, 0..8u32).prop_map(|(start, len)| Op::AddRange(start, len)),
+        3 => (0..WINDOW).prop_map(Op::Remove),
+        1 => (0..WINDOW).prop_map(Op::RemoveUpTo),
+    ]
+}
+
+/// Sequence number `offset` packets after `base`
+fn seq(base: u32, offset: u32) -> SeqNumber {
+    SeqNumber::new_unchecked(base.wrapping_add(offset))
+}
+
+fn apply(list: &mut LossList, model: &mut BTreeSet<u32>, base: u32, op: &Op) {
+    match *op {
+        Op::Add(offset) => {
+            list.add(seq(base, offset));
+            model.insert(offset);
+        }
+        Op::AddRange(start, len) => {
+            let end = (start + len).min(WINDOW - 1);
+            list.add_range(LossRange::new(seq(base, start), seq(base, end)));
+            model.extend(start..=end);
+        }
+        Op::Remove(offset) => {
+            list.remove(seq(base, offset));
+            model.remove(&offset);
+        }
+        Op::RemoveUpTo(offset) => {
+            list.remove_up_to(seq(base, offset));
+            model.retain(|&o| o > offset);
+        }
+    }
+}
+
+fn check_invariants(list: &LossList, model: &BTreeSet<u32>, base: u32) {
+    let ranges = list.ranges();
+
+    for range in &ranges {
+        assert!(range.start.le(range.end), "inverted range {:?}", range);
+    }
+    for pair in ranges.windows(2) {
+        // Strictly after the previous range with a gap, or it should have merged
+        assert!(
+            pair[0].end.next().lt(pair[1].start),
+            "ranges out of order, overlapping or adjacent: {:?}",
+            ranges
+        );
+    }
+
+    assert_eq!(list.len(), model.len(), "total mismatch: {:?}", ranges);
+    assert_eq!(list.is_empty(), model.is_empty());
+
+    // Every sequence in the window, and just either side of it, agrees
+    for offset in 0..WINDOW + 2 {
+        let s = seq(base, offset);
+        assert_eq!(
+            list.contains(s),
+            model.contains(&offset),
+            "seq {} (offset {}) in {:?}",
+            s,
+            offset,
+            ranges
+        );
+    }
+    assert!(!list.contains(seq(base, 0).prev()), "{:?}", ranges);
+}
+
+proptest! {
+    #[test]
+    fn prop_loss_list_matches_model(
+        base in base_strategy(),
+        ops in prop::collection::vec(op_strategy(), 1..60),
+    ) {
+        let mut list = LossList::new(3, Duration::from_millis(10));
+        let mut model = BTreeSet::new();
+
+        for op in &ops {
+            apply(&mut list, &mut model, base, op);
+            check_invariants(&list, &model, base);
+        }
+    }
+
+    #[test]
+    fn prop_range_merge_is_symmetric(
+        base in base_strategy(),
+        a in (0..WINDOW, 0..8u32),
+        b in (0..WINDOW, 0..8u32),
+    ) {
+        let ra = LossRange::new(seq(base, a.0), seq(base, a.0 + a.1));
+        let rb = LossRange::new(seq(base, b.0), seq(base, b.0 + b.1));
+
+        let touching = a.0 <= b.0 + b.1 + 1 && b.0 <= a.0 + a.1 + 1;
+        let merged = ra.try_merge(&rb);
+        prop_assert_eq!(merged, rb.try_merge(&ra));
+        prop_assert_eq!(merged.is_some(), touching);
+
+        if let Some(merged) = merged {
+            let start = a.0.min(b.0);
+            let end = (a.0 + a.1).max(b.0 + b.1);
+            prop_assert_eq!(merged, LossRange::new(seq(base, start), seq(base, end)));
+            prop_assert_eq!(merged.len() as u32, end - start + 1);
+        }
+    }
+}
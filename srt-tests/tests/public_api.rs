@@ -0,0 +1,278 @@
+//! Public API snapshot
+//!
+//! Lists every public item of the library crates and compares the list with
+//! `tests/public-api.txt`, so that a change to the public surface shows up
+//! in review instead of slipping into a release. After an intended change,
+//! regenerate the snapshot and commit it:
+//!
+//! ```text
+//! UPDATE_PUBLIC_API=1 cargo test -p srt-tests --test public_api
+//! ```
+
+use quote::ToTokens;
+use std::fs;
+use std::path::{Path, PathBuf};
+use syn::{Attribute, Fields, ImplItem, Item, TraitItem, Visibility};
+
+/// Library crates covered by the snapshot
+const CRATES: &[&str] = &["srt-protocol", "srt-bonding", "srt-crypto", "srt-io", "srt"];
+
+fn workspace_root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .unwrap()
+        .to_path_buf()
+}
+
+/// Render tokens the way rustfmt would, near enough for a stable listing
+fn render<T: ToTokens>(tokens: &T) -> String {
+    let mut s = tokens.to_token_stream().to_string();
+    for (from, to) in [
+        (" :: ", "::"),
+        (":: ", "::"),
+        (" ,", ","),
+        (" ;", ";"),
+        (" :", ":"),
+        ("< ", "<"),
+        (" <", "<"),
+        (" >", ">"),
+        ("( ", "("),
+        (" (", "("),
+        (" )", ")"),
+        ("[ ", "["),
+        (" ]", "]"),
+        ("& ", "&"),
+        ("' ", "'"),
+        ("{ ", "{"),
+        (" }", "}"),
+        (",)", ")"),
+        (",}", "}"),
+        (",>", ">"),
+        ("=(", "= ("),
+        ("->", " -> "),
+        ("  ", " "),
+    ] {
+        s = s.replace(from, to);
+    }
+    s.trim().to_string()
+}
+
+fn is_public(vis: &Visibility) -> bool {
+    matches!(vis, Visibility::Public(_))
+}
+
+/// Items outside the API: test-only or `#[doc(hidden)]`
+fn is_excluded(attrs: &[Attribute]) -> bool {
+    attrs.iter().any(|attr| {
+        let rendered = render(attr);
+        rendered == "#[cfg(test)]" || rendered == "#[doc(hidden)]"
+    })
+}
+
+fn tags(attrs: &[Attribute]) -> String {
+    let mut tags = String::new();
+    for attr in attrs {
+        if attr.path().is_ident("deprecated") {
+            tags.push_str(" [deprecated]");
+        } else if attr.path().is_ident("derive") {
+            tags.push_str(&format!(" [{}]", render(&attr.meta)));
+        } else if attr.path().is_ident("non_exhaustive") {
+            tags.push_str(" [non_exhaustive]");
+        } else if attr.path().is_ident("cfg") {
+            // Feature-gated items, e.g. [cfg(feature = "bonding")]
+            tags.push_str(&format!(" [{}]", render(&attr.meta)));
+        }
+    }
+    tags
+}
+
+struct Snapshot {
+    lines: Vec<String>,
+}
+
+impl Snapshot {
+    fn add(&mut self, path: &str, item: String) {
+        self.lines.push(format!("{}: {}", path, item));
+    }
+
+    fn add_fields(&mut self, path: &str, fields: &Fields) {
+        for (i, field) in fields.iter().enumerate() {
+            if !is_public(&field.vis) || is_excluded(&field.attrs) {
+                continue;
+            }
+            let name = field
+                .ident
+                .as_ref()
+                .map_or(i.to_string(), |ident| ident.to_string());
+            self.add(path, format!("pub {}: {}", name, render(&field.ty)));
+        }
+    }
+
+    /// List the public items of the module at `file` (`path` is its Rust path)
+    fn add_file(&mut self, path: &str, file: &Path) {
+        let source = fs::read_to_string(file).unwrap();
+        let ast = syn::parse_file(&source).unwrap();
+        self.add_items(path, file.parent().unwrap(), &ast.items);
+    }
+
+    fn add_items(&mut self, path: &str, dir: &Path, items: &[Item]) {
+        for item in items {
+            match item {
+                Item::Mod(m) if is_public(&m.vis) && !is_excluded(&m.attrs) => {
+                    let child = format!("{}::{}", path, m.ident);
+                    self.add(path, format!("pub mod {}{}", m.ident, tags(&m.attrs)));
+                    match &m.content {
+                        Some((_, items)) => self.add_items(&child, dir, items),
+                        None => self.add_file(&child, &dir.join(format!("{}.rs", m.ident))),
+                    }
+                }
+                Item::Use(u) if is_public(&u.vis) && !is_excluded(&u.attrs) => {
+                    let tags = tags(&u.attrs);
+                    let mut u = u.clone();
+                    u.attrs.clear();
+                    self.add(path, format!("{}{}", render(&u), tags));
+                }
+                Item::Const(c) if is_public(&c.vis) && !is_excluded(&c.attrs) => {
+                    let tags = tags(&c.attrs);
+                    self.add(
+                        path,
+                        format!("pub const {}: {}{}", c.ident, render(&c.ty), tags),
+                    );
+                }
+                Item::Static(s) if is_public(&s.vis) && !is_excluded(&s.attrs) => {
+                    let tags = tags(&s.attrs);
+                    self.add(
+                        path,
+                        format!("pub static {}: {}{}", s.ident, render(&s.ty), tags),
+                    );
+                }
+                Item::Type(t) if is_public(&t.vis) && !is_excluded(&t.attrs) => {
+                    self.add(
+                        path,
+                        format!(
+                            "pub type {}{} = {}",
+                            t.ident,
+                            render(&t.generics),
+                            render(&t.ty)
+                        ),
+                    );
+                }
+                Item::Fn(f) if is_public(&f.vis) && !is_excluded(&f.attrs) => {
+                    self.add(path, format!("pub {}{}", render(&f.sig), tags(&f.attrs)));
+                }
+                Item::Struct(s) if is_public(&s.vis) && !is_excluded(&s.attrs) => {
+                    let name = format!("{}{}", s.ident, render(&s.generics));
+                    self.add(path, format!("pub struct {}{}", name, tags(&s.attrs)));
+                    self.add_fields(&format!("{}::{}", path, s.ident), &s.fields);
+                }
+                Item::Enum(e) if is_public(&e.vis) && !is_excluded(&e.attrs) => {
+                    let name = format!("{}{}", e.ident, render(&e.generics));
+                    self.add(path, format!("pub enum {}{}", name, tags(&e.attrs)));
+                    let enum_path = format!("{}::{}", path, e.ident);
+                    for variant in &e.variants {
+                        let fields = match &variant.fields {
+                            Fields::Unit => String::new(),
+                            fields => render(fields),
+                        };
+                        self.add(&enum_path, format!("{}{}", variant.ident, fields));
+                    }
+                }
+                Item::Trait(t) if is_public(&t.vis) && !is_excluded(&t.attrs) => {
+                    let name = format!("{}{}", t.ident, render(&t.generics));
+                    self.add(path, format!("pub trait {}{}", name, tags(&t.attrs)));
+                    let trait_path = format!("{}::{}", path, t.ident);
+                    for item in &t.items {
+                        match item {
+                            TraitItem::Fn(f) => self.add(&trait_path, render(&f.sig)),
+                            TraitItem::Type(ty) => {
+                                self.add(&trait_path, format!("type {}", ty.ident))
+                            }
+                            TraitItem::Const(c) => self
+                                .add(&trait_path, format!("const {}: {}", c.ident, render(&c.ty))),
+                            _ => {}
+                        }
+                    }
+                }
+                Item::Impl(i) if !is_excluded(&i.attrs) => {
+                    let self_ty = render(&i.self_ty);
+                    let impl_path = format!("{}::{}", path, self_ty);
+                    if let Some((_, trait_path, _)) = &i.trait_ {
+                        // Trait impls are public whenever the type is
+                        self.add(
+                            path,
+                            format!(
+                                "impl{} {} for {}",
+                                render(&i.generics),
+                                render(trait_path),
+                                self_ty
+                            ),
+                        );
+                        continue;
+                    }
+                    for item in &i.items {
+                        match item {
+                            ImplItem::Fn(f) if is_public(&f.vis) && !is_excluded(&f.attrs) => {
+                                self.add(
+                                    &impl_path,
+                                    format!("pub {}{}", render(&f.sig), tags(&f.attrs)),
+                                );
+                            }
+                            ImplItem::Const(c) if is_public(&c.vis) && !is_excluded(&c.attrs) => {
+                                self.add(
+                                    &impl_path,
+                                    format!("pub const {}: {}", c.ident, render(&c.ty)),
+                                );
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+fn public_api() -> String {
+    let root = workspace_root();
+    let mut snapshot = Snapshot { lines: Vec::new() };
+    for krate in CRATES {
+        let name = krate.replace('-', "_");
+        snapshot.add_file(&name, &root.join(krate).join("src/lib.rs"));
+    }
+    snapshot.lines.join("\n") + "\n"
+}
+
+#[test]
+fn test_public_api_snapshot() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/public-api.txt");
+    let current = public_api();
+
+    if std::env::var_os("UPDATE_PUBLIC_API").is_some() {
+        fs::write(&path, &current).unwrap();
+        return;
+    }
+
+    let expected = fs::read_to_string(&path).unwrap_or_default();
+    if current == expected {
+        return;
+    }
+
+    let expected: Vec<_> = expected.lines().collect();
+    let current: Vec<_> = current.lines().collect();
+    let removed: Vec<_> = expected.iter().filter(|l| !current.contains(l)).collect();
+    let added: Vec<_> = current.iter().filter(|l| !expected.contains(l)).collect();
+    let mut diff = String::new();
+    for line in removed {
+        diff.push_str(&format!("- {}\n", line));
+    }
+    for line in added {
+        diff.push_str(&format!("+ {}\n", line));
+    }
+    panic!(
+        "The public API changed. If intended, rerun with UPDATE_PUBLIC_API=1 \
+         and commit tests/public-api.txt (see the deprecation policy in \
+         docs/RELEASING.md before removing anything):\n{}",
+        diff
+    );
+}
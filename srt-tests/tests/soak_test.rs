@@ -0,0 +1,386 @@
+//! Long-running soak test
+//!
+//! Streams continuously through an in-process simulated network with churn
+//! (members added/removed, loss bursts, key parity rotation) and checks that
+//! memory stays bounded, delivery never stalls, and packets are delivered
+//! exactly once in sequence order.
+//!
+//! Gated behind the `soak` feature. Runs for a few seconds by default, so
+//! `--all-features` test runs stay short; set `SRT_SOAK_SECS` for a real
+//! soak, such as four hours before a release:
+//!
+//! ```text
+//! SRT_SOAK_SECS=14400 cargo test -p srt-tests --features soak --release \
+//!     --test soak_test -- --nocapture
+//! ```
+
+#![cfg(feature = "soak")]
+
+use bytes::Bytes;
+use srt_bonding::*;
+use srt_protocol::loss::ReceiverLossList;
+use srt_protocol::packet::EncryptionKeySpec;
+use srt_protocol::{Connection, DataPacket, LossRange, Packetizer, SendBuffer, SeqNumber};
+use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// ============================================================================
+// PARAMETERS (in simulation ticks, 1 tick = 1 ms of virtual time)
+// ============================================================================
+
+const DEFAULT_SOAK_SECS: u64 = 5;
+
+const PACKETS_PER_TICK: usize = 4;
+const PAYLOAD_SIZE: usize = 1316;
+const MAX_PATHS: usize = 4;
+
+const SEND_BUFFER_CAPACITY: usize = 8192;
+const RECEIVER_BUFFER_CAPACITY: usize = 8192;
+
+const ACK_INTERVAL_TICKS: u64 = 10;
+const NAK_INTERVAL_TICKS: u64 = 100;
+const CHURN_INTERVAL_TICKS: u64 = 2_000;
+const OUTAGE_INTERVAL_TICKS: u64 = 7_500;
+const KEY_ROTATION_TICKS: u64 = 10_000;
+
+/// Delivery must advance at least once within this many ticks
+const STALL_LIMIT_TICKS: u64 = 5_000;
+
+// ============================================================================
+// SIMULATED NETWORK
+// ============================================================================
+
+/// Small deterministic PRNG (xorshift64*)
+struct Rng(u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
+
+    fn chance(&mut self, per_mille: u64) -> bool {
+        self.below(1000) < per_mille
+    }
+}
+
+/// One-way lossy link with delay, jitter, and Gilbert-Elliott burst loss
+struct SimLink {
+    /// Datagrams in flight, keyed by (arrival tick, send order)
+    in_flight: BTreeMap<(u64, u64), Bytes>,
+    base_delay: u64,
+    jitter: u64,
+    /// Currently in the bad (bursty) loss state
+    bad_state: bool,
+    sent: u64,
+}
+
+impl SimLink {
+    fn new(rng: &mut Rng) -> Self {
+        SimLink {
+            in_flight: BTreeMap::new(),
+            base_delay: 10 + rng.below(30),
+            jitter: 1 + rng.below(10),
+            bad_state: false,
+            sent: 0,
+        }
+    }
+
+    fn send(&mut self, rng: &mut Rng, now: u64, outage: bool, data: Bytes) {
+        self.sent += 1;
+
+        // Good -> bad 0.2%, bad -> good 5%
+        if self.bad_state {
+            self.bad_state = !rng.chance(50);
+        } else {
+            self.bad_state = rng.chance(2);
+        }
+
+        let loss_per_mille = if self.bad_state { 500 } else { 5 };
+        if outage || rng.chance(loss_per_mille) {
+            return;
+        }
+
+        let arrival = now + self.base_delay + rng.below(self.jitter);
+        self.in_flight.insert((arrival, self.sent), data);
+    }
+
+    fn deliver(&mut self, now: u64) -> Vec<Bytes> {
+        let later = self.in_flight.split_off(&(now + 1, 0));
+        std::mem::replace(&mut self.in_flight, later)
+            .into_values()
+            .collect()
+    }
+}
+
+/// Control feedback from receiver to sender
+enum Feedback {
+    Ack(SeqNumber),
+    Nak(Vec<LossRange>),
+}
+
+fn test_addr(port: u16) -> SocketAddr {
+    format!("127.0.0.1:{}", port).parse().unwrap()
+}
+
+fn add_member(group: &SocketGroup, id: u32) -> u32 {
+    let addr = test_addr(10_000 + (id % 50_000) as u16);
+    let mut conn = Connection::new(id, test_addr(8000), addr, SeqNumber::new(0), 120);
+    let handshake = conn.create_handshake();
+    conn.process_handshake(handshake).unwrap();
+
+    let member_id = group.add_member(Arc::new(conn), addr).unwrap();
+    group
+        .update_member_status(member_id, MemberStatus::Active)
+        .unwrap();
+    member_id
+}
+
+fn key_for_epoch(epoch: u64) -> EncryptionKeySpec {
+    if epoch % 2 == 0 {
+        EncryptionKeySpec::Even
+    } else {
+        EncryptionKeySpec::Odd
+    }
+}
+
+// ============================================================================
+// SOAK TEST
+// ============================================================================
+
+#[test]
+fn soak_bonded_stream_with_churn() {
+    let duration = Duration::from_secs(
+        std::env::var("SRT_SOAK_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(DEFAULT_SOAK_SECS),
+    );
+
+    let mut rng = Rng(0x5EED_CAFE_F00D_D00D);
+
+    // Sender side
+    let group = Arc::new(SocketGroup::new(1, GroupType::Broadcast, MAX_PATHS));
+    let mut packetizer = Packetizer::new(SeqNumber::new(0), 0, PAYLOAD_SIZE);
+    let mut send_buffer = SendBuffer::new(SEND_BUFFER_CAPACITY, Duration::from_secs(24 * 3600));
+    let mut links: HashMap<u32, SimLink> = HashMap::new();
+    let mut next_conn_id = 1u32;
+    for _ in 0..2 {
+        let member_id = add_member(&group, next_conn_id);
+        links.insert(member_id, SimLink::new(&mut rng));
+        next_conn_id += 1;
+    }
+
+    // Feedback path (receiver -> sender), shares the outage schedule
+    let mut feedback_link: BTreeMap<(u64, u64), Feedback> = BTreeMap::new();
+    let mut feedback_count = 0u64;
+
+    // Receiver side
+    let receiver = BroadcastReceiver::new(RECEIVER_BUFFER_CAPACITY);
+    let mut loss_list = ReceiverLossList::new(u32::MAX, Duration::ZERO);
+    let mut highest_received: Option<SeqNumber> = None;
+
+    // Invariant tracking
+    let mut next_index = 0u64;
+    let mut delivered = 0u64;
+    let mut last_progress_tick = 0u64;
+    let mut retransmissions = 0u64;
+    let mut outage_until = 0u64;
+
+    let start = Instant::now();
+    let mut last_report = Instant::now();
+    let mut tick = 0u64;
+
+    while start.elapsed() < duration {
+        tick += 1;
+        let epoch = tick / KEY_ROTATION_TICKS;
+
+        // Periodic full outage on all paths
+        if tick % OUTAGE_INTERVAL_TICKS == 0 {
+            outage_until = tick + 20 + rng.below(80);
+        }
+        let outage = tick < outage_until;
+
+        // Member churn, always keeping at least one path
+        if tick % CHURN_INTERVAL_TICKS == 0 {
+            let ids: Vec<u32> = links.keys().copied().collect();
+            if ids.len() < MAX_PATHS && (ids.len() == 1 || rng.chance(500)) {
+                let member_id = add_member(&group, next_conn_id);
+                links.insert(member_id, SimLink::new(&mut rng));
+                next_conn_id += 1;
+            } else {
+                let victim = ids[rng.below(ids.len() as u64) as usize];
+                group.remove_member(victim).unwrap();
+                links.remove(&victim);
+            }
+        }
+
+        // --- Sender: new data ---
+        let active: Vec<u32> = group
+            .get_active_members()
+            .iter()
+            .map(|m| m.connection.local_socket_id())
+            .collect();
+
+        for _ in 0..PACKETS_PER_TICK {
+            if send_buffer.available_space() == 0 {
+                break;
+            }
+
+            let mut payload = vec![0u8; PAYLOAD_SIZE];
+            payload[..8].copy_from_slice(&next_index.to_be_bytes());
+            payload[8..16].copy_from_slice(&epoch.to_be_bytes());
+            next_index += 1;
+
+            for mut packet in packetizer.packetize_at(&payload, tick as u32) {
+                let mut msg = packet.msg_number();
+                msg.encryption_key = key_for_epoch(epoch);
+                packet.header.msg_or_info = msg.to_raw();
+
+                send_buffer.push(packet.clone()).unwrap();
+                let wire = packet.to_bytes().freeze();
+                for id in &active {
+                    if let Some(link) = links.get_mut(id) {
+                        link.send(&mut rng, tick, outage, wire.clone());
+                    }
+                }
+            }
+        }
+
+        // --- Sender: feedback ---
+        let later = feedback_link.split_off(&(tick + 1, 0));
+        for feedback in std::mem::replace(&mut feedback_link, later).into_values() {
+            match feedback {
+                Feedback::Ack(seq) => {
+                    send_buffer.acknowledge_up_to(seq);
+                    send_buffer.flush_acknowledged();
+                }
+                Feedback::Nak(ranges) => {
+                    for range in ranges {
+                        let mut seq = range.start;
+                        while seq.le(range.end) {
+                            if let Ok(packet) = send_buffer.get_for_send(seq) {
+                                retransmissions += 1;
+                                let wire = packet.to_bytes().freeze();
+                                for id in &active {
+                                    if let Some(link) = links.get_mut(id) {
+                                        link.send(&mut rng, tick, outage, wire.clone());
+                                    }
+                                }
+                            }
+                            seq = seq.next();
+                        }
+                    }
+                }
+            }
+        }
+
+        // --- Receiver ---
+        for (member_id, link) in links.iter_mut() {
+            for wire in link.deliver(tick) {
+                let packet = DataPacket::from_bytes(&wire).unwrap();
+                let seq = packet.seq_number();
+
+                match highest_received {
+                    Some(highest) if seq.gt(highest.next()) => {
+                        loss_list.add_range(LossRange::new(highest.next(), seq - 1));
+                        highest_received = Some(seq);
+                    }
+                    Some(highest) if seq.gt(highest) => highest_received = Some(seq),
+                    Some(_) => loss_list.remove(seq),
+                    None => {
+                        if seq.as_raw() > 0 {
+                            loss_list.add_range(LossRange::new(SeqNumber::new(0), seq - 1));
+                        }
+                        highest_received = Some(seq);
+                    }
+                }
+
+                // A packet dropped by a full receiver buffer must be recovered
+                if let Ok(false) = receiver.on_packet_received(packet, *member_id) {
+                    loss_list.add(seq);
+                }
+            }
+        }
+
+        while let Some(packet) = receiver.pop_ready_packet() {
+            let index = u64::from_be_bytes(packet.payload[..8].try_into().unwrap());
+            let packet_epoch = u64::from_be_bytes(packet.payload[8..16].try_into().unwrap());
+
+            assert_eq!(index, delivered, "out-of-order or duplicate delivery");
+            assert_eq!(
+                packet.msg_number().encryption_key,
+                key_for_epoch(packet_epoch),
+                "key parity mismatch for packet {}",
+                index
+            );
+
+            delivered += 1;
+            last_progress_tick = tick;
+        }
+
+        if tick % ACK_INTERVAL_TICKS == 0 && delivered > 0 {
+            let ack = receiver.stats().next_expected - 1;
+            feedback_count += 1;
+            if !outage {
+                feedback_link.insert((tick + 20, feedback_count), Feedback::Ack(ack));
+            }
+        }
+
+        if tick % NAK_INTERVAL_TICKS == 0 && !loss_list.is_empty() {
+            let ranges = loss_list.get_nak_ranges();
+            feedback_count += 1;
+            if !outage {
+                feedback_link.insert((tick + 20, feedback_count), Feedback::Nak(ranges));
+            }
+        }
+
+        // --- Invariants ---
+        let receiver_stats = receiver.stats();
+        assert!(send_buffer.len() <= SEND_BUFFER_CAPACITY);
+        assert!(receiver_stats.buffered_packets <= RECEIVER_BUFFER_CAPACITY);
+        assert!(receiver_stats.ready_packets == 0);
+        assert!(loss_list.len() <= SEND_BUFFER_CAPACITY);
+        assert!(feedback_link.len() <= 64);
+        assert!(group.member_count() <= MAX_PATHS);
+        for link in links.values() {
+            assert!(link.in_flight.len() <= SEND_BUFFER_CAPACITY * 2);
+        }
+        assert!(
+            tick - last_progress_tick <= STALL_LIMIT_TICKS,
+            "delivery stalled at {} (tick {})",
+            receiver_stats.next_expected,
+            tick
+        );
+
+        if last_report.elapsed() >= Duration::from_secs(60) {
+            println!(
+                "soak: {:?} elapsed, {} ticks, {} delivered, {} retransmitted, {} paths, \
+                 send_buffer={}, receiver_buffer={}, losses={}",
+                start.elapsed(),
+                tick,
+                delivered,
+                retransmissions,
+                links.len(),
+                send_buffer.len(),
+                receiver_stats.buffered_packets,
+                loss_list.len()
+            );
+            last_report = Instant::now();
+        }
+    }
+
+    assert!(delivered > 0);
+    println!(
+        "soak complete: {} ticks, {} packets delivered, {} retransmissions",
+        tick, delivered, retransmissions
+    );
+}
@@ -0,0 +1,71 @@
+//! Unsafe code policy
+//!
+//! Every crate denies `unsafe_code`; the only exception is the audited
+//! `srt-io` `sys` module, which wraps each unsafe operation in a safe
+//! function. This test keeps new exceptions from slipping in unreviewed.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Crates and their root source file
+const CRATES: &[(&str, &str)] = &[
+    ("srt-protocol", "src/lib.rs"),
+    ("srt-bonding", "src/lib.rs"),
+    ("srt-crypto", "src/lib.rs"),
+    ("srt-io", "src/lib.rs"),
+    ("srt", "src/lib.rs"),
+    ("srt-cli", "src/lib.rs"),
+];
+
+/// The one module allowed to use unsafe code
+const AUDITED: &str = "srt-io/src/sys.rs";
+
+fn workspace_root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .unwrap()
+        .to_path_buf()
+}
+
+fn rust_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            rust_files(&path, files);
+        } else if path.extension().is_some_and(|e| e == "rs") {
+            files.push(path);
+        }
+    }
+}
+
+#[test]
+fn test_crates_deny_unsafe_code() {
+    let root = workspace_root();
+    for (krate, lib) in CRATES {
+        let source = fs::read_to_string(root.join(krate).join(lib)).unwrap();
+        assert!(
+            source.contains("#![deny(unsafe_code)]"),
+            "{krate}/{lib} must deny unsafe_code"
+        );
+    }
+}
+
+#[test]
+fn test_unsafe_code_only_in_audited_module() {
+    let root = workspace_root();
+    for (krate, _) in CRATES {
+        let mut files = Vec::new();
+        rust_files(&root.join(krate).join("src"), &mut files);
+        for file in files {
+            let name = file.strip_prefix(&root).unwrap().to_string_lossy();
+            if name == AUDITED {
+                continue;
+            }
+            let source = fs::read_to_string(&file).unwrap();
+            assert!(
+                !source.contains("allow(unsafe_code)") && !source.contains("unsafe {"),
+                "{name} uses unsafe code; move it into {AUDITED}"
+            );
+        }
+    }
+}
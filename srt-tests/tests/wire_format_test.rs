@@ -0,0 +1,210 @@
+//! Wire format test vectors
+//!
+//! Serialized packets are compared byte-for-byte against fixed vectors, so
+//! any dependence on host byte order or pointer width shows up as a
+//! mismatch. CI runs this file on 32-bit and big-endian targets under QEMU
+//! (see `scripts/cross-test.sh`).
+
+use bytes::Bytes;
+use srt_protocol::handshake::{SrtHandshakeExtension, UdtHandshake, SRT_VERSION};
+use srt_protocol::packet::ControlType;
+use srt_protocol::{
+    AckInfo, ControlPacket, DataPacket, LossRange, MsgNumber, NakInfo, Packet, SeqNumber,
+    SrtHandshake, SrtOptions,
+};
+
+#[test]
+fn test_data_packet_vector() {
+    let mut msg = MsgNumber::new(5);
+    msg.retransmitted = true;
+    let packet = DataPacket::new(
+        SeqNumber::new(0x0102_0304),
+        msg,
+        0x0A0B_0C0D,
+        0x1122_3344,
+        Bytes::from_static(&[0xAA, 0xBB]),
+    );
+
+    let expected = [
+        0x01, 0x02, 0x03, 0x04, // sequence number, data flag clear
+        0xC4, 0x00, 0x00, 0x05, // solo boundary, retransmitted, message 5
+        0x0A, 0x0B, 0x0C, 0x0D, // timestamp
+        0x11, 0x22, 0x33, 0x44, // destination socket ID
+        0xAA, 0xBB,
+    ];
+    assert_eq!(&packet.to_bytes()[..], &expected);
+
+    let Packet::Data(decoded) = Packet::from_bytes(&expected).unwrap() else {
+        panic!("expected a data packet");
+    };
+    assert_eq!(decoded, packet);
+    assert!(decoded.msg_number().retransmitted);
+}
+
+#[test]
+fn test_control_packet_vector() {
+    let packet = ControlPacket::new(
+        ControlType::Ack,
+        0x0102,
+        0x0304_0506,
+        0x0708_090A,
+        0x0B0C_0D0E,
+        Bytes::from_static(&[0xDE, 0xAD, 0xBE, 0xEF]),
+    );
+
+    let expected = [
+        0x80, 0x02, 0x01, 0x02, // control flag, ACK, type-specific info
+        0x03, 0x04, 0x05, 0x06, // additional info
+        0x07, 0x08, 0x09, 0x0A, // timestamp
+        0x0B, 0x0C, 0x0D, 0x0E, // destination socket ID
+        0xDE, 0xAD, 0xBE, 0xEF,
+    ];
+    assert_eq!(&packet.to_bytes()[..], &expected);
+
+    let Packet::Control(decoded) = Packet::from_bytes(&expected).unwrap() else {
+        panic!("expected a control packet");
+    };
+    assert_eq!(decoded.control_type(), ControlType::Ack);
+    assert_eq!(decoded.header.type_specific_info(), Some(0x0102));
+    assert_eq!(decoded.header.additional_info(), Some(0x0304_0506));
+}
+
+#[test]
+fn test_ack_vector() {
+    let ack = AckInfo {
+        ack_seq: SeqNumber::new(0x0102_0304),
+        rtt_us: 0x0506_0708,
+        rtt_var_us: 9,
+        buffer_available: 8192,
+        packet_arrival_rate: 0,
+        estimated_link_capacity: 0,
+        receive_rate_bps: 0x7FFF_FFFF,
+    };
+
+    let bytes = ack.to_bytes();
+    assert_eq!(
+        &bytes[..8],
+        &[0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08]
+    );
+    assert_eq!(&bytes[8..16], &[0, 0, 0, 9, 0, 0, 0x20, 0]);
+    assert_eq!(&bytes[bytes.len() - 4..], &[0x7F, 0xFF, 0xFF, 0xFF]);
+
+    let decoded = AckInfo::from_bytes(&bytes).unwrap();
+    assert_eq!(decoded.ack_seq, ack.ack_seq);
+    assert_eq!(decoded.rtt_us, ack.rtt_us);
+    assert_eq!(decoded.receive_rate_bps, ack.receive_rate_bps);
+}
+
+#[test]
+fn test_nak_vector() {
+    let nak = NakInfo::new(vec![
+        LossRange::single(SeqNumber::new(5)),
+        LossRange::new(SeqNumber::new(0x10), SeqNumber::new(0x20)),
+        LossRange::single(SeqNumber::new(0x7FFF_FFFF)),
+    ]);
+
+    let expected = [
+        0x00, 0x00, 0x00, 0x05, // single loss
+        0x80, 0x00, 0x00, 0x10, // range start, range flag set
+        0x00, 0x00, 0x00, 0x20, // range end
+        0x7F, 0xFF, 0xFF, 0xFF, // highest sequence number, flag clear
+    ];
+    assert_eq!(&nak.to_bytes()[..], &expected);
+    assert_eq!(
+        NakInfo::from_bytes(&expected).unwrap().loss_ranges,
+        nak.loss_ranges
+    );
+}
+
+#[test]
+fn test_udt_handshake_vector() {
+    let mut hs = UdtHandshake::new_request(
+        0x0102_0304,
+        1500,
+        8192,
+        0x0A0B_0C0D,
+        "192.168.1.2:9000".parse().unwrap(),
+    );
+    hs.handshake_type = -2;
+    hs.syn_cookie = 0xCAFE_BABE;
+
+    let expected = [
+        0x00, 0x00, 0x00, 0x04, // version
+        0x00, 0x00, 0x00, 0x01, // socket type
+        0x01, 0x02, 0x03, 0x04, // initial sequence number
+        0x00, 0x00, 0x05, 0xDC, // MSS 1500
+        0x00, 0x00, 0x20, 0x00, // flow window 8192
+        0xFF, 0xFF, 0xFF, 0xFE, // handshake type -2
+        0x0A, 0x0B, 0x0C, 0x0D, // socket ID
+        0xCA, 0xFE, 0xBA, 0xBE, // SYN cookie
+        192, 168, 1, 2, // peer IPv4 address
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    ];
+    assert_eq!(&hs.to_bytes()[..], &expected);
+
+    let decoded = UdtHandshake::from_bytes(&expected).unwrap();
+    assert_eq!(decoded.handshake_type, -2);
+    assert_eq!(decoded.syn_cookie, 0xCAFE_BABE);
+    assert_eq!(decoded.peer_addr, "192.168.1.2:0".parse().unwrap());
+
+    // IPv6 addresses are sent as their 16 octets
+    let hs = UdtHandshake::new_request(0, 1500, 8192, 1, "[2001:db8::1]:9000".parse().unwrap());
+    let bytes = hs.to_bytes();
+    assert_eq!(&bytes[32..36], &[0x20, 0x01, 0x0D, 0xB8]);
+    assert_eq!(bytes[47], 1);
+    assert_eq!(
+        UdtHandshake::from_bytes(&bytes).unwrap().peer_addr,
+        "[2001:db8::1]:0".parse().unwrap()
+    );
+}
+
+#[test]
+fn test_srt_extension_vector() {
+    let options = SrtOptions {
+        tsbpd_sender: true,
+        tsbpd_receiver: true,
+        encryption: false,
+        too_late_packet_drop: true,
+        nak_report: true,
+        rexmit_flag: true,
+        stream_mode: false,
+        packet_filter: false,
+    };
+    let ext = SrtHandshakeExtension::new(options, 0x0102, 0x0304);
+
+    let version = SRT_VERSION.to_be_bytes();
+    let expected = [
+        0x00, 0x01, 0x00, 0x03, // HSREQ, 3 words
+        version[0], version[1], version[2], version[3], 0x00, 0x00, 0x00, 0x3B, // flags
+        0x01, 0x02, 0x03, 0x04, // receiver latency, sender latency
+    ];
+    assert_eq!(&ext.to_bytes()[..], &expected);
+
+    let decoded = SrtHandshakeExtension::from_bytes(&expected).unwrap();
+    assert_eq!(decoded.recv_latency_ms(), 0x0102);
+    assert_eq!(decoded.send_latency_ms(), 0x0304);
+}
+
+#[test]
+fn test_string_extension_vector() {
+    let mut hs = SrtHandshake::new_request(
+        0,
+        1,
+        "127.0.0.1:9000".parse().unwrap(),
+        SrtOptions::default_capabilities(),
+        120,
+        120,
+    );
+    hs.set_stream_id(Some("abcde".to_string())).unwrap();
+
+    // Each 32-bit word is byte-reversed, matching the reference
+    // implementation regardless of host byte order
+    let bytes = hs.to_bytes();
+    let sid = [
+        0x00, 0x05, 0x00, 0x02, b'd', b'c', b'b', b'a', 0, 0, 0, b'e',
+    ];
+    assert!(bytes.windows(sid.len()).any(|w| w == sid));
+
+    let decoded = SrtHandshake::from_bytes(&bytes).unwrap();
+    assert_eq!(decoded.stream_id.as_deref(), Some("abcde"));
+}
@@ -1,7 +1,28 @@
 //! SRT - Secure Reliable Transport
 //!
 //! High-level Rust API for SRT protocol with multi-path bonding support.
+//!
+//! Start from [`prelude`], which holds the supported public API. The
+//! re-exported crates below give access to lower-level items that carry no
+//! stability guarantee before 1.0.
+//!
+//! Bonding is behind the default `bonding` feature. With
+//! `default-features = false` the crate is just the protocol, sockets and
+//! crypto, for embedded single-path use.
+//!
+//! The `tokio` feature adds async sockets and a task-based connection
+//! driver in `io::async_socket`.
+//!
+//! [`listener::SrtListener`] accepts many callers on one UDP port.
+//! [`stream::SrtSender`] and [`stream::SrtReceiver`] send and receive a
+//! stream without wiring up the protocol pieces.
+#![deny(unsafe_code)]
+
+pub mod listener;
+pub mod prelude;
+pub mod stream;
 
+#[cfg(feature = "bonding")]
 pub use srt_bonding as bonding;
 pub use srt_crypto as crypto;
 pub use srt_io as io;
@@ -0,0 +1,1226 @@
+//! Listener accepting many callers on one UDP port
+//!
+//! An [`SrtListener`] owns the socket every caller sends to. Once a caller
+//! has its handshake agreement it addresses packets to the socket ID the
+//! listener gave it, so packets are demultiplexed by destination socket ID.
+//! Handshakes carry no destination yet and are matched on the caller's
+//! address and socket ID instead.
+//!
+//! Data can race the handshake: a caller may send its first packets before
+//! its handshake has been accepted. With early data enabled
+//! ([`SrtListener::set_early_data_limit`]) those packets are held per
+//! source for a short while, and `recv` hands them out as packets of the
+//! connection once the caller is accepted.
+//!
+//! A listener may own several sockets, one per local address, so a
+//! multi-homed receiver can listen on each of its links
+//! ([`SrtListener::bind_all`]). Each connection belongs to the socket its
+//! handshake arrived on: its packets are only accepted there, and replies
+//! leave from there.
+//!
+//! The listener does not read the socket on its own. Pass each datagram to
+//! [`SrtListener::handle`], or use [`SrtListener::recv`], so applications
+//! keep control of batching and scheduling:
+//!
+//! ```no_run
+//! use srt::listener::{ListenerEvent, SrtListener};
+//! use srt::prelude::*;
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let mut listener = SrtListener::bind("0.0.0.0:9000".parse()?)?;
+//! let mut buf = vec![0u8; 1500];
+//! loop {
+//!     let Some((n, event)) = listener.recv(&mut buf)? else {
+//!         continue;
+//!     };
+//!     match event {
+//!         ListenerEvent::Handshake(request) => {
+//!             let conn = Connection::new(
+//!                 request.socket_id(),
+//!                 listener.local_addr()?,
+//!                 request.peer(),
+//!                 SeqNumber::new(0),
+//!                 120,
+//!             );
+//!             listener.accept(request, conn)?;
+//!         }
+//!         ListenerEvent::Packet { socket_id, .. } => {
+//!             let packet = Packet::from_bytes(&buf[..n])?;
+//!             // ... hand the packet to connection `socket_id`
+//!         }
+//!         _ => {}
+//!     }
+//! }
+//! # }
+//! ```
+
+use bytes::Bytes;
+use srt_io::{EpollNotifier, SocketError, SrtSocket};
+use srt_protocol::packet::ControlType;
+use srt_protocol::{
+    Connection, ConnectionError, ControlPacket, RejectReason, SrtHandshake, SrtOptions,
+};
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+/// SRT header size; the destination socket ID is its last word
+const HEADER_SIZE: usize = 16;
+
+/// Default time early data is held for its handshake
+pub const DEFAULT_EARLY_DATA_TTL: Duration = Duration::from_secs(1);
+
+/// Most sources whose early data is held at once
+pub const MAX_EARLY_DATA_SOURCES: usize = 16;
+
+/// Listener errors
+#[derive(Error, Debug)]
+pub enum ListenerError {
+    #[error("Socket error: {0}")]
+    Socket(#[from] SocketError),
+
+    #[error("Connection error: {0}")]
+    Connection(#[from] ConnectionError),
+
+    #[error("Connection has socket ID {actual}, but the handshake was for {expected}")]
+    SocketIdMismatch { expected: u32, actual: u32 },
+
+    #[error("No accepted connection with socket ID {0}")]
+    UnknownSocket(u32),
+
+    #[error("No address to listen on")]
+    NoAddress,
+
+    #[error("Caller refused by the accept hook: {0}")]
+    Refused(RejectReason),
+}
+
+/// Access control for callers, like libsrt's `srt_listen_callback`
+///
+/// Sees the caller's address, Stream ID and requested options before a
+/// connection is accepted for it. Closures taking a [`HandshakeRequest`]
+/// implement it.
+pub trait ListenerCallback: Send + Sync {
+    /// Return `Err(reason)` to refuse the caller
+    fn on_handshake(&self, request: &HandshakeRequest) -> Result<(), RejectReason>;
+}
+
+impl<F> ListenerCallback for F
+where
+    F: Fn(&HandshakeRequest) -> Result<(), RejectReason> + Send + Sync,
+{
+    fn on_handshake(&self, request: &HandshakeRequest) -> Result<(), RejectReason> {
+        self(request)
+    }
+}
+
+/// Accept hook: the callback `accept` consults
+pub type AcceptHook = Arc<dyn ListenerCallback>;
+
+/// A caller's first handshake, waiting to be accepted or rejected
+#[derive(Debug, Clone)]
+pub struct HandshakeRequest {
+    socket_id: u32,
+    peer: SocketAddr,
+    local: usize,
+    // Boxed to keep `ListenerEvent` small
+    handshake: Box<SrtHandshake>,
+}
+
+impl HandshakeRequest {
+    /// Socket ID the accepted connection must use
+    pub fn socket_id(&self) -> u32 {
+        self.socket_id
+    }
+
+    /// Index of the listening socket the handshake arrived on
+    pub fn local_index(&self) -> usize {
+        self.local
+    }
+
+    /// Address of the caller
+    pub fn peer(&self) -> SocketAddr {
+        self.peer
+    }
+
+    /// The caller's handshake
+    pub fn handshake(&self) -> &SrtHandshake {
+        &self.handshake
+    }
+
+    /// Stream ID the caller asked for
+    pub fn stream_id(&self) -> Option<&str> {
+        self.handshake.stream_id.as_deref()
+    }
+
+    /// Options the caller asked for, if it sent an SRT extension
+    pub fn options(&self) -> Option<SrtOptions> {
+        self.handshake.peer_capabilities()
+    }
+}
+
+/// What a received datagram was
+#[derive(Debug)]
+pub enum ListenerEvent {
+    /// A new caller; answer with [`SrtListener::accept`] or [`SrtListener::reject`]
+    Handshake(HandshakeRequest),
+
+    /// A packet for an accepted connection
+    Packet { socket_id: u32, peer: SocketAddr },
+
+    /// A repeated handshake from an accepted caller, answered again
+    Repeated { socket_id: u32 },
+
+    /// A data packet from a peer without an accepted connection, held
+    /// until its handshake is accepted
+    Early { peer: SocketAddr },
+
+    /// A packet from a peer without an accepted connection
+    Unknown { peer: SocketAddr },
+}
+
+/// Early data counters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ListenerStats {
+    /// Data packets held for a handshake not yet accepted
+    pub early_held: u64,
+    /// Held packets handed out once their caller was accepted
+    pub early_delivered: u64,
+    /// Held packets dropped because no handshake was accepted in time
+    pub early_expired: u64,
+    /// Packets not held because the source or listener limit was reached
+    pub early_dropped: u64,
+}
+
+/// An accepted connection and how to reach its caller
+struct Accepted {
+    peer: SocketAddr,
+    /// Index of the listening socket the caller reaches us on
+    local: usize,
+    caller_socket_id: u32,
+    connection: Arc<Connection>,
+    /// Agreement packet, sent again if the caller repeats its handshake
+    agreement: Bytes,
+}
+
+/// Accepts callers on one or more UDP sockets and routes their packets
+pub struct SrtListener {
+    sockets: Vec<SrtSocket>,
+    /// Socket `recv` reads first, rotated so no socket starves the others
+    next_read: usize,
+    connections: HashMap<u32, Accepted>,
+    /// Caller address and socket ID to our socket ID
+    callers: HashMap<(SocketAddr, u32), u32>,
+    next_socket_id: u32,
+    /// Data packets held per source (0 = early data off)
+    early_limit: usize,
+    early_ttl: Duration,
+    /// Early data by listening socket and source, with arrival times
+    early: HashMap<(usize, SocketAddr), VecDeque<(Instant, Bytes)>>,
+    /// Early data of accepted callers, waiting to be handed out
+    early_ready: VecDeque<(u32, SocketAddr, Bytes)>,
+    /// Hook consulted before accepting a caller
+    accept_hook: Option<AcceptHook>,
+    /// Woken when a connection is accepted or closed
+    notifier: Option<EpollNotifier>,
+    stats: ListenerStats,
+}
+
+impl SrtListener {
+    /// Listen on a socket bound to `addr`
+    pub fn bind(addr: SocketAddr) -> Result<Self, ListenerError> {
+        Ok(Self::new(SrtSocket::bind(addr)?))
+    }
+
+    /// Listen on one socket per address, in order
+    ///
+    /// For receivers with several links: each caller is answered from the
+    /// address it sent its handshake to.
+    pub fn bind_all(addrs: &[SocketAddr]) -> Result<Self, ListenerError> {
+        let (first, rest) = addrs.split_first().ok_or(ListenerError::NoAddress)?;
+        let mut listener = Self::bind(*first)?;
+        for addr in rest {
+            listener.add_socket(SrtSocket::bind(*addr)?);
+        }
+        Ok(listener)
+    }
+
+    /// Listen on an already bound socket
+    pub fn new(socket: SrtSocket) -> Self {
+        SrtListener {
+            sockets: vec![socket],
+            next_read: 0,
+            connections: HashMap::new(),
+            callers: HashMap::new(),
+            next_socket_id: 1,
+            early_limit: 0,
+            early_ttl: DEFAULT_EARLY_DATA_TTL,
+            early: HashMap::new(),
+            early_ready: VecDeque::new(),
+            accept_hook: None,
+            notifier: None,
+            stats: ListenerStats::default(),
+        }
+    }
+
+    /// Wake an epoll when a connection is accepted or closed
+    pub fn set_notifier(&mut self, notifier: Option<EpollNotifier>) {
+        self.notifier = notifier;
+    }
+
+    fn notify(&self) {
+        if let Some(notifier) = &self.notifier {
+            notifier.notify();
+        }
+    }
+
+    /// Set the hook `accept` consults before accepting a caller
+    ///
+    /// Lets a server route or refuse callers by Stream ID (e.g. libsrt's
+    /// `#!::r=live/cam1,m=publish`). A refusal is sent to the caller with
+    /// the hook's reason; libsrt's access control codes such as 1403
+    /// (forbidden) go as `RejectReason::Other`.
+    pub fn set_accept_hook<F>(&mut self, hook: F)
+    where
+        F: Fn(&HandshakeRequest) -> Result<(), RejectReason> + Send + Sync + 'static,
+    {
+        self.set_listener_callback(hook);
+    }
+
+    /// Set a [`ListenerCallback`] as the accept hook
+    ///
+    /// For access control that keeps state, such as an allow list.
+    pub fn set_listener_callback<C>(&mut self, callback: C)
+    where
+        C: ListenerCallback + 'static,
+    {
+        self.accept_hook = Some(Arc::new(callback));
+    }
+
+    /// Remove the accept hook, accepting every caller
+    pub fn clear_accept_hook(&mut self) {
+        self.accept_hook = None;
+    }
+
+    /// Check a caller against the accept hook
+    ///
+    /// Refused callers are sent the reason.
+    pub fn check_accept(&self, request: &HandshakeRequest) -> Result<(), ListenerError> {
+        let Some(hook) = &self.accept_hook else {
+            return Ok(());
+        };
+        hook.on_handshake(request).map_err(|reason| {
+            tracing::debug!(
+                "Refused caller {} (stream ID {:?}): {}",
+                request.peer,
+                request.stream_id(),
+                reason
+            );
+            self.reject(request, reason);
+            ListenerError::Refused(reason)
+        })
+    }
+
+    /// Hold up to `packets` data packets per source that arrive before
+    /// the source's handshake is accepted (0 = drop them, the default)
+    pub fn set_early_data_limit(&mut self, packets: usize) {
+        self.early_limit = packets;
+        if packets == 0 {
+            self.early.clear();
+        }
+    }
+
+    /// Get the number of early data packets held per source
+    pub fn early_data_limit(&self) -> usize {
+        self.early_limit
+    }
+
+    /// Change how long early data waits for its handshake
+    pub fn set_early_data_ttl(&mut self, ttl: Duration) {
+        self.early_ttl = ttl;
+    }
+
+    /// Get how long early data waits for its handshake
+    pub fn early_data_ttl(&self) -> Duration {
+        self.early_ttl
+    }
+
+    /// Get the early data counters
+    pub fn stats(&self) -> ListenerStats {
+        self.stats
+    }
+
+    /// Also listen on an already bound socket, returning its index
+    pub fn add_socket(&mut self, socket: SrtSocket) -> usize {
+        self.sockets.push(socket);
+        self.sockets.len() - 1
+    }
+
+    /// Get the first listening socket, e.g. to read batches from it
+    pub fn socket(&self) -> &SrtSocket {
+        &self.sockets[0]
+    }
+
+    /// Get the first listening socket mutably, e.g. to install a packet hook
+    pub fn socket_mut(&mut self) -> &mut SrtSocket {
+        &mut self.sockets[0]
+    }
+
+    /// Get the listening sockets, by index
+    pub fn sockets(&self) -> &[SrtSocket] {
+        &self.sockets
+    }
+
+    /// Get the listening sockets mutably
+    pub fn sockets_mut(&mut self) -> &mut [SrtSocket] {
+        &mut self.sockets
+    }
+
+    /// Get the local address of the first socket
+    pub fn local_addr(&self) -> Result<SocketAddr, ListenerError> {
+        self.local_addr_at(0)
+    }
+
+    /// Get the local address of socket `local`
+    ///
+    /// Create a connection accepted from a [`HandshakeRequest`] with the
+    /// address of the socket the request arrived on.
+    pub fn local_addr_at(&self, local: usize) -> Result<SocketAddr, ListenerError> {
+        let socket = self.sockets.get(local).ok_or(ListenerError::NoAddress)?;
+        Ok(socket.local_addr()?)
+    }
+
+    /// Get the index of the socket an accepted connection belongs to
+    pub fn local_index(&self, socket_id: u32) -> Option<usize> {
+        self.connections
+            .get(&socket_id)
+            .map(|accepted| accepted.local)
+    }
+
+    /// Receive one datagram into `buf` from any socket and classify it
+    ///
+    /// Returns `None` when no datagram is waiting. The datagram is the first
+    /// `n` bytes of `buf`.
+    pub fn recv(
+        &mut self,
+        buf: &mut [u8],
+    ) -> Result<Option<(usize, ListenerEvent)>, ListenerError> {
+        if let Some(early) = self.next_early(buf) {
+            return Ok(Some(early));
+        }
+        for _ in 0..self.sockets.len() {
+            let local = self.next_read;
+            self.next_read = (local + 1) % self.sockets.len();
+            let (n, from) = match self.sockets[local].recv_from(buf) {
+                Ok(received) => received,
+                Err(SocketError::Io(e)) if e.kind() == io::ErrorKind::WouldBlock => continue,
+                Err(e) => return Err(e.into()),
+            };
+            return Ok(Some((n, self.handle_on(local, &buf[..n], from))));
+        }
+        Ok(None)
+    }
+
+    /// Classify a datagram received from `from` on the first socket
+    ///
+    /// Repeated handshakes from accepted callers are answered here; all
+    /// other packets are left to the caller of this method.
+    pub fn handle(&mut self, datagram: &[u8], from: SocketAddr) -> ListenerEvent {
+        self.handle_on(0, datagram, from)
+    }
+
+    /// Classify a datagram received from `from` on socket `local`
+    ///
+    /// Packets for a connection that belongs to another socket are unknown.
+    pub fn handle_on(&mut self, local: usize, datagram: &[u8], from: SocketAddr) -> ListenerEvent {
+        if datagram.len() < HEADER_SIZE || local >= self.sockets.len() {
+            return ListenerEvent::Unknown { peer: from };
+        }
+
+        if let Some(handshake) = SrtHandshake::from_datagram(datagram) {
+            let caller = (from, handshake.udt.socket_id);
+            if let Some(accepted) = self
+                .callers
+                .get(&caller)
+                .and_then(|id| self.connections.get(id))
+                .filter(|accepted| accepted.local == local)
+            {
+                let socket_id = accepted.connection.local_socket_id();
+                if let Err(e) = self.sockets[local].send_to(&accepted.agreement, from) {
+                    tracing::debug!("Failed to repeat handshake agreement to {}: {}", from, e);
+                }
+                return ListenerEvent::Repeated { socket_id };
+            }
+            return ListenerEvent::Handshake(HandshakeRequest {
+                socket_id: self.next_socket_id,
+                peer: from,
+                local,
+                handshake: Box::new(handshake),
+            });
+        }
+
+        match self.route(local, datagram, from) {
+            Some(socket_id) => ListenerEvent::Packet {
+                socket_id,
+                peer: from,
+            },
+            None if self.hold_early(local, datagram, from, Instant::now()) => {
+                ListenerEvent::Early { peer: from }
+            }
+            None => ListenerEvent::Unknown { peer: from },
+        }
+    }
+
+    /// Copy the next early data packet of an accepted caller into `buf`
+    ///
+    /// `recv` hands these out before reading the sockets; call this when
+    /// reading the sockets yourself and classifying with `handle_on`.
+    pub fn next_early(&mut self, buf: &mut [u8]) -> Option<(usize, ListenerEvent)> {
+        while let Some((socket_id, peer, datagram)) = self.early_ready.pop_front() {
+            if !self.connections.contains_key(&socket_id) {
+                continue;
+            }
+            let n = datagram.len().min(buf.len());
+            buf[..n].copy_from_slice(&datagram[..n]);
+            self.stats.early_delivered += 1;
+            return Some((n, ListenerEvent::Packet { socket_id, peer }));
+        }
+        None
+    }
+
+    /// Hold a data packet from a source without an accepted connection
+    fn hold_early(
+        &mut self,
+        local: usize,
+        datagram: &[u8],
+        from: SocketAddr,
+        now: Instant,
+    ) -> bool {
+        // Control packets are left to be repeated by the caller
+        if self.early_limit == 0 || datagram[0] & 0x80 != 0 {
+            return false;
+        }
+        self.expire_early(now);
+        let sources = self.early.len();
+        let held = match self.early.get_mut(&(local, from)) {
+            Some(held) => held,
+            None if sources < MAX_EARLY_DATA_SOURCES => {
+                self.early.entry((local, from)).or_default()
+            }
+            None => {
+                self.stats.early_dropped += 1;
+                return false;
+            }
+        };
+        // Keep the first packets: they are the ones a late start loses
+        if held.len() >= self.early_limit {
+            self.stats.early_dropped += 1;
+            return false;
+        }
+        held.push_back((now, Bytes::copy_from_slice(datagram)));
+        self.stats.early_held += 1;
+        true
+    }
+
+    /// Drop early data held longer than the TTL
+    fn expire_early(&mut self, now: Instant) {
+        let ttl = self.early_ttl;
+        let mut expired = 0;
+        self.early.retain(|_, held| {
+            while held
+                .front()
+                .is_some_and(|(at, _)| now.saturating_duration_since(*at) > ttl)
+            {
+                held.pop_front();
+                expired += 1;
+            }
+            !held.is_empty()
+        });
+        self.stats.early_expired += expired;
+    }
+
+    /// Find the connection a non-handshake packet is for
+    ///
+    /// Packets sent before the caller knew our socket ID have destination 0;
+    /// those go to the newest connection from their address.
+    fn route(&self, local: usize, datagram: &[u8], from: SocketAddr) -> Option<u32> {
+        let dest = u32::from_be_bytes(datagram[12..HEADER_SIZE].try_into().ok()?);
+        let reaches = |accepted: &Accepted| accepted.peer == from && accepted.local == local;
+        if dest != 0 {
+            return self
+                .connections
+                .get(&dest)
+                .filter(|accepted| reaches(accepted))
+                .map(|_| dest);
+        }
+        self.connections
+            .iter()
+            .filter(|(_, accepted)| reaches(accepted))
+            .map(|(id, _)| *id)
+            .max()
+    }
+
+    /// Complete a caller's handshake with a configured connection
+    ///
+    /// `connection` must have been created with the request's socket ID.
+    /// The handshake agreement is sent on success; if the accept hook or
+    /// the connection refuses the handshake, the caller is sent the reason.
+    pub fn accept(
+        &mut self,
+        request: HandshakeRequest,
+        mut connection: Connection,
+    ) -> Result<Arc<Connection>, ListenerError> {
+        let socket_id = connection.local_socket_id();
+        if socket_id != request.socket_id {
+            return Err(ListenerError::SocketIdMismatch {
+                expected: request.socket_id,
+                actual: socket_id,
+            });
+        }
+        self.check_accept(&request)?;
+
+        if let Err(e) = connection.process_handshake((*request.handshake).clone()) {
+            if let Some(reason) = e.reject_reason() {
+                self.reject(&request, reason);
+            }
+            return Err(e.into());
+        }
+
+        let agreement = agreement_packet(&connection, *request.handshake);
+        if let Err(e) = self.sockets[request.local].send_to(&agreement, request.peer) {
+            tracing::debug!(
+                "Failed to send handshake agreement to {}: {}",
+                request.peer,
+                e
+            );
+        }
+
+        let connection = Arc::new(connection);
+        let caller_socket_id = connection.remote_socket_id().unwrap_or(0);
+        self.callers
+            .insert((request.peer, caller_socket_id), socket_id);
+        self.connections.insert(
+            socket_id,
+            Accepted {
+                peer: request.peer,
+                local: request.local,
+                caller_socket_id,
+                connection: Arc::clone(&connection),
+                agreement,
+            },
+        );
+        self.next_socket_id = self.next_socket_id.max(socket_id.wrapping_add(1)).max(1);
+
+        self.expire_early(Instant::now());
+        if let Some(held) = self.early.remove(&(request.local, request.peer)) {
+            tracing::debug!(
+                "Delivering {} early packets from {}",
+                held.len(),
+                request.peer
+            );
+            self.early_ready.extend(
+                held.into_iter()
+                    .map(|(_, datagram)| (socket_id, request.peer, datagram)),
+            );
+        }
+        self.notify();
+        Ok(connection)
+    }
+
+    /// Refuse a caller's handshake
+    ///
+    /// Best effort: the caller otherwise keeps retrying until it times out.
+    pub fn reject(&self, request: &HandshakeRequest, reason: RejectReason) {
+        let mut refusal = (*request.handshake).clone();
+        refusal.reject(reason);
+        let packet = ControlPacket::new(
+            ControlType::Handshake,
+            0,
+            0,
+            0,
+            request.handshake.udt.socket_id,
+            Bytes::copy_from_slice(&refusal.to_bytes()),
+        );
+        let Some(socket) = self.sockets.get(request.local) else {
+            return;
+        };
+        if let Err(e) = socket.send_to(&packet.to_bytes(), request.peer) {
+            tracing::debug!(
+                "Failed to send handshake rejection to {}: {}",
+                request.peer,
+                e
+            );
+        }
+    }
+
+    /// Send a datagram to the caller of an accepted connection
+    pub fn send_to(&self, socket_id: u32, datagram: &[u8]) -> Result<usize, ListenerError> {
+        let accepted = self
+            .connections
+            .get(&socket_id)
+            .ok_or(ListenerError::UnknownSocket(socket_id))?;
+        Ok(self.sockets[accepted.local].send_to(datagram, accepted.peer)?)
+    }
+
+    /// Get an accepted connection
+    pub fn connection(&self, socket_id: u32) -> Option<&Arc<Connection>> {
+        self.connections
+            .get(&socket_id)
+            .map(|accepted| &accepted.connection)
+    }
+
+    /// Get the caller address of an accepted connection
+    pub fn peer(&self, socket_id: u32) -> Option<SocketAddr> {
+        self.connections
+            .get(&socket_id)
+            .map(|accepted| accepted.peer)
+    }
+
+    /// Iterate over accepted connections as (socket ID, caller address)
+    pub fn peers(&self) -> impl Iterator<Item = (u32, SocketAddr)> + '_ {
+        self.connections
+            .iter()
+            .map(|(id, accepted)| (*id, accepted.peer))
+    }
+
+    /// Get the number of accepted connections
+    pub fn len(&self) -> usize {
+        self.connections.len()
+    }
+
+    /// Check if no connection has been accepted (or all were removed)
+    pub fn is_empty(&self) -> bool {
+        self.connections.is_empty()
+    }
+
+    /// Forget a connection without telling its caller
+    ///
+    /// Later packets from the caller are reported as unknown, and a new
+    /// handshake from it is a new request.
+    pub fn remove(&mut self, socket_id: u32) -> Option<Arc<Connection>> {
+        let accepted = self.connections.remove(&socket_id)?;
+        self.callers
+            .remove(&(accepted.peer, accepted.caller_socket_id));
+        Some(accepted.connection)
+    }
+
+    /// Close a connection, sending its caller a shutdown
+    pub fn close(&mut self, socket_id: u32) -> Option<Arc<Connection>> {
+        let caller_socket_id = self.connections.get(&socket_id)?.caller_socket_id;
+        let shutdown = ControlPacket::new(
+            ControlType::Shutdown,
+            0,
+            0,
+            0,
+            caller_socket_id,
+            Bytes::new(),
+        );
+        if let Err(e) = self.send_to(socket_id, &shutdown.to_bytes()) {
+            tracing::debug!("Failed to send shutdown for socket {}: {}", socket_id, e);
+        }
+        let connection = self.remove(socket_id)?;
+        connection.close();
+        self.notify();
+        Some(connection)
+    }
+}
+
+/// Build the agreement answering `handshake` for an accepted connection
+fn agreement_packet(connection: &Connection, handshake: SrtHandshake) -> Bytes {
+    let agreement = connection.create_agreement(handshake);
+    ControlPacket::new(
+        ControlType::Handshake,
+        0,
+        0,
+        0,
+        0,
+        agreement.to_bytes().freeze(),
+    )
+    .to_bytes()
+    .freeze()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use srt_protocol::handshake::HandshakeType;
+    use srt_protocol::SeqNumber;
+    use std::net::UdpSocket;
+    use std::time::Duration;
+
+    fn listener() -> SrtListener {
+        SrtListener::bind("127.0.0.1:0".parse().unwrap()).unwrap()
+    }
+
+    fn caller() -> UdpSocket {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        socket
+    }
+
+    fn handshake_datagram(socket_id: u32, listener: &SrtListener) -> Vec<u8> {
+        stream_handshake_datagram(socket_id, listener, None)
+    }
+
+    fn stream_handshake_datagram(
+        socket_id: u32,
+        listener: &SrtListener,
+        stream_id: Option<&str>,
+    ) -> Vec<u8> {
+        let mut conn = Connection::new(
+            socket_id,
+            "127.0.0.1:1".parse().unwrap(),
+            listener.local_addr().unwrap(),
+            SeqNumber::new(0),
+            120,
+        );
+        conn.set_stream_id(stream_id.map(str::to_string)).unwrap();
+        ControlPacket::new(
+            ControlType::Handshake,
+            0,
+            0,
+            0,
+            0,
+            conn.create_handshake().to_bytes().freeze(),
+        )
+        .to_bytes()
+        .to_vec()
+    }
+
+    /// A header-only data packet for socket `dest`
+    fn data_datagram(dest: u32) -> Vec<u8> {
+        let mut datagram = vec![0u8; HEADER_SIZE];
+        datagram[12..].copy_from_slice(&dest.to_be_bytes());
+        datagram
+    }
+
+    fn receive_handshake(socket: &UdpSocket) -> SrtHandshake {
+        let mut buf = [0u8; 1500];
+        let n = socket.recv(&mut buf).unwrap();
+        SrtHandshake::from_datagram(&buf[..n]).expect("handshake")
+    }
+
+    fn expect_request(event: ListenerEvent) -> HandshakeRequest {
+        match event {
+            ListenerEvent::Handshake(request) => request,
+            other => panic!("expected a handshake request, got {:?}", other),
+        }
+    }
+
+    fn connection_for(request: &HandshakeRequest, listener: &SrtListener) -> Connection {
+        Connection::new(
+            request.socket_id(),
+            listener.local_addr().unwrap(),
+            request.peer(),
+            SeqNumber::new(0),
+            120,
+        )
+    }
+
+    #[test]
+    fn test_accept_and_route_by_socket_id() {
+        let mut listener = listener();
+        let first = caller();
+        let second = caller();
+        let first_addr = first.local_addr().unwrap();
+        let second_addr = second.local_addr().unwrap();
+
+        let request =
+            expect_request(listener.handle(&handshake_datagram(70, &listener), first_addr));
+        assert_eq!(request.socket_id(), 1);
+        let conn = connection_for(&request, &listener);
+        let accepted = listener.accept(request, conn).unwrap();
+        assert_eq!(accepted.remote_socket_id(), Some(70));
+
+        let agreement = receive_handshake(&first);
+        assert_eq!(
+            agreement.udt.handshake_type,
+            HandshakeType::Agreement as i32
+        );
+        assert_eq!(agreement.udt.socket_id, 1);
+
+        let request =
+            expect_request(listener.handle(&handshake_datagram(80, &listener), second_addr));
+        assert_eq!(request.socket_id(), 2);
+        let conn = connection_for(&request, &listener);
+        listener.accept(request, conn).unwrap();
+        assert_eq!(receive_handshake(&second).udt.socket_id, 2);
+        assert_eq!(listener.len(), 2);
+
+        assert!(matches!(
+            listener.handle(&data_datagram(1), first_addr),
+            ListenerEvent::Packet { socket_id: 1, .. }
+        ));
+        assert!(matches!(
+            listener.handle(&data_datagram(2), second_addr),
+            ListenerEvent::Packet { socket_id: 2, .. }
+        ));
+        // Destination 0 falls back to the caller's address
+        assert!(matches!(
+            listener.handle(&data_datagram(0), second_addr),
+            ListenerEvent::Packet { socket_id: 2, .. }
+        ));
+        // Another caller's socket ID is not accepted
+        assert!(matches!(
+            listener.handle(&data_datagram(1), second_addr),
+            ListenerEvent::Unknown { .. }
+        ));
+    }
+
+    #[test]
+    fn test_repeated_handshake_is_answered_again() {
+        let mut listener = listener();
+        let socket = caller();
+        let addr = socket.local_addr().unwrap();
+
+        let request = expect_request(listener.handle(&handshake_datagram(70, &listener), addr));
+        let conn = connection_for(&request, &listener);
+        listener.accept(request, conn).unwrap();
+        receive_handshake(&socket);
+
+        assert!(matches!(
+            listener.handle(&handshake_datagram(70, &listener), addr),
+            ListenerEvent::Repeated { socket_id: 1 }
+        ));
+        assert_eq!(receive_handshake(&socket).udt.socket_id, 1);
+        assert_eq!(listener.len(), 1);
+
+        // A restarted caller has a new socket ID and is a new request
+        let request = expect_request(listener.handle(&handshake_datagram(71, &listener), addr));
+        assert_eq!(request.socket_id(), 2);
+    }
+
+    #[test]
+    fn test_refused_handshake_sends_reason() {
+        let mut listener = listener();
+        let socket = caller();
+        let addr = socket.local_addr().unwrap();
+
+        let request = expect_request(listener.handle(&handshake_datagram(70, &listener), addr));
+        let mut conn = connection_for(&request, &listener);
+        conn.set_enforce_encryption(true);
+        assert!(matches!(
+            listener.accept(request, conn),
+            Err(ListenerError::Connection(ConnectionError::Rejected(
+                RejectReason::Unsecure
+            )))
+        ));
+        assert_eq!(
+            receive_handshake(&socket).reject_reason(),
+            Some(RejectReason::Unsecure)
+        );
+        assert!(listener.is_empty());
+
+        // The socket ID was not used up
+        let request = expect_request(listener.handle(&handshake_datagram(70, &listener), addr));
+        assert_eq!(request.socket_id(), 1);
+        let conn = Connection::new(
+            5,
+            listener.local_addr().unwrap(),
+            addr,
+            SeqNumber::new(0),
+            120,
+        );
+        assert!(matches!(
+            listener.accept(request, conn),
+            Err(ListenerError::SocketIdMismatch {
+                expected: 1,
+                actual: 5
+            })
+        ));
+    }
+
+    struct AllowPeers(Vec<SocketAddr>);
+
+    impl ListenerCallback for AllowPeers {
+        fn on_handshake(&self, request: &HandshakeRequest) -> Result<(), RejectReason> {
+            if request.options().is_none() {
+                return Err(RejectReason::Peer);
+            }
+            if self.0.contains(&request.peer()) {
+                Ok(())
+            } else {
+                Err(RejectReason::Other(1401))
+            }
+        }
+    }
+
+    #[test]
+    fn test_listener_callback_filters_by_address() {
+        let mut listener = listener();
+        let allowed = caller();
+        let other = caller();
+        listener.set_listener_callback(AllowPeers(vec![allowed.local_addr().unwrap()]));
+
+        let request = expect_request(listener.handle(
+            &handshake_datagram(80, &listener),
+            other.local_addr().unwrap(),
+        ));
+        let conn = connection_for(&request, &listener);
+        assert!(matches!(
+            listener.accept(request, conn),
+            Err(ListenerError::Refused(RejectReason::Other(1401)))
+        ));
+        assert_eq!(
+            receive_handshake(&other).reject_reason(),
+            Some(RejectReason::Other(1401))
+        );
+
+        let request = expect_request(listener.handle(
+            &handshake_datagram(81, &listener),
+            allowed.local_addr().unwrap(),
+        ));
+        assert!(request.options().is_some());
+        let conn = connection_for(&request, &listener);
+        listener.accept(request, conn).unwrap();
+        assert_eq!(listener.len(), 1);
+    }
+
+    #[test]
+    fn test_accept_hook_routes_by_stream_id() {
+        let mut listener = listener();
+        listener.set_accept_hook(|request| match request.stream_id() {
+            Some(id) if id.starts_with("#!::r=live/") => Ok(()),
+            _ => Err(RejectReason::Other(1403)),
+        });
+        let socket = caller();
+        let addr = socket.local_addr().unwrap();
+
+        let datagram = stream_handshake_datagram(70, &listener, Some("#!::r=vod/x"));
+        let request = expect_request(listener.handle(&datagram, addr));
+        assert_eq!(request.stream_id(), Some("#!::r=vod/x"));
+        let conn = connection_for(&request, &listener);
+        assert!(matches!(
+            listener.accept(request, conn),
+            Err(ListenerError::Refused(RejectReason::Other(1403)))
+        ));
+        assert_eq!(
+            receive_handshake(&socket).reject_reason(),
+            Some(RejectReason::Other(1403))
+        );
+        assert!(listener.is_empty());
+
+        let datagram = stream_handshake_datagram(71, &listener, Some("#!::r=live/cam1,m=publish"));
+        let request = expect_request(listener.handle(&datagram, addr));
+        let conn = connection_for(&request, &listener);
+        let accepted = listener.accept(request, conn).unwrap();
+        assert_eq!(accepted.stream_id(), Some("#!::r=live/cam1,m=publish"));
+
+        // Without the hook every caller is accepted
+        listener.clear_accept_hook();
+        let request = expect_request(listener.handle(&handshake_datagram(72, &listener), addr));
+        let conn = connection_for(&request, &listener);
+        listener.accept(request, conn).unwrap();
+        assert_eq!(listener.len(), 2);
+    }
+
+    #[test]
+    fn test_close_sends_shutdown() {
+        let mut listener = listener();
+        let socket = caller();
+        let addr = socket.local_addr().unwrap();
+
+        let request = expect_request(listener.handle(&handshake_datagram(70, &listener), addr));
+        let conn = connection_for(&request, &listener);
+        listener.accept(request, conn).unwrap();
+        receive_handshake(&socket);
+
+        let closed = listener.close(1).unwrap();
+        assert!(closed.is_closed());
+        let mut buf = [0u8; 1500];
+        let n = socket.recv(&mut buf).unwrap();
+        let shutdown = ControlPacket::from_bytes(&buf[..n]).unwrap();
+        assert_eq!(shutdown.control_type(), ControlType::Shutdown);
+        assert_eq!(shutdown.header.dest_socket_id, 70);
+
+        assert!(listener.is_empty());
+        assert!(matches!(
+            listener.handle(&data_datagram(1), addr),
+            ListenerEvent::Unknown { .. }
+        ));
+        assert!(matches!(
+            listener.send_to(1, &[0]),
+            Err(ListenerError::UnknownSocket(1))
+        ));
+    }
+
+    #[test]
+    fn test_close_wakes_epoll_waiter() {
+        use srt_io::{Epoll, Events, Readiness, Trigger};
+        use std::sync::mpsc;
+        use std::thread;
+        use std::time::Duration;
+
+        let epoll = Arc::new(Epoll::new());
+        let mut listener = listener();
+        listener.set_notifier(Some(epoll.notifier()));
+        let socket = caller();
+        let addr = socket.local_addr().unwrap();
+        let request = expect_request(listener.handle(&handshake_datagram(70, &listener), addr));
+        let conn = connection_for(&request, &listener);
+        let accepted = listener.accept(request, conn).unwrap();
+        epoll
+            .add(
+                1,
+                Arc::new(move || accepted.readiness()),
+                Events::READ,
+                Trigger::Level,
+            )
+            .unwrap();
+
+        let (woken, wake) = mpsc::channel();
+        let waiter = {
+            let epoll = Arc::clone(&epoll);
+            thread::spawn(move || {
+                let mut ready = Vec::new();
+                epoll.wait(&mut ready, None);
+                woken.send(ready).unwrap();
+            })
+        };
+        listener.close(1).unwrap();
+        let ready = wake
+            .recv_timeout(Duration::from_secs(5))
+            .expect("waiter woken");
+        assert_eq!(ready[0].events, Events::ERROR);
+        waiter.join().unwrap();
+    }
+
+    #[test]
+    fn test_connections_stay_on_their_socket() {
+        let addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        let mut listener = SrtListener::bind_all(&[addr, addr]).unwrap();
+        assert_eq!(listener.sockets().len(), 2);
+        assert!(matches!(
+            SrtListener::bind_all(&[]),
+            Err(ListenerError::NoAddress)
+        ));
+        let second_local = listener.local_addr_at(1).unwrap();
+        assert_ne!(second_local, listener.local_addr().unwrap());
+
+        let socket = caller();
+        let from = socket.local_addr().unwrap();
+        let request =
+            expect_request(listener.handle_on(1, &handshake_datagram(70, &listener), from));
+        assert_eq!(request.local_index(), 1);
+        let conn = Connection::new(
+            request.socket_id(),
+            second_local,
+            from,
+            SeqNumber::new(0),
+            120,
+        );
+        listener.accept(request, conn).unwrap();
+        assert_eq!(listener.local_index(1), Some(1));
+
+        // The agreement leaves from the socket the handshake arrived on
+        let mut buf = [0u8; 1500];
+        let (_, replied_from) = socket.recv_from(&mut buf).unwrap();
+        assert_eq!(replied_from, second_local);
+
+        assert!(matches!(
+            listener.handle_on(1, &data_datagram(1), from),
+            ListenerEvent::Packet { socket_id: 1, .. }
+        ));
+        assert!(matches!(
+            listener.handle(&data_datagram(1), from),
+            ListenerEvent::Unknown { .. }
+        ));
+
+        listener.send_to(1, &[7]).unwrap();
+        let (_, sent_from) = socket.recv_from(&mut buf).unwrap();
+        assert_eq!(sent_from, second_local);
+
+        // recv reads from every socket
+        socket.send_to(&data_datagram(1), second_local).unwrap();
+        let received = (0..200).find_map(|_| {
+            let event = listener.recv(&mut buf).unwrap();
+            if event.is_none() {
+                std::thread::sleep(Duration::from_millis(5));
+            }
+            event
+        });
+        assert!(matches!(
+            received,
+            Some((_, ListenerEvent::Packet { socket_id: 1, .. }))
+        ));
+    }
+
+    /// A data packet with a payload for socket `dest`
+    fn payload_datagram(dest: u32, byte: u8) -> Vec<u8> {
+        let mut datagram = data_datagram(dest);
+        datagram.push(byte);
+        datagram
+    }
+
+    #[test]
+    fn test_early_data_delivered_after_accept() {
+        let mut listener = listener();
+        let peer = caller();
+        let addr = peer.local_addr().unwrap();
+
+        // Off by default
+        assert!(matches!(
+            listener.handle(&payload_datagram(0, 1), addr),
+            ListenerEvent::Unknown { .. }
+        ));
+
+        listener.set_early_data_limit(2);
+        for byte in 1..=3 {
+            listener.handle(&payload_datagram(0, byte), addr);
+        }
+        let request = expect_request(listener.handle(&handshake_datagram(70, &listener), addr));
+        let conn = connection_for(&request, &listener);
+        listener.accept(request, conn).unwrap();
+
+        let mut buf = [0u8; 1500];
+        let mut delivered = Vec::new();
+        while let Some((n, event)) = listener.next_early(&mut buf) {
+            assert!(matches!(event, ListenerEvent::Packet { socket_id: 1, .. }));
+            delivered.push(buf[n - 1]);
+        }
+        // The first packets are kept
+        assert_eq!(delivered, vec![1, 2]);
+        let stats = listener.stats();
+        assert_eq!(stats.early_held, 2);
+        assert_eq!(stats.early_delivered, 2);
+        assert_eq!(stats.early_dropped, 1);
+    }
+
+    #[test]
+    fn test_early_data_expires() {
+        let mut listener = listener();
+        listener.set_early_data_limit(8);
+        listener.set_early_data_ttl(Duration::from_millis(100));
+        let addr = "127.0.0.1:5000".parse().unwrap();
+        let start = Instant::now();
+        assert!(listener.hold_early(0, &payload_datagram(0, 1), addr, start));
+        // Control packets are not held
+        assert!(!listener.hold_early(0, &handshake_datagram(70, &listener), addr, start));
+
+        listener.expire_early(start + Duration::from_millis(200));
+        assert_eq!(listener.stats().early_expired, 1);
+        let request = expect_request(listener.handle(&handshake_datagram(70, &listener), addr));
+        let conn = connection_for(&request, &listener);
+        listener.accept(request, conn).unwrap();
+        assert!(listener.next_early(&mut [0u8; 1500]).is_none());
+    }
+
+    #[test]
+    fn test_early_data_source_limit() {
+        let mut listener = listener();
+        listener.set_early_data_limit(1);
+        let now = Instant::now();
+        for port in 0..MAX_EARLY_DATA_SOURCES as u16 {
+            let addr = SocketAddr::from(([127, 0, 0, 1], 6000 + port));
+            assert!(listener.hold_early(0, &payload_datagram(0, 1), addr, now));
+        }
+        let extra = "127.0.0.1:7000".parse().unwrap();
+        assert!(!listener.hold_early(0, &payload_datagram(0, 1), extra, now));
+        assert_eq!(listener.stats().early_dropped, 1);
+    }
+}
@@ -0,0 +1,66 @@
+//! Supported public API
+//!
+//! `use srt::prelude::*;` brings in the types an application needs to open
+//! connections, bond them into groups and read their statistics. Everything
+//! here follows the deprecation policy in `docs/RELEASING.md`: it is not
+//! removed or changed incompatibly without first being deprecated for at
+//! least one minor release.
+//!
+//! The crate modules (`srt::protocol`, `srt::bonding`, ...) expose more,
+//! such as wire formats and buffer internals. Those items may change in any
+//! minor release before 1.0.
+
+// Connections
+pub use srt_protocol::{
+    Connection, ConnectionError, ConnectionState, ConnectionStats, Liveness, ReceivedMessage,
+    RetransmitPolicy, SendLatencyEvent,
+};
+
+// Handshake options
+pub use srt_protocol::{
+    ArqMode, FilterConfig, FilterError, HandshakeError, ProbePlan, ProbeResult, SrtOptions,
+};
+
+// Packets and messages
+pub use srt_protocol::{
+    ControlPacket, DataPacket, Depacketizer, MsgNumber, Packet, PacketBoundary, PacketType,
+    Packetizer, SeqNumber,
+};
+
+// Labels and source time
+pub use srt_protocol::{LabelError, Labels, SourceClock, SourceTimeError};
+
+// Socket groups
+#[cfg(feature = "bonding")]
+pub use srt_bonding::{
+    AdmissionRequest, GroupError, GroupStats, GroupType, MemberStats, MemberStatus, SocketGroup,
+};
+
+// Bonding modes
+#[cfg(feature = "bonding")]
+pub use srt_bonding::{
+    BackupBonding, BackupConfig, BackupError, BalancerConfig, BalancingAlgorithm, BalancingError,
+    BroadcastError, BroadcastReceiver, BroadcastSender, ConfigChange, FailoverEvent,
+    FailoverReason, LoadBalancer,
+};
+
+// Adaptation and advice
+#[cfg(feature = "bonding")]
+pub use srt_bonding::{
+    AdaptiveConfig, AdaptiveModeController, AdvisoryConfig, BitrateAdvisor, BitrateAdvisory,
+    ModeSwitchEvent, ModeSwitchReason, RedundancyConfig, RedundancyLevel,
+};
+
+// Sockets
+pub use srt_io::{MulticastOptions, SocketError, SrtSocket};
+
+// Listening
+pub use crate::listener::{
+    AcceptHook, HandshakeRequest, ListenerCallback, ListenerError, ListenerEvent, SrtListener,
+};
+
+// Streams
+pub use crate::stream::{SrtReceiver, SrtSender, StreamError, StreamOptions};
+
+// Secrets
+pub use srt_crypto::{Passphrase, SecretBytes};
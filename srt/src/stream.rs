@@ -0,0 +1,1086 @@
+//! Blocking sender and receiver streams
+//!
+//! [`SrtSender`] and [`SrtReceiver`] hide the protocol pieces. Each runs
+//! its connection from a driver thread that reads the socket, sends queued
+//! data at the paced rate, retransmits losses and sends ACKs, NAKs and
+//! keep-alives, while the application calls `send`, `recv` and `close`:
+//!
+//! ```no_run
+//! use srt::stream::{SrtReceiver, SrtSender, StreamOptions};
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let receiver = SrtReceiver::bind("127.0.0.1:9000".parse()?, StreamOptions::default())?;
+//! let sender = SrtSender::connect(receiver.local_addr(), StreamOptions::default())?;
+//!
+//! sender.send(b"hello")?;
+//! assert_eq!(receiver.recv().as_deref(), Some(&b"hello"[..]));
+//! sender.close()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! For async applications see `io::async_socket` (`tokio` feature).
+
+use crate::listener::{HandshakeRequest, ListenerError, ListenerEvent, SrtListener};
+use bytes::Bytes;
+use parking_lot::{Condvar, Mutex};
+use srt_io::{EpollNotifier, Events, Readiness, SocketError, SrtSocket, Timer};
+use srt_protocol::buffer::BufferError;
+use srt_protocol::packet::ControlType;
+use srt_protocol::{
+    CongestionMode, Connection, ConnectionError, ConnectionStats, ControlPacket, Packet,
+    RejectReason, RetransmitPolicy, SendAction, SenderWorker, SeqNumber, SrtHandshake, StallAction,
+    StallEvent, DEFAULT_PEER_IDLE_TIMEOUT,
+};
+use std::collections::VecDeque;
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+/// Period of the driver's timers (ACK, NAK, keep-alive)
+const DRIVER_TICK: Duration = Duration::from_millis(10);
+
+/// Time between handshake requests while connecting
+const HANDSHAKE_RETRY: Duration = Duration::from_millis(250);
+
+/// Time the driver waits when the socket has nothing to read
+const IDLE_WAIT: Duration = Duration::from_millis(1);
+
+/// Datagrams read before the driver services its timers
+const READ_BATCH: usize = 64;
+
+/// Largest UDP payload
+const MAX_DATAGRAM: usize = 65_507;
+
+/// Stall events kept for `SrtReceiver::take_stall`; older ones are dropped
+const MAX_PENDING_STALLS: usize = 16;
+
+/// Stream errors
+#[derive(Error, Debug)]
+pub enum StreamError {
+    #[error("Socket error: {0}")]
+    Socket(#[from] SocketError),
+
+    #[error("Listener error: {0}")]
+    Listener(#[from] ListenerError),
+
+    #[error("Connection error: {0}")]
+    Connection(#[from] ConnectionError),
+
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Handshake timed out")]
+    HandshakeTimeout,
+
+    #[error("Peer stopped answering keep-alives")]
+    PeerLost,
+
+    #[error("Stream closed")]
+    Closed,
+
+    #[error("Driver thread panicked")]
+    DriverPanicked,
+}
+
+/// Options for [`SrtSender`] and [`SrtReceiver`]
+#[derive(Debug, Clone)]
+pub struct StreamOptions {
+    /// Latency in milliseconds; the higher of both ends is used
+    pub latency_ms: u16,
+    /// Stream ID the sender passes to the receiver
+    pub stream_id: Option<String>,
+    /// Sending rate cap in bytes per second (0 = unlimited)
+    pub max_bandwidth: u64,
+    /// Bytes of idle credit pacing may send back to back (0 = none)
+    pub pacing_burst: usize,
+    /// How long `SrtSender::connect` waits for the receiver to answer
+    pub connect_timeout: Duration,
+    /// How long `close` waits for sent data to be acknowledged (0 = not at all)
+    pub linger: Duration,
+    /// How long the peer may stay silent before the stream fails (0 = never)
+    pub peer_idle_timeout: Duration,
+    /// Congestion control; both ends must agree
+    pub congestion: CongestionMode,
+    /// Send a byte stream instead of messages; both ends must agree
+    pub stream_mode: bool,
+    /// How long delivery may make no progress before it counts as stalled
+    /// (0 = no watchdog)
+    pub stall_timeout: Duration,
+    /// What to do when delivery stalls
+    pub stall_action: StallAction,
+    /// Woken whenever the driver may have changed the stream's readiness
+    pub notifier: Option<EpollNotifier>,
+}
+
+impl Default for StreamOptions {
+    fn default() -> Self {
+        StreamOptions {
+            latency_ms: 120,
+            stream_id: None,
+            max_bandwidth: 0,
+            pacing_burst: 0,
+            connect_timeout: Duration::from_secs(3),
+            linger: Duration::from_secs(1),
+            peer_idle_timeout: DEFAULT_PEER_IDLE_TIMEOUT,
+            congestion: CongestionMode::Live,
+            stream_mode: false,
+            stall_timeout: Duration::ZERO,
+            stall_action: StallAction::Log,
+            notifier: None,
+        }
+    }
+}
+
+impl StreamOptions {
+    /// Build a connection with these options
+    ///
+    /// Unacknowledged packets are resent on timeout as well as on NAK: a
+    /// stream often ends in a burst whose lost tail the receiver never sees
+    /// a later packet to NAK against.
+    fn connection(
+        &self,
+        socket_id: u32,
+        local: SocketAddr,
+        peer: SocketAddr,
+    ) -> Result<Connection, StreamError> {
+        let mut conn = Connection::new(socket_id, local, peer, SeqNumber::new(0), self.latency_ms);
+        conn.set_stream_id(self.stream_id.clone())?;
+        conn.set_max_bandwidth(self.max_bandwidth);
+        conn.set_pacing_burst(self.pacing_burst);
+        conn.set_retransmit_policy(RetransmitPolicy::NakAndTimeout);
+        conn.set_peer_idle_timeout(self.peer_idle_timeout);
+        conn.set_congestion_mode(self.congestion);
+        conn.set_stream_mode(self.stream_mode);
+        conn.set_stall_timeout(self.stall_timeout);
+        conn.set_stall_action(self.stall_action);
+        Ok(conn)
+    }
+}
+
+/// Sends a stream to an [`SrtReceiver`] (or any SRT listener)
+pub struct SrtSender {
+    stream: Stream,
+}
+
+impl SrtSender {
+    /// Connect to a listener at `addr`
+    ///
+    /// Repeats the handshake request until the listener answers or
+    /// `options.connect_timeout` passes.
+    pub fn connect(addr: SocketAddr, options: StreamOptions) -> Result<Self, StreamError> {
+        let local: SocketAddr = if addr.is_ipv4() {
+            (Ipv4Addr::UNSPECIFIED, 0).into()
+        } else {
+            (Ipv6Addr::UNSPECIFIED, 0).into()
+        };
+        let socket = SrtSocket::bind(local)?;
+        socket.connect(addr)?;
+        let mut conn = options.connection(new_socket_id(), socket.local_addr()?, addr)?;
+        handshake(&socket, &mut conn, options.connect_timeout)?;
+
+        let shared = Arc::new(Shared::new(&options));
+        let _ = shared.conn.set(Arc::new(conn));
+        let stream = Stream::spawn(shared, Link::Caller(socket), options)?;
+        Ok(SrtSender { stream })
+    }
+
+    /// Queue a message, waiting while the send buffer is full
+    ///
+    /// Messages larger than a packet are split and delivered whole. In
+    /// stream mode the bytes join the stream, queued as room frees up.
+    pub fn send(&self, data: &[u8]) -> Result<usize, StreamError> {
+        let Some(conn) = self.stream.shared.conn.get() else {
+            return Err(StreamError::Closed);
+        };
+        let mut state = self.stream.shared.state.lock();
+        let mut sent = 0;
+        loop {
+            if state.stopped || state.close {
+                return Err(StreamError::Closed);
+            }
+            let result = if conn.stream_mode() {
+                conn.write(&data[sent..])
+            } else {
+                conn.send_message(data)
+            };
+            match result {
+                Ok(n) if conn.stream_mode() && sent + n < data.len() => sent += n,
+                Ok(n) => return Ok(sent + n),
+                Err(ConnectionError::Buffer(BufferError::Full)) => {
+                    self.stream.shared.changed.wait_for(&mut state, DRIVER_TICK);
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// Get the underlying connection
+    pub fn connection(&self) -> &Connection {
+        self.stream
+            .shared
+            .conn
+            .get()
+            .expect("sender is connected before it is created")
+    }
+
+    /// Get connection statistics
+    pub fn stats(&self) -> ConnectionStats {
+        self.connection().stats()
+    }
+
+    /// Check if the driver has stopped (closed, or the receiver left)
+    pub fn is_closed(&self) -> bool {
+        self.stream.is_closed()
+    }
+
+    /// Wait up to `linger` for queued data to be acknowledged, tell the
+    /// receiver we are done and stop the driver
+    ///
+    /// Returns the final statistics, or the error that stopped the driver.
+    pub fn close(self) -> Result<ConnectionStats, StreamError> {
+        self.stream.close()
+    }
+}
+
+/// Receives a stream from one [`SrtSender`] (or any SRT caller)
+///
+/// The first caller to complete a handshake is accepted; later callers are
+/// not answered. [`SrtReceiver::bind_with_accept_hook`] refuses callers the
+/// hook rejects, for example by Stream ID, and keeps waiting.
+pub struct SrtReceiver {
+    stream: Stream,
+    local_addr: SocketAddr,
+}
+
+impl SrtReceiver {
+    /// Listen on `addr` and accept the first caller in the background
+    pub fn bind(addr: SocketAddr, options: StreamOptions) -> Result<Self, StreamError> {
+        let listener = SrtListener::bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        let shared = Arc::new(Shared::new(&options));
+        let stream = Stream::spawn(shared, Link::Listener(Box::new(listener)), options)?;
+        Ok(SrtReceiver { stream, local_addr })
+    }
+
+    /// Listen on `addr` and accept the first caller the hook allows
+    ///
+    /// Refused callers get the hook's reject reason in the handshake reply.
+    pub fn bind_with_accept_hook<F>(
+        addr: SocketAddr,
+        options: StreamOptions,
+        hook: F,
+    ) -> Result<Self, StreamError>
+    where
+        F: Fn(&HandshakeRequest) -> Result<(), RejectReason> + Send + Sync + 'static,
+    {
+        let mut listener = SrtListener::bind(addr)?;
+        listener.set_accept_hook(hook);
+        let local_addr = listener.local_addr()?;
+        let shared = Arc::new(Shared::new(&options));
+        let stream = Stream::spawn(shared, Link::Listener(Box::new(listener)), options)?;
+        Ok(SrtReceiver { stream, local_addr })
+    }
+
+    /// Get the address the receiver listens on
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Check if a caller has been accepted
+    pub fn is_connected(&self) -> bool {
+        self.stream.shared.conn.get().is_some()
+    }
+
+    /// Get the accepted connection, once a caller has connected
+    pub fn connection(&self) -> Option<&Connection> {
+        self.stream.shared.conn.get().map(|conn| conn.as_ref())
+    }
+
+    /// Get the address of the accepted caller
+    pub fn peer_addr(&self) -> Option<SocketAddr> {
+        self.connection().map(Connection::remote_addr)
+    }
+
+    /// Receive the next message in order
+    ///
+    /// In stream mode this is the next piece of the byte stream, cut
+    /// wherever packets were. Returns None once the stream has closed and every message received
+    /// before the sender's shutdown has been delivered.
+    pub fn recv(&self) -> Option<Bytes> {
+        self.recv_until(None)
+    }
+
+    /// Receive the next message, waiting at most `timeout`
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<Bytes> {
+        self.recv_until(Instant::now().checked_add(timeout))
+    }
+
+    fn recv_until(&self, deadline: Option<Instant>) -> Option<Bytes> {
+        let shared = &self.stream.shared;
+        let mut state = shared.state.lock();
+        loop {
+            if let Some(data) = shared
+                .conn
+                .get()
+                .and_then(|conn| conn.recv().ok().flatten())
+            {
+                return Some(data);
+            }
+            if let Some(data) = state.drained.pop_front() {
+                return Some(data);
+            }
+            if state.stopped {
+                return None;
+            }
+            match deadline {
+                Some(deadline) => {
+                    if shared.changed.wait_until(&mut state, deadline).timed_out() {
+                        return None;
+                    }
+                }
+                None => shared.changed.wait(&mut state),
+            }
+        }
+    }
+
+    /// Take the oldest delivery stall the driver detected
+    ///
+    /// Only raised with `StreamOptions::stall_timeout` set. A stall whose
+    /// action is `Close` also stops the driver with
+    /// `ConnectionError::Stalled`.
+    pub fn take_stall(&self) -> Option<StallEvent> {
+        self.stream.shared.state.lock().stalls.pop_front()
+    }
+
+    /// Get connection statistics, once a caller has connected
+    pub fn stats(&self) -> Option<ConnectionStats> {
+        self.connection().map(Connection::stats)
+    }
+
+    /// Check if the driver has stopped (closed, or the sender left)
+    pub fn is_closed(&self) -> bool {
+        self.stream.is_closed()
+    }
+
+    /// Tell the sender we are done and stop the driver
+    ///
+    /// Returns the final statistics (empty if no caller connected), or
+    /// the error that stopped the driver.
+    pub fn close(self) -> Result<ConnectionStats, StreamError> {
+        self.stream.close()
+    }
+}
+
+impl Readiness for SrtSender {
+    fn readiness(&self) -> Events {
+        self.stream.readiness()
+    }
+}
+
+impl Readiness for SrtReceiver {
+    fn readiness(&self) -> Events {
+        self.stream.readiness()
+    }
+}
+
+/// State shared between a stream and its driver thread
+struct Shared {
+    /// Set once the handshake completes
+    conn: OnceLock<Arc<Connection>>,
+    state: Mutex<DriverState>,
+    /// Messages may be ready, buffer space freed, or the driver stopped
+    changed: Condvar,
+    notifier: Option<EpollNotifier>,
+}
+
+impl Shared {
+    fn new(options: &StreamOptions) -> Self {
+        Shared {
+            conn: OnceLock::new(),
+            state: Mutex::default(),
+            changed: Condvar::new(),
+            notifier: options.notifier.clone(),
+        }
+    }
+
+    /// Wake threads waiting on the stream or on its epoll
+    fn notify(&self) {
+        self.changed.notify_all();
+        if let Some(notifier) = &self.notifier {
+            notifier.notify();
+        }
+    }
+}
+
+#[derive(Default)]
+struct DriverState {
+    /// The application asked to close
+    close: bool,
+    /// The driver has stopped
+    stopped: bool,
+    /// Messages flushed when the peer shut down
+    drained: VecDeque<Bytes>,
+    /// Delivery stalls not yet taken by the application
+    stalls: VecDeque<StallEvent>,
+}
+
+/// A driver thread and the state it shares
+struct Stream {
+    shared: Arc<Shared>,
+    driver: Option<JoinHandle<Result<(), StreamError>>>,
+}
+
+impl Stream {
+    fn spawn(shared: Arc<Shared>, link: Link, options: StreamOptions) -> Result<Self, StreamError> {
+        let driver_shared = Arc::clone(&shared);
+        let driver = thread::Builder::new()
+            .name("srt-driver".into())
+            .spawn(move || drive(link, &driver_shared, &options))?;
+        Ok(Stream {
+            shared,
+            driver: Some(driver),
+        })
+    }
+
+    fn is_closed(&self) -> bool {
+        self.shared.state.lock().stopped
+    }
+
+    fn readiness(&self) -> Events {
+        let state = self.shared.state.lock();
+        let mut events = match self.shared.conn.get() {
+            Some(conn) => conn.readiness(),
+            None => Events::NONE,
+        };
+        if !state.drained.is_empty() {
+            events |= Events::READ;
+        }
+        if state.stopped {
+            events |= Events::ERROR;
+        }
+        events
+    }
+
+    fn close(mut self) -> Result<ConnectionStats, StreamError> {
+        self.shared.state.lock().close = true;
+        if let Some(driver) = self.driver.take() {
+            driver.join().map_err(|_| StreamError::DriverPanicked)??;
+        }
+        Ok(self
+            .shared
+            .conn
+            .get()
+            .map(|conn| conn.stats())
+            .unwrap_or_default())
+    }
+}
+
+impl Drop for Stream {
+    fn drop(&mut self) {
+        if self.driver.is_some() {
+            self.shared.state.lock().close = true;
+        }
+    }
+}
+
+/// How the driver reaches the peer
+enum Link {
+    /// Our own socket, connected to the listener
+    Caller(SrtSocket),
+    /// A listening socket; packets from other callers are dropped
+    Listener(Box<SrtListener>),
+}
+
+impl Link {
+    /// Send a datagram to the peer
+    ///
+    /// ICMP errors for a peer that went away are ignored; missed
+    /// keep-alives decide when it is gone.
+    fn send(&self, conn: &Connection, datagram: &[u8]) -> Result<(), StreamError> {
+        let result = match self {
+            Link::Caller(socket) => socket.send(datagram).map(drop),
+            Link::Listener(listener) => match listener.send_to(conn.local_socket_id(), datagram) {
+                Ok(_) => Ok(()),
+                Err(ListenerError::Socket(e)) => Err(e),
+                Err(e) => return Err(e.into()),
+            },
+        };
+        match result {
+            Err(e) if !e.is_peer_unreachable() => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Read the next datagram for `conn` into `buf`, if one is waiting
+    fn recv(&mut self, conn: &Connection, buf: &mut [u8]) -> Result<Option<usize>, StreamError> {
+        match self {
+            Link::Caller(socket) => loop {
+                match socket.recv(buf) {
+                    Ok(n) => return Ok(Some(n)),
+                    Err(e) if would_block(&e) => return Ok(None),
+                    // Reported ahead of queued datagrams, such as a shutdown
+                    Err(e) if e.is_peer_unreachable() => continue,
+                    Err(e) => return Err(e.into()),
+                }
+            },
+            Link::Listener(listener) => loop {
+                match listener.recv(buf)? {
+                    Some((n, ListenerEvent::Packet { socket_id, .. }))
+                        if socket_id == conn.local_socket_id() =>
+                    {
+                        return Ok(Some(n))
+                    }
+                    Some(_) => continue,
+                    None => return Ok(None),
+                }
+            },
+        }
+    }
+}
+
+/// Driver thread: run the connection until closed, then wake any waiters
+fn drive(mut link: Link, shared: &Shared, options: &StreamOptions) -> Result<(), StreamError> {
+    let result = run(&mut link, shared, options);
+    if let Some(conn) = shared.conn.get() {
+        if let Err(e) = &result {
+            tracing::debug!("SRT stream to {} stopped: {}", conn.remote_addr(), e);
+        }
+        conn.close();
+    }
+    shared.state.lock().stopped = true;
+    shared.notify();
+    result
+}
+
+fn run(link: &mut Link, shared: &Shared, options: &StreamOptions) -> Result<(), StreamError> {
+    let mut buf = vec![0u8; MAX_DATAGRAM];
+    let conn = match link {
+        Link::Caller(_) => shared.conn.get().cloned().ok_or(StreamError::Closed)?,
+        Link::Listener(listener) => match accept(listener, shared, options, &mut buf)? {
+            Some(conn) => conn,
+            None => return Ok(()),
+        },
+    };
+    let mut tick = Timer::new(DRIVER_TICK);
+    let mut sender = SenderWorker::new();
+    let mut lingering = false;
+
+    loop {
+        // Linger until what was sent is acknowledged, then say goodbye
+        if shared.state.lock().close {
+            if !lingering {
+                conn.shutdown(options.linger);
+                lingering = true;
+            }
+            if let Some(shutdown) = conn.poll_close() {
+                transmit(link, &conn, &mut sender)?;
+                link.send(&conn, &shutdown.to_bytes())?;
+                return Ok(());
+            }
+        }
+
+        let mut read = 0;
+        while read < READ_BATCH {
+            let Some(n) = link.recv(&conn, &mut buf)? else {
+                break;
+            };
+            read += 1;
+            if handle(&buf[..n], &conn, shared) {
+                return Ok(());
+            }
+        }
+
+        let ticked = tick.try_fire();
+        if ticked {
+            if !conn.liveness().is_alive() {
+                return Err(StreamError::PeerLost);
+            }
+            conn.check_timeout()?;
+            if let Some(stall) = conn.check_stall() {
+                let (action, stalled_for) = (stall.action, stall.stalled_for);
+                let mut state = shared.state.lock();
+                if state.stalls.len() >= MAX_PENDING_STALLS {
+                    state.stalls.pop_front();
+                }
+                state.stalls.push_back(stall);
+                if action == StallAction::Close {
+                    return Err(ConnectionError::Stalled(stalled_for).into());
+                }
+            }
+            let mut feedback: Vec<ControlPacket> = Vec::new();
+            feedback.extend(conn.create_ack_if_due());
+            feedback.extend(conn.create_nak());
+            while let Some(keepalive) = conn.create_keepalive() {
+                feedback.push(keepalive);
+            }
+            feedback.extend(conn.create_drop_requests());
+            feedback.extend(conn.create_probe_packets());
+            feedback.extend(conn.create_probe_report());
+            for packet in feedback {
+                link.send(&conn, &packet.to_bytes())?;
+            }
+        }
+        transmit(link, &conn, &mut sender)?;
+        // Epoll waiters only need waking when packets or timers moved the
+        // connection on; an idle pass changes nothing they could see
+        shared.changed.notify_all();
+        if read > 0 || ticked {
+            if let Some(notifier) = &shared.notifier {
+                notifier.notify();
+            }
+        }
+
+        if read == 0 {
+            thread::sleep(IDLE_WAIT);
+        }
+    }
+}
+
+/// Wait for the first caller and complete its handshake
+///
+/// Returns None if the stream was closed first.
+fn accept(
+    listener: &mut SrtListener,
+    shared: &Shared,
+    options: &StreamOptions,
+    buf: &mut [u8],
+) -> Result<Option<Arc<Connection>>, StreamError> {
+    loop {
+        if shared.state.lock().close {
+            return Ok(None);
+        }
+        let Some((_, event)) = listener.recv(buf)? else {
+            thread::sleep(IDLE_WAIT);
+            continue;
+        };
+        let ListenerEvent::Handshake(request) = event else {
+            continue;
+        };
+        let peer = request.peer();
+        let conn = options.connection(request.socket_id(), listener.local_addr()?, peer)?;
+        match listener.accept(request, conn) {
+            Ok(conn) => {
+                let _ = shared.conn.set(Arc::clone(&conn));
+                shared.notify();
+                return Ok(Some(conn));
+            }
+            Err(e) => tracing::debug!("Refused handshake from {}: {}", peer, e),
+        }
+    }
+}
+
+/// Process one datagram from the peer
+///
+/// Returns true once the peer has shut down.
+fn handle(datagram: &[u8], conn: &Connection, shared: &Shared) -> bool {
+    let Ok(packet) = Packet::from_bytes(datagram) else {
+        return false;
+    };
+    match packet {
+        Packet::Data(packet) => {
+            let _ = conn.process_data_packet(packet);
+        }
+        Packet::Control(control) => match control.control_type() {
+            // Repeated handshakes are answered by the listener
+            ControlType::Handshake => {}
+            ControlType::Shutdown => {
+                conn.begin_close();
+                if let Ok(drain) = conn.drain() {
+                    shared.state.lock().drained.extend(drain.messages);
+                }
+                return true;
+            }
+            _ => {
+                let _ = conn.process_control(&control);
+            }
+        },
+    }
+    false
+}
+
+/// Send due retransmissions, then new data, paced to `max_bandwidth`
+fn transmit(link: &Link, conn: &Connection, sender: &mut SenderWorker) -> Result<(), StreamError> {
+    loop {
+        match sender.poll(conn) {
+            SendAction::Send(packet) => link.send(conn, &packet.to_bytes())?,
+            SendAction::Wait(delay) => thread::sleep(delay),
+            SendAction::Idle => return Ok(()),
+        }
+    }
+}
+
+/// Handshake as a caller until the listener agrees
+fn handshake(
+    socket: &SrtSocket,
+    conn: &mut Connection,
+    timeout: Duration,
+) -> Result<(), StreamError> {
+    let deadline = Instant::now() + timeout;
+    let mut retry: Option<Timer> = None;
+    let mut buf = vec![0u8; MAX_DATAGRAM];
+
+    while !conn.is_connected() {
+        if Instant::now() >= deadline {
+            return Err(StreamError::HandshakeTimeout);
+        }
+        if retry.as_mut().map_or(true, Timer::try_fire) {
+            let body = conn.create_handshake().to_bytes().freeze();
+            let request = ControlPacket::new(ControlType::Handshake, 0, 0, 0, 0, body);
+            match socket.send(&request.to_bytes()) {
+                Err(e) if !e.is_peer_unreachable() => return Err(e.into()),
+                _ => {}
+            }
+            retry.get_or_insert_with(|| Timer::new(HANDSHAKE_RETRY));
+        }
+        match socket.recv(&mut buf) {
+            Ok(n) => {
+                if let Some(handshake) = SrtHandshake::from_datagram(&buf[..n]) {
+                    conn.process_agreement(handshake)?;
+                }
+            }
+            Err(e) if would_block(&e) || e.is_peer_unreachable() => thread::sleep(IDLE_WAIT),
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(())
+}
+
+fn would_block(e: &SocketError) -> bool {
+    matches!(e, SocketError::Io(e) if e.kind() == io::ErrorKind::WouldBlock)
+}
+
+/// Pick a socket ID for a new caller
+///
+/// Seeded from the clock so a restarted process is not mistaken for its
+/// predecessor, and counted so callers in one process differ.
+fn new_socket_id() -> u32 {
+    static NEXT: AtomicU32 = AtomicU32::new(0);
+    let seed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or_default();
+    let id = seed.wrapping_add(NEXT.fetch_add(1, Ordering::Relaxed)) & 0x3FFF_FFFF;
+    id.max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    fn pair(options: StreamOptions) -> (SrtSender, SrtReceiver) {
+        let receiver = SrtReceiver::bind("127.0.0.1:0".parse().unwrap(), options.clone()).unwrap();
+        let sender = SrtSender::connect(receiver.local_addr(), options).unwrap();
+        (sender, receiver)
+    }
+
+    #[test]
+    fn test_messages_in_order() {
+        let (sender, receiver) = pair(StreamOptions::default());
+        for i in 0..100u32 {
+            sender.send(&i.to_be_bytes()).unwrap();
+        }
+        for i in 0..100u32 {
+            let data = receiver.recv_timeout(TIMEOUT).expect("message");
+            assert_eq!(data.as_ref(), i.to_be_bytes());
+        }
+        assert!(receiver.is_connected());
+        assert_eq!(
+            receiver.connection().unwrap().remote_socket_id(),
+            Some(sender.connection().local_socket_id())
+        );
+
+        let stats = sender.close().unwrap();
+        assert_eq!(stats.send_buffer_packets, 0);
+        // The sender's shutdown ends the stream
+        assert_eq!(receiver.recv_timeout(TIMEOUT), None);
+        assert!(receiver.is_closed());
+        receiver.close().unwrap();
+    }
+
+    #[test]
+    fn test_large_messages_arrive_whole() {
+        let (sender, receiver) = pair(StreamOptions::default());
+        let message: Vec<u8> = (0..10_000u32).map(|i| i as u8).collect();
+        sender.send(&message).unwrap();
+        sender.send(b"next").unwrap();
+        assert_eq!(receiver.recv_timeout(TIMEOUT).unwrap().as_ref(), message);
+        assert_eq!(receiver.recv_timeout(TIMEOUT).unwrap().as_ref(), b"next");
+        sender.close().unwrap();
+        receiver.close().unwrap();
+    }
+
+    #[test]
+    fn test_multiplexed_streams_share_one_connection() {
+        use srt_protocol::{StreamDemux, StreamMux};
+
+        let (sender, receiver) = pair(StreamOptions::default());
+        let mut mux = StreamMux::new();
+        for i in 0..10u8 {
+            sender.send(&mux.encode(1, &[i])).unwrap();
+            if i % 2 == 0 {
+                sender.send(&mux.encode(2, b"video frame")).unwrap();
+            }
+        }
+
+        let mut demux = StreamDemux::new();
+        let audio = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&audio);
+        demux.set_handler(1, move |message| sink.lock().push(message[0]));
+        demux.set_handler(2, |message| assert_eq!(&message[..], b"video frame"));
+        for _ in 0..15 {
+            demux
+                .receive(receiver.recv_timeout(TIMEOUT).unwrap())
+                .unwrap();
+        }
+        assert_eq!(*audio.lock(), (0..10).collect::<Vec<u8>>());
+        assert_eq!(demux.stats(2).unwrap().delivered, 5);
+        sender.close().unwrap();
+        receiver.close().unwrap();
+    }
+
+    #[test]
+    fn test_stream_id_and_latency_reach_receiver() {
+        let receiver = SrtReceiver::bind(
+            "127.0.0.1:0".parse().unwrap(),
+            StreamOptions {
+                latency_ms: 300,
+                ..StreamOptions::default()
+            },
+        )
+        .unwrap();
+        let sender = SrtSender::connect(
+            receiver.local_addr(),
+            StreamOptions {
+                stream_id: Some("live/feed".into()),
+                ..StreamOptions::default()
+            },
+        )
+        .unwrap();
+        sender.send(b"x").unwrap();
+        receiver.recv_timeout(TIMEOUT).unwrap();
+
+        let conn = receiver.connection().unwrap();
+        assert_eq!(conn.stream_id(), Some("live/feed"));
+        assert_eq!(conn.latency_ms(), 300);
+        // The agreement tells the sender the negotiated latency
+        assert_eq!(sender.connection().latency_ms(), 300);
+        sender.close().unwrap();
+        receiver.close().unwrap();
+    }
+
+    #[test]
+    fn test_accept_hook_refuses_stream_id() {
+        let receiver = SrtReceiver::bind_with_accept_hook(
+            "127.0.0.1:0".parse().unwrap(),
+            StreamOptions::default(),
+            |request| match request.stream_id() {
+                Some("live/feed") => Ok(()),
+                _ => Err(RejectReason::Other(1403)),
+            },
+        )
+        .unwrap();
+        let stream_id = |id: &str| StreamOptions {
+            stream_id: Some(id.into()),
+            ..StreamOptions::default()
+        };
+
+        let result = SrtSender::connect(receiver.local_addr(), stream_id("live/other"));
+        assert!(matches!(
+            result,
+            Err(StreamError::Connection(ConnectionError::Rejected(
+                RejectReason::Other(1403)
+            )))
+        ));
+        assert!(!receiver.is_connected());
+
+        // The receiver keeps waiting for an allowed caller
+        let sender = SrtSender::connect(receiver.local_addr(), stream_id("live/feed")).unwrap();
+        sender.send(b"x").unwrap();
+        receiver.recv_timeout(TIMEOUT).unwrap();
+        assert_eq!(
+            receiver.connection().unwrap().stream_id(),
+            Some("live/feed")
+        );
+        sender.close().unwrap();
+        receiver.close().unwrap();
+    }
+
+    #[test]
+    fn test_congestion_modes_must_match() {
+        let file = StreamOptions {
+            congestion: CongestionMode::File,
+            ..StreamOptions::default()
+        };
+        let (sender, receiver) = pair(file.clone());
+        sender.send(b"x").unwrap();
+        receiver.recv_timeout(TIMEOUT).unwrap();
+        assert_eq!(
+            receiver.connection().unwrap().congestion_mode(),
+            CongestionMode::File
+        );
+        sender.close().unwrap();
+        receiver.close().unwrap();
+
+        let receiver =
+            SrtReceiver::bind("127.0.0.1:0".parse().unwrap(), StreamOptions::default()).unwrap();
+        let result = SrtSender::connect(receiver.local_addr(), file);
+        assert!(matches!(
+            result,
+            Err(StreamError::Connection(ConnectionError::Rejected(
+                RejectReason::Congestion
+            )))
+        ));
+        receiver.close().unwrap();
+    }
+
+    #[test]
+    fn test_stream_mode() {
+        let options = StreamOptions {
+            stream_mode: true,
+            ..StreamOptions::default()
+        };
+        let (sender, receiver) = pair(options.clone());
+        let data: Vec<u8> = (0..100_000u32).map(|i| i as u8).collect();
+        sender.send(&data[..30_000]).unwrap();
+        sender.send(&data[30_000..]).unwrap();
+
+        let mut received = Vec::new();
+        while received.len() < data.len() {
+            received.extend_from_slice(&receiver.recv_timeout(TIMEOUT).unwrap());
+        }
+        assert_eq!(received, data);
+        sender.close().unwrap();
+        receiver.close().unwrap();
+
+        // Both ends must agree
+        let receiver =
+            SrtReceiver::bind("127.0.0.1:0".parse().unwrap(), StreamOptions::default()).unwrap();
+        let result = SrtSender::connect(receiver.local_addr(), options);
+        assert!(matches!(
+            result,
+            Err(StreamError::Connection(ConnectionError::Rejected(
+                RejectReason::MessageApi
+            )))
+        ));
+        receiver.close().unwrap();
+    }
+
+    #[test]
+    fn test_stalled_delivery_closes_receiver() {
+        let options = StreamOptions {
+            stall_timeout: Duration::from_millis(200),
+            stall_action: StallAction::Close,
+            ..StreamOptions::default()
+        };
+        let receiver = SrtReceiver::bind("127.0.0.1:0".parse().unwrap(), options.clone()).unwrap();
+
+        // A caller that never answers NAKs and loses 1 for good
+        let socket = SrtSocket::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        socket.connect(receiver.local_addr()).unwrap();
+        let mut conn = options
+            .connection(
+                new_socket_id(),
+                socket.local_addr().unwrap(),
+                receiver.local_addr(),
+            )
+            .unwrap();
+        handshake(&socket, &mut conn, TIMEOUT).unwrap();
+        for i in 0..4u8 {
+            conn.send(&[i]).unwrap();
+        }
+        for packet in conn.packets_to_send() {
+            if packet.seq_number().as_raw() != 1 {
+                socket.send(&packet.to_bytes()).unwrap();
+            }
+        }
+
+        assert_eq!(receiver.recv_timeout(TIMEOUT).as_deref(), Some(&[0][..]));
+        assert_eq!(receiver.recv_timeout(TIMEOUT), None);
+        let stall = receiver.take_stall().unwrap();
+        assert_eq!(stall.action, StallAction::Close);
+        assert_eq!(stall.diagnostics.missing_packets, 1);
+        assert!(matches!(
+            receiver.close(),
+            Err(StreamError::Connection(ConnectionError::Stalled(_)))
+        ));
+    }
+
+    #[test]
+    fn test_connect_times_out_without_listener() {
+        // Bound but never read: the handshake is never answered
+        let silent = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let result = SrtSender::connect(
+            silent.local_addr().unwrap(),
+            StreamOptions {
+                connect_timeout: Duration::from_millis(100),
+                ..StreamOptions::default()
+            },
+        );
+        assert!(matches!(result, Err(StreamError::HandshakeTimeout)));
+    }
+
+    #[test]
+    fn test_driver_wakes_epoll_waiter() {
+        use srt_io::{Epoll, ReadyEvent, Trigger};
+        use std::sync::mpsc;
+
+        let epoll = Arc::new(Epoll::new());
+        let (sender, receiver) = pair(StreamOptions {
+            notifier: Some(epoll.notifier()),
+            ..StreamOptions::default()
+        });
+        let receiver = Arc::new(receiver);
+        epoll
+            .add(1, receiver.clone(), Events::READ, Trigger::Level)
+            .unwrap();
+
+        // Nothing else wakes a wait without a timeout
+        let (woken, wake) = mpsc::channel();
+        let waiter = {
+            let epoll = Arc::clone(&epoll);
+            thread::spawn(move || {
+                let mut ready = Vec::new();
+                epoll.wait(&mut ready, None);
+                woken.send(ready).unwrap();
+            })
+        };
+        sender.send(b"wake").unwrap();
+        let ready = wake.recv_timeout(TIMEOUT).expect("waiter woken");
+        assert_eq!(
+            ready,
+            vec![ReadyEvent {
+                token: 1,
+                events: Events::READ
+            }]
+        );
+        waiter.join().unwrap();
+
+        epoll.remove(1).unwrap();
+        assert_eq!(
+            receiver.recv_timeout(TIMEOUT).as_deref(),
+            Some(&b"wake"[..])
+        );
+        sender.close().unwrap();
+        let Ok(receiver) = Arc::try_unwrap(receiver) else {
+            panic!("receiver still shared");
+        };
+        receiver.close().unwrap();
+    }
+
+    #[test]
+    fn test_receiver_closes_without_caller() {
+        let receiver =
+            SrtReceiver::bind("127.0.0.1:0".parse().unwrap(), StreamOptions::default()).unwrap();
+        assert!(!receiver.is_connected());
+        assert_eq!(receiver.recv_timeout(Duration::from_millis(20)), None);
+        let stats = receiver.close().unwrap();
+        assert_eq!(stats.packets_received, 0);
+    }
+}